@@ -1,11 +1,71 @@
-use crate::protocol::{parse_model_output, ParseResult};
-use crate::skill::SkillRequest;
-use crate::tool::{ToolRequest, ToolResult};
+use crate::artifact::ArtifactRequest;
+use crate::capabilities::HostCapabilities;
+use crate::memory::RecallRequest;
+use crate::prelude::*;
+use crate::protocol::{
+    format_tool_call, parse_model_output_with, AnswerProtocol, InconclusiveReason, ParseResult,
+};
+use crate::reasoning::{split_reasoning, ReasoningDelimiters};
+use crate::response_spec::ResponseSpec;
+use crate::skill::{SkillRequest, SkillResult_};
+use crate::tool::{CallId, ToolRequest, ToolResult};
+use crate::transcript::{ChatTranscript, TranscriptEntry, TranscriptError};
+use crate::variables::{apply_variable_request, substitute_variables};
+use alloc::collections::BTreeMap;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+/// Schema version of serialized [`AgentState`]
+///
+/// 1. `history`, `is_complete` and `final_answer`; no `version` field
+/// 2. Adds `guardrail_rejections`, `variables`, call ids and `version`
+/// 3. Adds `answer_protocol`
+/// 4. Adds `reasoning_delimiters` and the messages' `reasoning`
+/// 5. Adds `speaker` and agent roles
+/// 6. Adds `refusals`
+/// 7. Adds `aliased_calls`
+/// 8. Adds `response_spec`
+///
+/// Bump it with every field added to the serialized state, so builds that do
+/// not know the field refuse the state rather than drop the field.
+pub const STATE_VERSION: u32 = 8;
+
+/// Why persisted state JSON could not be loaded
+#[derive(Debug, Error)]
+pub enum StateError {
+    #[error("invalid state JSON: {0}")]
+    Invalid(#[from] serde_json::Error),
+
+    #[error("state JSON must be an object")]
+    NotAnObject,
+
+    #[cfg(feature = "cbor")]
+    #[error("invalid state CBOR: {0}")]
+    Cbor(String),
+
+    #[error(
+        "state version {found} is newer than this build supports ({supported}); upgrade to load it"
+    )]
+    UnsupportedVersion { found: u32, supported: u32 },
+
+    #[error("invalid conversation: {0}")]
+    Conversation(#[from] TranscriptError),
+
+    #[error("the conversation must end with a user message or a tool result")]
+    NothingToAnswer,
+}
 
 /// The state of the agent during execution
+///
+/// Serialized state carries [`STATE_VERSION`]; load persisted state with
+/// [`AgentState::from_versioned_json`] so older versions are migrated.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AgentState {
+    /// Schema version this state was written with
+    #[serde(default = "legacy_version")]
+    version: u32,
+
     /// The conversation history (user messages, model responses, tool results)
     pub history: Vec<Message>,
 
@@ -14,44 +74,386 @@ pub struct AgentState {
 
     /// The final answer, if complete
     pub final_answer: Option<String>,
+
+    /// Guardrail rejections so far, counted per reason
+    #[serde(default)]
+    pub guardrail_rejections: BTreeMap<String, usize>,
+
+    /// Replies refused in a row (see [`HostCapabilities::check`]); hosts end
+    /// the run at [`MAX_REFUSALS`](crate::capabilities::MAX_REFUSALS)
+    #[serde(default)]
+    pub refusals: usize,
+
+    /// Calls the model made by another name, with the name it used (see
+    /// [`HostCapabilities::resolve_tool`])
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub aliased_calls: BTreeMap<CallId, String>,
+
+    /// Scratchpad variables set by the model (see [`crate::variables`])
+    #[serde(default)]
+    pub variables: BTreeMap<String, serde_json::Value>,
+
+    /// Calls issued so far; the next call gets the following id
+    #[serde(default)]
+    calls_issued: u64,
+
+    /// How final answers are recognized in model output
+    #[serde(default)]
+    pub answer_protocol: AnswerProtocol,
+
+    /// Delimiters of the reasoning blocks taken out of model output (`None`
+    /// leaves the output as it is)
+    #[serde(default = "default_reasoning_delimiters")]
+    pub reasoning_delimiters: Option<ReasoningDelimiters>,
+
+    /// The named agent whose replies are being processed (`None` for a
+    /// single-agent run); its replies are recorded as [`Role::Agent`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speaker: Option<String>,
+
+    /// The shape the caller wants the final answer in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_spec: Option<ResponseSpec>,
+
+    /// What the host running the state can do; set by each host, not saved
+    #[serde(skip)]
+    pub capabilities: HostCapabilities,
 }
 
+fn default_reasoning_delimiters() -> Option<ReasoningDelimiters> {
+    Some(ReasoningDelimiters::default())
+}
+
+/// Opening of the message that takes back a rejected answer
+pub(crate) const ANSWER_REJECTED: &str = "Your answer was rejected: ";
+
+/// Opening of the message that refuses a call the host does not offer
+pub(crate) const REQUEST_REFUSED: &str = "Your request was refused: ";
+
 /// A message in the conversation history
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Message {
     pub role: Role,
     pub content: String,
+    /// The call a request or result message belongs to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub call_id: Option<CallId>,
+    /// Reasoning the model gave before this message, kept out of `content`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<String>,
 }
 
 /// The role of a message
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum Role {
     User,
     Assistant,
     Tool,
+    /// A named participant in a multi-agent run (`{"agent": "critic"}`); see
+    /// [`crate::participants`] for how its messages reach each prompt
+    Agent(String),
+}
+
+impl Role {
+    /// Whether a model wrote the message (the assistant or a named agent)
+    pub fn is_model(&self) -> bool {
+        matches!(self, Role::Assistant | Role::Agent(_))
+    }
 }
 
 impl AgentState {
     /// Create a new agent state with an initial user query
     pub fn new(query: impl Into<String>) -> Self {
         Self {
+            version: STATE_VERSION,
             history: vec![Message {
                 role: Role::User,
                 content: query.into(),
+                call_id: None,
+                reasoning: None,
             }],
             is_complete: false,
             final_answer: None,
+            guardrail_rejections: BTreeMap::new(),
+            refusals: 0,
+            aliased_calls: BTreeMap::new(),
+            variables: BTreeMap::new(),
+            calls_issued: 0,
+            answer_protocol: AnswerProtocol::default(),
+            reasoning_delimiters: default_reasoning_delimiters(),
+            speaker: None,
+            response_spec: None,
+            capabilities: HostCapabilities::default(),
+        }
+    }
+
+    pub fn with_answer_protocol(mut self, protocol: AnswerProtocol) -> Self {
+        self.answer_protocol = protocol;
+        self
+    }
+
+    /// Reasoning block delimiters of the model (default `<think>`); `None`
+    /// keeps reasoning in the output
+    pub fn with_reasoning_delimiters(mut self, delimiters: Option<ReasoningDelimiters>) -> Self {
+        self.reasoning_delimiters = delimiters;
+        self
+    }
+
+    /// Record the following replies as written by the named agent (see
+    /// [`crate::participants`])
+    pub fn with_speaker(mut self, speaker: Option<String>) -> Self {
+        self.speaker = speaker;
+        self
+    }
+
+    /// What the current host can do (see [`crate::capabilities`])
+    pub fn with_capabilities(mut self, capabilities: HostCapabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Answer constraints from the caller (see [`crate::response_spec`]); an
+    /// empty spec is none
+    pub fn with_response_spec(mut self, spec: Option<ResponseSpec>) -> Self {
+        self.response_spec = spec.filter(|spec| !spec.is_empty());
+        self
+    }
+
+    /// Continue a conversation in OpenAI chat completions format: a
+    /// `messages` array, or a request body holding one (see
+    /// [`AgentState::from_transcript`])
+    pub fn from_openai_messages(json: &str) -> Result<Self, StateError> {
+        let value: Value = serde_json::from_str(json)?;
+        let transcript = ChatTranscript::from_openai(messages_array(&value)?)?;
+        Self::from_transcript(transcript)
+    }
+
+    /// Continue a conversation in Anthropic Messages API format: a `messages`
+    /// array, or a request body holding one (see
+    /// [`AgentState::from_transcript`])
+    pub fn from_anthropic_messages(json: &str) -> Result<Self, StateError> {
+        let value: Value = serde_json::from_str(json)?;
+        let system = value.get("system").and_then(Value::as_str);
+        let transcript = ChatTranscript::from_anthropic(system, messages_array(&value)?)?;
+        Self::from_transcript(transcript)
+    }
+
+    /// Continue a conversation held elsewhere, e.g. in a chat UI
+    ///
+    /// Tool calls get fresh call ids, and their results are recorded as
+    /// [`apply_tool_result`] would. The conversation must leave the agent
+    /// something to answer: it ends with a user message or a tool result. Its
+    /// system prompt is dropped; the host brings its own.
+    pub fn from_transcript(transcript: ChatTranscript) -> Result<Self, StateError> {
+        let mut state = Self::new("");
+        state.history.clear();
+        let mut call_ids: BTreeMap<String, CallId> = BTreeMap::new();
+
+        for entry in transcript.entries() {
+            match entry {
+                TranscriptEntry::User(content) => state.add_message(Role::User, content.as_str()),
+                TranscriptEntry::Assistant(content) => {
+                    state.add_message(Role::Assistant, content.as_str())
+                }
+                TranscriptEntry::ToolCall { id, request } => {
+                    let call_id = state.next_call_id();
+                    call_ids.insert(id.clone(), call_id);
+                    state.add_call_message(
+                        Role::Assistant,
+                        format_tool_call(request),
+                        Some(call_id),
+                    );
+                }
+                TranscriptEntry::ToolResult {
+                    id,
+                    content,
+                    is_error,
+                    ..
+                } => {
+                    let content = if content.starts_with("Tool output:")
+                        || content.starts_with("Tool failed")
+                    {
+                        content.clone()
+                    } else if *is_error {
+                        format!("Tool failed: {}", content)
+                    } else {
+                        format!("Tool output:\n{}", content)
+                    };
+                    state.add_call_message(Role::Tool, content, call_ids.get(id).copied());
+                }
+                TranscriptEntry::ToolOutput(content) => {
+                    state.add_message(Role::Tool, content.as_str())
+                }
+            }
+        }
+
+        match state.history.last() {
+            Some(message) if matches!(message.role, Role::User | Role::Tool) => Ok(state),
+            _ => Err(StateError::NothingToAnswer),
         }
     }
 
+    /// Load state JSON written by this or an older version, migrating it to
+    /// [`STATE_VERSION`]
+    ///
+    /// State from a newer version is rejected rather than loaded with its
+    /// unknown fields dropped.
+    pub fn from_versioned_json(json: &str) -> Result<Self, StateError> {
+        Self::from_versioned_value(serde_json::from_str(json)?)
+    }
+
+    fn from_versioned_value(mut value: Value) -> Result<Self, StateError> {
+        let object = value.as_object_mut().ok_or(StateError::NotAnObject)?;
+        let found = match object.get("version") {
+            None => legacy_version(),
+            Some(version) => version
+                .as_u64()
+                .and_then(|version| u32::try_from(version).ok())
+                .ok_or_else(|| {
+                    StateError::Invalid(serde::de::Error::custom(format!(
+                        "invalid state version {}",
+                        version
+                    )))
+                })?,
+        };
+        if found > STATE_VERSION {
+            return Err(StateError::UnsupportedVersion {
+                found,
+                supported: STATE_VERSION,
+            });
+        }
+
+        if found < 2 {
+            migrate_v1(object);
+        }
+        // Versions after 2 only add fields, which take their defaults when missing:
+        // - 3: the heuristic answer protocol
+        // - 4: the default reasoning delimiters, no reasoning
+        // - 5: no speaker
+        // - 6: no refusals
+        // - 7: no aliased calls
+        // - 8: no response spec
+        object.insert("version".to_string(), STATE_VERSION.into());
+        Ok(serde_json::from_value(value)?)
+    }
+
+    /// Encode as CBOR: smaller and faster to round-trip than JSON for hosts
+    /// that pass state across a boundary on every step
+    #[cfg(feature = "cbor")]
+    pub fn to_cbor(&self) -> Result<Vec<u8>, StateError> {
+        let mut bytes = Vec::new();
+        ciborium::ser::into_writer(self, &mut bytes)
+            .map_err(|e| StateError::Cbor(e.to_string()))?;
+        Ok(bytes)
+    }
+
+    /// Decode state written by [`AgentState::to_cbor`], migrating older
+    /// versions like [`AgentState::from_versioned_json`]
+    #[cfg(feature = "cbor")]
+    pub fn from_cbor(bytes: &[u8]) -> Result<Self, StateError> {
+        let cbor_error = |e: ciborium::de::Error<std::io::Error>| StateError::Cbor(e.to_string());
+        if let Ok(state) = ciborium::de::from_reader::<Self, _>(bytes) {
+            if state.version == STATE_VERSION {
+                return Ok(state);
+            }
+        }
+        // Another version: take the slow path through the migrations
+        Self::from_versioned_value(ciborium::de::from_reader(bytes).map_err(cbor_error)?)
+    }
+
+    /// Schema version this state was written with (see [`STATE_VERSION`])
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
     /// Add a message to the history
     pub fn add_message(&mut self, role: Role, content: impl Into<String>) {
+        self.add_call_message(role, content, None);
+    }
+
+    /// Add a message belonging to call `call_id` to the history
+    pub fn add_call_message(
+        &mut self,
+        role: Role,
+        content: impl Into<String>,
+        call_id: Option<CallId>,
+    ) {
         self.history.push(Message {
             role,
             content: content.into(),
+            call_id,
+            reasoning: None,
         });
     }
+
+    /// Add a model reply to the history, with the reasoning taken out of it
+    fn add_model_message(
+        &mut self,
+        content: impl Into<String>,
+        call_id: Option<CallId>,
+        reasoning: Option<String>,
+    ) {
+        let role = match &self.speaker {
+            Some(name) => Role::Agent(name.clone()),
+            None => Role::Assistant,
+        };
+        self.add_call_message(role, content, call_id);
+        if let Some(message) = self.history.last_mut() {
+            message.reasoning = reasoning;
+        }
+    }
+
+    /// A fresh id for the next call
+    pub fn next_call_id(&mut self) -> CallId {
+        self.calls_issued += 1;
+        CallId(self.calls_issued)
+    }
+
+    /// Rough number of tokens the history takes up (about four characters
+    /// per token)
+    pub fn estimated_tokens(&self) -> usize {
+        let chars: usize = self.history.iter().map(|m| m.content.chars().count()).sum();
+        chars.div_ceil(4)
+    }
+
+    /// Count a guardrail rejection of a tool output
+    pub fn record_rejection(&mut self, reason: impl Into<String>) {
+        *self.guardrail_rejections.entry(reason.into()).or_insert(0) += 1;
+    }
+
+    /// Take back a final answer that a guardrail rejected
+    ///
+    /// The answer stays in the history, followed by the rejection, so the
+    /// model sees what was wrong with it on the next turn.
+    pub fn reject_answer(&mut self, reason: impl Into<String>) {
+        let reason = reason.into();
+        self.is_complete = false;
+        self.final_answer = None;
+        self.add_message(
+            Role::User,
+            format!(
+                "{}{}. Answer only with facts from the tool outputs, \
+                 or call a tool to obtain the missing data.",
+                ANSWER_REJECTED, reason
+            ),
+        );
+        self.record_rejection(reason);
+    }
+
+    /// Keep only the first `messages` messages, to branch from an earlier
+    /// point; a final answer after it is dropped too
+    ///
+    /// A backend's cache holds the dropped messages until the host rewinds it
+    /// (see [`crate::kv_checkpoint`]).
+    pub fn rollback(&mut self, messages: usize) {
+        if messages >= self.history.len() {
+            return;
+        }
+        self.history.truncate(messages);
+        self.is_complete = false;
+        self.final_answer = None;
+    }
 }
 
 /// The decision made by the agent after processing model output
@@ -64,12 +466,34 @@ pub enum AgentDecision {
     /// Skills are contract-based, guardrail-enforced operations
     InvokeSkill(SkillRequest),
 
+    /// The agent wants to read more of a stored tool output
+    ReadArtifact(ArtifactRequest),
+
+    /// The agent wants to search the memory of earlier runs
+    Recall(RecallRequest),
+
+    /// The agent stored or read a scratchpad variable
+    ///
+    /// Already applied, with the result recorded in the history; the host
+    /// only reports it and asks the model again.
+    Variable {
+        operation: String,
+        result: ToolResult,
+    },
+
+    /// The model asked for something the host cannot do (see
+    /// [`HostCapabilities`])
+    ///
+    /// The reason is already sent back in the history; the host only reports
+    /// it and asks the model again.
+    Unsupported(String),
+
     /// The agent has produced a final answer
     Done(String),
 
-    /// The agent produced inconclusive output (reasoning without action)
-    /// This indicates the model failed to follow instructions properly
-    Inconclusive(String),
+    /// The agent produced inconclusive output (reasoning without action), and
+    /// why; this indicates the model failed to follow instructions properly
+    Inconclusive(String, InconclusiveReason),
 }
 
 /// Process model output and decide the next action
@@ -79,35 +503,118 @@ pub enum AgentDecision {
 /// 2. Decide if it's a tool call, skill invocation, final answer, or inconclusive
 /// 3. Return the appropriate decision
 ///
+/// Reasoning blocks are taken out first (see [`crate::reasoning`]) and kept
+/// on the history message; final answers are recognized by the state's
+/// [`AnswerProtocol`]. Calls the state's [`HostCapabilities`] rule out are
+/// refused.
 /// This function is pure, deterministic, and has no side effects.
 pub fn process_model_output(
     state: &mut AgentState,
     model_output: impl Into<String>,
 ) -> AgentDecision {
     let output = model_output.into();
+    let (output, reasoning) = match &state.reasoning_delimiters {
+        Some(delimiters) => {
+            let split = split_reasoning(&output, delimiters);
+            (split.visible, split.reasoning)
+        }
+        None => (output, None),
+    };
+    if output.is_empty() {
+        if let Some(reasoning) = reasoning {
+            // Only reasoning: the reply was cut off or never given
+            return AgentDecision::Inconclusive(reasoning, InconclusiveReason::ReasoningOnly);
+        }
+    }
+
+    let mut parsed = parse_model_output_with(&output, state.answer_protocol);
+
+    // A tool called by another name is the tool it stands for
+    let mut requested_as = None;
+    if let ParseResult::ToolCall(request) = &mut parsed {
+        if let Some(tool) = state.capabilities.resolve_tool(&request.tool) {
+            requested_as = Some(core::mem::replace(&mut request.tool, tool));
+        }
+    }
 
-    match parse_model_output(&output) {
-        ParseResult::ToolCall(tool_request) => {
-            // Add the model's tool call to history
-            state.add_message(Role::Assistant, output);
+    if let Err(reason) = state.capabilities.check(&parsed) {
+        // The model sees its request, why this host refused it and what it
+        // can call instead
+        let mut feedback = format!(
+            "{}{}. Use what this host offers, or answer with what you already know.",
+            REQUEST_REFUSED, reason
+        );
+        if let Some(offered) = state.capabilities.offered_calls(&parsed) {
+            feedback.push_str("\n\n");
+            feedback.push_str(&offered);
+        }
+        state.add_model_message(output, None, reasoning);
+        state.add_message(Role::User, feedback);
+        state.refusals += 1;
+        return AgentDecision::Unsupported(reason);
+    }
+    state.refusals = 0;
+
+    match parsed {
+        ParseResult::ToolCall(mut tool_request) => {
+            // Add the model's tool call to history, under the tool's own name,
+            // then fill in its variables
+            let call_id = state.next_call_id();
+            let output = match requested_as {
+                Some(name) => {
+                    state.aliased_calls.insert(call_id, name);
+                    format_tool_call(&tool_request)
+                }
+                None => output,
+            };
+            state.add_model_message(output, Some(call_id), reasoning);
+            tool_request.call_id = Some(call_id);
+            tool_request.params = substitute_variables(&tool_request.params, &state.variables);
             AgentDecision::InvokeTool(tool_request)
         }
-        ParseResult::SkillCall(skill_request) => {
+        ParseResult::SkillCall(mut skill_request) => {
             // Add the model's skill invocation to history
-            state.add_message(Role::Assistant, output);
+            let call_id = state.next_call_id();
+            state.add_model_message(output, Some(call_id), reasoning);
+            skill_request.call_id = Some(call_id);
             AgentDecision::InvokeSkill(skill_request)
         }
+        ParseResult::ReadArtifact(mut request) => {
+            // Add the model's read request to history
+            let call_id = state.next_call_id();
+            state.add_model_message(output, Some(call_id), reasoning);
+            request.call_id = Some(call_id);
+            AgentDecision::ReadArtifact(request)
+        }
+        ParseResult::Recall(mut request) => {
+            // Add the model's recall to history
+            let call_id = state.next_call_id();
+            state.add_model_message(output, Some(call_id), reasoning);
+            request.call_id = Some(call_id);
+            AgentDecision::Recall(request)
+        }
+        ParseResult::Variable(request) => {
+            // Scratchpad operations need no host: apply them here
+            let call_id = state.next_call_id();
+            state.add_model_message(output, Some(call_id), reasoning);
+            let result = apply_variable_request(state, &request).with_call_id(Some(call_id));
+            apply_tool_result(state, &result);
+            AgentDecision::Variable {
+                operation: request.operation().to_string(),
+                result,
+            }
+        }
         ParseResult::FinalAnswer(answer) => {
-            // Add the final answer to history
-            state.add_message(Role::Assistant, answer.clone());
+            // Add the final answer to history as written, sentinel included
+            state.add_model_message(output.trim(), None, reasoning);
             state.is_complete = true;
             state.final_answer = Some(answer.clone());
             AgentDecision::Done(answer)
         }
-        ParseResult::Inconclusive(output) => {
+        ParseResult::Inconclusive(output, reason) => {
             // Model produced reasoning/explanation without completing the task
             // Don't add to history yet - runtime will handle corrective retry
-            AgentDecision::Inconclusive(output)
+            AgentDecision::Inconclusive(output, reason)
         }
     }
 }
@@ -115,10 +622,11 @@ pub fn process_model_output(
 /// Apply a tool result to the agent state
 ///
 /// This adds the tool result to the conversation history so the model
-/// can see what happened when it invoked the tool.
+/// can see what happened when it invoked the tool. The message takes the
+/// result's call id.
 pub fn apply_tool_result(state: &mut AgentState, result: &ToolResult) {
     let content = if result.success {
-        format!("Tool output:\n{}", result.output)
+        format!("Tool output:\n{}", result.prompt_output())
     } else {
         format!(
             "Tool failed: {}",
@@ -126,12 +634,62 @@ pub fn apply_tool_result(state: &mut AgentState, result: &ToolResult) {
         )
     };
 
-    state.add_message(Role::Tool, content);
+    state.add_call_message(Role::Tool, content, result.call_id);
+}
+
+/// Add a skill result to the conversation history
+pub fn apply_skill_result(state: &mut AgentState, result: &SkillResult_) {
+    let content = if result.success {
+        format!("Skill output:\n{}", result.to_json())
+    } else {
+        format!(
+            "Skill failed: {}",
+            result.error.as_deref().unwrap_or("unknown error")
+        )
+    };
+
+    state.add_call_message(Role::Tool, content, result.call_id);
+}
+
+/// The `messages` array of a conversation, given bare or in a request body
+fn messages_array(value: &Value) -> Result<&[Value], StateError> {
+    value
+        .as_array()
+        .or_else(|| value.get("messages").and_then(Value::as_array))
+        .map(Vec::as_slice)
+        .ok_or_else(|| {
+            StateError::Conversation(TranscriptError::InvalidMessage(
+                "expected a messages array".into(),
+            ))
+        })
+}
+
+fn legacy_version() -> u32 {
+    1
+}
+
+/// Version 1 to 2: the new fields default to empty, but call ids resume
+/// after any already in the history so they stay unique
+fn migrate_v1(object: &mut serde_json::Map<String, Value>) {
+    let last_call = object
+        .get("history")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|message| message.get("call_id")?.as_u64())
+        .max()
+        .unwrap_or(0);
+    let issued = object
+        .get("calls_issued")
+        .and_then(Value::as_u64)
+        .unwrap_or(0);
+    object.insert("calls_issued".to_string(), issued.max(last_call).into());
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::tool::ContentKind;
 
     #[test]
     fn test_new_agent_state() {
@@ -173,6 +731,173 @@ mod tests {
         assert_eq!(state.final_answer, Some("The answer is 4.".to_string()));
     }
 
+    #[test]
+    fn test_sentinel_protocol() {
+        let mut state =
+            AgentState::new("Wie viele Dateien?").with_answer_protocol(AnswerProtocol::Sentinel);
+        assert!(matches!(
+            process_model_output(&mut state, "Es gibt 4 Dateien."),
+            AgentDecision::Inconclusive(..)
+        ));
+        assert_eq!(state.history.len(), 1);
+
+        // The protocol survives a round trip through the serialized state
+        let mut state =
+            AgentState::from_versioned_json(&serde_json::to_string(&state).unwrap()).unwrap();
+        match process_model_output(&mut state, "FINAL: Es gibt 4 Dateien.") {
+            AgentDecision::Done(answer) => assert_eq!(answer, "Es gibt 4 Dateien."),
+            _ => panic!("Expected final answer"),
+        }
+        assert_eq!(state.history[1].content, "FINAL: Es gibt 4 Dateien.");
+    }
+
+    #[test]
+    fn test_reasoning_kept_out_of_answers() {
+        let mut state = AgentState::new("How many files?");
+        let output = "<think>I will run ls and count.</think>There are 5 files.";
+        match process_model_output(&mut state, output) {
+            AgentDecision::Done(answer) => assert_eq!(answer, "There are 5 files."),
+            _ => panic!("Expected final answer"),
+        }
+        assert_eq!(state.history[1].content, "There are 5 files.");
+        assert_eq!(
+            state.history[1].reasoning.as_deref(),
+            Some("I will run ls and count.")
+        );
+
+        // Without delimiters the reasoning stays in and reads as planning
+        let mut state = AgentState::new("How many files?").with_reasoning_delimiters(None);
+        assert!(matches!(
+            process_model_output(&mut state, output),
+            AgentDecision::Inconclusive(..)
+        ));
+    }
+
+    #[test]
+    fn test_unsupported_calls_are_refused() {
+        let mut state = AgentState::new("List the files")
+            .with_capabilities(HostCapabilities::new().with_tools(["fs_list"]));
+        match process_model_output(&mut state, r#"{"tool": "shell", "command": "ls"}"#) {
+            AgentDecision::Unsupported(reason) => assert!(reason.contains("available: fs_list")),
+            other => panic!("Expected refusal, got {:?}", other),
+        }
+        assert!(state.history[2]
+            .content
+            .starts_with("Your request was refused"));
+        assert_eq!(state.refusals, 1);
+
+        // Capabilities are the host's: a loaded state allows everything again
+        let mut moved =
+            AgentState::from_versioned_json(&serde_json::to_string(&state).unwrap()).unwrap();
+        assert!(matches!(
+            process_model_output(&mut moved, r#"{"tool": "shell", "command": "ls"}"#),
+            AgentDecision::InvokeTool(_)
+        ));
+        assert_eq!(moved.refusals, 0);
+    }
+
+    #[test]
+    fn test_aliased_tool_calls() {
+        let mut state = AgentState::new("List the files")
+            .with_capabilities(HostCapabilities::new().with_tools(["shell", "eval_math"]));
+        match process_model_output(&mut state, r#"{"tool": "bash", "command": "ls"}"#) {
+            AgentDecision::InvokeTool(request) => {
+                assert_eq!(request.tool, "shell");
+                let call_id = request.call_id.unwrap();
+                assert_eq!(state.aliased_calls[&call_id], "bash");
+            }
+            other => panic!("Expected tool invocation, got {:?}", other),
+        }
+        // The history holds the call the host runs
+        assert_eq!(
+            state.history[1].content,
+            r#"{"command":"ls","tool":"shell"}"#
+        );
+        assert!(matches!(
+            process_model_output(&mut state, r#"{"tool": "weather"}"#),
+            AgentDecision::Unsupported(_)
+        ));
+    }
+
+    #[test]
+    fn test_variables_fill_tool_calls() {
+        let mut state = AgentState::new("Find order A-1042");
+        match process_model_output(
+            &mut state,
+            r#"{"set_var": {"name": "id", "value": "A-1042"}}"#,
+        ) {
+            AgentDecision::Variable { operation, result } => {
+                assert_eq!(operation, "set_var");
+                assert!(result.success);
+            }
+            _ => panic!("Expected variable operation"),
+        }
+        assert!(matches!(state.history.last().unwrap().role, Role::Tool));
+
+        let output = r#"{"tool": "shell", "command": "grep ${id} orders.csv"}"#;
+        match process_model_output(&mut state, output) {
+            AgentDecision::InvokeTool(req) => {
+                assert_eq!(req.params["command"], "grep A-1042 orders.csv");
+            }
+            _ => panic!("Expected tool invocation"),
+        }
+        assert_eq!(state.history.last().unwrap().content, output);
+    }
+
+    #[test]
+    fn test_call_ids_link_requests_to_results() {
+        let mut state = AgentState::new("List files");
+        let first = match process_model_output(&mut state, r#"{"tool": "shell", "command": "ls"}"#)
+        {
+            AgentDecision::InvokeTool(req) => req.call_id.unwrap(),
+            _ => panic!("Expected tool invocation"),
+        };
+        apply_tool_result(
+            &mut state,
+            &ToolResult::success("a").with_call_id(Some(first)),
+        );
+
+        let second =
+            match process_model_output(&mut state, r#"{"tool_more": {"id": 1, "page": 2}}"#) {
+                AgentDecision::ReadArtifact(req) => req.call_id.unwrap(),
+                _ => panic!("Expected artifact read"),
+            };
+        assert_ne!(first, second);
+        assert_eq!(state.history[1].call_id, Some(first));
+        assert_eq!(state.history[2].call_id, Some(first));
+        assert_eq!(state.history[3].call_id, Some(second));
+
+        // Ids survive a round trip through the serialized state
+        let mut restored: AgentState =
+            serde_json::from_str(&serde_json::to_string(&state).unwrap()).unwrap();
+        assert!(restored.next_call_id() > second);
+    }
+
+    #[test]
+    fn test_record_rejection() {
+        let mut state = AgentState::new("Test");
+        state.record_rejection("empty");
+        state.record_rejection("empty");
+        state.record_rejection("metadata");
+        assert_eq!(state.guardrail_rejections["empty"], 2);
+        assert_eq!(state.guardrail_rejections["metadata"], 1);
+    }
+
+    #[test]
+    fn test_reject_answer() {
+        let mut state = AgentState::new("How many files?");
+        process_model_output(&mut state, "There are 7 files.");
+        assert!(state.is_complete);
+
+        state.reject_answer("figures not found in tool outputs: 7");
+        assert!(!state.is_complete);
+        assert!(state.final_answer.is_none());
+        assert_eq!(state.history.len(), 3);
+        assert!(matches!(state.history[2].role, Role::User));
+        assert!(state.history[2].content.contains("tool outputs: 7"));
+        assert_eq!(state.guardrail_rejections.len(), 1);
+    }
+
     #[test]
     fn test_apply_tool_result() {
         let mut state = AgentState::new("Test");
@@ -183,4 +908,192 @@ mod tests {
         assert_eq!(state.history.len(), 2);
         assert!(matches!(state.history[1].role, Role::Tool));
     }
+
+    #[test]
+    fn test_tool_result_content_kinds() {
+        let mut state = AgentState::new("Test");
+        let json =
+            ToolResult::success("{\n  \"rows\": [1, 2]\n}").with_content_kind(ContentKind::Json);
+        apply_tool_result(&mut state, &json);
+        apply_tool_result(
+            &mut state,
+            &ToolResult::success("tool-002").with_content_kind(ContentKind::ImageRef),
+        );
+
+        assert_eq!(state.history[1].content, "Tool output:\n{\"rows\":[1,2]}");
+        assert_eq!(state.history[2].content, "Tool output:\n[image: tool-002]");
+        assert!(ContentKind::Json
+            .display(&json.output)
+            .contains("\n  \"rows\""));
+        let value = serde_json::to_value(&json).unwrap();
+        assert_eq!(value["content_kind"], "json");
+        assert!(serde_json::to_value(ToolResult::success("a")).unwrap()["content_kind"].is_null());
+    }
+
+    #[test]
+    fn test_versioned_state_json() {
+        // Version 1: no version, rejections, variables or call counter
+        let legacy = r#"{"history":[{"role":"user","content":"Hi"},
+            {"role":"tool","content":"Tool output","call_id":4}],
+            "is_complete":false,"final_answer":null}"#;
+        let mut state = AgentState::from_versioned_json(legacy).unwrap();
+        assert_eq!(state.version(), STATE_VERSION);
+        assert_eq!(state.history.len(), 2);
+        assert!(state.variables.is_empty());
+        assert_eq!(state.next_call_id(), CallId(5));
+
+        // Current state round-trips
+        let json = serde_json::to_string(&state).unwrap();
+        assert!(json.contains(&format!(r#""version":{}"#, STATE_VERSION)));
+        let restored = AgentState::from_versioned_json(&json).unwrap();
+        assert_eq!(restored.calls_issued, 5);
+    }
+
+    #[test]
+    fn test_versioned_state_json_loads_each_version() {
+        // Each version's state, with the fields it added set
+        let mut fixture = serde_json::json!({
+            "history": [
+                {"role": "user", "content": "Hi"},
+                {"role": "assistant", "content": "{\"tool\":\"shell\",\"command\":\"ls\"}", "call_id": 4}
+            ],
+            "is_complete": false,
+            "final_answer": null
+        });
+        let additions = [
+            (
+                2,
+                serde_json::json!({"guardrail_rejections": {"too_long": 1}, "variables": {"x": 1}, "calls_issued": 4}),
+            ),
+            (3, serde_json::json!({"answer_protocol": "sentinel"})),
+            (4, serde_json::json!({"reasoning_delimiters": null})),
+            (5, serde_json::json!({"speaker": "critic"})),
+            (6, serde_json::json!({"refusals": 1})),
+            (7, serde_json::json!({"aliased_calls": {"4": "ls"}})),
+            (8, serde_json::json!({"response_spec": {"max_words": 10}})),
+        ];
+        assert_eq!(additions.len() as u32 + 1, STATE_VERSION);
+
+        let state = AgentState::from_versioned_value(fixture.clone()).unwrap();
+        assert_eq!(state.version(), STATE_VERSION);
+        assert_eq!(state.answer_protocol, AnswerProtocol::Heuristic);
+        assert_eq!(state.reasoning_delimiters, default_reasoning_delimiters());
+        for (version, fields) in additions {
+            let object = fixture.as_object_mut().unwrap();
+            object.insert("version".to_string(), version.into());
+            object.extend(fields.as_object().unwrap().clone());
+            let mut state = AgentState::from_versioned_value(fixture.clone())
+                .unwrap_or_else(|e| panic!("version {}: {}", version, e));
+            assert_eq!(state.version(), STATE_VERSION);
+            assert_eq!(state.next_call_id(), CallId(5));
+            assert_eq!(state.refusals, usize::from(version >= 6));
+            assert_eq!(state.speaker.is_some(), version >= 5);
+            assert_eq!(state.response_spec.is_some(), version >= 8);
+        }
+    }
+
+    #[test]
+    fn test_versioned_state_json_rejects_newer_versions() {
+        let future = r#"{"version":99,"history":[],"is_complete":false,"final_answer":null}"#;
+        let error = AgentState::from_versioned_json(future).unwrap_err();
+        assert!(matches!(
+            error,
+            StateError::UnsupportedVersion {
+                found: 99,
+                supported: STATE_VERSION
+            }
+        ));
+        assert!(error.to_string().contains("newer than this build supports"));
+
+        assert!(matches!(
+            AgentState::from_versioned_json("[]"),
+            Err(StateError::NotAnObject)
+        ));
+        assert!(matches!(
+            AgentState::from_versioned_json(r#"{"version":"two"}"#),
+            Err(StateError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn test_conversation_import() {
+        let openai = r#"{"model": "gpt-4o", "messages": [
+            {"role": "system", "content": "Be helpful."},
+            {"role": "user", "content": [{"type": "text", "text": "How many files?"}]},
+            {"role": "assistant", "content": null, "tool_calls": [{"id": "call_a",
+             "type": "function", "function": {"name": "shell", "arguments": "{\"command\": \"ls\"}"}}]},
+            {"role": "tool", "tool_call_id": "call_a", "content": "a.txt\nb.txt"},
+            {"role": "assistant", "content": "There are 2 files."},
+            {"role": "user", "content": "And their sizes?"}
+        ]}"#;
+        let mut state = AgentState::from_openai_messages(openai).unwrap();
+        assert_eq!(state.history.len(), 5);
+        assert_eq!(state.history[0].content, "How many files?");
+        assert_eq!(
+            state.history[1].content,
+            r#"{"command":"ls","tool":"shell"}"#
+        );
+        assert_eq!(state.history[1].call_id, Some(CallId(1)));
+        assert_eq!(state.history[2].content, "Tool output:\na.txt\nb.txt");
+        assert_eq!(state.history[2].call_id, Some(CallId(1)));
+        assert!(matches!(state.history[4].role, Role::User));
+        // The run continues with fresh call ids
+        assert_eq!(state.next_call_id(), CallId(2));
+
+        let anthropic = r#"[
+            {"role": "user", "content": "Run it"},
+            {"role": "assistant", "content": [{"type": "tool_use", "id": "toolu_1",
+             "name": "shell", "input": {"command": "false"}}]},
+            {"role": "user", "content": [{"type": "tool_result", "tool_use_id": "toolu_1",
+             "content": "exit status 1", "is_error": true}]}
+        ]"#;
+        let state = AgentState::from_anthropic_messages(anthropic).unwrap();
+        assert_eq!(state.history[2].content, "Tool failed: exit status 1");
+
+        // Nothing left to answer, or no conversation at all
+        assert!(matches!(
+            AgentState::from_anthropic_messages(
+                r#"[{"role": "user", "content": "Hi"}, {"role": "assistant", "content": "Hello!"}]"#
+            ),
+            Err(StateError::NothingToAnswer)
+        ));
+        assert!(matches!(
+            AgentState::from_openai_messages("[]"),
+            Err(StateError::NothingToAnswer)
+        ));
+        assert!(matches!(
+            AgentState::from_openai_messages(r#"{"prompt": "Hi"}"#),
+            Err(StateError::Conversation(_))
+        ));
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn test_cbor_round_trip() {
+        let mut state = AgentState::new("Hi");
+        state.add_message(Role::Tool, "Tool output:\n".repeat(50));
+        state.variables.insert(
+            "count".to_string(),
+            serde_json::json!({"files": [1, 2.5, null]}),
+        );
+        state.next_call_id();
+
+        let bytes = state.to_cbor().unwrap();
+        assert!(bytes.len() < serde_json::to_vec(&state).unwrap().len());
+        let restored = AgentState::from_cbor(&bytes).unwrap();
+        assert_eq!(
+            serde_json::to_value(&restored).unwrap(),
+            serde_json::to_value(&state).unwrap()
+        );
+
+        state.version = STATE_VERSION + 1;
+        assert!(matches!(
+            AgentState::from_cbor(&state.to_cbor().unwrap()),
+            Err(StateError::UnsupportedVersion { .. })
+        ));
+        assert!(matches!(
+            AgentState::from_cbor(b"not cbor"),
+            Err(StateError::Cbor(_))
+        ));
+    }
 }
@@ -0,0 +1,187 @@
+//! Approval policy for tool execution
+//!
+//! Hosts decide whether to run a tool request without asking, ask the user, or
+//! refuse outright. The decision keys off the tool's [`SafetyClass`], with
+//! optional per-tool overrides. Destructive tools always require a prompt:
+//! neither a class rule nor a tool override can auto-approve them.
+
+use crate::prelude::*;
+use crate::tool_manifest::SafetyClass;
+use alloc::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+
+/// What the host does with a tool request before executing it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ApprovalMode {
+    /// Execute without asking
+    Auto,
+    /// Ask the user first
+    Prompt,
+    /// Never execute
+    Deny,
+}
+
+impl ApprovalMode {
+    /// Parse an approval mode from string
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "auto" => Some(Self::Auto),
+            "prompt" => Some(Self::Prompt),
+            "deny" => Some(Self::Deny),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::Prompt => "prompt",
+            Self::Deny => "deny",
+        }
+    }
+}
+
+/// Approval decisions per safety class, with per-tool overrides
+///
+/// The default auto-approves read-only tools and prompts for everything else.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApprovalPolicy {
+    classes: BTreeMap<SafetyClass, ApprovalMode>,
+    tools: BTreeMap<String, ApprovalMode>,
+}
+
+impl Default for ApprovalPolicy {
+    fn default() -> Self {
+        let classes = SafetyClass::ALL
+            .into_iter()
+            .map(|class| match class {
+                SafetyClass::ReadOnly => (class, ApprovalMode::Auto),
+                _ => (class, ApprovalMode::Prompt),
+            })
+            .collect();
+
+        Self {
+            classes,
+            tools: BTreeMap::new(),
+        }
+    }
+}
+
+impl ApprovalPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the mode for every tool of a safety class
+    pub fn with_class(mut self, class: SafetyClass, mode: ApprovalMode) -> Self {
+        self.classes.insert(class, mode);
+        self
+    }
+
+    /// Set the mode for a single tool, overriding its class
+    pub fn with_tool(mut self, tool: impl Into<String>, mode: ApprovalMode) -> Self {
+        self.tools.insert(tool.into(), mode);
+        self
+    }
+
+    /// Apply a `target=mode` rule, where target is a safety class or a tool name
+    /// (e.g. `network=auto`, `shell=deny`)
+    pub fn with_rule(self, rule: &str) -> Result<Self, String> {
+        let (target, mode) = rule
+            .split_once('=')
+            .ok_or_else(|| format!("Invalid approval rule '{}'. Expected target=mode", rule))?;
+        let target = target.trim();
+        let mode = ApprovalMode::from_str(mode.trim()).ok_or_else(|| {
+            format!(
+                "Invalid approval mode '{}'. Expected one of: auto, prompt, deny",
+                mode.trim()
+            )
+        })?;
+
+        if target.is_empty() {
+            return Err(format!("Invalid approval rule '{}': empty target", rule));
+        }
+
+        Ok(match SafetyClass::from_str(target) {
+            Some(class) => self.with_class(class, mode),
+            None => self.with_tool(target, mode),
+        })
+    }
+
+    /// Decide how to handle a request for `tool` with the given safety class
+    pub fn decide(&self, tool: &str, class: SafetyClass) -> ApprovalMode {
+        let mode = self
+            .tools
+            .get(tool)
+            .or_else(|| self.classes.get(&class))
+            .copied()
+            .unwrap_or(ApprovalMode::Prompt);
+
+        if class == SafetyClass::Destructive && mode == ApprovalMode::Auto {
+            ApprovalMode::Prompt
+        } else {
+            mode
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_policy() {
+        let policy = ApprovalPolicy::default();
+        assert_eq!(
+            policy.decide("ls", SafetyClass::ReadOnly),
+            ApprovalMode::Auto
+        );
+        assert_eq!(
+            policy.decide("fetch", SafetyClass::Network),
+            ApprovalMode::Prompt
+        );
+        assert_eq!(
+            policy.decide("shell", SafetyClass::Destructive),
+            ApprovalMode::Prompt
+        );
+    }
+
+    #[test]
+    fn test_rules_and_overrides() {
+        let policy = ApprovalPolicy::new()
+            .with_rule("network=auto")
+            .unwrap()
+            .with_rule("fetch=deny")
+            .unwrap();
+        assert_eq!(
+            policy.decide("http", SafetyClass::Network),
+            ApprovalMode::Auto
+        );
+        assert_eq!(
+            policy.decide("fetch", SafetyClass::Network),
+            ApprovalMode::Deny
+        );
+
+        assert!(ApprovalPolicy::new().with_rule("network").is_err());
+        assert!(ApprovalPolicy::new().with_rule("network=maybe").is_err());
+        assert!(ApprovalPolicy::new().with_rule("=auto").is_err());
+    }
+
+    #[test]
+    fn test_destructive_never_auto() {
+        let policy = ApprovalPolicy::new()
+            .with_class(SafetyClass::Destructive, ApprovalMode::Auto)
+            .with_tool("shell", ApprovalMode::Auto);
+        assert_eq!(
+            policy.decide("shell", SafetyClass::Destructive),
+            ApprovalMode::Prompt
+        );
+
+        let policy = ApprovalPolicy::new().with_tool("shell", ApprovalMode::Deny);
+        assert_eq!(
+            policy.decide("shell", SafetyClass::Destructive),
+            ApprovalMode::Deny
+        );
+    }
+}
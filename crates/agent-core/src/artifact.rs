@@ -0,0 +1,433 @@
+//! Run artifacts: large tool outputs kept out of the prompt
+//!
+//! A long tool output would crowd everything else out of the context window.
+//! Instead the host stores the full output as an artifact (one per tool output,
+//! named like `tool-003`) and records only an excerpt with a reference in the
+//! history. The model asks for the following pages by the output's number,
+//! or reads any lines by artifact id:
+//!
+//! ```text
+//! {"tool_more": {"id": 3, "page": 2}}
+//! {"read_artifact": {"id": "tool-003", "range": [41, 140]}}
+//! ```
+//!
+//! Page 1 is the excerpt. How many lines a page holds is decided when the
+//! output is spooled, from the context the conversation has left
+//! ([`ArtifactPolicy::page_lines_for`]), and stays fixed so page numbers keep
+//! their meaning.
+//!
+//! Where artifacts are stored is up to the host; this module decides when an
+//! output is spooled, what the excerpt looks like and which lines a read
+//! returns.
+
+use crate::prelude::*;
+use crate::tool::CallId;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// A request to read lines of an artifact
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArtifactRequest {
+    /// Artifact id, as given in the excerpt (e.g. `tool-003`)
+    pub id: String,
+    /// First and last line to read, 1-based and inclusive (default: first page)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub range: Option<(usize, usize)>,
+    /// Page to read (1-based) instead of a range
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page: Option<usize>,
+    /// The call this read belongs to (`None` until the agent assigns one)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub call_id: Option<CallId>,
+}
+
+/// The `tool_more` form of a read: the `id`-th tool output, page `page`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolMoreRequest {
+    pub id: usize,
+    pub page: usize,
+}
+
+impl From<ToolMoreRequest> for ArtifactRequest {
+    fn from(request: ToolMoreRequest) -> Self {
+        Self {
+            id: artifact_id(request.id),
+            range: None,
+            page: Some(request.page),
+            call_id: None,
+        }
+    }
+}
+
+/// Artifact errors
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ArtifactError {
+    #[error("Invalid artifact id '{0}'")]
+    InvalidId(String),
+
+    #[error("Artifact '{id}' has {total} lines; line {start} is out of range")]
+    OutOfRange {
+        id: String,
+        start: usize,
+        total: usize,
+    },
+
+    #[error("Artifact '{id}' has {pages} page(s); page {page} is out of range")]
+    PageOutOfRange {
+        id: String,
+        page: usize,
+        pages: usize,
+    },
+}
+
+/// Id of the artifact holding the `index`-th tool output (1-based)
+pub fn artifact_id(index: usize) -> String {
+    format!("tool-{:03}", index)
+}
+
+/// The tool output number of an artifact id (`tool-003` is 3)
+pub fn artifact_index(id: &str) -> Option<usize> {
+    id.strip_prefix("tool-")?.parse().ok()
+}
+
+/// Whether `id` is safe to use as a file name (`[a-z0-9-]`, not empty)
+pub fn is_valid_artifact_id(id: &str) -> bool {
+    !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+/// Fewest lines a page holds, however full the context is
+pub const MIN_PAGE_LINES: usize = 10;
+
+/// When tool outputs are spooled to artifacts and how much of them is shown
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArtifactPolicy {
+    /// Lines of a spooled output kept in the history (at most one page)
+    pub excerpt_lines: usize,
+    /// Lines returned by one read, at most
+    pub page_lines: usize,
+    /// Longer lines are shortened in excerpts and reads
+    pub max_line_chars: usize,
+}
+
+impl Default for ArtifactPolicy {
+    fn default() -> Self {
+        Self {
+            excerpt_lines: 40,
+            page_lines: 100,
+            max_line_chars: 500,
+        }
+    }
+}
+
+impl ArtifactPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_excerpt_lines(mut self, lines: usize) -> Self {
+        self.excerpt_lines = lines.max(1);
+        self
+    }
+
+    pub fn with_page_lines(mut self, lines: usize) -> Self {
+        self.page_lines = lines.max(1);
+        self
+    }
+
+    pub fn with_max_line_chars(mut self, chars: usize) -> Self {
+        self.max_line_chars = chars.max(1);
+        self
+    }
+
+    /// Whether an output is too large to go into the history whole
+    pub fn should_spool(&self, output: &str) -> bool {
+        let mut lines = 0;
+        for line in output.lines() {
+            lines += 1;
+            if lines > self.excerpt_lines || line.chars().count() > self.max_line_chars {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Lines per page for `output`, given the tokens left in the context
+    ///
+    /// A page may take a quarter of what is left (at about four characters
+    /// per token), between [`MIN_PAGE_LINES`] and `page_lines` lines. Hosts
+    /// apply the result with [`with_page_lines`](Self::with_page_lines) for
+    /// that output's excerpt and reads.
+    pub fn page_lines_for(&self, output: &str, free_tokens: usize) -> usize {
+        let lines = output.lines().count().max(1);
+        // Characters per line, newline included
+        let line_chars = (output.chars().count() / lines).clamp(1, self.max_line_chars + 1);
+        (free_tokens / line_chars).clamp(MIN_PAGE_LINES.min(self.page_lines), self.page_lines)
+    }
+
+    /// The history entry for an output spooled as artifact `id`
+    ///
+    /// This is page 1 of the artifact.
+    pub fn excerpt(&self, id: &str, output: &str) -> String {
+        let total = output.lines().count();
+        let shown = total.min(self.first_page_lines());
+
+        let pages = self.page_count(total);
+        let mut excerpt = self.render_lines(output, 1, shown);
+        excerpt.push_str(&format!(
+            "[Output truncated: showing lines 1-{} of {}",
+            shown, total
+        ));
+        if pages > 1 {
+            excerpt.push_str(&format!(" (page 1 of {})", pages));
+        }
+        excerpt.push_str(&format!(". Full output saved as artifact {}.", id));
+        if shown < total {
+            excerpt.push_str(" Read the next page with ");
+            excerpt.push_str(&self.page_hint(id, 2, total));
+        }
+        excerpt.push(']');
+        excerpt
+    }
+
+    /// The lines of an artifact a read request asks for
+    ///
+    /// Reads return at most one page; the reply says how to read the next one.
+    pub fn read(&self, request: &ArtifactRequest, content: &str) -> Result<String, ArtifactError> {
+        if !is_valid_artifact_id(&request.id) {
+            return Err(ArtifactError::InvalidId(request.id.clone()));
+        }
+        if let Some(page) = request.page {
+            return self.read_page(&request.id, page, content);
+        }
+
+        let total = content.lines().count();
+        let (start, end) = request.range.unwrap_or((1, self.page_lines));
+        if start == 0 || start > total {
+            return Err(ArtifactError::OutOfRange {
+                id: request.id.clone(),
+                start,
+                total,
+            });
+        }
+        let end = end.max(start).min(total).min(start + self.page_lines - 1);
+
+        let mut text = format!(
+            "Artifact {}, lines {}-{} of {}:\n",
+            request.id, start, end, total
+        );
+        text.push_str(&self.render_lines(content, start, end));
+        if end < total {
+            text.push_str("[Read more with ");
+            text.push_str(&self.read_hint(&request.id, end + 1, total));
+            text.push(']');
+        }
+        Ok(text)
+    }
+
+    fn read_page(&self, id: &str, page: usize, content: &str) -> Result<String, ArtifactError> {
+        let total = content.lines().count();
+        let pages = self.page_count(total);
+        if page == 0 || page > pages {
+            return Err(ArtifactError::PageOutOfRange {
+                id: id.to_string(),
+                page,
+                pages,
+            });
+        }
+
+        let (start, end) = self.page_range(page, total);
+        let mut text = format!(
+            "Artifact {}, page {} of {} (lines {}-{} of {}):\n",
+            id, page, pages, start, end, total
+        );
+        text.push_str(&self.render_lines(content, start, end));
+        if page < pages {
+            text.push_str("[Read the next page with ");
+            text.push_str(&self.page_hint(id, page + 1, total));
+            text.push(']');
+        }
+        Ok(text)
+    }
+
+    fn first_page_lines(&self) -> usize {
+        self.excerpt_lines.min(self.page_lines)
+    }
+
+    fn page_count(&self, total: usize) -> usize {
+        let first = self.first_page_lines();
+        1 + total.saturating_sub(first).div_ceil(self.page_lines)
+    }
+
+    /// First and last line of page `page` (1-based, at least 1)
+    fn page_range(&self, page: usize, total: usize) -> (usize, usize) {
+        let first = self.first_page_lines();
+        let (start, end) = match page {
+            1 => (1, first),
+            _ => {
+                let start = first + (page - 2) * self.page_lines + 1;
+                (start, start + self.page_lines - 1)
+            }
+        };
+        (start, end.min(total))
+    }
+
+    /// Lines `start..=end` (1-based), each shortened and newline-terminated
+    fn render_lines(&self, content: &str, start: usize, end: usize) -> String {
+        let mut text = String::new();
+        for line in content.lines().skip(start - 1).take(end + 1 - start) {
+            if line.chars().count() > self.max_line_chars {
+                text.extend(line.chars().take(self.max_line_chars));
+                text.push_str("...");
+            } else {
+                text.push_str(line);
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    /// The read decision for the page starting at `start`
+    fn read_hint(&self, id: &str, start: usize, total: usize) -> String {
+        let request = ArtifactRequest {
+            id: id.to_string(),
+            range: Some((start, total.min(start + self.page_lines - 1))),
+            page: None,
+            call_id: None,
+        };
+        serde_json::json!({ "read_artifact": request }).to_string()
+    }
+
+    /// The read decision for page `page`, as `tool_more` when the id allows
+    fn page_hint(&self, id: &str, page: usize, total: usize) -> String {
+        match artifact_index(id) {
+            Some(index) => {
+                serde_json::json!({ "tool_more": ToolMoreRequest { id: index, page } }).to_string()
+            }
+            None => {
+                let (start, _) = self.page_range(page, total);
+                self.read_hint(id, start, total)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbered(lines: usize) -> String {
+        (1..=lines).map(|n| format!("line {}\n", n)).collect()
+    }
+
+    #[test]
+    fn test_excerpt_references_artifact() {
+        let policy = ArtifactPolicy::new()
+            .with_excerpt_lines(3)
+            .with_page_lines(10);
+        let output = numbered(50);
+
+        assert!(policy.should_spool(&output));
+        assert!(!policy.should_spool(&numbered(3)));
+
+        let excerpt = policy.excerpt("tool-001", &output);
+        assert!(excerpt.starts_with("line 1\nline 2\nline 3\n[Output truncated"));
+        assert!(excerpt.contains("lines 1-3 of 50 (page 1 of 6)"));
+        assert!(excerpt.contains(r#"{"tool_more":{"id":1,"page":2}}"#));
+    }
+
+    #[test]
+    fn test_read_by_page() {
+        let policy = ArtifactPolicy::new()
+            .with_excerpt_lines(3)
+            .with_page_lines(10);
+        let content = numbered(25);
+
+        let request = ArtifactRequest::from(ToolMoreRequest { id: 4, page: 2 });
+        assert_eq!(request.id, "tool-004");
+        let page = policy.read(&request, &content).unwrap();
+        assert!(page.starts_with("Artifact tool-004, page 2 of 4 (lines 4-13 of 25):\nline 4\n"));
+        assert!(page.contains(r#"{"tool_more":{"id":4,"page":3}}"#));
+
+        let last = ArtifactRequest::from(ToolMoreRequest { id: 4, page: 4 });
+        let page = policy.read(&last, &content).unwrap();
+        assert!(page.contains("(lines 24-25 of 25)"));
+        assert!(page.ends_with("line 25\n"));
+
+        let beyond = ArtifactRequest::from(ToolMoreRequest { id: 4, page: 5 });
+        assert!(matches!(
+            policy.read(&beyond, &content),
+            Err(ArtifactError::PageOutOfRange { pages: 4, .. })
+        ));
+    }
+
+    #[test]
+    fn test_page_size_follows_context_budget() {
+        let policy = ArtifactPolicy::new().with_page_lines(100);
+        let output = "x".repeat(79) + "\n";
+        let output = output.repeat(500);
+
+        assert_eq!(policy.page_lines_for(&output, 100_000), 100);
+        assert_eq!(policy.page_lines_for(&output, 2_000), 25);
+        assert_eq!(policy.page_lines_for(&output, 0), MIN_PAGE_LINES);
+    }
+
+    #[test]
+    fn test_read_pages() {
+        let policy = ArtifactPolicy::new().with_page_lines(10);
+        let content = numbered(25);
+
+        let request = ArtifactRequest {
+            id: "tool-002".to_string(),
+            range: Some((5, 100)),
+            page: None,
+            call_id: None,
+        };
+        let page = policy.read(&request, &content).unwrap();
+        assert!(page.starts_with("Artifact tool-002, lines 5-14 of 25:\nline 5\n"));
+        assert!(page.contains(r#""range":[15,24]"#));
+
+        let last = ArtifactRequest {
+            id: "tool-002".to_string(),
+            range: Some((20, 30)),
+            page: None,
+            call_id: None,
+        };
+        let page = policy.read(&last, &content).unwrap();
+        assert!(page.ends_with("line 25\n"));
+
+        let beyond = ArtifactRequest {
+            id: "tool-002".to_string(),
+            range: Some((26, 30)),
+            page: None,
+            call_id: None,
+        };
+        assert!(matches!(
+            policy.read(&beyond, &content),
+            Err(ArtifactError::OutOfRange { total: 25, .. })
+        ));
+
+        let escape = ArtifactRequest {
+            id: "../secrets".to_string(),
+            range: None,
+            page: None,
+            call_id: None,
+        };
+        assert!(matches!(
+            policy.read(&escape, &content),
+            Err(ArtifactError::InvalidId(_))
+        ));
+    }
+
+    #[test]
+    fn test_long_lines_are_shortened() {
+        let policy = ArtifactPolicy::new().with_max_line_chars(5);
+        let output = "abcdefghij\n";
+        assert!(policy.should_spool(output));
+        assert!(policy
+            .excerpt("tool-001", output)
+            .starts_with("abcde...\n[Output truncated: showing lines 1-1 of 1."));
+    }
+}
@@ -0,0 +1,253 @@
+//! Append-only audit log of agent runs
+//!
+//! Hosts record what happened in a run (who approved which tool call, what
+//! ran, and a hash of what it produced) as one JSON [`AuditRecord`] per line.
+//! Tool executions are numbered in the order they are proposed, so every
+//! record about one call carries the same execution id.
+//!
+//! Each record holds the SHA-256 of the line before it, so [`verify_log`]
+//! notices records that were edited, reordered, or removed from inside the
+//! log after the fact. Records cut off its end leave a shorter chain that
+//! still verifies: the chain cannot tell a truncated log from one whose run
+//! stopped writing. This module only builds and checks records; hosts write
+//! them.
+
+use crate::prelude::*;
+use crate::tool_manifest::SafetyClass;
+use crate::undo::FileUndo;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+/// `prev` of the first record in a log
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// What an audit record is about
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AuditEntry {
+    /// The run started on behalf of `user`
+    RunStarted { query: String, user: String },
+    /// The model asked for a tool call
+    ToolProposed {
+        tool: String,
+        safety: SafetyClass,
+        params: Value,
+    },
+    /// The call was refused before it ran (manifest or pre-execution guardrail)
+    ToolRejected { tool: String, reason: String },
+    /// The call was approved or refused; `by` is `policy` or the user's name
+    ApprovalDecided {
+        tool: String,
+        approved: bool,
+        by: String,
+    },
+    /// The tool ran; its output is kept as a hash only
+    ToolExecuted {
+        tool: String,
+        success: bool,
+        output_bytes: usize,
+        output_sha256: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    /// The call changed files; their pre-images are kept for undo
+    FilesChanged { tool: String, files: Vec<FileUndo> },
+    /// Semantic guardrails judged the output
+    GuardrailVerdict {
+        tool: String,
+        accepted: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+    },
+    /// Sandbox changes were written to the real workspace
+    SandboxApplied { files: usize },
+    /// The run ended: `answered`, or the failure code
+    RunFinished {
+        outcome: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        answer_sha256: Option<String>,
+    },
+}
+
+/// One line of the audit log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// Run id
+    pub run: String,
+    /// Position in the log, from 1
+    pub seq: u64,
+    /// Seconds since the Unix epoch
+    pub time: u64,
+    /// Tool execution the record belongs to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub execution: Option<u64>,
+    /// SHA-256 of the previous line ([`GENESIS_HASH`] for the first)
+    pub prev: String,
+    #[serde(flatten)]
+    pub entry: AuditEntry,
+}
+
+/// Errors from [`verify_log`]
+#[derive(Debug, Error, PartialEq)]
+pub enum AuditError {
+    #[error("line {line}: not an audit record: {message}")]
+    Malformed { line: usize, message: String },
+    #[error(
+        "line {line}: record does not follow the line before it (edited, removed or reordered)"
+    )]
+    BrokenChain { line: usize },
+}
+
+/// Hex SHA-256 of `data`
+pub fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Builds the lines of one run's audit log, chaining each to the last
+#[derive(Debug, Clone)]
+pub struct AuditChain {
+    run: String,
+    seq: u64,
+    prev: String,
+}
+
+impl AuditChain {
+    /// A new, empty log for run `run`
+    pub fn new(run: impl Into<String>) -> Self {
+        Self {
+            run: run.into(),
+            seq: 0,
+            prev: GENESIS_HASH.to_string(),
+        }
+    }
+
+    /// The next line of the log (without the newline)
+    pub fn append(&mut self, time: u64, execution: Option<u64>, entry: AuditEntry) -> String {
+        self.seq += 1;
+        let record = AuditRecord {
+            run: self.run.clone(),
+            seq: self.seq,
+            time,
+            execution,
+            prev: self.prev.clone(),
+            entry,
+        };
+        let line = serde_json::to_string(&record).expect("audit records serialize");
+        self.prev = sha256_hex(line.as_bytes());
+        line
+    }
+}
+
+/// Parse an audit log and check that its records form an unbroken chain
+///
+/// Detects edits, reordering and removals before the last record, not
+/// records removed from the end.
+pub fn verify_log(log: &str) -> Result<Vec<AuditRecord>, AuditError> {
+    let mut prev = GENESIS_HASH.to_string();
+    let mut records = Vec::new();
+    for (index, line) in log.lines().enumerate() {
+        let record: AuditRecord =
+            serde_json::from_str(line).map_err(|e| AuditError::Malformed {
+                line: index + 1,
+                message: e.to_string(),
+            })?;
+        if record.prev != prev || record.seq != index as u64 + 1 {
+            return Err(AuditError::BrokenChain { line: index + 1 });
+        }
+        prev = sha256_hex(line.as_bytes());
+        records.push(record);
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_log() -> Vec<String> {
+        let mut chain = AuditChain::new("run-1");
+        vec![
+            chain.append(
+                10,
+                None,
+                AuditEntry::RunStarted {
+                    query: "list files".to_string(),
+                    user: "alice".to_string(),
+                },
+            ),
+            chain.append(
+                11,
+                Some(1),
+                AuditEntry::ApprovalDecided {
+                    tool: "shell".to_string(),
+                    approved: true,
+                    by: "alice".to_string(),
+                },
+            ),
+            chain.append(
+                12,
+                Some(1),
+                AuditEntry::ToolExecuted {
+                    tool: "shell".to_string(),
+                    success: true,
+                    output_bytes: 3,
+                    output_sha256: sha256_hex(b"ok\n"),
+                    error: None,
+                },
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_log_round_trips_and_verifies() {
+        let log = sample_log().join("\n");
+        let records = verify_log(&log).unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].prev, GENESIS_HASH);
+        assert_eq!(records[2].execution, Some(1));
+        assert!(matches!(
+            &records[1].entry,
+            AuditEntry::ApprovalDecided { by, .. } if by == "alice"
+        ));
+        assert!(log.contains(r#""kind":"tool_executed""#));
+    }
+
+    #[test]
+    fn test_tampering_breaks_the_chain() {
+        let mut lines = sample_log();
+        lines[1] = lines[1].replace("alice", "mallory");
+        assert_eq!(
+            verify_log(&lines.join("\n")),
+            Err(AuditError::BrokenChain { line: 3 })
+        );
+
+        let mut lines = sample_log();
+        lines.remove(1);
+        assert_eq!(
+            verify_log(&lines.join("\n")),
+            Err(AuditError::BrokenChain { line: 2 })
+        );
+
+        // A truncated log is a shorter chain
+        let lines = sample_log();
+        assert_eq!(verify_log(&lines[..2].join("\n")).unwrap().len(), 2);
+
+        assert!(matches!(
+            verify_log("not json"),
+            Err(AuditError::Malformed { line: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_sha256_hex() {
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
@@ -0,0 +1,317 @@
+//! API keys for hosts that serve agent runs over HTTP
+//!
+//! A host exposing the agent over the network (submitting runs, answering
+//! approval prompts, reading transcripts) hands out bearer keys and checks
+//! every request's `Authorization: Bearer <key>` header with
+//! [`ApiKeys::authorize`]. Each key carries the [`Scope`]s it may use and an
+//! optional requests-per-minute limit:
+//!
+//! ```yaml
+//! - name: ci
+//!   sha256: 9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08
+//!   scopes: [submit_runs, read_transcripts]
+//!   requests_per_minute: 30
+//! ```
+//!
+//! Keys are stored as their SHA-256 ([`hash_key`]), so a leaked config does
+//! not leak the keys. This module only decides whether a request may go and
+//! which status to answer otherwise; hosts parse requests and respond.
+//! Times are offsets from an epoch the host picks.
+//!
+//! No host in this repository checks keys yet: `agent-native` serves no HTTP.
+
+use crate::audit::sha256_hex;
+use crate::prelude::*;
+use crate::rate_limit::{RateLimits, RateWindow};
+use alloc::collections::BTreeMap;
+use core::time::Duration;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// What a key may do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scope {
+    /// Start runs
+    SubmitRuns,
+    /// Answer tool approval prompts
+    ApproveTools,
+    /// Read run transcripts and results
+    ReadTranscripts,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 3] = [
+        Scope::SubmitRuns,
+        Scope::ApproveTools,
+        Scope::ReadTranscripts,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Scope::SubmitRuns => "submit_runs",
+            Scope::ApproveTools => "approve_tools",
+            Scope::ReadTranscripts => "read_transcripts",
+        }
+    }
+
+    pub fn from_str(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|scope| scope.as_str() == value)
+    }
+}
+
+/// One key a host accepts
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ApiKey {
+    /// Name logged for the key's requests; never the key itself
+    pub name: String,
+    /// Hex SHA-256 of the key
+    pub sha256: String,
+    pub scopes: Vec<Scope>,
+    /// Requests the key may make per minute (default: unlimited)
+    #[serde(default)]
+    pub requests_per_minute: Option<u32>,
+}
+
+impl ApiKey {
+    /// An entry for `key`, stored hashed
+    pub fn new(name: impl Into<String>, key: &str, scopes: Vec<Scope>) -> Self {
+        Self {
+            name: name.into(),
+            sha256: hash_key(key),
+            scopes,
+            requests_per_minute: None,
+        }
+    }
+
+    pub fn with_requests_per_minute(mut self, limit: Option<u32>) -> Self {
+        self.requests_per_minute = limit;
+        self
+    }
+}
+
+/// Why a request was refused
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum AuthError {
+    #[error("missing Authorization header")]
+    Missing,
+    #[error("Authorization header is not 'Bearer <key>'")]
+    Malformed,
+    #[error("unknown API key")]
+    UnknownKey,
+    #[error("key '{key}' lacks the {} scope", scope.as_str())]
+    Forbidden { key: String, scope: Scope },
+    #[error("key '{key}' is over its rate limit; retry in {}s", retry_after.as_secs().max(1))]
+    RateLimited { key: String, retry_after: Duration },
+}
+
+impl AuthError {
+    /// HTTP status to answer with
+    pub fn status(&self) -> u16 {
+        match self {
+            AuthError::Missing | AuthError::Malformed | AuthError::UnknownKey => 401,
+            AuthError::Forbidden { .. } => 403,
+            AuthError::RateLimited { .. } => 429,
+        }
+    }
+}
+
+/// Why a key list was rejected
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ApiKeysError {
+    #[error("two API keys are named '{0}'")]
+    DuplicateName(String),
+    #[error("API keys '{0}' and '{1}' have the same SHA-256")]
+    DuplicateKey(String, String),
+}
+
+/// Hex SHA-256 of `key`, as stored in [`ApiKey::sha256`]
+pub fn hash_key(key: &str) -> String {
+    sha256_hex(key.as_bytes())
+}
+
+/// The key of an `Authorization: Bearer <key>` header value
+pub fn bearer_token(header: &str) -> Option<&str> {
+    let (scheme, token) = header.trim().split_once(' ')?;
+    let token = token.trim();
+    (scheme.eq_ignore_ascii_case("bearer") && !token.is_empty() && !token.contains(' '))
+        .then_some(token)
+}
+
+/// The keys a host accepts, with each key's recent requests
+#[derive(Debug, Clone, Default)]
+pub struct ApiKeys {
+    keys: Vec<ApiKey>,
+    /// Recent requests by key hash
+    windows: BTreeMap<String, RateWindow>,
+}
+
+impl ApiKeys {
+    /// Accept `keys`, which must have distinct names and hashes
+    pub fn new(keys: Vec<ApiKey>) -> Result<Self, ApiKeysError> {
+        for (i, key) in keys.iter().enumerate() {
+            for earlier in &keys[..i] {
+                if earlier.name == key.name {
+                    return Err(ApiKeysError::DuplicateName(key.name.clone()));
+                }
+                if earlier.sha256.eq_ignore_ascii_case(&key.sha256) {
+                    return Err(ApiKeysError::DuplicateKey(
+                        earlier.name.clone(),
+                        key.name.clone(),
+                    ));
+                }
+            }
+        }
+        Ok(Self {
+            keys,
+            windows: BTreeMap::new(),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Check a request's `Authorization` header for `scope` at `now`, and
+    /// count it against the key's rate limit
+    ///
+    /// Returns the key's name. Refused requests are not counted.
+    pub fn authorize(
+        &mut self,
+        header: Option<&str>,
+        scope: Scope,
+        now: Duration,
+    ) -> Result<&str, AuthError> {
+        let token = bearer_token(header.ok_or(AuthError::Missing)?).ok_or(AuthError::Malformed)?;
+        let hash = hash_key(token);
+        let key = self
+            .keys
+            .iter()
+            .find(|key| {
+                constant_time_eq(key.sha256.to_ascii_lowercase().as_bytes(), hash.as_bytes())
+            })
+            .ok_or(AuthError::UnknownKey)?;
+        if !key.scopes.contains(&scope) {
+            return Err(AuthError::Forbidden {
+                key: key.name.clone(),
+                scope,
+            });
+        }
+        // `hash` is the matched key's hash, lowercased
+        let window = self.windows.entry(hash).or_default();
+        let limits = RateLimits::default().with_requests_per_minute(key.requests_per_minute);
+        let retry_after = window.delay(&limits, now, 0);
+        if !retry_after.is_zero() {
+            return Err(AuthError::RateLimited {
+                key: key.name.clone(),
+                retry_after,
+            });
+        }
+        window.record(now, 0);
+        Ok(&key.name)
+    }
+}
+
+/// Compare without returning early, so timing does not tell how much matched
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys() -> ApiKeys {
+        ApiKeys::new(vec![
+            ApiKey::new(
+                "ci",
+                "secret-ci",
+                vec![Scope::SubmitRuns, Scope::ReadTranscripts],
+            )
+            .with_requests_per_minute(Some(2)),
+            ApiKey::new("ops", "secret-ops", Scope::ALL.to_vec()),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn test_bearer_token() {
+        assert_eq!(bearer_token("Bearer abc"), Some("abc"));
+        assert_eq!(bearer_token("bearer  abc "), Some("abc"));
+        assert_eq!(bearer_token("Basic abc"), None);
+        assert_eq!(bearer_token("Bearer"), None);
+        assert_eq!(bearer_token("Bearer a b"), None);
+    }
+
+    #[test]
+    fn test_authorize_scopes() {
+        let mut keys = keys();
+        let now = Duration::ZERO;
+        assert_eq!(
+            keys.authorize(Some("Bearer secret-ops"), Scope::ApproveTools, now),
+            Ok("ops")
+        );
+        assert_eq!(
+            keys.authorize(Some("Bearer secret-ci"), Scope::ApproveTools, now),
+            Err(AuthError::Forbidden {
+                key: "ci".to_string(),
+                scope: Scope::ApproveTools
+            })
+        );
+        let unknown = keys.authorize(Some("Bearer nope"), Scope::SubmitRuns, now);
+        assert_eq!(unknown, Err(AuthError::UnknownKey));
+        let missing = keys.authorize(None, Scope::SubmitRuns, now).unwrap_err();
+        assert_eq!(missing.status(), 401);
+        assert_eq!(
+            serde_json::from_str::<Vec<Scope>>(r#"["submit_runs","read_transcripts"]"#).unwrap(),
+            vec![Scope::SubmitRuns, Scope::ReadTranscripts]
+        );
+    }
+
+    #[test]
+    fn test_duplicate_keys_are_rejected() {
+        let ci = || ApiKey::new("ci", "secret-ci", vec![Scope::SubmitRuns]);
+        assert_eq!(
+            ApiKeys::new(vec![ci(), ApiKey::new("ci", "other", vec![])]).unwrap_err(),
+            ApiKeysError::DuplicateName("ci".to_string())
+        );
+        let mut upper = ApiKey::new("ci-2", "secret-ci", vec![]);
+        upper.sha256 = upper.sha256.to_ascii_uppercase();
+        assert_eq!(
+            ApiKeys::new(vec![ci(), upper]).unwrap_err(),
+            ApiKeysError::DuplicateKey("ci".to_string(), "ci-2".to_string())
+        );
+    }
+
+    #[test]
+    fn test_rate_limit_per_key() {
+        let mut keys = keys();
+        let ci = Some("Bearer secret-ci");
+        for second in [0, 1] {
+            let now = Duration::from_secs(second);
+            assert!(keys.authorize(ci, Scope::SubmitRuns, now).is_ok());
+        }
+        let limited = keys
+            .authorize(ci, Scope::ReadTranscripts, Duration::from_secs(2))
+            .unwrap_err();
+        assert_eq!(limited.status(), 429);
+        assert!(matches!(
+            limited,
+            AuthError::RateLimited { retry_after, .. } if retry_after == Duration::from_secs(58)
+        ));
+        // Other keys have their own window
+        for _ in 0..5 {
+            assert!(keys
+                .authorize(
+                    Some("Bearer secret-ops"),
+                    Scope::SubmitRuns,
+                    Duration::from_secs(2)
+                )
+                .is_ok());
+        }
+        assert!(keys
+            .authorize(ci, Scope::SubmitRuns, Duration::from_secs(60))
+            .is_ok());
+    }
+}
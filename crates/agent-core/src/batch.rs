@@ -0,0 +1,645 @@
+//! Batch runs: many independent queries, one result record each
+//!
+//! A task file is JSONL, one task per line:
+//!
+//! ```text
+//! {"id": "doc-001", "query": "Extract the people named in invoices/001.txt"}
+//! {"id": "doc-002", "query": "...", "args": ["--max-iterations", "8"]}
+//! {"query": "ids default to task-<line>"}
+//! {"id": "doc-003", "query": "...", "max_iterations": 8, "tools": ["shell"], "approve": ["shell=auto"]}
+//! ```
+//!
+//! A task can set its own limits ([`TaskOverrides`]). Hosts running tasks
+//! from others give [`OverrideBounds`]: a task asking for more than they allow
+//! is rejected with the reason instead of run, and raw `args`, which could
+//! step around the bounds, are refused.
+//!
+//! Hosts run each task as its own agent run and write a [`BatchRecord`] per
+//! task as it finishes, so a results file of a long batch is usable while the
+//! batch runs and tells a resumed batch which tasks are done.
+//!
+//! A batch can also tell others how it went: hosts send each
+//! [`BatchNotification`] that [`NotifyOn`] asks for to webhooks or commands,
+//! so alerting does not have to poll the results file.
+
+use crate::approval::{ApprovalMode, ApprovalPolicy};
+use crate::eval::CaseRun;
+use crate::outcome::RunOutcome;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use thiserror::Error;
+
+/// Errors from reading a task file
+#[derive(Debug, Error, PartialEq)]
+pub enum BatchError {
+    #[error("line {line}: not a task: {message}")]
+    Malformed { line: usize, message: String },
+    #[error("line {line}: task id '{id}' must be a plain file name")]
+    InvalidId { line: usize, id: String },
+    #[error("duplicate task id '{0}'")]
+    Duplicate(String),
+    #[error("no tasks")]
+    Empty,
+}
+
+/// One query of a batch
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatchTask {
+    /// Names the task's result record and transcript directory
+    pub id: String,
+    pub query: String,
+    /// Host arguments for this task only
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(flatten)]
+    pub overrides: TaskOverrides,
+}
+
+impl BatchTask {
+    /// Host arguments of the task's run: its `args` and overrides, with the
+    /// bounds' tool set unless the task narrows it
+    pub fn run_args(&self, bounds: &OverrideBounds) -> Vec<String> {
+        let mut args = self.args.clone();
+        let overrides = &self.overrides;
+        let numbers = [
+            ("--max-iterations", overrides.max_iterations),
+            ("--max-tokens", overrides.max_tokens),
+        ];
+        for (flag, value) in numbers {
+            if let Some(value) = value {
+                args.extend([flag.to_string(), value.to_string()]);
+            }
+        }
+        if let Some(tools) = overrides.tools.as_ref().or(bounds.tools.as_ref()) {
+            args.extend(["--tools".to_string(), tools.join(",")]);
+        }
+        for rule in &overrides.approve {
+            args.extend(["--approve".to_string(), rule.clone()]);
+        }
+        args
+    }
+}
+
+/// Limits a task sets for its own run
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskOverrides {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_iterations: Option<usize>,
+    /// Tokens per model call
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<usize>,
+    /// Tools offered to the model
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<String>>,
+    /// Approval rules (`target=mode`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub approve: Vec<String>,
+}
+
+/// How far tasks may go with their overrides; unset bounds leave a setting
+/// free
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OverrideBounds {
+    pub max_iterations: Option<usize>,
+    pub max_tokens: Option<usize>,
+    /// Tools tasks may use; also the tool set of tasks that set none
+    pub tools: Option<Vec<String>>,
+    /// Safety classes and tools tasks may approve with `auto`
+    pub auto_approve: Vec<String>,
+}
+
+impl OverrideBounds {
+    /// Whether no bound is set, so tasks may set anything, raw `args` included
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Why `task` cannot run within the bounds, if it cannot
+    pub fn check(&self, task: &BatchTask) -> Result<(), String> {
+        let overrides = &task.overrides;
+        for rule in &overrides.approve {
+            ApprovalPolicy::new().with_rule(rule)?;
+        }
+        if self.is_empty() {
+            return Ok(());
+        }
+        if !task.args.is_empty() {
+            return Err(
+                "args are not allowed in this batch; set max_iterations, max_tokens, tools or approve"
+                    .to_string(),
+            );
+        }
+        let numbers = [
+            (
+                "max_iterations",
+                overrides.max_iterations,
+                self.max_iterations,
+            ),
+            ("max_tokens", overrides.max_tokens, self.max_tokens),
+        ];
+        for (name, value, bound) in numbers {
+            if let (Some(value), Some(bound)) = (value, bound) {
+                if value > bound {
+                    return Err(format!(
+                        "{} {} is above the limit of {}",
+                        name, value, bound
+                    ));
+                }
+            }
+        }
+        if let (Some(tools), Some(allowed)) = (&overrides.tools, &self.tools) {
+            if let Some(tool) = tools.iter().find(|tool| !allowed.contains(tool)) {
+                return Err(format!(
+                    "tool '{}' is not allowed (allowed: {})",
+                    tool,
+                    allowed.join(", ")
+                ));
+            }
+        }
+        for rule in &overrides.approve {
+            let (target, mode) = rule.split_once('=').unwrap_or((rule, ""));
+            let auto = ApprovalMode::from_str(mode.trim()) == Some(ApprovalMode::Auto);
+            if auto
+                && !self
+                    .auto_approve
+                    .iter()
+                    .any(|allowed| allowed == target.trim())
+            {
+                return Err(format!(
+                    "approval rule '{}' is not allowed (auto-approvable: {})",
+                    rule,
+                    match self.auto_approve.is_empty() {
+                        true => "none".to_string(),
+                        false => self.auto_approve.join(", "),
+                    }
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TaskLine {
+    #[serde(default)]
+    id: Option<String>,
+    query: String,
+    #[serde(default)]
+    args: Vec<String>,
+    #[serde(default)]
+    max_iterations: Option<usize>,
+    #[serde(default)]
+    max_tokens: Option<usize>,
+    #[serde(default)]
+    tools: Option<Vec<String>>,
+    #[serde(default)]
+    approve: Vec<String>,
+}
+
+/// Parse a JSONL task file, skipping blank lines
+pub fn parse_tasks(jsonl: &str) -> Result<Vec<BatchTask>, BatchError> {
+    let mut tasks = Vec::new();
+    let mut ids = BTreeSet::new();
+    for (index, text) in jsonl.lines().enumerate() {
+        let line = index + 1;
+        if text.trim().is_empty() {
+            continue;
+        }
+        let task: TaskLine = serde_json::from_str(text).map_err(|e| BatchError::Malformed {
+            line,
+            message: e.to_string(),
+        })?;
+        let id = task.id.unwrap_or_else(|| format!("task-{}", line));
+        if !is_plain_name(&id) {
+            return Err(BatchError::InvalidId { line, id });
+        }
+        if !ids.insert(id.clone()) {
+            return Err(BatchError::Duplicate(id));
+        }
+        tasks.push(BatchTask {
+            id,
+            query: task.query,
+            args: task.args,
+            overrides: TaskOverrides {
+                max_iterations: task.max_iterations,
+                max_tokens: task.max_tokens,
+                tools: task.tools,
+                approve: task.approve,
+            },
+        });
+    }
+    if tasks.is_empty() {
+        return Err(BatchError::Empty);
+    }
+    Ok(tasks)
+}
+
+/// Whether `id` can name a directory without escaping its parent
+pub(crate) fn is_plain_name(id: &str) -> bool {
+    !id.is_empty() && id != "." && id != ".." && !id.contains(['/', '\\'])
+}
+
+/// How a task ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchStatus {
+    Completed,
+    Failed,
+}
+
+/// The result of one task, a line of the results file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatchRecord {
+    pub id: String,
+    pub status: BatchStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub answer: Option<String>,
+    /// Why the task failed (failure code, timeout or host error)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The run's id, naming its audit log
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_id: Option<String>,
+    /// Tools the run called, in order
+    pub tools: Vec<String>,
+    pub iterations: usize,
+    pub tokens: usize,
+    pub wall_seconds: f64,
+    /// The recorded run (prompts, model outputs, tool calls and answer)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transcript: Option<String>,
+}
+
+impl BatchRecord {
+    /// The record of `task` from what the host observed
+    pub fn new(task: &BatchTask, run: CaseRun, transcript: Option<String>) -> Self {
+        let status = match (&run.answer, &run.error) {
+            (Some(_), None) => BatchStatus::Completed,
+            _ => BatchStatus::Failed,
+        };
+        Self {
+            id: task.id.clone(),
+            status,
+            error: match (&run.answer, run.error) {
+                (None, None) => Some("no final answer".to_string()),
+                (_, error) => error,
+            },
+            answer: run.answer,
+            run_id: run.run_id,
+            tools: run.tools,
+            iterations: run.iterations,
+            tokens: run.tokens,
+            wall_seconds: run.wall_seconds,
+            transcript,
+        }
+    }
+
+    /// The record of a task refused before it ran
+    pub fn rejected(task: &BatchTask, reason: &str) -> Self {
+        Self {
+            id: task.id.clone(),
+            status: BatchStatus::Failed,
+            answer: None,
+            error: Some(format!("rejected: {}", reason)),
+            run_id: None,
+            tools: Vec::new(),
+            iterations: 0,
+            tokens: 0,
+            wall_seconds: 0.0,
+            transcript: None,
+        }
+    }
+}
+
+/// Ids of the tasks a results file records as completed; unreadable lines
+/// (such as one cut short by an interrupted batch) are skipped
+pub fn completed_tasks(results_jsonl: &str) -> BTreeSet<String> {
+    results_jsonl
+        .lines()
+        .filter_map(|line| serde_json::from_str::<BatchRecord>(line).ok())
+        .filter(|record| record.status == BatchStatus::Completed)
+        .map(|record| record.id)
+        .collect()
+}
+
+/// Totals of a batch
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BatchSummary {
+    pub tasks: usize,
+    pub completed: usize,
+    pub failed: usize,
+    pub total_tokens: usize,
+    pub total_wall_seconds: f64,
+}
+
+impl BatchSummary {
+    pub fn of(records: &[BatchRecord]) -> Self {
+        let completed = records
+            .iter()
+            .filter(|record| record.status == BatchStatus::Completed)
+            .count();
+        Self {
+            tasks: records.len(),
+            completed,
+            failed: records.len() - completed,
+            total_tokens: records.iter().map(|record| record.tokens).sum(),
+            total_wall_seconds: records.iter().map(|record| record.wall_seconds).sum(),
+        }
+    }
+}
+
+/// Which notifications a batch sends
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotifyOn {
+    /// One when the batch finishes
+    #[default]
+    Finish,
+    /// One for each task as it fails, and one when a batch with failed tasks
+    /// finishes
+    Failure,
+}
+
+impl NotifyOn {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "finish" => Some(Self::Finish),
+            "failure" => Some(Self::Failure),
+            _ => None,
+        }
+    }
+}
+
+/// What a batch tells webhooks and notification commands
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum BatchNotification {
+    /// A task failed; the batch goes on
+    TaskFailed {
+        /// The task file
+        batch: String,
+        task: String,
+        outcome: RunOutcome,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        run_id: Option<String>,
+    },
+    /// Every task ran
+    BatchFinished {
+        /// The task file
+        batch: String,
+        /// [`RunOutcome::Error`] if any task failed
+        outcome: RunOutcome,
+        summary: BatchSummary,
+        failed_tasks: Vec<String>,
+        /// The results file
+        results: String,
+    },
+}
+
+impl BatchNotification {
+    /// The notification of a failed task (`None` for a completed one)
+    pub fn task_failed(batch: impl Into<String>, record: &BatchRecord) -> Option<Self> {
+        (record.status == BatchStatus::Failed).then(|| Self::TaskFailed {
+            batch: batch.into(),
+            task: record.id.clone(),
+            outcome: RunOutcome::from_failure_code(record.error.as_deref().unwrap_or_default()),
+            error: record.error.clone(),
+            run_id: record.run_id.clone(),
+        })
+    }
+
+    /// The notification of a finished batch
+    pub fn finished(
+        batch: impl Into<String>,
+        records: &[BatchRecord],
+        results: impl Into<String>,
+    ) -> Self {
+        let summary = BatchSummary::of(records);
+        Self::BatchFinished {
+            batch: batch.into(),
+            outcome: match summary.failed {
+                0 => RunOutcome::Answered,
+                _ => RunOutcome::Error,
+            },
+            summary,
+            failed_tasks: records
+                .iter()
+                .filter(|record| record.status == BatchStatus::Failed)
+                .map(|record| record.id.clone())
+                .collect(),
+            results: results.into(),
+        }
+    }
+
+    /// Whether hosts send this notification under `on`
+    pub fn is_sent(&self, on: NotifyOn) -> bool {
+        match (self, on) {
+            (Self::TaskFailed { .. }, NotifyOn::Finish) => false,
+            (Self::TaskFailed { .. }, NotifyOn::Failure) => true,
+            (Self::BatchFinished { .. }, NotifyOn::Finish) => true,
+            (Self::BatchFinished { summary, .. }, NotifyOn::Failure) => summary.failed > 0,
+        }
+    }
+
+    /// The event name, as in the JSON
+    pub fn event(&self) -> &'static str {
+        match self {
+            Self::TaskFailed { .. } => "task_failed",
+            Self::BatchFinished { .. } => "batch_finished",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tasks_defaults_ids_and_skips_blank_lines() {
+        let tasks = parse_tasks(
+            "{\"id\": \"a\", \"query\": \"one\", \"args\": [\"-i\", \"8\"]}\n\n{\"query\": \"two\"}\n",
+        )
+        .unwrap();
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(tasks[0].args, vec!["-i", "8"]);
+        assert_eq!(tasks[1].id, "task-3");
+        assert_eq!(tasks[1].query, "two");
+    }
+
+    #[test]
+    fn test_parse_tasks_rejects_bad_lines() {
+        assert!(matches!(
+            parse_tasks("{\"id\": \"a\"}"),
+            Err(BatchError::Malformed { line: 1, .. })
+        ));
+        assert_eq!(
+            parse_tasks("{\"id\": \"../x\", \"query\": \"q\"}"),
+            Err(BatchError::InvalidId {
+                line: 1,
+                id: "../x".to_string()
+            })
+        );
+        assert_eq!(
+            parse_tasks("{\"id\": \"a\", \"query\": \"q\"}\n{\"id\": \"a\", \"query\": \"q\"}"),
+            Err(BatchError::Duplicate("a".to_string()))
+        );
+        assert_eq!(parse_tasks("\n"), Err(BatchError::Empty));
+    }
+
+    #[test]
+    fn test_records_and_resume() {
+        let task = |id: &str| BatchTask {
+            id: id.to_string(),
+            query: "q".to_string(),
+            args: Vec::new(),
+            overrides: TaskOverrides::default(),
+        };
+        let done = BatchRecord::new(
+            &task("a"),
+            CaseRun {
+                answer: Some("42".to_string()),
+                tokens: 10,
+                ..Default::default()
+            },
+            None,
+        );
+        let failed = BatchRecord::new(&task("b"), CaseRun::default(), None);
+        assert_eq!(done.status, BatchStatus::Completed);
+        assert_eq!(failed.status, BatchStatus::Failed);
+        assert_eq!(failed.error.as_deref(), Some("no final answer"));
+
+        let results = format!(
+            "{}\n{}\n{{\"id\": \"c\", \"sta",
+            serde_json::to_string(&done).unwrap(),
+            serde_json::to_string(&failed).unwrap()
+        );
+        assert_eq!(completed_tasks(&results), BTreeSet::from(["a".to_string()]));
+
+        let summary = BatchSummary::of(&[done, failed]);
+        assert_eq!((summary.completed, summary.failed), (1, 1));
+        assert_eq!(summary.total_tokens, 10);
+    }
+
+    #[test]
+    fn test_overrides_within_bounds() {
+        let tasks = parse_tasks(concat!(
+            "{\"id\": \"a\", \"query\": \"q\", \"max_iterations\": 8, \"tools\": [\"shell\"], \"approve\": [\"shell=auto\"]}\n",
+            "{\"id\": \"b\", \"query\": \"q\", \"max_tokens\": 4096}\n",
+            "{\"id\": \"c\", \"query\": \"q\", \"approve\": [\"network=auto\", \"shell=deny\"]}\n",
+            "{\"id\": \"d\", \"query\": \"q\", \"args\": [\"-i\", \"3\"]}\n",
+            "{\"id\": \"e\", \"query\": \"q\", \"tools\": [\"sql\"], \"approve\": [\"shell=sometimes\"]}\n",
+        ))
+        .unwrap();
+        let [a, b, c, d, e] = tasks.as_slice() else {
+            panic!("{:?}", tasks)
+        };
+
+        // No bounds: anything goes, but rules must parse
+        let free = OverrideBounds::default();
+        assert!(free.check(d).is_ok());
+        assert!(free.check(e).unwrap_err().contains("Invalid approval mode"));
+        assert_eq!(
+            a.run_args(&free),
+            vec![
+                "--max-iterations",
+                "8",
+                "--tools",
+                "shell",
+                "--approve",
+                "shell=auto"
+            ]
+        );
+
+        let bounds = OverrideBounds {
+            max_iterations: Some(10),
+            max_tokens: Some(1024),
+            tools: Some(vec!["shell".to_string(), "search".to_string()]),
+            auto_approve: vec!["shell".to_string(), "read-only".to_string()],
+        };
+        assert_eq!(bounds.check(a), Ok(()));
+        assert_eq!(
+            bounds.check(b),
+            Err("max_tokens 4096 is above the limit of 1024".to_string())
+        );
+        assert!(bounds
+            .check(c)
+            .unwrap_err()
+            .starts_with("approval rule 'network=auto'"));
+        assert!(bounds
+            .check(d)
+            .unwrap_err()
+            .starts_with("args are not allowed"));
+        assert_eq!(
+            b.run_args(&bounds),
+            vec!["--max-tokens", "4096", "--tools", "shell,search"]
+        );
+
+        let rejected = BatchRecord::rejected(b, "max_tokens 4096 is above the limit of 1024");
+        assert_eq!(rejected.status, BatchStatus::Failed);
+        assert_eq!(
+            rejected.error.as_deref(),
+            Some("rejected: max_tokens 4096 is above the limit of 1024")
+        );
+    }
+
+    #[test]
+    fn test_notifications() {
+        let task = |id: &str| BatchTask {
+            id: id.to_string(),
+            query: "q".to_string(),
+            args: Vec::new(),
+            overrides: TaskOverrides::default(),
+        };
+        let done = BatchRecord::new(
+            &task("a"),
+            CaseRun {
+                answer: Some("42".to_string()),
+                ..Default::default()
+            },
+            None,
+        );
+        let failed = BatchRecord::new(
+            &task("b"),
+            CaseRun {
+                error: Some("max_iterations".to_string()),
+                ..Default::default()
+            },
+            None,
+        );
+        assert_eq!(BatchNotification::task_failed("tasks.jsonl", &done), None);
+        let task_failed = BatchNotification::task_failed("tasks.jsonl", &failed).unwrap();
+        assert_eq!(
+            serde_json::to_value(&task_failed).unwrap(),
+            serde_json::json!({
+                "event": "task_failed",
+                "batch": "tasks.jsonl",
+                "task": "b",
+                "outcome": "max_iterations",
+                "error": "max_iterations"
+            })
+        );
+        assert!(!task_failed.is_sent(NotifyOn::Finish));
+        assert!(task_failed.is_sent(NotifyOn::Failure));
+
+        let finished = BatchNotification::finished(
+            "tasks.jsonl",
+            &[done.clone(), failed],
+            "tasks.results.jsonl",
+        );
+        assert!(matches!(
+            &finished,
+            BatchNotification::BatchFinished { outcome: RunOutcome::Error, failed_tasks, .. }
+                if failed_tasks == &["b".to_string()]
+        ));
+        assert_eq!(finished.event(), "batch_finished");
+        assert!(finished.is_sent(NotifyOn::Failure));
+
+        let clean = BatchNotification::finished("tasks.jsonl", &[done], "tasks.results.jsonl");
+        assert!(clean.is_sent(NotifyOn::Finish));
+        assert_eq!(NotifyOn::from_str("Failure"), Some(NotifyOn::Failure));
+        assert!(!clean.is_sent(NotifyOn::Failure));
+    }
+}
@@ -0,0 +1,378 @@
+//! What the host running an agent can do
+//!
+//! An [`AgentState`](crate::agent::AgentState) can move between hosts: a run
+//! started by the native CLI, with a shell and skills, may continue in a
+//! browser that only has a virtual filesystem. [`HostCapabilities`] describe
+//! the current host, and both sides of the protocol consult them:
+//!
+//! - [`turn_instructions`](crate::session::turn_instructions) tells the model
+//!   what this host offers ([`HostCapabilities::instructions`])
+//! - [`process_model_output`](crate::agent::process_model_output) refuses calls
+//!   the host cannot run ([`HostCapabilities::check`]) and sends the reason
+//!   back to the model instead of handing the host a call it would fail,
+//!   with the calls it can make instead ([`HostCapabilities::offered_calls`])
+//!
+//! Models often guess a plausible name (`bash`, `calculator`): the agent
+//! calls the tool it stands for when the host's [`ToolAliases`] accept it
+//! ([`HostCapabilities::resolve_tool`]), and the refusal suggests it otherwise. A refused call gets one retry, like
+//! inconclusive output: hosts end the run after [`MAX_REFUSALS`] in a row.
+//!
+//! Capabilities belong to the host, not the run, so they are not saved with
+//! the state: a host sets its own after loading one. The default allows
+//! everything.
+
+use crate::prelude::*;
+use crate::protocol::ParseResult;
+use crate::skill::{is_valid_skill, AVAILABLE_SKILLS};
+use crate::tool_manifest::{ToolAliases, ToolRegistry};
+use alloc::collections::{BTreeMap, BTreeSet};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// The tool that asks the user a question (a host tool, see the web example)
+pub const ASK_USER_TOOL: &str = "ask_user";
+
+/// Refused replies in a row that end a run
+pub const MAX_REFUSALS: usize = 2;
+
+/// What the current host can run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HostCapabilities {
+    /// Tools the host runs; `None` accepts any tool
+    pub tools: Option<BTreeSet<String>>,
+    /// Whether the host runs skills
+    pub skills: bool,
+    /// Whether someone is there to answer `ask_user`
+    pub ask_user: bool,
+    /// Whether the host keeps a memory to `recall` from
+    pub memory: bool,
+    /// Calls the host runs from one response. The agent issues one call per
+    /// response; a reply listing more calls than this is refused.
+    pub max_parallel_calls: usize,
+    /// Parameter schemas of the tools, by name, shown with a refused call
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub tool_schemas: BTreeMap<String, Value>,
+    /// How wrong tool names map to the tools above
+    pub tool_aliases: ToolAliases,
+}
+
+impl Default for HostCapabilities {
+    fn default() -> Self {
+        Self {
+            tools: None,
+            skills: true,
+            ask_user: true,
+            memory: true,
+            max_parallel_calls: 1,
+            tool_schemas: BTreeMap::new(),
+            tool_aliases: ToolAliases::default(),
+        }
+    }
+}
+
+impl HostCapabilities {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only these tools run on the host
+    pub fn with_tools<I, S>(mut self, tools: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.tools = Some(tools.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Only the registry's tools run on the host, under its aliases; their
+    /// schemas are shown with refused calls
+    pub fn with_registry(mut self, registry: &ToolRegistry) -> Self {
+        self.tool_schemas = registry
+            .tools()
+            .map(|tool| (tool.frontmatter.name.clone(), tool.input_schema()))
+            .collect();
+        self.tool_aliases = registry.aliases().clone();
+        self.with_tools(registry.tools().map(|tool| tool.frontmatter.name.clone()))
+    }
+
+    pub fn with_skills(mut self, skills: bool) -> Self {
+        self.skills = skills;
+        self
+    }
+
+    pub fn with_ask_user(mut self, ask_user: bool) -> Self {
+        self.ask_user = ask_user;
+        self
+    }
+
+    pub fn with_memory(mut self, memory: bool) -> Self {
+        self.memory = memory;
+        self
+    }
+
+    pub fn with_max_parallel_calls(mut self, calls: usize) -> Self {
+        self.max_parallel_calls = calls.max(1);
+        self
+    }
+
+    /// Whether the host runs `tool`
+    pub fn allows_tool(&self, tool: &str) -> bool {
+        if tool == ASK_USER_TOOL && !self.ask_user {
+            return false;
+        }
+        self.tools.as_ref().is_none_or(|tools| tools.contains(tool))
+    }
+
+    /// Whether every action is allowed (nothing to tell the model)
+    pub fn is_unrestricted(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Why the host cannot act on `parsed`, if it cannot
+    pub fn check(&self, parsed: &ParseResult) -> Result<(), String> {
+        match parsed {
+            ParseResult::ToolCall(request) if !self.allows_tool(&request.tool) => {
+                let mut reason = match self.offered_tools() {
+                    Some(offered) => format!(
+                        "tool '{}' is not available on this host (available: {})",
+                        request.tool, offered
+                    ),
+                    None => format!("tool '{}' is not available on this host", request.tool),
+                };
+                let meant = self
+                    .offered_names()
+                    .and_then(|offered| self.tool_aliases.suggest(&request.tool, &offered));
+                if let Some(meant) = meant {
+                    reason.push_str(&format!("; the closest is '{}'", meant));
+                }
+                Err(reason)
+            }
+            ParseResult::SkillCall(request) if !self.skills => Err(format!(
+                "skill '{}' is not available: this host runs no skills",
+                request.skill
+            )),
+            ParseResult::Recall(_) if !self.memory => {
+                Err("recall is not available: this host keeps no memory".to_string())
+            }
+            ParseResult::SkillCall(request) if !is_valid_skill(&request.skill) => {
+                Err(format!("skill '{}' does not exist", request.skill))
+            }
+            ParseResult::FinalAnswer(text) | ParseResult::Inconclusive(text, _) => {
+                match count_listed_calls(text) {
+                    Some(calls) if calls > self.max_parallel_calls => Err(format!(
+                        "{} calls in one response; this host runs at most {} per response",
+                        calls, self.max_parallel_calls
+                    )),
+                    _ => Ok(()),
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The calls the host offers instead of a refused `parsed`, one per line
+    /// with its parameters
+    pub fn offered_calls(&self, parsed: &ParseResult) -> Option<String> {
+        match parsed {
+            ParseResult::ToolCall(_) if !self.tool_schemas.is_empty() => {
+                let mut out = String::from("Available tools:");
+                for (name, schema) in &self.tool_schemas {
+                    if self.allows_tool(name) {
+                        out.push_str(&format!("\n- {}", call_template(name, schema)));
+                    }
+                }
+                Some(out)
+            }
+            ParseResult::SkillCall(_) if self.skills => {
+                let mut out = String::from("Available skills:");
+                for skill in AVAILABLE_SKILLS {
+                    out.push_str(&format!("\n- {}", skill.call));
+                }
+                Some(out)
+            }
+            _ => None,
+        }
+    }
+
+    /// The offered tool a refused `name` stands for (see [`ToolAliases`])
+    pub fn resolve_tool(&self, name: &str) -> Option<String> {
+        if self.allows_tool(name) {
+            return None;
+        }
+        let offered = self.offered_names()?;
+        self.tool_aliases.resolve(name, &offered).map(String::from)
+    }
+
+    /// Tool names the host runs (`None` if any tool is allowed)
+    fn offered_names(&self) -> Option<Vec<&str>> {
+        let tools = self.tools.as_ref()?;
+        Some(
+            tools
+                .iter()
+                .map(String::as_str)
+                .filter(|tool| self.allows_tool(tool))
+                .collect(),
+        )
+    }
+
+    /// What the model should know about the host (empty when unrestricted)
+    pub fn instructions(&self) -> String {
+        if self.is_unrestricted() {
+            return String::new();
+        }
+        let mut lines = vec!["HOST CAPABILITIES:".to_string()];
+        if let Some(offered) = self.offered_tools() {
+            lines.push(format!(
+                "- Only these tools are available: {}. Other tools will be refused.",
+                offered
+            ));
+        }
+        if !self.skills {
+            lines.push("- Skills are not available on this host.".to_string());
+        }
+        if !self.ask_user {
+            lines.push("- Nobody can answer questions; do not ask the user.".to_string());
+        }
+        if !self.memory {
+            lines.push("- There is no memory of earlier runs to recall.".to_string());
+        }
+        if self.max_parallel_calls == 1 {
+            lines.push("- Make one call per response.".to_string());
+        } else {
+            lines.push(format!(
+                "- Make at most {} calls per response.",
+                self.max_parallel_calls
+            ));
+        }
+        lines.join("\n")
+    }
+
+    /// The allowed tools, comma-separated (`None` if any tool is allowed)
+    fn offered_tools(&self) -> Option<String> {
+        let offered = self.offered_names()?;
+        Some(match offered.is_empty() {
+            true => "none".to_string(),
+            false => offered.join(", "),
+        })
+    }
+}
+
+/// A call of the tool as the model writes it, e.g.
+/// `{"tool": "shell", "command": <string>, "timeout": <integer, optional>}`
+fn call_template(name: &str, schema: &Value) -> String {
+    let required: Vec<&str> = schema["required"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .collect();
+    let mut call = format!("{{\"tool\": \"{}\"", name);
+    for (param, spec) in schema["properties"].as_object().into_iter().flatten() {
+        let kind = spec["type"].as_str().unwrap_or("value");
+        let optional = if required.contains(&param.as_str()) {
+            ""
+        } else {
+            ", optional"
+        };
+        call.push_str(&format!(", \"{}\": <{}{}>", param, kind, optional));
+    }
+    call.push('}');
+    call
+}
+
+/// Number of calls in a reply that lists several as a JSON array
+fn count_listed_calls(text: &str) -> Option<usize> {
+    let serde_json::Value::Array(items) = serde_json::from_str(text.trim()).ok()? else {
+        return None;
+    };
+    let is_call =
+        |item: &serde_json::Value| item.get("tool").is_some() || item.get("skill").is_some();
+    (!items.is_empty() && items.iter().all(is_call)).then_some(items.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::parse_model_output;
+
+    fn browser() -> HostCapabilities {
+        HostCapabilities::new()
+            .with_tools(["fs_read", "eval_math", "ask_user"])
+            .with_skills(false)
+            .with_ask_user(false)
+            .with_memory(false)
+    }
+
+    #[test]
+    fn test_check_refuses_unsupported_calls() {
+        let caps = browser();
+        let refused = caps
+            .check(&parse_model_output(r#"{"tool": "shell", "command": "ls"}"#))
+            .unwrap_err();
+        assert!(refused.contains("'shell'"));
+        assert!(refused.contains("available: eval_math, fs_read"));
+        assert!(caps
+            .check(&parse_model_output(
+                r#"{"tool": "ask_user", "question": "?"}"#
+            ))
+            .is_err());
+        assert!(caps
+            .check(&parse_model_output(
+                r#"{"skill": "extract", "text": "a", "target": "email"}"#
+            ))
+            .is_err());
+        assert!(caps
+            .check(&parse_model_output(r#"{"recall": "last week's report"}"#))
+            .is_err());
+        assert!(caps
+            .check(&parse_model_output(r#"{"tool": "fs_read", "path": "/a"}"#))
+            .is_ok());
+
+        let listed = r#"[{"tool": "fs_read", "path": "/a"}, {"tool": "fs_read", "path": "/b"}]"#;
+        assert!(caps.check(&parse_model_output(listed)).is_err());
+        assert!(HostCapabilities::default()
+            .check(&parse_model_output(r#"{"tool": "shell", "command": "ls"}"#))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_refusals_offer_known_calls() {
+        let caps = HostCapabilities::new().with_registry(&ToolRegistry::with_default_tools());
+        let guess = parse_model_output(r#"{"tool": "calculator", "expression": "2+2"}"#);
+        assert!(caps
+            .check(&guess)
+            .unwrap_err()
+            .ends_with("; the closest is 'eval_math'"));
+        let offered = caps.offered_calls(&guess).unwrap();
+        assert!(offered.starts_with("Available tools:\n- {\"tool\": \"eval_math\", "));
+        assert!(offered.contains("\"expression\": <string>"));
+
+        // Typos are matched by spelling, unrelated names are not
+        let typo = parse_model_output(r#"{"tool": "eval_mat", "expression": "1"}"#);
+        assert!(caps.check(&typo).unwrap_err().ends_with("'eval_math'"));
+        let other = parse_model_output(r#"{"tool": "weather", "city": "Oslo"}"#);
+        assert!(!caps.check(&other).unwrap_err().contains("closest"));
+
+        let skill = parse_model_output(r#"{"skill": "summarize", "text": "a"}"#);
+        assert_eq!(
+            caps.check(&skill).unwrap_err(),
+            "skill 'summarize' does not exist"
+        );
+        assert!(caps
+            .offered_calls(&skill)
+            .unwrap()
+            .contains(r#"{"skill": "extract""#));
+    }
+
+    #[test]
+    fn test_instructions() {
+        assert_eq!(HostCapabilities::default().instructions(), "");
+        let text = browser().instructions();
+        assert!(text.contains("Only these tools are available: eval_math, fs_read."));
+        assert!(text.contains("Skills are not available"));
+        assert!(text.contains("do not ask the user"));
+        assert!(text.contains("no memory of earlier runs"));
+    }
+}
@@ -0,0 +1,326 @@
+//! Fault injection for robustness testing
+//!
+//! A chaos run injects failures the agent has to get past: backend timeouts,
+//! malformed JSON and truncated text in model output, and empty or truncated
+//! tool output. Each one is drawn with the probability in the run's
+//! [`ChaosProfile`], from a seeded generator, so a failing run can be repeated
+//! with the same seed.
+//!
+//! [`ChaosInjector`] decides and corrupts; the host calls it where model output
+//! and tool results pass through, and reports progress so the [`ChaosReport`]
+//! can tell which faults the run recovered from:
+//!
+//! - a model fault is recovered when a later model reply is understood (a call
+//!   or an answer)
+//! - any fault is recovered when the run ends with an answer
+
+use crate::prelude::*;
+use crate::tool::ToolResult;
+use serde::Serialize;
+
+/// Probability of each fault in the `default` profile
+pub const DEFAULT_FAULT_PROBABILITY: f64 = 0.1;
+
+/// A failure the injector can cause
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Fault {
+    /// The model call fails as if the backend timed out
+    BackendTimeout,
+    /// A JSON object in the model's reply loses its closing brace
+    MalformedJson,
+    /// A successful tool call returns no output
+    EmptyToolOutput,
+    /// Model or tool output is cut in half
+    TruncatedOutput,
+}
+
+impl Fault {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::BackendTimeout => "backend_timeout",
+            Self::MalformedJson => "malformed_json",
+            Self::EmptyToolOutput => "empty_tool_output",
+            Self::TruncatedOutput => "truncated_output",
+        }
+    }
+}
+
+/// Where a fault was injected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FaultSite {
+    Model,
+    Tool,
+}
+
+/// Fault probabilities (0 to 1) and the seed they are drawn with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChaosProfile {
+    pub seed: u64,
+    pub backend_timeout: f64,
+    pub malformed_json: f64,
+    pub empty_tool_output: f64,
+    pub truncated_output: f64,
+}
+
+impl ChaosProfile {
+    /// Every fault at `probability`
+    pub fn uniform(seed: u64, probability: f64) -> Self {
+        Self {
+            seed,
+            backend_timeout: probability,
+            malformed_json: probability,
+            empty_tool_output: probability,
+            truncated_output: probability,
+        }
+    }
+
+    /// Parse `default` or a list like `timeout=0.05,malformed=0.3`
+    ///
+    /// Keys are `timeout`, `malformed`, `empty` and `truncate`; faults not
+    /// listed are off.
+    pub fn parse(spec: &str, seed: u64) -> Result<Self, String> {
+        let spec = spec.trim();
+        if spec.eq_ignore_ascii_case("default") {
+            return Ok(Self::uniform(seed, DEFAULT_FAULT_PROBABILITY));
+        }
+        let mut profile = Self::uniform(seed, 0.0);
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (key, value) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("Expected fault=probability, got '{}'", entry))?;
+            let probability = value
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|p| (0.0..=1.0).contains(p))
+                .ok_or_else(|| format!("Invalid probability '{}' (0 to 1)", value.trim()))?;
+            let slot = match key.trim() {
+                "timeout" => &mut profile.backend_timeout,
+                "malformed" => &mut profile.malformed_json,
+                "empty" => &mut profile.empty_tool_output,
+                "truncate" => &mut profile.truncated_output,
+                other => {
+                    return Err(format!(
+                        "Unknown fault '{}' (timeout, malformed, empty, truncate)",
+                        other
+                    ))
+                }
+            };
+            *slot = probability;
+        }
+        Ok(profile)
+    }
+}
+
+/// A fault the injector caused
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InjectedFault {
+    pub fault: Fault,
+    pub site: FaultSite,
+    /// The model call or tool call it hit, counting from 1
+    pub call: usize,
+    pub recovered: bool,
+}
+
+/// The faults of a run and whether it got past them
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChaosReport {
+    pub seed: u64,
+    pub faults: Vec<InjectedFault>,
+}
+
+impl ChaosReport {
+    pub fn recovered(&self) -> usize {
+        self.faults.iter().filter(|fault| fault.recovered).count()
+    }
+}
+
+/// Draws and applies faults for one run
+#[derive(Debug, Clone)]
+pub struct ChaosInjector {
+    profile: ChaosProfile,
+    state: u64,
+    model_calls: usize,
+    tool_calls: usize,
+    faults: Vec<InjectedFault>,
+}
+
+impl ChaosInjector {
+    pub fn new(profile: ChaosProfile) -> Self {
+        Self {
+            profile,
+            state: profile.seed,
+            model_calls: 0,
+            tool_calls: 0,
+            faults: Vec::new(),
+        }
+    }
+
+    /// Whether the next model call fails with [`Fault::BackendTimeout`]
+    pub fn fail_model_call(&mut self) -> bool {
+        self.model_calls += 1;
+        let fails = self.draw(self.profile.backend_timeout);
+        if fails {
+            self.record(Fault::BackendTimeout, FaultSite::Model);
+        }
+        fails
+    }
+
+    /// The model's reply, possibly corrupted
+    pub fn model_output(&mut self, text: &str) -> String {
+        if self.draw(self.profile.malformed_json) {
+            if let Some(end) = text.rfind('}') {
+                self.record(Fault::MalformedJson, FaultSite::Model);
+                return text[..end].to_string();
+            }
+        }
+        if self.draw(self.profile.truncated_output) && !text.is_empty() {
+            self.record(Fault::TruncatedOutput, FaultSite::Model);
+            return truncated(text);
+        }
+        text.to_string()
+    }
+
+    /// A tool's result, possibly emptied or truncated; failures pass unchanged
+    pub fn tool_result(&mut self, result: ToolResult) -> ToolResult {
+        self.tool_calls += 1;
+        if !result.success || result.output.is_empty() {
+            return result;
+        }
+        if self.draw(self.profile.empty_tool_output) {
+            self.record(Fault::EmptyToolOutput, FaultSite::Tool);
+            return ToolResult {
+                output: String::new(),
+                ..result
+            };
+        }
+        if self.draw(self.profile.truncated_output) {
+            self.record(Fault::TruncatedOutput, FaultSite::Tool);
+            let output = truncated(&result.output);
+            return ToolResult { output, ..result };
+        }
+        result
+    }
+
+    /// A model reply was understood: faults in earlier replies are recovered
+    pub fn model_progress(&mut self) {
+        let current = self.model_calls;
+        for fault in &mut self.faults {
+            if fault.site == FaultSite::Model && fault.call < current {
+                fault.recovered = true;
+            }
+        }
+    }
+
+    /// The run answered: every fault is recovered but one in the answer itself
+    pub fn answered(&mut self) {
+        self.model_progress();
+        for fault in &mut self.faults {
+            if fault.site == FaultSite::Tool {
+                fault.recovered = true;
+            }
+        }
+    }
+
+    pub fn report(&self) -> ChaosReport {
+        ChaosReport {
+            seed: self.profile.seed,
+            faults: self.faults.clone(),
+        }
+    }
+
+    fn record(&mut self, fault: Fault, site: FaultSite) {
+        let call = match site {
+            FaultSite::Model => self.model_calls,
+            FaultSite::Tool => self.tool_calls,
+        };
+        self.faults.push(InjectedFault {
+            fault,
+            site,
+            call,
+            recovered: false,
+        });
+    }
+
+    /// True with probability `p` (splitmix64)
+    fn draw(&mut self, p: f64) -> bool {
+        if p <= 0.0 {
+            return false;
+        }
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        ((z >> 11) as f64 / (1u64 << 53) as f64) < p
+    }
+}
+
+/// The first half of `text`, cut on a character boundary
+fn truncated(text: &str) -> String {
+    let half = text.chars().count() / 2;
+    text.chars().take(half).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_profile() {
+        let profile = ChaosProfile::parse("timeout=0.05, malformed=0.3", 7).unwrap();
+        assert_eq!(profile.backend_timeout, 0.05);
+        assert_eq!(profile.malformed_json, 0.3);
+        assert_eq!(profile.empty_tool_output, 0.0);
+        assert_eq!(
+            ChaosProfile::parse("default", 7).unwrap(),
+            ChaosProfile::uniform(7, DEFAULT_FAULT_PROBABILITY)
+        );
+        assert!(ChaosProfile::parse("timeout=2", 7).is_err());
+        assert!(ChaosProfile::parse("latency=0.1", 7).is_err());
+    }
+
+    #[test]
+    fn test_same_seed_same_faults() {
+        let run = |seed| {
+            let mut chaos = ChaosInjector::new(ChaosProfile::uniform(seed, 0.5));
+            for _ in 0..20 {
+                chaos.fail_model_call();
+                chaos.model_output(r#"{"tool": "shell", "command": "ls"}"#);
+                chaos.tool_result(ToolResult::success("a.txt\nb.txt"));
+            }
+            chaos.report()
+        };
+        assert_eq!(run(42), run(42));
+        assert_ne!(run(42), run(43));
+        assert!(!run(42).faults.is_empty());
+    }
+
+    #[test]
+    fn test_recovery() {
+        let mut chaos = ChaosInjector::new(ChaosProfile::parse("malformed=1,empty=1", 1).unwrap());
+        assert!(!chaos.fail_model_call());
+        assert_eq!(
+            chaos.model_output(r#"{"tool": "shell", "command": "ls"}"#),
+            r#"{"tool": "shell", "command": "ls""#
+        );
+        assert_eq!(chaos.tool_result(ToolResult::success("a.txt")).output, "");
+        assert!(!chaos.tool_result(ToolResult::failure("boom")).success);
+
+        // The reply with the fault does not recover from it
+        chaos.model_progress();
+        assert_eq!(chaos.report().recovered(), 0);
+
+        assert!(!chaos.fail_model_call());
+        chaos.model_progress();
+        let report = chaos.report();
+        assert_eq!(report.faults.len(), 2);
+        assert_eq!(report.recovered(), 1);
+        assert_eq!(report.faults[1].call, 1);
+
+        chaos.answered();
+        assert_eq!(chaos.report().recovered(), 2);
+    }
+}
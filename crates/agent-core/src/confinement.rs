@@ -0,0 +1,165 @@
+//! OS confinement policy for tool subprocesses
+//!
+//! Next to the approval policy, hosts decide how tightly the operating system
+//! confines the processes a tool starts (the shell tool's commands). Like
+//! approvals, the level keys off the tool's [`SafetyClass`], with optional
+//! per-tool overrides. The default confines nothing.
+//!
+//! This module only decides the level; hosts enforce it with what the platform
+//! offers and report when they cannot.
+
+use crate::prelude::*;
+use crate::tool_manifest::SafetyClass;
+use alloc::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+
+/// How tightly a tool's subprocesses are confined, from loosest to strictest
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfinementLevel {
+    /// No restrictions beyond the user's own
+    #[default]
+    None,
+    /// Writes only inside the workspace and the temp directory
+    Workspace,
+    /// As `Workspace`, and no network access
+    Offline,
+}
+
+impl ConfinementLevel {
+    /// Parse a confinement level from string
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "none" => Some(Self::None),
+            "workspace" => Some(Self::Workspace),
+            "offline" => Some(Self::Offline),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Workspace => "workspace",
+            Self::Offline => "offline",
+        }
+    }
+
+    /// Whether filesystem writes are limited to the workspace
+    pub fn restricts_filesystem(&self) -> bool {
+        *self >= Self::Workspace
+    }
+
+    /// Whether network access is blocked
+    pub fn restricts_network(&self) -> bool {
+        *self == Self::Offline
+    }
+}
+
+/// Confinement levels per safety class, with per-tool overrides
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfinementPolicy {
+    classes: BTreeMap<SafetyClass, ConfinementLevel>,
+    tools: BTreeMap<String, ConfinementLevel>,
+}
+
+impl ConfinementPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the level for every tool of a safety class
+    pub fn with_class(mut self, class: SafetyClass, level: ConfinementLevel) -> Self {
+        self.classes.insert(class, level);
+        self
+    }
+
+    /// Set the level for a single tool, overriding its class
+    pub fn with_tool(mut self, tool: impl Into<String>, level: ConfinementLevel) -> Self {
+        self.tools.insert(tool.into(), level);
+        self
+    }
+
+    /// Apply a `target=level` rule, where target is a safety class or a tool
+    /// name (e.g. `destructive=offline`, `shell=workspace`)
+    pub fn with_rule(self, rule: &str) -> Result<Self, String> {
+        let (target, level) = rule
+            .split_once('=')
+            .ok_or_else(|| format!("Invalid confinement rule '{}'. Expected target=level", rule))?;
+        let target = target.trim();
+        let level = ConfinementLevel::from_str(level.trim()).ok_or_else(|| {
+            format!(
+                "Invalid confinement level '{}'. Expected one of: none, workspace, offline",
+                level.trim()
+            )
+        })?;
+
+        if target.is_empty() {
+            return Err(format!("Invalid confinement rule '{}': empty target", rule));
+        }
+
+        Ok(match SafetyClass::from_str(target) {
+            Some(class) => self.with_class(class, level),
+            None => self.with_tool(target, level),
+        })
+    }
+
+    /// The level for `tool` with the given safety class
+    pub fn decide(&self, tool: &str, class: SafetyClass) -> ConfinementLevel {
+        self.tools
+            .get(tool)
+            .or_else(|| self.classes.get(&class))
+            .copied()
+            .unwrap_or(ConfinementLevel::None)
+    }
+
+    /// The strictest level any rule asks for, so hosts can report up front
+    /// what the platform cannot enforce
+    pub fn strictest(&self) -> ConfinementLevel {
+        self.classes
+            .values()
+            .chain(self.tools.values())
+            .copied()
+            .max()
+            .unwrap_or(ConfinementLevel::None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_confines_nothing() {
+        let policy = ConfinementPolicy::default();
+        assert_eq!(
+            policy.decide("shell", SafetyClass::Destructive),
+            ConfinementLevel::None
+        );
+        assert_eq!(policy.strictest(), ConfinementLevel::None);
+    }
+
+    #[test]
+    fn test_rules_and_overrides() {
+        let policy = ConfinementPolicy::new()
+            .with_rule("destructive=offline")
+            .unwrap()
+            .with_rule("shell=workspace")
+            .unwrap();
+        assert_eq!(
+            policy.decide("shell", SafetyClass::Destructive),
+            ConfinementLevel::Workspace
+        );
+        assert_eq!(
+            policy.decide("rm", SafetyClass::Destructive),
+            ConfinementLevel::Offline
+        );
+        assert_eq!(policy.strictest(), ConfinementLevel::Offline);
+        assert!(ConfinementLevel::Offline.restricts_filesystem());
+        assert!(!ConfinementLevel::Workspace.restricts_network());
+
+        assert!(ConfinementPolicy::new().with_rule("shell").is_err());
+        assert!(ConfinementPolicy::new().with_rule("shell=jail").is_err());
+        assert!(ConfinementPolicy::new().with_rule("=offline").is_err());
+    }
+}
@@ -0,0 +1,213 @@
+//! Critic pass over final answers
+//!
+//! Before an answer reaches the user, a second prompt (to the same model or
+//! another one) reviews it against the run's transcript. The critic replies
+//! `APPROVE`, or `REVISE: <what to change>`; a revision rejects the answer, so
+//! the instruction goes back to the model for one more iteration.
+//!
+//! [`CriticGuard`] is an [`AnswerGuardrail`]: hosts add it to the answer chain
+//! and supply a [`Reviewer`] that runs the critic prompt on their backend.
+//! Revisions are counted in the state's guardrail rejections, so a run asks
+//! for at most [`CriticGuard::with_max_revisions`] of them and then accepts.
+
+use crate::agent::AgentState;
+use crate::guardrail::{AnswerContext, AnswerGuardrail, GuardrailResult};
+use crate::prelude::*;
+use crate::transcript::ChatTranscript;
+use alloc::sync::Arc;
+
+/// Prefix of the rejection reasons the critic records
+pub const CRITIC_REJECTION: &str = "the critic asked for a revision";
+
+/// Runs a critic prompt and returns the critic's reply
+///
+/// An error (the backend failed) approves the answer: a critic that cannot be
+/// reached should not hold the run back.
+pub trait Reviewer: Send + Sync {
+    fn review(&self, prompt: &str) -> Result<String, String>;
+}
+
+impl<F> Reviewer for F
+where
+    F: Fn(&str) -> Result<String, String> + Send + Sync,
+{
+    fn review(&self, prompt: &str) -> Result<String, String> {
+        self(prompt)
+    }
+}
+
+/// What the critic made of an answer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CriticVerdict {
+    Approve,
+    /// Change the answer as instructed
+    Revise(String),
+}
+
+impl CriticVerdict {
+    /// Read a critic reply
+    ///
+    /// Only a line starting with `REVISE:` (any case, optionally in bold)
+    /// asks for a revision; anything else approves.
+    pub fn parse(reply: &str) -> Self {
+        let mut lines = reply.lines().map(|line| line.trim().trim_matches('*'));
+        while let Some(line) = lines.next() {
+            let Some(prefix) = line.get(..7) else {
+                continue;
+            };
+            if !prefix.eq_ignore_ascii_case("revise:") {
+                continue;
+            }
+            let mut instruction = line[7..].trim_start_matches('*').trim().to_string();
+            for rest in lines.by_ref() {
+                if !rest.is_empty() {
+                    if !instruction.is_empty() {
+                        instruction.push(' ');
+                    }
+                    instruction.push_str(rest);
+                }
+            }
+            if !instruction.is_empty() {
+                return Self::Revise(instruction);
+            }
+        }
+        Self::Approve
+    }
+}
+
+/// The prompt asking the critic to review `answer` to the run in `state`
+pub fn critic_prompt(state: &AgentState, answer: &str) -> String {
+    let query = state
+        .history
+        .first()
+        .map(|message| message.content.as_str())
+        .unwrap_or_default();
+    let transcript = ChatTranscript::from_history(&state.history, &[]).to_plain_text();
+    format!(
+        "You review an assistant's final answer before it is returned to the user.\n\n\
+         Question: {}\n\n\
+         Transcript:\n{}\
+         Final answer: {}\n\n\
+         Check that the answer addresses the question and that every fact in it comes \
+         from the transcript. Reply APPROVE if it does. Otherwise reply \
+         REVISE: followed by one sentence saying what to change.",
+        query,
+        transcript,
+        answer.trim()
+    )
+}
+
+/// Answer guardrail that has a critic review each final answer
+#[derive(Clone)]
+pub struct CriticGuard {
+    reviewer: Arc<dyn Reviewer>,
+    max_revisions: usize,
+}
+
+impl CriticGuard {
+    pub fn new(reviewer: impl Reviewer + 'static) -> Self {
+        Self {
+            reviewer: Arc::new(reviewer),
+            max_revisions: 1,
+        }
+    }
+
+    /// Revisions the critic may ask for in one run (default 1)
+    pub fn with_max_revisions(mut self, revisions: usize) -> Self {
+        self.max_revisions = revisions;
+        self
+    }
+
+    /// Revisions the critic asked for so far in `state`'s run
+    pub fn revisions(state: &AgentState) -> usize {
+        state
+            .guardrail_rejections
+            .iter()
+            .filter(|(reason, _)| reason.starts_with(CRITIC_REJECTION))
+            .map(|(_, count)| count)
+            .sum()
+    }
+}
+
+impl AnswerGuardrail for CriticGuard {
+    fn check(&self, context: &AnswerContext) -> GuardrailResult {
+        if Self::revisions(context.state) >= self.max_revisions {
+            return GuardrailResult::Accept;
+        }
+        let prompt = critic_prompt(context.state, context.answer);
+        match self
+            .reviewer
+            .review(&prompt)
+            .map(|reply| CriticVerdict::parse(&reply))
+        {
+            Ok(CriticVerdict::Revise(instruction)) => {
+                GuardrailResult::reject(format!("{}: {}", CRITIC_REJECTION, instruction))
+            }
+            Ok(CriticVerdict::Approve) | Err(_) => GuardrailResult::Accept,
+        }
+    }
+
+    fn name(&self) -> &str {
+        "critic_guard"
+    }
+}
+
+impl core::fmt::Debug for CriticGuard {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("CriticGuard")
+            .field("max_revisions", &self.max_revisions)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::{process_model_output, AgentDecision};
+    use crate::guardrail::AnswerChain;
+
+    #[test]
+    fn test_parse_verdict() {
+        assert_eq!(CriticVerdict::parse("APPROVE"), CriticVerdict::Approve);
+        assert_eq!(
+            CriticVerdict::parse("Looks off.\n**Revise:** Count hidden files\ntoo."),
+            CriticVerdict::Revise("Count hidden files too.".to_string())
+        );
+        assert_eq!(CriticVerdict::parse("REVISE:"), CriticVerdict::Approve);
+        assert_eq!(CriticVerdict::parse("Fine by me"), CriticVerdict::Approve);
+    }
+
+    #[test]
+    fn test_one_revision_per_run() {
+        let chain = AnswerChain::new().add(Box::new(CriticGuard::new(|prompt: &str| {
+            assert!(prompt.contains("Question: How many files are in src/?"));
+            Ok("REVISE: Include hidden files.".to_string())
+        })));
+        let mut state = AgentState::new("How many files are in src/?");
+
+        let AgentDecision::Done(answer) = process_model_output(&mut state, "4 files.") else {
+            panic!("expected a final answer");
+        };
+        let verdict = chain.check(&AnswerContext {
+            state: &state,
+            answer: &answer,
+        });
+        let GuardrailResult::Reject { reason } = verdict else {
+            panic!("expected a revision");
+        };
+        assert_eq!(
+            reason,
+            "the critic asked for a revision: Include hidden files."
+        );
+        state.reject_answer(reason);
+        assert_eq!(CriticGuard::revisions(&state), 1);
+
+        process_model_output(&mut state, "5 files, counting .gitignore.");
+        assert!(chain
+            .check(&AnswerContext {
+                state: &state,
+                answer: "5 files, counting .gitignore.",
+            })
+            .is_accept());
+    }
+}
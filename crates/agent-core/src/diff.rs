@@ -0,0 +1,255 @@
+//! Unified diffs between two versions of a file
+//!
+//! The counterpart of [`crate::patch`]: hosts use it to show what a run
+//! changed, and the output parses back with
+//! [`parse_patch`](crate::patch::parse_patch). Lines are matched exactly; a
+//! change to the final newline shows as `\ No newline at end of file`.
+//!
+//! This module is pure: the host reads both versions and passes them in.
+
+use crate::prelude::*;
+
+/// Unchanged lines shown around each change
+pub const CONTEXT_LINES: usize = 3;
+
+/// Beyond this many line pairs, the changed region of a file is shown as
+/// removed and re-added instead of searched for common lines
+const MAX_COMPARISONS: usize = 4_000_000;
+
+/// A line and whether it is the last one of a file with no final newline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Line<'a> {
+    text: &'a str,
+    no_newline: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// Unified diff of `path` from `old` to `new`
+///
+/// `None` stands for a file that does not exist (`/dev/null`). Returns an
+/// empty string when the versions are the same.
+pub fn unified_diff(path: &str, old: Option<&str>, new: Option<&str>) -> String {
+    let old_lines = split_lines(old.unwrap_or_default());
+    let new_lines = split_lines(new.unwrap_or_default());
+    let ops = diff_ops(&old_lines, &new_lines);
+    if old.is_some() == new.is_some() && ops.iter().all(|op| matches!(op, Op::Equal)) {
+        return String::new();
+    }
+
+    let mut out = format!(
+        "--- {}\n+++ {}\n",
+        old.map_or("/dev/null".to_string(), |_| format!("a/{}", path)),
+        new.map_or("/dev/null".to_string(), |_| format!("b/{}", path)),
+    );
+
+    // Position of each op in the old and new file
+    let mut positions = Vec::with_capacity(ops.len() + 1);
+    let (mut old_pos, mut new_pos) = (0, 0);
+    for op in &ops {
+        positions.push((old_pos, new_pos));
+        match op {
+            Op::Equal => {
+                old_pos += 1;
+                new_pos += 1;
+            }
+            Op::Delete => old_pos += 1,
+            Op::Insert => new_pos += 1,
+        }
+    }
+    positions.push((old_pos, new_pos));
+
+    for (start, end) in hunk_ranges(&ops) {
+        let (old_start, new_start) = positions[start];
+        let (old_end, new_end) = positions[end];
+        out.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            hunk_range(old_start, old_end - old_start),
+            hunk_range(new_start, new_end - new_start)
+        ));
+
+        for (index, op) in ops.iter().enumerate().take(end).skip(start) {
+            let (old_index, new_index) = positions[index];
+            let (prefix, line) = match op {
+                Op::Equal => (' ', new_lines[new_index]),
+                Op::Delete => ('-', old_lines[old_index]),
+                Op::Insert => ('+', new_lines[new_index]),
+            };
+            out.push(prefix);
+            out.push_str(line.text);
+            out.push('\n');
+            if line.no_newline {
+                out.push_str("\\ No newline at end of file\n");
+            }
+        }
+    }
+    out
+}
+
+fn split_lines(content: &str) -> Vec<Line<'_>> {
+    let mut lines: Vec<Line> = content
+        .lines()
+        .map(|text| Line {
+            text,
+            no_newline: false,
+        })
+        .collect();
+    if !content.ends_with('\n') {
+        if let Some(last) = lines.last_mut() {
+            last.no_newline = true;
+        }
+    }
+    lines
+}
+
+/// `start,len` of a hunk header; an empty range names the line it follows
+fn hunk_range(start: usize, len: usize) -> String {
+    if len == 0 {
+        format!("{},0", start)
+    } else {
+        format!("{},{}", start + 1, len)
+    }
+}
+
+/// Edit script turning `old` into `new`
+fn diff_ops(old: &[Line], new: &[Line]) -> Vec<Op> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let a = &old[prefix..old.len() - suffix];
+    let b = &new[prefix..new.len() - suffix];
+
+    let mut ops = vec![Op::Equal; prefix];
+    if a.len().saturating_mul(b.len()) > MAX_COMPARISONS {
+        ops.extend(a.iter().map(|_| Op::Delete));
+        ops.extend(b.iter().map(|_| Op::Insert));
+    } else {
+        ops.extend(middle_ops(a, b));
+    }
+    ops.extend(core::iter::repeat_n(Op::Equal, suffix));
+    ops
+}
+
+/// Edit script through the longest common subsequence of `a` and `b`
+fn middle_ops(a: &[Line], b: &[Line]) -> Vec<Op> {
+    // lcs[i * width + j]: length of the LCS of a[i..] and b[j..]
+    let width = b.len() + 1;
+    let mut lcs = vec![0u32; (a.len() + 1) * width];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i * width + j] = if a[i] == b[j] {
+                lcs[(i + 1) * width + j + 1] + 1
+            } else {
+                lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut ops = Vec::with_capacity(a.len() + b.len());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            ops.push(Op::Equal);
+            i += 1;
+            j += 1;
+        } else if lcs[(i + 1) * width + j] >= lcs[i * width + j + 1] {
+            ops.push(Op::Delete);
+            i += 1;
+        } else {
+            ops.push(Op::Insert);
+            j += 1;
+        }
+    }
+    ops.extend((i..a.len()).map(|_| Op::Delete));
+    ops.extend((j..b.len()).map(|_| Op::Insert));
+    ops
+}
+
+/// Op ranges of the hunks: changes with their context, merged when their
+/// context would overlap
+fn hunk_ranges(ops: &[Op]) -> Vec<(usize, usize)> {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for (index, op) in ops.iter().enumerate() {
+        if matches!(op, Op::Equal) {
+            continue;
+        }
+        let start = index.saturating_sub(CONTEXT_LINES);
+        let end = (index + 1 + CONTEXT_LINES).min(ops.len());
+        match ranges.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patch::parse_patch;
+
+    fn numbered(lines: core::ops::RangeInclusive<usize>) -> String {
+        lines.map(|n| format!("line {}\n", n)).collect()
+    }
+
+    #[test]
+    fn test_diff_applies_back() {
+        let old = numbered(1..=20);
+        let new = old
+            .replace("line 2\n", "line two\n")
+            .replace("line 15\n", "line 15\ninserted\n");
+
+        let diff = unified_diff("notes.txt", Some(&old), Some(&new));
+        assert!(diff.starts_with("--- a/notes.txt\n+++ b/notes.txt\n@@ -1,5 +1,5 @@\n"));
+        assert!(diff.contains("@@ -13,6 +13,7 @@\n"));
+
+        let patch = parse_patch(&diff).unwrap();
+        assert_eq!(patch.files[0].apply(Some(&old)).unwrap(), Some(new));
+        assert_eq!(unified_diff("notes.txt", Some(&old), Some(&old)), "");
+    }
+
+    #[test]
+    fn test_created_and_deleted_files() {
+        let created = unified_diff("new.txt", None, Some("a\nb\n"));
+        assert_eq!(
+            created,
+            "--- /dev/null\n+++ b/new.txt\n@@ -0,0 +1,2 @@\n+a\n+b\n"
+        );
+        let patch = parse_patch(&created).unwrap();
+        assert_eq!(
+            patch.files[0].apply(None).unwrap(),
+            Some("a\nb\n".to_string())
+        );
+
+        let deleted = unified_diff("old.txt", Some("a\n"), None);
+        assert_eq!(
+            deleted,
+            "--- a/old.txt\n+++ /dev/null\n@@ -1,1 +0,0 @@\n-a\n"
+        );
+        assert!(parse_patch(&deleted).unwrap().files[0].is_deletion());
+
+        // An empty file appearing is still a change
+        assert_eq!(
+            unified_diff("empty.txt", None, Some("")),
+            "--- /dev/null\n+++ b/empty.txt\n"
+        );
+    }
+
+    #[test]
+    fn test_final_newline_change() {
+        let diff = unified_diff("a.txt", Some("x\ny"), Some("x\ny\n"));
+        assert_eq!(
+            diff,
+            "--- a/a.txt\n+++ b/a.txt\n@@ -1,2 +1,2 @@\n x\n-y\n\\ No newline at end of file\n+y\n"
+        );
+    }
+}
@@ -0,0 +1,226 @@
+//! Comparing text embeddings
+//!
+//! Backends that can embed text turn each text into a vector; texts with
+//! similar meaning get vectors pointing in similar directions. These helpers
+//! compare such vectors, as groundwork for retrieval over the history and
+//! artifacts and for relevance checks. Vectors from different models are not
+//! comparable.
+//!
+//! [`RelevanceGuard`] is such a check: it rejects tool outputs and final
+//! answers whose meaning is too far from the user's query, where a keyword
+//! match would miss paraphrases. Hosts supply an [`Embedder`] that runs on
+//! their backend.
+
+use crate::agent::AgentState;
+use crate::guardrail::{
+    AnswerContext, AnswerGuardrail, GuardrailContext, GuardrailResult, SemanticGuardrail,
+};
+use crate::prelude::*;
+use alloc::sync::Arc;
+
+/// Similarity below which [`RelevanceGuard`] rejects, unless configured
+pub const DEFAULT_RELEVANCE_THRESHOLD: f32 = 0.3;
+
+/// Characters of a text embedded for a relevance check; the start of a long
+/// output says what it is about
+const RELEVANCE_TEXT_CHARS: usize = 2000;
+
+/// Computes one embedding per text
+///
+/// An error (the backend failed or cannot embed) accepts whatever was being
+/// checked: a check that cannot run should not hold the run back.
+pub trait Embedder: Send + Sync {
+    fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, String>;
+}
+
+impl<F> Embedder for F
+where
+    F: Fn(&[String]) -> Result<Vec<Vec<f32>>, String> + Send + Sync,
+{
+    fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, String> {
+        self(texts)
+    }
+}
+
+/// Cosine of the angle between `a` and `b`, from -1 to 1
+///
+/// 0 for vectors of different lengths or with no length, which share nothing.
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let (mut dot, mut norm_a, mut norm_b) = (0.0f64, 0.0f64, 0.0f64);
+    for (x, y) in a.iter().zip(b) {
+        let (x, y) = (f64::from(*x), f64::from(*y));
+        dot += x * y;
+        norm_a += x * x;
+        norm_b += y * y;
+    }
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    (dot / (libm::sqrt(norm_a) * libm::sqrt(norm_b))) as f32
+}
+
+/// Indices of `candidates` with their similarity to `query`, most similar first
+pub fn rank_by_similarity(query: &[f32], candidates: &[Vec<f32>]) -> Vec<(usize, f32)> {
+    let mut ranked: Vec<(usize, f32)> = candidates
+        .iter()
+        .enumerate()
+        .map(|(index, candidate)| (index, cosine_similarity(query, candidate)))
+        .collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+    ranked
+}
+
+/// Rejects tool outputs and final answers unrelated to the query
+///
+/// Both are compared with the user's query by the cosine similarity of their
+/// embeddings. Failed tool calls, binary outputs and empty texts are left to
+/// the other guards. What counts as related depends on the embedding model, so
+/// the threshold is worth tuning on a few runs (`agent embed` shows vectors).
+#[derive(Clone)]
+pub struct RelevanceGuard {
+    embedder: Arc<dyn Embedder>,
+    threshold: f32,
+}
+
+impl RelevanceGuard {
+    pub fn new(embedder: impl Embedder + 'static) -> Self {
+        Self::shared(Arc::new(embedder))
+    }
+
+    /// A guard over an embedder other guards use as well
+    pub fn shared(embedder: Arc<dyn Embedder>) -> Self {
+        Self {
+            embedder,
+            threshold: DEFAULT_RELEVANCE_THRESHOLD,
+        }
+    }
+
+    pub fn with_threshold(mut self, threshold: f32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Reject `text` (a `what`) if it is less similar to the query than the
+    /// threshold
+    fn judge(&self, state: &AgentState, what: &str, text: &str) -> GuardrailResult {
+        let Some(query) = state.history.first().map(|message| message.content.trim()) else {
+            return GuardrailResult::Accept;
+        };
+        let text = text.trim();
+        if query.is_empty() || text.is_empty() {
+            return GuardrailResult::Accept;
+        }
+        let texts = [query, text].map(|text| text.chars().take(RELEVANCE_TEXT_CHARS).collect());
+        let Ok(embeddings) = self.embedder.embed(&texts) else {
+            return GuardrailResult::Accept;
+        };
+        let [query, text] = embeddings.as_slice() else {
+            return GuardrailResult::Accept;
+        };
+        let similarity = cosine_similarity(query, text);
+        if similarity < self.threshold {
+            GuardrailResult::reject(format!(
+                "The {} seems unrelated to the question (similarity {:.2}, below {:.2})",
+                what, similarity, self.threshold
+            ))
+        } else {
+            GuardrailResult::Accept
+        }
+    }
+}
+
+impl core::fmt::Debug for RelevanceGuard {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("RelevanceGuard")
+            .field("threshold", &self.threshold)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SemanticGuardrail for RelevanceGuard {
+    fn validate(&self, context: &GuardrailContext) -> GuardrailResult {
+        if !context.tool_result.success || context.tool_result.content_kind.is_ref() {
+            return GuardrailResult::Accept;
+        }
+        self.judge(context.state, "tool output", &context.tool_result.output)
+    }
+
+    fn name(&self) -> &str {
+        "relevance_guard"
+    }
+}
+
+impl AnswerGuardrail for RelevanceGuard {
+    fn check(&self, context: &AnswerContext) -> GuardrailResult {
+        self.judge(context.state, "answer", context.answer)
+    }
+
+    fn name(&self) -> &str {
+        "relevance_guard"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cosine_similarity() {
+        assert!((cosine_similarity(&[1.0, 2.0], &[2.0, 4.0]) - 1.0).abs() < 1e-6);
+        assert!(cosine_similarity(&[1.0, 0.0], &[0.0, 3.0]).abs() < 1e-6);
+        assert!((cosine_similarity(&[1.0, 0.0], &[-1.0, 0.0]) + 1.0).abs() < 1e-6);
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[1.0]), 0.0);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 1.0]), 0.0);
+    }
+
+    #[test]
+    fn test_rank_by_similarity() {
+        let candidates = vec![vec![0.0, 1.0], vec![1.0, 0.1], vec![-1.0, 0.0]];
+        let ranked = rank_by_similarity(&[1.0, 0.0], &candidates);
+        let order: Vec<usize> = ranked.iter().map(|(index, _)| *index).collect();
+        assert_eq!(order, vec![1, 0, 2]);
+    }
+
+    #[test]
+    fn test_relevance_guard() {
+        // A toy embedding: does the text mention files, and weather?
+        let guard = RelevanceGuard::new(|texts: &[String]| {
+            Ok(texts
+                .iter()
+                .map(|text| {
+                    let has = |word: &str| f32::from(u8::from(text.contains(word)));
+                    vec![has("file"), has("rain"), 0.1]
+                })
+                .collect())
+        })
+        .with_threshold(0.5);
+        let state = AgentState::new("Which files are in /tmp?");
+        let check = |answer: &str| {
+            guard.check(&AnswerContext {
+                state: &state,
+                answer,
+            })
+        };
+
+        assert!(check("Two files: a.txt and b.txt").is_accept());
+        let verdict = check("Expect rain tomorrow");
+        assert!(
+            matches!(&verdict, GuardrailResult::Reject { reason } if reason.contains("similarity 0.01")),
+            "{:?}",
+            verdict
+        );
+        assert!(check("  ").is_accept());
+
+        // An embedder that fails accepts
+        let failing = RelevanceGuard::new(|_: &[String]| Err("no embeddings".to_string()));
+        assert!(failing
+            .check(&AnswerContext {
+                state: &state,
+                answer: "Expect rain tomorrow"
+            })
+            .is_accept());
+    }
+}
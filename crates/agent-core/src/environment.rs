@@ -0,0 +1,264 @@
+//! Facts about the environment a run starts in
+//!
+//! A model's idea of "now" is its training cutoff, and it cannot see the
+//! machine it works on. Hosts gather facts through [`ContextProvider`]s (the
+//! date and time zone, the operating system, the working directory) and
+//! [`environment_prompt`] states them in the system prompt, so "what changed
+//! today" or "next Friday" resolve against the real date.
+//!
+//! The facts are taken once, when the prompt is built: a long run sees the
+//! time it started at.
+
+use crate::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// One fact about the environment, e.g. `Current date: 2026-10-17 (Saturday)`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContextFact {
+    pub label: String,
+    pub value: String,
+}
+
+impl ContextFact {
+    pub fn new(label: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            label: label.into(),
+            value: value.into(),
+        }
+    }
+}
+
+/// A source of environment facts
+///
+/// A provider that cannot tell (no clock, an unreadable directory) returns no
+/// facts rather than failing the run.
+pub trait ContextProvider {
+    /// Name used to pick the provider (e.g. `time`)
+    fn name(&self) -> &str;
+
+    fn facts(&self) -> Vec<ContextFact>;
+}
+
+/// Facts the host states itself
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FixedContext(Vec<ContextFact>);
+
+impl FixedContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_fact(mut self, label: impl Into<String>, value: impl Into<String>) -> Self {
+        self.0.push(ContextFact::new(label, value));
+        self
+    }
+}
+
+impl ContextProvider for FixedContext {
+    fn name(&self) -> &str {
+        "fixed"
+    }
+
+    fn facts(&self) -> Vec<ContextFact> {
+        self.0.clone()
+    }
+}
+
+const WEEKDAYS: [&str; 7] = [
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+];
+
+/// A moment as the local clock shows it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalTime {
+    /// Seconds since the Unix epoch
+    pub unix_seconds: i64,
+    /// Offset of local time from UTC
+    pub utc_offset_seconds: i32,
+    /// Abbreviated zone name (`CEST`), if the host knows it
+    pub zone: Option<String>,
+}
+
+/// The calendar fields of a [`LocalTime`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CalendarTime {
+    pub year: i64,
+    /// 1 to 12
+    pub month: u32,
+    /// 1 to 31
+    pub day: u32,
+    /// 0 (Sunday) to 6 (Saturday)
+    pub weekday: u32,
+    pub hour: u32,
+    pub minute: u32,
+}
+
+impl core::fmt::Display for CalendarTime {
+    /// `2026-10-17 14:03`
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute
+        )
+    }
+}
+
+impl LocalTime {
+    /// Date and time on the local clock
+    pub fn calendar(&self) -> CalendarTime {
+        let local = self.unix_seconds + i64::from(self.utc_offset_seconds);
+        let days = local.div_euclid(86_400);
+        let seconds = local.rem_euclid(86_400);
+        let (year, month, day) = civil_from_days(days);
+        CalendarTime {
+            year,
+            month,
+            day,
+            // The epoch was a Thursday
+            weekday: (days + 4).rem_euclid(7) as u32,
+            hour: (seconds / 3600) as u32,
+            minute: (seconds % 3600 / 60) as u32,
+        }
+    }
+
+    /// Date, time and time zone facts
+    pub fn facts(&self) -> Vec<ContextFact> {
+        let CalendarTime {
+            year,
+            month,
+            day,
+            weekday,
+            hour,
+            minute,
+        } = self.calendar();
+        let weekday = WEEKDAYS[weekday as usize];
+
+        let offset = self.utc_offset_seconds.unsigned_abs() / 60;
+        let sign = if self.utc_offset_seconds < 0 {
+            '-'
+        } else {
+            '+'
+        };
+        let utc = format!("UTC{}{:02}:{:02}", sign, offset / 60, offset % 60);
+        vec![
+            ContextFact::new(
+                "Current date",
+                format!("{:04}-{:02}-{:02} ({})", year, month, day, weekday),
+            ),
+            ContextFact::new("Current time", format!("{:02}:{:02}", hour, minute)),
+            ContextFact::new(
+                "Time zone",
+                match &self.zone {
+                    Some(zone) => format!("{} ({})", zone, utc),
+                    None => utc,
+                },
+            ),
+        ]
+    }
+}
+
+/// Year, month and day of a day count since 1970-01-01 (proleptic Gregorian)
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    // Months counted from March, so leap days end the year
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month as u32, day as u32)
+}
+
+/// The system prompt section stating the providers' facts (empty if they
+/// have none)
+pub fn environment_prompt(providers: &[Box<dyn ContextProvider>]) -> String {
+    let facts: Vec<String> = providers
+        .iter()
+        .flat_map(|provider| provider.facts())
+        .map(|fact| format!("- {}: {}", fact.label, fact.value))
+        .collect();
+    if facts.is_empty() {
+        return String::new();
+    }
+    format!(
+        "ENVIRONMENT (when this run started; use it for \"today\", \"now\" and relative dates):\n{}",
+        facts.join("\n")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(facts: &[ContextFact], label: &str) -> String {
+        facts
+            .iter()
+            .find(|fact| fact.label == label)
+            .map(|fact| fact.value.clone())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_local_time_facts() {
+        // 2026-10-17 12:03 UTC
+        let facts = LocalTime {
+            unix_seconds: 1_792_238_580,
+            utc_offset_seconds: 2 * 3600,
+            zone: Some("CEST".to_string()),
+        }
+        .facts();
+        assert_eq!(value(&facts, "Current date"), "2026-10-17 (Saturday)");
+        assert_eq!(value(&facts, "Current time"), "14:03");
+        assert_eq!(value(&facts, "Time zone"), "CEST (UTC+02:00)");
+        assert_eq!(
+            LocalTime {
+                unix_seconds: 1_792_238_580,
+                utc_offset_seconds: 0,
+                zone: None,
+            }
+            .calendar()
+            .to_string(),
+            "2026-10-17 12:03"
+        );
+
+        // 2024-02-29 23:30 UTC is already March 1st east of it, still
+        // February 29th west of it
+        let leap = |offset: i32| LocalTime {
+            unix_seconds: 1_709_249_400,
+            utc_offset_seconds: offset,
+            zone: None,
+        };
+        assert_eq!(
+            value(&leap(3600).facts(), "Current date"),
+            "2024-03-01 (Friday)"
+        );
+        let west = leap(-(5 * 3600 + 1800)).facts();
+        assert_eq!(value(&west, "Current date"), "2024-02-29 (Thursday)");
+        assert_eq!(value(&west, "Time zone"), "UTC-05:30");
+    }
+
+    #[test]
+    fn test_environment_prompt() {
+        assert!(environment_prompt(&[]).is_empty());
+        let providers: Vec<Box<dyn ContextProvider>> = vec![
+            Box::new(FixedContext::new()),
+            Box::new(FixedContext::new().with_fact("Operating system", "linux (x86_64)")),
+        ];
+        assert_eq!(
+            environment_prompt(&providers),
+            "ENVIRONMENT (when this run started; use it for \"today\", \"now\" and relative dates):\n\
+             - Operating system: linux (x86_64)"
+        );
+    }
+}
@@ -0,0 +1,458 @@
+//! Evaluation suites and model comparison
+//!
+//! A suite is a YAML file of cases (a query and what a correct answer looks
+//! like) and the targets to run them against, each a set of host arguments
+//! naming a backend and model:
+//!
+//! ```yaml
+//! name: shell-basics
+//! targets:
+//!   - name: qwen-7b
+//!     args: [--backend, llama-server, --endpoint, "http://localhost:8080"]
+//!   - name: haiku
+//!     args: [--backend, anthropic, --model-id, claude-3-5-haiku-latest]
+//!     max_concurrent: 2
+//! cases:
+//!   - id: count-crates
+//!     query: How many crates are in the crates/ directory?
+//!     expect:
+//!       contains: ["3"]
+//!       tools: [shell]
+//!   - id: recorded-failure
+//!     fixture: fixtures/case-01
+//!     expect:
+//!       contains: ["42"]
+//! ```
+//!
+//! A case with a `fixture` replays a scenario recorded with `--record` (see
+//! [`crate::scenario`]): tool calls get the recorded results, so it checks the
+//! model against the exact environment of the recorded run. Its query defaults
+//! to the recorded one.
+//!
+//! Hosts run the cases and report each as a [`CaseRun`]; this module scores
+//! them, summarizes them per target and renders the comparison.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// Default time limit for one case, in seconds
+pub const DEFAULT_CASE_TIMEOUT_SECS: u64 = 300;
+
+/// Errors from loading a suite
+#[derive(Debug, Error)]
+pub enum EvalError {
+    #[error("invalid suite: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+    #[error("suite '{0}' has no cases")]
+    NoCases(String),
+    #[error("duplicate {kind} '{name}'")]
+    Duplicate { kind: &'static str, name: String },
+    #[error("case '{0}' needs a query or a fixture")]
+    NoQuery(String),
+}
+
+/// A set of cases and the targets to run them against
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EvalSuite {
+    pub name: String,
+    /// Backends/models to compare; hosts use their defaults when empty
+    #[serde(default)]
+    pub targets: Vec<EvalTarget>,
+    /// Host arguments added to every run (e.g. `--sandbox`)
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Time limit for one case, in seconds
+    #[serde(default = "default_timeout")]
+    pub timeout_secs: u64,
+    pub cases: Vec<EvalCase>,
+}
+
+fn default_timeout() -> u64 {
+    DEFAULT_CASE_TIMEOUT_SECS
+}
+
+/// A backend/model to evaluate
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EvalTarget {
+    pub name: String,
+    /// Host arguments selecting the backend and model
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Runs of this target at once, for APIs with concurrency limits
+    /// (default: as many as the runner allows)
+    #[serde(default)]
+    pub max_concurrent: Option<usize>,
+}
+
+/// One query and what a correct run looks like
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EvalCase {
+    pub id: String,
+    /// The query; defaults to the fixture's
+    #[serde(default)]
+    pub query: Option<String>,
+    /// Recorded scenario directory to replay, relative to the suite file
+    #[serde(default)]
+    pub fixture: Option<PathBuf>,
+    #[serde(default)]
+    pub expect: Expectation,
+}
+
+/// Checks on a run's final answer and tool use
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Expectation {
+    /// Text the answer must contain (case-insensitive)
+    #[serde(default)]
+    pub contains: Vec<String>,
+    /// Text the answer must not contain (case-insensitive)
+    #[serde(default)]
+    pub excludes: Vec<String>,
+    /// Tools the run must have called
+    #[serde(default)]
+    pub tools: Vec<String>,
+}
+
+impl Expectation {
+    /// Why a run with this answer and these tool calls fails, if it does
+    pub fn check(&self, answer: &str, tools: &[String]) -> Option<String> {
+        let lower = answer.to_lowercase();
+        if let Some(missing) = self
+            .contains
+            .iter()
+            .find(|text| !lower.contains(&text.to_lowercase()))
+        {
+            return Some(format!("answer does not contain '{}'", missing));
+        }
+        if let Some(found) = self
+            .excludes
+            .iter()
+            .find(|text| lower.contains(&text.to_lowercase()))
+        {
+            return Some(format!("answer contains '{}'", found));
+        }
+        self.tools
+            .iter()
+            .find(|tool| !tools.contains(tool))
+            .map(|tool| format!("tool '{}' was not called", tool))
+    }
+}
+
+impl EvalSuite {
+    /// Parse and check a suite
+    pub fn from_yaml(yaml: &str) -> Result<Self, EvalError> {
+        let suite: Self = serde_yaml::from_str(yaml)?;
+        if suite.cases.is_empty() {
+            return Err(EvalError::NoCases(suite.name));
+        }
+        if let Some(case) = suite
+            .cases
+            .iter()
+            .find(|case| case.query.is_none() && case.fixture.is_none())
+        {
+            return Err(EvalError::NoQuery(case.id.clone()));
+        }
+        check_unique("case", suite.cases.iter().map(|case| &case.id))?;
+        check_unique("target", suite.targets.iter().map(|target| &target.name))?;
+        Ok(suite)
+    }
+}
+
+fn check_unique<'a>(
+    kind: &'static str,
+    names: impl Iterator<Item = &'a String>,
+) -> Result<(), EvalError> {
+    let mut seen = BTreeSet::new();
+    for name in names {
+        if !seen.insert(name) {
+            return Err(EvalError::Duplicate {
+                kind,
+                name: name.clone(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// What a host observed running one case on one target
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CaseRun {
+    pub target: String,
+    pub case: String,
+    /// The run's id, naming its audit log
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_id: Option<String>,
+    /// Final answer, `None` if the run failed
+    pub answer: Option<String>,
+    /// Why the run failed (failure code, timeout or host error)
+    pub error: Option<String>,
+    /// Tools the run called, in order
+    pub tools: Vec<String>,
+    pub iterations: usize,
+    pub tokens: usize,
+    pub wall_seconds: f64,
+}
+
+/// A scored run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CaseResult {
+    #[serde(flatten)]
+    pub run: CaseRun,
+    pub passed: bool,
+    /// Why the case failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl CaseResult {
+    /// Score `run` against the case's expectation
+    pub fn score(run: CaseRun, expect: &Expectation) -> Self {
+        let reason = match (&run.answer, &run.error) {
+            (_, Some(error)) => Some(error.clone()),
+            (Some(answer), None) => expect.check(answer, &run.tools),
+            (None, None) => Some("no final answer".to_string()),
+        };
+        Self {
+            passed: reason.is_none(),
+            reason,
+            run,
+        }
+    }
+}
+
+/// One target's results across the suite
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TargetSummary {
+    pub target: String,
+    pub cases: usize,
+    pub passed: usize,
+    /// Share of cases passed, 0 to 1
+    pub accuracy: f64,
+    pub mean_iterations: f64,
+    pub mean_tokens: f64,
+    pub total_tokens: usize,
+    pub mean_wall_seconds: f64,
+    pub total_wall_seconds: f64,
+}
+
+/// Summarize results per target, in the order targets first appear
+pub fn summarize(results: &[CaseResult]) -> Vec<TargetSummary> {
+    let mut targets: Vec<&str> = Vec::new();
+    for result in results {
+        if !targets.contains(&result.run.target.as_str()) {
+            targets.push(&result.run.target);
+        }
+    }
+
+    targets
+        .into_iter()
+        .map(|target| {
+            let runs: Vec<&CaseResult> = results
+                .iter()
+                .filter(|result| result.run.target == target)
+                .collect();
+            let cases = runs.len();
+            let mean = |value: f64| value / cases as f64;
+            let passed = runs.iter().filter(|result| result.passed).count();
+            let total_tokens = runs.iter().map(|result| result.run.tokens).sum();
+            let total_wall_seconds = runs.iter().map(|result| result.run.wall_seconds).sum();
+            TargetSummary {
+                target: target.to_string(),
+                cases,
+                passed,
+                accuracy: mean(passed as f64),
+                mean_iterations: mean(
+                    runs.iter()
+                        .map(|result| result.run.iterations)
+                        .sum::<usize>() as f64,
+                ),
+                mean_tokens: mean(total_tokens as f64),
+                total_tokens,
+                mean_wall_seconds: mean(total_wall_seconds),
+                total_wall_seconds,
+            }
+        })
+        .collect()
+}
+
+/// Plain-text table comparing targets
+pub fn comparison_table(summaries: &[TargetSummary]) -> String {
+    let width = summaries
+        .iter()
+        .map(|summary| summary.target.len())
+        .max()
+        .unwrap_or(0)
+        .max("TARGET".len());
+
+    let mut table = format!(
+        "{:<width$}  {:>8}  {:>8}  {:>10}  {:>10}  {:>9}\n",
+        "TARGET", "PASSED", "ACCURACY", "MEAN ITERS", "MEAN TOKS", "MEAN WALL"
+    );
+    for summary in summaries {
+        table.push_str(&format!(
+            "{:<width$}  {:>8}  {:>7.1}%  {:>10.1}  {:>10.0}  {:>8.1}s\n",
+            summary.target,
+            format!("{}/{}", summary.passed, summary.cases),
+            summary.accuracy * 100.0,
+            summary.mean_iterations,
+            summary.mean_tokens,
+            summary.mean_wall_seconds
+        ));
+    }
+    table
+}
+
+/// Results as CSV, one row per run
+pub fn results_csv(results: &[CaseResult]) -> String {
+    let mut csv = String::from("target,case,passed,iterations,tokens,wall_seconds,tools,reason\n");
+    for result in results {
+        let run = &result.run;
+        csv.push_str(&format!(
+            "{},{},{},{},{},{:.3},{},{}\n",
+            csv_field(&run.target),
+            csv_field(&run.case),
+            result.passed,
+            run.iterations,
+            run.tokens,
+            run.wall_seconds,
+            csv_field(&run.tools.join(" ")),
+            csv_field(result.reason.as_deref().unwrap_or_default())
+        ));
+    }
+    csv
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SUITE: &str = r#"
+name: basics
+targets:
+  - name: small
+    args: [--backend, llama-server]
+  - name: large
+    max_concurrent: 1
+cases:
+  - id: files
+    query: How many files?
+    expect:
+      contains: ["3"]
+      tools: [shell]
+  - id: greet
+    query: Say hi
+"#;
+
+    fn run(target: &str, case: &str, answer: Option<&str>, tools: &[&str]) -> CaseRun {
+        CaseRun {
+            target: target.to_string(),
+            case: case.to_string(),
+            answer: answer.map(str::to_string),
+            tools: tools.iter().map(|tool| tool.to_string()).collect(),
+            iterations: 2,
+            tokens: 100,
+            wall_seconds: 1.5,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_suite_parsing() {
+        let suite = EvalSuite::from_yaml(SUITE).unwrap();
+        assert_eq!(suite.targets.len(), 2);
+        assert_eq!(suite.targets[0].max_concurrent, None);
+        assert_eq!(suite.targets[1].max_concurrent, Some(1));
+        assert_eq!(suite.timeout_secs, DEFAULT_CASE_TIMEOUT_SECS);
+        assert_eq!(suite.cases[0].expect.tools, vec!["shell"]);
+        assert_eq!(suite.cases[1].expect, Expectation::default());
+
+        assert!(matches!(
+            EvalSuite::from_yaml("name: empty\ncases: []"),
+            Err(EvalError::NoCases(_))
+        ));
+        let duplicate = SUITE.replace("id: greet", "id: files");
+        assert!(matches!(
+            EvalSuite::from_yaml(&duplicate),
+            Err(EvalError::Duplicate { kind: "case", .. })
+        ));
+    }
+
+    #[test]
+    fn test_fixture_cases() {
+        let suite = EvalSuite::from_yaml(
+            "name: replay\ncases:\n  - id: recorded\n    fixture: fixtures/case-01\n",
+        )
+        .unwrap();
+        assert_eq!(suite.cases[0].query, None);
+        assert_eq!(
+            suite.cases[0].fixture.as_deref(),
+            Some(std::path::Path::new("fixtures/case-01"))
+        );
+
+        let no_query = SUITE.replace("    query: Say hi\n", "");
+        assert!(matches!(
+            EvalSuite::from_yaml(&no_query),
+            Err(EvalError::NoQuery(id)) if id == "greet"
+        ));
+    }
+
+    #[test]
+    fn test_scoring() {
+        let expect = EvalSuite::from_yaml(SUITE).unwrap().cases[0].expect.clone();
+        let pass = CaseResult::score(
+            run("small", "files", Some("There are 3."), &["shell"]),
+            &expect,
+        );
+        assert!(pass.passed);
+
+        let no_tool = CaseResult::score(run("small", "files", Some("3"), &[]), &expect);
+        assert_eq!(
+            no_tool.reason.as_deref(),
+            Some("tool 'shell' was not called")
+        );
+
+        let wrong = CaseResult::score(run("small", "files", Some("Four"), &["shell"]), &expect);
+        assert_eq!(wrong.reason.as_deref(), Some("answer does not contain '3'"));
+
+        let mut failed = run("small", "files", None, &["shell"]);
+        failed.error = Some("max_iterations".to_string());
+        assert_eq!(
+            CaseResult::score(failed, &expect).reason.as_deref(),
+            Some("max_iterations")
+        );
+    }
+
+    #[test]
+    fn test_summary_and_exports() {
+        let expect = Expectation::default();
+        let results = vec![
+            CaseResult::score(run("small", "a", Some("x"), &[]), &expect),
+            CaseResult::score(run("large", "a", Some("x"), &[]), &expect),
+            CaseResult::score(run("small", "b", None, &[]), &expect),
+        ];
+        let summaries = summarize(&results);
+        assert_eq!(summaries.len(), 2);
+        assert_eq!(summaries[0].target, "small");
+        assert_eq!(summaries[0].passed, 1);
+        assert_eq!(summaries[0].accuracy, 0.5);
+        assert_eq!(summaries[0].total_tokens, 200);
+
+        let table = comparison_table(&summaries);
+        assert!(table.lines().nth(1).unwrap().contains("1/2"));
+        assert!(table.contains("50.0%"));
+
+        let csv = results_csv(&results);
+        assert_eq!(csv.lines().count(), 4);
+        assert!(csv.ends_with("small,b,false,2,100,1.500,,no final answer\n"));
+        assert_eq!(csv_field("a,\"b\""), "\"a,\"\"b\"\"\"");
+    }
+}
@@ -0,0 +1,201 @@
+//! Live event streams over SSE and WebSocket
+//!
+//! A host serving runs over HTTP forwards each run's JSON events (those of
+//! `--output jsonl`: tokens, tool proposals, approval requests...) to web UIs
+//! as they happen. [`EventLog`] numbers a run's events and keeps the recent
+//! ones, so a client that reconnects with the last id it saw (SSE's
+//! `Last-Event-ID` header, or a `last_event_id` query parameter for
+//! WebSockets) gets exactly the events it missed. A client too far behind is
+//! told so ([`ResumeError::Expired`]) and reloads the transcript instead.
+//!
+//! This module only numbers, keeps and frames events; hosts hold the
+//! connections. No SSE or WebSocket endpoint ships yet: `agent-native` serves
+//! no HTTP.
+
+use crate::prelude::*;
+use alloc::collections::VecDeque;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+/// Events of a run kept for reconnecting clients
+pub const DEFAULT_EVENT_BACKLOG: usize = 1000;
+
+/// SSE comment that keeps idle connections open through proxies
+pub const SSE_KEEP_ALIVE: &str = ": keep-alive\n\n";
+
+/// Event name of events without an `event` field
+const DEFAULT_EVENT_NAME: &str = "message";
+
+/// Why a stream cannot resume after an event id
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ResumeError {
+    #[error("events up to {forgotten} are no longer kept; reload the transcript")]
+    Expired { forgotten: u64 },
+    #[error("event {last} was never sent (latest: {latest})")]
+    Unknown { last: u64, latest: u64 },
+}
+
+/// An event with its position in the run's stream
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StreamEvent {
+    /// Starts at 1 and grows by one per event
+    pub id: u64,
+    /// The event's `event` field
+    pub event: String,
+    pub data: Value,
+}
+
+impl StreamEvent {
+    /// The event as an SSE frame
+    pub fn to_sse(&self) -> String {
+        let data = serde_json::to_string(&self.data).expect("JSON values serialize");
+        format!("id: {}\nevent: {}\ndata: {}\n\n", self.id, self.event, data)
+    }
+
+    /// The event as a WebSocket text message: its data with the id added
+    pub fn to_ws(&self) -> String {
+        let message = match &self.data {
+            Value::Object(fields) => {
+                let mut fields = fields.clone();
+                fields.insert("id".to_string(), Value::from(self.id));
+                Value::Object(fields)
+            }
+            data => serde_json::json!({ "id": self.id, "event": self.event, "data": data }),
+        };
+        serde_json::to_string(&message).expect("JSON values serialize")
+    }
+}
+
+/// The numbered events of one run, the most recent `capacity` of them kept
+#[derive(Debug, Clone)]
+pub struct EventLog {
+    events: VecDeque<StreamEvent>,
+    capacity: usize,
+    latest: u64,
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventLog {
+    pub fn new() -> Self {
+        Self {
+            events: VecDeque::new(),
+            capacity: DEFAULT_EVENT_BACKLOG,
+            latest: 0,
+        }
+    }
+
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// Id of the last event pushed (0: none yet)
+    pub fn latest(&self) -> u64 {
+        self.latest
+    }
+
+    /// Number `data` and keep it, forgetting the oldest event beyond capacity
+    pub fn push(&mut self, data: Value) -> &StreamEvent {
+        self.latest += 1;
+        let event = data
+            .get("event")
+            .and_then(Value::as_str)
+            .unwrap_or(DEFAULT_EVENT_NAME)
+            .to_string();
+        if self.events.len() == self.capacity {
+            self.events.pop_front();
+        }
+        self.events.push_back(StreamEvent {
+            id: self.latest,
+            event,
+            data,
+        });
+        self.events.back().expect("just pushed")
+    }
+
+    /// The events after `last` (`None`: a new client, all kept events)
+    pub fn since(
+        &self,
+        last: Option<u64>,
+    ) -> Result<impl Iterator<Item = &StreamEvent>, ResumeError> {
+        let last = last.unwrap_or(0);
+        if last > self.latest {
+            return Err(ResumeError::Unknown {
+                last,
+                latest: self.latest,
+            });
+        }
+        let oldest = self
+            .events
+            .front()
+            .map_or(self.latest + 1, |event| event.id);
+        // A new client only gets what is kept; a resuming one must not miss any
+        if last != 0 && last + 1 < oldest {
+            return Err(ResumeError::Expired {
+                forgotten: oldest - 1,
+            });
+        }
+        Ok(self.events.iter().filter(move |event| event.id > last))
+    }
+}
+
+/// The id of a `Last-Event-ID` header or `last_event_id` parameter
+pub fn parse_last_event_id(value: &str) -> Option<u64> {
+    value.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn ids<'a>(events: impl Iterator<Item = &'a StreamEvent>) -> Vec<u64> {
+        events.map(|event| event.id).collect()
+    }
+
+    #[test]
+    fn test_frames() {
+        let mut log = EventLog::new();
+        let event = log
+            .push(json!({"event": "tool_proposed", "tool": "shell"}))
+            .clone();
+        assert_eq!(
+            event.to_sse(),
+            "id: 1\nevent: tool_proposed\ndata: {\"event\":\"tool_proposed\",\"tool\":\"shell\"}\n\n"
+        );
+        let ws: Value = serde_json::from_str(&event.to_ws()).unwrap();
+        assert_eq!(
+            ws,
+            json!({"id": 1, "event": "tool_proposed", "tool": "shell"})
+        );
+        assert_eq!(log.push(json!("token")).event, "message");
+    }
+
+    #[test]
+    fn test_resume() {
+        let mut log = EventLog::new().with_capacity(3);
+        for number in 1..=5 {
+            log.push(json!({"event": "iteration", "number": number}));
+        }
+        assert_eq!(ids(log.since(None).unwrap()), vec![3, 4, 5]);
+        assert_eq!(ids(log.since(Some(2)).unwrap()), vec![3, 4, 5]);
+        assert_eq!(ids(log.since(Some(4)).unwrap()), vec![5]);
+        assert_eq!(ids(log.since(Some(5)).unwrap()), Vec::<u64>::new());
+        assert_eq!(
+            log.since(Some(1)).err(),
+            Some(ResumeError::Expired { forgotten: 2 })
+        );
+        assert_eq!(
+            log.since(Some(9)).err(),
+            Some(ResumeError::Unknown { last: 9, latest: 5 })
+        );
+        assert_eq!(parse_last_event_id(" 4 "), Some(4));
+        assert_eq!(parse_last_event_id("four"), None);
+    }
+}
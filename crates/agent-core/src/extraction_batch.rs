@@ -0,0 +1,528 @@
+//! Batch extraction over document chunks
+//!
+//! Long documents exceed what a small model can reliably attend to in one prompt,
+//! so matches near the end of the text get silently dropped. `ExtractionBatch`
+//! splits the input text into overlapping chunks, lets the host run the extraction
+//! skill once per chunk, and merges the per-chunk outputs into a single
+//! deduplicated result with provenance (which chunks each value came from).
+//!
+//! Like the rest of the skill system, agent-core only plans and merges; the host
+//! executes each chunk.
+
+use crate::prelude::*;
+use crate::skill::{
+    string_items, EntityClass, ExtractionInput, ExtractionOutput, ExtractionTarget, SkillError,
+    SkillPostProcess, SkillResult, SourceSpan, ValueSpans,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// Default chunk size in characters
+pub const DEFAULT_CHUNK_SIZE: usize = 2000;
+
+/// Default overlap between consecutive chunks in characters
+pub const DEFAULT_CHUNK_OVERLAP: usize = 200;
+
+/// A slice of the source text, addressed by character offsets
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextChunk {
+    /// Index of the chunk in the batch
+    pub index: usize,
+    /// Start offset (chars, inclusive) in the source text
+    pub start: usize,
+    /// End offset (chars, exclusive) in the source text
+    pub end: usize,
+    /// The chunk text
+    pub text: String,
+}
+
+/// Where a merged value was found
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValueProvenance {
+    /// The extracted value
+    pub value: String,
+    /// Entity class or field name the value belongs to (entity/fields targets)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    /// Char offsets `[start, end)` of every chunk that produced the value
+    pub chunks: Vec<(usize, usize)>,
+}
+
+/// Merged result of a batch extraction
+#[derive(Debug, Clone)]
+pub struct MergedExtraction {
+    /// The merged, deduplicated output (same shape as a single-prompt extraction)
+    pub output: ExtractionOutput,
+    /// Provenance for every value in `output`, in output order
+    pub provenance: Vec<ValueProvenance>,
+}
+
+impl MergedExtraction {
+    /// The output JSON with top-level `spans` and `provenance` arrays attached
+    pub fn to_value(&self) -> Value {
+        let mut result = self.output.to_value();
+        if let Value::Object(ref mut obj) = result {
+            obj.insert(
+                "provenance".to_string(),
+                serde_json::to_value(&self.provenance).unwrap_or(Value::Null),
+            );
+        }
+        result
+    }
+
+    /// Apply a skill's post-processing to the merged output, keeping the
+    /// provenance in output order (values it dropped lose their provenance)
+    pub fn post_processed(
+        mut self,
+        post_process: SkillPostProcess,
+        target: ExtractionTarget,
+    ) -> Self {
+        self.output = post_process(self.output, target);
+        let result = &self.output.result;
+        let position = |p: &ValueProvenance| {
+            let values = match target {
+                ExtractionTarget::Fields => return Some(0),
+                ExtractionTarget::Entity => string_items(
+                    result
+                        .get("entity")
+                        .zip(p.field.as_deref())
+                        .and_then(|(entity, class)| entity.get(class)),
+                ),
+                _ => string_items(result.get(target.as_str())),
+            };
+            values.iter().position(|v| v.eq_ignore_ascii_case(&p.value))
+        };
+        let mut provenance: Vec<(usize, ValueProvenance)> = core::mem::take(&mut self.provenance)
+            .into_iter()
+            .filter_map(|p| Some((position(&p)?, p)))
+            .collect();
+        provenance.sort_by_key(|(position, _)| *position);
+        self.provenance = provenance.into_iter().map(|(_, p)| p).collect();
+        self
+    }
+}
+
+/// Plan for running the extraction skill over overlapping chunks of a long text
+#[derive(Debug, Clone)]
+pub struct ExtractionBatch {
+    input: ExtractionInput,
+    chunk_size: usize,
+    overlap: usize,
+}
+
+impl ExtractionBatch {
+    /// Create a batch with the default chunk size and overlap
+    pub fn new(input: ExtractionInput) -> Self {
+        Self {
+            input,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            overlap: DEFAULT_CHUNK_OVERLAP,
+        }
+    }
+
+    /// Set the chunk size in characters
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    /// Set the overlap between consecutive chunks in characters
+    pub fn with_overlap(mut self, overlap: usize) -> Self {
+        self.overlap = overlap;
+        self
+    }
+
+    /// The full (unchunked) input
+    pub fn input(&self) -> &ExtractionInput {
+        &self.input
+    }
+
+    /// Validate the input and chunking parameters
+    pub fn validate(&self) -> SkillResult<ExtractionTarget> {
+        if self.chunk_size == 0 {
+            return Err(SkillError::SchemaViolation(
+                "chunk size must be greater than zero".to_string(),
+            ));
+        }
+        if self.overlap >= self.chunk_size {
+            return Err(SkillError::SchemaViolation(format!(
+                "chunk overlap ({}) must be smaller than chunk size ({})",
+                self.overlap, self.chunk_size
+            )));
+        }
+        self.input.validate()
+    }
+
+    /// Split the input text into overlapping chunks
+    ///
+    /// Chunk ends are moved back to the nearest whitespace (within the second
+    /// half of the chunk) so words are not cut in half.
+    pub fn chunks(&self) -> Vec<TextChunk> {
+        let chars: Vec<char> = self.input.text.chars().collect();
+        let size = self.chunk_size.max(1);
+        let overlap = self.overlap.min(size - 1);
+
+        let mut chunks = Vec::new();
+        let mut start = 0;
+
+        while start < chars.len() {
+            let mut end = (start + size).min(chars.len());
+            if end < chars.len() {
+                let min_end = start + size / 2;
+                if let Some(ws) = (min_end..end).rev().find(|&i| chars[i].is_whitespace()) {
+                    end = ws;
+                }
+            }
+
+            chunks.push(TextChunk {
+                index: chunks.len(),
+                start,
+                end,
+                text: chars[start..end].iter().collect(),
+            });
+
+            if end == chars.len() {
+                break;
+            }
+            start = end.saturating_sub(overlap).max(start + 1);
+        }
+
+        chunks
+    }
+
+    /// Build the skill input for a single chunk
+    pub fn chunk_input(&self, chunk: &TextChunk) -> ExtractionInput {
+        ExtractionInput {
+            text: chunk.text.clone(),
+            ..self.input.clone()
+        }
+    }
+
+    /// Merge per-chunk outputs into one deduplicated result with provenance
+    ///
+    /// List values are deduplicated case-insensitively, keeping the first spelling.
+    /// For the `fields` target, the first non-null value of each field wins.
+    pub fn merge(
+        &self,
+        target: ExtractionTarget,
+        results: &[(TextChunk, ExtractionOutput)],
+    ) -> MergedExtraction {
+        let mut provenance: Vec<ValueProvenance> = Vec::new();
+
+        let mut record = |field: Option<&str>, value: &str, chunk: &TextChunk| -> bool {
+            let span = (chunk.start, chunk.end);
+            let existing = provenance.iter_mut().find(|p| {
+                p.field.as_deref() == field && p.value.to_lowercase() == value.to_lowercase()
+            });
+            match existing {
+                Some(p) => {
+                    if !p.chunks.contains(&span) {
+                        p.chunks.push(span);
+                    }
+                    false
+                }
+                None => {
+                    provenance.push(ValueProvenance {
+                        value: value.to_string(),
+                        field: field.map(str::to_string),
+                        chunks: vec![span],
+                    });
+                    true
+                }
+            }
+        };
+
+        let result = match target {
+            ExtractionTarget::Entity => {
+                let classes = self.input.entity_classes();
+                let mut merged: Vec<(EntityClass, Vec<String>)> =
+                    classes.iter().map(|c| (*c, Vec::new())).collect();
+                for (chunk, output) in results {
+                    let entity = output.result.get("entity");
+                    for (class, values) in merged.iter_mut() {
+                        for value in string_items(entity.and_then(|e| e.get(class.as_str()))) {
+                            if record(Some(class.as_str()), value, chunk) {
+                                values.push(value.to_string());
+                            }
+                        }
+                    }
+                }
+                let entity: Map<String, Value> = merged
+                    .into_iter()
+                    .map(|(class, values)| (class.as_str().to_string(), Value::from(values)))
+                    .collect();
+                serde_json::json!({ "entity": entity })
+            }
+            ExtractionTarget::Fields => {
+                let mut fields: Map<String, Value> = self
+                    .input
+                    .schema
+                    .iter()
+                    .flatten()
+                    .map(|(name, _)| (name.clone(), Value::Null))
+                    .collect();
+                for (chunk, output) in results {
+                    let Some(Value::Object(chunk_fields)) = output.result.get("fields") else {
+                        continue;
+                    };
+                    for (name, value) in chunk_fields {
+                        let value = match value {
+                            Value::String(s) if !s.trim().is_empty() => s.clone(),
+                            Value::Number(n) => n.to_string(),
+                            Value::Bool(b) => b.to_string(),
+                            _ => continue,
+                        };
+                        let first = record(Some(name), &value, chunk);
+                        if let Some(slot) = fields.get_mut(name) {
+                            if first && slot.is_null() {
+                                *slot = Value::String(value);
+                            }
+                        }
+                    }
+                }
+                serde_json::json!({ "fields": fields })
+            }
+            _ => {
+                let mut values = Vec::new();
+                for (chunk, output) in results {
+                    for value in string_items(output.result.get(target.as_str())) {
+                        if record(None, value, chunk) {
+                            values.push(value.to_string());
+                        }
+                    }
+                }
+                serde_json::json!({ target.as_str(): values })
+            }
+        };
+
+        // Fields keep only provenance for the value that won
+        if target == ExtractionTarget::Fields {
+            provenance.retain(|p| {
+                let winner = p
+                    .field
+                    .as_deref()
+                    .and_then(|f| result["fields"].get(f))
+                    .and_then(|v| v.as_str());
+                winner.is_some_and(|w| w.eq_ignore_ascii_case(&p.value))
+            });
+        }
+
+        // Carry per-chunk source spans over, shifted to offsets in the full text
+        let mut spans: Vec<ValueSpans> = Vec::new();
+        for (chunk, output) in results {
+            for located in &output.spans {
+                let shifted = located.spans.iter().map(|span| SourceSpan {
+                    start: span.start + chunk.start,
+                    end: span.end + chunk.start,
+                });
+                let Some(kept) = provenance.iter().find(|p| {
+                    p.field == located.field && p.value.eq_ignore_ascii_case(&located.value)
+                }) else {
+                    continue;
+                };
+                let existing = spans
+                    .iter_mut()
+                    .find(|s| s.field == kept.field && s.value == kept.value);
+                match existing {
+                    Some(existing) => existing.spans.extend(shifted),
+                    None => spans.push(ValueSpans {
+                        value: kept.value.clone(),
+                        field: kept.field.clone(),
+                        spans: shifted.collect(),
+                    }),
+                }
+            }
+        }
+        for located in spans.iter_mut() {
+            located.spans.sort();
+            located.spans.dedup();
+        }
+
+        MergedExtraction {
+            output: ExtractionOutput::new(result).with_spans(spans),
+            provenance,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::skill::{normalize_extraction_output, validate_extraction_output};
+
+    #[test]
+    fn test_short_text_is_single_chunk() {
+        let batch = ExtractionBatch::new(ExtractionInput::new("a@b.com", "email"));
+        let chunks = batch.chunks();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!((chunks[0].start, chunks[0].end), (0, 7));
+    }
+
+    #[test]
+    fn test_chunks_overlap_and_cover_text() {
+        let text = "alpha beta gamma delta epsilon zeta eta theta iota kappa";
+        let batch = ExtractionBatch::new(ExtractionInput::new(text, "name"))
+            .with_chunk_size(20)
+            .with_overlap(6);
+        let chunks = batch.chunks();
+
+        assert!(chunks.len() > 1);
+        assert_eq!(chunks[0].start, 0);
+        assert_eq!(chunks.last().unwrap().end, text.chars().count());
+        for pair in chunks.windows(2) {
+            assert!(pair[1].start < pair[0].end, "chunks must overlap");
+        }
+        for chunk in &chunks {
+            let expected: String = text
+                .chars()
+                .skip(chunk.start)
+                .take(chunk.end - chunk.start)
+                .collect();
+            assert_eq!(chunk.text, expected);
+        }
+    }
+
+    #[test]
+    fn test_invalid_overlap() {
+        let batch = ExtractionBatch::new(ExtractionInput::new("text", "email"))
+            .with_chunk_size(10)
+            .with_overlap(10);
+        assert!(matches!(
+            batch.validate(),
+            Err(SkillError::SchemaViolation(_))
+        ));
+    }
+
+    #[test]
+    fn test_merge_deduplicates_with_provenance() {
+        let batch = ExtractionBatch::new(ExtractionInput::new("irrelevant", "email"));
+        let c0 = TextChunk {
+            index: 0,
+            start: 0,
+            end: 10,
+            text: String::new(),
+        };
+        let c1 = TextChunk {
+            index: 1,
+            start: 8,
+            end: 20,
+            text: String::new(),
+        };
+        let results = vec![
+            (
+                c0,
+                ExtractionOutput::emails(vec!["A@x.io".to_string(), "b@x.io".to_string()]),
+            ),
+            (c1, ExtractionOutput::emails(vec!["a@x.io".to_string()])),
+        ];
+
+        let merged = batch.merge(ExtractionTarget::Email, &results);
+        assert_eq!(
+            merged.output.result,
+            serde_json::json!({"email": ["A@x.io", "b@x.io"]})
+        );
+        assert_eq!(merged.provenance[0].chunks, vec![(0, 10), (8, 20)]);
+        assert_eq!(merged.provenance[1].chunks, vec![(0, 10)]);
+    }
+
+    #[test]
+    fn test_post_processed_merge_sorts_dates() {
+        let batch = ExtractionBatch::new(ExtractionInput::new("x".repeat(20), "date"));
+        let chunk = |index, start, end| TextChunk {
+            index,
+            start,
+            end,
+            text: String::new(),
+        };
+        let results = vec![
+            (
+                chunk(0, 0, 10),
+                ExtractionOutput::dates(vec!["2024-05-01".to_string()]),
+            ),
+            (
+                chunk(1, 8, 20),
+                ExtractionOutput::dates(vec!["2023-01-09".to_string()]),
+            ),
+        ];
+
+        let merged = batch
+            .merge(ExtractionTarget::Date, &results)
+            .post_processed(normalize_extraction_output, ExtractionTarget::Date);
+        assert_eq!(
+            merged.output.result,
+            serde_json::json!({"date": ["2023-01-09", "2024-05-01"]})
+        );
+        assert_eq!(merged.provenance[0].value, "2023-01-09");
+        assert_eq!(merged.provenance[0].chunks, vec![(8, 20)]);
+    }
+
+    #[test]
+    fn test_merge_shifts_spans_to_full_text() {
+        let text = "mail a@x.io now, later mail A@x.io";
+        let batch = ExtractionBatch::new(ExtractionInput::new(text, "email"))
+            .with_chunk_size(20)
+            .with_overlap(4);
+        let chunks = batch.chunks();
+
+        let results: Vec<_> = chunks
+            .iter()
+            .map(|chunk| {
+                let input = batch.chunk_input(chunk);
+                let found = chunk
+                    .text
+                    .match_indices("a@x.io")
+                    .chain(chunk.text.match_indices("A@x.io"))
+                    .map(|(_, m)| m.to_string())
+                    .collect();
+                let output = ExtractionOutput::emails(found);
+                let spans =
+                    validate_extraction_output(&input, &output, ExtractionTarget::Email).unwrap();
+                (chunk.clone(), output.with_spans(spans))
+            })
+            .collect();
+
+        let merged = batch.merge(ExtractionTarget::Email, &results);
+        assert_eq!(merged.output.spans.len(), 1);
+        assert_eq!(
+            merged.output.spans[0].spans,
+            vec![
+                SourceSpan { start: 5, end: 11 },
+                SourceSpan { start: 28, end: 34 }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_fields_first_value_wins() {
+        let input = ExtractionInput::new("irrelevant", "fields")
+            .with_schema([("invoice", "id"), ("total", "amount")]);
+        let batch = ExtractionBatch::new(input);
+        let chunk = |start| TextChunk {
+            index: 0,
+            start,
+            end: start + 5,
+            text: String::new(),
+        };
+        let results = vec![
+            (
+                chunk(0),
+                ExtractionOutput::new(
+                    serde_json::json!({"fields": {"invoice": "INV-1", "total": null}}),
+                ),
+            ),
+            (
+                chunk(5),
+                ExtractionOutput::new(
+                    serde_json::json!({"fields": {"invoice": "INV-2", "total": "$5"}}),
+                ),
+            ),
+        ];
+
+        let merged = batch.merge(ExtractionTarget::Fields, &results);
+        assert_eq!(
+            merged.output.result,
+            serde_json::json!({"fields": {"invoice": "INV-1", "total": "$5"}})
+        );
+        assert_eq!(merged.provenance.len(), 2);
+    }
+}
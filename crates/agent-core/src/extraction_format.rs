@@ -0,0 +1,215 @@
+//! Extraction results as rows, for pipelines
+//!
+//! The extraction skill returns JSON. Hosts that feed the values into other
+//! tools can render them as CSV, an aligned table or plain lines instead: one
+//! row per value, with the entity class or field name as a first column for
+//! the `entity` and `fields` targets. Fields without a value are left out.
+
+use crate::prelude::*;
+use crate::skill::{string_items, EntityClass, ExtractionTarget};
+use serde_json::Value;
+
+/// How an extraction result is printed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExtractFormat {
+    /// The result object, spans and provenance included
+    #[default]
+    Json,
+    /// A header row, then one row per value
+    Csv,
+    /// Aligned columns under a header, for reading
+    Table,
+    /// One value per line, tab-separated from its class or field
+    Plain,
+}
+
+impl ExtractFormat {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "json" => Some(Self::Json),
+            "csv" => Some(Self::Csv),
+            "table" => Some(Self::Table),
+            "plain" | "tsv" => Some(Self::Plain),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Csv => "csv",
+            Self::Table => "table",
+            Self::Plain => "plain",
+        }
+    }
+}
+
+/// One extracted value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtractedRow {
+    /// Entity class or field name (`entity` and `fields` targets)
+    pub kind: Option<String>,
+    pub value: String,
+}
+
+/// The values of an extraction result (the output's `result` object), in
+/// output order
+pub fn extraction_rows(target: ExtractionTarget, output: &Value) -> Vec<ExtractedRow> {
+    let row = |kind: Option<&str>, value: &str| ExtractedRow {
+        kind: kind.map(str::to_string),
+        value: value.to_string(),
+    };
+    match target {
+        ExtractionTarget::Entity => {
+            let entity = output.get("entity");
+            EntityClass::ALL
+                .iter()
+                .flat_map(|class| {
+                    string_items(entity.and_then(|e| e.get(class.as_str())))
+                        .into_iter()
+                        .map(|value| row(Some(class.as_str()), value))
+                })
+                .collect()
+        }
+        ExtractionTarget::Fields => match output.get("fields") {
+            Some(Value::Object(fields)) => fields
+                .iter()
+                .filter_map(|(name, value)| match value {
+                    Value::Null => None,
+                    Value::String(s) => Some(row(Some(name), s)),
+                    other => Some(row(Some(name), &other.to_string())),
+                })
+                .collect(),
+            _ => Vec::new(),
+        },
+        _ => string_items(output.get(target.as_str()))
+            .into_iter()
+            .map(|value| row(None, value))
+            .collect(),
+    }
+}
+
+/// Render an extraction result in `format`
+///
+/// Every format but JSON ends with a newline after its last row.
+pub fn format_extraction(
+    target: ExtractionTarget,
+    output: &Value,
+    format: ExtractFormat,
+) -> String {
+    if format == ExtractFormat::Json {
+        return serde_json::to_string(output).unwrap_or_default();
+    }
+
+    let header: Vec<&str> = match target {
+        ExtractionTarget::Entity => vec!["type", "value"],
+        ExtractionTarget::Fields => vec!["field", "value"],
+        _ => vec![target.as_str()],
+    };
+    let rows: Vec<Vec<String>> = extraction_rows(target, output)
+        .into_iter()
+        .map(|row| row.kind.into_iter().chain([row.value]).collect())
+        .collect();
+
+    let mut text = String::new();
+    match format {
+        ExtractFormat::Csv => {
+            text.push_str(&header.join(","));
+            text.push('\n');
+            for row in &rows {
+                let fields: Vec<String> = row.iter().map(|field| csv_field(field)).collect();
+                text.push_str(&fields.join(","));
+                text.push('\n');
+            }
+        }
+        ExtractFormat::Table => {
+            let widths: Vec<usize> = (0..header.len())
+                .map(|column| {
+                    rows.iter()
+                        .map(|row| row[column].chars().count())
+                        .chain([header[column].len()])
+                        .max()
+                        .unwrap_or(0)
+                })
+                .collect();
+            let header: Vec<String> = header.iter().map(|name| name.to_uppercase()).collect();
+            for row in core::iter::once(&header).chain(&rows) {
+                let cells: Vec<String> = row
+                    .iter()
+                    .zip(&widths)
+                    .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                    .collect();
+                text.push_str(cells.join("  ").trim_end());
+                text.push('\n');
+            }
+        }
+        ExtractFormat::Plain => {
+            for row in &rows {
+                let cells: Vec<String> = row
+                    .iter()
+                    .map(|cell| cell.replace(['\t', '\n'], " "))
+                    .collect();
+                text.push_str(&cells.join("\t"));
+                text.push('\n');
+            }
+        }
+        ExtractFormat::Json => unreachable!("handled above"),
+    }
+    text
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_list_targets() {
+        let output = json!({"email": ["a@b.com", "c@d.org"], "spans": []});
+        assert_eq!(
+            format_extraction(ExtractionTarget::Email, &output, ExtractFormat::Csv),
+            "email\na@b.com\nc@d.org\n"
+        );
+        assert_eq!(
+            format_extraction(ExtractionTarget::Email, &output, ExtractFormat::Plain),
+            "a@b.com\nc@d.org\n"
+        );
+    }
+
+    #[test]
+    fn test_entities_carry_their_class() {
+        let output = json!({"entity": {"people": ["Ada Lovelace"], "organizations": [], "locations": ["London, UK"]}});
+        assert_eq!(
+            format_extraction(ExtractionTarget::Entity, &output, ExtractFormat::Csv),
+            "type,value\npeople,Ada Lovelace\nlocations,\"London, UK\"\n"
+        );
+        assert_eq!(
+            format_extraction(ExtractionTarget::Entity, &output, ExtractFormat::Plain),
+            "people\tAda Lovelace\nlocations\tLondon, UK\n"
+        );
+        assert_eq!(
+            format_extraction(ExtractionTarget::Entity, &output, ExtractFormat::Table),
+            "TYPE       VALUE\npeople     Ada Lovelace\nlocations  London, UK\n"
+        );
+    }
+
+    #[test]
+    fn test_fields_skip_missing_values() {
+        let output = json!({"fields": {"vendor": "ACME", "total": 12.5, "due": null}});
+        let rows = extraction_rows(ExtractionTarget::Fields, &output);
+        assert_eq!(rows.len(), 2);
+        assert!(rows.contains(&ExtractedRow {
+            kind: Some("total".to_string()),
+            value: "12.5".to_string(),
+        }));
+        assert_eq!(ExtractFormat::from_str("TSV"), Some(ExtractFormat::Plain));
+    }
+}
@@ -0,0 +1,150 @@
+//! Bulk extraction: many texts, one result line each
+//!
+//! A records file is JSONL, one text per line, either as an object or as a
+//! bare JSON string:
+//!
+//! ```text
+//! {"id": "mail-001", "text": "Write to ana@example.com"}
+//! {"id": 2, "text": "...", "source": "other fields are ignored"}
+//! "ids default to record-<line>"
+//! ```
+//!
+//! Hosts extract from every record over one loaded model and write an
+//! [`ExtractionRecordResult`] per record as it finishes. A record that fails
+//! does not stop the others.
+
+use crate::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+/// Errors from reading a records file
+#[derive(Debug, Error, PartialEq)]
+pub enum RecordsError {
+    #[error("line {line}: not a record: {message}")]
+    Malformed { line: usize, message: String },
+    #[error("no records")]
+    Empty,
+}
+
+/// One text to extract from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtractionRecord {
+    pub id: String,
+    pub text: String,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RecordLine {
+    Text(String),
+    Object {
+        #[serde(default)]
+        id: Option<Value>,
+        text: String,
+    },
+}
+
+/// Parse a JSONL records file, skipping blank lines
+pub fn parse_records(jsonl: &str) -> Result<Vec<ExtractionRecord>, RecordsError> {
+    let mut records = Vec::new();
+    for (index, text) in jsonl.lines().enumerate() {
+        let line = index + 1;
+        if text.trim().is_empty() {
+            continue;
+        }
+        let record: RecordLine =
+            serde_json::from_str(text).map_err(|_| RecordsError::Malformed {
+                line,
+                message: "expected a JSON string or an object with a \"text\" string".to_string(),
+            })?;
+        let (id, text) = match record {
+            RecordLine::Text(text) => (None, text),
+            RecordLine::Object { id, text } => (id, text),
+        };
+        let id = match id {
+            None | Some(Value::Null) => format!("record-{}", line),
+            Some(Value::String(id)) => id,
+            Some(other) => other.to_string(),
+        };
+        records.push(ExtractionRecord { id, text });
+    }
+    if records.is_empty() {
+        return Err(RecordsError::Empty);
+    }
+    Ok(records)
+}
+
+/// The result of one record, a line of the output
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExtractionRecordResult {
+    pub id: String,
+    pub success: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ExtractionRecordResult {
+    pub fn success(id: impl Into<String>, output: Value) -> Self {
+        Self {
+            id: id.into(),
+            success: true,
+            output: Some(output),
+            error: None,
+        }
+    }
+
+    pub fn failure(id: impl Into<String>, error: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            success: false,
+            output: None,
+            error: Some(error.into()),
+        }
+    }
+
+    /// The result as one JSONL line (without the newline)
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("record results serialize")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_records() {
+        let records = parse_records(
+            "{\"id\": \"a\", \"text\": \"one\"}\n\n\"two\"\n{\"id\": 7, \"text\": \"three\", \"lang\": \"en\"}\n",
+        )
+        .unwrap();
+        let ids: Vec<&str> = records.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "record-3", "7"]);
+        assert_eq!(records[1].text, "two");
+
+        assert_eq!(
+            parse_records("{\"id\": \"a\"}"),
+            Err(RecordsError::Malformed {
+                line: 1,
+                message: "expected a JSON string or an object with a \"text\" string".to_string()
+            })
+        );
+        assert_eq!(parse_records("\n  \n"), Err(RecordsError::Empty));
+    }
+
+    #[test]
+    fn test_record_result_lines() {
+        assert_eq!(
+            ExtractionRecordResult::success("a", json!({"emails": ["x@y.z"]})).to_json(),
+            r#"{"id":"a","success":true,"output":{"emails":["x@y.z"]}}"#
+        );
+        assert_eq!(
+            ExtractionRecordResult::failure("b", "no output").to_json(),
+            r#"{"id":"b","success":false,"error":"no output"}"#
+        );
+    }
+}
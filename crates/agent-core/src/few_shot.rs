@@ -0,0 +1,241 @@
+//! Few-shot examples for the system prompt
+//!
+//! Tool and skill manifests declare examples: the call as the model would emit
+//! it and, optionally, what came back. Small models follow the JSON call format
+//! far more reliably after seeing a few concrete calls, so hosts put some of
+//! these examples into the system prompt. [`FewShotPolicy`] bounds how many and
+//! how much of the prompt they may take.
+//!
+//! In YAML an example is either the bare call or a call/response pair:
+//!
+//! ```yaml
+//! examples:
+//!   - {"tool": "shell", "command": "ls"}
+//!   - call: {"tool": "shell", "command": "wc -l README.md"}
+//!     response: "42 README.md"
+//! ```
+
+use crate::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Default number of examples per tool or skill
+pub const DEFAULT_EXAMPLES_PER_ITEM: usize = 1;
+
+/// Default token budget of the examples section
+pub const DEFAULT_EXAMPLES_BUDGET: usize = 600;
+
+/// Characters per token assumed when sizing the examples section
+const CHARS_PER_TOKEN: usize = 4;
+
+/// An example call and, optionally, its response
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "ExampleRepr", into = "ExampleRepr")]
+pub struct FewShotExample {
+    /// The call as the model would emit it
+    pub call: Value,
+    /// What the tool or skill returned (a string is shown as is)
+    pub response: Option<Value>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum ExampleRepr {
+    Pair(ExamplePair),
+    Call(Value),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ExamplePair {
+    call: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    response: Option<Value>,
+}
+
+impl From<ExampleRepr> for FewShotExample {
+    fn from(repr: ExampleRepr) -> Self {
+        match repr {
+            ExampleRepr::Pair(ExamplePair { call, response }) => Self { call, response },
+            ExampleRepr::Call(call) => Self {
+                call,
+                response: None,
+            },
+        }
+    }
+}
+
+impl From<FewShotExample> for ExampleRepr {
+    fn from(example: FewShotExample) -> Self {
+        match example.response {
+            None => Self::Call(example.call),
+            response => Self::Pair(ExamplePair {
+                call: example.call,
+                response,
+            }),
+        }
+    }
+}
+
+impl FewShotExample {
+    /// The example as prompt text: the call, then its response
+    pub fn render(&self) -> String {
+        match &self.response {
+            None => self.call.to_string(),
+            Some(Value::String(text)) => format!("{}\nResult: {}", self.call, text.trim_end()),
+            Some(value) => format!("{}\nResult: {}", self.call, value),
+        }
+    }
+}
+
+/// How many examples go into the system prompt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FewShotPolicy {
+    /// Examples per tool or skill (0 leaves them out)
+    pub per_item: usize,
+    /// Tokens the examples section may take
+    pub budget_tokens: usize,
+}
+
+impl Default for FewShotPolicy {
+    fn default() -> Self {
+        Self {
+            per_item: DEFAULT_EXAMPLES_PER_ITEM,
+            budget_tokens: DEFAULT_EXAMPLES_BUDGET,
+        }
+    }
+}
+
+impl FewShotPolicy {
+    pub fn with_per_item(mut self, per_item: usize) -> Self {
+        self.per_item = per_item;
+        self
+    }
+
+    pub fn with_budget(mut self, tokens: usize) -> Self {
+        self.budget_tokens = tokens;
+        self
+    }
+
+    /// The examples section of the system prompt for `items` (the examples of
+    /// each tool or skill); empty if no example fits
+    ///
+    /// Examples are taken breadth first, every item's first example before
+    /// any second one, so a tight budget still covers as many tools as it
+    /// can. An example that does not fit is skipped; a shorter later one may
+    /// still go in.
+    pub fn build_prompt(&self, items: &[&[FewShotExample]]) -> String {
+        let mut chosen: Vec<(usize, String)> = Vec::new();
+        let mut used = 0;
+        for round in 0..self.per_item {
+            for (index, examples) in items.iter().enumerate() {
+                let Some(example) = examples.get(round) else {
+                    continue;
+                };
+                let text = example.render();
+                let tokens = estimate_tokens(&text);
+                if used + tokens <= self.budget_tokens {
+                    used += tokens;
+                    chosen.push((index, text));
+                }
+            }
+        }
+        if chosen.is_empty() {
+            return String::new();
+        }
+
+        // Group each item's examples together, in item order
+        chosen.sort_by_key(|(index, _)| *index);
+        let mut out = String::from("Examples:");
+        for (_, text) in chosen {
+            out.push_str("\n\n");
+            out.push_str(&text);
+        }
+        out
+    }
+}
+
+/// Rough token count of `text`
+fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn examples(yaml: &str) -> Vec<FewShotExample> {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_example_forms() {
+        let parsed = examples(
+            r#"
+- {"tool": "shell", "command": "ls"}
+- call: {"tool": "shell", "command": "wc -l README.md"}
+  response: "42 README.md\n"
+- call: {"skill": "extract", "text": "a@b.com", "target": "email"}
+  response: {"email": ["a@b.com"]}
+"#,
+        );
+        assert_eq!(parsed[0].response, None);
+        assert_eq!(parsed[0].render(), r#"{"command":"ls","tool":"shell"}"#);
+        assert_eq!(
+            parsed[1].render(),
+            "{\"command\":\"wc -l README.md\",\"tool\":\"shell\"}\nResult: 42 README.md"
+        );
+        assert!(parsed[2]
+            .render()
+            .ends_with(r#"Result: {"email":["a@b.com"]}"#));
+
+        // A call with a parameter named `call` is still a bare call
+        let bare = examples(r#"- {"tool": "phone", "call": "555"}"#);
+        assert_eq!(bare[0].call, json!({"tool": "phone", "call": "555"}));
+
+        let round_trip: Vec<FewShotExample> =
+            serde_json::from_value(serde_json::to_value(&parsed).unwrap()).unwrap();
+        assert_eq!(round_trip, parsed);
+    }
+
+    #[test]
+    fn test_selection_is_breadth_first_and_budgeted() {
+        let shell = examples(
+            r#"
+- {"tool": "shell", "command": "ls"}
+- {"tool": "shell", "command": "pwd"}
+"#,
+        );
+        let math = examples(r#"- {"tool": "eval_math", "expression": "1 + 1"}"#);
+        let items = [shell.as_slice(), math.as_slice()];
+
+        let all = FewShotPolicy::default()
+            .with_per_item(2)
+            .build_prompt(&items);
+        assert_eq!(
+            all,
+            "Examples:\n\n{\"command\":\"ls\",\"tool\":\"shell\"}\n\n\
+             {\"command\":\"pwd\",\"tool\":\"shell\"}\n\n\
+             {\"expression\":\"1 + 1\",\"tool\":\"eval_math\"}"
+        );
+
+        // Room for two: both tools' first examples beat shell's second
+        let tight = FewShotPolicy::default()
+            .with_per_item(2)
+            .with_budget(20)
+            .build_prompt(&items);
+        assert!(tight.contains("ls") && tight.contains("eval_math") && !tight.contains("pwd"));
+
+        assert_eq!(
+            FewShotPolicy::default()
+                .with_per_item(0)
+                .build_prompt(&items),
+            ""
+        );
+        assert_eq!(
+            FewShotPolicy::default().with_budget(1).build_prompt(&items),
+            ""
+        );
+    }
+}
@@ -5,8 +5,11 @@
 //!
 //! This is NOT safety moderation - it's correctness validation.
 
-use crate::agent::AgentState;
-use crate::tool::{ToolRequest, ToolResult};
+use crate::agent::{AgentState, Role};
+use crate::prelude::*;
+use crate::tool::{ContentKind, ToolRequest, ToolResult};
+use crate::tool_manifest::SafetyClass;
+use alloc::sync::Arc;
 
 /// Result of guardrail validation
 #[derive(Debug, Clone)]
@@ -15,6 +18,13 @@ pub enum GuardrailResult {
     Accept,
     /// Output is invalid and should be rejected
     Reject { reason: String },
+    /// Confidence in the output, from 0.0 (implausible) to 1.0 (plausible)
+    ///
+    /// Fuzzy validators (relevance, LLM-judge confidence) score instead of
+    /// vetoing. A chain combines the scores of its guards into a weighted mean
+    /// and rejects when it falls below the chain's threshold, so no single
+    /// heuristic decides alone. Chains never return this variant.
+    Score(f32),
 }
 
 impl GuardrailResult {
@@ -28,6 +38,15 @@ impl GuardrailResult {
         }
     }
 
+    /// A scoring verdict, clamped to 0.0..=1.0 (NaN counts as 0.0)
+    pub fn score(score: f32) -> Self {
+        Self::Score(if score.is_nan() {
+            0.0
+        } else {
+            score.clamp(0.0, 1.0)
+        })
+    }
+
     pub fn is_accept(&self) -> bool {
         matches!(self, Self::Accept)
     }
@@ -35,6 +54,67 @@ impl GuardrailResult {
     pub fn is_reject(&self) -> bool {
         matches!(self, Self::Reject { .. })
     }
+
+    /// The score of a scoring verdict
+    pub fn as_score(&self) -> Option<f32> {
+        match self {
+            Self::Score(score) => Some(*score),
+            _ => None,
+        }
+    }
+}
+
+/// Score threshold of a new chain
+pub const DEFAULT_SCORE_THRESHOLD: f32 = 0.5;
+
+/// Weighted mean of the scoring verdicts in one chain run
+#[derive(Debug, Default)]
+struct ScoreTally {
+    weighted_sum: f32,
+    total_weight: f32,
+    scores: Vec<String>,
+}
+
+impl ScoreTally {
+    /// Fold one guard's verdict into the tally
+    ///
+    /// Returns the verdict back if it is a rejection, which ends the chain.
+    fn add(
+        &mut self,
+        name: &str,
+        weight: f32,
+        verdict: GuardrailResult,
+    ) -> Option<GuardrailResult> {
+        match verdict {
+            GuardrailResult::Accept => None,
+            GuardrailResult::Score(score) => {
+                self.weighted_sum += score * weight;
+                self.total_weight += weight;
+                self.scores.push(format!("{} {:.2}", name, score));
+                None
+            }
+            reject => Some(reject),
+        }
+    }
+
+    /// Accept unless the weighted mean of the scores is below `threshold`
+    fn verdict(self, threshold: f32) -> GuardrailResult {
+        if self.total_weight <= 0.0 {
+            return GuardrailResult::Accept;
+        }
+
+        let score = self.weighted_sum / self.total_weight;
+        if score < threshold {
+            GuardrailResult::reject(format!(
+                "Guardrail score {:.2} is below the threshold {:.2} ({})",
+                score,
+                threshold,
+                self.scores.join(", ")
+            ))
+        } else {
+            GuardrailResult::Accept
+        }
+    }
 }
 
 /// Context provided to guardrails for validation
@@ -56,7 +136,9 @@ pub struct GuardrailContext<'a> {
 /// This is inspired by:
 /// - Mozilla.ai's any-guardrail unified validation pattern
 /// - agent.cpp's after_tool_execution lifecycle hook
-pub trait SemanticGuardrail {
+///
+/// Guardrails are `Send + Sync` so one chain can be shared across threads.
+pub trait SemanticGuardrail: Send + Sync {
     /// Validate a tool output
     ///
     /// Returns Accept if output is plausible, Reject if invalid.
@@ -68,37 +150,131 @@ pub trait SemanticGuardrail {
     }
 }
 
+/// A shared, cheaply cloned guardrail
+///
+/// Lets one guard instance sit in several chains (e.g. the agent loop's and a
+/// server's), keeping any state it holds in common.
+#[derive(Clone)]
+pub struct BoxedGuard(Arc<dyn SemanticGuardrail>);
+
+impl BoxedGuard {
+    pub fn new(guard: impl SemanticGuardrail + 'static) -> Self {
+        Self(Arc::new(guard))
+    }
+}
+
+impl From<Box<dyn SemanticGuardrail>> for BoxedGuard {
+    fn from(guard: Box<dyn SemanticGuardrail>) -> Self {
+        Self(Arc::from(guard))
+    }
+}
+
+impl SemanticGuardrail for BoxedGuard {
+    fn validate(&self, context: &GuardrailContext) -> GuardrailResult {
+        self.0.validate(context)
+    }
+
+    fn name(&self) -> &str {
+        self.0.name()
+    }
+}
+
+impl core::fmt::Debug for BoxedGuard {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("BoxedGuard").field(&self.name()).finish()
+    }
+}
+
 /// Composable chain of guardrails
 ///
 /// Executes guards in order. First rejection stops evaluation.
 /// This mirrors any-guardrail's "swap validators without changing consumers" philosophy.
+///
+/// Guards are reference-counted: cloning a chain shares them rather than
+/// copying them.
+///
+/// A tool can bring validators of its own results (e.g. the structure of its
+/// JSON output), registered with [`add_tool_validator`](Self::add_tool_validator).
+/// They run before the shared guards, on that tool's results only.
+#[derive(Clone)]
 pub struct GuardrailChain {
-    guards: Vec<Box<dyn SemanticGuardrail>>,
+    guards: Vec<(BoxedGuard, f32)>,
+    tool_validators: Vec<(String, BoxedGuard)>,
+    threshold: f32,
 }
 
 impl GuardrailChain {
     /// Create an empty guardrail chain
     pub fn new() -> Self {
-        Self { guards: Vec::new() }
+        Self {
+            guards: Vec::new(),
+            tool_validators: Vec::new(),
+            threshold: DEFAULT_SCORE_THRESHOLD,
+        }
+    }
+
+    /// Add a validator of `tool`'s results, run before the shared guards
+    pub fn add_tool_validator(mut self, tool: impl Into<String>, validator: BoxedGuard) -> Self {
+        self.tool_validators.push((tool.into(), validator));
+        self
     }
 
     /// Add a guardrail to the chain
-    pub fn add(mut self, guard: Box<dyn SemanticGuardrail>) -> Self {
-        self.guards.push(guard);
+    pub fn add(self, guard: Box<dyn SemanticGuardrail>) -> Self {
+        self.add_shared(guard.into())
+    }
+
+    /// Add a guardrail shared with other chains
+    pub fn add_shared(self, guard: BoxedGuard) -> Self {
+        self.add_weighted(guard, 1.0)
+    }
+
+    /// Add a guardrail whose scores count `weight` times in the chain's mean
+    ///
+    /// The weight has no effect on Accept and Reject verdicts.
+    pub fn add_weighted(mut self, guard: BoxedGuard, weight: f32) -> Self {
+        self.guards.push((guard, weight.max(0.0)));
         self
     }
 
-    /// Run all guardrails in order
+    /// Minimum weighted mean score for the chain to accept
+    pub fn with_threshold(mut self, threshold: f32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Names of the guardrails, in evaluation order
+    pub fn names(&self) -> Vec<&str> {
+        self.guards.iter().map(|(guard, _)| guard.name()).collect()
+    }
+
+    /// Names of the validators of `tool`'s results, in evaluation order
+    pub fn tool_validator_names(&self, tool: &str) -> Vec<&str> {
+        self.tool_validators
+            .iter()
+            .filter(|(name, _)| name == tool)
+            .map(|(_, validator)| validator.name())
+            .collect()
+    }
+
+    /// Run the tool's validators, then all guardrails, in order
     ///
-    /// Returns the first rejection, or Accept if all pass.
+    /// Returns the first rejection. Otherwise the weighted mean of any scores
+    /// decides against the threshold; without scores the chain accepts.
     pub fn validate(&self, context: &GuardrailContext) -> GuardrailResult {
-        for guard in &self.guards {
-            let result = guard.validate(context);
-            if result.is_reject() {
-                return result;
+        let mut tally = ScoreTally::default();
+        let tool = &context.tool_request.tool;
+        for (_, validator) in self.tool_validators.iter().filter(|(name, _)| name == tool) {
+            if let Some(reject) = tally.add(validator.name(), 1.0, validator.validate(context)) {
+                return reject;
             }
         }
-        GuardrailResult::Accept
+        for (guard, weight) in &self.guards {
+            if let Some(reject) = tally.add(guard.name(), *weight, guard.validate(context)) {
+                return reject;
+            }
+        }
+        tally.verdict(self.threshold)
     }
 
     /// Check if chain is empty
@@ -121,10 +297,16 @@ impl Default for GuardrailChain {
 /// Minimal plausibility guardrail
 ///
 /// Rejects outputs that are obviously invalid:
-/// - Empty output
+/// - Empty output, unless the command legitimately prints nothing
+///   (`mkdir`, `touch`, `rm`, a `grep` that matched nothing)
 /// - Metadata-only output (e.g. "total 12345")
+/// - A bare `wc` count when the user asked for names
 /// - Outputs with no task-relevant content
 ///
+/// Results of `eval_math` are exact by construction, so a short one like `46`
+/// is accepted. JSON outputs ([`ContentKind::Json`]) only need to parse, and a
+/// reference to binary data only needs to be there.
+///
 /// This is sanity checking, not full semantic correctness.
 pub struct PlausibilityGuard;
 
@@ -160,6 +342,15 @@ impl PlausibilityGuard {
         false
     }
 
+    /// Check if output is a single `wc` count line (`42` or `42 file.txt`)
+    fn is_bare_count(output: &str) -> bool {
+        let trimmed = output.trim();
+        let parts: Vec<&str> = trimmed.split_whitespace().collect();
+        trimmed.lines().count() == 1
+            && (1..=2).contains(&parts.len())
+            && parts[0].chars().all(|c| c.is_ascii_digit())
+    }
+
     /// Check if output has minimal substance
     fn has_minimal_substance(output: &str) -> bool {
         let trimmed = output.trim();
@@ -192,10 +383,34 @@ impl SemanticGuardrail for PlausibilityGuard {
             return GuardrailResult::Accept;
         }
 
+        // A computed value is the whole answer, however short
+        if context.tool_request.tool == crate::math::TOOL_NAME {
+            return GuardrailResult::Accept;
+        }
+
         let output = &context.tool_result.output;
 
-        // Check for empty output
+        // Structured outputs are judged by their syntax, not as prose
+        match context.tool_result.content_kind {
+            ContentKind::Json => {
+                return match serde_json::from_str::<serde_json::Value>(output) {
+                    Ok(_) => GuardrailResult::Accept,
+                    Err(e) => {
+                        GuardrailResult::reject(format!("Tool output is not valid JSON: {}", e))
+                    }
+                };
+            }
+            kind if kind.is_ref() && !output.trim().is_empty() => return GuardrailResult::Accept,
+            _ => {}
+        }
+
+        let command = shell_command(context.tool_request);
+
+        // Check for empty output, unless the command legitimately prints nothing
         if output.trim().is_empty() {
+            if command.is_some_and(expects_empty_output) {
+                return GuardrailResult::Accept;
+            }
             return GuardrailResult::reject("Tool output is empty - no data returned");
         }
 
@@ -206,6 +421,16 @@ impl SemanticGuardrail for PlausibilityGuard {
             );
         }
 
+        // A bare count is an answer to "how many", not to "which"
+        if command.is_some_and(|c| last_program(c) == Some("wc")) && Self::is_bare_count(output) {
+            if asks_for_names(context.state) {
+                return GuardrailResult::reject(
+                    "Tool output is only a count (wc), not the names requested",
+                );
+            }
+            return GuardrailResult::Accept;
+        }
+
         // Check for minimal substance
         if !Self::has_minimal_substance(output) {
             return GuardrailResult::reject("Tool output lacks substantive content");
@@ -219,6 +444,444 @@ impl SemanticGuardrail for PlausibilityGuard {
     }
 }
 
+/// Commands that succeed without printing anything
+const SILENT_COMMANDS: &[&str] = &[
+    "mkdir", "touch", "rm", "rmdir", "mv", "cp", "ln", "chmod", "chown", "true", ":", "cd",
+];
+
+/// Search commands, for which empty output means "no match"
+const SEARCH_COMMANDS: &[&str] = &["grep", "egrep", "fgrep", "rg"];
+
+/// Words in a query asking for names rather than a count
+const NAME_REQUEST_WORDS: &[&str] = &["list", "name", "names", "which", "show"];
+
+/// Phrases in a query asking for a count
+const COUNT_REQUEST_PHRASES: &[&str] = &["how many", "count", "number of"];
+
+/// The command line of a shell tool request
+fn shell_command(request: &ToolRequest) -> Option<&str> {
+    if request.tool != "shell" {
+        return None;
+    }
+    request.params.get("command").and_then(|v| v.as_str())
+}
+
+/// Program names in a command line, in order (`a | b && c` gives a, b, c)
+///
+/// Leading `VAR=value` assignments and `sudo` are skipped; paths are reduced to
+/// the program name.
+fn command_programs(command: &str) -> Vec<&str> {
+    command
+        .split(['|', '&', ';'])
+        .filter_map(|segment| {
+            segment
+                .split_whitespace()
+                .find(|word| !word.contains('=') && *word != "sudo")
+        })
+        .map(|program| program.rsplit('/').next().unwrap_or(program))
+        .collect()
+}
+
+/// The program whose output the tool returns
+fn last_program(command: &str) -> Option<&str> {
+    command_programs(command).last().copied()
+}
+
+/// Whether a command's output comes from a search command
+///
+/// Searches print nothing (and exit with status 1) when nothing matched, which
+/// is a valid result rather than a failure.
+pub fn is_search_command(command: &str) -> bool {
+    last_program(command).is_some_and(|program| SEARCH_COMMANDS.contains(&program))
+}
+
+/// Whether a command legitimately prints nothing: a search, or only commands
+/// that are silent on success (`mkdir`, `touch`, `rm`, ...)
+fn expects_empty_output(command: &str) -> bool {
+    let programs = command_programs(command);
+    is_search_command(command)
+        || (!programs.is_empty()
+            && programs
+                .iter()
+                .all(|program| SILENT_COMMANDS.contains(program)))
+}
+
+/// Whether the user's query asks for names (files, entries) rather than a count
+fn asks_for_names(state: &AgentState) -> bool {
+    let Some(query) = state
+        .history
+        .iter()
+        .find(|message| matches!(message.role, Role::User))
+    else {
+        return false;
+    };
+    let query = query.content.to_lowercase();
+    let words: Vec<&str> = query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect();
+
+    COUNT_REQUEST_PHRASES
+        .iter()
+        .all(|phrase| !query.contains(phrase))
+        && NAME_REQUEST_WORDS.iter().any(|word| words.contains(word))
+}
+
+/// Context provided to pre-execution guardrails
+#[derive(Debug)]
+pub struct PreExecutionContext<'a> {
+    /// The agent state (conversation history, user query, etc.)
+    pub state: &'a AgentState,
+    /// The tool about to be invoked
+    pub tool_request: &'a ToolRequest,
+    /// Safety class declared in the tool's manifest
+    pub safety: SafetyClass,
+}
+
+/// Guardrail run before a tool executes
+///
+/// Unlike [`SemanticGuardrail`], which judges outputs, these decide whether a
+/// request may run at all, typically from the tool's safety class. A rejection
+/// fails the tool without executing it or asking the user.
+pub trait PreExecutionGuardrail: Send + Sync {
+    /// Check a tool request before execution
+    fn check(&self, context: &PreExecutionContext) -> GuardrailResult;
+
+    /// Optional name for debugging
+    fn name(&self) -> &str {
+        "unnamed_pre_execution_guardrail"
+    }
+}
+
+/// Composable chain of pre-execution guardrails
+///
+/// Executes guards in order. First rejection stops evaluation. Cloning shares
+/// the guards.
+#[derive(Clone)]
+pub struct PreExecutionChain {
+    guards: Vec<(Arc<dyn PreExecutionGuardrail>, f32)>,
+    threshold: f32,
+}
+
+impl Default for PreExecutionChain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PreExecutionChain {
+    /// Create an empty chain
+    pub fn new() -> Self {
+        Self {
+            guards: Vec::new(),
+            threshold: DEFAULT_SCORE_THRESHOLD,
+        }
+    }
+
+    /// Add a guardrail to the chain
+    pub fn add(self, guard: Box<dyn PreExecutionGuardrail>) -> Self {
+        self.add_weighted(guard, 1.0)
+    }
+
+    /// Add a guardrail whose scores count `weight` times in the chain's mean
+    pub fn add_weighted(mut self, guard: Box<dyn PreExecutionGuardrail>, weight: f32) -> Self {
+        self.guards.push((Arc::from(guard), weight.max(0.0)));
+        self
+    }
+
+    /// Minimum weighted mean score for the chain to accept
+    pub fn with_threshold(mut self, threshold: f32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Run all guardrails in order
+    ///
+    /// Returns the first rejection. Otherwise the weighted mean of any scores
+    /// decides against the threshold; without scores the chain accepts.
+    pub fn check(&self, context: &PreExecutionContext) -> GuardrailResult {
+        let mut tally = ScoreTally::default();
+        for (guard, weight) in &self.guards {
+            if let Some(reject) = tally.add(guard.name(), *weight, guard.check(context)) {
+                return reject;
+            }
+        }
+        tally.verdict(self.threshold)
+    }
+
+    /// Check if chain is empty
+    pub fn is_empty(&self) -> bool {
+        self.guards.is_empty()
+    }
+
+    /// Number of guardrails in chain
+    pub fn len(&self) -> usize {
+        self.guards.len()
+    }
+}
+
+/// Rejects tools whose safety class exceeds a ceiling
+///
+/// E.g. `SafetyCeilingGuard::new(SafetyClass::ReadOnly)` keeps an agent
+/// strictly observational.
+pub struct SafetyCeilingGuard {
+    max: SafetyClass,
+}
+
+impl SafetyCeilingGuard {
+    pub fn new(max: SafetyClass) -> Self {
+        Self { max }
+    }
+}
+
+impl PreExecutionGuardrail for SafetyCeilingGuard {
+    fn check(&self, context: &PreExecutionContext) -> GuardrailResult {
+        if context.safety > self.max {
+            return GuardrailResult::reject(format!(
+                "Tool '{}' is {}, above the allowed maximum '{}'",
+                context.tool_request.tool,
+                context.safety.as_str(),
+                self.max.as_str()
+            ));
+        }
+        GuardrailResult::Accept
+    }
+
+    fn name(&self) -> &str {
+        "safety_ceiling_guard"
+    }
+}
+
+/// Context provided to final-answer guardrails
+#[derive(Debug)]
+pub struct AnswerContext<'a> {
+    /// The agent state (conversation history, user query, etc.)
+    pub state: &'a AgentState,
+    /// The final answer the model produced
+    pub answer: &'a str,
+}
+
+/// Guardrail run on the model's final answer
+///
+/// Where [`SemanticGuardrail`] judges what a tool returned, these judge what
+/// the model concluded from it. A rejection sends the answer back to the
+/// model instead of returning it to the user.
+pub trait AnswerGuardrail: Send + Sync {
+    /// Check a final answer against the conversation
+    fn check(&self, context: &AnswerContext) -> GuardrailResult;
+
+    /// Optional name for debugging
+    fn name(&self) -> &str {
+        "unnamed_answer_guardrail"
+    }
+}
+
+/// Composable chain of final-answer guardrails
+///
+/// Executes guards in order. First rejection stops evaluation. Cloning shares
+/// the guards.
+#[derive(Clone)]
+pub struct AnswerChain {
+    guards: Vec<(Arc<dyn AnswerGuardrail>, f32)>,
+    threshold: f32,
+}
+
+impl Default for AnswerChain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AnswerChain {
+    /// Create an empty chain
+    pub fn new() -> Self {
+        Self {
+            guards: Vec::new(),
+            threshold: DEFAULT_SCORE_THRESHOLD,
+        }
+    }
+
+    /// Add a guardrail to the chain
+    pub fn add(self, guard: Box<dyn AnswerGuardrail>) -> Self {
+        self.add_weighted(guard, 1.0)
+    }
+
+    /// Add a guardrail whose scores count `weight` times in the chain's mean
+    pub fn add_weighted(mut self, guard: Box<dyn AnswerGuardrail>, weight: f32) -> Self {
+        self.guards.push((Arc::from(guard), weight.max(0.0)));
+        self
+    }
+
+    /// Minimum weighted mean score for the chain to accept
+    pub fn with_threshold(mut self, threshold: f32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Run all guardrails in order
+    ///
+    /// Returns the first rejection. Otherwise the weighted mean of any scores
+    /// decides against the threshold; without scores the chain accepts.
+    pub fn check(&self, context: &AnswerContext) -> GuardrailResult {
+        let mut tally = ScoreTally::default();
+        for (guard, weight) in &self.guards {
+            if let Some(reject) = tally.add(guard.name(), *weight, guard.check(context)) {
+                return reject;
+            }
+        }
+        tally.verdict(self.threshold)
+    }
+
+    /// Check if chain is empty
+    pub fn is_empty(&self) -> bool {
+        self.guards.is_empty()
+    }
+
+    /// Number of guardrails in chain
+    pub fn len(&self) -> usize {
+        self.guards.len()
+    }
+}
+
+/// Rejects final answers with figures the tools never produced
+///
+/// Every number in the answer must appear in a tool output, or be derivable
+/// from one by simple counting or summing:
+/// - the number of non-empty lines of an output (`ls` gives 3 files)
+/// - the sum of the numbers in an output (sizes, per-file counts)
+/// - the total number of non-empty lines across all outputs
+///
+/// Numbers from the user's query are allowed as well ("the 5 largest files").
+/// Answers given without any tool output are not checked: there is nothing to
+/// check them against.
+pub struct NumericConsistencyGuard;
+
+impl NumericConsistencyGuard {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Numbers the answer may mention, given the conversation so far
+    fn supported_numbers(state: &AgentState) -> Option<Vec<f64>> {
+        let outputs: Vec<&str> = state
+            .history
+            .iter()
+            .filter(|message| matches!(message.role, Role::Tool))
+            .map(|message| message.content.as_str())
+            .collect();
+        if outputs.is_empty() {
+            return None;
+        }
+
+        let mut supported = Vec::new();
+        let mut total_lines = 0;
+        for output in &outputs {
+            let numbers: Vec<f64> = extract_numbers(output)
+                .into_iter()
+                .map(|(_, n)| n)
+                .collect();
+            // The first line is the host's "Tool output:" header
+            let lines = output
+                .lines()
+                .skip(1)
+                .filter(|line| !line.trim().is_empty())
+                .count();
+
+            supported.push(lines as f64);
+            supported.push(numbers.iter().sum());
+            supported.extend(numbers);
+            total_lines += lines;
+        }
+        supported.push(total_lines as f64);
+
+        if let Some(query) = state
+            .history
+            .iter()
+            .find(|message| matches!(message.role, Role::User))
+        {
+            supported.extend(extract_numbers(&query.content).into_iter().map(|(_, n)| n));
+        }
+
+        Some(supported)
+    }
+}
+
+impl Default for NumericConsistencyGuard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AnswerGuardrail for NumericConsistencyGuard {
+    fn check(&self, context: &AnswerContext) -> GuardrailResult {
+        let Some(supported) = Self::supported_numbers(context.state) else {
+            return GuardrailResult::Accept;
+        };
+
+        let mut unsupported: Vec<&str> = extract_numbers(context.answer)
+            .into_iter()
+            .filter(|(_, n)| !supported.iter().any(|s| (s - n).abs() < 1e-9))
+            .map(|(text, _)| text)
+            .collect();
+        unsupported.dedup();
+
+        if unsupported.is_empty() {
+            GuardrailResult::Accept
+        } else {
+            GuardrailResult::reject(format!(
+                "Final answer contains figures not found in tool outputs: {}",
+                unsupported.join(", ")
+            ))
+        }
+    }
+
+    fn name(&self) -> &str {
+        "numeric_consistency_guard"
+    }
+}
+
+/// Numbers in a text, with the text they were parsed from
+///
+/// Accepts thousands separators (`1,024`) and decimals (`3.5`); a trailing
+/// period ends the number (`42.`). Signs are ignored.
+fn extract_numbers(text: &str) -> Vec<(&str, f64)> {
+    let bytes = text.as_bytes();
+    let mut numbers = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if !bytes[i].is_ascii_digit() {
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        let mut seen_point = false;
+        while i < bytes.len() {
+            let next_digits = bytes[i + 1..]
+                .iter()
+                .take_while(|b| b.is_ascii_digit())
+                .count();
+            match bytes[i] {
+                b if b.is_ascii_digit() => i += 1,
+                b',' if !seen_point && next_digits == 3 => i += 1,
+                b'.' if !seen_point && next_digits > 0 => {
+                    seen_point = true;
+                    i += 1;
+                }
+                _ => break,
+            }
+        }
+
+        let raw = &text[start..i];
+        if let Ok(value) = raw.replace(',', "").parse::<f64>() {
+            numbers.push((raw, value));
+        }
+    }
+
+    numbers
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -241,6 +904,7 @@ mod tests {
         let state = AgentState::new("test");
         let request = ToolRequest {
             tool: "shell".to_string(),
+            call_id: None,
             params: json!({"command": "ls"}),
         };
         let result = ToolResult::success("file1.txt\nfile2.txt\n");
@@ -252,11 +916,32 @@ mod tests {
         assert!(validation.is_accept());
     }
 
+    #[test]
+    fn test_plausibility_guard_checks_json_syntax() {
+        let state = AgentState::new("Which tables are there?");
+        let request = ToolRequest {
+            tool: "sql".to_string(),
+            call_id: None,
+            params: json!({"query": "SELECT name FROM sqlite_master"}),
+        };
+        let guard = PlausibilityGuard::new();
+
+        let valid = ToolResult::success(r#"{"rows": []}"#).with_content_kind(ContentKind::Json);
+        assert!(guard
+            .validate(&make_context(&state, &request, &valid))
+            .is_accept());
+        let cut = ToolResult::success(r#"{"rows": [["us"#).with_content_kind(ContentKind::Json);
+        assert!(guard
+            .validate(&make_context(&state, &request, &cut))
+            .is_reject());
+    }
+
     #[test]
     fn test_plausibility_guard_rejects_empty() {
         let state = AgentState::new("test");
         let request = ToolRequest {
             tool: "shell".to_string(),
+            call_id: None,
             params: json!({"command": "ls"}),
         };
         let result = ToolResult::success("");
@@ -273,6 +958,7 @@ mod tests {
         let state = AgentState::new("test");
         let request = ToolRequest {
             tool: "shell".to_string(),
+            call_id: None,
             params: json!({"command": "ls -l"}),
         };
         let result = ToolResult::success("total 7079928");
@@ -292,6 +978,7 @@ mod tests {
         let state = AgentState::new("test");
         let request = ToolRequest {
             tool: "shell".to_string(),
+            call_id: None,
             params: json!({"command": "ls -l"}),
         };
         let result = ToolResult::success("total 8\n-rw-r--r-- 1 user group 1234 file.txt");
@@ -303,11 +990,136 @@ mod tests {
         assert!(validation.is_accept());
     }
 
+    #[test]
+    fn test_plausibility_guard_accepts_empty_from_silent_commands() {
+        let state = AgentState::new("Create a build directory");
+        let guard = PlausibilityGuard::new();
+        let result = ToolResult::success("");
+
+        for command in [
+            "mkdir -p build",
+            "touch a.txt && rm b.txt",
+            "true",
+            "ls | grep nothing",
+            "/usr/bin/grep -r TODO src",
+        ] {
+            let request = ToolRequest {
+                tool: "shell".to_string(),
+                call_id: None,
+                params: json!({ "command": command }),
+            };
+            let ctx = make_context(&state, &request, &result);
+            assert!(guard.validate(&ctx).is_accept(), "{}", command);
+        }
+
+        // Silent commands followed by one that should print still need output
+        let request = ToolRequest {
+            tool: "shell".to_string(),
+            call_id: None,
+            params: json!({"command": "mkdir -p build && ls build"}),
+        };
+        let ctx = make_context(&state, &request, &result);
+        assert!(guard.validate(&ctx).is_reject());
+    }
+
+    #[test]
+    fn test_plausibility_guard_wc_counts() {
+        let request = ToolRequest {
+            tool: "shell".to_string(),
+            call_id: None,
+            params: json!({"command": "ls | wc -l"}),
+        };
+        let result = ToolResult::success("      12\n");
+        let guard = PlausibilityGuard::new();
+
+        let state = AgentState::new("List the files in this directory");
+        let validation = guard.validate(&make_context(&state, &request, &result));
+        assert!(validation.is_reject());
+        if let GuardrailResult::Reject { reason } = validation {
+            assert!(reason.contains("count"));
+        }
+
+        let state = AgentState::new("How many files are in this directory?");
+        let validation = guard.validate(&make_context(&state, &request, &result));
+        assert!(validation.is_accept());
+    }
+
+    #[test]
+    fn test_plausibility_guard_accepts_computed_values() {
+        let state = AgentState::new("How many days are left until March?");
+        let request = ToolRequest {
+            tool: "eval_math".to_string(),
+            call_id: None,
+            params: json!({"expression": "date('2024-03-01') - date('2024-02-25')"}),
+        };
+        let result = ToolResult::success("5");
+        let guard = PlausibilityGuard::new();
+        assert!(guard
+            .validate(&make_context(&state, &request, &result))
+            .is_accept());
+    }
+
+    #[test]
+    fn test_is_search_command() {
+        assert!(is_search_command("cat log.txt | grep error"));
+        assert!(is_search_command("LC_ALL=C rg foo"));
+        assert!(!is_search_command("grep foo a.txt | wc -l"));
+    }
+
+    #[test]
+    fn test_extract_numbers() {
+        let numbers: Vec<f64> = extract_numbers("1,024 files, 3.5 MB and 42. Version 1.2.3")
+            .into_iter()
+            .map(|(_, n)| n)
+            .collect();
+        assert_eq!(numbers, vec![1024.0, 3.5, 42.0, 1.2, 3.0]);
+    }
+
+    #[test]
+    fn test_numeric_consistency_guard() {
+        let guard = NumericConsistencyGuard::new();
+        let mut state = AgentState::new("How many files are in the directory?");
+
+        // Nothing to check against yet
+        let ctx = AnswerContext {
+            state: &state,
+            answer: "There are 7 files.",
+        };
+        assert!(guard.check(&ctx).is_accept());
+
+        state.add_message(Role::Tool, "Tool output:\na.txt\nb.txt\nc.log\n");
+        state.add_message(Role::Tool, "Tool output:\n  120 a.txt\n   30 b.txt\n");
+
+        for answer in [
+            "There are 3 files.",      // line count
+            "a.txt has 120 lines.",    // appears in output
+            "Together they have 150.", // sum
+            "5 lines were listed.",    // total line count
+        ] {
+            let ctx = AnswerContext {
+                state: &state,
+                answer,
+            };
+            assert!(guard.check(&ctx).is_accept(), "{}", answer);
+        }
+
+        let ctx = AnswerContext {
+            state: &state,
+            answer: "There are 7 files totalling 2,000 lines.",
+        };
+        let result = guard.check(&ctx);
+        assert!(result.is_reject());
+        if let GuardrailResult::Reject { reason } = result {
+            assert!(reason.ends_with("7, 2,000"));
+        }
+    }
+
     #[test]
     fn test_guardrail_chain() {
         let state = AgentState::new("test");
         let request = ToolRequest {
             tool: "shell".to_string(),
+            call_id: None,
             params: json!({"command": "ls"}),
         };
         let result = ToolResult::success("total 123");
@@ -320,6 +1132,126 @@ mod tests {
         assert!(validation.is_reject());
     }
 
+    #[test]
+    fn test_safety_ceiling_guard() {
+        let state = AgentState::new("test");
+        let request = ToolRequest {
+            tool: "shell".to_string(),
+            call_id: None,
+            params: json!({"command": "rm -rf build"}),
+        };
+        let chain =
+            PreExecutionChain::new().add(Box::new(SafetyCeilingGuard::new(SafetyClass::Mutating)));
+
+        let ctx = PreExecutionContext {
+            state: &state,
+            tool_request: &request,
+            safety: SafetyClass::Destructive,
+        };
+        let validation = chain.check(&ctx);
+        assert!(validation.is_reject());
+        if let GuardrailResult::Reject { reason } = validation {
+            assert!(reason.contains("destructive"));
+        }
+
+        let ctx = PreExecutionContext {
+            safety: SafetyClass::Mutating,
+            ..ctx
+        };
+        assert!(chain.check(&ctx).is_accept());
+    }
+
+    #[test]
+    fn test_cloned_chains_share_guards() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        struct CountingGuard(AtomicUsize);
+
+        impl SemanticGuardrail for CountingGuard {
+            fn validate(&self, _context: &GuardrailContext) -> GuardrailResult {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                GuardrailResult::Accept
+            }
+
+            fn name(&self) -> &str {
+                "counting_guard"
+            }
+        }
+
+        let counter = Arc::new(CountingGuard(AtomicUsize::new(0)));
+        let shared = BoxedGuard(counter.clone());
+        let chain = GuardrailChain::new()
+            .add(Box::new(PlausibilityGuard::new()))
+            .add_shared(shared.clone());
+        let server_chain = chain.clone();
+        let other_chain = GuardrailChain::new().add_shared(shared);
+
+        let state = AgentState::new("List files");
+        let request = ToolRequest {
+            tool: "shell".to_string(),
+            call_id: None,
+            params: json!({"command": "ls"}),
+        };
+        let result = ToolResult::success("file1.txt\nfile2.txt");
+        let ctx = make_context(&state, &request, &result);
+
+        for chain in [&chain, &server_chain, &other_chain] {
+            assert!(chain.validate(&ctx).is_accept());
+        }
+        assert_eq!(counter.0.load(Ordering::SeqCst), 3);
+        assert_eq!(
+            server_chain.names(),
+            vec!["plausibility_guard", "counting_guard"]
+        );
+    }
+
+    #[test]
+    fn test_chain_combines_weighted_scores() {
+        struct FixedScore(&'static str, f32);
+
+        impl SemanticGuardrail for FixedScore {
+            fn validate(&self, _context: &GuardrailContext) -> GuardrailResult {
+                GuardrailResult::score(self.1)
+            }
+
+            fn name(&self) -> &str {
+                self.0
+            }
+        }
+
+        let state = AgentState::new("List files");
+        let request = ToolRequest {
+            tool: "shell".to_string(),
+            call_id: None,
+            params: json!({"command": "ls"}),
+        };
+        let result = ToolResult::success("file1.txt\nfile2.txt");
+        let ctx = make_context(&state, &request, &result);
+
+        // (0.2 * 1 + 0.9 * 3) / 4 = 0.725: the low score alone does not veto
+        let chain = GuardrailChain::new()
+            .add(Box::new(PlausibilityGuard::new()))
+            .add(Box::new(FixedScore("relevance", 0.2)))
+            .add_weighted(BoxedGuard::new(FixedScore("judge", 0.9)), 3.0);
+        assert!(chain.validate(&ctx).is_accept());
+
+        let strict = chain.clone().with_threshold(0.8);
+        let verdict = strict.validate(&ctx);
+        assert!(verdict.is_reject());
+        if let GuardrailResult::Reject { reason } = verdict {
+            assert!(reason.contains("0.72"));
+            assert!(reason.contains("relevance 0.20"));
+        }
+
+        // A binary rejection still vetoes regardless of scores
+        let empty = ToolResult::success("");
+        let ctx = make_context(&state, &request, &empty);
+        assert!(chain.validate(&ctx).is_reject());
+
+        assert_eq!(GuardrailResult::score(1.5).as_score(), Some(1.0));
+        assert_eq!(GuardrailResult::score(f32::NAN).as_score(), Some(0.0));
+    }
+
     #[test]
     fn test_guardrail_chain_stops_on_first_reject() {
         struct AlwaysReject;
@@ -339,6 +1271,7 @@ mod tests {
         let state = AgentState::new("test");
         let request = ToolRequest {
             tool: "shell".to_string(),
+            call_id: None,
             params: json!({"command": "ls"}),
         };
         let result = ToolResult::success("data");
@@ -352,4 +1285,52 @@ mod tests {
 
         assert!(validation.is_reject());
     }
+
+    #[test]
+    fn test_tool_validators_run_first_on_their_tool() {
+        struct NeedsHeader;
+        impl SemanticGuardrail for NeedsHeader {
+            fn validate(&self, context: &GuardrailContext) -> GuardrailResult {
+                if context.tool_result.output.starts_with("name,") {
+                    GuardrailResult::Accept
+                } else {
+                    GuardrailResult::reject("csv_export output has no header row")
+                }
+            }
+
+            fn name(&self) -> &str {
+                "csv_header"
+            }
+        }
+
+        let chain = GuardrailChain::new()
+            .add(Box::new(PlausibilityGuard::new()))
+            .add_tool_validator("csv_export", BoxedGuard::new(NeedsHeader));
+        assert_eq!(chain.tool_validator_names("csv_export"), vec!["csv_header"]);
+        assert!(chain.tool_validator_names("shell").is_empty());
+
+        let state = AgentState::new("Export the users");
+        let export = ToolRequest {
+            tool: "csv_export".to_string(),
+            call_id: None,
+            params: json!({}),
+        };
+        let rows = ToolResult::success("alice,42\nbob,7");
+        let verdict = chain.validate(&make_context(&state, &export, &rows));
+        assert!(
+            matches!(&verdict, GuardrailResult::Reject { reason } if reason.contains("header row")),
+            "{:?}",
+            verdict
+        );
+
+        // Other tools' results skip the validator
+        let shell = ToolRequest {
+            tool: "shell".to_string(),
+            call_id: None,
+            params: json!({"command": "cat users.csv"}),
+        };
+        assert!(chain
+            .validate(&make_context(&state, &shell, &rows))
+            .is_accept());
+    }
 }
@@ -0,0 +1,223 @@
+//! Test support for guardrail authors
+//!
+//! Guardrail contexts borrow their state, request and result, which makes
+//! every test start with the same handful of `let` bindings.
+//! [`GuardrailFixture`] owns all three and hands out contexts for semantic,
+//! pre-execution and answer guardrails. The `assert_*` helpers print the
+//! guard's reason when a verdict is not the expected one.
+
+use crate::agent::{apply_tool_result, AgentState, Role};
+use crate::guardrail::{
+    AnswerContext, GuardrailContext, GuardrailResult, PreExecutionContext, SemanticGuardrail,
+};
+use crate::prelude::*;
+use crate::tool::{ToolRequest, ToolResult};
+use crate::tool_manifest::SafetyClass;
+use serde_json::{json, Value};
+
+/// A query asking for names
+pub const LISTING_QUERY: &str = "List the files in the current directory";
+
+/// A query asking for a number
+pub const COUNTING_QUERY: &str = "How many files are in the current directory?";
+
+/// Plausible `ls` output
+pub const FILE_LISTING: &str = "Cargo.toml\nREADME.md\nsrc\n";
+
+/// `ls -l` output of an empty directory: metadata, no data
+pub const METADATA_ONLY: &str = "total 0\n";
+
+/// Owned state, tool request and tool result for building guardrail contexts
+///
+/// Starts as a successful `shell` call with no command and no output.
+#[derive(Debug, Clone)]
+pub struct GuardrailFixture {
+    pub state: AgentState,
+    pub request: ToolRequest,
+    pub result: ToolResult,
+}
+
+impl GuardrailFixture {
+    /// A fixture for a conversation that starts with `query`
+    pub fn new(query: impl Into<String>) -> Self {
+        Self {
+            state: AgentState::new(query),
+            request: ToolRequest {
+                tool: "shell".to_string(),
+                call_id: None,
+                params: json!({}),
+            },
+            result: ToolResult::success(""),
+        }
+    }
+
+    /// Set the invoked tool and its parameters
+    pub fn with_tool(mut self, tool: impl Into<String>, params: Value) -> Self {
+        self.request = ToolRequest {
+            tool: tool.into(),
+            call_id: None,
+            params,
+        };
+        self
+    }
+
+    /// Set the invoked tool to `shell` running `command`
+    pub fn with_command(self, command: &str) -> Self {
+        self.with_tool("shell", json!({ "command": command }))
+    }
+
+    /// Make the tool succeed with `output`
+    pub fn with_output(mut self, output: impl Into<String>) -> Self {
+        self.result = ToolResult::success(output);
+        self
+    }
+
+    /// Make the tool fail with `error`
+    pub fn with_failure(mut self, error: impl Into<String>) -> Self {
+        self.result = ToolResult::failure(error);
+        self
+    }
+
+    /// Append a message to the conversation history
+    pub fn with_message(mut self, role: Role, content: impl Into<String>) -> Self {
+        self.state.add_message(role, content);
+        self
+    }
+
+    /// Append an earlier tool output to the history, as the host records it
+    pub fn with_tool_output(mut self, output: impl Into<String>) -> Self {
+        apply_tool_result(&mut self.state, &ToolResult::success(output));
+        self
+    }
+
+    /// Context for a [`SemanticGuardrail`]
+    pub fn context(&self) -> GuardrailContext<'_> {
+        GuardrailContext {
+            state: &self.state,
+            tool_request: &self.request,
+            tool_result: &self.result,
+        }
+    }
+
+    /// Context for a pre-execution guardrail, with the request's safety class
+    pub fn pre_execution(&self, safety: SafetyClass) -> PreExecutionContext<'_> {
+        PreExecutionContext {
+            state: &self.state,
+            tool_request: &self.request,
+            safety,
+        }
+    }
+
+    /// Context for an answer guardrail judging `answer`
+    pub fn answer<'a>(&'a self, answer: &'a str) -> AnswerContext<'a> {
+        AnswerContext {
+            state: &self.state,
+            answer,
+        }
+    }
+}
+
+/// Assert that a verdict accepts
+#[track_caller]
+pub fn assert_accept(result: &GuardrailResult) {
+    match result {
+        GuardrailResult::Accept => {}
+        GuardrailResult::Reject { reason } => panic!("expected Accept, got Reject: {}", reason),
+        GuardrailResult::Score(score) => panic!("expected Accept, got Score({})", score),
+    }
+}
+
+/// Assert that a verdict is a score within `min..=max`
+#[track_caller]
+pub fn assert_score(result: &GuardrailResult, min: f32, max: f32) {
+    match result.as_score() {
+        Some(score) => assert!(
+            (min..=max).contains(&score),
+            "expected Score in {}..={}, got Score({})",
+            min,
+            max,
+            score
+        ),
+        None => panic!("expected Score in {}..={}, got {:?}", min, max, result),
+    }
+}
+
+/// Assert that a verdict rejects with a reason containing `reason_contains`
+#[track_caller]
+pub fn assert_reject(result: &GuardrailResult, reason_contains: &str) {
+    match result {
+        GuardrailResult::Accept | GuardrailResult::Score(_) => {
+            panic!(
+                "expected Reject containing {:?}, got {:?}",
+                reason_contains, result
+            )
+        }
+        GuardrailResult::Reject { reason } => assert!(
+            reason.contains(reason_contains),
+            "expected Reject containing {:?}, got Reject: {}",
+            reason_contains,
+            reason
+        ),
+    }
+}
+
+/// Assert that `guard` accepts the fixture's tool output
+#[track_caller]
+pub fn assert_accepts(guard: &dyn SemanticGuardrail, fixture: &GuardrailFixture) {
+    assert_accept(&guard.validate(&fixture.context()));
+}
+
+/// Assert that `guard` rejects the fixture's tool output with a reason
+/// containing `reason_contains`
+#[track_caller]
+pub fn assert_rejects(
+    guard: &dyn SemanticGuardrail,
+    fixture: &GuardrailFixture,
+    reason_contains: &str,
+) {
+    assert_reject(&guard.validate(&fixture.context()), reason_contains);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::guardrail::{
+        AnswerGuardrail, NumericConsistencyGuard, PlausibilityGuard, PreExecutionGuardrail,
+        SafetyCeilingGuard,
+    };
+
+    #[test]
+    fn test_fixture_contexts() {
+        let guard = PlausibilityGuard::new();
+
+        let listing = GuardrailFixture::new(LISTING_QUERY).with_command("ls");
+        assert_accepts(&guard, &listing.clone().with_output(FILE_LISTING));
+        assert_rejects(
+            &guard,
+            &listing.clone().with_output(METADATA_ONLY),
+            "metadata",
+        );
+        assert_accepts(&guard, &listing.with_failure("ls: cannot access"));
+
+        let counting = GuardrailFixture::new(COUNTING_QUERY)
+            .with_tool_output(FILE_LISTING)
+            .with_command("rm -rf build");
+        assert_accept(&NumericConsistencyGuard::new().check(&counting.answer("There are 3.")));
+        assert_reject(
+            &SafetyCeilingGuard::new(SafetyClass::ReadOnly)
+                .check(&counting.pre_execution(SafetyClass::Destructive)),
+            "destructive",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "expected Reject containing \"empty\", got Accept")]
+    fn test_assert_reject_reports_verdict() {
+        assert_reject(&GuardrailResult::Accept, "empty");
+    }
+
+    #[test]
+    fn test_assert_score() {
+        assert_score(&GuardrailResult::score(0.7), 0.5, 1.0);
+    }
+}
@@ -0,0 +1,276 @@
+//! Token budget for the history in a prompt, by role
+//!
+//! A run's history grows with every call, and long tool outputs can push the
+//! question itself out of a small context window. [`HistoryBudget`] shortens
+//! the history a prompt is rendered from, leaving the state untouched:
+//!
+//! - user messages (the query, rejections, corrections) are always kept whole
+//! - each tool output is cut to [`HistoryBudget::with_tool_output_tokens`]
+//! - the last [`HistoryBudget::with_full_assistant_turns`] assistant turns
+//!   (named agents' turns included) are
+//!   kept whole; older ones are cut to
+//!   [`HistoryBudget::with_older_turn_tokens`]
+//!
+//! With [`HistoryBudget::with_pruned_corrections`], corrective retries the
+//! run has recovered from are left out as well: a rejected answer or refused
+//! call with the message saying so, and calls whose output a guardrail
+//! rejected. A correction counts as recovered from once a tool result follows
+//! it; until then it is shown, once if the model repeated the same mistake.
+//!
+//! The system prompt is not part of the history and is never cut. The default
+//! budget cuts nothing.
+
+use crate::agent::{Message, Role, ANSWER_REJECTED, REQUEST_REFUSED};
+use crate::prelude::*;
+
+/// Default [`HistoryBudget::with_older_turn_tokens`]
+pub const DEFAULT_OLDER_TURN_TOKENS: usize = 64;
+
+/// How much of each message a prompt may show
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryBudget {
+    tool_output_tokens: Option<usize>,
+    full_assistant_turns: Option<usize>,
+    older_turn_tokens: usize,
+    prune_corrections: bool,
+}
+
+impl Default for HistoryBudget {
+    fn default() -> Self {
+        Self {
+            tool_output_tokens: None,
+            full_assistant_turns: None,
+            older_turn_tokens: DEFAULT_OLDER_TURN_TOKENS,
+            prune_corrections: false,
+        }
+    }
+}
+
+impl HistoryBudget {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Tokens each tool output may take
+    pub fn with_tool_output_tokens(mut self, tokens: usize) -> Self {
+        self.tool_output_tokens = Some(tokens);
+        self
+    }
+
+    /// Assistant turns, counted from the end, that are kept whole
+    pub fn with_full_assistant_turns(mut self, turns: usize) -> Self {
+        self.full_assistant_turns = Some(turns);
+        self
+    }
+
+    /// Tokens each older assistant turn may take
+    pub fn with_older_turn_tokens(mut self, tokens: usize) -> Self {
+        self.older_turn_tokens = tokens;
+        self
+    }
+
+    /// Leave out the corrective retries the run has recovered from
+    pub fn with_pruned_corrections(mut self) -> Self {
+        self.prune_corrections = true;
+        self
+    }
+
+    /// Whether the budget shows every message whole
+    pub fn is_unlimited(&self) -> bool {
+        self.tool_output_tokens.is_none()
+            && self.full_assistant_turns.is_none()
+            && !self.prune_corrections
+    }
+
+    /// `history` with each message cut to its budget
+    pub fn apply(&self, history: &[Message]) -> Vec<Message> {
+        if self.prune_corrections {
+            return self.cut(&prune_corrections(history));
+        }
+        self.cut(history)
+    }
+
+    fn cut(&self, history: &[Message]) -> Vec<Message> {
+        let assistant_turns = history
+            .iter()
+            .filter(|message| message.role.is_model())
+            .count();
+        let older_turns = self
+            .full_assistant_turns
+            .map_or(0, |full| assistant_turns.saturating_sub(full));
+
+        let mut seen_turns = 0;
+        history
+            .iter()
+            .map(|message| {
+                let limit = match message.role {
+                    Role::User => None,
+                    Role::Tool => self.tool_output_tokens,
+                    Role::Assistant | Role::Agent(_) => {
+                        seen_turns += 1;
+                        (seen_turns <= older_turns).then_some(self.older_turn_tokens)
+                    }
+                };
+                match limit {
+                    Some(tokens) => Message {
+                        content: cut_to_tokens(&message.content, tokens),
+                        ..message.clone()
+                    },
+                    None => message.clone(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// `history` without the corrective retries a later tool result recovered
+/// from, and with a repeated correction shown only the last time
+fn prune_corrections(history: &[Message]) -> Vec<Message> {
+    let is_correction = |message: &Message| {
+        message.role == Role::User
+            && (message.content.starts_with(ANSWER_REJECTED)
+                || message.content.starts_with(REQUEST_REFUSED))
+    };
+    let last_result = history
+        .iter()
+        .rposition(|message| message.role == Role::Tool);
+    let recovered = |index: usize| last_result.is_some_and(|last| index < last);
+
+    let mut pruned = vec![false; history.len()];
+    for (index, message) in history.iter().enumerate() {
+        let next = history.get(index + 1);
+        if is_correction(message) {
+            let repeated = history[index + 1..]
+                .iter()
+                .any(|later| later.content == message.content);
+            if recovered(index) || repeated {
+                pruned[index] = true;
+                // The reply it corrects goes with it
+                if index > 0 && history[index - 1].role.is_model() {
+                    pruned[index - 1] = true;
+                }
+            }
+        } else if message.role.is_model()
+            && message.call_id.is_some()
+            && next.is_some_and(|next| next.role.is_model())
+            && recovered(index)
+        {
+            // A call without a result: a guardrail rejected its output
+            pruned[index] = true;
+        }
+    }
+
+    history
+        .iter()
+        .zip(pruned)
+        .filter(|(_, pruned)| !pruned)
+        .map(|(message, _)| message.clone())
+        .collect()
+}
+
+/// `text` cut to about `tokens` tokens (four characters each), with a note of
+/// what was left out
+fn cut_to_tokens(text: &str, tokens: usize) -> String {
+    let keep = tokens.saturating_mul(4);
+    let total = text.chars().count();
+    if total <= keep {
+        return text.to_string();
+    }
+    let head: String = text.chars().take(keep).collect();
+    format!(
+        "{}\n[... about {} more tokens not shown]",
+        head.trim_end(),
+        (total - keep).div_ceil(4)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::AgentState;
+
+    fn history() -> Vec<Message> {
+        let mut state = AgentState::new("Which file is largest?");
+        state.add_message(Role::Assistant, r#"{"tool": "shell", "command": "ls -lS"}"#);
+        state.add_message(Role::Tool, "x".repeat(400));
+        state.add_message(Role::Assistant, "The largest file is a.bin, really.");
+        state.add_message(Role::User, "Your answer was rejected: figures not found");
+        state.add_message(Role::Assistant, "a.bin");
+        state.history
+    }
+
+    #[test]
+    fn test_default_cuts_nothing() {
+        let history = history();
+        let budgeted = HistoryBudget::default().apply(&history);
+        assert!(HistoryBudget::default().is_unlimited());
+        assert_eq!(budgeted[2].content, history[2].content);
+    }
+
+    #[test]
+    fn test_tool_outputs_are_cut() {
+        let history = history();
+        let budgeted = HistoryBudget::new()
+            .with_tool_output_tokens(10)
+            .apply(&history);
+        assert_eq!(budgeted[0].content, "Which file is largest?");
+        assert!(budgeted[2].content.starts_with(&"x".repeat(40)));
+        assert!(budgeted[2]
+            .content
+            .ends_with("[... about 90 more tokens not shown]"));
+    }
+
+    #[test]
+    fn test_recent_assistant_turns_stay_whole() {
+        let history = history();
+        let budgeted = HistoryBudget::new()
+            .with_full_assistant_turns(2)
+            .with_older_turn_tokens(2)
+            .apply(&history);
+        // The oldest of three turns is cut; users and the last two are whole
+        assert!(budgeted[1].content.starts_with(r#"{"tool""#));
+        assert!(budgeted[1].content.contains("not shown"));
+        assert_eq!(budgeted[3].content, history[3].content);
+        assert_eq!(budgeted[4].content, history[4].content);
+        assert_eq!(budgeted[5].content, "a.bin");
+    }
+
+    #[test]
+    fn test_recovered_corrections_are_pruned() {
+        let mut state = AgentState::new("Which file is largest?");
+        let call = |state: &mut AgentState, command: &str| {
+            let id = state.next_call_id();
+            let call = format!(r#"{{"tool": "shell", "command": "{}"}}"#, command);
+            state.add_call_message(Role::Assistant, call, Some(id));
+            id
+        };
+        state.add_message(Role::Assistant, "a.bin, at 4 GB.");
+        state.reject_answer("figures not found");
+        call(&mut state, "wc -l *"); // its output was rejected by a guardrail
+        state.add_message(Role::Assistant, "a.bin, at 4 GB.");
+        state.reject_answer("figures not found");
+
+        // Not recovered yet: the repeated correction is shown once
+        let budget = HistoryBudget::new().with_pruned_corrections();
+        assert!(!budget.is_unlimited());
+        let pruned = budget.apply(&state.history);
+        assert_eq!(pruned.len(), 4);
+        assert!(pruned[1].content.contains("wc -l"));
+        assert!(pruned[3].content.starts_with(ANSWER_REJECTED));
+
+        let id = call(&mut state, "ls -lS");
+        state.add_call_message(Role::Tool, "Tool output:\na.bin", Some(id));
+        let pruned = budget.apply(&state.history);
+        let contents: Vec<&str> = pruned.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(
+            contents,
+            [
+                "Which file is largest?",
+                r#"{"tool": "shell", "command": "ls -lS"}"#,
+                "Tool output:\na.bin"
+            ]
+        );
+        // The state keeps the full record
+        assert_eq!(state.history.len(), 8);
+    }
+}
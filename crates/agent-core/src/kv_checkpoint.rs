@@ -0,0 +1,104 @@
+//! Backend KV cache positions, matched to the history they hold
+//!
+//! An in-process backend (llama.cpp) can save its context to a file and load
+//! it back, so a resumed session does not decode its transcript again. The
+//! saved cache is only good for the history it was built from: a
+//! [`KvCheckpoint`] records the history prefix (by SHA-256) and the cache
+//! position after it. A host resuming or rolling back a session restores the
+//! position of the latest checkpoint that still matches rather than trusting
+//! its own position counter, and starts from an empty cache when none does.
+
+use crate::agent::{AgentState, Message};
+use crate::audit::sha256_hex;
+use crate::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// The backend cache position after the first `messages` history messages
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KvCheckpoint {
+    pub messages: usize,
+    pub position: i32,
+    /// SHA-256 of those messages
+    pub history_sha256: String,
+}
+
+impl KvCheckpoint {
+    /// Checkpoint of `state`'s whole history at cache position `position`
+    pub fn of(state: &AgentState, position: i32) -> Self {
+        Self {
+            messages: state.history.len(),
+            position,
+            history_sha256: history_digest(&state.history),
+        }
+    }
+
+    /// Whether `state`'s history starts with the messages this checkpoint holds
+    pub fn matches(&self, state: &AgentState) -> bool {
+        state
+            .history
+            .get(..self.messages)
+            .is_some_and(|prefix| history_digest(prefix) == self.history_sha256)
+    }
+}
+
+/// The checkpoint to restore for `state`: the matching one covering the most
+/// messages
+pub fn resume_point<'a>(
+    checkpoints: impl IntoIterator<Item = &'a KvCheckpoint>,
+    state: &AgentState,
+) -> Option<&'a KvCheckpoint> {
+    checkpoints
+        .into_iter()
+        .filter(|checkpoint| checkpoint.matches(state))
+        .max_by_key(|checkpoint| checkpoint.messages)
+}
+
+fn history_digest(messages: &[Message]) -> String {
+    let mut bytes = Vec::new();
+    for message in messages {
+        // Messages always serialize; one per line keeps them apart
+        bytes.extend(serde_json::to_vec(message).unwrap_or_default());
+        bytes.push(b'\n');
+    }
+    sha256_hex(&bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::Role;
+
+    #[test]
+    fn test_checkpoint_matches_history_prefix() {
+        let mut state = AgentState::new("What is in /tmp?");
+        state.add_message(Role::Assistant, "{\"tool\": \"shell\"}");
+        let checkpoint = KvCheckpoint::of(&state, 120);
+        assert!(checkpoint.matches(&state));
+
+        state.add_message(Role::Tool, "a.txt");
+        assert!(checkpoint.matches(&state));
+
+        // A different reply at the same point does not match
+        let mut other = AgentState::new("What is in /tmp?");
+        other.add_message(Role::Assistant, "{\"tool\": \"search\"}");
+        assert!(!checkpoint.matches(&other));
+        // Nor does a shorter history
+        assert!(!checkpoint.matches(&AgentState::new("What is in /tmp?")));
+    }
+
+    #[test]
+    fn test_rollback_resumes_from_earlier_checkpoint() {
+        let mut state = AgentState::new("query");
+        let first = KvCheckpoint::of(&state, 40);
+        state.add_message(Role::Assistant, "call");
+        state.add_message(Role::Tool, "result");
+        let second = KvCheckpoint::of(&state, 95);
+        let checkpoints = [first.clone(), second.clone()];
+        assert_eq!(resume_point(&checkpoints, &state), Some(&second));
+
+        // Roll back and branch: only the first checkpoint still holds
+        state.rollback(1);
+        state.add_message(Role::Assistant, "another call");
+        assert_eq!(resume_point(&checkpoints, &state), Some(&first));
+    }
+}
@@ -7,27 +7,180 @@
 //! - Manage agent state and conversation history
 //! - Make deterministic decisions about next actions
 //! - Enforce semantic guardrails on outputs
+//! - Drive a whole run step by step from any host ([`AgentSession`])
 //!
 //! This crate compiles to `wasm32-unknown-unknown` without any feature flags.
+//!
+//! Without the default `std` feature it is `no_std` + `alloc`, for embedded
+//! targets and constrained WASM runtimes. YAML parsing (TOOL.md and SKILL.md
+//! frontmatter, [`profile`], [`eval`], [`task`], [`schedule`]) needs `std`; the decision logic,
+//! guardrails and sessions do not.
 
+#![cfg_attr(not(feature = "std"), no_std)]
 #![forbid(unsafe_code)]
 
+extern crate alloc;
+
+/// The `alloc` parts of the std prelude, for `no_std` builds
+mod prelude {
+    pub(crate) use alloc::boxed::Box;
+    pub(crate) use alloc::format;
+    pub(crate) use alloc::string::{String, ToString};
+    pub(crate) use alloc::vec;
+    pub(crate) use alloc::vec::Vec;
+}
+
 pub mod agent;
+pub mod approval;
+pub mod artifact;
+pub mod audit;
+pub mod auth;
+#[cfg(feature = "std")]
+pub mod batch;
+pub mod capabilities;
+pub mod chaos;
+pub mod confinement;
+pub mod critic;
+pub mod diff;
+pub mod embedding;
+pub mod environment;
+#[cfg(feature = "std")]
+pub mod eval;
+pub mod event_stream;
+pub mod extraction_batch;
+pub mod extraction_format;
+pub mod extraction_records;
+pub mod few_shot;
 pub mod guardrail;
+pub mod guardrail_testing;
+pub mod history_budget;
+pub mod kv_checkpoint;
+pub mod limits;
+pub mod math;
+pub mod memory;
+pub mod observation;
+pub mod outcome;
+pub mod participants;
+pub mod patch;
+pub mod plan;
+#[cfg(feature = "std")]
+pub mod profile;
+pub mod prompt_adaptation;
+pub mod prompt_template;
 pub mod protocol;
+pub mod rate_limit;
+#[cfg(feature = "raw-params")]
+pub mod raw_params;
+pub mod reasoning;
+pub mod response_spec;
+pub mod scenario;
+#[cfg(feature = "std")]
+pub mod schedule;
+pub mod search;
+pub mod session;
+pub mod session_store;
 pub mod skill;
+pub mod skill_checks;
+pub mod skill_guardrail;
 pub mod skill_manifest;
+pub mod sql;
+pub mod stop;
+pub mod sweep;
+#[cfg(feature = "std")]
+pub mod task;
+pub mod telemetry;
 pub mod tool;
+pub mod tool_manifest;
+pub mod transcript;
+pub mod undo;
+pub mod variables;
 
 // Re-export commonly used types
-pub use agent::{AgentDecision, AgentState, Message, Role};
+pub use agent::{AgentDecision, AgentState, Message, Role, StateError, STATE_VERSION};
+pub use approval::{ApprovalMode, ApprovalPolicy};
+pub use artifact::{ArtifactError, ArtifactPolicy, ArtifactRequest, ToolMoreRequest};
+pub use audit::{verify_log, AuditChain, AuditEntry, AuditError, AuditRecord};
+pub use auth::{ApiKey, ApiKeys, ApiKeysError, AuthError, Scope};
+#[cfg(feature = "std")]
+pub use batch::{
+    BatchError, BatchNotification, BatchRecord, BatchStatus, BatchSummary, BatchTask, NotifyOn,
+    OverrideBounds, TaskOverrides,
+};
+pub use capabilities::HostCapabilities;
+pub use chaos::{ChaosInjector, ChaosProfile, ChaosReport, Fault};
+pub use confinement::{ConfinementLevel, ConfinementPolicy};
+pub use critic::{critic_prompt, CriticGuard, CriticVerdict, Reviewer};
+pub use diff::unified_diff;
+pub use embedding::{
+    cosine_similarity, rank_by_similarity, Embedder, RelevanceGuard, DEFAULT_RELEVANCE_THRESHOLD,
+};
+pub use environment::{
+    environment_prompt, CalendarTime, ContextFact, ContextProvider, FixedContext, LocalTime,
+};
+#[cfg(feature = "std")]
+pub use eval::{CaseResult, CaseRun, EvalCase, EvalError, EvalSuite, EvalTarget, Expectation};
+pub use event_stream::{EventLog, ResumeError, StreamEvent};
+pub use extraction_batch::{ExtractionBatch, MergedExtraction, TextChunk, ValueProvenance};
+pub use extraction_format::{format_extraction, ExtractFormat};
+pub use extraction_records::{
+    parse_records, ExtractionRecord, ExtractionRecordResult, RecordsError,
+};
+pub use few_shot::{FewShotExample, FewShotPolicy};
 pub use guardrail::{
-    GuardrailChain, GuardrailContext, GuardrailResult, PlausibilityGuard, SemanticGuardrail,
+    AnswerChain, AnswerContext, AnswerGuardrail, BoxedGuard, GuardrailChain, GuardrailContext,
+    GuardrailResult, NumericConsistencyGuard, PlausibilityGuard, PreExecutionChain,
+    PreExecutionContext, PreExecutionGuardrail, SafetyCeilingGuard, SemanticGuardrail,
+};
+pub use history_budget::HistoryBudget;
+pub use kv_checkpoint::{resume_point, KvCheckpoint};
+pub use limits::{LimitPolicy, ResourceLimits};
+pub use math::{evaluate, MathError, MathValue};
+pub use memory::{
+    chunk_text, recall_output, session_note, MemoryEntry, MemoryStore, RecallRequest,
+};
+pub use observation::{ObservationMode, ObservationPolicy};
+pub use outcome::RunOutcome;
+pub use participants::Participants;
+pub use patch::{parse_patch, FilePatch, Patch, PatchError};
+pub use plan::{Plan, PlanError, PlanReview, PlanStep, StepReview};
+pub use prompt_adaptation::{AdaptationRule, PromptAdaptation};
+pub use prompt_template::{PromptTemplate, TemplateError};
+pub use protocol::{
+    format_tool_call, parse_model_output, parse_model_output_with, AnswerProtocol,
+    InconclusiveReason, ParseResult,
+};
+pub use rate_limit::{RateLimits, RateWindow};
+#[cfg(feature = "raw-params")]
+pub use raw_params::{parse_model_output_raw, RawParseResult, RawToolRequest};
+pub use reasoning::{split_reasoning, ReasoningDelimiters};
+pub use response_spec::{ResponseSpec, ResponseSpecGuard};
+#[cfg(feature = "std")]
+pub use schedule::{
+    CronSchedule, ScheduleError, ScheduleFile, ScheduledJob, ScheduledRun, ScheduledStatus,
+};
+pub use search::{
+    SearchError, SearchLimits, SearchMatch, SearchRequest, SearchResultValidator, SearchResults,
 };
-pub use protocol::{parse_model_output, ParseResult};
+pub use session::{AgentSession, PromptSpec, SessionError, SessionFailure, StepAction, ToolReview};
+pub use session_store::{OpenRun, Refused, SessionStore, StoreError};
 pub use skill::{
-    is_valid_skill, parse_skill_output, validate_extraction_output, ExtractionInput,
-    ExtractionOutput, ExtractionTarget, SkillError, SkillMetadata, SkillRequest, SkillResult,
+    is_valid_skill, normalize_extraction_output, parse_skill_json, parse_skill_output,
+    validate_extraction_output, EntityClass, ExtractionInput, ExtractionOutput, ExtractionTarget,
+    SkillError, SkillMetadata, SkillPostProcess, SkillRequest, SkillResult, SourceSpan, ValueSpans,
     AVAILABLE_SKILLS, EXTRACTION_SKILL,
 };
-pub use tool::{ToolRequest, ToolResult};
+pub use skill_checks::SkillChecks;
+#[cfg(feature = "std")]
+pub use skill_checks::{DeclaredChecks, InvalidSkillCheck};
+pub use skill_guardrail::{SkillGuardContext, SkillGuardrail, SkillGuardrailChain};
+pub use sql::{QueryResultValidator, QueryResults, SqlError, SqlLimits, SqlRequest};
+pub use stop::{stop_position, PromptPhase, StopSequences};
+pub use sweep::{SweepReport, SweepRun, SweepSetting};
+#[cfg(feature = "std")]
+pub use task::{TaskError, TaskFile, TaskVariable};
+pub use telemetry::ContentPolicy;
+pub use tool::{execute_builtin_tool, CallId, ContentKind, ToolRequest, ToolResult};
+pub use tool_manifest::{SafetyClass, ToolManifest, ToolRegistry};
+pub use transcript::{ChatTranscript, TranscriptEntry, TranscriptError};
+pub use undo::{plan_undo, FileUndo, SkipReason, UndoStep};
+pub use variables::{substitute_variables, VariableRequest};
@@ -0,0 +1,246 @@
+//! Resource limits for tool subprocesses
+//!
+//! Hosts cap what a process started by a tool may consume, so a runaway
+//! command (an endless loop, `yes | head -c 10G`) fails instead of taking the
+//! host down. Like approvals, limits key off the tool's [`SafetyClass`], with
+//! per-tool overrides; a rule only changes the limits it names.
+//!
+//! This module only decides the limits; hosts enforce them.
+
+use crate::prelude::*;
+use crate::tool_manifest::SafetyClass;
+use alloc::collections::BTreeMap;
+
+/// Limits for one process tree; `None` leaves a resource unlimited
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceLimits {
+    /// CPU time per process, in seconds
+    pub cpu_seconds: Option<u64>,
+    /// Address space per process, in bytes
+    pub memory_bytes: Option<u64>,
+    /// Processes the user may have running (the OS counts all of the user's
+    /// processes, not only the command's)
+    pub max_processes: Option<u64>,
+    /// Output kept from stdout and stderr each; the command is stopped when
+    /// it writes more
+    pub max_output_bytes: Option<u64>,
+}
+
+impl Default for ResourceLimits {
+    fn default() -> Self {
+        Self {
+            cpu_seconds: Some(60),
+            memory_bytes: None,
+            max_processes: None,
+            max_output_bytes: Some(10 * 1024 * 1024),
+        }
+    }
+}
+
+impl ResourceLimits {
+    /// No limits at all
+    pub fn unlimited() -> Self {
+        Self {
+            cpu_seconds: None,
+            memory_bytes: None,
+            max_processes: None,
+            max_output_bytes: None,
+        }
+    }
+
+    fn set(&mut self, kind: LimitKind, value: Option<u64>) {
+        match kind {
+            LimitKind::Cpu => self.cpu_seconds = value,
+            LimitKind::Memory => self.memory_bytes = value,
+            LimitKind::Processes => self.max_processes = value,
+            LimitKind::Output => self.max_output_bytes = value,
+        }
+    }
+}
+
+/// A resource a rule can limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LimitKind {
+    Cpu,
+    Memory,
+    Processes,
+    Output,
+}
+
+impl LimitKind {
+    fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "cpu" => Some(Self::Cpu),
+            "memory" | "mem" => Some(Self::Memory),
+            "procs" | "processes" => Some(Self::Processes),
+            "output" => Some(Self::Output),
+            _ => None,
+        }
+    }
+
+    /// Parse a value: seconds (`30`, `30s`, `2m`), sizes (`512M`, `1G`), or
+    /// `none`
+    fn parse_value(self, value: &str) -> Option<Option<u64>> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("none") {
+            return Some(None);
+        }
+
+        let split = value
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(value.len());
+        let (number, unit) = value.split_at(split);
+        let number: u64 = number.parse().ok()?;
+        let scale = match (self, unit.to_ascii_lowercase().as_str()) {
+            (_, "") => 1,
+            (Self::Cpu, "s") => 1,
+            (Self::Cpu, "m") => 60,
+            (Self::Cpu, "h") => 3600,
+            (Self::Memory | Self::Output, "k" | "kb") => 1024,
+            (Self::Memory | Self::Output, "m" | "mb") => 1024 * 1024,
+            (Self::Memory | Self::Output, "g" | "gb") => 1024 * 1024 * 1024,
+            _ => return None,
+        };
+        number.checked_mul(scale).map(Some)
+    }
+}
+
+/// Parse a size in bytes (`4096`, `512K`, `16M`, `1G`)
+pub fn parse_size(value: &str) -> Option<u64> {
+    LimitKind::Output.parse_value(value).flatten()
+}
+
+/// Limit settings of one rule target
+type Settings = Vec<(LimitKind, Option<u64>)>;
+
+/// Resource limits per safety class, with per-tool overrides
+///
+/// Starts from [`ResourceLimits::default`]: 60 seconds of CPU time and 10 MB of
+/// output.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LimitPolicy {
+    base: ResourceLimits,
+    classes: BTreeMap<SafetyClass, Settings>,
+    tools: BTreeMap<String, Settings>,
+}
+
+impl LimitPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the limits every tool starts from
+    pub fn with_base(mut self, limits: ResourceLimits) -> Self {
+        self.base = limits;
+        self
+    }
+
+    /// Apply a `target=limit:value,...` rule, where target is `all`, a safety
+    /// class or a tool name and limits are `cpu`, `memory`, `procs` and
+    /// `output` (e.g. `destructive=cpu:30s,memory:1G`, `shell=output:none`)
+    pub fn with_rule(mut self, rule: &str) -> Result<Self, String> {
+        let (target, spec) = rule.split_once('=').ok_or_else(|| {
+            format!(
+                "Invalid limit rule '{}'. Expected target=limit:value,...",
+                rule
+            )
+        })?;
+        let target = target.trim();
+        if target.is_empty() {
+            return Err(format!("Invalid limit rule '{}': empty target", rule));
+        }
+
+        let settings = spec
+            .split(',')
+            .map(|setting| {
+                let (name, value) = setting.split_once(':').unwrap_or((setting, ""));
+                let kind = LimitKind::from_str(name.trim()).ok_or_else(|| {
+                    format!(
+                        "Unknown limit '{}'. Expected one of: cpu, memory, procs, output",
+                        name.trim()
+                    )
+                })?;
+                let value = kind
+                    .parse_value(value)
+                    .ok_or_else(|| format!("Invalid value for {}: '{}'", name.trim(), value))?;
+                Ok((kind, value))
+            })
+            .collect::<Result<Settings, String>>()?;
+
+        match (target, SafetyClass::from_str(target)) {
+            ("all", _) => {
+                for (kind, value) in settings {
+                    self.base.set(kind, value);
+                }
+            }
+            (_, Some(class)) => self.classes.entry(class).or_default().extend(settings),
+            (tool, None) => self
+                .tools
+                .entry(tool.to_string())
+                .or_default()
+                .extend(settings),
+        }
+        Ok(self)
+    }
+
+    /// The limits for `tool` with the given safety class
+    pub fn decide(&self, tool: &str, class: SafetyClass) -> ResourceLimits {
+        let mut limits = self.base;
+        let settings = self
+            .classes
+            .get(&class)
+            .into_iter()
+            .chain(self.tools.get(tool));
+        for &(kind, value) in settings.flatten() {
+            limits.set(kind, value);
+        }
+        limits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_limits() {
+        let limits = LimitPolicy::default().decide("shell", SafetyClass::Destructive);
+        assert_eq!(limits.cpu_seconds, Some(60));
+        assert_eq!(limits.max_output_bytes, Some(10 * 1024 * 1024));
+        assert_eq!(limits.memory_bytes, None);
+    }
+
+    #[test]
+    fn test_rules_and_overrides() {
+        let policy = LimitPolicy::new()
+            .with_rule("all=procs:256")
+            .unwrap()
+            .with_rule("destructive=cpu:2m,memory:1G")
+            .unwrap()
+            .with_rule("shell=output:none")
+            .unwrap();
+
+        let shell = policy.decide("shell", SafetyClass::Destructive);
+        assert_eq!(shell.cpu_seconds, Some(120));
+        assert_eq!(shell.memory_bytes, Some(1024 * 1024 * 1024));
+        assert_eq!(shell.max_processes, Some(256));
+        assert_eq!(shell.max_output_bytes, None);
+
+        let fetch = policy.decide("fetch", SafetyClass::Network);
+        assert_eq!(fetch.cpu_seconds, Some(60));
+        assert_eq!(fetch.max_processes, Some(256));
+
+        assert!(LimitPolicy::new().with_rule("shell").is_err());
+        assert!(LimitPolicy::new().with_rule("shell=disk:1G").is_err());
+        assert!(LimitPolicy::new().with_rule("shell=cpu:1G").is_err());
+        assert!(LimitPolicy::new().with_rule("=cpu:10").is_err());
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("4096"), Some(4096));
+        assert_eq!(parse_size("16M"), Some(16 * 1024 * 1024));
+        assert_eq!(parse_size("none"), None);
+        assert_eq!(parse_size("2s"), None);
+    }
+}
@@ -0,0 +1,679 @@
+//! Arithmetic expression evaluation
+//!
+//! Backs the `eval_math` tool, so sums, percentages and date differences the
+//! model needs are computed rather than guessed. The evaluator is pure and
+//! deterministic (no clock, no I/O), which lets any host run it locally:
+//!
+//! ```text
+//! 1200 * 15% + 40          => 220
+//! round(7 / 3, 2)          => 2.33
+//! date("2024-03-01") - date("2024-01-15")   => 46
+//! date("2024-01-31") + 30  => 2024-03-01
+//! ```
+//!
+//! Operators: `+ - * /`, `^` (power), postfix `%` (divide by 100) and
+//! parentheses. Functions: `abs`, `sqrt`, `exp`, `ln`, `log10`, `floor`,
+//! `ceil`, `round(x[, digits])`, `pow`, `mod`, `min`, `max`, `sum`, `avg` and
+//! `date("YYYY-MM-DD")`; constants `pi` and `e`. Subtracting two dates gives
+//! days, and adding days to a date gives a date.
+//!
+//! Float functions come from `libm` rather than the platform, so results are
+//! the same on every host, with or without `std`.
+
+use crate::prelude::*;
+use crate::tool::{ToolRequest, ToolResult};
+use thiserror::Error;
+
+/// Name of the tool backed by this module
+pub const TOOL_NAME: &str = "eval_math";
+
+/// Manifest of the `eval_math` tool
+pub const MANIFEST: &str = include_str!("../tools/eval_math/TOOL.md");
+
+/// Deepest nesting of parentheses, calls and signs, so model input cannot
+/// overflow the host's stack
+const MAX_DEPTH: usize = 100;
+
+/// Years dates may fall in
+const YEARS: core::ops::RangeInclusive<i64> = 0..=9999;
+
+/// Expression errors
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum MathError {
+    #[error("Unexpected '{found}' at position {position}")]
+    Unexpected { found: String, position: usize },
+
+    #[error("Unexpected end of expression")]
+    UnexpectedEnd,
+
+    #[error("Unknown function or constant '{0}'")]
+    UnknownName(String),
+
+    #[error("{name}() takes {expected} argument(s)")]
+    Arity { name: String, expected: String },
+
+    #[error("Invalid date '{0}' (expected YYYY-MM-DD)")]
+    InvalidDate(String),
+
+    #[error("{0}")]
+    Type(String),
+
+    #[error("Division by zero")]
+    DivisionByZero,
+
+    #[error("Expression nests deeper than {} levels", MAX_DEPTH)]
+    TooDeep,
+
+    #[error("Result is not a finite number")]
+    NotFinite,
+}
+
+/// The value of an expression
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MathValue {
+    Number(f64),
+    /// Days since 1970-01-01
+    Date(i64),
+}
+
+impl core::fmt::Display for MathValue {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match *self {
+            MathValue::Number(n) if fract(n) == 0.0 && n.abs() < 1e15 => {
+                write!(f, "{}", n as i64)
+            }
+            MathValue::Number(n) if n != 0.0 && n.abs() < 1e-6 => write!(f, "{:e}", n),
+            MathValue::Number(n) => {
+                // Drop float noise like 0.30000000000000004
+                let text = format!("{:.10}", n);
+                write!(f, "{}", text.trim_end_matches('0').trim_end_matches('.'))
+            }
+            MathValue::Date(days) => match civil_from_days(days) {
+                Some((year, month, day)) => write!(f, "{:04}-{:02}-{:02}", year, month, day),
+                None => write!(f, "day {} after 1970-01-01", days),
+            },
+        }
+    }
+}
+
+/// Evaluate an expression
+pub fn evaluate(expression: &str) -> Result<MathValue, MathError> {
+    let tokens = tokenize(expression)?;
+    let mut parser = Parser {
+        tokens,
+        next: 0,
+        depth: 0,
+    };
+    let value = parser.expression()?;
+    if let Some((token, position)) = parser.tokens.get(parser.next) {
+        return Err(MathError::Unexpected {
+            found: token.to_string(),
+            position: *position,
+        });
+    }
+    match value {
+        MathValue::Number(n) if !n.is_finite() => Err(MathError::NotFinite),
+        value => Ok(value),
+    }
+}
+
+/// Execute an `eval_math` tool request
+pub fn execute(request: &ToolRequest) -> ToolResult {
+    let Some(expression) = request.params.get("expression").and_then(|v| v.as_str()) else {
+        return ToolResult::failure("Missing 'expression' parameter");
+    };
+    match evaluate(expression) {
+        Ok(value) => ToolResult::success(value.to_string()),
+        Err(e) => ToolResult::failure(e.to_string()),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Name(String),
+    Text(String),
+    Symbol(char),
+}
+
+impl core::fmt::Display for Token {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "{}", n),
+            Token::Name(name) => write!(f, "{}", name),
+            Token::Text(text) => write!(f, "\"{}\"", text),
+            Token::Symbol(c) => write!(f, "{}", c),
+        }
+    }
+}
+
+/// Tokens with their character positions (0-based)
+fn tokenize(expression: &str) -> Result<Vec<(Token, usize)>, MathError> {
+    let chars: Vec<char> = expression.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        if c.is_ascii_digit() || c == '.' {
+            while i < chars.len()
+                && (chars[i].is_ascii_digit() || chars[i] == '.' || chars[i] == '_')
+            {
+                i += 1;
+            }
+            // Exponent, e.g. 1.5e3
+            if i + 1 < chars.len()
+                && (chars[i] == 'e' || chars[i] == 'E')
+                && (chars[i + 1].is_ascii_digit()
+                    || (matches!(chars[i + 1], '+' | '-')
+                        && chars.get(i + 2).is_some_and(|c| c.is_ascii_digit())))
+            {
+                i += 2;
+                while i < chars.len() && chars[i].is_ascii_digit() {
+                    i += 1;
+                }
+            }
+            let text: String = chars[start..i].iter().filter(|&&c| c != '_').collect();
+            let number = text.parse().map_err(|_| MathError::Unexpected {
+                found: text.clone(),
+                position: start,
+            })?;
+            tokens.push((Token::Number(number), start));
+        } else if c.is_alphabetic() || c == '_' {
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let name: String = chars[start..i].iter().collect();
+            tokens.push((Token::Name(name.to_lowercase()), start));
+        } else if c == '"' || c == '\'' {
+            i += 1;
+            while i < chars.len() && chars[i] != c {
+                i += 1;
+            }
+            if i == chars.len() {
+                return Err(MathError::UnexpectedEnd);
+            }
+            tokens.push((Token::Text(chars[start + 1..i].iter().collect()), start));
+            i += 1;
+        } else if "+-*/^%(),".contains(c) {
+            tokens.push((Token::Symbol(c), start));
+            i += 1;
+        } else if c == '×' || c == '÷' {
+            tokens.push((Token::Symbol(if c == '×' { '*' } else { '/' }), start));
+            i += 1;
+        } else {
+            return Err(MathError::Unexpected {
+                found: c.to_string(),
+                position: start,
+            });
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Recursive descent over the token list
+///
+/// ```text
+/// expression := term (("+" | "-") term)*
+/// term       := unary (("*" | "/") unary)*
+/// unary      := ("-" | "+") unary | power
+/// power      := postfix ("^" unary)?
+/// postfix    := primary "%"*
+/// primary    := number | name | name "(" arguments ")" | "(" expression ")"
+/// ```
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    next: usize,
+    /// Nested `unary` calls, which every nesting goes through
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next).map(|(token, _)| token)
+    }
+
+    fn eat(&mut self, symbol: char) -> bool {
+        if self.peek() == Some(&Token::Symbol(symbol)) {
+            self.next += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<(), MathError> {
+        if self.eat(symbol) {
+            return Ok(());
+        }
+        match self.tokens.get(self.next) {
+            Some((token, position)) => Err(MathError::Unexpected {
+                found: token.to_string(),
+                position: *position,
+            }),
+            None => Err(MathError::UnexpectedEnd),
+        }
+    }
+
+    fn expression(&mut self) -> Result<MathValue, MathError> {
+        let mut value = self.term()?;
+        loop {
+            if self.eat('+') {
+                value = add(value, self.term()?)?;
+            } else if self.eat('-') {
+                value = subtract(value, self.term()?)?;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn term(&mut self) -> Result<MathValue, MathError> {
+        let mut value = self.unary()?;
+        loop {
+            if self.eat('*') {
+                value = MathValue::Number(number(value)? * number(self.unary()?)?);
+            } else if self.eat('/') {
+                let divisor = number(self.unary()?)?;
+                if divisor == 0.0 {
+                    return Err(MathError::DivisionByZero);
+                }
+                value = MathValue::Number(number(value)? / divisor);
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Result<MathValue, MathError> {
+        if self.depth == MAX_DEPTH {
+            return Err(MathError::TooDeep);
+        }
+        self.depth += 1;
+        let value = self.signed();
+        self.depth -= 1;
+        value
+    }
+
+    fn signed(&mut self) -> Result<MathValue, MathError> {
+        if self.eat('-') {
+            return Ok(MathValue::Number(-number(self.unary()?)?));
+        }
+        if self.eat('+') {
+            return self.unary();
+        }
+        self.power()
+    }
+
+    fn power(&mut self) -> Result<MathValue, MathError> {
+        let base = self.postfix()?;
+        if self.eat('^') {
+            let exponent = number(self.unary()?)?;
+            return Ok(MathValue::Number(libm::pow(number(base)?, exponent)));
+        }
+        Ok(base)
+    }
+
+    fn postfix(&mut self) -> Result<MathValue, MathError> {
+        let mut value = self.primary()?;
+        while self.eat('%') {
+            value = MathValue::Number(number(value)? / 100.0);
+        }
+        Ok(value)
+    }
+
+    fn primary(&mut self) -> Result<MathValue, MathError> {
+        let Some((token, position)) = self.tokens.get(self.next).cloned() else {
+            return Err(MathError::UnexpectedEnd);
+        };
+        self.next += 1;
+
+        match token {
+            Token::Number(n) => Ok(MathValue::Number(n)),
+            Token::Symbol('(') => {
+                let value = self.expression()?;
+                self.expect(')')?;
+                Ok(value)
+            }
+            Token::Name(name) if self.eat('(') => {
+                let arguments = self.arguments()?;
+                call(&name, &arguments)
+            }
+            Token::Name(name) => match name.as_str() {
+                "pi" => Ok(MathValue::Number(core::f64::consts::PI)),
+                "e" => Ok(MathValue::Number(core::f64::consts::E)),
+                _ => Err(MathError::UnknownName(name)),
+            },
+            token => Err(MathError::Unexpected {
+                found: token.to_string(),
+                position,
+            }),
+        }
+    }
+
+    /// Arguments after `(`, up to and including `)`
+    fn arguments(&mut self) -> Result<Vec<Argument>, MathError> {
+        let mut arguments = Vec::new();
+        if self.eat(')') {
+            return Ok(arguments);
+        }
+        loop {
+            match self.peek() {
+                Some(Token::Text(text)) => {
+                    arguments.push(Argument::Text(text.clone()));
+                    self.next += 1;
+                }
+                _ => arguments.push(Argument::Value(self.expression()?)),
+            }
+            if self.eat(')') {
+                return Ok(arguments);
+            }
+            self.expect(',')?;
+        }
+    }
+}
+
+enum Argument {
+    Value(MathValue),
+    Text(String),
+}
+
+fn number(value: MathValue) -> Result<f64, MathError> {
+    match value {
+        MathValue::Number(n) => Ok(n),
+        MathValue::Date(_) => Err(MathError::Type(
+            "Dates can only be subtracted from each other or shifted by days".to_string(),
+        )),
+    }
+}
+
+fn add(left: MathValue, right: MathValue) -> Result<MathValue, MathError> {
+    match (left, right) {
+        (MathValue::Date(days), MathValue::Number(n))
+        | (MathValue::Number(n), MathValue::Date(days)) => {
+            shift(days, days.checked_add(whole_days(n)?))
+        }
+        (left, right) => Ok(MathValue::Number(number(left)? + number(right)?)),
+    }
+}
+
+fn subtract(left: MathValue, right: MathValue) -> Result<MathValue, MathError> {
+    match (left, right) {
+        (MathValue::Date(a), MathValue::Date(b)) => a
+            .checked_sub(b)
+            .map(|days| MathValue::Number(days as f64))
+            .ok_or_else(out_of_range),
+        (MathValue::Date(days), MathValue::Number(n)) => {
+            shift(days, days.checked_sub(whole_days(n)?))
+        }
+        (left, right) => Ok(MathValue::Number(number(left)? - number(right)?)),
+    }
+}
+
+/// `shifted`, the date `days` moved by some days, if it is within [`YEARS`]
+fn shift(days: i64, shifted: Option<i64>) -> Result<MathValue, MathError> {
+    let in_range = |days| civil_from_days(days).is_some_and(|(year, _, _)| YEARS.contains(&year));
+    match shifted {
+        Some(shifted) if in_range(shifted) => Ok(MathValue::Date(shifted)),
+        _ => Err(MathError::Type(format!(
+            "{} moved that far is outside the years {} to {}",
+            MathValue::Date(days),
+            YEARS.start(),
+            YEARS.end()
+        ))),
+    }
+}
+
+fn out_of_range() -> MathError {
+    MathError::Type("Date difference out of range".to_string())
+}
+
+fn whole_days(n: f64) -> Result<i64, MathError> {
+    if fract(n) != 0.0 || n.abs() > 1e7 {
+        return Err(MathError::Type(format!(
+            "Dates can only be shifted by whole days, not {}",
+            n
+        )));
+    }
+    Ok(n as i64)
+}
+
+fn fract(n: f64) -> f64 {
+    n - libm::trunc(n)
+}
+
+fn rem_euclid(x: f64, y: f64) -> f64 {
+    let r = libm::fmod(x, y);
+    if r < 0.0 {
+        r + y.abs()
+    } else {
+        r
+    }
+}
+
+fn call(name: &str, arguments: &[Argument]) -> Result<MathValue, MathError> {
+    let arity = |expected: &str| MathError::Arity {
+        name: name.to_string(),
+        expected: expected.to_string(),
+    };
+
+    if name == "date" {
+        return match arguments {
+            [Argument::Text(text)] => parse_date(text).map(MathValue::Date),
+            _ => Err(arity("1 quoted")),
+        };
+    }
+
+    let values = arguments
+        .iter()
+        .map(|argument| match argument {
+            Argument::Value(value) => number(*value),
+            Argument::Text(text) => Err(MathError::Type(format!(
+                "Text \"{}\" is only allowed in date()",
+                text
+            ))),
+        })
+        .collect::<Result<Vec<f64>, MathError>>()?;
+
+    let result = match (name, values.as_slice()) {
+        ("abs", [x]) => x.abs(),
+        ("sqrt", [x]) => libm::sqrt(*x),
+        ("exp", [x]) => libm::exp(*x),
+        ("ln", [x]) => libm::log(*x),
+        ("log10", [x]) => libm::log10(*x),
+        ("floor", [x]) => libm::floor(*x),
+        ("ceil", [x]) => libm::ceil(*x),
+        ("round", [x]) => libm::round(*x),
+        ("round", [x, digits]) => {
+            let scale = libm::pow(10.0, libm::trunc(*digits));
+            libm::round(x * scale) / scale
+        }
+        ("pow", [x, y]) => libm::pow(*x, *y),
+        ("mod", [_, y]) if *y == 0.0 => return Err(MathError::DivisionByZero),
+        ("mod", [x, y]) => rem_euclid(*x, *y),
+        ("min", [first, rest @ ..]) => rest.iter().fold(*first, |a, b| a.min(*b)),
+        ("max", [first, rest @ ..]) => rest.iter().fold(*first, |a, b| a.max(*b)),
+        ("sum", values) => values.iter().sum(),
+        ("avg", [_, ..]) => values.iter().sum::<f64>() / values.len() as f64,
+        ("abs" | "sqrt" | "exp" | "ln" | "log10" | "floor" | "ceil", _) => return Err(arity("1")),
+        ("round", _) => return Err(arity("1 or 2")),
+        ("pow" | "mod", _) => return Err(arity("2")),
+        ("min" | "max" | "avg", _) => return Err(arity("at least 1")),
+        _ => return Err(MathError::UnknownName(name.to_string())),
+    };
+    Ok(MathValue::Number(result))
+}
+
+/// Days since 1970-01-01 of a `YYYY-MM-DD` date
+fn parse_date(text: &str) -> Result<i64, MathError> {
+    let invalid = || MathError::InvalidDate(text.to_string());
+    let parts: Vec<&str> = text.trim().split('-').collect();
+    let [year, month, day] = parts.as_slice() else {
+        return Err(invalid());
+    };
+    let year: i64 = year.parse().map_err(|_| invalid())?;
+    let month: i64 = month.parse().map_err(|_| invalid())?;
+    let day: i64 = day.parse().map_err(|_| invalid())?;
+    if !YEARS.contains(&year) {
+        return Err(invalid());
+    }
+
+    let leap = (year % 4 == 0 && year % 100 != 0) || year % 400 == 0;
+    let month_days = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap => 29,
+        2 => 28,
+        _ => return Err(invalid()),
+    };
+    if !(1..=month_days).contains(&day) {
+        return Err(invalid());
+    }
+    Ok(days_from_civil(year, month, day))
+}
+
+// Proleptic Gregorian calendar conversions (Howard Hinnant's algorithms)
+
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// `None` for days too far from 1970 to convert
+fn civil_from_days(days: i64) -> Option<(i64, i64, i64)> {
+    let days = days.checked_add(719_468)?;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = (era.checked_mul(400)?).checked_add(year_of_era + i64::from(month <= 2))?;
+    Some((year, month, day))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(expression: &str) -> String {
+        evaluate(expression).unwrap().to_string()
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!(eval("1 + 2 * 3"), "7");
+        assert_eq!(eval("(1 + 2) * 3"), "9");
+        assert_eq!(eval("-2 ^ 2"), "-4");
+        assert_eq!(eval("2 ^ 3 ^ 2"), "512");
+        assert_eq!(eval("0.1 + 0.2"), "0.3");
+        assert_eq!(eval("1200 * 15% + 40"), "220");
+        assert_eq!(eval("1_000_000 / 8"), "125000");
+        assert_eq!(eval("round(7 / 3, 2)"), "2.33");
+        assert_eq!(eval("sum(1, 2, 3.5) + max(4, 9, 2)"), "15.5");
+        assert_eq!(eval("avg(2, 4)"), "3");
+        assert_eq!(eval("mod(-7, 3)"), "2");
+        assert_eq!(eval("1.5e3"), "1500");
+        assert_eq!(eval("1 / 4e7"), "2.5e-8");
+    }
+
+    #[test]
+    fn test_dates() {
+        assert_eq!(eval(r#"date("2024-03-01") - date("2024-01-15")"#), "46");
+        assert_eq!(eval("date('2024-01-31') + 30"), "2024-03-01");
+        assert_eq!(eval("date('2000-03-01') - 1"), "2000-02-29");
+        assert_eq!(eval("date('1970-01-01') - 1"), "1969-12-31");
+        assert_eq!(
+            evaluate("date('2023-02-29')"),
+            Err(MathError::InvalidDate("2023-02-29".to_string()))
+        );
+        assert!(matches!(
+            evaluate("date('2024-01-01') * 2"),
+            Err(MathError::Type(_))
+        ));
+    }
+
+    #[test]
+    fn test_date_range() {
+        assert_eq!(
+            evaluate("date('99999999999999999-03-01')"),
+            Err(MathError::InvalidDate(
+                "99999999999999999-03-01".to_string()
+            ))
+        );
+        assert_eq!(eval("date('9999-12-30') + 1"), "9999-12-31");
+        assert!(matches!(
+            evaluate("date('9999-12-31') + 1"),
+            Err(MathError::Type(_))
+        ));
+        assert!(matches!(
+            evaluate("date('0000-01-01') - 9999999"),
+            Err(MathError::Type(_))
+        ));
+        assert_eq!(
+            MathValue::Date(i64::MAX).to_string(),
+            format!("day {} after 1970-01-01", i64::MAX)
+        );
+    }
+
+    #[test]
+    fn test_nesting_limit() {
+        let nested = |depth: usize| format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+        assert_eq!(eval(&nested(50)), "1");
+        assert_eq!(evaluate(&nested(200_000)), Err(MathError::TooDeep));
+        assert_eq!(evaluate(&"-".repeat(200_000)), Err(MathError::TooDeep));
+        assert_eq!(
+            evaluate(&format!("{}1{}", "abs(".repeat(500), ")".repeat(500))),
+            Err(MathError::TooDeep)
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(evaluate("1 / 0"), Err(MathError::DivisionByZero));
+        assert_eq!(evaluate("2 *"), Err(MathError::UnexpectedEnd));
+        assert_eq!(
+            evaluate("2 3"),
+            Err(MathError::Unexpected {
+                found: "3".to_string(),
+                position: 2
+            })
+        );
+        assert_eq!(
+            evaluate("foo(1)"),
+            Err(MathError::UnknownName("foo".to_string()))
+        );
+        assert!(matches!(evaluate("round()"), Err(MathError::Arity { .. })));
+        assert_eq!(evaluate("sqrt(-1)"), Err(MathError::NotFinite));
+    }
+
+    #[test]
+    fn test_execute_tool() {
+        let request: ToolRequest =
+            serde_json::from_str(r#"{"tool": "eval_math", "expression": "19.99 * 3"}"#).unwrap();
+        let result = execute(&request);
+        assert!(result.success);
+        assert_eq!(result.output, "59.97");
+
+        let manifest = crate::tool_manifest::parse_tool_manifest(MANIFEST).unwrap();
+        assert_eq!(manifest.frontmatter.name, TOOL_NAME);
+        assert!(manifest.check_request(&request).is_ok());
+    }
+}
@@ -0,0 +1,245 @@
+//! Long-term memory: recall over past tool outputs and session notes
+//!
+//! Artifacts keep a run's large outputs within reach of that run only. A
+//! [`MemoryStore`] keeps them across runs: hosts cut each tool output and each
+//! finished session's question and answer ([`session_note`]) into chunks,
+//! embed them and add them to a flat index they persist as JSON. A model
+//! looking for something an earlier run saw asks
+//!
+//! ```text
+//! {"recall": "where the staging database credentials live"}
+//! {"recall": "invoice totals from March", "k": 5}
+//! ```
+//!
+//! and the host answers with the chunks most similar to the query
+//! ([`recall_output`]), like a tool output. Vectors from different embedding
+//! models are not comparable, so a store records the model that built it.
+
+use crate::agent::{AgentState, Role};
+use crate::embedding::cosine_similarity;
+use crate::prelude::*;
+use crate::tool::CallId;
+use serde::{Deserialize, Serialize};
+
+/// Chunks a recall returns unless the model asks for another number
+pub const DEFAULT_RECALL_K: usize = 3;
+
+/// Most chunks one recall returns
+pub const MAX_RECALL_K: usize = 10;
+
+/// Characters of a chunk; longer texts are cut at line ends where possible
+pub const MEMORY_CHUNK_CHARS: usize = 1000;
+
+/// Instructions telling the model it can recall, for hosts with a memory
+pub const RECALL_INSTRUCTIONS: &str = r#"Earlier runs are kept in memory. To look up what an earlier run found, respond
+with JSON describing what you need:
+{"recall": "where the staging database credentials live"}"#;
+
+/// A request to search the memory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecallRequest {
+    /// What to look for
+    #[serde(rename = "recall")]
+    pub query: String,
+    /// Chunks to return (default [`DEFAULT_RECALL_K`], at most [`MAX_RECALL_K`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub k: Option<usize>,
+    /// The call this recall belongs to (`None` until the agent assigns one)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub call_id: Option<CallId>,
+}
+
+impl RecallRequest {
+    pub fn new(query: impl Into<String>) -> Self {
+        Self {
+            query: query.into(),
+            k: None,
+            call_id: None,
+        }
+    }
+
+    /// Chunks to return
+    pub fn k(&self) -> usize {
+        self.k.unwrap_or(DEFAULT_RECALL_K).clamp(1, MAX_RECALL_K)
+    }
+}
+
+/// One remembered chunk
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MemoryEntry {
+    /// Where the chunk came from (e.g. `<run id>/tool-003`)
+    pub source: String,
+    pub text: String,
+    pub embedding: Vec<f32>,
+}
+
+/// A flat index of remembered chunks
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MemoryStore {
+    /// The embedding model the entries were embedded with
+    pub model: String,
+    #[serde(default)]
+    pub entries: Vec<MemoryEntry>,
+}
+
+impl MemoryStore {
+    pub fn new(model: impl Into<String>) -> Self {
+        Self {
+            model: model.into(),
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, source: impl Into<String>, text: impl Into<String>, embedding: Vec<f32>) {
+        self.entries.push(MemoryEntry {
+            source: source.into(),
+            text: text.into(),
+            embedding,
+        });
+    }
+
+    /// Whether chunks from `source` are stored
+    pub fn has_source(&self, source: &str) -> bool {
+        self.entries.iter().any(|entry| entry.source == source)
+    }
+
+    /// The `k` entries most similar to `query`, most similar first
+    pub fn search(&self, query: &[f32], k: usize) -> Vec<(&MemoryEntry, f32)> {
+        let mut ranked: Vec<(&MemoryEntry, f32)> = self
+            .entries
+            .iter()
+            .map(|entry| (entry, cosine_similarity(query, &entry.embedding)))
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranked.truncate(k);
+        ranked
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Cut `text` into chunks of at most [`MEMORY_CHUNK_CHARS`] characters,
+/// skipping blank ones
+pub fn chunk_text(text: &str) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    let mut current_chars = 0;
+    for line in text.lines() {
+        let mut line: Vec<char> = line.chars().collect();
+        while !line.is_empty() {
+            let room = MEMORY_CHUNK_CHARS.saturating_sub(current_chars);
+            if line.len() + 1 > room && current_chars > 0 {
+                chunks.push(core::mem::take(&mut current));
+                current_chars = 0;
+                continue;
+            }
+            let take = line.len().min(MEMORY_CHUNK_CHARS);
+            current.extend(line.drain(..take));
+            current.push('\n');
+            current_chars += take + 1;
+        }
+    }
+    chunks.push(current);
+    chunks
+        .into_iter()
+        .map(|chunk| chunk.trim().to_string())
+        .filter(|chunk| !chunk.is_empty())
+        .collect()
+}
+
+/// A finished session's question and answer, to remember
+pub fn session_note(state: &AgentState) -> Option<String> {
+    let answer = state.final_answer.as_deref()?;
+    let query = state
+        .history
+        .iter()
+        .find(|message| matches!(message.role, Role::User))?;
+    Some(format!(
+        "Question: {}\nAnswer: {}",
+        query.content.trim(),
+        answer.trim()
+    ))
+}
+
+/// What the model sees for a recall: the hits, most similar first
+pub fn recall_output(request: &RecallRequest, hits: &[(&MemoryEntry, f32)]) -> String {
+    if hits.is_empty() {
+        return format!("Nothing in memory matches \"{}\".", request.query);
+    }
+    let mut output = format!("Memory for \"{}\":", request.query);
+    for (rank, (entry, similarity)) in hits.iter().enumerate() {
+        output.push_str(&format!(
+            "\n\n[{}] {} (similarity {:.2})\n{}",
+            rank + 1,
+            entry.source,
+            similarity,
+            entry.text
+        ));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_text() {
+        assert_eq!(chunk_text("one\ntwo\n\n"), vec!["one\ntwo"]);
+        assert!(chunk_text(" \n ").is_empty());
+
+        let line = "x".repeat(600);
+        let chunks = chunk_text(&format!("{}\n{}\n{}", line, line, "y".repeat(2500)));
+        let sizes: Vec<usize> = chunks.iter().map(|chunk| chunk.chars().count()).collect();
+        assert_eq!(sizes, vec![600, 600, 1000, 1000, 500]);
+    }
+
+    #[test]
+    fn test_recall_from_store() {
+        let mut store = MemoryStore::new("nomic-embed");
+        store.add(
+            "run-1/tool-001",
+            "db host: staging.internal",
+            vec![1.0, 0.0],
+        );
+        store.add("run-1/answer", "Question: weather?", vec![0.0, 1.0]);
+        store.add("run-2/tool-002", "db password in vault", vec![0.6, 0.8]);
+        assert!(store.has_source("run-1/answer"));
+
+        let request: RecallRequest =
+            serde_json::from_str(r#"{"recall": "database", "k": 2}"#).unwrap();
+        assert_eq!(request.k(), 2);
+        let hits = store.search(&[1.0, 0.2], request.k());
+        let sources: Vec<&str> = hits
+            .iter()
+            .map(|(entry, _)| entry.source.as_str())
+            .collect();
+        assert_eq!(sources, vec!["run-1/tool-001", "run-2/tool-002"]);
+
+        let output = recall_output(&request, &hits);
+        assert!(output.starts_with(
+            "Memory for \"database\":\n\n[1] run-1/tool-001 (similarity 0.98)\ndb host"
+        ));
+        assert_eq!(
+            recall_output(&RecallRequest::new("x"), &[]),
+            "Nothing in memory matches \"x\"."
+        );
+    }
+
+    #[test]
+    fn test_session_note() {
+        let mut state = AgentState::new("How many files?");
+        assert_eq!(session_note(&state), None);
+        state.final_answer = Some("Three.".to_string());
+        assert_eq!(
+            session_note(&state).as_deref(),
+            Some("Question: How many files?\nAnswer: Three.")
+        );
+    }
+}
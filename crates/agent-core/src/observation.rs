@@ -0,0 +1,213 @@
+//! Observations: large tool outputs compressed before they enter the history
+//!
+//! Paging ([`crate::artifact`]) keeps a long output from filling the context
+//! once, but every excerpt stays in the history for the rest of the run. With
+//! an [`ObservationPolicy`] the host instead compresses each accepted output
+//! above a size into a short observation, keeps the raw output as an artifact,
+//! and records the observation in its place:
+//!
+//! - [`ObservationMode::Model`] asks the model for the summary, with
+//!   [`ObservationPolicy::summary_prompt`]
+//! - [`ObservationMode::Local`] keeps the first and last lines, with no model
+//!   call ([`ObservationPolicy::local_summary`])
+//!
+//! The model can still read the raw output through the artifact id in the
+//! observation.
+
+use crate::prelude::*;
+use crate::tool::ToolResult;
+
+/// Default [`ObservationPolicy::with_min_tokens`]
+pub const DEFAULT_OBSERVATION_MIN_TOKENS: usize = 200;
+
+/// Default [`ObservationPolicy::with_summary_tokens`]
+pub const DEFAULT_SUMMARY_TOKENS: usize = 96;
+
+/// How accepted tool outputs are compressed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ObservationMode {
+    /// Record outputs as they are (paged if long)
+    #[default]
+    Raw,
+    /// Ask the model to summarize them
+    Model,
+    /// Keep their first and last lines
+    Local,
+}
+
+impl ObservationMode {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "raw" | "off" => Some(Self::Raw),
+            "model" => Some(Self::Model),
+            "local" => Some(Self::Local),
+            _ => None,
+        }
+    }
+}
+
+/// When and how tool outputs become observations
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObservationPolicy {
+    mode: ObservationMode,
+    min_tokens: usize,
+    summary_tokens: usize,
+}
+
+impl Default for ObservationPolicy {
+    fn default() -> Self {
+        Self::new(ObservationMode::Raw)
+    }
+}
+
+impl ObservationPolicy {
+    pub fn new(mode: ObservationMode) -> Self {
+        Self {
+            mode,
+            min_tokens: DEFAULT_OBSERVATION_MIN_TOKENS,
+            summary_tokens: DEFAULT_SUMMARY_TOKENS,
+        }
+    }
+
+    /// Outputs up to this many tokens are recorded as they are
+    pub fn with_min_tokens(mut self, tokens: usize) -> Self {
+        self.min_tokens = tokens;
+        self
+    }
+
+    /// Tokens a summary may take
+    pub fn with_summary_tokens(mut self, tokens: usize) -> Self {
+        self.summary_tokens = tokens;
+        self
+    }
+
+    pub fn mode(&self) -> ObservationMode {
+        self.mode
+    }
+
+    pub fn summary_tokens(&self) -> usize {
+        self.summary_tokens
+    }
+
+    /// Whether `result` is compressed: a successful output above the minimum
+    pub fn should_summarize(&self, result: &ToolResult) -> bool {
+        self.mode != ObservationMode::Raw
+            && result.success
+            && result.output.chars().count().div_ceil(4) > self.min_tokens
+    }
+
+    /// The prompt asking the model to summarize `output` of `tool` for `query`
+    pub fn summary_prompt(&self, query: &str, tool: &str, output: &str) -> String {
+        format!(
+            "Summarize the output of the {} tool below in at most {} words, for answering: {}\n\n\
+             Keep exact names, numbers, paths and error messages that bear on the question. \
+             Leave out everything else. Respond with the summary only.\n\n\
+             Output:\n{}\n\nSummary:",
+            tool,
+            self.summary_tokens * 3 / 4,
+            query,
+            output
+        )
+    }
+
+    /// A summary without a model: the first and last lines of `output`
+    /// within the summary budget
+    pub fn local_summary(&self, output: &str) -> String {
+        let budget = self.summary_tokens * 4;
+        let lines: Vec<&str> = output.lines().collect();
+        let (mut head, mut tail) = (Vec::new(), Vec::new());
+        let mut used = 0;
+        let (mut front, mut back) = (0, lines.len());
+        // Alternate between the ends so both are represented
+        while front < back {
+            let take_front = head.len() <= tail.len();
+            let line = if take_front {
+                lines[front]
+            } else {
+                lines[back - 1]
+            };
+            let cost = line.chars().count() + 1;
+            if used + cost > budget {
+                // A single long line: keep its start rather than nothing
+                if head.is_empty() {
+                    let end = line
+                        .char_indices()
+                        .nth(budget)
+                        .map_or(line.len(), |(i, _)| i);
+                    head.push(&line[..end]);
+                    front += 1;
+                }
+                break;
+            }
+            used += cost;
+            if take_front {
+                head.push(line);
+                front += 1;
+            } else {
+                tail.push(line);
+                back -= 1;
+            }
+        }
+
+        let mut summary = head.join("\n");
+        if front < back {
+            summary.push_str(&format!("\n[... {} lines omitted ...]", back - front));
+        }
+        tail.reverse();
+        for line in tail {
+            summary.push('\n');
+            summary.push_str(line);
+        }
+        summary.trim_start_matches('\n').to_string()
+    }
+
+    /// The history entry for an output kept as artifact `id`, summarized
+    pub fn observation(&self, id: &str, total_lines: usize, summary: &str) -> String {
+        format!(
+            "Observation (summary of {} lines, saved as artifact {}; read it with \
+             {{\"read_artifact\": {{\"id\": \"{}\"}}}}):\n{}",
+            total_lines,
+            id,
+            id,
+            summary.trim()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_should_summarize() {
+        let policy = ObservationPolicy::new(ObservationMode::Local).with_min_tokens(10);
+        assert!(!policy.should_summarize(&ToolResult::success("short")));
+        assert!(policy.should_summarize(&ToolResult::success("x".repeat(100))));
+        assert!(!policy.should_summarize(&ToolResult::failure("x".repeat(100))));
+        assert!(!ObservationPolicy::default()
+            .should_summarize(&ToolResult::success("x".repeat(10_000))));
+    }
+
+    #[test]
+    fn test_local_summary_keeps_both_ends() {
+        let output: Vec<String> = (1..=100).map(|n| format!("line {}", n)).collect();
+        let summary = ObservationPolicy::new(ObservationMode::Local)
+            .with_summary_tokens(10)
+            .local_summary(&output.join("\n"));
+        assert!(summary.starts_with("line 1\nline 2\n"));
+        assert!(summary.contains("lines omitted"));
+        assert!(summary.ends_with("line 99\nline 100"));
+        assert!(summary.chars().count() < 80);
+    }
+
+    #[test]
+    fn test_observation_references_the_artifact() {
+        let text = ObservationPolicy::new(ObservationMode::Model).observation(
+            "tool-002",
+            300,
+            " 3 files exceed 1 MB. ",
+        );
+        assert!(text.contains(r#"{"read_artifact": {"id": "tool-002"}}"#));
+        assert!(text.ends_with(":\n3 files exceed 1 MB."));
+    }
+}
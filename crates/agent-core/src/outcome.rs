@@ -0,0 +1,101 @@
+//! How a run ended, as a process exit code
+//!
+//! Hosts that run as commands exit with the code of the run's outcome, so
+//! scripts can branch on why a run failed without parsing its messages:
+//!
+//! | Code | Outcome | Failure codes |
+//! |------|---------|---------------|
+//! | 0 | [`RunOutcome::Answered`] | |
+//! | 1 | [`RunOutcome::Error`] (bad arguments, I/O, invalid plan, ...) | any other |
+//! | 2 | [`RunOutcome::GuardrailFailure`] | `guardrail_failure` |
+//! | 3 | [`RunOutcome::Inconclusive`] | `inconclusive`, `inconclusive_after_guardrail`, `refused` |
+//! | 4 | [`RunOutcome::MaxIterations`] | `max_iterations` |
+//! | 5 | [`RunOutcome::BackendError`] | `backend_error` |
+//! | 6 | [`RunOutcome::ToolDenied`] | `tool_denied` |
+//!
+//! The failure codes are the ones in host `failed` events and
+//! [`SessionFailure::code`].
+
+use crate::session::SessionFailure;
+use serde::{Deserialize, Serialize};
+
+/// How a run ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunOutcome {
+    /// The run produced an accepted answer
+    Answered,
+    /// The run could not start or stopped on an error outside the loop
+    Error,
+    /// A guardrail rejected the tool output again after a corrective retry
+    GuardrailFailure,
+    /// The model produced neither a call nor an answer, or a call the host
+    /// refused, after a retry
+    Inconclusive,
+    /// The iteration limit was reached without an answer
+    MaxIterations,
+    /// The model backend failed to load or to answer
+    BackendError,
+    /// The run ended without an answer after a tool call was denied
+    ToolDenied,
+}
+
+impl RunOutcome {
+    /// Process exit code of the outcome
+    pub const fn exit_code(self) -> i32 {
+        match self {
+            Self::Answered => 0,
+            Self::Error => 1,
+            Self::GuardrailFailure => 2,
+            Self::Inconclusive => 3,
+            Self::MaxIterations => 4,
+            Self::BackendError => 5,
+            Self::ToolDenied => 6,
+        }
+    }
+
+    /// The outcome of a failure code; unknown codes are [`RunOutcome::Error`]
+    pub fn from_failure_code(code: &str) -> Self {
+        match code {
+            "guardrail_failure" => Self::GuardrailFailure,
+            "inconclusive" | "inconclusive_after_guardrail" | "refused" => Self::Inconclusive,
+            "max_iterations" => Self::MaxIterations,
+            "backend_error" => Self::BackendError,
+            "tool_denied" => Self::ToolDenied,
+            _ => Self::Error,
+        }
+    }
+}
+
+impl From<&SessionFailure> for RunOutcome {
+    fn from(failure: &SessionFailure) -> Self {
+        Self::from_failure_code(failure.code())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_codes() {
+        assert_eq!(RunOutcome::Answered.exit_code(), 0);
+        assert_eq!(
+            RunOutcome::from_failure_code("guardrail_failure").exit_code(),
+            2
+        );
+        assert_eq!(
+            RunOutcome::from_failure_code("inconclusive_after_guardrail").exit_code(),
+            3
+        );
+        assert_eq!(RunOutcome::from_failure_code("tool_denied").exit_code(), 6);
+        assert_eq!(RunOutcome::from_failure_code("invalid_plan").exit_code(), 1);
+    }
+
+    #[test]
+    fn test_session_failures() {
+        let failure = SessionFailure::MaxIterations { max: 5 };
+        assert_eq!(RunOutcome::from(&failure), RunOutcome::MaxIterations);
+        assert_eq!(RunOutcome::from(&failure).exit_code(), 4);
+    }
+}
@@ -0,0 +1,153 @@
+//! Named agents sharing one history
+//!
+//! Critic/worker loops and debates keep every participant's turns in one
+//! history, as [`Role::Agent`] messages (an [`AgentState`] records replies
+//! under its [`speaker`](AgentState::speaker)). Chat models only know user and
+//! assistant turns, so each prompt is rendered from one participant's point of
+//! view:
+//!
+//! - the participant being prompted sees its own turns as assistant turns
+//! - other agents' turns become user turns prefixed with their name
+//!   (`[critic] The count misses hidden files.`), unless a rule says otherwise
+//!
+//! [`Participants::apply`] does the mapping; the history itself is unchanged.
+//!
+//! [`AgentState`]: crate::agent::AgentState
+
+use crate::agent::{Message, Role};
+use crate::prelude::*;
+use alloc::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+
+/// How another agent's turns appear in a prompt
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rendering {
+    /// As user turns, prefixed with the agent's name
+    #[default]
+    AsUser,
+    /// As assistant turns, as if the prompted model had written them
+    AsAssistant,
+    /// Left out
+    Hidden,
+}
+
+/// The point of view a prompt is rendered from
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Participants {
+    /// The agent being prompted (`None`: no named agent is)
+    #[serde(default)]
+    speaker: Option<String>,
+    /// Rules for other agents, by name
+    #[serde(default)]
+    rules: BTreeMap<String, Rendering>,
+    /// Rule for agents without one
+    #[serde(default)]
+    default: Rendering,
+}
+
+impl Participants {
+    /// Render prompts for the named agent
+    pub fn speaking_as(speaker: impl Into<String>) -> Self {
+        Self {
+            speaker: Some(speaker.into()),
+            ..Self::default()
+        }
+    }
+
+    /// How the named agent's turns appear
+    pub fn with_rule(mut self, agent: impl Into<String>, rendering: Rendering) -> Self {
+        self.rules.insert(agent.into(), rendering);
+        self
+    }
+
+    /// How turns of agents without a rule appear (default [`Rendering::AsUser`])
+    pub fn with_default(mut self, rendering: Rendering) -> Self {
+        self.default = rendering;
+        self
+    }
+
+    pub fn speaker(&self) -> Option<&str> {
+        self.speaker.as_deref()
+    }
+
+    /// How `agent`'s turns appear in the speaker's prompts
+    pub fn rendering(&self, agent: &str) -> Rendering {
+        if self.speaker.as_deref() == Some(agent) {
+            return Rendering::AsAssistant;
+        }
+        self.rules.get(agent).copied().unwrap_or(self.default)
+    }
+
+    /// `history` as the speaker sees it: only user, assistant and tool turns
+    pub fn apply(&self, history: &[Message]) -> Vec<Message> {
+        history
+            .iter()
+            .filter_map(|message| {
+                let Role::Agent(name) = &message.role else {
+                    return Some(message.clone());
+                };
+                match self.rendering(name) {
+                    Rendering::AsAssistant => Some(Message {
+                        role: Role::Assistant,
+                        ..message.clone()
+                    }),
+                    Rendering::AsUser => Some(Message {
+                        role: Role::User,
+                        content: attributed(name, &message.content),
+                        ..message.clone()
+                    }),
+                    Rendering::Hidden => None,
+                }
+            })
+            .collect()
+    }
+}
+
+/// A turn of `agent` as another participant reads it
+pub fn attributed(agent: &str, content: &str) -> String {
+    format!("[{}] {}", agent, content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::{process_model_output, AgentState};
+
+    fn debate() -> AgentState {
+        let mut state =
+            AgentState::new("How many files are in src/?").with_speaker(Some("worker".to_string()));
+        process_model_output(&mut state, "There are 4 files.");
+        state.add_message(
+            Role::Agent("critic".to_string()),
+            "The count misses hidden files.",
+        );
+        state.add_message(Role::Agent("judge".to_string()), "Noted.");
+        state
+    }
+
+    #[test]
+    fn test_speaker_replies_are_named() {
+        let state = debate();
+        assert_eq!(state.history[1].role, Role::Agent("worker".to_string()));
+        let json = serde_json::to_value(&state.history[2]).unwrap();
+        assert_eq!(json["role"], serde_json::json!({"agent": "critic"}));
+    }
+
+    #[test]
+    fn test_rendering_per_participant() {
+        let history = debate().history;
+
+        let worker = Participants::speaking_as("worker").with_rule("judge", Rendering::Hidden);
+        let seen = worker.apply(&history);
+        assert_eq!(seen.len(), 3);
+        assert_eq!(seen[1].role, Role::Assistant);
+        assert_eq!(seen[2].role, Role::User);
+        assert_eq!(seen[2].content, "[critic] The count misses hidden files.");
+
+        let critic = Participants::speaking_as("critic").apply(&history);
+        assert_eq!(critic[1].content, "[worker] There are 4 files.");
+        assert_eq!(critic[2].role, Role::Assistant);
+        assert_eq!(critic[3].content, "[judge] Noted.");
+    }
+}
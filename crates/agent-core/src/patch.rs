@@ -0,0 +1,417 @@
+//! Unified diff parsing and application
+//!
+//! Backs the `apply_patch` tool: the model proposes an edit as a unified diff,
+//! and the host applies it only if every hunk's context and removed lines
+//! match the target file. Hunks may have moved by a few lines since the model
+//! read the file (like `patch`'s offset); they are searched for nearest to the
+//! line the header names. Trailing whitespace is ignored when matching.
+//!
+//! This module is pure: the host reads the target files, applies the patch to
+//! their contents and writes the results.
+
+use crate::prelude::*;
+use crate::tool::is_workspace_path;
+use thiserror::Error;
+
+/// A parsed unified diff
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Patch {
+    pub files: Vec<FilePatch>,
+}
+
+/// Changes to a single file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilePatch {
+    /// Path before the change (`None` when the file is created)
+    pub old_path: Option<String>,
+    /// Path after the change (`None` when the file is deleted)
+    pub new_path: Option<String>,
+    pub hunks: Vec<Hunk>,
+}
+
+/// A `@@ -a,b +c,d @@` section
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    /// First line of the hunk in the original file (1-based)
+    pub old_start: usize,
+    pub lines: Vec<HunkLine>,
+}
+
+/// A line of a hunk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HunkLine {
+    Context(String),
+    Remove(String),
+    Add(String),
+}
+
+/// Patch errors
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum PatchError {
+    #[error("Malformed patch at line {line}: {reason}")]
+    Malformed { line: usize, reason: String },
+
+    #[error("Patch contains no file changes")]
+    Empty,
+
+    #[error("Unsafe path '{0}': paths must be relative and stay inside the working directory")]
+    UnsafePath(String),
+
+    #[error("Renaming files is not supported ('{from}' to '{to}')")]
+    Rename { from: String, to: String },
+
+    #[error("Hunk {hunk} does not apply to '{path}': context does not match near line {line}")]
+    ContextMismatch {
+        path: String,
+        hunk: usize,
+        line: usize,
+    },
+
+    #[error("'{0}' still has content after the patch deletes it")]
+    IncompleteDeletion(String),
+}
+
+impl Hunk {
+    /// Lines the hunk expects in the original file
+    fn old_lines(&self) -> impl Iterator<Item = &str> {
+        self.lines.iter().filter_map(|line| match line {
+            HunkLine::Context(text) | HunkLine::Remove(text) => Some(text.as_str()),
+            HunkLine::Add(_) => None,
+        })
+    }
+
+    /// Lines the hunk leaves in the patched file
+    fn new_lines(&self) -> impl Iterator<Item = &str> {
+        self.lines.iter().filter_map(|line| match line {
+            HunkLine::Context(text) | HunkLine::Add(text) => Some(text.as_str()),
+            HunkLine::Remove(_) => None,
+        })
+    }
+}
+
+impl FilePatch {
+    /// The file the patch touches
+    pub fn path(&self) -> &str {
+        self.new_path
+            .as_deref()
+            .or(self.old_path.as_deref())
+            .unwrap_or_default()
+    }
+
+    pub fn is_creation(&self) -> bool {
+        self.old_path.is_none()
+    }
+
+    pub fn is_deletion(&self) -> bool {
+        self.new_path.is_none()
+    }
+
+    /// Added and removed line counts
+    pub fn stats(&self) -> (usize, usize) {
+        let lines = self.hunks.iter().flat_map(|hunk| &hunk.lines);
+        lines.fold((0, 0), |(added, removed), line| match line {
+            HunkLine::Add(_) => (added + 1, removed),
+            HunkLine::Remove(_) => (added, removed + 1),
+            HunkLine::Context(_) => (added, removed),
+        })
+    }
+
+    /// Apply the hunks to the file's current content
+    ///
+    /// `original` is `None` for a file that does not exist yet. Returns the new
+    /// content, or `None` when the patch deletes the file.
+    pub fn apply(&self, original: Option<&str>) -> Result<Option<String>, PatchError> {
+        let original = original.unwrap_or_default();
+        let lines: Vec<&str> = original.lines().collect();
+        let mut patched: Vec<&str> = Vec::with_capacity(lines.len());
+        let mut cursor = 0;
+        let mut offset: isize = 0;
+
+        for (index, hunk) in self.hunks.iter().enumerate() {
+            let old: Vec<&str> = hunk.old_lines().collect();
+            // A pure insertion's start names the line it follows
+            let stated = if old.is_empty() {
+                hunk.old_start
+            } else {
+                hunk.old_start.saturating_sub(1)
+            };
+            let expected = stated.saturating_add_signed(offset);
+
+            let position =
+                find_block(&lines, &old, cursor, expected).ok_or(PatchError::ContextMismatch {
+                    path: self.path().to_string(),
+                    hunk: index + 1,
+                    line: hunk.old_start,
+                })?;
+
+            patched.extend_from_slice(&lines[cursor..position]);
+            patched.extend(hunk.new_lines());
+            cursor = position + old.len();
+            offset = position as isize - stated as isize;
+        }
+        patched.extend_from_slice(&lines[cursor..]);
+
+        if self.is_deletion() {
+            if patched.iter().any(|line| !line.trim().is_empty()) {
+                return Err(PatchError::IncompleteDeletion(self.path().to_string()));
+            }
+            return Ok(None);
+        }
+
+        let mut content = patched.join("\n");
+        if !content.is_empty() && (original.is_empty() || original.ends_with('\n')) {
+            content.push('\n');
+        }
+        Ok(Some(content))
+    }
+}
+
+/// Index of `block` in `lines` at or after `from`, nearest to `expected`
+fn find_block(lines: &[&str], block: &[&str], from: usize, expected: usize) -> Option<usize> {
+    let last = lines.len().checked_sub(block.len())?;
+    if from > last {
+        return None;
+    }
+
+    let matches = |start: usize| {
+        lines[start..start + block.len()]
+            .iter()
+            .zip(block)
+            .all(|(line, expected)| line.trim_end() == expected.trim_end())
+    };
+
+    let expected = expected.clamp(from, last);
+    let max_distance = (expected - from).max(last - expected);
+    (0..=max_distance).find_map(|distance| {
+        let below = expected
+            .checked_sub(distance)
+            .filter(|&start| start >= from);
+        let above = Some(expected + distance).filter(|&start| start <= last);
+        below
+            .filter(|&start| matches(start))
+            .or(above.filter(|&start| matches(start)))
+    })
+}
+
+/// Parse a unified diff (`diff -u` or `git diff` output)
+///
+/// Lines outside hunks that are not file headers (`diff --git`, `index`,
+/// commentary) are ignored.
+pub fn parse_patch(text: &str) -> Result<Patch, PatchError> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut files: Vec<FilePatch> = Vec::new();
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+        let number = i + 1;
+        i += 1;
+
+        if let Some(old) = line.strip_prefix("--- ") {
+            let new = lines
+                .get(i)
+                .and_then(|next| next.strip_prefix("+++ "))
+                .ok_or_else(|| malformed(number + 1, "expected '+++' after '---'"))?;
+            i += 1;
+
+            let file = FilePatch {
+                old_path: parse_path(old)?,
+                new_path: parse_path(new)?,
+                hunks: Vec::new(),
+            };
+            if let (Some(from), Some(to)) = (&file.old_path, &file.new_path) {
+                if from != to {
+                    return Err(PatchError::Rename {
+                        from: from.clone(),
+                        to: to.clone(),
+                    });
+                }
+            }
+            if file.old_path.is_none() && file.new_path.is_none() {
+                return Err(malformed(number, "both paths are /dev/null"));
+            }
+            files.push(file);
+        } else if line.starts_with("@@") {
+            let (old_start, old_len, new_len) = parse_hunk_header(line, number)?;
+            let file = files
+                .last_mut()
+                .ok_or_else(|| malformed(number, "hunk before any file header"))?;
+
+            let mut hunk = Hunk {
+                old_start,
+                lines: Vec::new(),
+            };
+            let (mut old_seen, mut new_seen) = (0, 0);
+            while old_seen < old_len || new_seen < new_len {
+                let Some(&body) = lines.get(i) else {
+                    return Err(malformed(i, "hunk ends early"));
+                };
+                i += 1;
+
+                // Editors and models often strip the space of empty context lines
+                let (kind, text) = match body.chars().next() {
+                    Some(kind) => (kind, &body[kind.len_utf8()..]),
+                    None => (' ', ""),
+                };
+                match kind {
+                    ' ' => {
+                        hunk.lines.push(HunkLine::Context(text.to_string()));
+                        old_seen += 1;
+                        new_seen += 1;
+                    }
+                    '-' => {
+                        hunk.lines.push(HunkLine::Remove(text.to_string()));
+                        old_seen += 1;
+                    }
+                    '+' => {
+                        hunk.lines.push(HunkLine::Add(text.to_string()));
+                        new_seen += 1;
+                    }
+                    '\\' => {} // "\ No newline at end of file"
+                    _ => return Err(malformed(i, "unexpected line inside hunk")),
+                }
+            }
+            if old_seen != old_len || new_seen != new_len {
+                return Err(malformed(
+                    number,
+                    "hunk line counts do not match its header",
+                ));
+            }
+            file.hunks.push(hunk);
+        }
+    }
+
+    files.retain(|file| !file.hunks.is_empty());
+    if files.is_empty() {
+        return Err(PatchError::Empty);
+    }
+    Ok(Patch { files })
+}
+
+fn malformed(line: usize, reason: &str) -> PatchError {
+    PatchError::Malformed {
+        line,
+        reason: reason.to_string(),
+    }
+}
+
+/// Path from a `---`/`+++` header: no timestamp, no `a/`/`b/` prefix, `None`
+/// for `/dev/null`
+fn parse_path(header: &str) -> Result<Option<String>, PatchError> {
+    let path = header.split('\t').next().unwrap_or_default().trim();
+    if path == "/dev/null" {
+        return Ok(None);
+    }
+
+    let path = path
+        .strip_prefix("a/")
+        .or_else(|| path.strip_prefix("b/"))
+        .unwrap_or(path);
+    if !is_workspace_path(path) {
+        return Err(PatchError::UnsafePath(path.to_string()));
+    }
+    Ok(Some(path.to_string()))
+}
+
+/// `(old_start, old_len, new_len)` from `@@ -a,b +c,d @@`
+fn parse_hunk_header(line: &str, number: usize) -> Result<(usize, usize, usize), PatchError> {
+    let invalid = || malformed(number, "invalid hunk header");
+    let ranges = line
+        .strip_prefix("@@ ")
+        .and_then(|rest| rest.split(" @@").next())
+        .ok_or_else(invalid)?;
+    let (old, new) = ranges.split_once(' ').ok_or_else(invalid)?;
+
+    let range = |range: Option<&str>| -> Result<(usize, usize), PatchError> {
+        let range = range.ok_or_else(invalid)?;
+        let (start, len) = range.split_once(',').unwrap_or((range, "1"));
+        Ok((
+            start.parse().map_err(|_| invalid())?,
+            len.parse().map_err(|_| invalid())?,
+        ))
+    };
+    let (old_start, old_len) = range(old.strip_prefix('-'))?;
+    let (_, new_len) = range(new.strip_prefix('+'))?;
+    Ok((old_start, old_len, new_len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORIGINAL: &str = "fn main() {\n    let x = 1;\n    println!(\"{}\", x);\n}\n";
+
+    const PATCH: &str = "\
+diff --git a/src/main.rs b/src/main.rs
+--- a/src/main.rs
++++ b/src/main.rs
+@@ -1,4 +1,4 @@
+ fn main() {
+-    let x = 1;
++    let x = 2;
+     println!(\"{}\", x);
+ }
+";
+
+    #[test]
+    fn test_parse_and_apply() {
+        let patch = parse_patch(PATCH).unwrap();
+        assert_eq!(patch.files.len(), 1);
+
+        let file = &patch.files[0];
+        assert_eq!(file.path(), "src/main.rs");
+        assert_eq!(file.stats(), (1, 1));
+        assert_eq!(
+            file.apply(Some(ORIGINAL)).unwrap().unwrap(),
+            ORIGINAL.replace("x = 1", "x = 2")
+        );
+    }
+
+    #[test]
+    fn test_apply_with_offset() {
+        let shifted = format!("// header\n// more\n{}", ORIGINAL);
+        let patch = parse_patch(PATCH).unwrap();
+        let patched = patch.files[0].apply(Some(&shifted)).unwrap().unwrap();
+        assert!(patched.starts_with("// header\n// more\nfn main() {\n    let x = 2;"));
+    }
+
+    #[test]
+    fn test_context_mismatch() {
+        let patch = parse_patch(PATCH).unwrap();
+        let changed = ORIGINAL.replace("x = 1", "y = 1");
+        assert_eq!(
+            patch.files[0].apply(Some(&changed)),
+            Err(PatchError::ContextMismatch {
+                path: "src/main.rs".to_string(),
+                hunk: 1,
+                line: 1,
+            })
+        );
+    }
+
+    #[test]
+    fn test_create_and_delete() {
+        let create =
+            parse_patch("--- /dev/null\n+++ b/notes.txt\n@@ -0,0 +1,2 @@\n+one\n+two\n").unwrap();
+        let file = &create.files[0];
+        assert!(file.is_creation());
+        assert_eq!(file.apply(None).unwrap(), Some("one\ntwo\n".to_string()));
+
+        let delete =
+            parse_patch("--- a/notes.txt\n+++ /dev/null\n@@ -1,2 +0,0 @@\n-one\n-two\n").unwrap();
+        assert_eq!(delete.files[0].apply(Some("one\ntwo\n")).unwrap(), None);
+    }
+
+    #[test]
+    fn test_rejects_unsafe_and_malformed_patches() {
+        assert!(matches!(
+            parse_patch("--- a/../etc/passwd\n+++ b/../etc/passwd\n@@ -1 +1 @@\n-a\n+b\n"),
+            Err(PatchError::UnsafePath(_))
+        ));
+        assert!(matches!(
+            parse_patch("--- a/x\n+++ b/x\n@@ -1,2 +1,2 @@\n-a\n+b\n"),
+            Err(PatchError::Malformed { .. })
+        ));
+        assert_eq!(parse_patch("just some text"), Err(PatchError::Empty));
+    }
+}
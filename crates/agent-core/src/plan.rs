@@ -0,0 +1,387 @@
+//! Dry-run plans: what the agent would do, reviewed before anything runs
+//!
+//! A dry run asks the model once for the whole sequence of calls it intends to
+//! make, then checks each call the way the agent loop would (TOOL.md
+//! parameters, pre-execution guardrails, approval policy) without executing
+//! it. The reviewed plan renders as Markdown or serializes as JSON, so users
+//! can see what auto-approval would let through before enabling it.
+//!
+//! The model sees no tool output while planning, so steps after the first are
+//! its best guess at what it will need.
+
+use crate::agent::AgentState;
+use crate::approval::{ApprovalMode, ApprovalPolicy};
+use crate::guardrail::{GuardrailResult, PreExecutionChain, PreExecutionContext};
+use crate::prelude::*;
+use crate::protocol::{parse_model_output, ParseResult};
+use crate::tool_manifest::{SafetyClass, ToolRegistry};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+/// Instructions appended to the system prompt for the planning call
+pub const PLAN_INSTRUCTIONS: &str =
+    "PLANNING MODE: Do not answer the query and do not call a tool now.
+Instead, list every tool or skill call you would make to answer it, in order,
+as one JSON array. Each element is a call in the exact JSON format shown above,
+plus a \"reason\" field saying why it is needed. Later calls cannot see the output
+of earlier ones, so make each call as specific as you can.
+Respond ONLY with the JSON array.";
+
+/// Plan errors
+#[derive(Debug, Error, PartialEq)]
+pub enum PlanError {
+    #[error("Plan is not a JSON array of calls: {0}")]
+    NotAnArray(String),
+
+    #[error("Plan has no steps")]
+    Empty,
+}
+
+/// The planning system prompt: the run's system prompt and the instructions
+/// to plan at most `max_steps` calls
+pub fn plan_system_prompt(system_prompt: &str, max_steps: usize) -> String {
+    format!(
+        "{}\n\n{}\nPlan at most {} calls.",
+        system_prompt, PLAN_INSTRUCTIONS, max_steps
+    )
+}
+
+/// What each step of a plan is checked against
+pub struct PlanReview<'a> {
+    pub registry: &'a ToolRegistry,
+    pub policy: &'a ApprovalPolicy,
+    pub guards: &'a PreExecutionChain,
+    /// Names of the skills the host can run
+    pub skills: &'a [String],
+}
+
+/// How the agent loop would handle a planned call
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum StepReview {
+    /// A valid tool call, run, prompted for or denied per the approval policy
+    Tool {
+        tool: String,
+        safety: SafetyClass,
+        approval: ApprovalMode,
+    },
+    /// A skill call (skills need no approval)
+    Skill { skill: String },
+    /// Refused before running: unknown tool or skill, invalid parameters or a
+    /// pre-execution guardrail
+    Rejected { reason: String },
+}
+
+/// One planned call and its review
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlanStep {
+    /// The call as the model proposed it, in the protocol's JSON form
+    pub call: Value,
+    /// Why the model wants it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    pub review: StepReview,
+}
+
+/// A reviewed dry-run plan
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Plan {
+    pub query: String,
+    pub steps: Vec<PlanStep>,
+}
+
+impl Plan {
+    /// Parse the model's planning output and review every step
+    ///
+    /// Text around the JSON array (code fences, a sentence of preamble) is
+    /// ignored.
+    pub fn parse(query: &str, output: &str, review: &PlanReview) -> Result<Self, PlanError> {
+        let start = output.find('[');
+        let end = output.rfind(']');
+        let json = match (start, end) {
+            (Some(start), Some(end)) if start < end => &output[start..=end],
+            _ => return Err(PlanError::NotAnArray("no JSON array found".to_string())),
+        };
+        let calls: Vec<Value> =
+            serde_json::from_str(json).map_err(|e| PlanError::NotAnArray(e.to_string()))?;
+        if calls.is_empty() {
+            return Err(PlanError::Empty);
+        }
+
+        let state = AgentState::new(query);
+        let steps = calls
+            .into_iter()
+            .map(|mut call| {
+                let reason = call
+                    .as_object_mut()
+                    .and_then(|fields| fields.remove("reason"))
+                    .and_then(|reason| reason.as_str().map(str::to_string));
+                let review = review_call(&call, &state, review);
+                PlanStep {
+                    call,
+                    reason,
+                    review,
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            query: query.to_string(),
+            steps,
+        })
+    }
+
+    /// Number of steps the approval policy would ask the user about
+    pub fn prompts(&self) -> usize {
+        self.count(|review| {
+            matches!(
+                review,
+                StepReview::Tool {
+                    approval: ApprovalMode::Prompt,
+                    ..
+                }
+            )
+        })
+    }
+
+    /// Number of steps that would not run (rejected or denied)
+    pub fn refusals(&self) -> usize {
+        self.count(|review| {
+            matches!(
+                review,
+                StepReview::Rejected { .. }
+                    | StepReview::Tool {
+                        approval: ApprovalMode::Deny,
+                        ..
+                    }
+            )
+        })
+    }
+
+    fn count(&self, predicate: impl Fn(&StepReview) -> bool) -> usize {
+        self.steps
+            .iter()
+            .filter(|step| predicate(&step.review))
+            .count()
+    }
+
+    /// Render the plan for review
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!("# Plan: {}\n\n", self.query);
+        for (index, step) in self.steps.iter().enumerate() {
+            let (name, verdict) = match &step.review {
+                StepReview::Tool {
+                    tool,
+                    safety,
+                    approval,
+                } => (
+                    tool.as_str(),
+                    format!("{}, {}", safety.as_str(), approval_label(*approval)),
+                ),
+                StepReview::Skill { skill } => (skill.as_str(), "skill".to_string()),
+                StepReview::Rejected { reason } => (
+                    call_name(&step.call).unwrap_or("?"),
+                    format!("rejected: {}", reason),
+                ),
+            };
+            out.push_str(&format!("{}. **{}** ({})\n", index + 1, name, verdict));
+            out.push_str(&render_call(&step.call));
+            if let Some(reason) = &step.reason {
+                out.push_str(&format!("   {}\n", reason));
+            }
+        }
+
+        out.push_str(&format!(
+            "\n{} step(s): {} would ask for approval, {} would not run. Nothing was executed.\n",
+            self.steps.len(),
+            self.prompts(),
+            self.refusals()
+        ));
+        out
+    }
+}
+
+/// Review one call the way the agent loop would before executing it
+fn review_call(call: &Value, state: &AgentState, review: &PlanReview) -> StepReview {
+    match parse_model_output(&call.to_string()) {
+        ParseResult::ToolCall(request) => {
+            let safety = match review.registry.check_request(&request) {
+                Ok(manifest) => manifest.frontmatter.safety,
+                Err(e) => {
+                    return StepReview::Rejected {
+                        reason: e.to_string(),
+                    }
+                }
+            };
+            let context = PreExecutionContext {
+                state,
+                tool_request: &request,
+                safety,
+            };
+            if let GuardrailResult::Reject { reason } = review.guards.check(&context) {
+                return StepReview::Rejected { reason };
+            }
+            StepReview::Tool {
+                approval: review.policy.decide(&request.tool, safety),
+                tool: request.tool,
+                safety,
+            }
+        }
+        ParseResult::SkillCall(request) if review.skills.contains(&request.skill) => {
+            StepReview::Skill {
+                skill: request.skill,
+            }
+        }
+        ParseResult::SkillCall(request) => StepReview::Rejected {
+            reason: format!("Unknown skill: {}", request.skill),
+        },
+        _ => StepReview::Rejected {
+            reason: "Not a tool or skill call".to_string(),
+        },
+    }
+}
+
+fn approval_label(mode: ApprovalMode) -> &'static str {
+    match mode {
+        ApprovalMode::Auto => "runs without asking",
+        ApprovalMode::Prompt => "asks first",
+        ApprovalMode::Deny => "denied by policy",
+    }
+}
+
+fn call_name(call: &Value) -> Option<&str> {
+    call.get("tool")
+        .or_else(|| call.get("skill"))
+        .and_then(Value::as_str)
+}
+
+/// The call's parameters as a code block: a lone string parameter (a shell
+/// command, a patch) as-is, anything else as JSON
+fn render_call(call: &Value) -> String {
+    let params: Vec<(&String, &Value)> = call
+        .as_object()
+        .map(|fields| {
+            fields
+                .iter()
+                .filter(|(name, _)| !matches!(name.as_str(), "tool" | "skill" | "call_id"))
+                .collect()
+        })
+        .unwrap_or_default();
+    let text = match params.as_slice() {
+        [(_, Value::String(value))] => value.clone(),
+        _ => call.to_string(),
+    };
+    let indented: Vec<String> = text.lines().map(|line| format!("   {}", line)).collect();
+    format!("   ```\n{}\n   ```\n", indented.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::guardrail::SafetyCeilingGuard;
+    use crate::tool_manifest::parse_tool_manifest;
+
+    const SHELL: &str = "---\nname: shell\ndescription: Run a shell command\nsafety: destructive\nparams:\n  command:\n    type: string\n    required: true\n---\n";
+
+    fn registry() -> ToolRegistry {
+        let mut registry = ToolRegistry::with_default_tools();
+        registry.register(parse_tool_manifest(SHELL).unwrap());
+        registry
+    }
+
+    #[test]
+    fn test_plan_reviews_each_step() {
+        let registry = registry();
+        let policy = ApprovalPolicy::default();
+        let guards = PreExecutionChain::new();
+        let skills = vec!["extract".to_string()];
+        let review = PlanReview {
+            registry: &registry,
+            policy: &policy,
+            guards: &guards,
+            skills: &skills,
+        };
+        let output = r#"Here is my plan:
+```json
+[
+  {"tool": "shell", "command": "ls *.csv", "reason": "Find the data files"},
+  {"tool": "eval_math", "expression": "12 * 3"},
+  {"skill": "extract", "text": "mail bob@example.org", "target": "email"},
+  {"tool": "shell"},
+  {"tool": "ftp", "url": "ftp://example.org"}
+]
+```"#;
+
+        let plan = Plan::parse("Which files?", output, &review).unwrap();
+        let reviews: Vec<&StepReview> = plan.steps.iter().map(|step| &step.review).collect();
+        assert_eq!(
+            reviews[0],
+            &StepReview::Tool {
+                tool: "shell".to_string(),
+                safety: SafetyClass::Destructive,
+                approval: ApprovalMode::Prompt,
+            }
+        );
+        assert_eq!(plan.steps[0].reason.as_deref(), Some("Find the data files"));
+        assert!(plan.steps[0].call.get("reason").is_none());
+        assert!(matches!(
+            reviews[1],
+            StepReview::Tool {
+                approval: ApprovalMode::Auto,
+                ..
+            }
+        ));
+        assert_eq!(
+            reviews[2],
+            &StepReview::Skill {
+                skill: "extract".to_string()
+            }
+        );
+        assert!(matches!(reviews[3], StepReview::Rejected { .. }));
+        assert!(matches!(reviews[4], StepReview::Rejected { .. }));
+        assert_eq!(plan.prompts(), 1);
+        assert_eq!(plan.refusals(), 2);
+
+        let markdown = plan.to_markdown();
+        assert!(markdown.starts_with("# Plan: Which files?\n"));
+        assert!(markdown.contains("1. **shell** (destructive, asks first)\n   ```\n   ls *.csv\n"));
+        assert!(markdown.contains("5. **ftp** (rejected: "));
+        assert!(markdown.contains("Nothing was executed."));
+    }
+
+    #[test]
+    fn test_plan_applies_pre_execution_guards() {
+        let registry = registry();
+        let policy = ApprovalPolicy::default();
+        let guards =
+            PreExecutionChain::new().add(Box::new(SafetyCeilingGuard::new(SafetyClass::ReadOnly)));
+        let review = PlanReview {
+            registry: &registry,
+            policy: &policy,
+            guards: &guards,
+            skills: &[],
+        };
+
+        let plan = Plan::parse("q", r#"[{"tool": "shell", "command": "rm x"}]"#, &review).unwrap();
+        assert!(matches!(plan.steps[0].review, StepReview::Rejected { .. }));
+        assert_eq!(plan.refusals(), 1);
+    }
+
+    #[test]
+    fn test_plan_requires_a_json_array() {
+        let registry = registry();
+        let review = PlanReview {
+            registry: &registry,
+            policy: &ApprovalPolicy::default(),
+            guards: &PreExecutionChain::new(),
+            skills: &[],
+        };
+
+        assert!(matches!(
+            Plan::parse("q", "I would list the files first.", &review),
+            Err(PlanError::NotAnArray(_))
+        ));
+        assert_eq!(Plan::parse("q", "[]", &review), Err(PlanError::Empty));
+    }
+}
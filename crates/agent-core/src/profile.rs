@@ -0,0 +1,367 @@
+//! Prompt profiles and system prompt composition
+//!
+//! A profile bundles a system prompt, the tools the model is offered and the
+//! guardrails its work is checked with, under a name. Hosts read profiles from
+//! a YAML config file:
+//!
+//! ```yaml
+//! profiles:
+//!   coder:
+//!     system_prompt_file: prompts/coder.md
+//!     tools: [shell, apply_patch, search]
+//!     max_safety: mutating
+//!   researcher:
+//!     system_prompt: Cite the file and line every fact comes from.
+//!     tools: [search, sql, eval_math]
+//!     guardrails: [plausibility, numeric-consistency]
+//!   support:
+//!     guardrails: [plausibility, semantic-relevance]
+//!     relevance_threshold: 0.4
+//! ```
+//!
+//! A custom prompt extends the built-in instructions or replaces them (see
+//! [`PromptMode`]); either way the generated "Available tools" and skills
+//! sections are kept, so a custom prompt never has to list them. So are the
+//! environment facts, which end the prompt.
+
+use crate::embedding::DEFAULT_RELEVANCE_THRESHOLD;
+use crate::guardrail::{AnswerChain, GuardrailChain, NumericConsistencyGuard, PlausibilityGuard};
+use crate::response_spec::ResponseSpecGuard;
+use crate::tool_manifest::SafetyClass;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// Errors from loading a config or selecting a profile
+#[derive(Debug, Error)]
+pub enum ProfileError {
+    #[error("invalid config: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+    #[error("unknown profile '{name}' (available: {available})")]
+    UnknownProfile { name: String, available: String },
+    #[error("profile '{0}' sets both system_prompt and system_prompt_file")]
+    ConflictingPrompts(String),
+}
+
+/// How a custom prompt combines with the built-in instructions
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PromptMode {
+    /// Add the custom text after the built-in instructions
+    #[default]
+    Extend,
+    /// Use the custom text instead of the built-in intro and instructions
+    Replace,
+}
+
+impl PromptMode {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "extend" => Some(Self::Extend),
+            "replace" => Some(Self::Replace),
+            _ => None,
+        }
+    }
+}
+
+/// A semantic guardrail a profile can enable
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GuardName {
+    /// [`PlausibilityGuard`] on tool outputs
+    Plausibility,
+    /// [`NumericConsistencyGuard`] on final answers
+    NumericConsistency,
+    /// [`RelevanceGuard`](crate::embedding::RelevanceGuard) on tool outputs and
+    /// final answers; needs a backend that computes embeddings
+    SemanticRelevance,
+}
+
+/// The semantic guardrails a run uses (plausibility and numeric consistency by
+/// default)
+#[derive(Debug, Clone, PartialEq)]
+pub struct GuardrailSet {
+    guards: Vec<GuardName>,
+    relevance_threshold: f32,
+}
+
+impl Default for GuardrailSet {
+    fn default() -> Self {
+        Self::new(vec![GuardName::Plausibility, GuardName::NumericConsistency])
+    }
+}
+
+impl GuardrailSet {
+    pub fn new(guards: Vec<GuardName>) -> Self {
+        Self {
+            guards,
+            relevance_threshold: DEFAULT_RELEVANCE_THRESHOLD,
+        }
+    }
+
+    /// Minimum similarity of the semantic relevance guard
+    pub fn with_relevance_threshold(mut self, threshold: f32) -> Self {
+        self.relevance_threshold = threshold;
+        self
+    }
+
+    /// The similarity threshold, if the semantic relevance guard is enabled
+    ///
+    /// The guard needs an embedder, so hosts build it and add it to both
+    /// chains themselves.
+    pub fn relevance_threshold(&self) -> Option<f32> {
+        self.guards
+            .contains(&GuardName::SemanticRelevance)
+            .then_some(self.relevance_threshold)
+    }
+
+    /// Chain checking tool outputs
+    pub fn output_chain(&self) -> GuardrailChain {
+        let mut chain = GuardrailChain::new();
+        if self.guards.contains(&GuardName::Plausibility) {
+            chain = chain.add(Box::new(PlausibilityGuard::new()));
+        }
+        chain
+    }
+
+    /// Chain checking final answers; the caller's [`ResponseSpec`] is always
+    /// checked
+    ///
+    /// [`ResponseSpec`]: crate::response_spec::ResponseSpec
+    pub fn answer_chain(&self) -> AnswerChain {
+        let mut chain = AnswerChain::new();
+        if self.guards.contains(&GuardName::NumericConsistency) {
+            chain = chain.add(Box::new(NumericConsistencyGuard::new()));
+        }
+        chain.add(Box::new(ResponseSpecGuard::new()))
+    }
+}
+
+/// A named bundle of system prompt, tool set and guardrails
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    /// Custom prompt text
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+    /// File holding the custom prompt, relative to the config file
+    #[serde(default)]
+    pub system_prompt_file: Option<PathBuf>,
+    #[serde(default)]
+    pub prompt_mode: PromptMode,
+    /// Tools offered to the model (default: all)
+    #[serde(default)]
+    pub tools: Option<Vec<String>>,
+    /// Semantic guardrails to run (default: plausibility and
+    /// numeric-consistency)
+    #[serde(default)]
+    pub guardrails: Option<Vec<GuardName>>,
+    /// Minimum query similarity for `semantic-relevance`
+    #[serde(default)]
+    pub relevance_threshold: Option<f32>,
+    /// Refuse tools above this safety class
+    #[serde(default)]
+    pub max_safety: Option<SafetyClass>,
+}
+
+impl Profile {
+    pub fn guardrails(&self) -> GuardrailSet {
+        let set = self
+            .guardrails
+            .clone()
+            .map_or_else(GuardrailSet::default, GuardrailSet::new);
+        match self.relevance_threshold {
+            Some(threshold) => set.with_relevance_threshold(threshold),
+            None => set,
+        }
+    }
+}
+
+/// The host config file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AgentConfig {
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+impl AgentConfig {
+    /// Parse and check a config
+    pub fn from_yaml(yaml: &str) -> Result<Self, ProfileError> {
+        let config: Self = serde_yaml::from_str(yaml)?;
+        if let Some((name, _)) = config.profiles.iter().find(|(_, profile)| {
+            profile.system_prompt.is_some() && profile.system_prompt_file.is_some()
+        }) {
+            return Err(ProfileError::ConflictingPrompts(name.clone()));
+        }
+        Ok(config)
+    }
+
+    pub fn profile(&self, name: &str) -> Result<&Profile, ProfileError> {
+        self.profiles
+            .get(name)
+            .ok_or_else(|| ProfileError::UnknownProfile {
+                name: name.to_string(),
+                available: match self.profiles.is_empty() {
+                    true => "none".to_string(),
+                    false => self.profiles.keys().cloned().collect::<Vec<_>>().join(", "),
+                },
+            })
+    }
+}
+
+/// The parts of a system prompt
+#[derive(Debug, Clone, Copy)]
+pub struct PromptSections<'a> {
+    /// Opening line
+    pub intro: &'a str,
+    /// Generated "Available tools" section
+    pub tools: &'a str,
+    /// Built-in instructions (invocation formats and answer rules)
+    pub instructions: &'a str,
+    /// Few-shot examples of tool and skill calls (may be empty)
+    pub examples: &'a str,
+    /// Generated skills section (may be empty)
+    pub skills: &'a str,
+    /// Environment facts (see [`crate::environment`]; may be empty)
+    pub environment: &'a str,
+}
+
+/// Build a system prompt, merging in a custom prompt if there is one
+pub fn compose_system_prompt(
+    sections: PromptSections,
+    custom: Option<(&str, PromptMode)>,
+) -> String {
+    let parts = match custom {
+        None => vec![
+            sections.intro,
+            sections.tools,
+            sections.instructions,
+            sections.examples,
+        ],
+        Some((text, PromptMode::Extend)) => vec![
+            sections.intro,
+            sections.tools,
+            sections.instructions,
+            sections.examples,
+            text.trim(),
+        ],
+        Some((text, PromptMode::Replace)) => vec![text.trim(), sections.tools, sections.examples],
+    };
+    parts
+        .into_iter()
+        .chain([sections.skills, sections.environment])
+        .filter(|part| !part.trim().is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECTIONS: PromptSections = PromptSections {
+        intro: "INTRO",
+        tools: "TOOLS",
+        instructions: "INSTRUCTIONS",
+        examples: "EXAMPLES",
+        skills: "SKILLS",
+        environment: "",
+    };
+
+    #[test]
+    fn test_compose_keeps_generated_sections() {
+        assert_eq!(
+            compose_system_prompt(SECTIONS, None),
+            "INTRO\n\nTOOLS\n\nINSTRUCTIONS\n\nEXAMPLES\n\nSKILLS"
+        );
+        assert_eq!(
+            compose_system_prompt(SECTIONS, Some(("Be terse.\n", PromptMode::Extend))),
+            "INTRO\n\nTOOLS\n\nINSTRUCTIONS\n\nEXAMPLES\n\nBe terse.\n\nSKILLS"
+        );
+        assert_eq!(
+            compose_system_prompt(SECTIONS, Some(("You review code.", PromptMode::Replace))),
+            "You review code.\n\nTOOLS\n\nEXAMPLES\n\nSKILLS"
+        );
+        // Environment facts hold whatever the prompt says
+        let dated = PromptSections {
+            environment: "ENVIRONMENT",
+            ..SECTIONS
+        };
+        assert_eq!(
+            compose_system_prompt(dated, Some(("You review code.", PromptMode::Replace))),
+            "You review code.\n\nTOOLS\n\nEXAMPLES\n\nSKILLS\n\nENVIRONMENT"
+        );
+
+        let no_skills = PromptSections {
+            examples: "",
+            skills: " \n",
+            ..SECTIONS
+        };
+        assert_eq!(
+            compose_system_prompt(no_skills, None),
+            "INTRO\n\nTOOLS\n\nINSTRUCTIONS"
+        );
+    }
+
+    #[test]
+    fn test_config_parsing() {
+        let config = AgentConfig::from_yaml(
+            r#"
+profiles:
+  coder:
+    system_prompt_file: prompts/coder.md
+    prompt_mode: replace
+    tools: [shell, apply_patch]
+    max_safety: mutating
+  researcher:
+    system_prompt: Cite sources.
+    guardrails: [numeric-consistency]
+  support:
+    guardrails: [semantic-relevance]
+    relevance_threshold: 0.45
+"#,
+        )
+        .unwrap();
+
+        let coder = config.profile("coder").unwrap();
+        assert_eq!(coder.prompt_mode, PromptMode::Replace);
+        assert_eq!(coder.max_safety, Some(SafetyClass::Mutating));
+        assert_eq!(coder.guardrails(), GuardrailSet::default());
+        let researcher = config.profile("researcher").unwrap();
+        assert_eq!(researcher.prompt_mode, PromptMode::Extend);
+        assert_eq!(
+            researcher.guardrails(),
+            GuardrailSet::new(vec![GuardName::NumericConsistency])
+        );
+        assert_eq!(researcher.guardrails().relevance_threshold(), None);
+        let support = config.profile("support").unwrap().guardrails();
+        assert_eq!(support.relevance_threshold(), Some(0.45));
+        assert!(support.output_chain().is_empty());
+
+        assert!(matches!(
+            config.profile("writer"),
+            Err(ProfileError::UnknownProfile { available, .. }) if available == "coder, researcher, support"
+        ));
+    }
+
+    #[test]
+    fn test_invalid_configs() {
+        assert!(matches!(
+            AgentConfig::from_yaml(
+                "profiles:\n  a:\n    system_prompt: x\n    system_prompt_file: x.md\n"
+            ),
+            Err(ProfileError::ConflictingPrompts(name)) if name == "a"
+        ));
+        assert!(matches!(
+            AgentConfig::from_yaml("profiles:\n  a:\n    guardrails: [sanity]\n"),
+            Err(ProfileError::Yaml(_))
+        ));
+        assert!(matches!(
+            AgentConfig::from_yaml("profiles:\n  a:\n    tool: [shell]\n"),
+            Err(ProfileError::Yaml(_))
+        ));
+        assert_eq!(PromptMode::from_str("Replace"), Some(PromptMode::Replace));
+        assert_eq!(PromptMode::from_str("merge"), None);
+    }
+}
@@ -0,0 +1,134 @@
+//! Adaptive corrective prompting
+//!
+//! Small models tend to repeat the mistake a guardrail just rejected. The
+//! agent state counts guardrail rejections per reason; [`PromptAdaptation`]
+//! turns repeated rejections into reason-specific guidance that the host adds
+//! to its corrective prompt.
+
+use crate::agent::AgentState;
+use crate::prelude::*;
+
+/// Guidance added once rejections matching a reason reach a threshold
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdaptationRule {
+    /// Case-insensitive substring of the rejection reason
+    pub reason_contains: String,
+    /// Matching rejections needed before the guidance is added
+    pub min_rejections: usize,
+    /// Instruction added to the corrective prompt
+    pub guidance: String,
+}
+
+/// Reason-specific guidance for corrective prompts
+///
+/// The default rules cover the [`PlausibilityGuard`](crate::guardrail::PlausibilityGuard)
+/// rejections and trigger on the second matching rejection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PromptAdaptation {
+    rules: Vec<AdaptationRule>,
+}
+
+impl Default for PromptAdaptation {
+    fn default() -> Self {
+        Self::new()
+            .with_rule(
+                "metadata",
+                2,
+                "Do NOT use `ls -l` or any command whose output starts with a 'total' line. \
+                 Use plain `ls` or a command that prints only the requested data.",
+            )
+            .with_rule(
+                "empty",
+                2,
+                "Previous commands printed nothing. Use a command that writes the requested \
+                 data to stdout, and check paths and filters before running it.",
+            )
+            .with_rule(
+                "lacks substantive content",
+                2,
+                "Previous outputs had no usable content. Use a command that prints the \
+                 actual data requested, not symbols, separators or counts alone.",
+            )
+            .with_rule(
+                "only a count",
+                2,
+                "Do NOT pipe into `wc`. The user asked for names, so print the names \
+                 themselves.",
+            )
+    }
+}
+
+impl PromptAdaptation {
+    /// An adaptation without rules
+    pub fn new() -> Self {
+        Self { rules: Vec::new() }
+    }
+
+    /// Add guidance for rejections whose reason contains `reason_contains`
+    pub fn with_rule(
+        mut self,
+        reason_contains: impl Into<String>,
+        min_rejections: usize,
+        guidance: impl Into<String>,
+    ) -> Self {
+        self.rules.push(AdaptationRule {
+            reason_contains: reason_contains.into().to_lowercase(),
+            min_rejections,
+            guidance: guidance.into(),
+        });
+        self
+    }
+
+    pub fn rules(&self) -> &[AdaptationRule] {
+        &self.rules
+    }
+
+    /// Guidance for the rejections recorded so far, in rule order
+    pub fn guidance(&self, state: &AgentState) -> Vec<&str> {
+        self.rules
+            .iter()
+            .filter(|rule| {
+                let count: usize = state
+                    .guardrail_rejections
+                    .iter()
+                    .filter(|(reason, _)| reason.to_lowercase().contains(&rule.reason_contains))
+                    .map(|(_, count)| count)
+                    .sum();
+                count >= rule.min_rejections
+            })
+            .map(|rule| rule.guidance.as_str())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const METADATA: &str =
+        "Tool output contains only metadata (e.g. 'total' line), not actual data";
+
+    #[test]
+    fn test_guidance_after_repeated_rejections() {
+        let adaptation = PromptAdaptation::default();
+        let mut state = AgentState::new("List files");
+        assert!(adaptation.guidance(&state).is_empty());
+
+        state.record_rejection(METADATA);
+        assert!(adaptation.guidance(&state).is_empty());
+
+        state.record_rejection(METADATA);
+        let guidance = adaptation.guidance(&state);
+        assert_eq!(guidance.len(), 1);
+        assert!(guidance[0].contains("ls -l"));
+    }
+
+    #[test]
+    fn test_custom_rule_matches_across_reasons() {
+        let adaptation = PromptAdaptation::new().with_rule("Timeout", 2, "Use faster commands.");
+        let mut state = AgentState::new("Find files");
+        state.record_rejection("timeout after 5s");
+        state.record_rejection("TIMEOUT after 10s");
+        assert_eq!(adaptation.guidance(&state), vec!["Use faster commands."]);
+    }
+}
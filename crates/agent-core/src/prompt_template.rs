@@ -0,0 +1,169 @@
+//! Prompt templates with mustache-style variable substitution
+//!
+//! Skill manifests can declare the prompt used to execute the skill, e.g.:
+//!
+//! ```text
+//! Extract {{target}} values from: {{input.text}}
+//! ```
+//!
+//! Variables are dotted paths resolved against a JSON context. Rendering is strict:
+//! a variable that does not resolve is an error rather than an empty string, so
+//! typos in SKILL.md surface immediately instead of silently degrading the prompt.
+
+use crate::prelude::*;
+use serde_json::Value;
+
+/// Errors while parsing or rendering a prompt template
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum TemplateError {
+    #[error("unclosed '{{{{' at offset {0}")]
+    Unclosed(usize),
+    #[error("empty variable name at offset {0}")]
+    EmptyVariable(usize),
+    #[error("unknown template variable '{0}'")]
+    UnknownVariable(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Text(String),
+    Variable(String),
+}
+
+/// A parsed prompt template
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PromptTemplate {
+    segments: Vec<Segment>,
+}
+
+impl PromptTemplate {
+    /// Parse a template string
+    pub fn parse(source: &str) -> Result<Self, TemplateError> {
+        let mut segments = Vec::new();
+        let mut rest = source;
+        let mut offset = 0;
+
+        while let Some(open) = rest.find("{{") {
+            if open > 0 {
+                segments.push(Segment::Text(rest[..open].to_string()));
+            }
+            let after_open = &rest[open + 2..];
+            let close = after_open
+                .find("}}")
+                .ok_or(TemplateError::Unclosed(offset + open))?;
+            let name = after_open[..close].trim();
+            if name.is_empty() {
+                return Err(TemplateError::EmptyVariable(offset + open));
+            }
+            segments.push(Segment::Variable(name.to_string()));
+
+            let consumed = open + 2 + close + 2;
+            offset += consumed;
+            rest = &rest[consumed..];
+        }
+        if !rest.is_empty() {
+            segments.push(Segment::Text(rest.to_string()));
+        }
+
+        Ok(Self { segments })
+    }
+
+    /// Names of all variables referenced by the template, in order of first use
+    pub fn variables(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = Vec::new();
+        for segment in &self.segments {
+            if let Segment::Variable(name) = segment {
+                if !names.contains(&name.as_str()) {
+                    names.push(name);
+                }
+            }
+        }
+        names
+    }
+
+    /// Check that every variable resolves against the given context
+    pub fn check(&self, context: &Value) -> Result<(), TemplateError> {
+        for name in self.variables() {
+            if lookup(context, name).is_none() {
+                return Err(TemplateError::UnknownVariable(name.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Render the template against a JSON context
+    ///
+    /// Strings render verbatim, `null` renders as an empty string, and other
+    /// values render as compact JSON.
+    pub fn render(&self, context: &Value) -> Result<String, TemplateError> {
+        let mut out = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Text(text) => out.push_str(text),
+                Segment::Variable(name) => {
+                    let value = lookup(context, name)
+                        .ok_or_else(|| TemplateError::UnknownVariable(name.clone()))?;
+                    match value {
+                        Value::String(s) => out.push_str(s),
+                        Value::Null => {}
+                        other => out.push_str(&other.to_string()),
+                    }
+                }
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// Resolve a dotted path (`input.text`) in a JSON context
+pub(crate) fn lookup<'a>(context: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.')
+        .try_fold(context, |value, key| value.as_object()?.get(key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_render_dotted_variables() {
+        let template = PromptTemplate::parse("Find {{ target }} in: {{input.text}}!").unwrap();
+        let context = json!({"target": "email", "input": {"text": "hi@agent.rs"}});
+        assert_eq!(
+            template.render(&context).unwrap(),
+            "Find email in: hi@agent.rs!"
+        );
+        assert_eq!(template.variables(), vec!["target", "input.text"]);
+    }
+
+    #[test]
+    fn test_unknown_variable_is_error() {
+        let template = PromptTemplate::parse("{{input.txt}}").unwrap();
+        let context = json!({"input": {"text": "x"}});
+        assert_eq!(
+            template.render(&context),
+            Err(TemplateError::UnknownVariable("input.txt".to_string()))
+        );
+        assert!(template.check(&context).is_err());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            PromptTemplate::parse("abc {{target"),
+            Err(TemplateError::Unclosed(4))
+        );
+        assert_eq!(
+            PromptTemplate::parse("{{ }}"),
+            Err(TemplateError::EmptyVariable(0))
+        );
+    }
+
+    #[test]
+    fn test_non_string_values() {
+        let template = PromptTemplate::parse("{{a}}|{{b}}|{{c}}").unwrap();
+        let context = json!({"a": ["x", "y"], "b": null, "c": 3});
+        assert_eq!(template.render(&context).unwrap(), r#"["x","y"]||3"#);
+    }
+}
@@ -1,44 +1,302 @@
+use crate::artifact::{ArtifactRequest, ToolMoreRequest};
+use crate::memory::RecallRequest;
+use crate::prelude::*;
 use crate::skill::SkillRequest;
 use crate::tool::ToolRequest;
+use crate::variables::VariableRequest;
+use serde::{Deserialize, Serialize};
+
+/// Prefix that marks a final answer under [`AnswerProtocol::Sentinel`]
+pub const FINAL_PREFIX: &str = "FINAL:";
+
+/// Line openings of tool outputs, which only the host writes
+const OUTPUT_MARKERS: &[&str] = &["Tool output:", "Observation:"];
+
+/// How a final answer is told apart from reasoning
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnswerProtocol {
+    /// Plain text is an answer unless it reads like planning ("I will",
+    /// "let me", ...); English-only and easy to trip
+    #[default]
+    Heuristic,
+    /// Only `FINAL: <answer>` or `{"final": "<answer>"}` is an answer; any
+    /// other text is inconclusive, in whatever language or prompt
+    Sentinel,
+}
+
+impl AnswerProtocol {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "heuristic" => Some(Self::Heuristic),
+            "sentinel" => Some(Self::Sentinel),
+            _ => None,
+        }
+    }
+
+    /// System prompt instructions the protocol needs (empty if none)
+    pub fn instructions(&self) -> &'static str {
+        match self {
+            Self::Heuristic => "",
+            Self::Sentinel => SENTINEL_INSTRUCTIONS,
+        }
+    }
+}
+
+const SENTINEL_INSTRUCTIONS: &str = r#"FINAL ANSWERS:
+- Start a final answer with FINAL: followed by the answer, in any language:
+FINAL: The directory contains 5 files.
+- Or respond with JSON: {"final": "The directory contains 5 files."}
+- A plain text reply without FINAL: is not taken as an answer"#;
 
 /// Parse model output to determine if it contains a tool call, skill invocation, or final answer
 ///
 /// Protocol:
 /// - If the output contains valid JSON with a "skill" field, it's a skill invocation
+/// - If the output contains valid JSON with a "read_artifact" or "tool_more" object, it's an
+///   artifact read
+/// - If the output contains valid JSON with a "set_var" or "get_var" object, it's a
+///   scratchpad operation
+/// - If the output contains valid JSON with a "recall" string, it's a memory search
 /// - If the output contains valid JSON with a "tool" field, it's a tool call
+/// - If a line opens a tool output ("Tool output:", "Observation:"), the model
+///   made it up; the output is inconclusive, cut before that line
 /// - If the output appears to be reasoning/explanation without action, it's inconclusive
 /// - Otherwise, it's treated as a final answer
 pub fn parse_model_output(output: &str) -> ParseResult {
+    parse_model_output_with(output, AnswerProtocol::Heuristic)
+}
+
+/// Parse model output, recognizing final answers by `protocol`
+///
+/// Calls are parsed the same under both protocols; under
+/// [`AnswerProtocol::Sentinel`] output without the sentinel is inconclusive.
+pub fn parse_model_output_with(output: &str, protocol: AnswerProtocol) -> ParseResult {
     let trimmed = output.trim();
+    let inconclusive = |reason| ParseResult::Inconclusive(trimmed.to_string(), reason);
+    if trimmed.is_empty() {
+        return inconclusive(InconclusiveReason::Empty);
+    }
+
+    // Whatever follows a made-up tool output is not kept
+    if let Some((start, marker)) = fabricated_output(trimmed) {
+        return ParseResult::Inconclusive(
+            trimmed[..start].trim_end().to_string(),
+            InconclusiveReason::FabricatedOutput {
+                marker: marker.trim_end_matches(':').to_string(),
+            },
+        );
+    }
 
     // Try to parse as JSON
-    if let Ok(value) = serde_json::from_str::<serde_json::Value>(trimmed) {
+    let json = serde_json::from_str::<serde_json::Value>(trimmed);
+    if let Ok(value) = &json {
         // Check if it has a "skill" field first (skills take precedence)
         if value.get("skill").is_some() {
             // Try to deserialize as SkillRequest
-            if let Ok(skill_request) = serde_json::from_value::<SkillRequest>(value.clone()) {
-                return ParseResult::SkillCall(skill_request);
+            match serde_json::from_value::<SkillRequest>(value.clone()) {
+                Ok(skill_request) => return ParseResult::SkillCall(skill_request),
+                Err(e) => {
+                    return inconclusive(InconclusiveReason::InvalidCall {
+                        error: e.to_string(),
+                    })
+                }
+            }
+        }
+
+        // Check if it asks for more of a stored tool output
+        if let Some(request) = value.get("read_artifact") {
+            if let Ok(request) = serde_json::from_value::<ArtifactRequest>(request.clone()) {
+                return ParseResult::ReadArtifact(request);
+            }
+        }
+        if let Some(request) = value.get("tool_more") {
+            if let Ok(request) = serde_json::from_value::<ToolMoreRequest>(request.clone()) {
+                return ParseResult::ReadArtifact(request.into());
+            }
+        }
+
+        // Check if it stores or reads a scratchpad variable
+        if value.get("set_var").is_some() || value.get("get_var").is_some() {
+            if let Ok(request) = serde_json::from_value::<VariableRequest>(value.clone()) {
+                return ParseResult::Variable(request);
+            }
+        }
+
+        // Check if it searches the memory
+        if value.get("recall").is_some_and(|query| query.is_string()) {
+            if let Ok(request) = serde_json::from_value::<RecallRequest>(value.clone()) {
+                return ParseResult::Recall(request);
             }
         }
 
         // Check if it has a "tool" field
         if value.get("tool").is_some() {
             // Try to deserialize as ToolRequest
-            if let Ok(tool_request) = serde_json::from_value::<ToolRequest>(value) {
-                return ParseResult::ToolCall(tool_request);
+            match serde_json::from_value::<ToolRequest>(value.clone()) {
+                Ok(tool_request) => return ParseResult::ToolCall(tool_request),
+                Err(e) => {
+                    return inconclusive(InconclusiveReason::InvalidCall {
+                        error: e.to_string(),
+                    })
+                }
             }
         }
+
+        if protocol == AnswerProtocol::Sentinel {
+            if let Some(serde_json::Value::String(answer)) = value.get("final") {
+                return ParseResult::FinalAnswer(answer.trim().to_string());
+            }
+        }
+    }
+
+    // A call that is not valid JSON is never an answer
+    if let Err(e) = &json {
+        if trimmed.starts_with('{') && names_call(trimmed) {
+            return inconclusive(InconclusiveReason::MalformedJson {
+                error: e.to_string(),
+            });
+        }
+    }
+
+    if protocol == AnswerProtocol::Sentinel {
+        return match trimmed.strip_prefix(FINAL_PREFIX) {
+            Some(answer) if !answer.trim().is_empty() => {
+                ParseResult::FinalAnswer(answer.trim().to_string())
+            }
+            _ => inconclusive(InconclusiveReason::of_text(trimmed, protocol)),
+        };
     }
 
     // Detect inconclusive outputs - reasoning without action
     if is_inconclusive(trimmed) {
-        return ParseResult::Inconclusive(trimmed.to_string());
+        return inconclusive(InconclusiveReason::of_text(trimmed, protocol));
     }
 
     // Otherwise, treat as final answer
     ParseResult::FinalAnswer(trimmed.to_string())
 }
 
+/// Where the first line opening a tool output starts, and its marker
+fn fabricated_output(text: &str) -> Option<(usize, &'static str)> {
+    let mut start = 0;
+    for line in text.split('\n') {
+        let opening = line.trim_start();
+        if let Some(marker) = OUTPUT_MARKERS.iter().find(|m| opening.starts_with(**m)) {
+            return Some((start, marker));
+        }
+        start += line.len() + 1;
+    }
+    None
+}
+
+/// Whether `text` mentions a call key (`"tool"` or `"skill"`)
+fn names_call(text: &str) -> bool {
+    text.contains("\"tool\"") || text.contains("\"skill\"")
+}
+
+/// Why a model output was neither a call nor an answer
+///
+/// The corrective retry tells the model what went wrong
+/// ([`instruction`](Self::instruction)).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum InconclusiveReason {
+    /// Nothing was written
+    Empty,
+    /// Only a reasoning block, no reply after it
+    ReasoningOnly,
+    /// A call that is not valid JSON (`error` from the JSON parser)
+    MalformedJson { error: String },
+    /// Valid JSON naming a tool or skill whose fields do not fit a call
+    InvalidCall { error: String },
+    /// A JSON call with text around it
+    TextAroundCall,
+    /// Plain text without the `FINAL:` sentinel
+    MissingSentinel,
+    /// A description of what the model will do instead of doing it
+    Planning,
+    /// A tool output the model wrote itself (`marker` opened it, e.g.
+    /// "Tool output"); the output is cut before it
+    FabricatedOutput { marker: String },
+}
+
+impl InconclusiveReason {
+    /// The reason for inconclusive text that is not a call
+    fn of_text(text: &str, protocol: AnswerProtocol) -> Self {
+        if text.contains('{') && names_call(text) {
+            Self::TextAroundCall
+        } else if protocol == AnswerProtocol::Sentinel {
+            Self::MissingSentinel
+        } else {
+            Self::Planning
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Empty => "empty",
+            Self::ReasoningOnly => "reasoning_only",
+            Self::MalformedJson { .. } => "malformed_json",
+            Self::InvalidCall { .. } => "invalid_call",
+            Self::TextAroundCall => "text_around_call",
+            Self::MissingSentinel => "missing_sentinel",
+            Self::Planning => "planning",
+            Self::FabricatedOutput { .. } => "fabricated_output",
+        }
+    }
+
+    /// What the corrective retry tells the model about its last reply
+    pub fn instruction(&self) -> String {
+        match self {
+            Self::Empty => "Your last reply was empty.".to_string(),
+            Self::ReasoningOnly => {
+                "Your last reply stopped after reasoning, with no call or answer.".to_string()
+            }
+            Self::MalformedJson { error } => format!("Your JSON was invalid: {}.", error),
+            Self::InvalidCall { error } => {
+                format!("Your call did not match the call format: {}.", error)
+            }
+            Self::TextAroundCall => {
+                "Your last reply had text around the JSON call; send the JSON object alone."
+                    .to_string()
+            }
+            Self::MissingSentinel => {
+                "Your last reply had neither a call nor a FINAL: answer.".to_string()
+            }
+            Self::Planning => {
+                "You described the plan instead of acting; make the call now.".to_string()
+            }
+            Self::FabricatedOutput { marker } => format!(
+                "Your last reply made up a '{}:' section; only the host writes tool outputs. \
+                 Send the call alone and wait for its output.",
+                marker
+            ),
+        }
+    }
+}
+
+/// Render a tool call in the protocol's JSON form
+///
+/// The inverse of [`parse_model_output`] for tool calls: backends whose APIs
+/// return native tool-use blocks translate them through this, so the agent loop
+/// sees the same output as from a text-only model.
+pub fn format_tool_call(request: &ToolRequest) -> String {
+    let mut call = serde_json::Map::new();
+    call.insert(
+        "tool".to_string(),
+        serde_json::Value::String(request.tool.clone()),
+    );
+    if let Some(params) = request.params.as_object() {
+        for (name, value) in params {
+            if name != "tool" {
+                call.insert(name.clone(), value.clone());
+            }
+        }
+    }
+    serde_json::Value::Object(call).to_string()
+}
+
 /// Detect if output is inconclusive (reasoning without action)
 ///
 /// An output is inconclusive if it describes intent or approach but doesn't
@@ -84,12 +342,21 @@ pub enum ParseResult {
     /// The model wants to invoke a skill
     SkillCall(SkillRequest),
 
+    /// The model wants to read more of a stored tool output
+    ReadArtifact(ArtifactRequest),
+
+    /// The model wants to store or read a scratchpad variable
+    Variable(VariableRequest),
+
+    /// The model wants to search the memory
+    Recall(RecallRequest),
+
     /// The model has produced a final answer
     FinalAnswer(String),
 
     /// The model produced output that doesn't complete the task or invoke a tool/skill
-    /// (reasoning, explanation, or malformed output)
-    Inconclusive(String),
+    /// (reasoning, explanation, or malformed output), and why
+    Inconclusive(String, InconclusiveReason),
 }
 
 #[cfg(test)]
@@ -107,6 +374,69 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_format_tool_call_round_trip() {
+        let request = ToolRequest {
+            tool: "shell".to_string(),
+            call_id: None,
+            params: serde_json::json!({"command": "ls -la"}),
+        };
+        match parse_model_output(&format_tool_call(&request)) {
+            ParseResult::ToolCall(req) => {
+                assert_eq!(req.tool, "shell");
+                assert_eq!(req.params["command"], "ls -la");
+            }
+            _ => panic!("Expected tool call"),
+        }
+    }
+
+    #[test]
+    fn test_parse_read_artifact() {
+        let json = r#"{"read_artifact": {"id": "tool-003", "range": [41, 140]}}"#;
+        match parse_model_output(json) {
+            ParseResult::ReadArtifact(request) => {
+                assert_eq!(request.id, "tool-003");
+                assert_eq!(request.range, Some((41, 140)));
+            }
+            _ => panic!("Expected artifact read"),
+        }
+    }
+
+    #[test]
+    fn test_parse_tool_more() {
+        let json = r#"{"tool_more": {"id": 3, "page": 2}}"#;
+        match parse_model_output(json) {
+            ParseResult::ReadArtifact(request) => {
+                assert_eq!(request.id, "tool-003");
+                assert_eq!(request.page, Some(2));
+            }
+            _ => panic!("Expected artifact read"),
+        }
+    }
+
+    #[test]
+    fn test_parse_variable_request() {
+        let json = r#"{"set_var": {"name": "ids", "value": [3, 7]}}"#;
+        match parse_model_output(json) {
+            ParseResult::Variable(VariableRequest::SetVar { name, value }) => {
+                assert_eq!(name, "ids");
+                assert_eq!(value, serde_json::json!([3, 7]));
+            }
+            _ => panic!("Expected variable request"),
+        }
+    }
+
+    #[test]
+    fn test_parse_recall_request() {
+        match parse_model_output(r#"{"recall": "staging credentials"}"#) {
+            ParseResult::Recall(request) => {
+                assert_eq!(request.query, "staging credentials");
+                assert_eq!(request.k, None);
+            }
+            _ => panic!("Expected recall request"),
+        }
+    }
+
     #[test]
     fn test_parse_final_answer() {
         let text = "The current directory contains 5 files.";
@@ -118,6 +448,83 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_sentinel_answers() {
+        let sentinel = AnswerProtocol::Sentinel;
+        match parse_model_output_with("FINAL: Il y a 5 fichiers.", sentinel) {
+            ParseResult::FinalAnswer(answer) => assert_eq!(answer, "Il y a 5 fichiers."),
+            _ => panic!("Expected final answer"),
+        }
+        match parse_model_output_with(r#"{"final": "There are 5 files."}"#, sentinel) {
+            ParseResult::FinalAnswer(answer) => assert_eq!(answer, "There are 5 files."),
+            _ => panic!("Expected final answer"),
+        }
+        // Answers without the sentinel are not taken, planning-speak or not
+        for output in [
+            "The current directory contains 5 files.",
+            "FINAL:",
+            "{\"result\": 1}",
+        ] {
+            assert!(matches!(
+                parse_model_output_with(output, sentinel),
+                ParseResult::Inconclusive(..)
+            ));
+        }
+        assert!(matches!(
+            parse_model_output_with(r#"{"tool": "shell", "command": "ls"}"#, sentinel),
+            ParseResult::ToolCall(_)
+        ));
+    }
+
+    #[test]
+    fn test_inconclusive_reasons() {
+        let reason = |output| match parse_model_output(output) {
+            ParseResult::Inconclusive(_, reason) => reason,
+            other => panic!("Expected inconclusive, got {:?}", other),
+        };
+        assert_eq!(reason("  "), InconclusiveReason::Empty);
+        assert_eq!(
+            reason("I will list the files first."),
+            InconclusiveReason::Planning
+        );
+        assert_eq!(
+            reason(r#"Let me run {"tool": "shell", "command": "ls"}"#),
+            InconclusiveReason::TextAroundCall
+        );
+        assert!(matches!(
+            reason(r#"{"tool": "shell", "command": "ls",}"#),
+            InconclusiveReason::MalformedJson { error } if error.contains("trailing comma")
+        ));
+        assert!(matches!(
+            reason(r#"{"tool": 7}"#),
+            InconclusiveReason::InvalidCall { .. }
+        ));
+        // The made-up output is cut off
+        assert!(matches!(
+            parse_model_output(
+                "{\"tool\": \"shell\", \"command\": \"ls\"}\nTool output:\nREADME.md\nsrc"
+            ),
+            ParseResult::Inconclusive(output, InconclusiveReason::FabricatedOutput { marker })
+                if output == r#"{"tool": "shell", "command": "ls"}"# && marker == "Tool output"
+        ));
+        assert!(matches!(
+            reason("Checking the size.\n  Observation: 4.2 MB\nThe file is 4.2 MB."),
+            InconclusiveReason::FabricatedOutput { marker } if marker == "Observation"
+        ));
+        assert_eq!(
+            match parse_model_output_with("There are 5 files.", AnswerProtocol::Sentinel) {
+                ParseResult::Inconclusive(_, reason) => reason,
+                other => panic!("Expected inconclusive, got {:?}", other),
+            },
+            InconclusiveReason::MissingSentinel
+        );
+        assert!(InconclusiveReason::MalformedJson {
+            error: "trailing comma at line 1 column 36".into()
+        }
+        .instruction()
+        .starts_with("Your JSON was invalid: trailing comma"));
+    }
+
     #[test]
     fn test_parse_json_without_tool() {
         let json = r#"{"result": "some data"}"#;
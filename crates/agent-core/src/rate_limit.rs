@@ -0,0 +1,261 @@
+//! Client-side rate limits for model APIs
+//!
+//! Hosted APIs cap requests and tokens per minute and answer `429 Too Many
+//! Requests` beyond that. Hosts pace their own requests to stay under the caps
+//! ([`RateWindow`]) and retry throttled ones after the delay the server asks
+//! for ([`RateLimits::retry_delay`]).
+//!
+//! This module only decides when a request may go and how long to back off;
+//! hosts sleep and send. Times are offsets from an epoch the host picks.
+
+use crate::prelude::*;
+use alloc::collections::VecDeque;
+use core::time::Duration;
+
+/// Retries of a throttled request before giving up
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+
+/// Longest `Retry-After` a request waits for; beyond it the request fails
+pub const MAX_RETRY_WAIT: Duration = Duration::from_secs(300);
+
+/// Span the per-minute limits are counted over
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// First backoff when the server gives no `Retry-After`; doubles per attempt
+const BASE_BACKOFF: Duration = Duration::from_secs(1);
+
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Limits for one API; `None` leaves a limit off
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimits {
+    pub requests_per_minute: Option<u32>,
+    /// Prompt plus completion tokens per minute
+    pub tokens_per_minute: Option<u64>,
+    /// Requests in flight at once; further requests queue
+    pub max_concurrent: Option<usize>,
+    /// Retries of a request the server throttled (429 or 503)
+    pub max_retries: u32,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        Self {
+            requests_per_minute: None,
+            tokens_per_minute: None,
+            max_concurrent: None,
+            max_retries: DEFAULT_MAX_RETRIES,
+        }
+    }
+}
+
+impl RateLimits {
+    pub fn with_requests_per_minute(mut self, limit: Option<u32>) -> Self {
+        self.requests_per_minute = limit;
+        self
+    }
+
+    pub fn with_tokens_per_minute(mut self, limit: Option<u64>) -> Self {
+        self.tokens_per_minute = limit;
+        self
+    }
+
+    pub fn with_max_concurrent(mut self, limit: Option<usize>) -> Self {
+        self.max_concurrent = limit;
+        self
+    }
+
+    pub fn with_max_retries(mut self, retries: u32) -> Self {
+        self.max_retries = retries;
+        self
+    }
+
+    /// How long to wait before retrying a throttled request, or `None` to
+    /// give up
+    ///
+    /// `attempt` counts retries so far. The server's `retry_after` wins over
+    /// exponential backoff; a wait beyond [`MAX_RETRY_WAIT`] gives up.
+    pub fn retry_delay(&self, attempt: u32, retry_after: Option<Duration>) -> Option<Duration> {
+        if attempt >= self.max_retries {
+            return None;
+        }
+        let delay = retry_after.unwrap_or_else(|| {
+            BASE_BACKOFF
+                .saturating_mul(1 << attempt.min(16))
+                .min(MAX_BACKOFF)
+        });
+        (delay <= MAX_RETRY_WAIT).then_some(delay)
+    }
+}
+
+/// Whether a response status means "slow down and retry"
+pub fn is_throttled(status: u16) -> bool {
+    matches!(status, 429 | 503)
+}
+
+/// Requests sent in the last minute, with their tokens
+#[derive(Debug, Clone, Default)]
+pub struct RateWindow {
+    sent: VecDeque<(Duration, u64)>,
+}
+
+impl RateWindow {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How long a request of `tokens` must wait at `now` to stay within
+    /// `limits` (zero: it may go now)
+    ///
+    /// A request larger than the whole token limit goes once the window is
+    /// empty rather than never.
+    pub fn delay(&mut self, limits: &RateLimits, now: Duration, tokens: u64) -> Duration {
+        while self.sent.front().is_some_and(|(at, _)| *at + WINDOW <= now) {
+            self.sent.pop_front();
+        }
+
+        let mut wait = Duration::ZERO;
+        if let Some(limit) = limits.requests_per_minute {
+            let limit = limit.max(1) as usize;
+            if self.sent.len() >= limit {
+                let (at, _) = self.sent[self.sent.len() - limit];
+                wait = wait.max(at + WINDOW - now);
+            }
+        }
+        if let Some(limit) = limits.tokens_per_minute {
+            let mut used: u64 = self.sent.iter().map(|(_, tokens)| tokens).sum();
+            // Wait for the oldest requests to leave the window until this one fits
+            for (at, sent_tokens) in &self.sent {
+                if used + tokens <= limit {
+                    break;
+                }
+                used -= sent_tokens;
+                wait = wait.max(*at + WINDOW - now);
+            }
+        }
+        wait
+    }
+
+    /// Count a request sent at `now`
+    pub fn record(&mut self, now: Duration, tokens: u64) {
+        self.sent.push_back((now, tokens));
+    }
+}
+
+/// Parse a `Retry-After` header: seconds, or an HTTP date (`Wed, 21 Oct 2015
+/// 07:28:00 GMT`) compared against `now_unix` seconds
+pub fn parse_retry_after(value: &str, now_unix: u64) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let at = parse_http_date(value)?;
+    Some(Duration::from_secs(at.saturating_sub(now_unix)))
+}
+
+/// Unix time of an IMF-fixdate (`Sun, 06 Nov 1994 08:49:37 GMT`)
+fn parse_http_date(value: &str) -> Option<u64> {
+    let parts: Vec<&str> = value.split_whitespace().collect();
+    let [_, day, month, year, time, "GMT"] = parts.as_slice() else {
+        return None;
+    };
+    let day: u64 = day.parse().ok()?;
+    let month = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ]
+    .iter()
+    .position(|name| name == month)? as u64
+        + 1;
+    let year: u64 = year.parse().ok()?;
+    let mut clock = time.split(':').map(|part| part.parse::<u64>().ok());
+    let (Some(Some(hours)), Some(Some(minutes)), Some(Some(seconds)), None) =
+        (clock.next(), clock.next(), clock.next(), clock.next())
+    else {
+        return None;
+    };
+    if !(1..=31).contains(&day) || year < 1970 || hours > 23 || minutes > 59 || seconds > 60 {
+        return None;
+    }
+
+    // Days since 1970-01-01 (civil-from-days, inverted)
+    let (y, m) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+    let era = y / 400;
+    let year_of_era = y % 400;
+    let day_of_year = (153 * m + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = (era * 146_097 + day_of_era).checked_sub(719_468)?;
+    Some(days * 86_400 + hours * 3_600 + minutes * 60 + seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(seconds: u64) -> Duration {
+        Duration::from_secs(seconds)
+    }
+
+    #[test]
+    fn test_window_paces_requests_and_tokens() {
+        let limits = RateLimits::default()
+            .with_requests_per_minute(Some(2))
+            .with_tokens_per_minute(Some(1_000));
+        let mut window = RateWindow::new();
+
+        assert_eq!(window.delay(&limits, secs(0), 300), Duration::ZERO);
+        window.record(secs(0), 300);
+        assert_eq!(window.delay(&limits, secs(10), 300), Duration::ZERO);
+        window.record(secs(10), 300);
+
+        // Third request: over the request limit until the first one expires
+        assert_eq!(window.delay(&limits, secs(20), 100), secs(40));
+        // Once it has, 800 more tokens exceed the limit until the second expires
+        assert_eq!(window.delay(&limits, secs(60), 800), secs(10));
+        assert_eq!(window.delay(&limits, secs(60), 700), Duration::ZERO);
+
+        // A request bigger than the limit waits for an empty window, then goes
+        assert_eq!(window.delay(&limits, secs(65), 5_000), secs(5));
+        assert_eq!(window.delay(&limits, secs(70), 5_000), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        let limits = RateLimits::default();
+        assert_eq!(limits.retry_delay(0, None), Some(secs(1)));
+        assert_eq!(limits.retry_delay(2, None), Some(secs(4)));
+        assert_eq!(limits.retry_delay(1, Some(secs(30))), Some(secs(30)));
+        assert_eq!(limits.retry_delay(3, None), None);
+        assert_eq!(limits.retry_delay(0, Some(secs(3_600))), None);
+        assert_eq!(
+            limits.with_max_retries(10).retry_delay(9, None),
+            Some(MAX_BACKOFF)
+        );
+        assert!(is_throttled(429) && is_throttled(503) && !is_throttled(500));
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        assert_eq!(parse_retry_after(" 20 ", 0), Some(secs(20)));
+        // 2015-10-21T07:28:00Z
+        let at = 1_445_412_480;
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT", at - 90),
+            Some(secs(90))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT", at + 10),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_http_date("Thu, 01 Jan 1970 00:00:00 GMT"), Some(0));
+        assert_eq!(
+            parse_http_date("Tue, 29 Feb 2028 12:00:00 GMT"),
+            Some(1_835_438_400)
+        );
+        assert_eq!(parse_retry_after("soon", 0), None);
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28 GMT", 0), None);
+    }
+}
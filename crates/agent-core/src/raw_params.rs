@@ -0,0 +1,239 @@
+//! Tool calls with their parameters kept as JSON text (feature `raw-params`)
+//!
+//! Hosts that do their own JSON handling, such as a WASM host handing
+//! parameters straight to JavaScript, can parse model output with
+//! [`parse_model_output_raw`]: a tool call comes back as a [`RawToolRequest`]
+//! whose parameters are the reply's own JSON text, in the order and spelling
+//! the model wrote them. What counts as a call is decided by
+//! [`parse_model_output_with`], so both paths agree; every other reply
+//! (skills, artifact reads, variables, recalls, answers, inconclusive output)
+//! is returned as that function parsed it.
+//!
+//! Calls parsed this way skip what agent-core does with parameters (manifest
+//! checks, guardrails, variable substitution); convert them with
+//! [`RawToolRequest::to_request`] for that.
+
+use crate::prelude::*;
+use crate::protocol::{parse_model_output_with, AnswerProtocol, ParseResult};
+use crate::tool::{CallId, ToolRequest};
+use core::fmt;
+use core::marker::PhantomData;
+use serde::de::{Deserialize, Deserializer, MapAccess, Visitor};
+use serde_json::value::RawValue;
+
+/// A tool request with its parameters as JSON text
+#[derive(Debug, Clone)]
+pub struct RawToolRequest {
+    pub tool: String,
+    pub call_id: Option<CallId>,
+    /// The call's fields other than `tool` and `call_id`, as a JSON object
+    pub params: Box<RawValue>,
+}
+
+impl RawToolRequest {
+    /// The request with its parameters parsed
+    pub fn to_request(&self) -> Result<ToolRequest, serde_json::Error> {
+        Ok(ToolRequest {
+            tool: self.tool.clone(),
+            call_id: self.call_id,
+            params: serde_json::from_str(self.params.get())?,
+        })
+    }
+}
+
+/// What [`parse_model_output_raw`] found
+#[derive(Debug, Clone)]
+pub enum RawParseResult {
+    ToolCall(RawToolRequest),
+    /// Anything but a tool call, as [`parse_model_output_with`] parses it;
+    /// never a [`ParseResult::ToolCall`]
+    Other(ParseResult),
+}
+
+/// Parse model output like [`parse_model_output_with`], leaving a tool call's
+/// parameters as JSON text
+pub fn parse_model_output_raw(output: &str, protocol: AnswerProtocol) -> RawParseResult {
+    match parse_model_output_with(output, protocol) {
+        ParseResult::ToolCall(request) => RawParseResult::ToolCall(raw_request(output, request)),
+        other => RawParseResult::Other(other),
+    }
+}
+
+/// `request` with its parameters taken from the text it was parsed from
+fn raw_request(output: &str, request: ToolRequest) -> RawToolRequest {
+    // The value path parses the whole trimmed output as the call
+    let params = serde_json::from_str::<Fields>(output.trim())
+        .ok()
+        .and_then(|fields| RawValue::from_string(fields.params()).ok())
+        .unwrap_or_else(|| {
+            let params = serde_json::to_string(&request.params).expect("JSON values serialize");
+            RawValue::from_string(params).expect("serialized JSON is valid")
+        });
+    RawToolRequest {
+        tool: request.tool,
+        call_id: request.call_id,
+        params,
+    }
+}
+
+/// A JSON object's fields in the order they were written, values as text
+struct Fields<'a>(Vec<(String, &'a RawValue)>);
+
+impl Fields<'_> {
+    /// The fields other than `tool` and `call_id`, as an object
+    fn params(&self) -> String {
+        let mut params = String::from("{");
+        for (key, value) in self
+            .0
+            .iter()
+            .filter(|(key, _)| !matches!(key.as_str(), "tool" | "call_id"))
+        {
+            if params.len() > 1 {
+                params.push(',');
+            }
+            params.push_str(&serde_json::to_string(key).expect("strings serialize"));
+            params.push(':');
+            params.push_str(value.get());
+        }
+        params.push('}');
+        params
+    }
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for Fields<'a> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FieldsVisitor<'a>(PhantomData<&'a ()>);
+
+        impl<'de: 'a, 'a> Visitor<'de> for FieldsVisitor<'a> {
+            type Value = Fields<'a>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a JSON object")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Fields<'a>, A::Error> {
+                let mut fields: Vec<(String, &'a RawValue)> = Vec::new();
+                while let Some((key, value)) = map.next_entry::<String, &'a RawValue>()? {
+                    // A repeated key keeps its last value, as in the value path
+                    match fields.iter_mut().find(|(seen, _)| *seen == key) {
+                        Some(field) => field.1 = value,
+                        None => fields.push((key, value)),
+                    }
+                }
+                Ok(Fields(fields))
+            }
+        }
+
+        deserializer.deserialize_map(FieldsVisitor(PhantomData))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::InconclusiveReason;
+
+    fn raw_call(output: &str, protocol: AnswerProtocol) -> RawToolRequest {
+        match parse_model_output_raw(output, protocol) {
+            RawParseResult::ToolCall(request) => request,
+            other => panic!("expected a tool call, got {:?}", other),
+        }
+    }
+
+    fn other(output: &str, protocol: AnswerProtocol) -> ParseResult {
+        match parse_model_output_raw(output, protocol) {
+            RawParseResult::Other(result) => result,
+            RawParseResult::ToolCall(request) => panic!("unexpected tool call {:?}", request),
+        }
+    }
+
+    #[test]
+    fn test_raw_tool_call_keeps_json_text() {
+        let request = raw_call(
+            r#" {"tool": "sql", "query": "SELECT 1", "limit": 1.50, "rows": [ 1, 2 ]} "#,
+            AnswerProtocol::Heuristic,
+        );
+        assert_eq!(request.tool, "sql");
+        assert_eq!(request.call_id, None);
+        // The values are the reply's own text, in the reply's order
+        assert_eq!(
+            request.params.get(),
+            r#"{"query":"SELECT 1","limit":1.50,"rows":[ 1, 2 ]}"#
+        );
+        assert_eq!(
+            raw_call(r#"{"tool": "ls", "call_id": 3}"#, AnswerProtocol::Heuristic).call_id,
+            Some(CallId(3))
+        );
+        assert_eq!(
+            raw_call(
+                r#"{"tool": "ls", "a": 1, "a": 2}"#,
+                AnswerProtocol::Heuristic
+            )
+            .params
+            .get(),
+            r#"{"a":2}"#
+        );
+    }
+
+    #[test]
+    fn test_same_request_as_value_path() {
+        let output = r#"{"tool": "shell", "command": "ls -la", "env": {"A": "1"}}"#;
+        for protocol in [AnswerProtocol::Heuristic, AnswerProtocol::Sentinel] {
+            let ParseResult::ToolCall(expected) = parse_model_output_with(output, protocol) else {
+                panic!("expected a tool call");
+            };
+            assert_eq!(raw_call(output, protocol).to_request().unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_other_replies_use_the_value_path() {
+        let heuristic = AnswerProtocol::Heuristic;
+        assert!(matches!(
+            other(
+                r#"{"skill": "extract", "input": {"text": "x"}, "target": "email"}"#,
+                heuristic
+            ),
+            ParseResult::SkillCall(_)
+        ));
+        assert!(matches!(
+            other(r#"{"tool": 5}"#, heuristic),
+            ParseResult::Inconclusive(_, InconclusiveReason::InvalidCall { .. })
+        ));
+        assert!(matches!(
+            other(
+                r#"{"read_artifact": {"id": "tool-003", "range": [41, 140]}}"#,
+                heuristic
+            ),
+            ParseResult::ReadArtifact(_)
+        ));
+        assert!(matches!(
+            other("There are 3 files.", heuristic),
+            ParseResult::FinalAnswer(answer) if answer == "There are 3 files."
+        ));
+        // Under the sentinel protocol, answers need the sentinel
+        assert!(matches!(
+            other("FINAL: There are 3 files.", AnswerProtocol::Sentinel),
+            ParseResult::FinalAnswer(answer) if answer == "There are 3 files."
+        ));
+        assert!(matches!(
+            other("There are 3 files.", AnswerProtocol::Sentinel),
+            ParseResult::Inconclusive(..)
+        ));
+    }
+
+    #[test]
+    fn test_fenced_call_is_not_a_call_on_either_path() {
+        let fenced = "```json\n{\"tool\": \"shell\", \"command\": \"ls\"}\n```";
+        for protocol in [AnswerProtocol::Heuristic, AnswerProtocol::Sentinel] {
+            assert_eq!(
+                format!("{:?}", other(fenced, protocol)),
+                format!("{:?}", parse_model_output_with(fenced, protocol))
+            );
+        }
+        assert!(matches!(
+            other(fenced, AnswerProtocol::Heuristic),
+            ParseResult::FinalAnswer(_)
+        ));
+    }
+}
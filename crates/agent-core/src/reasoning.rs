@@ -0,0 +1,153 @@
+//! Reasoning blocks in model output
+//!
+//! Reasoning models put their chain of thought between delimiters such as
+//! `<think>...</think>` ahead of the reply. [`split_reasoning`] takes the
+//! blocks out so the reply is parsed on its own: the reasoning neither ends up
+//! in a final answer nor makes the reply look like planning. The agent keeps it
+//! on the history message, as [`Message::reasoning`](crate::agent::Message).
+//!
+//! Some chat templates open the block in the prompt, so the output starts
+//! inside it: text before a closing delimiter with no opening one is
+//! reasoning. A block left open (the model ran out of tokens) runs to the end.
+
+use crate::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// The delimiters around a reasoning block
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReasoningDelimiters {
+    pub open: String,
+    pub close: String,
+}
+
+impl ReasoningDelimiters {
+    pub fn new(open: impl Into<String>, close: impl Into<String>) -> Self {
+        Self {
+            open: open.into(),
+            close: close.into(),
+        }
+    }
+
+    /// `<think>` and `</think>` (DeepSeek-R1, Qwen3, Granite)
+    pub fn think() -> Self {
+        Self::new("<think>", "</think>")
+    }
+
+    /// Delimiters of a model family (`think`, `thinking`, `magistral`), or
+    /// custom ones as `OPEN,CLOSE`
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "think" | "deepseek" | "qwen" | "granite" => Some(Self::think()),
+            "thinking" => Some(Self::new("<thinking>", "</thinking>")),
+            "magistral" => Some(Self::new("[THINK]", "[/THINK]")),
+            _ => {
+                let (open, close) = s.split_once(',')?;
+                let (open, close) = (open.trim(), close.trim());
+                (!open.is_empty() && !close.is_empty() && open != close)
+                    .then(|| Self::new(open, close))
+            }
+        }
+    }
+}
+
+impl Default for ReasoningDelimiters {
+    fn default() -> Self {
+        Self::think()
+    }
+}
+
+/// Model output with its reasoning taken out
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitOutput {
+    /// The reasoning blocks, trimmed and joined by blank lines (`None` if
+    /// there were none or all were empty)
+    pub reasoning: Option<String>,
+    /// The rest of the output, trimmed
+    pub visible: String,
+}
+
+/// Separate the reasoning blocks of `output` from its reply
+pub fn split_reasoning(output: &str, delimiters: &ReasoningDelimiters) -> SplitOutput {
+    let mut blocks = Vec::new();
+    let mut visible = String::new();
+    let mut rest = output;
+
+    // A block opened by the prompt: the output starts inside it
+    if let Some(close) = rest.find(delimiters.close.as_str()) {
+        if !rest[..close].contains(delimiters.open.as_str()) {
+            blocks.push(&rest[..close]);
+            rest = &rest[close + delimiters.close.len()..];
+        }
+    }
+
+    while let Some(open) = rest.find(delimiters.open.as_str()) {
+        visible.push_str(&rest[..open]);
+        let block = &rest[open + delimiters.open.len()..];
+        match block.find(delimiters.close.as_str()) {
+            Some(close) => {
+                blocks.push(&block[..close]);
+                rest = &block[close + delimiters.close.len()..];
+            }
+            None => {
+                blocks.push(block);
+                rest = "";
+            }
+        }
+    }
+    visible.push_str(rest);
+
+    let blocks: Vec<&str> = blocks
+        .into_iter()
+        .map(str::trim)
+        .filter(|block| !block.is_empty())
+        .collect();
+    SplitOutput {
+        reasoning: (!blocks.is_empty()).then(|| blocks.join("\n\n")),
+        visible: visible.trim().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_think_blocks() {
+        let split = split_reasoning(
+            "<think>I will count the files.</think>\nThere are 5 files.",
+            &ReasoningDelimiters::think(),
+        );
+        assert_eq!(split.reasoning.as_deref(), Some("I will count the files."));
+        assert_eq!(split.visible, "There are 5 files.");
+
+        let plain = split_reasoning("There are 5 files.", &ReasoningDelimiters::think());
+        assert_eq!(plain.reasoning, None);
+        assert_eq!(plain.visible, "There are 5 files.");
+    }
+
+    #[test]
+    fn test_open_and_unclosed_blocks() {
+        let magistral = ReasoningDelimiters::from_str("magistral").unwrap();
+        // Opened by the chat template
+        let split = split_reasoning("Let me check.[/THINK]Done.", &magistral);
+        assert_eq!(split.reasoning.as_deref(), Some("Let me check."));
+        assert_eq!(split.visible, "Done.");
+        // Cut off by the token limit
+        let split = split_reasoning("[THINK]Let me check", &magistral);
+        assert_eq!(split.reasoning.as_deref(), Some("Let me check"));
+        assert_eq!(split.visible, "");
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!(
+            ReasoningDelimiters::from_str("<r>, </r>"),
+            Some(ReasoningDelimiters::new("<r>", "</r>"))
+        );
+        assert_eq!(
+            ReasoningDelimiters::from_str("qwen"),
+            Some(ReasoningDelimiters::think())
+        );
+        assert_eq!(ReasoningDelimiters::from_str("<r>"), None);
+    }
+}
@@ -0,0 +1,346 @@
+//! Answer constraints set by the caller
+//!
+//! An embedder can ask for answers of a certain shape: at most so many words,
+//! a bullet list, a language. The [`ResponseSpec`] travels in the state; the
+//! turn instructions state it and [`ResponseSpecGuard`] checks each final
+//! answer against it, so an answer that ignores it goes back to the model.
+//!
+//! Languages are checked by their common words, for the languages in
+//! [`LANGUAGES`]; an answer in another language is only asked for.
+
+use crate::guardrail::{AnswerContext, AnswerGuardrail, GuardrailResult};
+use crate::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Answers shorter than this many words are not checked for their language
+const MIN_LANGUAGE_WORDS: usize = 6;
+
+/// A language's code, English name and common words
+pub type Language = (&'static str, &'static str, &'static [&'static str]);
+
+/// Languages answers are checked for
+pub const LANGUAGES: &[Language] = &[
+    (
+        "en",
+        "English",
+        &[
+            "the", "and", "is", "are", "of", "to", "it", "that", "with", "for", "this", "was",
+            "has", "there", "which",
+        ],
+    ),
+    (
+        "de",
+        "German",
+        &[
+            "der", "die", "das", "und", "ist", "sind", "nicht", "mit", "ein", "eine", "den", "dem",
+            "auf", "für", "von", "auch", "es",
+        ],
+    ),
+    (
+        "fr",
+        "French",
+        &[
+            "le", "les", "et", "est", "sont", "des", "une", "dans", "pour", "pas", "avec", "du",
+            "il", "ce", "qui",
+        ],
+    ),
+    (
+        "es",
+        "Spanish",
+        &[
+            "el", "los", "las", "y", "es", "son", "una", "para", "con", "por", "hay", "del",
+            "está", "muy",
+        ],
+    ),
+    (
+        "it",
+        "Italian",
+        &[
+            "il", "gli", "e", "è", "sono", "di", "per", "che", "non", "della", "nel", "anche",
+            "questo",
+        ],
+    ),
+    (
+        "nl",
+        "Dutch",
+        &[
+            "de", "het", "een", "en", "zijn", "van", "niet", "met", "op", "voor", "dat", "er",
+            "ook",
+        ],
+    ),
+    (
+        "pt",
+        "Portuguese",
+        &[
+            "o", "os", "e", "é", "são", "um", "uma", "em", "para", "com", "não", "do", "da",
+            "também",
+        ],
+    ),
+];
+
+/// The shape the caller wants final answers in; unset fields are free
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResponseSpec {
+    /// Words the answer may take
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_words: Option<usize>,
+    /// Whether the answer must be a bullet list
+    #[serde(default, skip_serializing_if = "core::ops::Not::not")]
+    pub bullets: bool,
+    /// Language of the answer, by name or code (`German`, `de`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+}
+
+impl ResponseSpec {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_words(mut self, words: usize) -> Self {
+        self.max_words = Some(words);
+        self
+    }
+
+    pub fn with_bullets(mut self) -> Self {
+        self.bullets = true;
+        self
+    }
+
+    pub fn with_language(mut self, language: impl Into<String>) -> Self {
+        self.language = Some(language.into());
+        self
+    }
+
+    /// Whether the spec constrains nothing
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The language's name, for known codes and names
+    fn language_name(&self) -> Option<&str> {
+        let language = self.language.as_deref()?;
+        Some(
+            known_language(language)
+                .map(|(_, name, _)| *name)
+                .unwrap_or(language),
+        )
+    }
+
+    /// Turn instructions stating the spec (empty if it constrains nothing)
+    pub fn instructions(&self) -> String {
+        let mut rules = Vec::new();
+        if let Some(language) = self.language_name() {
+            rules.push(format!("- Write the final answer in {}.", language));
+        }
+        if let Some(words) = self.max_words {
+            rules.push(format!("- Use at most {} words.", words));
+        }
+        if self.bullets {
+            rules.push(
+                "- Write it as a bullet list: one line per item, each starting with \"- \"."
+                    .to_string(),
+            );
+        }
+        if rules.is_empty() {
+            return String::new();
+        }
+        format!("ANSWER FORMAT:\n{}", rules.join("\n"))
+    }
+
+    /// Check an answer against the spec, saying what it got wrong
+    pub fn check(&self, answer: &str) -> Result<(), String> {
+        let words: Vec<&str> = answer
+            .split_whitespace()
+            .filter(|word| !is_bullet_marker(word))
+            .collect();
+
+        if let Some(max) = self.max_words {
+            if words.len() > max {
+                return Err(format!(
+                    "the answer has {} words, more than the {} allowed",
+                    words.len(),
+                    max
+                ));
+            }
+        }
+
+        if self.bullets {
+            let lines: Vec<&str> = answer
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .collect();
+            if !lines.iter().all(|line| is_bullet_line(line)) {
+                return Err("the answer is not a bullet list".to_string());
+            }
+        }
+
+        if let Some((code, name, _)) = self.language.as_deref().and_then(known_language) {
+            if let Some(other) = written_in(&words).filter(|other| other.0 != *code) {
+                return Err(format!(
+                    "the answer is not in {} (it reads as {})",
+                    name, other.1
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The entry of [`LANGUAGES`] for a code or English name
+fn known_language(language: &str) -> Option<&'static Language> {
+    let language = language.trim();
+    LANGUAGES.iter().find(|(code, name, _)| {
+        code.eq_ignore_ascii_case(language) || name.eq_ignore_ascii_case(language)
+    })
+}
+
+/// The language `words` are clearly written in: the one whose common words
+/// they use most, when no other comes close
+fn written_in(words: &[&str]) -> Option<&'static Language> {
+    if words.len() < MIN_LANGUAGE_WORDS {
+        return None;
+    }
+    let words: Vec<String> = words
+        .iter()
+        .map(|word| {
+            word.trim_matches(|c: char| !c.is_alphanumeric())
+                .to_lowercase()
+        })
+        .collect();
+    let score = |common: &[&str]| {
+        words
+            .iter()
+            .filter(|w| common.contains(&w.as_str()))
+            .count()
+    };
+
+    let mut scores: Vec<(usize, &'static Language)> = LANGUAGES
+        .iter()
+        .map(|language| (score(language.2), language))
+        .collect();
+    scores.sort_by_key(|(score, _)| core::cmp::Reverse(*score));
+    let (best, language) = scores[0];
+    let runner_up = scores[1].0;
+    (best >= 3 && runner_up * 2 < best).then_some(language)
+}
+
+fn is_bullet_marker(word: &str) -> bool {
+    matches!(word, "-" | "*" | "•")
+}
+
+fn is_bullet_line(line: &str) -> bool {
+    let numbered = line.split_once(". ").is_some_and(|(number, _)| {
+        !number.is_empty() && number.chars().all(|c| c.is_ascii_digit())
+    });
+    numbered
+        || ["- ", "* ", "• "]
+            .iter()
+            .any(|marker| line.starts_with(marker))
+}
+
+/// Checks final answers against the state's [`ResponseSpec`]; answers pass
+/// when the state has none
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ResponseSpecGuard;
+
+impl ResponseSpecGuard {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl AnswerGuardrail for ResponseSpecGuard {
+    fn check(&self, context: &AnswerContext) -> GuardrailResult {
+        let Some(spec) = &context.state.response_spec else {
+            return GuardrailResult::Accept;
+        };
+        match spec.check(context.answer) {
+            Ok(()) => GuardrailResult::Accept,
+            Err(reason) => GuardrailResult::reject(reason),
+        }
+    }
+
+    fn name(&self) -> &str {
+        "response_spec_guard"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::AgentState;
+
+    #[test]
+    fn test_instructions() {
+        assert!(ResponseSpec::new().instructions().is_empty());
+        let spec = ResponseSpec::new()
+            .with_max_words(50)
+            .with_bullets()
+            .with_language("de");
+        let instructions = spec.instructions();
+        assert!(instructions.starts_with("ANSWER FORMAT:\n- Write the final answer in German."));
+        assert!(instructions.contains("at most 50 words"));
+        assert!(instructions.contains("bullet list"));
+        // Unknown languages are asked for as given
+        assert!(ResponseSpec::new()
+            .with_language("Finnish")
+            .instructions()
+            .contains("in Finnish."));
+    }
+
+    #[test]
+    fn test_check_answers() {
+        let short = ResponseSpec::new().with_max_words(5);
+        assert!(short.check("a.bin is the largest").is_ok());
+        assert!(short
+            .check("The largest file is a.bin, at 4 GB.")
+            .unwrap_err()
+            .contains("8 words"));
+
+        let bullets = ResponseSpec::new().with_bullets();
+        assert!(bullets
+            .check("- a.bin\n- b.txt\n\n* c.log\n1. d.md")
+            .is_ok());
+        assert!(bullets.check("Files:\n- a.bin").is_err());
+
+        let german = ResponseSpec::new().with_language("German");
+        assert!(german
+            .check("Die größte Datei ist a.bin, und sie ist 4 GB groß.")
+            .is_ok());
+        assert_eq!(
+            german.check("The largest file is a.bin and it is 4 GB in size."),
+            Err("the answer is not in German (it reads as English)".to_string())
+        );
+        // Too short, or too mixed, to tell
+        assert!(german.check("a.bin is largest").is_ok());
+        assert!(ResponseSpec::new()
+            .with_language("Finnish")
+            .check("The largest file is a.bin and it is 4 GB in size.")
+            .is_ok());
+    }
+
+    #[test]
+    fn test_guard_reads_state_spec() {
+        let guard = ResponseSpecGuard::new();
+        let state = AgentState::new("Which file is largest?");
+        let answer = "The largest file is a.bin and it is 4 GB in size.";
+        assert!(matches!(
+            guard.check(&AnswerContext {
+                state: &state,
+                answer
+            }),
+            GuardrailResult::Accept
+        ));
+
+        let state = state.with_response_spec(Some(ResponseSpec::new().with_max_words(3)));
+        assert!(matches!(
+            guard.check(&AnswerContext {
+                state: &state,
+                answer
+            }),
+            GuardrailResult::Reject { .. }
+        ));
+    }
+}
@@ -0,0 +1,120 @@
+//! Recorded scenarios: replayable fixtures built from real runs
+//!
+//! A scenario holds what happened in one run: the query, every prompt the
+//! model was given with its output, every tool call with the result it got,
+//! and the final answer. Replaying a scenario serves tool calls from the
+//! recording instead of executing them, so a run that once failed can be
+//! re-run against any model in the same environment, as a regression test.
+
+use crate::prelude::*;
+use crate::tool::{ToolRequest, ToolResult};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// File a scenario is stored in, inside its fixture directory
+pub const SCENARIO_FILE: &str = "scenario.json";
+
+/// One model call: the rendered prompt and the model's output
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelCall {
+    pub prompt: String,
+    pub output: String,
+}
+
+/// One executed tool call and its result
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCall {
+    pub tool: String,
+    pub params: Value,
+    pub result: ToolResult,
+}
+
+/// Everything recorded about one run
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Scenario {
+    pub query: String,
+    #[serde(default)]
+    pub model_calls: Vec<ModelCall>,
+    #[serde(default)]
+    pub tool_calls: Vec<ToolCall>,
+    /// Final answer, `None` if the run did not finish
+    #[serde(default)]
+    pub answer: Option<String>,
+}
+
+impl Scenario {
+    pub fn new(query: impl Into<String>) -> Self {
+        Self {
+            query: query.into(),
+            ..Default::default()
+        }
+    }
+
+    /// The recorded result of a call with the same tool and parameters
+    pub fn result_for(&self, request: &ToolRequest) -> Option<&ToolResult> {
+        self.tool_calls
+            .iter()
+            .find(|call| call.tool == request.tool && call.params == request.params)
+            .map(|call| &call.result)
+    }
+
+    /// Result for a call the recording does not have: the model left the
+    /// recorded path, which replay reports to it as a failed call
+    pub fn unrecorded(request: &ToolRequest) -> ToolResult {
+        ToolResult::failure(format!(
+            "No recorded result for this {} call (replaying a recorded scenario)",
+            request.tool
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn request(tool: &str, params: Value) -> ToolRequest {
+        ToolRequest {
+            tool: tool.to_string(),
+            call_id: None,
+            params,
+        }
+    }
+
+    #[test]
+    fn test_replay_lookup() {
+        let mut scenario = Scenario::new("count files");
+        scenario.tool_calls.push(ToolCall {
+            tool: "shell".to_string(),
+            params: json!({"command": "ls | wc -l"}),
+            result: ToolResult::success("3\n"),
+        });
+
+        let recorded = scenario.result_for(&request("shell", json!({"command": "ls | wc -l"})));
+        assert_eq!(recorded.map(|result| result.output.as_str()), Some("3\n"));
+        assert!(scenario
+            .result_for(&request("shell", json!({"command": "ls"})))
+            .is_none());
+        assert!(scenario
+            .result_for(&request("search", json!({"command": "ls | wc -l"})))
+            .is_none());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut scenario = Scenario::new("say hi");
+        scenario.model_calls.push(ModelCall {
+            prompt: "User: say hi".to_string(),
+            output: "hi".to_string(),
+        });
+        scenario.answer = Some("hi".to_string());
+
+        let json = serde_json::to_string(&scenario).unwrap();
+        let parsed: Scenario = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.model_calls, scenario.model_calls);
+        assert_eq!(parsed.answer.as_deref(), Some("hi"));
+
+        let minimal: Scenario = serde_json::from_str(r#"{"query": "q"}"#).unwrap();
+        assert!(minimal.tool_calls.is_empty());
+    }
+}
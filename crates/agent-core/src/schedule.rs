@@ -0,0 +1,429 @@
+//! Recurring jobs: task files run on cron schedules
+//!
+//! A schedule file lists jobs, each a task file (see [`crate::task`]) with the
+//! variables to run it with and a cron expression saying when:
+//!
+//! ```yaml
+//! jobs:
+//!   - name: log-summary
+//!     cron: "0 7 * * *"
+//!     task: tasks/summarize-logs.yaml
+//!     vars:
+//!       day: yesterday
+//!     args: [--approve, read-only=auto]
+//!   - name: disk-report
+//!     cron: "*/30 9-17 * * 1-5"
+//!     task: tasks/disk-report.yaml
+//! ```
+//!
+//! Cron expressions have the usual five fields: minute, hour, day of month,
+//! month and day of week (0 or 7 is Sunday). Each field is `*`, a number, a
+//! range `a-b`, a step `*/n` or `a-b/n`, or a comma-separated list of these;
+//! `@hourly`, `@daily`, `@weekly` and `@monthly` stand for common ones. As in
+//! cron, a day that matches either a restricted day of month or a restricted
+//! day of week is a match.
+//!
+//! A job still running when it comes due again is not started a second time;
+//! hosts record the missed run as [`ScheduledStatus::Skipped`]. Every run is
+//! kept in the job's history as a [`ScheduledRun`].
+
+use crate::batch::is_plain_name;
+use crate::environment::LocalTime;
+use crate::eval::CaseRun;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// How far ahead [`CronSchedule::next_after`] looks for a matching minute
+const MAX_LOOKAHEAD_MINUTES: i64 = 366 * 24 * 60;
+
+/// Errors from reading a schedule file
+#[derive(Debug, Error)]
+pub enum ScheduleError {
+    #[error("invalid schedule: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+    #[error("invalid cron expression '{expression}': {message}")]
+    InvalidCron { expression: String, message: String },
+    #[error("job name '{0}' must be a plain file name")]
+    InvalidName(String),
+    #[error("duplicate job '{0}'")]
+    Duplicate(String),
+    #[error("job '{job}': {source}")]
+    Job {
+        job: String,
+        #[source]
+        source: Box<ScheduleError>,
+    },
+    #[error("no jobs")]
+    Empty,
+}
+
+/// The minutes a cron expression matches
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Whether the day of month is restricted (does not start with `*`)
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+impl CronSchedule {
+    /// Parse a five-field expression or one of the `@` shorthands
+    pub fn parse(expression: &str) -> Result<Self, ScheduleError> {
+        let invalid = |message: String| ScheduleError::InvalidCron {
+            expression: expression.to_string(),
+            message,
+        };
+        let expanded = match expression.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            other => other,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields.as_slice() else {
+            return Err(invalid(format!(
+                "expected 5 fields (minute hour day month weekday), found {}",
+                fields.len()
+            )));
+        };
+        let weekdays = parse_field(weekday, 0, 7, "day of week").map_err(invalid)?;
+        Ok(Self {
+            minutes: parse_field(minute, 0, 59, "minute").map_err(invalid)?,
+            hours: parse_field(hour, 0, 23, "hour").map_err(invalid)?,
+            days: parse_field(day, 1, 31, "day of month").map_err(invalid)?,
+            months: parse_field(month, 1, 12, "month").map_err(invalid)?,
+            // 7 is Sunday as well
+            weekdays: (weekdays | (weekdays >> 7)) & 0x7f,
+            days_restricted: !day.starts_with('*'),
+            weekdays_restricted: !weekday.starts_with('*'),
+        })
+    }
+
+    /// Whether the schedule matches the minute `time` falls in
+    pub fn matches(&self, time: &LocalTime) -> bool {
+        let calendar = time.calendar();
+        let has = |set: u64, value: u32| set & (1 << value) != 0;
+        let day = has(self.days, calendar.day);
+        let weekday = has(self.weekdays, calendar.weekday);
+        let day_matches = match (self.days_restricted, self.weekdays_restricted) {
+            (true, true) => day || weekday,
+            _ => day && weekday,
+        };
+        has(self.minutes, calendar.minute)
+            && has(self.hours, calendar.hour)
+            && has(self.months, calendar.month)
+            && day_matches
+    }
+
+    /// The start of the first matching minute after `unix_seconds`, with
+    /// `local` giving the local clock at a moment; `None` if none comes
+    /// within a year (`0 0 30 2 *`)
+    pub fn next_after(&self, unix_seconds: i64, local: impl Fn(i64) -> LocalTime) -> Option<i64> {
+        let first = unix_seconds.div_euclid(60) * 60 + 60;
+        (0..MAX_LOOKAHEAD_MINUTES)
+            .map(|minute| first + minute * 60)
+            .find(|moment| self.matches(&local(*moment)))
+    }
+}
+
+/// The values of one cron field as bits, checked against `min..=max`
+fn parse_field(field: &str, min: u32, max: u32, name: &str) -> Result<u64, String> {
+    let mut set = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => match step.parse::<u32>() {
+                Ok(step) if step > 0 => (range, step),
+                _ => return Err(format!("invalid step '{}' in the {}", step, name)),
+            },
+            None => (part, 1),
+        };
+        let number = |text: &str| match text.parse::<u32>() {
+            Ok(value) if (min..=max).contains(&value) => Ok(value),
+            _ => Err(format!(
+                "'{}' is not a valid {} ({} to {})",
+                text, name, min, max
+            )),
+        };
+        let (start, end) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((start, end)) => (number(start)?, number(end)?),
+            // `5/15` runs from 5 to the end of the range
+            None if step > 1 => (number(range)?, max),
+            None => {
+                let value = number(range)?;
+                (value, value)
+            }
+        };
+        if start > end {
+            return Err(format!("empty range '{}' in the {}", range, name));
+        }
+        for value in (start..=end).step_by(step as usize) {
+            set |= 1 << value;
+        }
+    }
+    Ok(set)
+}
+
+/// A task file run on a schedule
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScheduledJob {
+    /// Names the job's history
+    pub name: String,
+    pub cron: String,
+    /// Task file, relative to the schedule file
+    pub task: PathBuf,
+    /// Values of the task's query variables
+    #[serde(default)]
+    pub vars: BTreeMap<String, String>,
+    /// Host arguments for this job only
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Time limit of one run, in seconds (default: the host's)
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+impl ScheduledJob {
+    pub fn schedule(&self) -> Result<CronSchedule, ScheduleError> {
+        CronSchedule::parse(&self.cron)
+    }
+}
+
+/// The jobs of a schedule file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScheduleFile {
+    pub jobs: Vec<ScheduledJob>,
+}
+
+impl ScheduleFile {
+    /// Parse a schedule file and check its job names and cron expressions
+    pub fn from_yaml(yaml: &str) -> Result<Self, ScheduleError> {
+        let file: Self = serde_yaml::from_str(yaml)?;
+        if file.jobs.is_empty() {
+            return Err(ScheduleError::Empty);
+        }
+        let mut names = BTreeSet::new();
+        for job in &file.jobs {
+            if !is_plain_name(&job.name) {
+                return Err(ScheduleError::InvalidName(job.name.clone()));
+            }
+            if !names.insert(job.name.as_str()) {
+                return Err(ScheduleError::Duplicate(job.name.clone()));
+            }
+            job.schedule().map_err(|source| ScheduleError::Job {
+                job: job.name.clone(),
+                source: Box::new(source),
+            })?;
+        }
+        Ok(file)
+    }
+}
+
+/// How a scheduled run ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScheduledStatus {
+    Completed,
+    Failed,
+    /// Not started: the job's previous run was still going
+    Skipped,
+}
+
+/// One run of a job, a line of its history
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScheduledRun {
+    pub job: String,
+    /// When the run was due, on the local clock (`2026-10-17 07:00`)
+    pub due: String,
+    pub status: ScheduledStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub answer: Option<String>,
+    /// Why the run failed (failure code, timeout or host error)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The run's id, naming its audit log
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_id: Option<String>,
+    #[serde(default)]
+    pub iterations: usize,
+    #[serde(default)]
+    pub tokens: usize,
+    #[serde(default)]
+    pub wall_seconds: f64,
+}
+
+impl ScheduledRun {
+    /// The record of a run from what the host observed
+    pub fn new(job: impl Into<String>, due: impl Into<String>, run: CaseRun) -> Self {
+        let status = match (&run.answer, &run.error) {
+            (Some(_), None) => ScheduledStatus::Completed,
+            _ => ScheduledStatus::Failed,
+        };
+        Self {
+            job: job.into(),
+            due: due.into(),
+            status,
+            error: match (&run.answer, run.error) {
+                (None, None) => Some("no final answer".to_string()),
+                (_, error) => error,
+            },
+            answer: run.answer,
+            run_id: run.run_id,
+            iterations: run.iterations,
+            tokens: run.tokens,
+            wall_seconds: run.wall_seconds,
+        }
+    }
+
+    /// The record of a run skipped because the previous one was still going
+    pub fn skipped(job: impl Into<String>, due: impl Into<String>) -> Self {
+        Self {
+            job: job.into(),
+            due: due.into(),
+            status: ScheduledStatus::Skipped,
+            answer: None,
+            error: Some("the previous run was still going".to_string()),
+            run_id: None,
+            iterations: 0,
+            tokens: 0,
+            wall_seconds: 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2026-10-17 (a Saturday) at `hour:minute` UTC
+    fn at(hour: i64, minute: i64) -> LocalTime {
+        utc(1_792_195_200 + hour * 3600 + minute * 60)
+    }
+
+    fn utc(unix_seconds: i64) -> LocalTime {
+        LocalTime {
+            unix_seconds,
+            utc_offset_seconds: 0,
+            zone: None,
+        }
+    }
+
+    #[test]
+    fn test_cron_matching() {
+        let daily = CronSchedule::parse("0 7 * * *").unwrap();
+        assert!(daily.matches(&at(7, 0)));
+        assert!(!daily.matches(&at(7, 1)));
+        assert_eq!(
+            CronSchedule::parse("@daily").unwrap(),
+            CronSchedule::parse("0 0 * * *").unwrap()
+        );
+
+        let office = CronSchedule::parse("*/30 9-17 * * 1-5").unwrap();
+        // A Saturday
+        assert!(!office.matches(&at(9, 30)));
+        let weekend = CronSchedule::parse("15,45 8 * * 6,7").unwrap();
+        assert!(weekend.matches(&at(8, 45)));
+        assert!(!weekend.matches(&at(8, 30)));
+
+        // Day of month or day of week, when both are restricted
+        let either = CronSchedule::parse("0 12 1 * 6").unwrap();
+        assert!(either.matches(&at(12, 0)));
+        let both = CronSchedule::parse("0 12 1 * *").unwrap();
+        assert!(!both.matches(&at(12, 0)));
+
+        assert_eq!(
+            daily.next_after(at(7, 0).unix_seconds, utc),
+            Some(at(7, 0).unix_seconds + 86_400)
+        );
+        assert_eq!(
+            CronSchedule::parse("5/20 * * * *")
+                .unwrap()
+                .next_after(at(10, 30).unix_seconds, utc),
+            Some(at(10, 45).unix_seconds)
+        );
+        assert_eq!(
+            CronSchedule::parse("0 0 30 2 *")
+                .unwrap()
+                .next_after(0, utc),
+            None
+        );
+    }
+
+    #[test]
+    fn test_invalid_cron() {
+        for expression in [
+            "0 7 * *",
+            "60 * * * *",
+            "* * 0 * *",
+            "*/0 * * * *",
+            "5-1 * * * *",
+            "x * * * *",
+        ] {
+            assert!(
+                matches!(
+                    CronSchedule::parse(expression),
+                    Err(ScheduleError::InvalidCron { .. })
+                ),
+                "{}",
+                expression
+            );
+        }
+        let error = CronSchedule::parse("0 25 * * *").unwrap_err().to_string();
+        assert_eq!(
+            error,
+            "invalid cron expression '0 25 * * *': '25' is not a valid hour (0 to 23)"
+        );
+    }
+
+    #[test]
+    fn test_schedule_file() {
+        let file = ScheduleFile::from_yaml(
+            r#"
+jobs:
+  - name: log-summary
+    cron: "0 7 * * *"
+    task: tasks/summarize-logs.yaml
+    vars: {day: yesterday}
+"#,
+        )
+        .unwrap();
+        assert_eq!(file.jobs[0].vars["day"], "yesterday");
+
+        let job = |name: &str, cron: &str| {
+            format!(
+                "  - name: {}\n    cron: \"{}\"\n    task: t.yaml\n",
+                name, cron
+            )
+        };
+        assert!(matches!(
+            ScheduleFile::from_yaml(&format!("jobs:\n{}{}", job("a", "@hourly"), job("a", "@daily"))),
+            Err(ScheduleError::Duplicate(name)) if name == "a"
+        ));
+        assert!(matches!(
+            ScheduleFile::from_yaml(&format!("jobs:\n{}", job("../a", "@hourly"))),
+            Err(ScheduleError::InvalidName(_))
+        ));
+        assert!(matches!(
+            ScheduleFile::from_yaml(&format!("jobs:\n{}", job("a", "@yearly"))),
+            Err(ScheduleError::Job { job, .. }) if job == "a"
+        ));
+        assert!(matches!(
+            ScheduleFile::from_yaml("jobs: []"),
+            Err(ScheduleError::Empty)
+        ));
+
+        let skipped = ScheduledRun::skipped("log-summary", "2026-10-17 07:00");
+        assert_eq!(skipped.status, ScheduledStatus::Skipped);
+        let failed = ScheduledRun::new("log-summary", "2026-10-17 07:00", CaseRun::default());
+        assert_eq!(failed.status, ScheduledStatus::Failed);
+    }
+}
@@ -0,0 +1,362 @@
+//! Structured search results
+//!
+//! Backs the `search` tool: the host runs a regex over the files under a
+//! directory and reports each matching line as `{path, line, snippet}` JSON
+//! rather than raw grep text. A broad pattern would otherwise flood the
+//! context, so matches are capped per file and in total, long lines are
+//! shortened around the match, and the result says whether it was cut off.
+//!
+//! The host walks the files and runs the regex; this module decides what the
+//! request means and what the model gets back, and checks results have that
+//! shape ([`SearchResultValidator`]).
+
+use crate::guardrail::{GuardrailContext, GuardrailResult, SemanticGuardrail};
+use crate::prelude::*;
+use crate::tool::is_workspace_path;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+/// Parameters of a `search` call
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct SearchRequest {
+    /// Regex matched against each line
+    pub pattern: String,
+    /// Directory (or file) to search, relative to the working directory
+    #[serde(default = "default_search_path")]
+    pub path: String,
+    /// Only search files whose name matches this glob (e.g. `*.rs`)
+    #[serde(default)]
+    pub glob: Option<String>,
+    #[serde(default)]
+    pub ignore_case: bool,
+    /// Fewer matches than the host's cap
+    #[serde(default)]
+    pub max_matches: Option<usize>,
+}
+
+fn default_search_path() -> String {
+    ".".to_string()
+}
+
+impl SearchRequest {
+    /// Read a request from tool call parameters
+    pub fn from_params(params: &Value) -> Result<Self, SearchError> {
+        let request: Self = serde_json::from_value(params.clone())
+            .map_err(|e| SearchError::InvalidRequest(e.to_string()))?;
+        if request.pattern.is_empty() {
+            return Err(SearchError::InvalidRequest("empty pattern".to_string()));
+        }
+        if request.path != "." && !is_workspace_path(&request.path) {
+            return Err(SearchError::UnsafePath(request.path));
+        }
+        Ok(request)
+    }
+}
+
+/// Search errors
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum SearchError {
+    #[error("Invalid search request: {0}")]
+    InvalidRequest(String),
+
+    #[error("Unsafe path '{0}': paths must be relative and stay inside the working directory")]
+    UnsafePath(String),
+}
+
+/// Caps on what one search returns
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchLimits {
+    pub max_matches: usize,
+    pub max_per_file: usize,
+    /// Longer lines are shortened around the match
+    pub max_snippet_chars: usize,
+}
+
+impl Default for SearchLimits {
+    fn default() -> Self {
+        Self {
+            max_matches: 50,
+            max_per_file: 10,
+            max_snippet_chars: 160,
+        }
+    }
+}
+
+impl SearchLimits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_matches(mut self, matches: usize) -> Self {
+        self.max_matches = matches.max(1);
+        self
+    }
+
+    pub fn with_max_per_file(mut self, matches: usize) -> Self {
+        self.max_per_file = matches.max(1);
+        self
+    }
+
+    pub fn with_max_snippet_chars(mut self, chars: usize) -> Self {
+        self.max_snippet_chars = chars.max(1);
+        self
+    }
+
+    /// These limits, lowered to what the request asks for
+    pub fn for_request(&self, request: &SearchRequest) -> Self {
+        match request.max_matches {
+            Some(matches) => self.clone().with_max_matches(matches.min(self.max_matches)),
+            None => self.clone(),
+        }
+    }
+}
+
+/// A matching line
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SearchMatch {
+    pub path: String,
+    /// 1-based line number
+    pub line: usize,
+    pub snippet: String,
+}
+
+/// Matches collected during a search
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchResults {
+    limits: SearchLimits,
+    matches: Vec<SearchMatch>,
+    files_searched: usize,
+    file_matches: usize,
+    truncated: bool,
+}
+
+impl SearchResults {
+    pub fn new(limits: SearchLimits) -> Self {
+        Self {
+            limits,
+            matches: Vec::new(),
+            files_searched: 0,
+            file_matches: 0,
+            truncated: false,
+        }
+    }
+
+    /// Count a file about to be searched
+    pub fn start_file(&mut self) {
+        self.files_searched += 1;
+        self.file_matches = 0;
+    }
+
+    /// Record a match starting at byte `column` of `line`
+    ///
+    /// Returns `false` when a cap is reached: the caller moves on to the next
+    /// file, or stops if [`is_full`](Self::is_full).
+    pub fn push(&mut self, path: &str, number: usize, line: &str, column: usize) -> bool {
+        if self.is_full() || self.file_matches >= self.limits.max_per_file {
+            self.truncated = true;
+            return false;
+        }
+        self.matches.push(SearchMatch {
+            path: path.to_string(),
+            line: number,
+            snippet: snippet(line, column, self.limits.max_snippet_chars),
+        });
+        self.file_matches += 1;
+        true
+    }
+
+    /// Whether the total cap is reached
+    pub fn is_full(&self) -> bool {
+        self.matches.len() >= self.limits.max_matches
+    }
+
+    pub fn matches(&self) -> &[SearchMatch] {
+        &self.matches
+    }
+
+    /// Whether matches were left out because of a cap
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// The tool output handed to the model
+    pub fn to_json(&self) -> String {
+        serde_json::json!({
+            "matches": self.matches,
+            "files_searched": self.files_searched,
+            "truncated": self.truncated,
+        })
+        .to_string()
+    }
+}
+
+/// `line` without indentation, shortened to `max_chars` around byte `column`
+fn snippet(line: &str, column: usize, max_chars: usize) -> String {
+    let indent = line.len() - line.trim_start().len();
+    let text = line.trim();
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+
+    // Keep some text before the match so the snippet reads in context
+    let column = column.saturating_sub(indent).min(text.len());
+    let match_char = text.char_indices().take_while(|(i, _)| *i < column).count();
+    let start = match_char.saturating_sub(max_chars / 4);
+    let mut shortened: String = text.chars().skip(start).take(max_chars).collect();
+    if start > 0 {
+        shortened.insert_str(0, "...");
+    }
+    if start + max_chars < text.chars().count() {
+        shortened.push_str("...");
+    }
+    shortened
+}
+
+/// The `search` tool's validator of its results: the JSON
+/// [`SearchResults::to_json`] writes
+///
+/// Registered with [`GuardrailChain::add_tool_validator`](crate::guardrail::GuardrailChain::add_tool_validator),
+/// it rejects a replayed or cut-off result before the shared guards read it.
+/// Failed calls pass.
+pub struct SearchResultValidator;
+
+impl SemanticGuardrail for SearchResultValidator {
+    fn validate(&self, context: &GuardrailContext) -> GuardrailResult {
+        if !context.tool_result.success {
+            return GuardrailResult::Accept;
+        }
+        match check_search_output(&context.tool_result.output) {
+            Ok(()) => GuardrailResult::Accept,
+            Err(problem) => {
+                GuardrailResult::reject(format!("Malformed search result: {}", problem))
+            }
+        }
+    }
+
+    fn name(&self) -> &str {
+        "search_result"
+    }
+}
+
+fn check_search_output(output: &str) -> Result<(), String> {
+    let value: Value = serde_json::from_str(output).map_err(|e| format!("not JSON ({})", e))?;
+    let matches = value
+        .get("matches")
+        .and_then(Value::as_array)
+        .ok_or("no 'matches' array")?;
+    if !value.get("truncated").is_some_and(Value::is_boolean) {
+        return Err("no 'truncated' flag".to_string());
+    }
+    for (index, item) in matches.iter().enumerate() {
+        let complete = item.get("path").is_some_and(Value::is_string)
+            && item
+                .get("line")
+                .and_then(Value::as_u64)
+                .is_some_and(|line| line > 0)
+            && item.get("snippet").is_some_and(Value::is_string);
+        if !complete {
+            return Err(format!(
+                "match {} needs a path, a line number and a snippet",
+                index + 1
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_from_params() {
+        let request =
+            SearchRequest::from_params(&serde_json::json!({"pattern": "fn main", "glob": "*.rs"}))
+                .unwrap();
+        assert_eq!(request.path, ".");
+        assert_eq!(request.glob.as_deref(), Some("*.rs"));
+
+        assert!(matches!(
+            SearchRequest::from_params(&serde_json::json!({"pattern": "x", "path": "../etc"})),
+            Err(SearchError::UnsafePath(_))
+        ));
+        assert!(matches!(
+            SearchRequest::from_params(&serde_json::json!({"path": "src"})),
+            Err(SearchError::InvalidRequest(_))
+        ));
+    }
+
+    #[test]
+    fn test_results_are_capped() {
+        let mut results =
+            SearchResults::new(SearchLimits::new().with_max_matches(3).with_max_per_file(2));
+
+        results.start_file();
+        assert!(results.push("a.rs", 1, "    let x = 1;", 8));
+        assert!(results.push("a.rs", 2, "let x = 2;", 4));
+        assert!(!results.push("a.rs", 3, "let x = 3;", 4));
+
+        results.start_file();
+        assert!(results.push("b.rs", 7, "x", 0));
+        assert!(results.is_full());
+        assert!(!results.push("b.rs", 8, "x", 0));
+
+        assert!(results.is_truncated());
+        assert_eq!(results.matches()[0].snippet, "let x = 1;");
+        let json: Value = serde_json::from_str(&results.to_json()).unwrap();
+        assert_eq!(json["files_searched"], 2);
+        assert_eq!(json["matches"][2]["path"], "b.rs");
+        assert_eq!(json["matches"][2]["line"], 7);
+    }
+
+    #[test]
+    fn test_long_lines_are_shortened_around_match() {
+        let line = format!("{}needle{}", "a".repeat(100), "b".repeat(100));
+        let shortened = snippet(&line, 100, 20);
+        assert!(shortened.starts_with("...aaaaaneedle"));
+        assert!(shortened.ends_with("b..."));
+
+        let limits = SearchLimits::new().with_max_matches(50);
+        let request =
+            SearchRequest::from_params(&serde_json::json!({"pattern": "x", "max_matches": 500}))
+                .unwrap();
+        assert_eq!(limits.for_request(&request).max_matches, 50);
+    }
+
+    #[test]
+    fn test_result_validator() {
+        use crate::agent::AgentState;
+        use crate::tool::{ToolRequest, ToolResult};
+
+        let state = AgentState::new("Where is main defined?");
+        let request = ToolRequest {
+            tool: "search".to_string(),
+            call_id: None,
+            params: serde_json::json!({"pattern": "fn main"}),
+        };
+        let check = |result: ToolResult| {
+            SearchResultValidator.validate(&GuardrailContext {
+                state: &state,
+                tool_request: &request,
+                tool_result: &result,
+            })
+        };
+
+        let mut results = SearchResults::new(SearchLimits::new());
+        results.start_file();
+        results.push("src/main.rs", 3, "fn main() {", 0);
+        assert!(check(ToolResult::success(results.to_json())).is_accept());
+
+        let verdict = check(ToolResult::success(
+            r#"{"matches":[{"path":"src/main.rs","snippet":"fn main() {"}],"truncated":false}"#,
+        ));
+        assert!(
+            matches!(&verdict, GuardrailResult::Reject { reason } if reason.contains("match 1")),
+            "{:?}",
+            verdict
+        );
+        assert!(check(ToolResult::success("src/main.rs:3:fn main() {")).is_reject());
+        assert!(check(ToolResult::failure("Invalid pattern")).is_accept());
+    }
+}
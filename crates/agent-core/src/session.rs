@@ -0,0 +1,964 @@
+//! Host-driven agent sessions
+//!
+//! [`AgentSession`] owns the sequencing of an agent run: iterations, corrective
+//! retries, guardrail checks and pending calls. Hosts only move data between
+//! the session, their model and their tools:
+//!
+//! ```text
+//! loop {
+//!     let prompt = session.next_prompt()?;         // render for the backend
+//!     match session.accept_model_output(text)? {
+//!         StepAction::CallTool { id, request } => {
+//!             session.accept_tool_result(id, run(request))?;
+//!         }
+//!         StepAction::Done { answer } => break,
+//!         StepAction::Failed(failure) => break,
+//!         _ => {}
+//!     }
+//! }
+//! ```
+//!
+//! A corrective retry follows a guardrail rejection or an inconclusive output
+//! and does not count as an iteration; a second failure ends the run.
+//!
+//! Hosts that show the model something other than the raw tool output (a
+//! summary, a pointer to a stored copy) check the raw output with
+//! [`AgentSession::review_tool_result`] first, then pass what the model should
+//! see to [`AgentSession::accept_tool_result`].
+
+use crate::agent::{
+    apply_skill_result, apply_tool_result, process_model_output, AgentDecision, AgentState, Message,
+};
+use crate::artifact::ArtifactRequest;
+use crate::capabilities::{HostCapabilities, MAX_REFUSALS};
+use crate::guardrail::{
+    AnswerChain, AnswerContext, GuardrailChain, GuardrailContext, GuardrailResult,
+};
+use crate::history_budget::HistoryBudget;
+use crate::memory::RecallRequest;
+use crate::participants::Participants;
+use crate::prelude::*;
+use crate::prompt_adaptation::PromptAdaptation;
+use crate::protocol::{AnswerProtocol, InconclusiveReason};
+use crate::reasoning::ReasoningDelimiters;
+use crate::response_spec::ResponseSpec;
+use crate::skill::{SkillRequest, SkillResult_};
+use crate::stop::{PromptPhase, StopSequences};
+use crate::tool::{CallId, ToolRequest, ToolResult};
+use crate::transcript::ChatTranscript;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Response format asked for once a tool has been used
+pub const TOOL_RESPONSE_SCHEMA: &str = r#"When responding after tool usage:
+- First provide an OBSERVATIONS section containing factual information derived directly from tool output.
+- Then provide a FINAL ANSWER section that directly answers the user request.
+
+Both sections are required."#;
+
+/// [`TOOL_RESPONSE_SCHEMA`] under [`AnswerProtocol::Sentinel`]
+pub const SENTINEL_TOOL_RESPONSE_SCHEMA: &str = r#"When responding after tool usage:
+- Start with FINAL: on the first line.
+- Then provide an OBSERVATIONS section containing factual information derived directly from tool output.
+- Then provide a FINAL ANSWER section that directly answers the user request.
+
+Both sections are required."#;
+
+/// Default iteration limit of a session
+pub const DEFAULT_MAX_ITERATIONS: usize = 5;
+
+/// Why a session ended without an answer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Error)]
+#[serde(tag = "code", rename_all = "snake_case")]
+pub enum SessionFailure {
+    #[error("reached {max} iterations without completing")]
+    MaxIterations { max: usize },
+    #[error("guardrail rejected the tool output twice: {initial}; then: {retry}")]
+    GuardrailFailure { initial: String, retry: String },
+    #[error("inconclusive output after a guardrail rejection ({reason})")]
+    InconclusiveAfterGuardrail { reason: String, output: String },
+    #[error("inconclusive output after a corrective retry")]
+    Inconclusive { first: String, retry: String },
+    #[error("the host refused the model's request again: {reason}")]
+    Refused { reason: String },
+}
+
+impl SessionFailure {
+    /// Stable failure code, as used in host events and exit reports
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::MaxIterations { .. } => "max_iterations",
+            Self::GuardrailFailure { .. } => "guardrail_failure",
+            Self::InconclusiveAfterGuardrail { .. } => "inconclusive_after_guardrail",
+            Self::Inconclusive { .. } => "inconclusive",
+            Self::Refused { .. } => "refused",
+        }
+    }
+}
+
+/// Errors from driving a session out of order
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum SessionError {
+    /// The run cannot continue; `next_prompt` reports this once iterations run out
+    #[error("{0}")]
+    Failed(SessionFailure),
+    #[error("the session has ended")]
+    Ended,
+    #[error("call {0} is still waiting for its result")]
+    AwaitingResult(CallId),
+    #[error("no prompt is waiting for model output")]
+    NoPrompt,
+    #[error("no pending call with id {0}")]
+    UnknownCall(CallId),
+}
+
+/// Why the next prompt is a corrective retry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RetryReason {
+    /// The guardrails rejected a tool output
+    Guardrail { reason: String },
+    /// The model neither called nor answered, for `reason`
+    Inconclusive {
+        output: String,
+        reason: InconclusiveReason,
+    },
+}
+
+/// What the host does after handing the session a model output or a result
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum StepAction {
+    /// Run the tool and pass its result to `accept_tool_result`
+    CallTool { id: CallId, request: ToolRequest },
+    /// Run the skill and pass its result to `accept_skill_result`
+    CallSkill { id: CallId, request: SkillRequest },
+    /// Read the stored output and pass it to `accept_tool_result`
+    ReadArtifact {
+        id: CallId,
+        request: ArtifactRequest,
+    },
+    /// Search the memory and pass the hits to `accept_tool_result`
+    Recall { id: CallId, request: RecallRequest },
+    /// A scratchpad operation was applied; ask the model again
+    Variable {
+        operation: String,
+        result: ToolResult,
+    },
+    /// The answer was rejected and sent back; ask the model again
+    AnswerRejected { answer: String, reason: String },
+    /// The host cannot do what the model asked; the reason was sent back, ask
+    /// the model again
+    Unsupported { reason: String },
+    /// The next prompt is a corrective retry
+    Retry(RetryReason),
+    /// Ask the model again
+    Continue,
+    /// The run finished with an accepted answer
+    Done { answer: String },
+    /// The run ended without an answer
+    Failed(SessionFailure),
+}
+
+/// What the guardrails made of a tool result (see
+/// [`AgentSession::review_tool_result`])
+#[derive(Debug, Clone)]
+pub enum ToolReview {
+    /// The result is taken as it is (a retry after inconclusive output)
+    Unchecked,
+    /// The guardrails accepted the result
+    Accepted,
+    /// The guardrails rejected the result, which is not recorded; the call is
+    /// over and `action` says what follows
+    Rejected { reason: String, action: StepAction },
+}
+
+/// The prompt for the next model call
+#[derive(Debug, Clone, Serialize)]
+pub struct PromptSpec {
+    /// Iteration this call belongs to (a retry repeats its iteration)
+    pub iteration: usize,
+    pub max_iterations: usize,
+    /// Whether this call is a corrective retry
+    pub retry: bool,
+    pub system: String,
+    pub history: Vec<Message>,
+    /// Instructions that follow the conversation (may be empty)
+    pub instructions: String,
+    /// Where a completion backend should stop generating (see
+    /// [`StopSequences`])
+    pub stop: Vec<String>,
+}
+
+impl PromptSpec {
+    /// The prompt as plain text for completion backends
+    pub fn to_plain_text(&self) -> String {
+        let mut prompt = ChatTranscript::from_history(&self.history, &[])
+            .with_system(&self.system)
+            .to_plain_text();
+        prompt.push_str(&self.instructions);
+        prompt.push_str("Assistant: ");
+        prompt
+    }
+
+    /// The prompt as a chat transcript, with the instructions in the system
+    /// message; `native_tools` are the tools the backend calls natively
+    pub fn to_transcript(&self, native_tools: &[&str]) -> ChatTranscript {
+        let system = format!("{}\n\n{}", self.system, self.instructions);
+        ChatTranscript::from_history(&self.history, native_tools).with_system(system.trim_end())
+    }
+}
+
+#[derive(Debug, Clone)]
+enum PendingCall {
+    /// A tool call; the result of a `trusted` one is recorded unchecked
+    Tool {
+        request: ToolRequest,
+        trusted: bool,
+    },
+    Skill,
+    /// An artifact read or a recall, whose result is taken as it is
+    Lookup,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    /// Waiting for `next_prompt`
+    Ready,
+    /// A prompt is out; waiting for the model output
+    Prompted,
+    /// A call is out; waiting for its result
+    Calling(CallId),
+    Ended,
+}
+
+/// An agent run driven by the host, one step at a time
+pub struct AgentSession {
+    state: AgentState,
+    system_prompt: String,
+    max_iterations: usize,
+    iteration: usize,
+    tool_used: bool,
+    phase: Phase,
+    /// Retry the next prompt will be
+    retry: Option<RetryReason>,
+    /// Retry the outstanding prompt or call is answering
+    current: Option<RetryReason>,
+    pending: Option<PendingCall>,
+    output_guards: GuardrailChain,
+    answer_guards: AnswerChain,
+    adaptation: PromptAdaptation,
+    history_budget: HistoryBudget,
+    participants: Participants,
+    stop_sequences: StopSequences,
+}
+
+impl AgentSession {
+    /// A session answering `query`, with no guardrails
+    pub fn new(query: impl Into<String>, system_prompt: impl Into<String>) -> Self {
+        Self::from_state(AgentState::new(query), system_prompt)
+    }
+
+    /// A session continuing `state`, such as an imported conversation
+    pub fn from_state(state: AgentState, system_prompt: impl Into<String>) -> Self {
+        Self {
+            state,
+            system_prompt: system_prompt.into(),
+            max_iterations: DEFAULT_MAX_ITERATIONS,
+            iteration: 0,
+            tool_used: false,
+            phase: Phase::Ready,
+            retry: None,
+            current: None,
+            pending: None,
+            output_guards: GuardrailChain::new(),
+            answer_guards: AnswerChain::new(),
+            adaptation: PromptAdaptation::default(),
+            history_budget: HistoryBudget::default(),
+            participants: Participants::default(),
+            stop_sequences: StopSequences::default(),
+        }
+    }
+
+    pub fn with_max_iterations(mut self, max: usize) -> Self {
+        self.max_iterations = max;
+        self
+    }
+
+    /// Stop sequences of the prompts; each carries those of
+    /// [`PromptPhase::Turn`]
+    pub fn with_stop_sequences(mut self, stops: StopSequences) -> Self {
+        self.stop_sequences = stops;
+        self
+    }
+
+    /// Guardrails checking tool outputs
+    pub fn with_guardrails(mut self, chain: GuardrailChain) -> Self {
+        self.output_guards = chain;
+        self
+    }
+
+    /// Guardrails checking final answers
+    pub fn with_answer_guards(mut self, chain: AnswerChain) -> Self {
+        self.answer_guards = chain;
+        self
+    }
+
+    pub fn with_adaptation(mut self, adaptation: PromptAdaptation) -> Self {
+        self.adaptation = adaptation;
+        self
+    }
+
+    /// How final answers are recognized (see [`AnswerProtocol`])
+    pub fn with_answer_protocol(mut self, protocol: AnswerProtocol) -> Self {
+        self.state.answer_protocol = protocol;
+        self
+    }
+
+    /// How much of the history prompts show, by role (see [`HistoryBudget`])
+    pub fn with_history_budget(mut self, budget: HistoryBudget) -> Self {
+        self.history_budget = budget;
+        self
+    }
+
+    /// Reasoning block delimiters of the model (see [`crate::reasoning`])
+    pub fn with_reasoning_delimiters(mut self, delimiters: Option<ReasoningDelimiters>) -> Self {
+        self.state.reasoning_delimiters = delimiters;
+        self
+    }
+
+    /// Run as one named agent of several sharing the history: its replies are
+    /// recorded under its name and prompts render the others by `participants`
+    /// (see [`crate::participants`])
+    pub fn with_participants(mut self, participants: Participants) -> Self {
+        self.state.speaker = participants.speaker().map(str::to_string);
+        self.participants = participants;
+        self
+    }
+
+    /// What the host can run (see [`HostCapabilities`])
+    pub fn with_capabilities(mut self, capabilities: HostCapabilities) -> Self {
+        self.state.capabilities = capabilities;
+        self
+    }
+
+    /// Answer constraints stated in each turn's instructions; answers are
+    /// checked against them by a [`ResponseSpecGuard`] among the answer guards
+    ///
+    /// [`ResponseSpecGuard`]: crate::response_spec::ResponseSpecGuard
+    pub fn with_response_spec(mut self, spec: Option<ResponseSpec>) -> Self {
+        self.state = self.state.with_response_spec(spec);
+        self
+    }
+
+    pub fn state(&self) -> &AgentState {
+        &self.state
+    }
+
+    pub fn iteration(&self) -> usize {
+        self.iteration
+    }
+
+    /// Whether the session has finished or failed
+    pub fn is_ended(&self) -> bool {
+        self.phase == Phase::Ended
+    }
+
+    /// The prompt for the next model call
+    ///
+    /// Starts a new iteration unless the call is a corrective retry. Once the
+    /// iterations run out this fails with [`SessionFailure::MaxIterations`].
+    pub fn next_prompt(&mut self) -> Result<PromptSpec, SessionError> {
+        match self.phase {
+            Phase::Ready => {}
+            Phase::Prompted => return Err(SessionError::NoPrompt),
+            Phase::Calling(id) => return Err(SessionError::AwaitingResult(id)),
+            Phase::Ended => return Err(SessionError::Ended),
+        }
+
+        self.current = self.retry.take();
+        if self.current.is_none() {
+            if self.iteration == self.max_iterations {
+                self.phase = Phase::Ended;
+                return Err(SessionError::Failed(SessionFailure::MaxIterations {
+                    max: self.max_iterations,
+                }));
+            }
+            self.iteration += 1;
+        }
+        self.phase = Phase::Prompted;
+
+        let retry = self.current.is_some();
+        let instructions = turn_instructions(
+            &self.state,
+            self.tool_used,
+            self.current.as_ref(),
+            &self.adaptation,
+        );
+        Ok(PromptSpec {
+            iteration: self.iteration,
+            max_iterations: self.max_iterations,
+            retry,
+            system: self.system_prompt.clone(),
+            history: self
+                .history_budget
+                .apply(&self.participants.apply(&self.state.history)),
+            instructions,
+            stop: self.stop_sequences.for_phase(PromptPhase::Turn).to_vec(),
+        })
+    }
+
+    /// Hand the session the model's reply to the last prompt
+    pub fn accept_model_output(
+        &mut self,
+        output: impl Into<String>,
+    ) -> Result<StepAction, SessionError> {
+        match self.phase {
+            Phase::Prompted => {}
+            Phase::Ready => return Err(SessionError::NoPrompt),
+            Phase::Calling(id) => return Err(SessionError::AwaitingResult(id)),
+            Phase::Ended => return Err(SessionError::Ended),
+        }
+        self.phase = Phase::Ready;
+
+        let action = match process_model_output(&mut self.state, output.into()) {
+            AgentDecision::InvokeTool(request) => {
+                let trusted = matches!(self.current, Some(RetryReason::Inconclusive { .. }));
+                let id = self.start_call(
+                    request.call_id,
+                    PendingCall::Tool {
+                        request: request.clone(),
+                        trusted,
+                    },
+                );
+                StepAction::CallTool { id, request }
+            }
+            AgentDecision::InvokeSkill(request) => {
+                let id = self.start_call(request.call_id, PendingCall::Skill);
+                StepAction::CallSkill { id, request }
+            }
+            AgentDecision::ReadArtifact(request) => {
+                let id = self.start_call(request.call_id, PendingCall::Lookup);
+                StepAction::ReadArtifact { id, request }
+            }
+            AgentDecision::Recall(request) => {
+                let id = self.start_call(request.call_id, PendingCall::Lookup);
+                StepAction::Recall { id, request }
+            }
+            AgentDecision::Variable { operation, result } => {
+                StepAction::Variable { operation, result }
+            }
+            AgentDecision::Unsupported(reason) if self.state.refusals >= MAX_REFUSALS => {
+                self.fail(SessionFailure::Refused { reason })
+            }
+            AgentDecision::Unsupported(reason) => StepAction::Unsupported { reason },
+            AgentDecision::Done(answer) => {
+                let verdict = self.answer_guards.check(&AnswerContext {
+                    state: &self.state,
+                    answer: &answer,
+                });
+                match verdict {
+                    GuardrailResult::Reject { reason } => {
+                        self.state.reject_answer(&reason);
+                        StepAction::AnswerRejected { answer, reason }
+                    }
+                    GuardrailResult::Accept | GuardrailResult::Score(_) => {
+                        self.phase = Phase::Ended;
+                        StepAction::Done { answer }
+                    }
+                }
+            }
+            AgentDecision::Inconclusive(output, reason) => match self.current.take() {
+                Some(RetryReason::Guardrail { reason }) => {
+                    self.fail(SessionFailure::InconclusiveAfterGuardrail { reason, output })
+                }
+                Some(RetryReason::Inconclusive { output: first, .. }) => {
+                    self.fail(SessionFailure::Inconclusive {
+                        first,
+                        retry: output,
+                    })
+                }
+                None => self.start_retry(RetryReason::Inconclusive { output, reason }),
+            },
+        };
+        Ok(action)
+    }
+
+    /// Hand the session the result of a tool call or an artifact read
+    ///
+    /// Tool outputs go through the guardrails first, unless
+    /// [`review_tool_result`](Self::review_tool_result) already checked them
+    /// or the call is a retry after inconclusive output, which is trusted
+    /// as-is.
+    pub fn accept_tool_result(
+        &mut self,
+        id: CallId,
+        result: ToolResult,
+    ) -> Result<StepAction, SessionError> {
+        if matches!(self.pending, Some(PendingCall::Tool { .. })) {
+            if let ToolReview::Rejected { action, .. } = self.review_tool_result(id, &result)? {
+                return Ok(action);
+            }
+        }
+        let result = result.with_call_id(Some(id));
+        match self.finish_call(id, false)? {
+            PendingCall::Tool { .. } => self.tool_used = true,
+            PendingCall::Lookup => {}
+            PendingCall::Skill => unreachable!("finish_call checks the call kind"),
+        }
+        apply_tool_result(&mut self.state, &result);
+        Ok(StepAction::Continue)
+    }
+
+    /// Check the raw result of tool call `id` against the guardrails without
+    /// recording it
+    ///
+    /// Unless rejected, the call stays pending: pass what the model should see
+    /// to [`accept_tool_result`](Self::accept_tool_result), which records it
+    /// without checking it again.
+    pub fn review_tool_result(
+        &mut self,
+        id: CallId,
+        result: &ToolResult,
+    ) -> Result<ToolReview, SessionError> {
+        let request = match (self.phase, &self.pending) {
+            (Phase::Ended, _) => return Err(SessionError::Ended),
+            (Phase::Calling(pending), Some(PendingCall::Tool { request, trusted }))
+                if pending == id =>
+            {
+                if *trusted {
+                    return Ok(ToolReview::Unchecked);
+                }
+                request.clone()
+            }
+            _ => return Err(SessionError::UnknownCall(id)),
+        };
+
+        let verdict = self.output_guards.validate(&GuardrailContext {
+            state: &self.state,
+            tool_request: &request,
+            tool_result: &result.clone().with_call_id(Some(id)),
+        });
+        match verdict {
+            GuardrailResult::Accept | GuardrailResult::Score(_) => {
+                self.pending = Some(PendingCall::Tool {
+                    request,
+                    trusted: true,
+                });
+                Ok(ToolReview::Accepted)
+            }
+            GuardrailResult::Reject { reason } => {
+                self.finish_call(id, false)?;
+                self.state.record_rejection(&reason);
+                let action = match self.current.take() {
+                    Some(RetryReason::Guardrail { reason: initial }) => {
+                        self.fail(SessionFailure::GuardrailFailure {
+                            initial,
+                            retry: reason.clone(),
+                        })
+                    }
+                    _ => self.start_retry(RetryReason::Guardrail {
+                        reason: reason.clone(),
+                    }),
+                };
+                Ok(ToolReview::Rejected { reason, action })
+            }
+        }
+    }
+
+    /// Hand the session the result of a skill call
+    pub fn accept_skill_result(
+        &mut self,
+        id: CallId,
+        result: &SkillResult_,
+    ) -> Result<StepAction, SessionError> {
+        self.finish_call(id, true)?;
+        apply_skill_result(&mut self.state, &result.clone().with_call_id(Some(id)));
+        Ok(StepAction::Continue)
+    }
+
+    fn start_call(&mut self, id: Option<CallId>, call: PendingCall) -> CallId {
+        let id = id.unwrap_or_else(|| self.state.next_call_id());
+        self.pending = Some(call);
+        self.phase = Phase::Calling(id);
+        id
+    }
+
+    /// Take the pending call `id`, which must be a skill call iff `skill`
+    fn finish_call(&mut self, id: CallId, skill: bool) -> Result<PendingCall, SessionError> {
+        match (self.phase, &self.pending) {
+            (Phase::Ended, _) => return Err(SessionError::Ended),
+            (Phase::Calling(pending), Some(call))
+                if pending == id && matches!(call, PendingCall::Skill) == skill => {}
+            _ => return Err(SessionError::UnknownCall(id)),
+        }
+        self.phase = Phase::Ready;
+        Ok(self.pending.take().expect("checked above"))
+    }
+
+    fn start_retry(&mut self, reason: RetryReason) -> StepAction {
+        self.retry = Some(reason.clone());
+        StepAction::Retry(reason)
+    }
+
+    fn fail(&mut self, failure: SessionFailure) -> StepAction {
+        self.phase = Phase::Ended;
+        StepAction::Failed(failure)
+    }
+}
+
+/// Instructions that follow the conversation: what the host can run (see
+/// [`HostCapabilities::instructions`]), how to mark final answers under
+/// [`AnswerProtocol::Sentinel`], the response schema once a tool has been
+/// used, and stricter instructions on a corrective retry (`retry`), adapted
+/// to what went wrong and the guardrail rejections so far
+pub fn turn_instructions(
+    state: &AgentState,
+    tool_used: bool,
+    retry: Option<&RetryReason>,
+    adaptation: &PromptAdaptation,
+) -> String {
+    let mut prompt = String::new();
+    let sentinel = state.answer_protocol == AnswerProtocol::Sentinel;
+
+    // The system prompt may come from another host; these take precedence
+    let capabilities = state.capabilities.instructions();
+    if !capabilities.is_empty() {
+        prompt.push_str(&capabilities);
+        prompt.push_str("\n\n");
+    }
+
+    // Final answers need the sentinel whatever the system prompt says
+    if sentinel {
+        prompt.push_str(state.answer_protocol.instructions());
+        prompt.push_str("\n\n");
+    }
+
+    // Inject response schema if at least one tool has been used
+    if tool_used {
+        prompt.push_str(if sentinel {
+            SENTINEL_TOOL_RESPONSE_SCHEMA
+        } else {
+            TOOL_RESPONSE_SCHEMA
+        });
+        prompt.push_str("\n\n");
+    }
+
+    // The caller's answer constraints
+    if let Some(spec) = &state.response_spec {
+        let format = spec.instructions();
+        if !format.is_empty() {
+            prompt.push_str(&format);
+            prompt.push_str("\n\n");
+        }
+    }
+
+    // Add corrective instruction if this is a retry
+    // This prompt addresses common LLM failures: reasoning instead of action,
+    // and generating commands that produce unusable outputs (headers, summaries).
+    if let Some(retry) = retry {
+        // Say what was wrong with the inconclusive reply first
+        if let RetryReason::Inconclusive { reason, .. } = retry {
+            prompt.push_str(&reason.instruction());
+            prompt.push('\n');
+        }
+        prompt.push_str("CRITICAL: You MUST call a tool to complete this task.\n");
+        prompt.push_str("Respond ONLY with valid JSON in the exact format shown above.\n");
+        prompt.push_str(
+            "Do NOT explain what you will do. Do NOT use plain text. Output JSON only.\n\n",
+        );
+        if sentinel {
+            prompt.push_str("If you already have the answer, start it with FINAL: instead.\n\n");
+        }
+
+        prompt.push_str("IMPORTANT: The tool command must directly produce the final answer.\n");
+        prompt.push_str("Avoid commands that output headers, summaries, or non-answer lines.\n");
+        prompt.push_str(
+            "The tool output should be the actual data requested, not metadata about it.\n\n",
+        );
+
+        // Repeated rejections for the same reason get targeted guidance
+        let guidance = adaptation.guidance(state);
+        if !guidance.is_empty() {
+            prompt.push_str("Previous attempts were rejected repeatedly:\n");
+            for line in guidance {
+                prompt.push_str("- ");
+                prompt.push_str(line);
+                prompt.push('\n');
+            }
+            prompt.push('\n');
+        }
+
+        // NOTE: Semantic guardrails validate tool outputs at runtime.
+        // TODO: Future enhancement - Tool-defined postconditions
+        //
+        // Tools should optionally declare explicit semantic contracts (postconditions)
+        // that replace heuristic guardrails. This aligns with agent.cpp's callback
+        // extensibility and any-guardrail's pluggable validation model.
+    }
+
+    prompt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::guardrail::PlausibilityGuard;
+    use crate::response_spec::ResponseSpecGuard;
+
+    const SHELL_CALL: &str = r#"{"tool": "shell", "command": "ls"}"#;
+
+    fn call_id(action: StepAction) -> CallId {
+        match action {
+            StepAction::CallTool { id, .. } => id,
+            other => panic!("expected a tool call, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_tool_then_answer() {
+        let mut session = AgentSession::new("List files", "SYSTEM").with_max_iterations(3);
+
+        let prompt = session.next_prompt().unwrap();
+        assert_eq!((prompt.iteration, prompt.retry), (1, false));
+        assert!(prompt.to_plain_text().starts_with("SYSTEM"));
+        assert!(prompt.instructions.is_empty());
+        assert!(prompt.stop.contains(&"\nTool output:".to_string()));
+
+        let id = call_id(session.accept_model_output(SHELL_CALL).unwrap());
+        assert!(matches!(
+            session.next_prompt(),
+            Err(SessionError::AwaitingResult(pending)) if pending == id
+        ));
+        assert!(matches!(
+            session.accept_tool_result(CallId(id.0 + 1), ToolResult::success("x")),
+            Err(SessionError::UnknownCall(_))
+        ));
+        // A skill result for a tool call leaves the call pending
+        assert!(matches!(
+            session.accept_skill_result(id, &SkillResult_::success(serde_json::json!({}))),
+            Err(SessionError::UnknownCall(_))
+        ));
+        assert!(matches!(
+            session.accept_tool_result(id, ToolResult::success("README.md\nsrc")),
+            Ok(StepAction::Continue)
+        ));
+
+        let prompt = session.next_prompt().unwrap();
+        assert_eq!(prompt.iteration, 2);
+        assert!(prompt.instructions.starts_with(TOOL_RESPONSE_SCHEMA));
+        assert!(prompt.to_plain_text().contains("README.md"));
+
+        assert!(matches!(
+            session.accept_model_output("Two entries: README.md and src."),
+            Ok(StepAction::Done { answer }) if answer == "Two entries: README.md and src."
+        ));
+        assert!(session.is_ended());
+        assert!(matches!(session.next_prompt(), Err(SessionError::Ended)));
+    }
+
+    #[test]
+    fn test_guardrail_retry_then_failure() {
+        let mut session = AgentSession::new("Count the files", "SYSTEM")
+            .with_guardrails(GuardrailChain::new().add(Box::new(PlausibilityGuard::new())));
+
+        session.next_prompt().unwrap();
+        let id = call_id(session.accept_model_output(SHELL_CALL).unwrap());
+        let action = session
+            .accept_tool_result(id, ToolResult::success(""))
+            .unwrap();
+        assert!(matches!(
+            action,
+            StepAction::Retry(RetryReason::Guardrail { .. })
+        ));
+
+        // The retry repeats the iteration with corrective instructions
+        let prompt = session.next_prompt().unwrap();
+        assert_eq!((prompt.iteration, prompt.retry), (1, true));
+        assert!(prompt.instructions.contains("CRITICAL"));
+
+        let id = call_id(session.accept_model_output(SHELL_CALL).unwrap());
+        let action = session
+            .accept_tool_result(id, ToolResult::success(""))
+            .unwrap();
+        match action {
+            StepAction::Failed(failure) => assert_eq!(failure.code(), "guardrail_failure"),
+            other => panic!("expected a failure, got {:?}", other),
+        }
+        assert!(session.is_ended());
+    }
+
+    #[test]
+    fn test_review_then_record() {
+        let state = AgentState::from_openai_messages(
+            r#"[{"role": "user", "content": "Count the files"},
+                {"role": "assistant", "content": "Which directory?"},
+                {"role": "user", "content": "src"}]"#,
+        )
+        .unwrap();
+        let mut session = AgentSession::from_state(state, "SYSTEM")
+            .with_guardrails(GuardrailChain::new().add(Box::new(PlausibilityGuard::new())));
+        assert!(session
+            .next_prompt()
+            .unwrap()
+            .to_plain_text()
+            .contains("Which directory?"));
+
+        let id = call_id(session.accept_model_output(SHELL_CALL).unwrap());
+        match session
+            .review_tool_result(id, &ToolResult::success(""))
+            .unwrap()
+        {
+            ToolReview::Rejected { action, .. } => assert!(matches!(
+                action,
+                StepAction::Retry(RetryReason::Guardrail { .. })
+            )),
+            other => panic!("expected a rejection, got {:?}", other),
+        }
+        assert!(matches!(
+            session.accept_tool_result(id, ToolResult::success("")),
+            Err(SessionError::UnknownCall(_))
+        ));
+
+        // An accepted raw output is recorded as what the host passes
+        assert!(session.next_prompt().unwrap().retry);
+        let id = call_id(session.accept_model_output(SHELL_CALL).unwrap());
+        let raw = ToolResult::success("a.rs\nb.rs\nc.rs");
+        assert!(matches!(
+            session.review_tool_result(id, &raw).unwrap(),
+            ToolReview::Accepted
+        ));
+        assert!(matches!(
+            session.accept_tool_result(id, ToolResult::success("3 files (summary)")),
+            Ok(StepAction::Continue)
+        ));
+        let prompt = session.next_prompt().unwrap().to_plain_text();
+        assert!(prompt.contains("3 files (summary)") && !prompt.contains("a.rs"));
+    }
+
+    #[test]
+    fn test_inconclusive_retry_is_trusted() {
+        let mut session = AgentSession::new("Count the files", "SYSTEM")
+            .with_guardrails(GuardrailChain::new().add(Box::new(PlausibilityGuard::new())));
+
+        session.next_prompt().unwrap();
+        let action = session
+            .accept_model_output("I will first list the files and then count them.")
+            .unwrap();
+        assert!(matches!(
+            action,
+            StepAction::Retry(RetryReason::Inconclusive {
+                reason: InconclusiveReason::Planning,
+                ..
+            })
+        ));
+
+        // The retry says what was wrong
+        let prompt = session.next_prompt().unwrap();
+        assert!(prompt.retry);
+        assert!(prompt.instructions.starts_with("You described the plan"));
+        let id = call_id(session.accept_model_output(SHELL_CALL).unwrap());
+        // Accepted even though the guardrail would reject the empty output
+        assert!(matches!(
+            session.accept_tool_result(id, ToolResult::success("")),
+            Ok(StepAction::Continue)
+        ));
+        assert!(!session.next_prompt().unwrap().retry);
+    }
+
+    #[test]
+    fn test_sentinel_answers() {
+        let mut session = AgentSession::new("Combien de fichiers ?", "SYSTEM")
+            .with_answer_protocol(AnswerProtocol::Sentinel);
+        assert!(session
+            .next_prompt()
+            .unwrap()
+            .instructions
+            .contains("FINAL:"));
+
+        // An answer without the sentinel is retried, planning-speak or not
+        assert!(matches!(
+            session.accept_model_output("Il y a 3 fichiers.").unwrap(),
+            StepAction::Retry(RetryReason::Inconclusive { .. })
+        ));
+        assert!(session.next_prompt().unwrap().retry);
+        match session
+            .accept_model_output("FINAL: Il y a 3 fichiers.")
+            .unwrap()
+        {
+            StepAction::Done { answer } => assert_eq!(answer, "Il y a 3 fichiers."),
+            other => panic!("expected an answer, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_response_spec() {
+        let mut session = AgentSession::new("Which file is largest?", "SYSTEM")
+            .with_response_spec(Some(ResponseSpec::new().with_max_words(4)))
+            .with_answer_guards(AnswerChain::new().add(Box::new(ResponseSpecGuard::new())));
+        assert!(session
+            .next_prompt()
+            .unwrap()
+            .instructions
+            .contains("ANSWER FORMAT:\n- Use at most 4 words."));
+
+        // A long answer goes back with the reason
+        assert!(matches!(
+            session
+                .accept_model_output("The largest file in the directory is a.bin.")
+                .unwrap(),
+            StepAction::AnswerRejected { .. }
+        ));
+        session.next_prompt().unwrap();
+        assert!(matches!(
+            session.accept_model_output("a.bin is largest.").unwrap(),
+            StepAction::Done { .. }
+        ));
+    }
+
+    #[test]
+    fn test_unsupported_calls() {
+        let mut session = AgentSession::new("List the files", "SYSTEM")
+            .with_capabilities(HostCapabilities::new().with_tools(["fs_list"]));
+        assert!(session
+            .next_prompt()
+            .unwrap()
+            .instructions
+            .contains("Only these tools are available: fs_list."));
+        assert!(matches!(
+            session.accept_model_output(SHELL_CALL).unwrap(),
+            StepAction::Unsupported { .. }
+        ));
+        // The refusal is no retry: the next prompt starts a new iteration
+        let prompt = session.next_prompt().unwrap();
+        assert_eq!((prompt.iteration, prompt.retry), (2, false));
+
+        // A second refusal in a row ends the run
+        match session.accept_model_output(SHELL_CALL).unwrap() {
+            StepAction::Failed(failure) => assert_eq!(failure.code(), "refused"),
+            other => panic!("expected a failure, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_max_iterations() {
+        let mut session = AgentSession::new("Remember", "SYSTEM").with_max_iterations(1);
+        assert!(matches!(
+            session.accept_model_output("early"),
+            Err(SessionError::NoPrompt)
+        ));
+
+        session.next_prompt().unwrap();
+        let action = session
+            .accept_model_output(r#"{"set_var": {"name": "a", "value": "1"}}"#)
+            .unwrap();
+        assert!(matches!(action, StepAction::Variable { .. }));
+        assert!(matches!(
+            session.next_prompt(),
+            Err(SessionError::Failed(SessionFailure::MaxIterations {
+                max: 1
+            }))
+        ));
+        assert!(session.is_ended());
+    }
+}
@@ -0,0 +1,241 @@
+//! Many sessions in one host
+//!
+//! A server runs one [`AgentSession`] per client run. [`SessionStore`] keeps
+//! them apart by run id, each next to the backend context it uses: a context
+//! of its own (a llama context, a KV cache) or a lease on a shared, pooled
+//! backend. It caps the runs open at once and reaps runs left idle, handing
+//! their contexts back so the host can free or return them.
+//!
+//! The store only decides; hosts own the clock, the backends and the locking
+//! (one lock around the store, or one per run taken out of it). Times are
+//! offsets from an epoch the host picks.
+//!
+//! Nothing in this repository uses the store yet: `agent-native` has no server
+//! mode, and one process still runs one session.
+
+use crate::prelude::*;
+use crate::session::AgentSession;
+use alloc::collections::BTreeMap;
+use core::fmt;
+use core::time::Duration;
+use thiserror::Error;
+
+/// Runs open at once unless the host says otherwise
+pub const DEFAULT_MAX_RUNS: usize = 8;
+
+/// Time a run may sit without a request before it is reaped
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+
+/// Errors from [`SessionStore`]
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum StoreError {
+    #[error("{max} runs are already open; close one or retry later")]
+    Full { max: usize },
+    #[error("run '{0}' is already open")]
+    Duplicate(String),
+    #[error("no open run '{0}' (finished, closed or idle too long)")]
+    UnknownRun(String),
+}
+
+/// A run [`SessionStore::open`] refused, with what it was given
+pub struct Refused<C> {
+    pub error: StoreError,
+    pub session: AgentSession,
+    pub context: C,
+}
+
+impl<C> fmt::Debug for Refused<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Refused")
+            .field("error", &self.error)
+            .finish_non_exhaustive()
+    }
+}
+
+/// One open run
+pub struct OpenRun<C> {
+    pub session: AgentSession,
+    /// Backend context the run's prompts go to
+    pub context: C,
+    last_active: Duration,
+}
+
+/// Open runs by id, with their backend contexts
+pub struct SessionStore<C> {
+    runs: BTreeMap<String, OpenRun<C>>,
+    max_runs: usize,
+    idle_timeout: Duration,
+}
+
+impl<C> Default for SessionStore<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C> SessionStore<C> {
+    pub fn new() -> Self {
+        Self {
+            runs: BTreeMap::new(),
+            max_runs: DEFAULT_MAX_RUNS,
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+        }
+    }
+
+    /// Cap the runs open at once (at least one)
+    pub fn with_max_runs(mut self, max: usize) -> Self {
+        self.max_runs = max.max(1);
+        self
+    }
+
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = timeout;
+        self
+    }
+
+    /// Open run `id` with its session and context
+    ///
+    /// A refused run gets its session and context back, so a pooled context
+    /// can be returned. Idle runs only make room once [`reap`](Self::reap)ed.
+    pub fn open(
+        &mut self,
+        id: impl Into<String>,
+        session: AgentSession,
+        context: C,
+        now: Duration,
+    ) -> Result<(), Box<Refused<C>>> {
+        let id = id.into();
+        let error = if self.runs.contains_key(&id) {
+            StoreError::Duplicate(id)
+        } else if self.runs.len() >= self.max_runs {
+            StoreError::Full { max: self.max_runs }
+        } else {
+            self.runs.insert(
+                id,
+                OpenRun {
+                    session,
+                    context,
+                    last_active: now,
+                },
+            );
+            return Ok(());
+        };
+        Err(Box::new(Refused {
+            error,
+            session,
+            context,
+        }))
+    }
+
+    /// Run `id`, marked active at `now`
+    pub fn get_mut(&mut self, id: &str, now: Duration) -> Result<&mut OpenRun<C>, StoreError> {
+        let run = self
+            .runs
+            .get_mut(id)
+            .ok_or_else(|| StoreError::UnknownRun(id.to_string()))?;
+        run.last_active = now;
+        Ok(run)
+    }
+
+    /// Take run `id` out of the store (it finished or the client left)
+    pub fn close(&mut self, id: &str) -> Result<OpenRun<C>, StoreError> {
+        self.runs
+            .remove(id)
+            .ok_or_else(|| StoreError::UnknownRun(id.to_string()))
+    }
+
+    /// Take out the runs idle longer than the timeout, oldest first
+    pub fn reap(&mut self, now: Duration) -> Vec<(String, OpenRun<C>)> {
+        let timeout = self.idle_timeout;
+        let idle: Vec<String> = self
+            .runs
+            .iter()
+            .filter(|(_, run)| now.saturating_sub(run.last_active) > timeout)
+            .map(|(id, _)| id.clone())
+            .collect();
+        let mut reaped: Vec<(String, OpenRun<C>)> = idle
+            .into_iter()
+            .filter_map(|id| self.runs.remove(&id).map(|run| (id, run)))
+            .collect();
+        reaped.sort_by_key(|(_, run)| run.last_active);
+        reaped
+    }
+
+    /// When the next run becomes idle, for a host that sleeps until then
+    pub fn next_reap(&self) -> Option<Duration> {
+        self.runs
+            .values()
+            .map(|run| run.last_active + self.idle_timeout)
+            .min()
+    }
+
+    /// Ids of the open runs
+    pub fn ids(&self) -> impl Iterator<Item = &str> {
+        self.runs.keys().map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.runs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.runs.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(seconds: u64) -> Duration {
+        Duration::from_secs(seconds)
+    }
+
+    fn session() -> AgentSession {
+        AgentSession::new("query", "system")
+    }
+
+    #[test]
+    fn test_runs_are_isolated_and_capped() {
+        let mut store: SessionStore<u32> = SessionStore::new().with_max_runs(2);
+        store.open("a", session(), 1, secs(0)).unwrap();
+        store.open("b", session(), 2, secs(0)).unwrap();
+        let refused = store.open("a", session(), 3, secs(1)).unwrap_err();
+        assert_eq!(refused.error, StoreError::Duplicate("a".to_string()));
+        let refused = store.open("c", session(), 3, secs(1)).unwrap_err();
+        assert_eq!(refused.error, StoreError::Full { max: 2 });
+        assert_eq!(refused.context, 3);
+
+        store.get_mut("b", secs(2)).unwrap().context += 10;
+        assert_eq!(store.get_mut("a", secs(2)).unwrap().context, 1);
+        assert_eq!(store.close("b").unwrap().context, 12);
+        assert_eq!(
+            store.get_mut("b", secs(3)).err(),
+            Some(StoreError::UnknownRun("b".to_string()))
+        );
+        store.open("c", session(), 3, secs(3)).unwrap();
+        assert_eq!(store.ids().collect::<Vec<_>>(), vec!["a", "c"]);
+    }
+
+    #[test]
+    fn test_idle_runs_are_reaped() {
+        let mut store: SessionStore<&str> = SessionStore::new()
+            .with_max_runs(2)
+            .with_idle_timeout(secs(60));
+        store.open("a", session(), "ctx-a", secs(0)).unwrap();
+        store.open("b", session(), "ctx-b", secs(30)).unwrap();
+        assert_eq!(store.next_reap(), Some(secs(60)));
+
+        // Touching a run keeps it alive
+        store.get_mut("a", secs(50)).unwrap();
+        assert!(store.reap(secs(85)).is_empty());
+        assert_eq!(store.next_reap(), Some(secs(90)));
+
+        // Reaping hands the contexts back and makes room
+        let reaped = store.reap(secs(95));
+        assert_eq!(reaped.len(), 1);
+        assert_eq!((reaped[0].0.as_str(), reaped[0].1.context), ("b", "ctx-b"));
+        store.open("c", session(), "ctx-c", secs(95)).unwrap();
+        assert_eq!(store.len(), 2);
+    }
+}
@@ -9,6 +9,9 @@
 //!
 //! The host executes skills; agent-core defines the contracts and validates outputs.
 
+use crate::prelude::*;
+use crate::tool::CallId;
+use alloc::collections::BTreeMap;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
@@ -21,6 +24,7 @@ pub enum ExtractionTarget {
     Date,
     Entity,
     Name,
+    Fields,
 }
 
 impl ExtractionTarget {
@@ -32,6 +36,7 @@ impl ExtractionTarget {
             "date" => Some(Self::Date),
             "entity" => Some(Self::Entity),
             "name" => Some(Self::Name),
+            "fields" => Some(Self::Fields),
             _ => None,
         }
     }
@@ -44,6 +49,40 @@ impl ExtractionTarget {
             Self::Date => "date",
             Self::Entity => "entity",
             Self::Name => "name",
+            Self::Fields => "fields",
+        }
+    }
+}
+
+/// Entity classes that can be requested with the `entity` target
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntityClass {
+    People,
+    Organizations,
+    Locations,
+}
+
+impl EntityClass {
+    /// All entity classes, in output order
+    pub const ALL: [EntityClass; 3] = [Self::People, Self::Organizations, Self::Locations];
+
+    /// Parse an entity class from string
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "people" => Some(Self::People),
+            "organizations" => Some(Self::Organizations),
+            "locations" => Some(Self::Locations),
+            _ => None,
+        }
+    }
+
+    /// Get the class name as string (also the output field name)
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::People => "people",
+            Self::Organizations => "organizations",
+            Self::Locations => "locations",
         }
     }
 }
@@ -55,6 +94,13 @@ pub struct ExtractionInput {
     pub text: String,
     /// What to extract from the text
     pub target: String,
+    /// Entity classes to return (only valid with the `entity` target).
+    /// `None` means all classes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub classes: Option<Vec<String>>,
+    /// Field names and descriptions to extract (only valid with the `fields` target)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<BTreeMap<String, String>>,
 }
 
 impl ExtractionInput {
@@ -63,9 +109,37 @@ impl ExtractionInput {
         Self {
             text: text.into(),
             target: target.into(),
+            classes: None,
+            schema: None,
         }
     }
 
+    /// Restrict an entity extraction to the given classes
+    pub fn with_classes<I, S>(mut self, classes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.classes = Some(classes.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Set the user-defined fields for a `fields` extraction (name -> description)
+    pub fn with_schema<I, K, V>(mut self, schema: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.schema = Some(
+            schema
+                .into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect(),
+        );
+        self
+    }
+
     /// Validate the input
     pub fn validate(&self) -> Result<ExtractionTarget, SkillError> {
         // Check for empty text
@@ -74,59 +148,153 @@ impl ExtractionInput {
         }
 
         // Validate target
-        ExtractionTarget::from_str(&self.target)
-            .ok_or_else(|| SkillError::InvalidTarget(self.target.clone()))
+        let target = ExtractionTarget::from_str(&self.target)
+            .ok_or_else(|| SkillError::InvalidTarget(self.target.clone()))?;
+
+        // Validate entity classes
+        if let Some(ref classes) = self.classes {
+            if target != ExtractionTarget::Entity {
+                return Err(SkillError::SchemaViolation(format!(
+                    "'classes' is only supported for target 'entity', not '{}'",
+                    target.as_str()
+                )));
+            }
+            if classes.is_empty() {
+                return Err(SkillError::SchemaViolation(
+                    "'classes' must not be empty".to_string(),
+                ));
+            }
+            for class in classes {
+                if EntityClass::from_str(class).is_none() {
+                    return Err(SkillError::InvalidClass(class.clone()));
+                }
+            }
+        }
+
+        // Validate user-defined fields
+        match (target, &self.schema) {
+            (ExtractionTarget::Fields, None) => {
+                return Err(SkillError::SchemaViolation(
+                    "target 'fields' requires a 'schema' object".to_string(),
+                ));
+            }
+            (ExtractionTarget::Fields, Some(schema)) => {
+                if schema.is_empty() {
+                    return Err(SkillError::SchemaViolation(
+                        "'schema' must not be empty".to_string(),
+                    ));
+                }
+                if schema.keys().any(|k| k.trim().is_empty()) {
+                    return Err(SkillError::SchemaViolation(
+                        "'schema' field names must not be empty".to_string(),
+                    ));
+                }
+            }
+            (_, Some(_)) => {
+                return Err(SkillError::SchemaViolation(format!(
+                    "'schema' is only supported for target 'fields', not '{}'",
+                    target.as_str()
+                )));
+            }
+            (_, None) => {}
+        }
+
+        Ok(target)
+    }
+
+    /// The entity classes requested by this input, in output order
+    ///
+    /// Unknown class names are ignored; call `validate` first to reject them.
+    pub fn entity_classes(&self) -> Vec<EntityClass> {
+        match self.classes {
+            Some(ref classes) => EntityClass::ALL
+                .into_iter()
+                .filter(|c| classes.iter().any(|s| EntityClass::from_str(s) == Some(*c)))
+                .collect(),
+            None => EntityClass::ALL.to_vec(),
+        }
     }
 }
 
+/// A half-open range `[start, end)` of character offsets in the source text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct SourceSpan {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Where an extracted value was found in the source text
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValueSpans {
+    /// The extracted value
+    pub value: String,
+    /// Entity class or field name the value belongs to (entity/fields targets)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    /// Every location of the value in the source text
+    pub spans: Vec<SourceSpan>,
+}
+
 /// Output from the extraction skill
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExtractionOutput {
-    /// The extraction result as JSON
-    #[serde(flatten)]
+    /// The extraction result as JSON, keyed by target (`{"email": [...]}`)
     pub result: Value,
+    /// Source locations of the extracted values, as computed by
+    /// `validate_extraction_output` (empty until validated)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub spans: Vec<ValueSpans>,
 }
 
 impl ExtractionOutput {
-    /// Create output for email extraction
-    pub fn emails(emails: Vec<String>) -> Self {
+    /// Create output from a raw extraction result
+    pub fn new(result: Value) -> Self {
         Self {
-            result: serde_json::json!({ "email": emails }),
+            result,
+            spans: Vec::new(),
         }
     }
 
+    /// Attach source spans (usually the ones returned by `validate_extraction_output`)
+    pub fn with_spans(mut self, spans: Vec<ValueSpans>) -> Self {
+        self.spans = spans;
+        self
+    }
+
+    /// Create output for email extraction
+    pub fn emails(emails: Vec<String>) -> Self {
+        Self::new(serde_json::json!({ "email": emails }))
+    }
+
     /// Create output for URL extraction
     pub fn urls(urls: Vec<String>) -> Self {
-        Self {
-            result: serde_json::json!({ "url": urls }),
-        }
+        Self::new(serde_json::json!({ "url": urls }))
     }
 
     /// Create output for date extraction
     pub fn dates(dates: Vec<String>) -> Self {
-        Self {
-            result: serde_json::json!({ "date": dates }),
-        }
+        Self::new(serde_json::json!({ "date": dates }))
     }
 
     /// Create output for entity extraction
     pub fn entities(people: Vec<String>, orgs: Vec<String>, locations: Vec<String>) -> Self {
-        Self {
-            result: serde_json::json!({
-                "entity": {
-                    "people": people,
-                    "organizations": orgs,
-                    "locations": locations
-                }
-            }),
-        }
+        Self::new(serde_json::json!({
+            "entity": {
+                "people": people,
+                "organizations": orgs,
+                "locations": locations
+            }
+        }))
     }
 
     /// Create output for name extraction
     pub fn names(names: Vec<String>) -> Self {
-        Self {
-            result: serde_json::json!({ "name": names }),
-        }
+        Self::new(serde_json::json!({ "name": names }))
+    }
+
+    /// Create output for user-defined field extraction (`None` = not found)
+    pub fn fields(fields: BTreeMap<String, Option<String>>) -> Self {
+        Self::new(serde_json::json!({ "fields": fields }))
     }
 
     /// Check if the output contains the expected target field
@@ -138,6 +306,12 @@ impl ExtractionOutput {
     pub fn to_json(&self) -> String {
         serde_json::to_string(&self.result).unwrap_or_default()
     }
+
+    /// Get the output as JSON: the `result` object, and a `spans` array next
+    /// to it when spans are attached
+    pub fn to_value(&self) -> Value {
+        serde_json::to_value(self).unwrap_or_else(|_| serde_json::json!({ "result": self.result }))
+    }
 }
 
 /// Errors that can occur during skill execution
@@ -147,6 +321,8 @@ pub enum SkillError {
     EmptyInput,
     /// The specified target is not supported
     InvalidTarget(String),
+    /// The specified entity class is not supported
+    InvalidClass(String),
     /// The skill output is not valid JSON
     MalformedOutput(String),
     /// The output does not match the expected schema
@@ -155,6 +331,8 @@ pub enum SkillError {
     HallucinationDetected(String),
     /// Unknown skill name
     UnknownSkill(String),
+    /// The skill's prompt template could not be rendered
+    InvalidTemplate(String),
 }
 
 impl core::fmt::Display for SkillError {
@@ -162,6 +340,7 @@ impl core::fmt::Display for SkillError {
         match self {
             Self::EmptyInput => write!(f, "EmptyInput: the input text is empty"),
             Self::InvalidTarget(t) => write!(f, "InvalidTarget: unknown target '{}'", t),
+            Self::InvalidClass(c) => write!(f, "InvalidClass: unknown entity class '{}'", c),
             Self::MalformedOutput(msg) => write!(f, "MalformedOutput: {}", msg),
             Self::SchemaViolation(msg) => write!(f, "SchemaViolation: {}", msg),
             Self::HallucinationDetected(val) => {
@@ -172,6 +351,7 @@ impl core::fmt::Display for SkillError {
                 )
             }
             Self::UnknownSkill(name) => write!(f, "UnknownSkill: '{}'", name),
+            Self::InvalidTemplate(msg) => write!(f, "InvalidTemplate: {}", msg),
         }
     }
 }
@@ -184,6 +364,9 @@ pub type SkillResult<T> = Result<T, SkillError>;
 pub struct SkillRequest {
     /// The skill name (e.g., "extract")
     pub skill: String,
+    /// The call this request belongs to (`None` until the agent assigns one)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub call_id: Option<CallId>,
     /// The skill parameters
     #[serde(flatten)]
     pub params: Value,
@@ -194,6 +377,7 @@ impl SkillRequest {
     pub fn new(skill: impl Into<String>, params: Value) -> Self {
         Self {
             skill: skill.into(),
+            call_id: None,
             params,
         }
     }
@@ -217,7 +401,35 @@ impl SkillRequest {
             .and_then(|v| v.as_str())
             .ok_or_else(|| SkillError::SchemaViolation("missing 'target' field".to_string()))?;
 
-        Ok(ExtractionInput::new(text, target))
+        let mut input = ExtractionInput::new(text, target);
+
+        if let Some(classes) = self.params.get("classes") {
+            let classes = classes
+                .as_array()
+                .and_then(|arr| arr.iter().map(|v| v.as_str()).collect::<Option<Vec<_>>>())
+                .ok_or_else(|| {
+                    SkillError::SchemaViolation("'classes' must be an array of strings".to_string())
+                })?;
+            input = input.with_classes(classes);
+        }
+
+        if let Some(schema) = self.params.get("schema") {
+            let schema = schema
+                .as_object()
+                .and_then(|obj| {
+                    obj.iter()
+                        .map(|(k, v)| v.as_str().map(|d| (k.clone(), d.to_string())))
+                        .collect::<Option<Vec<_>>>()
+                })
+                .ok_or_else(|| {
+                    SkillError::SchemaViolation(
+                        "'schema' must be an object of field descriptions".to_string(),
+                    )
+                })?;
+            input = input.with_schema(schema);
+        }
+
+        Ok(input)
     }
 }
 
@@ -230,6 +442,9 @@ pub struct SkillResult_ {
     pub output: Option<Value>,
     /// Error information (if failed)
     pub error: Option<String>,
+    /// The call this result answers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub call_id: Option<CallId>,
 }
 
 impl SkillResult_ {
@@ -239,6 +454,7 @@ impl SkillResult_ {
             success: true,
             output: Some(output),
             error: None,
+            call_id: None,
         }
     }
 
@@ -248,9 +464,16 @@ impl SkillResult_ {
             success: false,
             output: None,
             error: Some(error.to_string()),
+            call_id: None,
         }
     }
 
+    /// Mark the result as the answer to call `id`
+    pub fn with_call_id(mut self, id: Option<CallId>) -> Self {
+        self.call_id = id;
+        self
+    }
+
     /// Get the output as JSON string
     pub fn to_json(&self) -> String {
         if let Some(ref output) = self.output {
@@ -263,12 +486,19 @@ impl SkillResult_ {
     }
 }
 
+/// Post-processing of a skill's validated output, before it enters the history
+pub type SkillPostProcess = fn(ExtractionOutput, ExtractionTarget) -> ExtractionOutput;
+
 /// Skill metadata for registration
 #[derive(Debug, Clone, Copy)]
 pub struct SkillMetadata {
     pub name: &'static str,
     pub description: &'static str,
     pub version: &'static str,
+    /// A call of the skill as the model writes it
+    pub call: &'static str,
+    /// Applied to every output that passed the skill's guardrails
+    pub post_process: Option<SkillPostProcess>,
 }
 
 /// Extraction skill metadata
@@ -276,6 +506,8 @@ pub const EXTRACTION_SKILL: SkillMetadata = SkillMetadata {
     name: "extract",
     description: "Extract structured information from unstructured text",
     version: "1.0.0",
+    call: r#"{"skill": "extract", "text": <string>, "target": <email|url|date|entity|name|fields>}"#,
+    post_process: Some(normalize_extraction_output),
 };
 
 /// Available skills registry
@@ -286,17 +518,107 @@ pub fn is_valid_skill(name: &str) -> bool {
     AVAILABLE_SKILLS.iter().any(|s| s.name == name)
 }
 
+/// Tidy a validated extraction: drop duplicate values, lowercase URL schemes
+/// and hosts, sort dates
+///
+/// Emails, names and entities are deduplicated ignoring case, keeping the
+/// first spelling; URLs that differ only in scheme, `www.` or a trailing slash
+/// count as duplicates. Spans of dropped duplicates move to the value kept.
+pub fn normalize_extraction_output(
+    mut output: ExtractionOutput,
+    target: ExtractionTarget,
+) -> ExtractionOutput {
+    let key = |value: &str| match target {
+        ExtractionTarget::Url => normalize_url(&value.to_lowercase()),
+        _ => value.to_lowercase(),
+    };
+
+    let tidy = |values: Vec<&str>| -> Vec<String> {
+        let mut kept: Vec<String> = Vec::new();
+        for value in values {
+            let value = match target {
+                ExtractionTarget::Url => lowercase_url_host(value.trim()),
+                _ => value.trim().to_string(),
+            };
+            if !kept.iter().any(|k| key(k) == key(&value)) {
+                kept.push(value);
+            }
+        }
+        if target == ExtractionTarget::Date {
+            kept.sort();
+        }
+        kept
+    };
+
+    match target {
+        ExtractionTarget::Fields => return output,
+        ExtractionTarget::Entity => {
+            if let Some(Value::Object(classes)) = output.result.get_mut("entity") {
+                for values in classes.values_mut() {
+                    *values = Value::from(tidy(string_items(Some(&*values))));
+                }
+            }
+        }
+        _ => {
+            let values = tidy(string_items(output.result.get(target.as_str())));
+            output.result[target.as_str()] = Value::from(values);
+        }
+    }
+
+    // Merge the spans of duplicates into the entry of the value kept
+    let mut spans: Vec<ValueSpans> = Vec::new();
+    for located in core::mem::take(&mut output.spans) {
+        let existing = spans
+            .iter_mut()
+            .find(|s| s.field == located.field && key(&s.value) == key(&located.value));
+        match existing {
+            Some(existing) => {
+                for span in located.spans {
+                    if !existing.spans.contains(&span) {
+                        existing.spans.push(span);
+                    }
+                }
+                existing.spans.sort_by_key(|span| span.start);
+            }
+            None => spans.push(ValueSpans {
+                value: match target {
+                    ExtractionTarget::Url => lowercase_url_host(&located.value),
+                    _ => located.value,
+                },
+                ..located
+            }),
+        }
+    }
+    if target == ExtractionTarget::Date {
+        spans.sort_by(|a, b| a.value.cmp(&b.value));
+    }
+    output.spans = spans;
+    output
+}
+
+/// Lowercase the scheme and host of a URL, leaving its path as written
+fn lowercase_url_host(url: &str) -> String {
+    let host_start = url.find("://").map_or(0, |i| i + 3);
+    let host_end = url[host_start..]
+        .find(['/', '?', '#'])
+        .map_or(url.len(), |i| host_start + i);
+    format!("{}{}", url[..host_end].to_lowercase(), &url[host_end..])
+}
+
 /// Validate extraction output against input (guardrail)
 ///
 /// This is the core guardrail for the extraction skill.
 /// It ensures:
 /// 1. Output contains the expected target field
 /// 2. Extracted values appear in the source text (no hallucination)
+///
+/// On success, returns where each extracted value was found in the source text
+/// (char offsets), ready to attach with `ExtractionOutput::with_spans`.
 pub fn validate_extraction_output(
     input: &ExtractionInput,
     output: &ExtractionOutput,
     target: ExtractionTarget,
-) -> SkillResult<()> {
+) -> SkillResult<Vec<ValueSpans>> {
     // Check target field exists
     if !output.has_target_field(target) {
         return Err(SkillError::SchemaViolation(format!(
@@ -306,70 +628,439 @@ pub fn validate_extraction_output(
     }
 
     // Validate extracted values appear in source text (anti-hallucination)
-    let source_lower = input.text.to_lowercase();
+    // Normalized once: outputs can hold many values
+    let source = GroundingSource::new(target, &input.text);
+    let mut located = Vec::new();
+
+    let mut locate = |field: Option<&str>, item: &str| -> SkillResult<()> {
+        let spans = source.locate(item);
+        if spans.is_empty() {
+            return Err(SkillError::HallucinationDetected(item.to_string()));
+        }
+        located.push(ValueSpans {
+            value: item.to_string(),
+            field: field.map(str::to_string),
+            spans,
+        });
+        Ok(())
+    };
 
     match target {
-        ExtractionTarget::Email | ExtractionTarget::Url | ExtractionTarget::Date => {
-            if let Some(values) = output.result.get(target.as_str()) {
-                let items: Vec<&str> = match values {
-                    Value::String(s) => vec![s.as_str()],
-                    Value::Array(arr) => arr.iter().filter_map(|v| v.as_str()).collect(),
-                    _ => vec![],
-                };
+        ExtractionTarget::Email
+        | ExtractionTarget::Url
+        | ExtractionTarget::Date
+        | ExtractionTarget::Name => {
+            for item in string_items(output.result.get(target.as_str())) {
+                locate(None, item)?;
+            }
+        }
+        ExtractionTarget::Entity => {
+            let classes = input.entity_classes();
+            let entity = output
+                .result
+                .get("entity")
+                .and_then(|v| v.as_object())
+                .ok_or_else(|| {
+                    SkillError::SchemaViolation("'entity' must be an object".to_string())
+                })?;
 
-                for item in items {
-                    // Check if the extracted value appears in source
-                    if !source_lower.contains(&item.to_lowercase()) {
-                        return Err(SkillError::HallucinationDetected(item.to_string()));
+            // Reject classes that were not requested
+            for key in entity.keys() {
+                let requested = EntityClass::from_str(key)
+                    .map(|c| classes.contains(&c))
+                    .unwrap_or(false);
+                if !requested {
+                    return Err(SkillError::SchemaViolation(format!(
+                        "unrequested entity class '{}'",
+                        key
+                    )));
+                }
+            }
+
+            // For entities, check each extracted value of the requested classes
+            for field in classes.iter().map(|c| c.as_str()) {
+                if let Some(Value::Array(arr)) = entity.get(field) {
+                    for item in arr.iter().filter_map(|v| v.as_str()) {
+                        locate(Some(field), item)?;
                     }
                 }
             }
         }
-        ExtractionTarget::Name => {
-            // For names, use lenient matching (check individual words)
-            if let Some(values) = output.result.get("name") {
-                let items: Vec<&str> = match values {
-                    Value::String(s) => vec![s.as_str()],
-                    Value::Array(arr) => arr.iter().filter_map(|v| v.as_str()).collect(),
-                    _ => vec![],
+        ExtractionTarget::Fields => {
+            let schema = input.schema.as_ref().ok_or_else(|| {
+                SkillError::SchemaViolation(
+                    "target 'fields' requires a 'schema' object".to_string(),
+                )
+            })?;
+            let fields = output
+                .result
+                .get("fields")
+                .and_then(|v| v.as_object())
+                .ok_or_else(|| {
+                    SkillError::SchemaViolation("'fields' must be an object".to_string())
+                })?;
+
+            // Output must contain exactly the requested keys
+            for key in schema.keys() {
+                if !fields.contains_key(key) {
+                    return Err(SkillError::SchemaViolation(format!(
+                        "output missing field '{}'",
+                        key
+                    )));
+                }
+            }
+            for key in fields.keys() {
+                if !schema.contains_key(key) {
+                    return Err(SkillError::SchemaViolation(format!(
+                        "unrequested field '{}'",
+                        key
+                    )));
+                }
+            }
+
+            // Values must be null (not found) or grounded in the source text
+            for (key, value) in fields {
+                let item = match value {
+                    Value::Null => continue,
+                    Value::String(s) if s.trim().is_empty() => continue,
+                    Value::String(s) => s.clone(),
+                    Value::Number(n) => n.to_string(),
+                    Value::Bool(b) => b.to_string(),
+                    _ => {
+                        return Err(SkillError::SchemaViolation(format!(
+                            "field '{}' must be a string or null",
+                            key
+                        )));
+                    }
                 };
+                locate(Some(key), &item)?;
+            }
+        }
+    }
+
+    Ok(located)
+}
+
+/// String items of a `string | string[]` output value
+pub(crate) fn string_items(value: Option<&Value>) -> Vec<&str> {
+    match value {
+        Some(Value::String(s)) => vec![s.as_str()],
+        Some(Value::Array(arr)) => arr.iter().filter_map(|v| v.as_str()).collect(),
+        _ => vec![],
+    }
+}
+
+/// Source text normalized once for locating many values of one target
+pub(crate) struct GroundingSource {
+    target: ExtractionTarget,
+    chars: Vec<char>,
+    lower: NormalizedText,
+    /// The target's own normalization (dates, collapsed fields), if any
+    normalized: Option<NormalizedText>,
+}
+
+impl GroundingSource {
+    pub(crate) fn new(target: ExtractionTarget, source: &str) -> Self {
+        let normalized = match target {
+            ExtractionTarget::Date => Some(NormalizedText::date(source)),
+            ExtractionTarget::Fields => Some(NormalizedText::collapsed(source)),
+            _ => None,
+        };
+        Self {
+            target,
+            chars: source.chars().collect(),
+            lower: NormalizedText::lowercase(source),
+            normalized,
+        }
+    }
+
+    /// Find where `item` is grounded in the source text
+    ///
+    /// Matching is verbatim (case-insensitive) first, then target-aware:
+    /// - Email: `mailto:` prefix is ignored
+    /// - Url: scheme, `www.` and trailing slash are ignored
+    /// - Date: an ISO date matches any common written form of the same date
+    /// - Name/Entity: any individual word of the value may match
+    /// - Fields: whitespace runs are collapsed on both sides
+    ///
+    /// Returns an empty list if the value is not grounded (hallucination).
+    pub(crate) fn locate(&self, item: &str) -> Vec<SourceSpan> {
+        let item = item.trim();
+        if item.is_empty() {
+            return Vec::new();
+        }
+
+        let lower = &self.lower;
+        let item_lower = item.to_lowercase();
+        let verbatim = lower.find(&item_lower, false);
+        if !verbatim.is_empty() {
+            return verbatim;
+        }
 
-                for item in items {
-                    // More lenient matching for names (check individual words)
-                    let words: Vec<&str> = item.split_whitespace().collect();
-                    let found = words
+        match (self.target, &self.normalized) {
+            (ExtractionTarget::Email, _) => match item_lower.strip_prefix("mailto:") {
+                Some(email) => lower.find(email, false),
+                None => Vec::new(),
+            },
+            (ExtractionTarget::Url, _) => {
+                let url = normalize_url(&item_lower);
+                let mut spans: Vec<SourceSpan> = lower
+                    .find(&url, false)
+                    .into_iter()
+                    .map(|span| extend_url_prefix(&self.chars, span))
+                    .collect();
+                spans.dedup();
+                spans
+            }
+            (ExtractionTarget::Date, Some(normalized)) => match parse_iso_date(&item_lower) {
+                Some(date) => {
+                    let mut spans: Vec<SourceSpan> = date_variants(date)
                         .iter()
-                        .any(|w| source_lower.contains(&w.to_lowercase()));
-                    if !found {
-                        return Err(SkillError::HallucinationDetected(item.to_string()));
-                    }
+                        .flat_map(|v| normalized.find(&NormalizedText::date(v).text(), true))
+                        .collect();
+                    spans.sort();
+                    spans.dedup();
+                    spans
                 }
+                None => Vec::new(),
+            },
+            (ExtractionTarget::Name | ExtractionTarget::Entity, _) => {
+                // Lenient matching for names (check individual words)
+                let mut spans: Vec<SourceSpan> = item_lower
+                    .split_whitespace()
+                    .flat_map(|w| lower.find(w, false))
+                    .collect();
+                spans.sort();
+                spans.dedup();
+                spans
             }
+            (ExtractionTarget::Fields, Some(normalized)) => {
+                normalized.find(&NormalizedText::collapsed(item).text(), false)
+            }
+            (ExtractionTarget::Date | ExtractionTarget::Fields, None) => Vec::new(),
         }
-        ExtractionTarget::Entity => {
-            // For entities, check each extracted name/org/location
-            if let Some(entity) = output.result.get("entity") {
-                for field in ["people", "organizations", "locations"] {
-                    if let Some(Value::Array(arr)) = entity.get(field) {
-                        for val in arr {
-                            if let Some(s) = val.as_str() {
-                                // More lenient matching for entities (check individual words)
-                                let words: Vec<&str> = s.split_whitespace().collect();
-                                let found = words
-                                    .iter()
-                                    .any(|w| source_lower.contains(&w.to_lowercase()));
-                                if !found {
-                                    return Err(SkillError::HallucinationDetected(s.to_string()));
-                                }
-                            }
-                        }
-                    }
+    }
+}
+
+/// Text normalized for matching, with a map back to source char offsets
+struct NormalizedText {
+    chars: Vec<char>,
+    /// `origin[i]` is the source char offset that produced `chars[i]`
+    origin: Vec<usize>,
+}
+
+impl NormalizedText {
+    /// Lowercased text
+    fn lowercase(s: &str) -> Self {
+        let mut chars = Vec::with_capacity(s.len());
+        let mut origin = Vec::with_capacity(s.len());
+        for (i, c) in s.chars().enumerate() {
+            for lc in c.to_lowercase() {
+                chars.push(lc);
+                origin.push(i);
+            }
+        }
+        Self { chars, origin }
+    }
+
+    /// Lowercased text with whitespace runs collapsed to a single space
+    fn collapsed(s: &str) -> Self {
+        Self::lowercase(s).collapse_whitespace()
+    }
+
+    /// Lowercased text with commas and ordinal suffixes (`5th` -> `5`) removed and
+    /// whitespace collapsed
+    fn date(s: &str) -> Self {
+        let mut text = Self::lowercase(s);
+        for c in text.chars.iter_mut() {
+            if *c == ',' {
+                *c = ' ';
+            }
+        }
+        text.strip_ordinals().collapse_whitespace()
+    }
+
+    fn text(&self) -> String {
+        self.chars.iter().collect()
+    }
+
+    fn collapse_whitespace(self) -> Self {
+        let mut out = Self {
+            chars: Vec::with_capacity(self.chars.len()),
+            origin: Vec::with_capacity(self.chars.len()),
+        };
+        let mut pending_space = None;
+        for (c, o) in self.chars.into_iter().zip(self.origin) {
+            if c.is_whitespace() {
+                if !out.chars.is_empty() && pending_space.is_none() {
+                    pending_space = Some(o);
+                }
+                continue;
+            }
+            if let Some(space) = pending_space.take() {
+                out.chars.push(' ');
+                out.origin.push(space);
+            }
+            out.chars.push(c);
+            out.origin.push(o);
+        }
+        out
+    }
+
+    fn strip_ordinals(self) -> Self {
+        let chars = &self.chars;
+        let mut out = Self {
+            chars: Vec::with_capacity(chars.len()),
+            origin: Vec::with_capacity(chars.len()),
+        };
+        let mut i = 0;
+        while i < chars.len() {
+            out.chars.push(chars[i]);
+            out.origin.push(self.origin[i]);
+            if chars[i].is_ascii_digit() && i + 2 < chars.len() {
+                let suffix: String = chars[i + 1..i + 3].iter().collect();
+                let boundary = chars.get(i + 3).is_none_or(|c| !c.is_alphanumeric());
+                if matches!(suffix.as_str(), "st" | "nd" | "rd" | "th") && boundary {
+                    i += 3;
+                    continue;
+                }
+            }
+            i += 1;
+        }
+        out
+    }
+
+    /// All occurrences of `needle` (already normalized), as source spans.
+    /// With `token`, matches must not be surrounded by alphanumeric characters.
+    fn find(&self, needle: &str, token: bool) -> Vec<SourceSpan> {
+        let needle: Vec<char> = needle.chars().collect();
+        if needle.is_empty() || needle.len() > self.chars.len() {
+            return Vec::new();
+        }
+
+        let mut spans = Vec::new();
+        for start in 0..=self.chars.len() - needle.len() {
+            let end = start + needle.len();
+            if self.chars[start..end] != needle[..] {
+                continue;
+            }
+            if token {
+                let before_ok = start == 0 || !self.chars[start - 1].is_alphanumeric();
+                let after_ok = self.chars.get(end).is_none_or(|c| !c.is_alphanumeric());
+                if !before_ok || !after_ok {
+                    continue;
                 }
             }
+            let span = SourceSpan {
+                start: self.origin[start],
+                end: self.origin[end - 1] + 1,
+            };
+            if spans.last() != Some(&span) {
+                spans.push(span);
+            }
+        }
+        spans
+    }
+}
+
+/// Strip scheme, `www.` and trailing slashes from a lowercased URL
+fn normalize_url(url: &str) -> String {
+    let url = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .unwrap_or(url);
+    let url = url.strip_prefix("www.").unwrap_or(url);
+    url.trim_end_matches('/').to_string()
+}
+
+/// Grow a span over a `www.` and/or scheme prefix directly before it in the source
+fn extend_url_prefix(source: &[char], mut span: SourceSpan) -> SourceSpan {
+    for prefix in ["www.", "https://", "http://"] {
+        let len = prefix.chars().count();
+        if span.start >= len {
+            let before: String = source[span.start - len..span.start].iter().collect();
+            if before.eq_ignore_ascii_case(prefix) {
+                span.start -= len;
+            }
+        }
+    }
+    span
+}
+
+const MONTHS: [&str; 12] = [
+    "january",
+    "february",
+    "march",
+    "april",
+    "may",
+    "june",
+    "july",
+    "august",
+    "september",
+    "october",
+    "november",
+    "december",
+];
+
+/// Parse `YYYY-MM-DD` or `YYYY-MM` into (year, month, day)
+fn parse_iso_date(s: &str) -> Option<(u32, u32, Option<u32>)> {
+    let parts: Vec<&str> = s.split('-').collect();
+    if parts.len() < 2 || parts.len() > 3 || parts[0].len() != 4 {
+        return None;
+    }
+    let year = parts[0].parse().ok()?;
+    let month: u32 = parts[1].parse().ok()?;
+    if !(1..=12).contains(&month) {
+        return None;
+    }
+    let day = match parts.get(2) {
+        Some(d) => {
+            let day: u32 = d.parse().ok()?;
+            if !(1..=31).contains(&day) {
+                return None;
+            }
+            Some(day)
         }
+        None => None,
+    };
+    Some((year, month, day))
+}
+
+/// Common written forms of a date, for matching against source text
+fn date_variants((year, month, day): (u32, u32, Option<u32>)) -> Vec<String> {
+    let full = MONTHS[month as usize - 1];
+    let mut names = vec![full.to_string(), full[..3].to_string()];
+    if month == 9 {
+        names.push("sept".to_string());
     }
 
-    Ok(())
+    let mut variants = Vec::new();
+    match day {
+        Some(day) => {
+            for name in &names {
+                variants.push(format!("{} {} {}", name, day, year));
+                variants.push(format!("{} {} {}", day, name, year));
+            }
+            for sep in ['/', '-', '.'] {
+                for (m, d) in [
+                    (format!("{:02}", month), format!("{:02}", day)),
+                    (month.to_string(), day.to_string()),
+                ] {
+                    variants.push(format!("{m}{sep}{d}{sep}{year}"));
+                    variants.push(format!("{d}{sep}{m}{sep}{year}"));
+                }
+                variants.push(format!("{year}{sep}{:02}{sep}{:02}", month, day));
+            }
+        }
+        None => {
+            for name in &names {
+                variants.push(format!("{} {}", name, year));
+            }
+            variants.push(format!("{:02}/{}", month, year));
+            variants.push(format!("{}-{:02}", year, month));
+        }
+    }
+    variants
 }
 
 /// Parse skill output from LLM response
@@ -377,28 +1068,32 @@ pub fn validate_extraction_output(
 /// Expects JSON output. Returns error if output is not valid JSON
 /// or doesn't match expected schema.
 pub fn parse_skill_output(output: &str, target: ExtractionTarget) -> SkillResult<ExtractionOutput> {
-    let trimmed = output.trim();
+    let output = parse_skill_json(output)?;
+
+    // Check target field exists
+    if !output.has_target_field(target) {
+        return Err(SkillError::SchemaViolation(format!(
+            "output missing '{}' field",
+            target.as_str()
+        )));
+    }
+
+    Ok(output)
+}
 
-    // Try to parse as JSON
-    let value: Value = serde_json::from_str(trimmed)
+/// Parse skill output as a JSON object, leaving its checks to the skill's
+/// guardrails (see [`crate::skill_guardrail`])
+pub fn parse_skill_json(output: &str) -> SkillResult<ExtractionOutput> {
+    let value: Value = serde_json::from_str(output.trim())
         .map_err(|e| SkillError::MalformedOutput(format!("invalid JSON: {}", e)))?;
 
-    // Check it's an object
     if !value.is_object() {
         return Err(SkillError::MalformedOutput(
             "output must be a JSON object".to_string(),
         ));
     }
 
-    // Check target field exists
-    if value.get(target.as_str()).is_none() {
-        return Err(SkillError::SchemaViolation(format!(
-            "output missing '{}' field",
-            target.as_str()
-        )));
-    }
-
-    Ok(ExtractionOutput { result: value })
+    Ok(ExtractionOutput::new(value))
 }
 
 /// Apply a skill result to the agent state
@@ -448,6 +1143,105 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_entity_classes_validation() {
+        let valid = ExtractionInput::new("Ada met Bob", "entity").with_classes(["people"]);
+        assert_eq!(valid.validate(), Ok(ExtractionTarget::Entity));
+        assert_eq!(valid.entity_classes(), vec![EntityClass::People]);
+
+        let unknown = ExtractionInput::new("text", "entity").with_classes(["planets"]);
+        assert_eq!(
+            unknown.validate(),
+            Err(SkillError::InvalidClass("planets".to_string()))
+        );
+
+        let wrong_target = ExtractionInput::new("text", "email").with_classes(["people"]);
+        assert!(matches!(
+            wrong_target.validate(),
+            Err(SkillError::SchemaViolation(_))
+        ));
+
+        let all = ExtractionInput::new("text", "entity");
+        assert_eq!(all.entity_classes(), EntityClass::ALL.to_vec());
+    }
+
+    #[test]
+    fn test_entity_output_rejects_unrequested_class() {
+        let input = ExtractionInput::new("Ada Lovelace visited London", "entity")
+            .with_classes(["people", "locations"]);
+
+        let output = ExtractionOutput::new(serde_json::json!({
+            "entity": {"people": ["Ada Lovelace"], "locations": ["London"]}
+        }));
+        assert!(validate_extraction_output(&input, &output, ExtractionTarget::Entity).is_ok());
+
+        let extra = ExtractionOutput::entities(
+            vec!["Ada Lovelace".to_string()],
+            vec![],
+            vec!["London".to_string()],
+        );
+        assert!(matches!(
+            validate_extraction_output(&input, &extra, ExtractionTarget::Entity),
+            Err(SkillError::SchemaViolation(_))
+        ));
+    }
+
+    #[test]
+    fn test_fields_input_validation() {
+        let valid = ExtractionInput::new("Invoice INV-42", "fields")
+            .with_schema([("invoice_number", "the invoice id")]);
+        assert_eq!(valid.validate(), Ok(ExtractionTarget::Fields));
+
+        let missing = ExtractionInput::new("Invoice INV-42", "fields");
+        assert!(matches!(
+            missing.validate(),
+            Err(SkillError::SchemaViolation(_))
+        ));
+
+        let wrong_target =
+            ExtractionInput::new("text", "email").with_schema([("id", "an identifier")]);
+        assert!(matches!(
+            wrong_target.validate(),
+            Err(SkillError::SchemaViolation(_))
+        ));
+    }
+
+    #[test]
+    fn test_fields_output_validation() {
+        let input = ExtractionInput::new("Invoice INV-42\nTotal:  $1,200.00", "fields")
+            .with_schema([
+                ("invoice_number", "the invoice id"),
+                ("total_amount", "the amount due"),
+                ("due_date", "when payment is due"),
+            ]);
+
+        let output = ExtractionOutput::fields(BTreeMap::from([
+            ("invoice_number".to_string(), Some("INV-42".to_string())),
+            ("total_amount".to_string(), Some("$1,200.00".to_string())),
+            ("due_date".to_string(), None),
+        ]));
+        assert!(validate_extraction_output(&input, &output, ExtractionTarget::Fields).is_ok());
+
+        let missing_key = ExtractionOutput::fields(BTreeMap::from([(
+            "invoice_number".to_string(),
+            Some("INV-42".to_string()),
+        )]));
+        assert!(matches!(
+            validate_extraction_output(&input, &missing_key, ExtractionTarget::Fields),
+            Err(SkillError::SchemaViolation(_))
+        ));
+
+        let invented = ExtractionOutput::fields(BTreeMap::from([
+            ("invoice_number".to_string(), Some("INV-99".to_string())),
+            ("total_amount".to_string(), None),
+            ("due_date".to_string(), None),
+        ]));
+        assert_eq!(
+            validate_extraction_output(&input, &invented, ExtractionTarget::Fields),
+            Err(SkillError::HallucinationDetected("INV-99".to_string()))
+        );
+    }
+
     #[test]
     fn test_output_construction() {
         let emails = ExtractionOutput::emails(vec!["a@b.com".to_string()]);
@@ -473,6 +1267,128 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_normalized_date_matching() {
+        let input = ExtractionInput::new("The launch is on March 5th, 2024 in Berlin", "date");
+        let output = ExtractionOutput::dates(vec!["2024-03-05".to_string()]);
+        assert!(validate_extraction_output(&input, &output, ExtractionTarget::Date).is_ok());
+
+        let numeric = ExtractionInput::new("Due 05/03/2024.", "date");
+        assert!(validate_extraction_output(&numeric, &output, ExtractionTarget::Date).is_ok());
+
+        let wrong_year = ExtractionInput::new("The launch is on March 5, 2023", "date");
+        assert!(matches!(
+            validate_extraction_output(&wrong_year, &output, ExtractionTarget::Date),
+            Err(SkillError::HallucinationDetected(_))
+        ));
+    }
+
+    #[test]
+    fn test_normalized_url_and_email_matching() {
+        let input = ExtractionInput::new(
+            "Docs live at www.agent.rs/docs/ and MAIL Hello@Agent.rs",
+            "url",
+        );
+        let urls = ExtractionOutput::urls(vec!["https://agent.rs/docs".to_string()]);
+        assert!(validate_extraction_output(&input, &urls, ExtractionTarget::Url).is_ok());
+
+        let emails = ExtractionOutput::emails(vec!["mailto:hello@agent.rs".to_string()]);
+        assert!(validate_extraction_output(&input, &emails, ExtractionTarget::Email).is_ok());
+
+        let other = ExtractionOutput::urls(vec!["https://agent.rs/blog".to_string()]);
+        assert!(matches!(
+            validate_extraction_output(&input, &other, ExtractionTarget::Url),
+            Err(SkillError::HallucinationDetected(_))
+        ));
+    }
+
+    #[test]
+    fn test_validator_returns_char_spans() {
+        let input = ExtractionInput::new(
+            "Café: hi@café.fr, see https://www.agent.rs/ or HI@café.fr",
+            "email",
+        );
+        let output = ExtractionOutput::emails(vec!["hi@café.fr".to_string()]);
+        let spans = validate_extraction_output(&input, &output, ExtractionTarget::Email).unwrap();
+        assert_eq!(
+            spans[0].spans,
+            vec![
+                SourceSpan { start: 6, end: 16 },
+                SourceSpan { start: 47, end: 57 }
+            ]
+        );
+
+        let urls = ExtractionOutput::urls(vec!["https://agent.rs".to_string()]);
+        let spans = validate_extraction_output(&input, &urls, ExtractionTarget::Url).unwrap();
+        assert_eq!(spans[0].spans, vec![SourceSpan { start: 22, end: 42 }]);
+
+        let dated = ExtractionInput::new("Due on March 5th,  2024.", "date");
+        let dates = ExtractionOutput::dates(vec!["2024-03-05".to_string()]);
+        let spans = validate_extraction_output(&dated, &dates, ExtractionTarget::Date).unwrap();
+        assert_eq!(spans[0].spans, vec![SourceSpan { start: 7, end: 23 }]);
+    }
+
+    #[test]
+    fn test_result_keys_do_not_collide_with_spans() {
+        let input = ExtractionInput::new("Mail ada@example.com", "email");
+        let output =
+            parse_skill_json(r#"{"email": ["ada@example.com"], "spans": "model text"}"#).unwrap();
+        let spans = validate_extraction_output(&input, &output, ExtractionTarget::Email).unwrap();
+        let value = output.with_spans(spans).to_value();
+        assert_eq!(value["result"]["spans"], "model text");
+        assert_eq!(value["spans"][0]["value"], "ada@example.com");
+    }
+
+    #[test]
+    fn test_normalize_extraction_output() {
+        let input = ExtractionInput::new(
+            "Mail ada@example.com or ADA@example.com; see HTTPS://Agent.RS/Docs and agent.rs/Docs/",
+            "email",
+        );
+        let emails = ExtractionOutput::emails(vec![
+            "ada@example.com".to_string(),
+            "ADA@example.com".to_string(),
+        ]);
+        let spans = validate_extraction_output(&input, &emails, ExtractionTarget::Email).unwrap();
+        let emails = (EXTRACTION_SKILL.post_process.unwrap())(
+            emails.with_spans(spans),
+            ExtractionTarget::Email,
+        );
+        assert_eq!(
+            emails.result["email"],
+            serde_json::json!(["ada@example.com"])
+        );
+        assert_eq!(emails.spans.len(), 1);
+        assert_eq!(emails.spans[0].spans.len(), 2);
+        assert_eq!(emails.to_value()["result"]["email"], emails.result["email"]);
+        assert_eq!(emails.to_value()["spans"][0]["value"], "ada@example.com");
+
+        let urls = normalize_extraction_output(
+            ExtractionOutput::urls(vec![
+                "HTTPS://Agent.RS/Docs".to_string(),
+                "agent.rs/Docs/".to_string(),
+            ]),
+            ExtractionTarget::Url,
+        );
+        assert_eq!(
+            urls.result["url"],
+            serde_json::json!(["https://agent.rs/Docs"])
+        );
+
+        let dates = normalize_extraction_output(
+            ExtractionOutput::dates(vec![
+                "2024-03-01".to_string(),
+                "2023-12-24".to_string(),
+                "2024-03-01".to_string(),
+            ]),
+            ExtractionTarget::Date,
+        );
+        assert_eq!(
+            dates.result["date"],
+            serde_json::json!(["2023-12-24", "2024-03-01"])
+        );
+    }
+
     #[test]
     fn test_parse_skill_output() {
         let json = r#"{"email": ["test@example.com"]}"#;
@@ -502,6 +1418,21 @@ mod tests {
         let input = req.parse_extraction_input().unwrap();
         assert_eq!(input.text, "hello@test.com");
         assert_eq!(input.target, "email");
+        assert!(input.classes.is_none());
+
+        let req = SkillRequest::new(
+            "extract",
+            serde_json::json!({
+                "text": "Ada visited Paris",
+                "target": "entity",
+                "classes": ["people", "locations"]
+            }),
+        );
+        let input = req.parse_extraction_input().unwrap();
+        assert_eq!(
+            input.entity_classes(),
+            vec![EntityClass::People, EntityClass::Locations]
+        );
     }
 
     #[test]
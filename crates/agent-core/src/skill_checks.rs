@@ -0,0 +1,276 @@
+//! Output checks a skill declares in its SKILL.md
+//!
+//! Skill authors get field-level checks without writing a [`SkillGuardrail`]:
+//!
+//! ```yaml
+//! checks:
+//!   required: [invoice_number, total]
+//!   patterns:
+//!     invoice_number: "^INV-[0-9]+$"
+//!   values_in_source: true
+//!   max_items: 20
+//! ```
+//!
+//! Fields are looked up in the object the skill's target holds (`fields` or
+//! `entity`), or in the output itself for list targets (`email`). A pattern
+//! must match the whole of every value of its field. Hosts compile the checks
+//! ([`SkillChecks::compile`]) into a guard they append to the skill's chain.
+
+use crate::prelude::*;
+#[cfg(feature = "std")]
+use crate::skill::{ExtractionOutput, GroundingSource, SkillError, SkillResult};
+#[cfg(feature = "std")]
+use crate::skill_guardrail::{SkillGuardContext, SkillGuardrail};
+use alloc::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "std")]
+use serde_json::Value;
+#[cfg(feature = "std")]
+use thiserror::Error;
+
+/// Checks declared under `checks` in SKILL.md frontmatter
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SkillChecks {
+    /// Fields that must be present and not empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub required: Vec<String>,
+    /// Regular expression per field that each of its values must match
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub patterns: BTreeMap<String, String>,
+    /// Every extracted value must appear in the input text
+    #[serde(default)]
+    pub values_in_source: bool,
+    /// Most items any list in the output may hold
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_items: Option<usize>,
+}
+
+/// A declared pattern that is not a valid regular expression
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("invalid pattern for '{field}': {message}")]
+pub struct InvalidSkillCheck {
+    pub field: String,
+    pub message: String,
+}
+
+#[cfg(feature = "std")]
+impl SkillChecks {
+    /// The guard enforcing these checks
+    pub fn compile(&self) -> Result<DeclaredChecks, InvalidSkillCheck> {
+        let patterns = self
+            .patterns
+            .iter()
+            .map(|(field, pattern)| {
+                // Checked alone so errors point into the pattern as written,
+                // then anchored so it cannot pass on a substring
+                regex::Regex::new(pattern)
+                    .and_then(|_| regex::Regex::new(&format!("^(?:{})$", pattern)))
+                    .map(|regex| (field.clone(), regex))
+                    .map_err(|err| InvalidSkillCheck {
+                        field: field.clone(),
+                        message: err.to_string(),
+                    })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(DeclaredChecks {
+            required: self.required.clone(),
+            patterns,
+            values_in_source: self.values_in_source,
+            max_items: self.max_items,
+        })
+    }
+}
+
+/// Compiled [`SkillChecks`] (`checks`)
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+pub struct DeclaredChecks {
+    required: Vec<String>,
+    patterns: Vec<(String, regex::Regex)>,
+    values_in_source: bool,
+    max_items: Option<usize>,
+}
+
+#[cfg(feature = "std")]
+impl SkillGuardrail for DeclaredChecks {
+    fn name(&self) -> &str {
+        "checks"
+    }
+
+    fn check(&self, context: &SkillGuardContext, output: &mut ExtractionOutput) -> SkillResult<()> {
+        let target = output.result.get(context.target.as_str());
+        let field = |name: &str| match target {
+            Some(Value::Object(fields)) => fields.get(name),
+            _ => output.result.get(name),
+        };
+
+        for name in &self.required {
+            if field(name).is_none_or(is_empty) {
+                return Err(SkillError::SchemaViolation(format!(
+                    "required field '{}' is missing or empty",
+                    name
+                )));
+            }
+        }
+
+        for (name, regex) in &self.patterns {
+            for value in field(name).map(leaf_values).unwrap_or_default() {
+                if !regex.is_match(&value) {
+                    return Err(SkillError::SchemaViolation(format!(
+                        "'{}' in field '{}' does not match the declared pattern",
+                        value, name
+                    )));
+                }
+            }
+        }
+
+        let Some(target) = target else {
+            return Ok(());
+        };
+        if let Some(max) = self.max_items {
+            if let Some(len) = longest_list(target).filter(|len| *len > max) {
+                return Err(SkillError::SchemaViolation(format!(
+                    "{} items in a list, more than the {} allowed",
+                    len, max
+                )));
+            }
+        }
+        if self.values_in_source {
+            let source = GroundingSource::new(context.target, &context.input.text);
+            for value in leaf_values(target) {
+                if source.locate(&value).is_empty() {
+                    return Err(SkillError::HallucinationDetected(value));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Null, blank strings and empty lists or objects
+#[cfg(feature = "std")]
+fn is_empty(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::String(s) => s.trim().is_empty(),
+        Value::Array(items) => items.is_empty(),
+        Value::Object(fields) => fields.is_empty(),
+        Value::Bool(_) | Value::Number(_) => false,
+    }
+}
+
+/// The non-blank strings, numbers and booleans in `value`, as text
+#[cfg(feature = "std")]
+fn leaf_values(value: &Value) -> Vec<String> {
+    match value {
+        Value::String(s) if s.trim().is_empty() => vec![],
+        Value::String(s) => vec![s.clone()],
+        Value::Number(n) => vec![n.to_string()],
+        Value::Bool(b) => vec![b.to_string()],
+        Value::Null => vec![],
+        Value::Array(items) => items.iter().flat_map(leaf_values).collect(),
+        Value::Object(fields) => fields.values().flat_map(leaf_values).collect(),
+    }
+}
+
+/// Length of the longest list in `value`
+#[cfg(feature = "std")]
+fn longest_list(value: &Value) -> Option<usize> {
+    match value {
+        Value::Array(items) => items
+            .iter()
+            .filter_map(longest_list)
+            .chain([items.len()])
+            .max(),
+        Value::Object(fields) => fields.values().filter_map(longest_list).max(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::skill::{parse_skill_json, ExtractionInput, ExtractionTarget};
+
+    fn checks(yaml: &str) -> DeclaredChecks {
+        serde_yaml::from_str::<SkillChecks>(yaml)
+            .unwrap()
+            .compile()
+            .unwrap()
+    }
+
+    fn run(
+        guard: &DeclaredChecks,
+        input: &ExtractionInput,
+        target: ExtractionTarget,
+        json: &str,
+    ) -> SkillResult<()> {
+        let context = SkillGuardContext {
+            skill: "invoice",
+            input,
+            target,
+        };
+        guard.check(&context, &mut parse_skill_json(json).unwrap())
+    }
+
+    #[test]
+    fn test_field_checks() {
+        let input = ExtractionInput::new("Invoice INV-042, total 120.50 EUR", "fields");
+        let guard = checks(
+            "required: [invoice_number, total]\npatterns:\n  invoice_number: \"INV-[0-9]+\"\nvalues_in_source: true\n",
+        );
+        let fields = ExtractionTarget::Fields;
+
+        assert!(run(
+            &guard,
+            &input,
+            fields,
+            r#"{"fields": {"invoice_number": "INV-042", "total": 120.50}}"#
+        )
+        .is_ok());
+        assert!(matches!(
+            run(&guard, &input, fields, r#"{"fields": {"invoice_number": "INV-042", "total": null}}"#),
+            Err(SkillError::SchemaViolation(msg)) if msg.contains("'total'")
+        ));
+        // Patterns match whole values
+        assert!(matches!(
+            run(&guard, &input, fields, r#"{"fields": {"invoice_number": "INV-042 (EUR)", "total": "120.50"}}"#),
+            Err(SkillError::SchemaViolation(msg)) if msg.contains("declared pattern")
+        ));
+        assert_eq!(
+            run(
+                &guard,
+                &input,
+                fields,
+                r#"{"fields": {"invoice_number": "INV-042", "total": "99.00"}}"#
+            ),
+            Err(SkillError::HallucinationDetected("99.00".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_list_checks() {
+        let input = ExtractionInput::new("a@x.io, b@x.io, c@y.io", "email");
+        let guard = checks("patterns:\n  email: \"[^@]+@x\\\\.io\"\nmax_items: 2\n");
+        let email = ExtractionTarget::Email;
+
+        assert!(run(&guard, &input, email, r#"{"email": ["a@x.io", "b@x.io"]}"#).is_ok());
+        assert!(run(&guard, &input, email, r#"{"email": ["a@x.io", "c@y.io"]}"#).is_err());
+        assert!(matches!(
+            run(&guard, &input, email, r#"{"email": ["a@x.io", "b@x.io", "a@x.io"]}"#),
+            Err(SkillError::SchemaViolation(msg)) if msg.contains("3 items")
+        ));
+    }
+
+    #[test]
+    fn test_invalid_checks() {
+        let bad = SkillChecks {
+            patterns: BTreeMap::from([("total".to_string(), "[0-9".to_string())]),
+            ..SkillChecks::default()
+        };
+        assert_eq!(bad.compile().unwrap_err().field, "total");
+        assert!(serde_yaml::from_str::<SkillChecks>("max_item: 3\n").is_err());
+    }
+}
@@ -0,0 +1,192 @@
+//! Guardrails on skill outputs, declared by each skill
+//!
+//! A skill names the checks its output must pass in its SKILL.md, and hosts run
+//! them through one chain instead of coding them into the skill's runner:
+//!
+//! ```yaml
+//! metadata:
+//!   guardrails: ["json-schema", "anti-hallucination"]
+//! ```
+//!
+//! Built-in checks are looked up by name ([`SkillGuardrailChain::from_names`]);
+//! hosts add their own with [`SkillGuardrailChain::add`]. A skill that declares
+//! none gets [`SkillGuardrailChain::extraction`].
+
+use crate::prelude::*;
+use crate::skill::{
+    validate_extraction_output, ExtractionInput, ExtractionOutput, ExtractionTarget, SkillError,
+    SkillResult,
+};
+use alloc::sync::Arc;
+use thiserror::Error;
+
+/// What a skill guardrail sees of the call
+pub struct SkillGuardContext<'a> {
+    /// The skill that produced the output
+    pub skill: &'a str,
+    pub input: &'a ExtractionInput,
+    pub target: ExtractionTarget,
+}
+
+/// A check on a skill's output
+///
+/// A guard may annotate the output it accepts (the anti-hallucination check
+/// attaches the source spans it found).
+pub trait SkillGuardrail: Send + Sync {
+    /// The name skills declare the guard by
+    fn name(&self) -> &str;
+
+    fn check(&self, context: &SkillGuardContext, output: &mut ExtractionOutput) -> SkillResult<()>;
+}
+
+/// The output has the field its target names (`json-schema`)
+pub struct SchemaGuard;
+
+impl SkillGuardrail for SchemaGuard {
+    fn name(&self) -> &str {
+        "json-schema"
+    }
+
+    fn check(&self, context: &SkillGuardContext, output: &mut ExtractionOutput) -> SkillResult<()> {
+        if output.has_target_field(context.target) {
+            Ok(())
+        } else {
+            Err(SkillError::SchemaViolation(format!(
+                "output missing '{}' field",
+                context.target.as_str()
+            )))
+        }
+    }
+}
+
+/// Every extracted value appears in the input text (`anti-hallucination`)
+pub struct HallucinationGuard;
+
+impl SkillGuardrail for HallucinationGuard {
+    fn name(&self) -> &str {
+        "anti-hallucination"
+    }
+
+    fn check(&self, context: &SkillGuardContext, output: &mut ExtractionOutput) -> SkillResult<()> {
+        output.spans = validate_extraction_output(context.input, output, context.target)?;
+        Ok(())
+    }
+}
+
+/// A skill declared a guardrail this build does not know
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("unknown skill guardrail '{0}' (known: json-schema, anti-hallucination)")]
+pub struct UnknownSkillGuard(pub String);
+
+/// The guardrails a skill's outputs pass through, in order
+#[derive(Clone, Default)]
+pub struct SkillGuardrailChain {
+    guards: Vec<Arc<dyn SkillGuardrail>>,
+}
+
+impl SkillGuardrailChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The checks of the built-in extraction skill
+    pub fn extraction() -> Self {
+        Self::new()
+            .add(Box::new(SchemaGuard))
+            .add(Box::new(HallucinationGuard))
+    }
+
+    /// A chain of built-in guards, by the names skills declare them with
+    pub fn from_names<S: AsRef<str>>(names: &[S]) -> Result<Self, UnknownSkillGuard> {
+        names.iter().try_fold(Self::new(), |chain, name| {
+            let guard: Box<dyn SkillGuardrail> = match name.as_ref() {
+                "json-schema" => Box::new(SchemaGuard),
+                "anti-hallucination" => Box::new(HallucinationGuard),
+                other => return Err(UnknownSkillGuard(other.to_string())),
+            };
+            Ok(chain.add(guard))
+        })
+    }
+
+    pub fn add(mut self, guard: Box<dyn SkillGuardrail>) -> Self {
+        self.guards.push(Arc::from(guard));
+        self
+    }
+
+    /// Names of the guards, in order
+    pub fn names(&self) -> Vec<&str> {
+        self.guards.iter().map(|guard| guard.name()).collect()
+    }
+
+    /// Run every guard on `output`; the first rejection fails the output
+    pub fn check(
+        &self,
+        context: &SkillGuardContext,
+        mut output: ExtractionOutput,
+    ) -> SkillResult<ExtractionOutput> {
+        for guard in &self.guards {
+            guard.check(context, &mut output)?;
+        }
+        Ok(output)
+    }
+}
+
+impl core::fmt::Debug for SkillGuardrailChain {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("SkillGuardrailChain")
+            .field(&self.names())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::skill::parse_skill_json;
+
+    fn context(input: &ExtractionInput) -> SkillGuardContext<'_> {
+        SkillGuardContext {
+            skill: "extract",
+            input,
+            target: ExtractionTarget::Email,
+        }
+    }
+
+    #[test]
+    fn test_extraction_chain() {
+        let input = ExtractionInput::new("Write to ada@example.com", "email");
+        let chain = SkillGuardrailChain::extraction();
+        assert_eq!(chain.names(), vec!["json-schema", "anti-hallucination"]);
+
+        let output = parse_skill_json(r#"{"email": ["ada@example.com"]}"#).unwrap();
+        let checked = chain.check(&context(&input), output).unwrap();
+        assert_eq!(checked.spans.len(), 1);
+
+        let invented = parse_skill_json(r#"{"email": ["bob@example.com"]}"#).unwrap();
+        assert!(matches!(
+            chain.check(&context(&input), invented),
+            Err(SkillError::HallucinationDetected(_))
+        ));
+    }
+
+    #[test]
+    fn test_declared_chain() {
+        let input = ExtractionInput::new("Write to ada@example.com", "email");
+        let schema_only = SkillGuardrailChain::from_names(&["json-schema"]).unwrap();
+        // Without the hallucination check an invented value passes
+        let invented = parse_skill_json(r#"{"email": ["bob@example.com"]}"#).unwrap();
+        assert!(schema_only.check(&context(&input), invented).is_ok());
+        assert!(matches!(
+            schema_only.check(
+                &context(&input),
+                parse_skill_json(r#"{"url": []}"#).unwrap()
+            ),
+            Err(SkillError::SchemaViolation(_))
+        ));
+
+        assert_eq!(
+            SkillGuardrailChain::from_names(&["json-schema", "spellcheck"]).unwrap_err(),
+            UnknownSkillGuard("spellcheck".to_string())
+        );
+    }
+}
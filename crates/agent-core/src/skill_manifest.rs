@@ -3,6 +3,11 @@
 //! This module parses SKILL.md frontmatter (YAML) into a typed struct so hosts
 //! can implement progressive disclosure and discovery.
 
+use crate::few_shot::FewShotExample;
+use crate::prelude::*;
+use crate::prompt_template::{lookup, PromptTemplate, TemplateError};
+use crate::skill_checks::SkillChecks;
+use alloc::collections::BTreeMap;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
@@ -19,6 +24,81 @@ pub struct SkillFrontmatter {
     pub metadata: Option<Value>,
     #[serde(default, rename = "allowed-tools")]
     pub allowed_tools: Option<String>,
+    /// Auxiliary files shipped with the skill, as name -> path relative to the
+    /// skill directory (e.g. `examples: examples/few-shot.md`)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub assets: BTreeMap<String, String>,
+    /// Example invocations with their results (see [`crate::few_shot`])
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub examples: Vec<FewShotExample>,
+    /// Checks every output must pass (see [`crate::skill_checks`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checks: Option<SkillChecks>,
+}
+
+impl SkillFrontmatter {
+    /// Skill version declared as `metadata.version`, if any
+    pub fn version(&self) -> Option<&str> {
+        self.metadata.as_ref()?.get("version")?.as_str()
+    }
+
+    /// Guardrails declared as `metadata.guardrails` (see
+    /// [`crate::skill_guardrail`]), if any
+    pub fn guardrails(&self) -> Option<Vec<&str>> {
+        let names = self.metadata.as_ref()?.get("guardrails")?.as_array()?;
+        Some(names.iter().filter_map(|name| name.as_str()).collect())
+    }
+}
+
+/// Top-level variables hosts provide when rendering a skill prompt template
+pub const TEMPLATE_CONTEXT: &[&str] = &[
+    "input",
+    "target",
+    "target_desc",
+    "output_format",
+    "no_match",
+    "assets",
+];
+
+/// Fields of `input` present in every rendering context (`{{input.text}}`)
+pub const TEMPLATE_INPUT_FIELDS: &[&str] = &["text", "target"];
+
+/// Maximum length of a skill name (Agent Skills spec)
+pub const MAX_NAME_LEN: usize = 64;
+
+/// Maximum length of a skill description (Agent Skills spec)
+pub const MAX_DESCRIPTION_LEN: usize = 1024;
+
+/// Severity of a manifest lint finding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LintLevel {
+    /// The skill will not work as declared
+    Error,
+    /// The skill works but deviates from the spec or conventions
+    Warning,
+}
+
+/// A single finding from [`SkillManifest::lint`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestLint {
+    pub level: LintLevel,
+    pub message: String,
+}
+
+impl ManifestLint {
+    fn error(message: impl Into<String>) -> Self {
+        Self {
+            level: LintLevel::Error,
+            message: message.into(),
+        }
+    }
+
+    fn warning(message: impl Into<String>) -> Self {
+        Self {
+            level: LintLevel::Warning,
+            message: message.into(),
+        }
+    }
 }
 
 /// Full manifest with body content
@@ -29,6 +109,161 @@ pub struct SkillManifest {
     pub body: String,
 }
 
+impl SkillManifest {
+    /// The raw prompt template declared in the body, if any
+    ///
+    /// Templates are declared as a fenced code block with the `prompt` info string:
+    ///
+    /// ````markdown
+    /// ```prompt
+    /// Extract {{target}} from: {{input.text}}
+    /// ```
+    /// ````
+    pub fn prompt_template_source(&self) -> Option<String> {
+        let mut lines = self.body.lines();
+        lines.find(|line| line.trim() == "```prompt")?;
+
+        let mut template = Vec::new();
+        for line in lines {
+            if line.trim() == "```" {
+                return Some(template.join("\n"));
+            }
+            template.push(line);
+        }
+        None
+    }
+
+    /// The parsed prompt template declared in the body, if any
+    pub fn prompt_template(&self) -> Option<Result<PromptTemplate, TemplateError>> {
+        self.prompt_template_source()
+            .map(|source| PromptTemplate::parse(&source))
+    }
+
+    /// Check the manifest against the frontmatter schema and its template variables
+    ///
+    /// Parsing already guarantees the required fields are present; linting covers
+    /// what a host would otherwise only discover when executing the skill.
+    pub fn lint(&self) -> Vec<ManifestLint> {
+        let mut lints = Vec::new();
+        let fm = &self.frontmatter;
+
+        if !is_valid_skill_name(&fm.name) {
+            lints.push(ManifestLint::error(format!(
+                "name '{}' must be 1-{} lowercase letters, digits or single hyphens",
+                fm.name, MAX_NAME_LEN
+            )));
+        }
+
+        if fm.description.trim().is_empty() {
+            lints.push(ManifestLint::error("description is empty"));
+        } else if fm.description.chars().count() > MAX_DESCRIPTION_LEN {
+            lints.push(ManifestLint::warning(format!(
+                "description is longer than {} characters",
+                MAX_DESCRIPTION_LEN
+            )));
+        }
+
+        if fm.version().is_none() {
+            lints.push(ManifestLint::warning("metadata.version is not set"));
+        }
+
+        for (index, example) in fm.examples.iter().enumerate() {
+            if example.call.get("skill").and_then(Value::as_str) != Some(fm.name.as_str()) {
+                lints.push(ManifestLint::error(format!(
+                    "example {}: call must be an object with \"skill\": \"{}\"",
+                    index + 1,
+                    fm.name
+                )));
+            }
+        }
+
+        match self.prompt_template() {
+            Some(Ok(template)) => {
+                for name in template.variables() {
+                    lints.extend(lint_template_variable(name, &fm.assets));
+                }
+            }
+            Some(Err(err)) => {
+                lints.push(ManifestLint::error(format!("prompt template: {}", err)));
+            }
+            None => {}
+        }
+
+        #[cfg(feature = "std")]
+        if let Some(Err(err)) = fm.checks.as_ref().map(SkillChecks::compile) {
+            lints.push(ManifestLint::error(format!("checks: {}", err)));
+        }
+
+        lints
+    }
+}
+
+/// Whether a name follows the Agent Skills naming rules
+fn is_valid_skill_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        && !name.starts_with('-')
+        && !name.ends_with('-')
+        && !name.contains("--")
+}
+
+fn lint_template_variable(name: &str, assets: &BTreeMap<String, String>) -> Option<ManifestLint> {
+    let mut parts = name.split('.');
+    let root = parts.next().unwrap_or_default();
+
+    if !TEMPLATE_CONTEXT.contains(&root) {
+        return Some(ManifestLint::error(format!(
+            "prompt template: unknown variable '{}' (expected one of: {})",
+            name,
+            TEMPLATE_CONTEXT.join(", ")
+        )));
+    }
+
+    if root != "assets" {
+        // Resolve the path as rendering would, against the context's shape
+        let shape = template_context_shape();
+        return lookup(&shape, name).is_none().then(|| {
+            ManifestLint::error(format!(
+                "prompt template: '{}' does not resolve (input fields: {})",
+                name,
+                TEMPLATE_INPUT_FIELDS.join(", ")
+            ))
+        });
+    }
+
+    match parts.next() {
+        Some(asset) if !assets.contains_key(asset) => Some(ManifestLint::error(format!(
+            "prompt template: asset '{}' is not declared under `assets`",
+            asset
+        ))),
+        Some(_) if parts.next().is_some() => Some(ManifestLint::error(format!(
+            "prompt template: '{}' does not resolve (assets are text)",
+            name
+        ))),
+        Some(_) => None,
+        None => Some(ManifestLint::error(
+            "prompt template: use '{{assets.<name>}}' to reference an asset",
+        )),
+    }
+}
+
+/// The template context with every value a host always provides, as empty text
+fn template_context_shape() -> Value {
+    let mut context = serde_json::Map::new();
+    for root in TEMPLATE_CONTEXT {
+        context.insert(root.to_string(), Value::String(String::new()));
+    }
+    let input = TEMPLATE_INPUT_FIELDS
+        .iter()
+        .map(|field| (field.to_string(), Value::String(String::new())))
+        .collect();
+    context.insert("input".to_string(), Value::Object(input));
+    Value::Object(context)
+}
+
 /// Errors while parsing a skill manifest
 #[derive(Debug, thiserror::Error, PartialEq)]
 pub enum SkillManifestError {
@@ -38,16 +273,52 @@ pub enum SkillManifestError {
     MissingFrontmatter,
     #[error("failed to parse frontmatter: {0}")]
     FrontmatterParse(String),
+    #[error("invalid asset path '{0}': must be relative and stay inside the skill directory")]
+    InvalidAssetPath(String),
 }
 
-/// Parse a SKILL.md string into a manifest (frontmatter + body).
-/// Expects YAML frontmatter delimited by `---` at the start of the file.
-pub fn parse_skill_manifest(markdown: &str) -> Result<SkillManifest, SkillManifestError> {
+/// Check that an asset path is relative and cannot escape the skill directory
+pub fn validate_asset_path(path: &str) -> Result<(), SkillManifestError> {
+    let invalid = path.trim().is_empty()
+        || path.starts_with('/')
+        || path.starts_with('\\')
+        || path.contains(':')
+        || path.split(['/', '\\']).any(|component| component == "..");
+
+    if invalid {
+        Err(SkillManifestError::InvalidAssetPath(path.to_string()))
+    } else {
+        Ok(())
+    }
+}
+
+/// Frontmatter delimiter errors shared by SKILL.md and TOOL.md parsing
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FrontmatterError {
+    MissingDelimiter,
+    MissingFrontmatter,
+}
+
+#[cfg(feature = "std")]
+impl From<FrontmatterError> for SkillManifestError {
+    fn from(err: FrontmatterError) -> Self {
+        match err {
+            FrontmatterError::MissingDelimiter => Self::MissingDelimiter,
+            FrontmatterError::MissingFrontmatter => Self::MissingFrontmatter,
+        }
+    }
+}
+
+/// Split a markdown manifest into raw YAML frontmatter and body.
+/// Expects frontmatter delimited by `---` at the start of the file.
+#[cfg(feature = "std")]
+pub(crate) fn split_frontmatter(markdown: &str) -> Result<(String, String), FrontmatterError> {
     let mut lines = markdown.lines();
-    let first = lines.next().ok_or(SkillManifestError::MissingFrontmatter)?;
+    let first = lines.next().ok_or(FrontmatterError::MissingFrontmatter)?;
 
     if first.trim() != "---" {
-        return Err(SkillManifestError::MissingDelimiter);
+        return Err(FrontmatterError::MissingDelimiter);
     }
 
     let mut frontmatter_raw = String::new();
@@ -63,13 +334,183 @@ pub fn parse_skill_manifest(markdown: &str) -> Result<SkillManifest, SkillManife
     }
 
     if in_frontmatter {
-        return Err(SkillManifestError::MissingFrontmatter);
+        return Err(FrontmatterError::MissingFrontmatter);
     }
 
+    let body = lines.collect::<Vec<_>>().join("\n");
+
+    Ok((frontmatter_raw, body))
+}
+
+/// Parse a SKILL.md string into a manifest (frontmatter + body).
+/// Expects YAML frontmatter delimited by `---` at the start of the file.
+#[cfg(feature = "std")]
+pub fn parse_skill_manifest(markdown: &str) -> Result<SkillManifest, SkillManifestError> {
+    let (frontmatter_raw, body) = split_frontmatter(markdown)?;
+
     let frontmatter: SkillFrontmatter = serde_yaml::from_str(&frontmatter_raw)
         .map_err(|e| SkillManifestError::FrontmatterParse(e.to_string()))?;
 
-    let body = lines.collect::<Vec<_>>().join("\n");
+    for path in frontmatter.assets.values() {
+        validate_asset_path(path)?;
+    }
 
     Ok(SkillManifest { frontmatter, body })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = "---\nname: demo\ndescription: Demo skill\n---\n# Demo\n\n```prompt\nSay {{input.text}}\n```\n";
+
+    #[test]
+    fn test_parse_manifest_with_prompt_template() {
+        let manifest = parse_skill_manifest(MANIFEST).unwrap();
+        assert_eq!(manifest.frontmatter.name, "demo");
+        assert_eq!(
+            manifest.prompt_template_source().as_deref(),
+            Some("Say {{input.text}}")
+        );
+
+        let template = manifest.prompt_template().unwrap().unwrap();
+        assert_eq!(template.variables(), vec!["input.text"]);
+    }
+
+    #[test]
+    fn test_asset_paths() {
+        let manifest = parse_skill_manifest(
+            "---\nname: a\ndescription: b\nassets:\n  examples: examples/few-shot.md\n---\n",
+        )
+        .unwrap();
+        assert_eq!(
+            manifest
+                .frontmatter
+                .assets
+                .get("examples")
+                .map(String::as_str),
+            Some("examples/few-shot.md")
+        );
+
+        assert!(validate_asset_path("data/ref.json").is_ok());
+        for bad in ["/etc/passwd", "../secret", "a/../../b", "C:\\x", ""] {
+            assert_eq!(
+                validate_asset_path(bad),
+                Err(SkillManifestError::InvalidAssetPath(bad.to_string()))
+            );
+        }
+
+        let escaping =
+            parse_skill_manifest("---\nname: a\ndescription: b\nassets:\n  x: ../x.md\n---\n");
+        assert!(matches!(
+            escaping,
+            Err(SkillManifestError::InvalidAssetPath(_))
+        ));
+    }
+
+    #[test]
+    fn test_lint_clean_manifest() {
+        let manifest = parse_skill_manifest(
+            "---\nname: demo\ndescription: Demo skill\nmetadata:\n  version: \"1.0.0\"\nassets:\n  examples: ex.md\n---\n```prompt\n{{assets.examples}} {{input.text}}\n```\n",
+        )
+        .unwrap();
+        assert_eq!(manifest.frontmatter.version(), Some("1.0.0"));
+        assert!(manifest.lint().is_empty());
+    }
+
+    #[test]
+    fn test_lint_findings() {
+        let manifest = parse_skill_manifest(
+            "---\nname: Bad--Name\ndescription: \" \"\n---\n```prompt\n{{inptu.text}} {{assets.missing}}\n```\n",
+        )
+        .unwrap();
+        let lints = manifest.lint();
+        let errors: Vec<_> = lints
+            .iter()
+            .filter(|l| l.level == LintLevel::Error)
+            .map(|l| l.message.as_str())
+            .collect();
+
+        assert_eq!(errors.len(), 4);
+        assert!(errors[0].contains("Bad--Name"));
+        assert!(errors[1].contains("description"));
+        assert!(errors[2].contains("inptu.text"));
+        assert!(errors[3].contains("missing"));
+        assert!(lints
+            .iter()
+            .any(|l| l.level == LintLevel::Warning && l.message.contains("version")));
+
+        let broken = parse_skill_manifest(
+            "---\nname: demo\ndescription: d\n---\n```prompt\n{{input.text\n```\n",
+        )
+        .unwrap();
+        assert!(broken
+            .lint()
+            .iter()
+            .any(|l| l.level == LintLevel::Error && l.message.contains("unclosed")));
+    }
+
+    #[test]
+    fn test_lint_unresolved_paths() {
+        let manifest = parse_skill_manifest(
+            "---\nname: demo\ndescription: d\nassets:\n  examples: ex.md\n---\n```prompt\n{{input.txt}} {{target.name}} {{assets.examples.x}} {{input.text}} {{input}}\n```\n",
+        )
+        .unwrap();
+        let errors: Vec<_> = manifest
+            .lint()
+            .into_iter()
+            .filter(|l| l.level == LintLevel::Error)
+            .map(|l| l.message)
+            .collect();
+
+        assert_eq!(errors.len(), 3);
+        assert!(errors[0].contains("'input.txt' does not resolve"));
+        assert!(errors[1].contains("'target.name' does not resolve"));
+        assert!(errors[2].contains("'assets.examples.x' does not resolve"));
+    }
+
+    #[test]
+    fn test_lint_examples() {
+        let manifest = parse_skill_manifest(
+            "---
+name: demo
+description: d
+metadata:
+  version: \"1.0.0\"
+examples:
+  - call: {\"skill\": \"demo\", \"text\": \"x\"}
+    response: {\"ok\": true}
+  - {\"skill\": \"other\"}
+---
+",
+        )
+        .unwrap();
+        assert_eq!(manifest.frontmatter.examples.len(), 2);
+        let lints = manifest.lint();
+        assert_eq!(lints.len(), 1);
+        assert!(lints[0].message.starts_with("example 2:"));
+    }
+
+    #[test]
+    fn test_lint_checks() {
+        let manifest = parse_skill_manifest(
+            "---\nname: demo\ndescription: d\nmetadata:\n  version: \"1.0.0\"\nchecks:\n  required: [total]\n  patterns:\n    total: \"[0-9.\"\n---\n",
+        )
+        .unwrap();
+        assert_eq!(
+            manifest.frontmatter.checks.as_ref().unwrap().required,
+            vec!["total"]
+        );
+        let lints = manifest.lint();
+        assert_eq!(lints.len(), 1);
+        assert!(lints[0]
+            .message
+            .starts_with("checks: invalid pattern for 'total'"));
+    }
+
+    #[test]
+    fn test_manifest_without_template() {
+        let manifest = parse_skill_manifest("---\nname: a\ndescription: b\n---\nbody").unwrap();
+        assert!(manifest.prompt_template().is_none());
+    }
+}
@@ -0,0 +1,312 @@
+//! Read-only SQL query results
+//!
+//! Backs the `sql` tool: the host runs one read-only query against a SQLite
+//! file and hands the rows back as compact JSON, column names once and each
+//! row as an array:
+//!
+//! ```text
+//! {"columns":["name","total"],"rows":[["alice",42],["bob",7]],"truncated":false}
+//! ```
+//!
+//! Rows past the limit are dropped and long text values are shortened, so a
+//! `SELECT *` on a large table cannot flood the context. Running the query is
+//! up to the host; [`QueryResultValidator`] checks what comes back has this
+//! shape.
+
+use crate::guardrail::{GuardrailContext, GuardrailResult, SemanticGuardrail};
+use crate::prelude::*;
+use crate::tool::is_workspace_path;
+use serde::Deserialize;
+use serde_json::Value;
+use thiserror::Error;
+
+/// Parameters of a `sql` call
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct SqlRequest {
+    /// SQLite file, relative to the working directory
+    pub database: String,
+    /// A single read-only statement
+    pub query: String,
+    /// Fewer rows than the host's cap
+    #[serde(default)]
+    pub max_rows: Option<usize>,
+}
+
+impl SqlRequest {
+    /// Read a request from tool call parameters
+    pub fn from_params(params: &Value) -> Result<Self, SqlError> {
+        let request: Self = serde_json::from_value(params.clone())
+            .map_err(|e| SqlError::InvalidRequest(e.to_string()))?;
+        if request.query.trim().is_empty() {
+            return Err(SqlError::InvalidRequest("empty query".to_string()));
+        }
+        if !is_workspace_path(&request.database) {
+            return Err(SqlError::UnsafePath(request.database));
+        }
+        Ok(request)
+    }
+}
+
+/// SQL tool errors
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum SqlError {
+    #[error("Invalid sql request: {0}")]
+    InvalidRequest(String),
+
+    #[error("Unsafe path '{0}': paths must be relative and stay inside the working directory")]
+    UnsafePath(String),
+
+    #[error("Only read-only queries are allowed (SELECT, WITH, EXPLAIN, VALUES)")]
+    NotReadOnly,
+}
+
+/// Caps on what one query returns
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SqlLimits {
+    pub max_rows: usize,
+    /// Longer text values are shortened
+    pub max_cell_chars: usize,
+}
+
+impl Default for SqlLimits {
+    fn default() -> Self {
+        Self {
+            max_rows: 50,
+            max_cell_chars: 200,
+        }
+    }
+}
+
+impl SqlLimits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_rows(mut self, rows: usize) -> Self {
+        self.max_rows = rows.max(1);
+        self
+    }
+
+    pub fn with_max_cell_chars(mut self, chars: usize) -> Self {
+        self.max_cell_chars = chars.max(1);
+        self
+    }
+
+    /// These limits, lowered to what the request asks for
+    pub fn for_request(&self, request: &SqlRequest) -> Self {
+        match request.max_rows {
+            Some(rows) => self.clone().with_max_rows(rows.min(self.max_rows)),
+            None => self.clone(),
+        }
+    }
+}
+
+/// Rows collected from a query
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryResults {
+    limits: SqlLimits,
+    columns: Vec<String>,
+    rows: Vec<Vec<Value>>,
+    truncated: bool,
+}
+
+impl QueryResults {
+    pub fn new(columns: Vec<String>, limits: SqlLimits) -> Self {
+        Self {
+            limits,
+            columns,
+            rows: Vec::new(),
+            truncated: false,
+        }
+    }
+
+    /// Record a row; returns `false` once the row limit is reached
+    pub fn push_row(&mut self, row: Vec<Value>) -> bool {
+        if self.rows.len() >= self.limits.max_rows {
+            self.truncated = true;
+            return false;
+        }
+        let max_chars = self.limits.max_cell_chars;
+        self.rows.push(
+            row.into_iter()
+                .map(|value| match value {
+                    Value::String(text) if text.chars().count() > max_chars => {
+                        let mut short: String = text.chars().take(max_chars).collect();
+                        short.push_str("...");
+                        Value::String(short)
+                    }
+                    value => value,
+                })
+                .collect(),
+        );
+        true
+    }
+
+    pub fn rows(&self) -> &[Vec<Value>] {
+        &self.rows
+    }
+
+    /// Whether rows were left out because of the limit
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// The tool output handed to the model
+    pub fn to_json(&self) -> String {
+        serde_json::json!({
+            "columns": self.columns,
+            "rows": self.rows,
+            "truncated": self.truncated,
+        })
+        .to_string()
+    }
+}
+
+/// Whether `query` starts like a read-only statement
+///
+/// A quick check that gives the model a clear error; hosts must still make
+/// sure the statement cannot write (e.g. by opening the database read-only).
+pub fn is_read_only_query(query: &str) -> bool {
+    let keyword: String = query
+        .trim_start()
+        .chars()
+        .take_while(|c| c.is_ascii_alphabetic())
+        .collect();
+    matches!(
+        keyword.to_ascii_uppercase().as_str(),
+        "SELECT" | "WITH" | "EXPLAIN" | "VALUES"
+    )
+}
+
+/// The `sql` tool's validator of its results: the JSON
+/// [`QueryResults::to_json`] writes, with one value per column in every row
+///
+/// Registered with [`GuardrailChain::add_tool_validator`](crate::guardrail::GuardrailChain::add_tool_validator).
+/// Failed calls pass.
+pub struct QueryResultValidator;
+
+impl SemanticGuardrail for QueryResultValidator {
+    fn validate(&self, context: &GuardrailContext) -> GuardrailResult {
+        if !context.tool_result.success {
+            return GuardrailResult::Accept;
+        }
+        match check_query_output(&context.tool_result.output) {
+            Ok(()) => GuardrailResult::Accept,
+            Err(problem) => GuardrailResult::reject(format!("Malformed sql result: {}", problem)),
+        }
+    }
+
+    fn name(&self) -> &str {
+        "sql_result"
+    }
+}
+
+fn check_query_output(output: &str) -> Result<(), String> {
+    let value: Value = serde_json::from_str(output).map_err(|e| format!("not JSON ({})", e))?;
+    let columns = value
+        .get("columns")
+        .and_then(Value::as_array)
+        .filter(|columns| columns.iter().all(Value::is_string))
+        .ok_or("no 'columns' array of names")?;
+    let rows = value
+        .get("rows")
+        .and_then(Value::as_array)
+        .ok_or("no 'rows' array")?;
+    if !value.get("truncated").is_some_and(Value::is_boolean) {
+        return Err("no 'truncated' flag".to_string());
+    }
+    for (index, row) in rows.iter().enumerate() {
+        let width = row.as_array().map(Vec::len);
+        if width != Some(columns.len()) {
+            return Err(format!(
+                "row {} does not hold one value per column ({})",
+                index + 1,
+                columns.len()
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_from_params() {
+        let request = SqlRequest::from_params(&serde_json::json!({
+            "database": "data/sales.db",
+            "query": "SELECT * FROM orders"
+        }))
+        .unwrap();
+        assert_eq!(request.database, "data/sales.db");
+
+        assert!(matches!(
+            SqlRequest::from_params(
+                &serde_json::json!({"database": "/etc/x.db", "query": "SELECT 1"})
+            ),
+            Err(SqlError::UnsafePath(_))
+        ));
+        assert!(matches!(
+            SqlRequest::from_params(&serde_json::json!({"database": "x.db", "query": " "})),
+            Err(SqlError::InvalidRequest(_))
+        ));
+    }
+
+    #[test]
+    fn test_read_only_queries() {
+        assert!(is_read_only_query("select count(*) from t"));
+        assert!(is_read_only_query("  WITH x AS (SELECT 1) SELECT * FROM x"));
+        assert!(!is_read_only_query("DELETE FROM t"));
+        assert!(!is_read_only_query("ATTACH 'other.db' AS o"));
+    }
+
+    #[test]
+    fn test_rows_are_capped() {
+        let mut results = QueryResults::new(
+            vec!["name".to_string(), "total".to_string()],
+            SqlLimits::new().with_max_rows(2).with_max_cell_chars(5),
+        );
+        assert!(results.push_row(vec![serde_json::json!("alice"), serde_json::json!(42)]));
+        assert!(results.push_row(vec![serde_json::json!("bartholomew"), Value::Null]));
+        assert!(!results.push_row(vec![serde_json::json!("carol"), serde_json::json!(1)]));
+
+        assert!(results.is_truncated());
+        assert_eq!(
+            results.to_json(),
+            r#"{"columns":["name","total"],"rows":[["alice",42],["barth...",null]],"truncated":true}"#
+        );
+    }
+
+    #[test]
+    fn test_result_validator() {
+        use crate::agent::AgentState;
+        use crate::tool::{ToolRequest, ToolResult};
+
+        let state = AgentState::new("Top customers?");
+        let request = ToolRequest {
+            tool: "sql".to_string(),
+            call_id: None,
+            params: serde_json::json!({"database": "sales.db", "query": "SELECT 1"}),
+        };
+        let check = |output: &str| {
+            QueryResultValidator.validate(&GuardrailContext {
+                state: &state,
+                tool_request: &request,
+                tool_result: &ToolResult::success(output),
+            })
+        };
+
+        let mut results = QueryResults::new(vec!["name".to_string()], SqlLimits::new());
+        results.push_row(vec![serde_json::json!("alice")]);
+        assert!(check(&results.to_json()).is_accept());
+
+        let verdict = check(r#"{"columns":["name","total"],"rows":[["alice"]],"truncated":false}"#);
+        assert!(
+            matches!(&verdict, GuardrailResult::Reject { reason } if reason.contains("row 1")),
+            "{:?}",
+            verdict
+        );
+        assert!(check(r#"{"columns":["name"],"rows":[["ali"#).is_reject());
+    }
+}
@@ -0,0 +1,199 @@
+//! Stop sequences for plain-text prompts
+//!
+//! A completion model continues the prompt's text, and once its own turn is
+//! done it readily writes the next one: a `User:` line, or a `Tool output:`
+//! it made up for the call it just made. Generation stops where any of the
+//! prompt phase's stop sequences begins, and the sequence is not part of the
+//! output.
+//!
+//! Chat backends keep roles apart themselves and ignore stop sequences.
+
+use crate::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// What a prompt asks the model for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PromptPhase {
+    /// An agent turn: a call or the answer
+    Turn,
+    /// A summary of a long tool output
+    Summary,
+    /// Values extracted from text
+    Extraction,
+    /// A critic's review of an answer
+    Critique,
+}
+
+impl PromptPhase {
+    pub const ALL: [PromptPhase; 4] = [
+        PromptPhase::Turn,
+        PromptPhase::Summary,
+        PromptPhase::Extraction,
+        PromptPhase::Critique,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PromptPhase::Turn => "turn",
+            PromptPhase::Summary => "summary",
+            PromptPhase::Extraction => "extraction",
+            PromptPhase::Critique => "critique",
+        }
+    }
+
+    pub fn from_str(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|phase| phase.as_str() == s)
+    }
+}
+
+/// Stop sequences of each prompt phase
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StopSequences {
+    turn: Vec<String>,
+    summary: Vec<String>,
+    extraction: Vec<String>,
+    critique: Vec<String>,
+}
+
+impl Default for StopSequences {
+    /// Stop at the next role marker; a turn also stops at a tool output
+    fn default() -> Self {
+        let roles = || vec!["\nUser:".to_string(), "\nAssistant:".to_string()];
+        let mut turn = roles();
+        turn.push("\nTool output:".to_string());
+        Self {
+            turn,
+            summary: roles(),
+            extraction: roles(),
+            critique: roles(),
+        }
+    }
+}
+
+impl StopSequences {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// No stop sequences in any phase
+    pub fn none() -> Self {
+        Self {
+            turn: Vec::new(),
+            summary: Vec::new(),
+            extraction: Vec::new(),
+            critique: Vec::new(),
+        }
+    }
+
+    /// Also stop `phase` at `sequence`
+    pub fn with_stop(mut self, phase: PromptPhase, sequence: impl Into<String>) -> Self {
+        let sequence = sequence.into();
+        let stops = self.phase_mut(phase);
+        if !sequence.is_empty() && !stops.contains(&sequence) {
+            stops.push(sequence);
+        }
+        self
+    }
+
+    /// No stop sequences in `phase`
+    pub fn without(mut self, phase: PromptPhase) -> Self {
+        self.phase_mut(phase).clear();
+        self
+    }
+
+    /// Apply a `phase=sequence` rule (e.g. `turn=\nObservation:`), where `\n`
+    /// stands for a newline; an empty sequence clears the phase
+    pub fn with_rule(self, rule: &str) -> Result<Self, String> {
+        let (phase, sequence) = rule
+            .split_once('=')
+            .ok_or_else(|| format!("Invalid stop rule '{}'. Expected phase=sequence", rule))?;
+        let phase = PromptPhase::from_str(phase.trim()).ok_or_else(|| {
+            format!(
+                "Invalid prompt phase '{}'. Expected one of: turn, summary, extraction, critique",
+                phase.trim()
+            )
+        })?;
+        let sequence = sequence.replace("\\n", "\n");
+        Ok(if sequence.is_empty() {
+            self.without(phase)
+        } else {
+            self.with_stop(phase, sequence)
+        })
+    }
+
+    /// Stop sequences of `phase`
+    pub fn for_phase(&self, phase: PromptPhase) -> &[String] {
+        match phase {
+            PromptPhase::Turn => &self.turn,
+            PromptPhase::Summary => &self.summary,
+            PromptPhase::Extraction => &self.extraction,
+            PromptPhase::Critique => &self.critique,
+        }
+    }
+
+    fn phase_mut(&mut self, phase: PromptPhase) -> &mut Vec<String> {
+        match phase {
+            PromptPhase::Turn => &mut self.turn,
+            PromptPhase::Summary => &mut self.summary,
+            PromptPhase::Extraction => &mut self.extraction,
+            PromptPhase::Critique => &mut self.critique,
+        }
+    }
+}
+
+/// Where the earliest of `stops` begins in `text`, for backends that check
+/// their output as it grows
+pub fn stop_position(text: &str, stops: &[String]) -> Option<usize> {
+    stops
+        .iter()
+        .filter_map(|stop| text.find(stop.as_str()))
+        .min()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_stops() {
+        let stops = StopSequences::default();
+        assert!(stops
+            .for_phase(PromptPhase::Turn)
+            .contains(&"\nTool output:".to_string()));
+        assert!(!stops
+            .for_phase(PromptPhase::Extraction)
+            .contains(&"\nTool output:".to_string()));
+        assert!(StopSequences::none()
+            .for_phase(PromptPhase::Turn)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_stop_rules() {
+        let stops = StopSequences::default()
+            .with_rule("turn=\\n```")
+            .unwrap()
+            .with_rule("critique=")
+            .unwrap();
+        assert!(stops
+            .for_phase(PromptPhase::Turn)
+            .contains(&"\n```".to_string()));
+        assert!(stops.for_phase(PromptPhase::Critique).is_empty());
+
+        assert!(StopSequences::default().with_rule("turn").is_err());
+        assert!(StopSequences::default().with_rule("answer=x").is_err());
+    }
+
+    #[test]
+    fn test_stop_position() {
+        let stops = StopSequences::default();
+        let reply = "{\"tool\": \"shell\", \"command\": \"ls\"}\nTool output:\nsrc\nUser: thanks";
+        let end = stop_position(reply, stops.for_phase(PromptPhase::Turn)).unwrap();
+        assert_eq!(&reply[..end], "{\"tool\": \"shell\", \"command\": \"ls\"}");
+        assert_eq!(
+            stop_position("42", stops.for_phase(PromptPhase::Turn)),
+            None
+        );
+    }
+}
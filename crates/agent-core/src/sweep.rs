@@ -0,0 +1,286 @@
+//! Sampling sweeps: how stable a model's decisions are
+//!
+//! A sweep runs one query several times across temperatures and seeds. Each
+//! run is reduced to its tool sequence and its outcome (the normalized final
+//! answer, or the failure code), and the report gives how often the most
+//! common of each came up, overall and per temperature. A model that calls
+//! different tools or lands on different answers for the same query is one
+//! to check before trusting it unattended.
+
+use crate::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Default number of runs in a sweep
+pub const DEFAULT_SWEEP_RUNS: usize = 9;
+
+/// Outcomes longer than this are cut in the text report
+const OUTCOME_DISPLAY_CHARS: usize = 60;
+
+/// The sampling settings of one run
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SweepSetting {
+    pub temperature: f32,
+    pub seed: u32,
+}
+
+/// `runs` settings cycling through `temperatures`, each with its own seed
+/// counting up from `first_seed`
+pub fn sweep_settings(runs: usize, temperatures: &[f32], first_seed: u32) -> Vec<SweepSetting> {
+    if temperatures.is_empty() {
+        return Vec::new();
+    }
+    (0..runs)
+        .map(|i| SweepSetting {
+            temperature: temperatures[i % temperatures.len()],
+            seed: first_seed.wrapping_add(i as u32),
+        })
+        .collect()
+}
+
+/// What one run of the sweep did
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SweepRun {
+    #[serde(flatten)]
+    pub setting: SweepSetting,
+    /// Tools the run called, in order
+    pub tools: Vec<String>,
+    /// Final answer, `None` if the run failed
+    pub answer: Option<String>,
+    /// Why the run failed
+    pub error: Option<String>,
+}
+
+impl SweepRun {
+    /// The tool calls as one comparable string
+    pub fn sequence(&self) -> String {
+        if self.tools.is_empty() {
+            "(no tools)".to_string()
+        } else {
+            self.tools.join(" → ")
+        }
+    }
+
+    /// The answer with case, spacing and a trailing period normalized, or
+    /// the failure
+    pub fn outcome(&self) -> String {
+        match (&self.answer, &self.error) {
+            (Some(answer), None) => {
+                let words: Vec<&str> = answer.split_whitespace().collect();
+                words.join(" ").trim_end_matches('.').to_lowercase()
+            }
+            (_, Some(error)) => format!("failed: {}", error),
+            (None, None) => "failed: no final answer".to_string(),
+        }
+    }
+
+    fn failed(&self) -> bool {
+        self.answer.is_none() || self.error.is_some()
+    }
+}
+
+/// How often a set of runs agreed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Stability {
+    pub runs: usize,
+    pub failures: usize,
+    /// The most common tool sequence
+    pub sequence: String,
+    /// Share of runs with that sequence, 0 to 1
+    pub sequence_share: f64,
+    pub distinct_sequences: usize,
+    /// The most common outcome
+    pub outcome: String,
+    /// Share of runs with that outcome, 0 to 1
+    pub outcome_share: f64,
+    pub distinct_outcomes: usize,
+}
+
+impl Stability {
+    fn of(runs: &[&SweepRun]) -> Self {
+        let (sequence, sequence_count, distinct_sequences) =
+            most_common(runs.iter().map(|run| run.sequence()));
+        let (outcome, outcome_count, distinct_outcomes) =
+            most_common(runs.iter().map(|run| run.outcome()));
+        let share = |count: usize| {
+            if runs.is_empty() {
+                0.0
+            } else {
+                count as f64 / runs.len() as f64
+            }
+        };
+        Self {
+            runs: runs.len(),
+            failures: runs.iter().filter(|run| run.failed()).count(),
+            sequence,
+            sequence_share: share(sequence_count),
+            distinct_sequences,
+            outcome,
+            outcome_share: share(outcome_count),
+            distinct_outcomes,
+        }
+    }
+}
+
+/// The most common value (the first seen on a tie), its count and the
+/// number of distinct values
+fn most_common(values: impl Iterator<Item = String>) -> (String, usize, usize) {
+    let mut counts: Vec<(String, usize)> = Vec::new();
+    for value in values {
+        match counts.iter_mut().find(|(seen, _)| *seen == value) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((value, 1)),
+        }
+    }
+    let distinct = counts.len();
+    let mut best = (String::new(), 0);
+    for (value, count) in counts {
+        if count > best.1 {
+            best = (value, count);
+        }
+    }
+    (best.0, best.1, distinct)
+}
+
+/// Stability of the runs at one temperature
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemperatureStability {
+    pub temperature: f32,
+    #[serde(flatten)]
+    pub stability: Stability,
+}
+
+/// The result of a sweep
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SweepReport {
+    pub overall: Stability,
+    /// In the order the temperatures were first run
+    pub by_temperature: Vec<TemperatureStability>,
+}
+
+impl SweepReport {
+    pub fn new(runs: &[SweepRun]) -> Self {
+        let mut temperatures: Vec<f32> = Vec::new();
+        for run in runs {
+            if !temperatures.contains(&run.setting.temperature) {
+                temperatures.push(run.setting.temperature);
+            }
+        }
+        let by_temperature = temperatures
+            .into_iter()
+            .map(|temperature| {
+                let runs: Vec<&SweepRun> = runs
+                    .iter()
+                    .filter(|run| run.setting.temperature == temperature)
+                    .collect();
+                TemperatureStability {
+                    temperature,
+                    stability: Stability::of(&runs),
+                }
+            })
+            .collect();
+        Self {
+            overall: Stability::of(&runs.iter().collect::<Vec<_>>()),
+            by_temperature,
+        }
+    }
+
+    /// The overall agreement followed by a table per temperature
+    pub fn to_text(&self) -> String {
+        let overall = &self.overall;
+        let mut outcome: String = overall
+            .outcome
+            .chars()
+            .take(OUTCOME_DISPLAY_CHARS)
+            .collect();
+        if overall.outcome.chars().count() > OUTCOME_DISPLAY_CHARS {
+            outcome.push('…');
+        }
+        let mut text = format!(
+            "Runs: {} ({} failed)\n\
+             Same tools:   {:>5.1}% ({} distinct)  {}\n\
+             Same outcome: {:>5.1}% ({} distinct)  {}\n\n",
+            overall.runs,
+            overall.failures,
+            overall.sequence_share * 100.0,
+            overall.distinct_sequences,
+            overall.sequence,
+            overall.outcome_share * 100.0,
+            overall.distinct_outcomes,
+            outcome
+        );
+        text.push_str(&format!(
+            "{:>11}  {:>4}  {:>10}  {:>12}  {:>6}\n",
+            "TEMPERATURE", "RUNS", "SAME TOOLS", "SAME OUTCOME", "FAILED"
+        ));
+        for row in &self.by_temperature {
+            let stability = &row.stability;
+            text.push_str(&format!(
+                "{:>11.2}  {:>4}  {:>9.1}%  {:>11.1}%  {:>6}\n",
+                row.temperature,
+                stability.runs,
+                stability.sequence_share * 100.0,
+                stability.outcome_share * 100.0,
+                stability.failures
+            ));
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(temperature: f32, tools: &[&str], answer: Option<&str>) -> SweepRun {
+        SweepRun {
+            setting: SweepSetting {
+                temperature,
+                seed: 0,
+            },
+            tools: tools.iter().map(|tool| tool.to_string()).collect(),
+            answer: answer.map(str::to_string),
+            error: answer.is_none().then(|| "max_iterations".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_settings_cycle_temperatures_with_distinct_seeds() {
+        let settings = sweep_settings(5, &[0.2, 0.8], 7);
+        let temperatures: Vec<f32> = settings.iter().map(|s| s.temperature).collect();
+        let seeds: Vec<u32> = settings.iter().map(|s| s.seed).collect();
+        assert_eq!(temperatures, vec![0.2, 0.8, 0.2, 0.8, 0.2]);
+        assert_eq!(seeds, vec![7, 8, 9, 10, 11]);
+        assert!(sweep_settings(3, &[], 0).is_empty());
+    }
+
+    #[test]
+    fn test_outcomes_ignore_case_spacing_and_final_period() {
+        let a = run(0.2, &["shell"], Some("There are  61 files."));
+        let b = run(0.2, &["shell"], Some("there are 61 files"));
+        assert_eq!(a.outcome(), b.outcome());
+        assert_eq!(run(0.2, &[], None).outcome(), "failed: max_iterations");
+    }
+
+    #[test]
+    fn test_report_counts_agreement_overall_and_per_temperature() {
+        let runs = vec![
+            run(0.2, &["shell"], Some("61")),
+            run(0.2, &["shell"], Some("61")),
+            run(1.0, &["shell", "eval_math"], Some("61")),
+            run(1.0, &[], None),
+        ];
+        let report = SweepReport::new(&runs);
+        assert_eq!(report.overall.sequence, "shell");
+        assert_eq!(report.overall.sequence_share, 0.5);
+        assert_eq!(report.overall.distinct_sequences, 3);
+        assert_eq!(report.overall.outcome_share, 0.75);
+        assert_eq!(report.overall.failures, 1);
+
+        assert_eq!(report.by_temperature.len(), 2);
+        assert_eq!(report.by_temperature[0].stability.sequence_share, 1.0);
+        assert_eq!(report.by_temperature[1].stability.outcome_share, 0.5);
+        assert!(report
+            .to_text()
+            .contains("Same tools:    50.0% (3 distinct)  shell"));
+    }
+}
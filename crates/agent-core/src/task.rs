@@ -0,0 +1,239 @@
+//! Task files: named, repeatable agent jobs
+//!
+//! A task file is YAML describing one job, so teams can keep the jobs they run
+//! again and again under version control instead of retyping command lines:
+//!
+//! ```yaml
+//! name: todo-report
+//! description: List the open TODOs of a repository
+//! query: List the TODO comments under {{repo}}, grouped by {{group_by}}.
+//! variables:
+//!   repo:
+//!     description: Repository to scan
+//!   group_by:
+//!     default: file
+//! profile: researcher
+//! tools: [search, shell]
+//! guardrails: [plausibility]
+//! max_safety: read-only
+//! max_iterations: 8
+//! output:
+//!   max_words: 200
+//!   bullets: true
+//! ```
+//!
+//! The query is a [`PromptTemplate`]; every variable it uses must be declared,
+//! and a variable without a default must be given when the task runs. The
+//! tool set, guardrails and safety ceiling override those of `profile`, a
+//! profile of the host config; `output` is the [`ResponseSpec`] answers are
+//! checked against.
+
+use crate::profile::{GuardName, Profile};
+use crate::prompt_template::{PromptTemplate, TemplateError};
+use crate::response_spec::ResponseSpec;
+use crate::tool_manifest::SafetyClass;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+/// Errors from loading a task file or filling in its query
+#[derive(Debug, Error)]
+pub enum TaskError {
+    #[error("invalid task file: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+    #[error("invalid query: {0}")]
+    Template(#[from] TemplateError),
+    #[error("the query uses '{{{{{0}}}}}', which is not declared in variables")]
+    Undeclared(String),
+    #[error("missing variable '{0}' (give it with --var {0}=...)")]
+    Missing(String),
+    #[error("unknown variable '{name}' (declared: {declared})")]
+    Unknown { name: String, declared: String },
+}
+
+/// A variable of a task's query
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TaskVariable {
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Value used when the run does not give one (required otherwise)
+    #[serde(default)]
+    pub default: Option<String>,
+}
+
+/// A named agent job
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TaskFile {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Query template
+    pub query: String,
+    #[serde(default)]
+    pub variables: BTreeMap<String, TaskVariable>,
+    /// Profile of the host config to start from
+    #[serde(default)]
+    pub profile: Option<String>,
+    /// Tools offered to the model (default: the profile's)
+    #[serde(default)]
+    pub tools: Option<Vec<String>>,
+    /// Semantic guardrails to run (default: the profile's)
+    #[serde(default)]
+    pub guardrails: Option<Vec<GuardName>>,
+    /// Refuse tools above this safety class (default: the profile's)
+    #[serde(default)]
+    pub max_safety: Option<SafetyClass>,
+    /// Iteration limit (default: the host's)
+    #[serde(default)]
+    pub max_iterations: Option<usize>,
+    /// Shape the final answer must have
+    #[serde(default)]
+    pub output: ResponseSpec,
+}
+
+impl TaskFile {
+    /// Parse a task file and check its query against its variables
+    pub fn from_yaml(yaml: &str) -> Result<Self, TaskError> {
+        let task: Self = serde_yaml::from_str(yaml)?;
+        let template = PromptTemplate::parse(&task.query)?;
+        if let Some(name) = template
+            .variables()
+            .into_iter()
+            .find(|name| !task.variables.contains_key(*name))
+        {
+            return Err(TaskError::Undeclared(name.to_string()));
+        }
+        Ok(task)
+    }
+
+    /// The query with `values` and the defaults filled in
+    pub fn render_query(&self, values: &BTreeMap<String, String>) -> Result<String, TaskError> {
+        if let Some(name) = values
+            .keys()
+            .find(|name| !self.variables.contains_key(*name))
+        {
+            return Err(TaskError::Unknown {
+                name: name.clone(),
+                declared: match self.variables.is_empty() {
+                    true => "none".to_string(),
+                    false => self
+                        .variables
+                        .keys()
+                        .cloned()
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                },
+            });
+        }
+        let mut context = serde_json::Map::new();
+        for (name, variable) in &self.variables {
+            let value = values
+                .get(name)
+                .or(variable.default.as_ref())
+                .ok_or_else(|| TaskError::Missing(name.clone()))?;
+            context.insert(name.clone(), Value::String(value.clone()));
+        }
+        Ok(PromptTemplate::parse(&self.query)?.render(&Value::Object(context))?)
+    }
+
+    /// `base` with the task's tools, guardrails and safety ceiling applied
+    pub fn apply_to(&self, mut base: Profile) -> Profile {
+        if let Some(tools) = &self.tools {
+            base.tools = Some(tools.clone());
+        }
+        if let Some(guardrails) = &self.guardrails {
+            base.guardrails = Some(guardrails.clone());
+        }
+        if let Some(max_safety) = self.max_safety {
+            base.max_safety = Some(max_safety);
+        }
+        base
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TASK: &str = r#"
+name: todo-report
+query: List the TODO comments under {{repo}}, grouped by {{group_by}}.
+variables:
+  repo:
+    description: Repository to scan
+  group_by:
+    default: file
+tools: [search]
+max_safety: read-only
+max_iterations: 8
+output:
+  max_words: 200
+"#;
+
+    fn values(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_render_query() {
+        let task = TaskFile::from_yaml(TASK).unwrap();
+        assert_eq!(task.max_iterations, Some(8));
+        assert_eq!(task.output, ResponseSpec::new().with_max_words(200));
+        assert_eq!(
+            task.render_query(&values(&[("repo", "crates/")])).unwrap(),
+            "List the TODO comments under crates/, grouped by file."
+        );
+        assert_eq!(
+            task.render_query(&values(&[("repo", "."), ("group_by", "author")]))
+                .unwrap(),
+            "List the TODO comments under ., grouped by author."
+        );
+        assert!(matches!(
+            task.render_query(&values(&[])),
+            Err(TaskError::Missing(name)) if name == "repo"
+        ));
+        assert!(matches!(
+            task.render_query(&values(&[("repo", "."), ("branch", "main")])),
+            Err(TaskError::Unknown { name, declared }) if name == "branch" && declared == "group_by, repo"
+        ));
+    }
+
+    #[test]
+    fn test_invalid_tasks() {
+        assert!(matches!(
+            TaskFile::from_yaml("name: t\nquery: Scan {{repo}}\n"),
+            Err(TaskError::Undeclared(name)) if name == "repo"
+        ));
+        assert!(matches!(
+            TaskFile::from_yaml("name: t\nquery: Scan {{repo\n"),
+            Err(TaskError::Template(_))
+        ));
+        assert!(matches!(
+            TaskFile::from_yaml("name: t\nquery: q\nmax_iteration: 3\n"),
+            Err(TaskError::Yaml(_))
+        ));
+    }
+
+    #[test]
+    fn test_apply_to_profile() {
+        let task = TaskFile::from_yaml(TASK).unwrap();
+        let base = Profile {
+            tools: Some(vec!["shell".to_string()]),
+            guardrails: Some(vec![GuardName::NumericConsistency]),
+            ..Profile::default()
+        };
+        let profile = task.apply_to(base);
+        assert_eq!(profile.tools, Some(vec!["search".to_string()]));
+        assert_eq!(
+            profile.guardrails,
+            Some(vec![GuardName::NumericConsistency])
+        );
+        assert_eq!(profile.max_safety, Some(SafetyClass::ReadOnly));
+    }
+}
@@ -0,0 +1,380 @@
+//! Run telemetry in the OpenTelemetry data model
+//!
+//! Hosts that export telemetry turn a run into an `agent.run` span with
+//! [`LLM_CALL_SPAN`], [`TOOL_EXEC_SPAN`] and [`GUARDRAIL_SPAN`] children, and
+//! count calls in [`Counters`]. This module holds those records, decides what
+//! an attribute carrying content records, and encodes both as OTLP/JSON request
+//! bodies ([`traces_request`] for `/v1/traces`, [`metrics_request`] for
+//! `/v1/metrics`). Hosts keep the clock, pick the ids and send.
+//!
+//! Queries, prompts, answers, tool parameters and tool output are content:
+//! under the default [`ContentPolicy::Redact`] only their length is recorded,
+//! as `<key>.length`.
+
+use crate::prelude::*;
+use alloc::collections::BTreeMap;
+use serde_json::{json, Value};
+
+/// The whole run
+pub const RUN_SPAN: &str = "agent.run";
+
+/// One model call
+pub const LLM_CALL_SPAN: &str = "llm.call";
+
+/// One tool call, from proposal to output
+pub const TOOL_EXEC_SPAN: &str = "tool.exec";
+
+/// One guardrail verdict on a tool call, tool output or final answer
+pub const GUARDRAIL_SPAN: &str = "guardrail.validate";
+
+/// Instrumentation scope the spans and metrics are reported under
+pub const SCOPE_NAME: &str = "agent.rs";
+
+/// OTLP `SPAN_KIND_INTERNAL`
+const SPAN_KIND_INTERNAL: u8 = 1;
+
+/// OTLP `STATUS_CODE_ERROR`
+const STATUS_CODE_ERROR: u8 = 2;
+
+/// OTLP `AGGREGATION_TEMPORALITY_CUMULATIVE`
+const TEMPORALITY_CUMULATIVE: u8 = 2;
+
+/// Whether content attributes record their text
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ContentPolicy {
+    /// Record only the length of the text
+    #[default]
+    Redact,
+    Include,
+}
+
+/// An attribute value
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeValue {
+    String(String),
+    Int(i64),
+    Double(f64),
+    Bool(bool),
+}
+
+impl AttributeValue {
+    fn to_otlp(&self) -> Value {
+        match self {
+            Self::String(value) => json!({ "stringValue": value }),
+            // 64-bit integers are strings in the protobuf JSON mapping
+            Self::Int(value) => json!({ "intValue": value.to_string() }),
+            Self::Double(value) => json!({ "doubleValue": value }),
+            Self::Bool(value) => json!({ "boolValue": value }),
+        }
+    }
+}
+
+impl From<&str> for AttributeValue {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_string())
+    }
+}
+
+impl From<String> for AttributeValue {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+impl From<i64> for AttributeValue {
+    fn from(value: i64) -> Self {
+        Self::Int(value)
+    }
+}
+
+impl From<usize> for AttributeValue {
+    fn from(value: usize) -> Self {
+        Self::Int(i64::try_from(value).unwrap_or(i64::MAX))
+    }
+}
+
+impl From<f64> for AttributeValue {
+    fn from(value: f64) -> Self {
+        Self::Double(value)
+    }
+}
+
+impl From<bool> for AttributeValue {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+/// Key-value pairs, in the order they were added
+pub type Attributes = Vec<(String, AttributeValue)>;
+
+fn attributes_to_otlp(attributes: &[(String, AttributeValue)]) -> Value {
+    attributes
+        .iter()
+        .map(|(key, value)| json!({ "key": key, "value": value.to_otlp() }))
+        .collect()
+}
+
+/// A finished or open operation
+///
+/// Ids are lowercase hex: 32 digits for traces, 16 for spans.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+    pub name: String,
+    pub trace_id: String,
+    pub span_id: String,
+    pub parent_span_id: Option<String>,
+    pub start_unix_nanos: u64,
+    pub end_unix_nanos: u64,
+    pub attributes: Attributes,
+    /// Why the operation failed, if it did
+    pub error: Option<String>,
+}
+
+impl Span {
+    /// A span starting (and, until [`end`](Self::end), ending) at `start_unix_nanos`
+    pub fn new(
+        name: impl Into<String>,
+        trace_id: impl Into<String>,
+        span_id: impl Into<String>,
+        start_unix_nanos: u64,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            trace_id: trace_id.into(),
+            span_id: span_id.into(),
+            parent_span_id: None,
+            start_unix_nanos,
+            end_unix_nanos: start_unix_nanos,
+            attributes: Vec::new(),
+            error: None,
+        }
+    }
+
+    pub fn with_parent(mut self, parent_span_id: Option<String>) -> Self {
+        self.parent_span_id = parent_span_id;
+        self
+    }
+
+    pub fn end(&mut self, end_unix_nanos: u64) {
+        self.end_unix_nanos = end_unix_nanos.max(self.start_unix_nanos);
+    }
+
+    pub fn set(&mut self, key: impl Into<String>, value: impl Into<AttributeValue>) {
+        let key = key.into();
+        let value = value.into();
+        match self
+            .attributes
+            .iter_mut()
+            .find(|(existing, _)| *existing == key)
+        {
+            Some((_, existing)) => *existing = value,
+            None => self.attributes.push((key, value)),
+        }
+    }
+
+    /// Record content under `key`, or only its length (`<key>.length`)
+    pub fn set_content(&mut self, key: &str, text: &str, policy: ContentPolicy) {
+        match policy {
+            ContentPolicy::Include => self.set(key, text),
+            ContentPolicy::Redact => self.set(format!("{}.length", key), text.chars().count()),
+        }
+    }
+
+    /// Mark the operation as failed
+    pub fn fail(&mut self, reason: impl Into<String>) {
+        self.error = Some(reason.into());
+    }
+
+    pub fn attribute(&self, key: &str) -> Option<&AttributeValue> {
+        self.attributes
+            .iter()
+            .find(|(existing, _)| existing == key)
+            .map(|(_, value)| value)
+    }
+
+    fn to_otlp(&self) -> Value {
+        let mut span = json!({
+            "traceId": self.trace_id,
+            "spanId": self.span_id,
+            "name": self.name,
+            "kind": SPAN_KIND_INTERNAL,
+            "startTimeUnixNano": self.start_unix_nanos.to_string(),
+            "endTimeUnixNano": self.end_unix_nanos.to_string(),
+            "attributes": attributes_to_otlp(&self.attributes),
+        });
+        if let Some(parent) = &self.parent_span_id {
+            span["parentSpanId"] = json!(parent);
+        }
+        if let Some(error) = &self.error {
+            span["status"] = json!({ "code": STATUS_CODE_ERROR, "message": error });
+        }
+        span
+    }
+}
+
+/// Cumulative counters, per name and attribute set
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Counters {
+    start_unix_nanos: u64,
+    values: BTreeMap<(String, Vec<(String, String)>), u64>,
+}
+
+impl Counters {
+    /// Counters counting from `start_unix_nanos`
+    pub fn new(start_unix_nanos: u64) -> Self {
+        Self {
+            start_unix_nanos,
+            values: BTreeMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, name: &str, attributes: &[(&str, &str)], value: u64) {
+        *self
+            .values
+            .entry(counter_key(name, attributes))
+            .or_default() += value;
+    }
+
+    /// The count for `name` with exactly `attributes`
+    pub fn get(&self, name: &str, attributes: &[(&str, &str)]) -> u64 {
+        self.values
+            .get(&counter_key(name, attributes))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+fn counter_key(name: &str, attributes: &[(&str, &str)]) -> (String, Vec<(String, String)>) {
+    let attributes = attributes
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+    (name.to_string(), attributes)
+}
+
+fn scope() -> Value {
+    json!({ "name": SCOPE_NAME, "version": env!("CARGO_PKG_VERSION") })
+}
+
+/// OTLP/JSON body exporting `spans` (`POST /v1/traces`)
+pub fn traces_request(resource: &[(String, AttributeValue)], spans: &[Span]) -> Value {
+    json!({
+        "resourceSpans": [{
+            "resource": { "attributes": attributes_to_otlp(resource) },
+            "scopeSpans": [{
+                "scope": scope(),
+                "spans": spans.iter().map(Span::to_otlp).collect::<Vec<_>>(),
+            }],
+        }],
+    })
+}
+
+/// OTLP/JSON body exporting `counters` as monotonic sums as of
+/// `now_unix_nanos` (`POST /v1/metrics`)
+pub fn metrics_request(
+    resource: &[(String, AttributeValue)],
+    counters: &Counters,
+    now_unix_nanos: u64,
+) -> Value {
+    let mut metrics: BTreeMap<&str, Vec<Value>> = BTreeMap::new();
+    for ((name, attributes), count) in &counters.values {
+        let attributes: Attributes = attributes
+            .iter()
+            .map(|(key, value)| (key.clone(), AttributeValue::from(value.as_str())))
+            .collect();
+        metrics.entry(name).or_default().push(json!({
+            "attributes": attributes_to_otlp(&attributes),
+            "startTimeUnixNano": counters.start_unix_nanos.to_string(),
+            "timeUnixNano": now_unix_nanos.to_string(),
+            "asInt": count.to_string(),
+        }));
+    }
+    let metrics: Vec<Value> = metrics
+        .into_iter()
+        .map(|(name, data_points)| {
+            json!({
+                "name": name,
+                "sum": {
+                    "aggregationTemporality": TEMPORALITY_CUMULATIVE,
+                    "isMonotonic": true,
+                    "dataPoints": data_points,
+                },
+            })
+        })
+        .collect();
+    json!({
+        "resourceMetrics": [{
+            "resource": { "attributes": attributes_to_otlp(resource) },
+            "scopeMetrics": [{ "scope": scope(), "metrics": metrics }],
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRACE: &str = "0af7651916cd43dd8448eb211c80319c";
+
+    #[test]
+    fn test_content_is_redacted_by_default() {
+        let mut span = Span::new(LLM_CALL_SPAN, TRACE, "b7ad6b7169203331", 10);
+        span.set_content("llm.prompt", "List the files", ContentPolicy::default());
+        assert_eq!(span.attribute("llm.prompt"), None);
+        assert_eq!(
+            span.attribute("llm.prompt.length"),
+            Some(&AttributeValue::Int(14))
+        );
+
+        span.set_content("llm.prompt", "List the files", ContentPolicy::Include);
+        assert_eq!(
+            span.attribute("llm.prompt"),
+            Some(&AttributeValue::from("List the files"))
+        );
+    }
+
+    #[test]
+    fn test_traces_request() {
+        let mut span = Span::new(TOOL_EXEC_SPAN, TRACE, "b7ad6b7169203331", 10)
+            .with_parent(Some("00f067aa0ba902b7".to_string()));
+        span.set("tool.name", "shell");
+        span.set("tool.exit_code", 1_i64);
+        span.fail("exit status 1");
+        span.end(5);
+
+        let body = traces_request(&[("service.name".to_string(), "agent".into())], &[span]);
+        let resource = &body["resourceSpans"][0];
+        assert_eq!(
+            resource["resource"]["attributes"][0]["value"]["stringValue"],
+            "agent"
+        );
+        let span = &resource["scopeSpans"][0]["spans"][0];
+        assert_eq!(span["name"], "tool.exec");
+        assert_eq!(span["parentSpanId"], "00f067aa0ba902b7");
+        assert_eq!(span["endTimeUnixNano"], "10");
+        assert_eq!(span["attributes"][1]["value"]["intValue"], "1");
+        assert_eq!(span["status"]["code"], 2);
+    }
+
+    #[test]
+    fn test_metrics_request() {
+        let mut counters = Counters::new(1);
+        counters.add("agent.tool.calls", &[("tool", "shell")], 1);
+        counters.add("agent.tool.calls", &[("tool", "shell")], 1);
+        counters.add("agent.tool.calls", &[("tool", "search")], 1);
+        assert_eq!(counters.get("agent.tool.calls", &[("tool", "shell")]), 2);
+
+        let body = metrics_request(&[], &counters, 9);
+        let metrics = &body["resourceMetrics"][0]["scopeMetrics"][0]["metrics"];
+        assert_eq!(metrics.as_array().unwrap().len(), 1);
+        let points = &metrics[0]["sum"]["dataPoints"];
+        assert_eq!(points[0]["attributes"][0]["value"]["stringValue"], "search");
+        assert_eq!(points[1]["asInt"], "2");
+        assert_eq!(points[1]["timeUnixNano"], "9");
+    }
+}
@@ -1,16 +1,123 @@
+use crate::prelude::*;
+use core::fmt;
 use serde::{Deserialize, Serialize};
 
+/// Identifies one tool, skill or artifact call within a run
+///
+/// [`process_model_output`](crate::agent::process_model_output) assigns it to
+/// each request the model emits; the result and both history messages carry
+/// it, so hosts match results to requests by id rather than by order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CallId(pub u64);
+
+impl fmt::Display for CallId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "call_{}", self.0)
+    }
+}
+
 /// A tool request parsed from model output
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ToolRequest {
     /// The tool name (e.g., "shell")
     pub tool: String,
 
+    /// The call this request belongs to (`None` until the agent assigns one)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub call_id: Option<CallId>,
+
     /// The command or parameters for the tool
     #[serde(flatten)]
     pub params: serde_json::Value,
 }
 
+/// Whether a path from a tool request is relative and stays inside the
+/// working directory (no absolute paths, drive letters or `..`)
+pub fn is_workspace_path(path: &str) -> bool {
+    !(path.is_empty()
+        || path.starts_with('/')
+        || path.starts_with('\\')
+        || path.contains(':')
+        || path.split(['/', '\\']).any(|part| part == ".."))
+}
+
+/// Execute a tool that agent-core implements itself
+///
+/// These tools are pure, so every host (native or WASM) can run them locally.
+/// Returns `None` for any other tool. Their manifests are in
+/// [`DEFAULT_TOOL_MANIFESTS`](crate::tool_manifest::DEFAULT_TOOL_MANIFESTS).
+pub fn execute_builtin_tool(request: &ToolRequest) -> Option<ToolResult> {
+    match request.tool.as_str() {
+        crate::math::TOOL_NAME => Some(crate::math::execute(request)),
+        _ => None,
+    }
+}
+
+/// What a tool's output holds
+///
+/// Lets guardrails and prompt rendering treat structured outputs differently
+/// from prose. Binary data is never inlined: the output of a `*_ref` result is
+/// a reference to it (an artifact id or path).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContentKind {
+    /// Prose or command output
+    #[default]
+    Text,
+    /// A JSON document
+    Json,
+    /// Rows and columns as text (e.g. `ls -l`, CSV)
+    Table,
+    /// A reference to binary data
+    BinaryRef,
+    /// A reference to an image
+    ImageRef,
+}
+
+impl ContentKind {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "text" | "plain" => Some(Self::Text),
+            "json" => Some(Self::Json),
+            "table" => Some(Self::Table),
+            "binary_ref" | "binary" => Some(Self::BinaryRef),
+            "image_ref" | "image" => Some(Self::ImageRef),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Text => "text",
+            Self::Json => "json",
+            Self::Table => "table",
+            Self::BinaryRef => "binary_ref",
+            Self::ImageRef => "image_ref",
+        }
+    }
+
+    pub fn is_text(&self) -> bool {
+        *self == Self::Text
+    }
+
+    /// Whether the output is a reference rather than the content itself
+    pub fn is_ref(&self) -> bool {
+        matches!(self, Self::BinaryRef | Self::ImageRef)
+    }
+
+    /// `output` as a person reads it: JSON pretty-printed
+    pub fn display(&self, output: &str) -> String {
+        match self {
+            Self::Json => serde_json::from_str::<serde_json::Value>(output)
+                .ok()
+                .and_then(|value| serde_json::to_string_pretty(&value).ok())
+                .unwrap_or_else(|| output.to_string()),
+            _ => output.to_string(),
+        }
+    }
+}
+
 /// The result of executing a tool
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolResult {
@@ -23,6 +130,14 @@ pub struct ToolResult {
     /// Optional error message
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
+
+    /// The call this result answers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub call_id: Option<CallId>,
+
+    /// What `output` holds
+    #[serde(default, skip_serializing_if = "ContentKind::is_text")]
+    pub content_kind: ContentKind,
 }
 
 impl ToolResult {
@@ -31,6 +146,8 @@ impl ToolResult {
             success: true,
             output: output.into(),
             error: None,
+            call_id: None,
+            content_kind: ContentKind::Text,
         }
     }
 
@@ -39,6 +156,30 @@ impl ToolResult {
             success: false,
             output: String::new(),
             error: Some(error.into()),
+            call_id: None,
+            content_kind: ContentKind::Text,
+        }
+    }
+
+    /// Mark the result as the answer to call `id`
+    pub fn with_call_id(mut self, id: Option<CallId>) -> Self {
+        self.call_id = id;
+        self
+    }
+
+    pub fn with_content_kind(mut self, kind: ContentKind) -> Self {
+        self.content_kind = kind;
+        self
+    }
+
+    /// The output as the model sees it: JSON compacted, references described
+    pub fn prompt_output(&self) -> String {
+        match self.content_kind {
+            ContentKind::Json => serde_json::from_str::<serde_json::Value>(&self.output)
+                .map_or_else(|_| self.output.clone(), |value| value.to_string()),
+            ContentKind::BinaryRef => format!("[binary data: {}]", self.output.trim()),
+            ContentKind::ImageRef => format!("[image: {}]", self.output.trim()),
+            ContentKind::Text | ContentKind::Table => self.output.clone(),
         }
     }
 }
@@ -0,0 +1,838 @@
+//! Tool manifest parsing (TOOL.md)
+//!
+//! Host tools are declared the same way skills are: a markdown file with YAML
+//! frontmatter describing the tool's name, parameters, safety class and example
+//! invocations (see [`crate::few_shot`]). Hosts discover these files to build
+//! the tool section of the system prompt and to check tool requests before
+//! executing them.
+//!
+//! ```text
+//! ---
+//! name: shell
+//! description: Execute shell commands
+//! safety: destructive
+//! params:
+//!   command:
+//!     type: string
+//!     description: The command to run
+//! examples:
+//!   - {"tool": "shell", "command": "ls -la"}
+//!   - call: {"tool": "shell", "command": "wc -l README.md"}
+//!     response: "42 README.md"
+//! ---
+//! ```
+//!
+//! Parameters declare the other names models give them (`aliases`). Before a
+//! request is checked, [`ToolManifest::coerce_request`] maps the shapes small
+//! models produce onto the declared ones: aliased names, parameters nested
+//! under `args`, arrays for strings (`["ls", "-la"]`), numbers as strings.
+//!
+//! Models often call a tool by a name they expect rather than the declared one
+//! (`bash` for `shell`). The registry's [`ToolAliases`] map such names to its
+//! tools, and can take close misspellings as the tool they spell.
+
+use crate::few_shot::FewShotExample;
+use crate::prelude::*;
+#[cfg(feature = "std")]
+use crate::skill_manifest::{split_frontmatter, FrontmatterError};
+use crate::tool::ToolRequest;
+use alloc::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// How much damage a tool can do, from harmless to irreversible
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SafetyClass {
+    /// Only reads local state (e.g. listing files)
+    ReadOnly,
+    /// Talks to the network
+    Network,
+    /// Changes local state in a recoverable way
+    Mutating,
+    /// Can cause irreversible changes (e.g. arbitrary shell commands)
+    Destructive,
+}
+
+impl SafetyClass {
+    pub const ALL: [SafetyClass; 4] = [
+        Self::ReadOnly,
+        Self::Network,
+        Self::Mutating,
+        Self::Destructive,
+    ];
+
+    /// Parse a safety class from string
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "read-only" => Some(Self::ReadOnly),
+            "network" => Some(Self::Network),
+            "mutating" => Some(Self::Mutating),
+            "destructive" => Some(Self::Destructive),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SafetyClass::ReadOnly => "read-only",
+            SafetyClass::Network => "network",
+            SafetyClass::Mutating => "mutating",
+            SafetyClass::Destructive => "destructive",
+        }
+    }
+}
+
+/// JSON type of a tool parameter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ParamType {
+    String,
+    Number,
+    Integer,
+    Boolean,
+    Array,
+    Object,
+}
+
+impl ParamType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ParamType::String => "string",
+            ParamType::Number => "number",
+            ParamType::Integer => "integer",
+            ParamType::Boolean => "boolean",
+            ParamType::Array => "array",
+            ParamType::Object => "object",
+        }
+    }
+
+    /// `value` as this type, when the conversion loses nothing
+    fn coerce(&self, value: &Value) -> Option<Value> {
+        let scalar = |value: &Value| match value {
+            Value::String(text) => Some(text.clone()),
+            Value::Number(_) | Value::Bool(_) => Some(value.to_string()),
+            _ => None,
+        };
+        match (self, value) {
+            (ParamType::String, Value::Array(items)) if !items.is_empty() => items
+                .iter()
+                .map(scalar)
+                .collect::<Option<Vec<_>>>()
+                .map(|items| Value::String(items.join(" "))),
+            (ParamType::String, _) => scalar(value).map(Value::String),
+            (ParamType::Number | ParamType::Integer, Value::String(text)) => {
+                let number: Value = serde_json::from_str(text.trim()).ok()?;
+                Some(number).filter(|number| self.matches(number))
+            }
+            (ParamType::Boolean, Value::String(text)) => match text.trim() {
+                "true" => Some(Value::Bool(true)),
+                "false" => Some(Value::Bool(false)),
+                _ => None,
+            },
+            (ParamType::Array, Value::Array(_)) => None,
+            (ParamType::Array, _) => Some(Value::Array(vec![value.clone()])),
+            _ => None,
+        }
+    }
+
+    /// Whether a JSON value has this type
+    pub fn matches(&self, value: &Value) -> bool {
+        match self {
+            ParamType::String => value.is_string(),
+            ParamType::Number => value.is_number(),
+            ParamType::Integer => value.is_i64() || value.is_u64(),
+            ParamType::Boolean => value.is_boolean(),
+            ParamType::Array => value.is_array(),
+            ParamType::Object => value.is_object(),
+        }
+    }
+}
+
+/// A declared tool parameter
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ToolParam {
+    #[serde(rename = "type")]
+    pub param_type: ParamType,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default = "default_required")]
+    pub required: bool,
+    /// Other names models give the parameter (`cmd` for `command`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+}
+
+fn default_required() -> bool {
+    true
+}
+
+/// Parsed frontmatter of a TOOL.md file
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ToolFrontmatter {
+    pub name: String,
+    pub description: String,
+    pub safety: SafetyClass,
+    #[serde(default)]
+    pub params: BTreeMap<String, ToolParam>,
+    /// Example invocations as the model would emit them, with their results
+    #[serde(default)]
+    pub examples: Vec<FewShotExample>,
+}
+
+/// Full tool manifest with body content
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolManifest {
+    pub frontmatter: ToolFrontmatter,
+    /// The body of TOOL.md after the frontmatter (left raw for host usage)
+    pub body: String,
+}
+
+impl ToolManifest {
+    /// Check a tool request against the declared parameters
+    ///
+    /// Missing required parameters, undeclared parameters and type mismatches
+    /// are rejected before the host executes anything.
+    pub fn check_request(&self, request: &ToolRequest) -> Result<(), ToolManifestError> {
+        let fm = &self.frontmatter;
+        if request.tool != fm.name {
+            return Err(ToolManifestError::UnknownTool(request.tool.clone()));
+        }
+
+        let empty = serde_json::Map::new();
+        let params = request.params.as_object().unwrap_or(&empty);
+
+        for (name, param) in &fm.params {
+            match params.get(name) {
+                Some(value) if !param.param_type.matches(value) => {
+                    return Err(ToolManifestError::InvalidParam(format!(
+                        "'{}' must be of type {}",
+                        name,
+                        param.param_type.as_str()
+                    )));
+                }
+                None if param.required => {
+                    return Err(ToolManifestError::InvalidParam(format!(
+                        "missing required parameter '{}'",
+                        name
+                    )));
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(name) = params.keys().find(|k| !fm.params.contains_key(*k)) {
+            return Err(ToolManifestError::InvalidParam(format!(
+                "unknown parameter '{}'",
+                name
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Map the parameters of `request` onto the declared ones, returning
+    /// what was changed
+    ///
+    /// Parameters nested under `args`, `arguments`, `parameters`, `params` or
+    /// `input` are lifted out, aliases are renamed, and values are converted
+    /// to the declared type where that is lossless: scalars to strings, arrays
+    /// of scalars to a string joined by spaces, numeric strings to numbers,
+    /// `"true"`/`"false"` to booleans and single values to arrays.
+    pub fn coerce_request(&self, request: &mut ToolRequest) -> Vec<String> {
+        let fm = &self.frontmatter;
+        let mut changes = Vec::new();
+        let Some(params) = request.params.as_object_mut() else {
+            return changes;
+        };
+
+        for wrapper in NESTING_KEYS {
+            if fm.params.contains_key(*wrapper) {
+                continue;
+            }
+            if let Some(Value::Object(nested)) = params.get(*wrapper).cloned() {
+                params.remove(*wrapper);
+                for (name, value) in nested {
+                    params.entry(name).or_insert(value);
+                }
+                changes.push(format!("lifted the parameters out of '{}'", wrapper));
+            }
+        }
+
+        for (name, param) in &fm.params {
+            if !params.contains_key(name) {
+                let alias = param
+                    .aliases
+                    .iter()
+                    .find(|alias| params.contains_key(*alias));
+                if let Some(alias) = alias {
+                    let value = params.remove(alias).expect("checked above");
+                    params.insert(name.clone(), value);
+                    changes.push(format!("took '{}' as '{}'", alias, name));
+                }
+            }
+            let Some(value) = params.get_mut(name) else {
+                continue;
+            };
+            if param.param_type.matches(value) {
+                continue;
+            }
+            if let Some(coerced) = param.param_type.coerce(value) {
+                changes.push(format!(
+                    "converted '{}' to {}",
+                    name,
+                    param.param_type.as_str()
+                ));
+                *value = coerced;
+            }
+        }
+        changes
+    }
+
+    /// JSON Schema for the tool's parameters
+    ///
+    /// Hosted APIs with native tool use take this as the tool's input schema.
+    pub fn input_schema(&self) -> Value {
+        let fm = &self.frontmatter;
+        let properties: serde_json::Map<String, Value> = fm
+            .params
+            .iter()
+            .map(|(name, param)| {
+                let mut property = serde_json::json!({ "type": param.param_type.as_str() });
+                if !param.description.is_empty() {
+                    property["description"] = Value::String(param.description.clone());
+                }
+                (name.clone(), property)
+            })
+            .collect();
+        let required: Vec<&String> = fm
+            .params
+            .iter()
+            .filter(|(_, param)| param.required)
+            .map(|(name, _)| name)
+            .collect();
+
+        serde_json::json!({
+            "type": "object",
+            "properties": properties,
+            "required": required,
+        })
+    }
+}
+
+/// Errors while parsing a tool manifest or checking a request against it
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum ToolManifestError {
+    #[error("missing frontmatter delimiter '---'")]
+    MissingDelimiter,
+    #[error("frontmatter not found")]
+    MissingFrontmatter,
+    #[error("failed to parse frontmatter: {0}")]
+    FrontmatterParse(String),
+    #[error("invalid example: {0}")]
+    InvalidExample(String),
+    #[error("unknown tool '{0}'")]
+    UnknownTool(String),
+    #[error("invalid parameters: {0}")]
+    InvalidParam(String),
+}
+
+#[cfg(feature = "std")]
+impl From<FrontmatterError> for ToolManifestError {
+    fn from(err: FrontmatterError) -> Self {
+        match err {
+            FrontmatterError::MissingDelimiter => Self::MissingDelimiter,
+            FrontmatterError::MissingFrontmatter => Self::MissingFrontmatter,
+        }
+    }
+}
+
+/// Parse a TOOL.md string into a manifest (frontmatter + body).
+/// Examples must themselves be valid requests for the tool.
+#[cfg(feature = "std")]
+pub fn parse_tool_manifest(markdown: &str) -> Result<ToolManifest, ToolManifestError> {
+    let (frontmatter_raw, body) = split_frontmatter(markdown)?;
+
+    let frontmatter: ToolFrontmatter = serde_yaml::from_str(&frontmatter_raw)
+        .map_err(|e| ToolManifestError::FrontmatterParse(e.to_string()))?;
+
+    let manifest = ToolManifest { frontmatter, body };
+
+    for example in &manifest.frontmatter.examples {
+        let request: ToolRequest = serde_json::from_value(example.call.clone())
+            .map_err(|e| ToolManifestError::InvalidExample(e.to_string()))?;
+        manifest
+            .check_request(&request)
+            .map_err(|e| ToolManifestError::InvalidExample(e.to_string()))?;
+    }
+
+    Ok(manifest)
+}
+
+/// Manifests of the tools agent-core executes itself
+/// (see [`execute_builtin_tool`](crate::tool::execute_builtin_tool))
+pub const DEFAULT_TOOL_MANIFESTS: &[&str] = &[crate::math::MANIFEST];
+
+/// Keys models nest a call's parameters under
+const NESTING_KEYS: &[&str] = &["args", "arguments", "parameters", "params", "input"];
+
+/// Names models use for tools, and the tool each one means
+pub const DEFAULT_TOOL_ALIASES: &[(&str, &str)] = &[
+    ("bash", "shell"),
+    ("sh", "shell"),
+    ("zsh", "shell"),
+    ("terminal", "shell"),
+    ("cmd", "shell"),
+    ("command", "shell"),
+    ("run", "shell"),
+    ("run_command", "shell"),
+    ("exec", "shell"),
+    ("execute", "shell"),
+    ("fetch", "http_get"),
+    ("curl", "http_get"),
+    ("http", "http_get"),
+    ("wget", "http_get"),
+    ("calculator", "eval_math"),
+    ("calc", "eval_math"),
+    ("math", "eval_math"),
+    ("grep", "search"),
+    ("find", "search"),
+    ("patch", "apply_patch"),
+    ("edit", "apply_patch"),
+    ("query", "sql"),
+];
+
+/// Misspellings within this edit distance are suggested to the model
+pub const SUGGEST_DISTANCE: usize = 2;
+
+/// How tool names a model gets wrong map to the tools it means
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ToolAliases {
+    /// Guessed name to tool name
+    pub aliases: BTreeMap<String, String>,
+    /// Misspellings within this edit distance of exactly one tool are taken
+    /// as that tool; `None` only suggests them
+    pub max_distance: Option<usize>,
+}
+
+impl Default for ToolAliases {
+    /// [`DEFAULT_TOOL_ALIASES`], without misspellings
+    fn default() -> Self {
+        Self {
+            aliases: DEFAULT_TOOL_ALIASES
+                .iter()
+                .map(|(alias, tool)| (alias.to_string(), tool.to_string()))
+                .collect(),
+            max_distance: None,
+        }
+    }
+}
+
+impl ToolAliases {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take `alias` as `tool`
+    pub fn with_alias(mut self, alias: impl Into<String>, tool: impl Into<String>) -> Self {
+        self.aliases
+            .insert(alias.into().to_lowercase(), tool.into());
+        self
+    }
+
+    /// Take misspellings within `max_distance` as the tool they spell
+    pub fn with_max_distance(mut self, max_distance: Option<usize>) -> Self {
+        self.max_distance = max_distance;
+        self
+    }
+
+    /// The tool among `tools` that `name` stands for: an alias, or a
+    /// misspelling within `max_distance`
+    pub fn resolve<'a>(&self, name: &str, tools: &[&'a str]) -> Option<&'a str> {
+        self.closest(name, tools, self.max_distance.unwrap_or(0))
+    }
+
+    /// The tool among `tools` that `name` most likely means, to suggest
+    pub fn suggest<'a>(&self, name: &str, tools: &[&'a str]) -> Option<&'a str> {
+        self.closest(
+            name,
+            tools,
+            SUGGEST_DISTANCE.max(self.max_distance.unwrap_or(0)),
+        )
+    }
+
+    fn closest<'a>(&self, name: &str, tools: &[&'a str], max_distance: usize) -> Option<&'a str> {
+        let name = name.to_lowercase();
+        if let Some(meant) = self.aliases.get(&name) {
+            if let Some(tool) = tools.iter().find(|tool| *tool == meant) {
+                return Some(tool);
+            }
+        }
+        if max_distance == 0 {
+            return None;
+        }
+
+        // A misspelling: close to one tool only, and not most of its name
+        let mut matches: Vec<(usize, &'a str)> = tools
+            .iter()
+            .map(|tool| (edit_distance(&name, tool), *tool))
+            .filter(|(distance, tool)| *distance <= max_distance && *distance < tool.len() / 2)
+            .collect();
+        matches.sort();
+        match matches.as_slice() {
+            [(_, tool)] => Some(tool),
+            [(best, tool), (next, _), ..] if best < next => Some(tool),
+            _ => None,
+        }
+    }
+}
+
+/// Levenshtein distance between two names
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = (above + 1)
+                .min(row[j] + 1)
+                .min(diagonal + usize::from(ca != *cb));
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+/// Tools available to the agent, keyed by name
+#[derive(Debug, Clone, Default)]
+pub struct ToolRegistry {
+    tools: BTreeMap<String, ToolManifest>,
+    aliases: ToolAliases,
+}
+
+impl ToolRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A registry holding the default tools (see [`DEFAULT_TOOL_MANIFESTS`])
+    #[cfg(feature = "std")]
+    pub fn with_default_tools() -> Self {
+        let mut registry = Self::new();
+        for markdown in DEFAULT_TOOL_MANIFESTS {
+            let manifest = parse_tool_manifest(markdown).expect("default tool manifest is valid");
+            registry.register(manifest);
+        }
+        registry
+    }
+
+    /// Register a tool, replacing any earlier tool with the same name
+    pub fn register(&mut self, manifest: ToolManifest) {
+        self.tools
+            .insert(manifest.frontmatter.name.clone(), manifest);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ToolManifest> {
+        self.tools.get(name)
+    }
+
+    /// Map wrong tool names with `aliases` instead of the defaults
+    pub fn with_aliases(mut self, aliases: ToolAliases) -> Self {
+        self.aliases = aliases;
+        self
+    }
+
+    pub fn aliases(&self) -> &ToolAliases {
+        &self.aliases
+    }
+
+    /// The registered tool `name` stands for (itself, an alias or a
+    /// misspelling the aliases accept)
+    pub fn resolve(&self, name: &str) -> Option<&str> {
+        if let Some((name, _)) = self.tools.get_key_value(name) {
+            return Some(name);
+        }
+        let names: Vec<&str> = self.tools.keys().map(String::as_str).collect();
+        self.aliases.resolve(name, &names)
+    }
+
+    /// Keep only the named tools; fails on a name that is not registered
+    pub fn retain(&mut self, names: &[String]) -> Result<(), ToolManifestError> {
+        if let Some(unknown) = names.iter().find(|name| !self.tools.contains_key(*name)) {
+            return Err(ToolManifestError::UnknownTool(unknown.clone()));
+        }
+        self.tools.retain(|name, _| names.contains(name));
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tools.is_empty()
+    }
+
+    /// Registered tools, sorted by name
+    pub fn tools(&self) -> impl Iterator<Item = &ToolManifest> {
+        self.tools.values()
+    }
+
+    /// Map the request's parameters onto its tool's (see
+    /// [`ToolManifest::coerce_request`]); nothing changes for unknown tools
+    pub fn coerce_request(&self, request: &mut ToolRequest) -> Vec<String> {
+        match self.get(&request.tool) {
+            Some(manifest) => manifest.coerce_request(request),
+            None => Vec::new(),
+        }
+    }
+
+    /// Look up the tool for a request and check its parameters
+    pub fn check_request(&self, request: &ToolRequest) -> Result<&ToolManifest, ToolManifestError> {
+        let manifest = self
+            .get(&request.tool)
+            .ok_or_else(|| ToolManifestError::UnknownTool(request.tool.clone()))?;
+        manifest.check_request(request)?;
+        Ok(manifest)
+    }
+
+    /// The "Available tools" section of the system prompt
+    pub fn build_prompt(&self) -> String {
+        let mut out = String::from("Available tools:\n");
+
+        for tool in self.tools() {
+            let fm = &tool.frontmatter;
+            out.push_str(&format!("- {}: {}\n", fm.name, fm.description));
+            for (name, param) in &fm.params {
+                out.push_str(&format!(
+                    "    {} ({}{}): {}\n",
+                    name,
+                    param.param_type.as_str(),
+                    if param.required { "" } else { ", optional" },
+                    param.description
+                ));
+            }
+        }
+
+        out.trim_end().to_string()
+    }
+
+    /// Each tool's examples, for [`FewShotPolicy::build_prompt`](crate::few_shot::FewShotPolicy::build_prompt)
+    pub fn examples(&self) -> Vec<&[FewShotExample]> {
+        self.tools()
+            .map(|tool| tool.frontmatter.examples.as_slice())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHELL: &str = r#"---
+name: shell
+description: Execute shell commands
+safety: destructive
+params:
+  command:
+    type: string
+    description: The command to run
+  timeout:
+    type: integer
+    required: false
+examples:
+  - {"tool": "shell", "command": "ls -la"}
+  - call: {"tool": "shell", "command": "pwd", "timeout": 5}
+    response: "/home/agent"
+---
+# Shell
+"#;
+
+    fn request(json: &str) -> ToolRequest {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_parse_tool_manifest() {
+        let manifest = parse_tool_manifest(SHELL).unwrap();
+        let fm = &manifest.frontmatter;
+        assert_eq!(fm.name, "shell");
+        assert_eq!(fm.safety, SafetyClass::Destructive);
+        assert!(fm.params["command"].required);
+        assert!(!fm.params["timeout"].required);
+        assert_eq!(fm.examples.len(), 2);
+        assert_eq!(
+            fm.examples[1].response,
+            Some(Value::String("/home/agent".to_string()))
+        );
+        assert_eq!(manifest.body, "# Shell");
+    }
+
+    #[test]
+    fn test_check_request() {
+        let manifest = parse_tool_manifest(SHELL).unwrap();
+        assert!(manifest
+            .check_request(&request(
+                r#"{"tool": "shell", "command": "ls", "timeout": 5}"#
+            ))
+            .is_ok());
+
+        for bad in [
+            r#"{"tool": "shell"}"#,
+            r#"{"tool": "shell", "command": 42}"#,
+            r#"{"tool": "shell", "command": "ls", "cwd": "/"}"#,
+            r#"{"tool": "shell", "command": "ls", "timeout": 1.5}"#,
+        ] {
+            assert!(matches!(
+                manifest.check_request(&request(bad)),
+                Err(ToolManifestError::InvalidParam(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_input_schema() {
+        let schema = parse_tool_manifest(SHELL).unwrap().input_schema();
+        assert_eq!(schema["type"], "object");
+        assert_eq!(schema["properties"]["command"]["type"], "string");
+        assert_eq!(schema["properties"]["timeout"]["type"], "integer");
+        assert_eq!(schema["required"], serde_json::json!(["command"]));
+    }
+
+    #[test]
+    fn test_invalid_example_rejected() {
+        let markdown = SHELL.replace(r#""command": "ls -la""#, r#""cmd": "ls -la""#);
+        assert!(matches!(
+            parse_tool_manifest(&markdown),
+            Err(ToolManifestError::InvalidExample(_))
+        ));
+        let markdown = SHELL.replace(r#""timeout": 5"#, r#""timeout": "5""#);
+        assert!(matches!(
+            parse_tool_manifest(&markdown),
+            Err(ToolManifestError::InvalidExample(_))
+        ));
+        assert!(matches!(
+            parse_tool_manifest("---\nname: x\ndescription: y\n---\n"),
+            Err(ToolManifestError::FrontmatterParse(_))
+        ));
+    }
+
+    #[test]
+    fn test_registry_prompt_and_lookup() {
+        let mut registry = ToolRegistry::new();
+        registry.register(parse_tool_manifest(SHELL).unwrap());
+
+        let prompt = registry.build_prompt();
+        assert!(prompt.starts_with("Available tools:\n- shell: Execute shell commands"));
+        assert!(prompt.contains("timeout (integer, optional)"));
+        assert!(!prompt.contains("ls -la"));
+        assert_eq!(registry.examples()[0].len(), 2);
+
+        assert!(registry
+            .check_request(&request(r#"{"tool": "shell", "command": "ls"}"#))
+            .is_ok());
+        assert_eq!(
+            registry
+                .check_request(&request(r#"{"tool": "fetch", "url": "x"}"#))
+                .unwrap_err(),
+            ToolManifestError::UnknownTool("fetch".to_string())
+        );
+    }
+
+    #[test]
+    fn test_default_tools() {
+        let registry = ToolRegistry::with_default_tools();
+        let manifest = registry.get("eval_math").unwrap();
+        assert_eq!(manifest.frontmatter.safety, SafetyClass::ReadOnly);
+
+        let call = request(r#"{"tool": "eval_math", "expression": "2 + 2"}"#);
+        assert!(registry.check_request(&call).is_ok());
+        assert_eq!(
+            crate::tool::execute_builtin_tool(&call).unwrap().output,
+            "4"
+        );
+        assert!(crate::tool::execute_builtin_tool(&request(r#"{"tool": "shell"}"#)).is_none());
+    }
+
+    #[test]
+    fn test_retain() {
+        let mut registry = ToolRegistry::with_default_tools();
+        registry.register(parse_tool_manifest(SHELL).unwrap());
+
+        assert_eq!(
+            registry.retain(&["shell".to_string(), "fetch".to_string()]),
+            Err(ToolManifestError::UnknownTool("fetch".to_string()))
+        );
+        assert!(registry.get("eval_math").is_some());
+
+        registry.retain(&["shell".to_string()]).unwrap();
+        let names: Vec<_> = registry
+            .tools()
+            .map(|tool| &tool.frontmatter.name)
+            .collect();
+        assert_eq!(names, ["shell"]);
+    }
+
+    #[test]
+    fn test_resolve_tool_names() {
+        let mut registry = ToolRegistry::with_default_tools();
+        registry.register(parse_tool_manifest(SHELL).unwrap());
+        assert_eq!(registry.resolve("shell"), Some("shell"));
+        assert_eq!(registry.resolve("Bash"), Some("shell"));
+        assert_eq!(registry.resolve("calculator"), Some("eval_math"));
+        // Aliases of tools the registry lacks, and misspellings by default
+        assert_eq!(registry.resolve("curl"), None);
+        assert_eq!(registry.resolve("shel"), None);
+
+        let registry = registry.with_aliases(
+            ToolAliases::new()
+                .with_alias("python", "eval_math")
+                .with_max_distance(Some(1)),
+        );
+        assert_eq!(registry.resolve("python"), Some("eval_math"));
+        assert_eq!(registry.resolve("shel"), Some("shell"));
+        assert_eq!(registry.resolve("shll_x"), None);
+        assert_eq!(
+            registry
+                .aliases()
+                .suggest("eval_mth", &["eval_math", "shell"]),
+            Some("eval_math")
+        );
+        // Equally close to two tools is no match
+        assert_eq!(ToolAliases::new().suggest("cat", &["bat", "hat"]), None);
+    }
+    #[test]
+    fn test_coerce_request() {
+        let markdown = SHELL.replace(
+            "    description: The command to run\n",
+            "    description: The command to run\n    aliases: [cmd]\n",
+        );
+        let manifest = parse_tool_manifest(&markdown).unwrap();
+        let coerce = |json: &str| {
+            let mut request = request(json);
+            let changes = manifest.coerce_request(&mut request);
+            assert!(manifest.check_request(&request).is_ok(), "{}", json);
+            (request.params, changes.len())
+        };
+
+        let expected = serde_json::json!({"command": "ls -la", "timeout": 5});
+        for (json, changes) in [
+            (
+                r#"{"tool": "shell", "command": ["ls", "-la"], "timeout": "5"}"#,
+                2,
+            ),
+            (r#"{"tool": "shell", "cmd": "ls -la", "timeout": 5}"#, 1),
+            (
+                r#"{"tool": "shell", "args": {"command": "ls -la", "timeout": 5}}"#,
+                1,
+            ),
+        ] {
+            assert_eq!(coerce(json), (expected.clone(), changes));
+        }
+        assert_eq!(coerce(r#"{"tool": "shell", "command": "ls"}"#).1, 0);
+
+        // Conversions that would lose something are left to the check
+        let mut lossy = request(r#"{"tool": "shell", "command": "ls", "timeout": "1.5"}"#);
+        assert!(manifest.coerce_request(&mut lossy).is_empty());
+        assert!(manifest.check_request(&lossy).is_err());
+    }
+}
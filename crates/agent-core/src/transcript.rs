@@ -0,0 +1,602 @@
+//! Conversation-format adapters
+//!
+//! [`ChatTranscript`] is the structured view of [`AgentState::history`] that
+//! backends render into their wire format, instead of each one re-deriving
+//! tool calls from raw history text.
+//!
+//! Role mapping rules:
+//! - `User` messages are user turns.
+//! - An `Assistant` message that parses as a call to one of the native tools,
+//!   and is directly followed by a `Tool` message, becomes a tool call; the
+//!   `Tool` message becomes its result. Calls without a result (their output
+//!   was rejected by a guardrail) stay assistant text, since chat APIs require
+//!   every tool call to be answered.
+//! - Any other `Assistant` message is assistant text.
+//! - A `Tool` message without a preceding call (skill output, or tool output
+//!   when calls stay text) is host output, sent to chat APIs as a user turn.
+//!
+//! Plain text is render-only: it is the prompt format for completion models.
+//!
+//! [`AgentState::history`]: crate::agent::AgentState::history
+
+use crate::agent::{Message, Role};
+use crate::participants::attributed;
+use crate::prelude::*;
+use crate::protocol::{format_tool_call, parse_model_output, ParseResult};
+use crate::tool::ToolRequest;
+use serde_json::{json, Value};
+
+/// Prefix of tool messages recording a failed tool call
+const TOOL_FAILED_PREFIX: &str = "Tool failed";
+
+/// One entry of a transcript
+#[derive(Debug, Clone, PartialEq)]
+pub enum TranscriptEntry {
+    User(String),
+    Assistant(String),
+    /// The model called a tool
+    ToolCall {
+        id: String,
+        request: ToolRequest,
+    },
+    /// Host output not tied to a native tool call (e.g. a skill result)
+    ToolOutput(String),
+    /// The host answered a tool call
+    ToolResult {
+        id: String,
+        tool: String,
+        content: String,
+        is_error: bool,
+    },
+}
+
+/// Conversation with an optional system prompt, convertible between formats
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChatTranscript {
+    system: Option<String>,
+    entries: Vec<TranscriptEntry>,
+}
+
+/// Errors while reading a wire-format conversation
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum TranscriptError {
+    #[error("Unknown role '{0}'")]
+    UnknownRole(String),
+
+    #[error("Invalid message: {0}")]
+    InvalidMessage(String),
+}
+
+impl ChatTranscript {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build a transcript from agent history
+    ///
+    /// Calls to `native_tools` become tool call entries (see the module docs);
+    /// pass no tools to keep every message as text.
+    pub fn from_history(history: &[Message], native_tools: &[&str]) -> Self {
+        let mut entries = Vec::with_capacity(history.len());
+        let mut pending: Option<(String, String)> = None;
+
+        for (index, message) in history.iter().enumerate() {
+            let entry = match &message.role {
+                Role::User => TranscriptEntry::User(message.content.clone()),
+                // Render named agents for a speaker first (`Participants::apply`);
+                // left as they are, they read as attributed user turns
+                Role::Agent(name) => TranscriptEntry::User(attributed(name, &message.content)),
+                Role::Assistant => {
+                    let answered = history
+                        .get(index + 1)
+                        .is_some_and(|next| matches!(next.role, Role::Tool));
+                    match parse_model_output(&message.content) {
+                        ParseResult::ToolCall(request)
+                            if answered && native_tools.contains(&request.tool.as_str()) =>
+                        {
+                            let id = message
+                                .call_id
+                                .map_or_else(|| format!("call_{}", index), |id| id.to_string());
+                            pending = Some((id.clone(), request.tool.clone()));
+                            TranscriptEntry::ToolCall { id, request }
+                        }
+                        _ => TranscriptEntry::Assistant(message.content.clone()),
+                    }
+                }
+                Role::Tool => match pending.take() {
+                    Some((id, tool)) => TranscriptEntry::ToolResult {
+                        id,
+                        tool,
+                        content: message.content.clone(),
+                        is_error: message.content.starts_with(TOOL_FAILED_PREFIX),
+                    },
+                    None => TranscriptEntry::ToolOutput(message.content.clone()),
+                },
+            };
+            entries.push(entry);
+        }
+
+        Self {
+            system: None,
+            entries,
+        }
+    }
+
+    pub fn with_system(mut self, system: impl Into<String>) -> Self {
+        self.system = Some(system.into());
+        self
+    }
+
+    pub fn with_entry(mut self, entry: TranscriptEntry) -> Self {
+        self.entries.push(entry);
+        self
+    }
+
+    pub fn system(&self) -> Option<&str> {
+        self.system.as_deref()
+    }
+
+    pub fn entries(&self) -> &[TranscriptEntry] {
+        &self.entries
+    }
+
+    /// Convert back to agent history
+    ///
+    /// Tool calls are rendered in the agent's JSON protocol; the system prompt
+    /// is not part of history and is dropped.
+    pub fn into_history(self) -> Vec<Message> {
+        self.entries
+            .into_iter()
+            .map(|entry| match entry {
+                TranscriptEntry::User(content) => Message {
+                    role: Role::User,
+                    content,
+                    call_id: None,
+                    reasoning: None,
+                },
+                TranscriptEntry::Assistant(content) => Message {
+                    role: Role::Assistant,
+                    content,
+                    call_id: None,
+                    reasoning: None,
+                },
+                TranscriptEntry::ToolCall { request, .. } => Message {
+                    role: Role::Assistant,
+                    content: format_tool_call(&request),
+                    call_id: request.call_id,
+                    reasoning: None,
+                },
+                TranscriptEntry::ToolOutput(content)
+                | TranscriptEntry::ToolResult { content, .. } => Message {
+                    role: Role::Tool,
+                    content,
+                    call_id: None,
+                    reasoning: None,
+                },
+            })
+            .collect()
+    }
+
+    /// Render as a completion prompt (`User: ...`, `Assistant: ...`)
+    pub fn to_plain_text(&self) -> String {
+        let mut text = String::new();
+        if let Some(system) = &self.system {
+            text.push_str(system);
+            text.push_str("\n\n");
+        }
+
+        for entry in &self.entries {
+            match entry {
+                TranscriptEntry::User(content) => {
+                    text.push_str("User: ");
+                    text.push_str(content);
+                }
+                TranscriptEntry::Assistant(content) => {
+                    text.push_str("Assistant: ");
+                    text.push_str(content);
+                }
+                TranscriptEntry::ToolCall { request, .. } => {
+                    text.push_str("Assistant: ");
+                    text.push_str(&format_tool_call(request));
+                }
+                TranscriptEntry::ToolOutput(content)
+                | TranscriptEntry::ToolResult { content, .. } => text.push_str(content),
+            }
+            text.push_str("\n\n");
+        }
+
+        text
+    }
+
+    /// OpenAI chat completions `messages` array, including the system message
+    pub fn to_openai(&self) -> Vec<Value> {
+        let mut messages = Vec::with_capacity(self.entries.len() + 1);
+        if let Some(system) = &self.system {
+            messages.push(json!({ "role": "system", "content": system }));
+        }
+
+        for entry in &self.entries {
+            messages.push(match entry {
+                TranscriptEntry::User(content) | TranscriptEntry::ToolOutput(content) => {
+                    json!({ "role": "user", "content": content })
+                }
+                TranscriptEntry::Assistant(content) => {
+                    json!({ "role": "assistant", "content": content })
+                }
+                TranscriptEntry::ToolCall { id, request } => json!({
+                    "role": "assistant",
+                    "content": null,
+                    "tool_calls": [{
+                        "id": id,
+                        "type": "function",
+                        "function": {
+                            "name": request.tool,
+                            "arguments": request.params.to_string(),
+                        },
+                    }],
+                }),
+                TranscriptEntry::ToolResult { id, content, .. } => {
+                    json!({ "role": "tool", "tool_call_id": id, "content": content })
+                }
+            });
+        }
+
+        messages
+    }
+
+    /// Read an OpenAI chat completions `messages` array
+    pub fn from_openai(messages: &[Value]) -> Result<Self, TranscriptError> {
+        let mut transcript = Self::new();
+        let mut call_names: Vec<(String, String)> = Vec::new();
+
+        for message in messages {
+            let role = str_field(message, "role")?;
+            let content = block_text(message.get("content"));
+            let content = content.as_str();
+
+            match role {
+                "system" | "developer" => transcript.system = Some(content.to_string()),
+                "user" => transcript
+                    .entries
+                    .push(TranscriptEntry::User(content.to_string())),
+                "assistant" => {
+                    if !content.is_empty() {
+                        transcript
+                            .entries
+                            .push(TranscriptEntry::Assistant(content.to_string()));
+                    }
+                    let calls = message
+                        .get("tool_calls")
+                        .and_then(Value::as_array)
+                        .map(Vec::as_slice)
+                        .unwrap_or_default();
+                    for call in calls {
+                        let id = str_field(call, "id")?.to_string();
+                        let function = call.get("function").ok_or_else(|| {
+                            TranscriptError::InvalidMessage("tool call without function".into())
+                        })?;
+                        let name = str_field(function, "name")?.to_string();
+                        let params = serde_json::from_str(str_field(function, "arguments")?)
+                            .map_err(|e| TranscriptError::InvalidMessage(e.to_string()))?;
+                        call_names.push((id.clone(), name.clone()));
+                        transcript.entries.push(TranscriptEntry::ToolCall {
+                            id,
+                            request: ToolRequest {
+                                tool: name,
+                                call_id: None,
+                                params,
+                            },
+                        });
+                    }
+                }
+                "tool" => {
+                    let id = str_field(message, "tool_call_id")?.to_string();
+                    let tool = call_names
+                        .iter()
+                        .find(|(call_id, _)| *call_id == id)
+                        .map(|(_, name)| name.clone())
+                        .unwrap_or_default();
+                    transcript.entries.push(TranscriptEntry::ToolResult {
+                        id,
+                        tool,
+                        content: content.to_string(),
+                        is_error: content.starts_with(TOOL_FAILED_PREFIX),
+                    });
+                }
+                other => return Err(TranscriptError::UnknownRole(other.to_string())),
+            }
+        }
+
+        Ok(transcript)
+    }
+
+    /// Anthropic Messages API `messages` array (the system prompt is sent
+    /// separately, see [`system`](Self::system))
+    pub fn to_anthropic(&self) -> Vec<Value> {
+        self.entries
+            .iter()
+            .map(|entry| match entry {
+                TranscriptEntry::User(content) | TranscriptEntry::ToolOutput(content) => {
+                    json!({ "role": "user", "content": content })
+                }
+                TranscriptEntry::Assistant(content) => {
+                    json!({ "role": "assistant", "content": content })
+                }
+                TranscriptEntry::ToolCall { id, request } => json!({
+                    "role": "assistant",
+                    "content": [{
+                        "type": "tool_use",
+                        "id": id,
+                        "name": request.tool,
+                        "input": request.params,
+                    }],
+                }),
+                TranscriptEntry::ToolResult {
+                    id,
+                    content,
+                    is_error,
+                    ..
+                } => json!({
+                    "role": "user",
+                    "content": [{
+                        "type": "tool_result",
+                        "tool_use_id": id,
+                        "content": content,
+                        "is_error": is_error,
+                    }],
+                }),
+            })
+            .collect()
+    }
+
+    /// Read an Anthropic Messages API conversation
+    pub fn from_anthropic(
+        system: Option<&str>,
+        messages: &[Value],
+    ) -> Result<Self, TranscriptError> {
+        let mut transcript = Self {
+            system: system.map(str::to_string),
+            entries: Vec::new(),
+        };
+        let mut call_names: Vec<(String, String)> = Vec::new();
+
+        for message in messages {
+            let role = str_field(message, "role")?;
+            if role != "user" && role != "assistant" {
+                return Err(TranscriptError::UnknownRole(role.to_string()));
+            }
+
+            let blocks = match message.get("content") {
+                Some(Value::String(text)) => vec![json!({ "type": "text", "text": text })],
+                Some(Value::Array(blocks)) => blocks.clone(),
+                _ => {
+                    return Err(TranscriptError::InvalidMessage(
+                        "content must be a string or an array of blocks".into(),
+                    ))
+                }
+            };
+
+            for block in &blocks {
+                let entry = match str_field(block, "type")? {
+                    "text" => {
+                        let text = str_field(block, "text")?.to_string();
+                        if role == "user" {
+                            TranscriptEntry::User(text)
+                        } else {
+                            TranscriptEntry::Assistant(text)
+                        }
+                    }
+                    "tool_use" => {
+                        let id = str_field(block, "id")?.to_string();
+                        let name = str_field(block, "name")?.to_string();
+                        call_names.push((id.clone(), name.clone()));
+                        TranscriptEntry::ToolCall {
+                            id,
+                            request: ToolRequest {
+                                tool: name,
+                                call_id: None,
+                                params: block.get("input").cloned().unwrap_or(json!({})),
+                            },
+                        }
+                    }
+                    "tool_result" => {
+                        let id = str_field(block, "tool_use_id")?.to_string();
+                        let tool = call_names
+                            .iter()
+                            .find(|(call_id, _)| *call_id == id)
+                            .map(|(_, name)| name.clone())
+                            .unwrap_or_default();
+                        TranscriptEntry::ToolResult {
+                            id,
+                            tool,
+                            content: block_text(block.get("content")),
+                            is_error: block
+                                .get("is_error")
+                                .and_then(Value::as_bool)
+                                .unwrap_or(false),
+                        }
+                    }
+                    // Images, thinking blocks etc. have no history equivalent
+                    _ => continue,
+                };
+                transcript.entries.push(entry);
+            }
+        }
+
+        Ok(transcript)
+    }
+
+    /// Gemini `contents` array (the system prompt is sent separately as
+    /// `systemInstruction`)
+    pub fn to_gemini(&self) -> Vec<Value> {
+        self.entries
+            .iter()
+            .map(|entry| match entry {
+                TranscriptEntry::User(content) | TranscriptEntry::ToolOutput(content) => {
+                    json!({ "role": "user", "parts": [{ "text": content }] })
+                }
+                TranscriptEntry::Assistant(content) => {
+                    json!({ "role": "model", "parts": [{ "text": content }] })
+                }
+                TranscriptEntry::ToolCall { request, .. } => json!({
+                    "role": "model",
+                    "parts": [{
+                        "functionCall": { "name": request.tool, "args": request.params },
+                    }],
+                }),
+                TranscriptEntry::ToolResult { tool, content, .. } => json!({
+                    "role": "user",
+                    "parts": [{
+                        "functionResponse": {
+                            "name": tool,
+                            "response": { "content": content },
+                        },
+                    }],
+                }),
+            })
+            .collect()
+    }
+}
+
+fn str_field<'a>(value: &'a Value, field: &str) -> Result<&'a str, TranscriptError> {
+    value
+        .get(field)
+        .and_then(Value::as_str)
+        .ok_or_else(|| TranscriptError::InvalidMessage(format!("missing '{}'", field)))
+}
+
+/// Text of a message's or tool result's content: a string or an array of
+/// text blocks (other blocks, such as images, are left out)
+fn block_text(content: Option<&Value>) -> String {
+    match content {
+        Some(Value::String(text)) => text.clone(),
+        Some(Value::Array(blocks)) => blocks
+            .iter()
+            .filter_map(|block| block.get("text").and_then(Value::as_str))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(role: Role, content: &str) -> Message {
+        Message {
+            role,
+            content: content.to_string(),
+            call_id: None,
+            reasoning: None,
+        }
+    }
+
+    fn history() -> Vec<Message> {
+        vec![
+            message(Role::User, "List files"),
+            message(Role::Assistant, r#"{"tool": "shell", "command": "ls"}"#),
+            message(Role::Tool, "Tool output:\na.txt"),
+            message(Role::Assistant, "There is one file: a.txt"),
+        ]
+    }
+
+    #[test]
+    fn test_from_history_role_mapping() {
+        let transcript = ChatTranscript::from_history(&history(), &["shell"]);
+        let entries = transcript.entries();
+        assert_eq!(entries[0], TranscriptEntry::User("List files".to_string()));
+        assert!(
+            matches!(&entries[1], TranscriptEntry::ToolCall { id, request }
+            if id == "call_1" && request.tool == "shell")
+        );
+        assert_eq!(
+            entries[2],
+            TranscriptEntry::ToolResult {
+                id: "call_1".to_string(),
+                tool: "shell".to_string(),
+                content: "Tool output:\na.txt".to_string(),
+                is_error: false,
+            }
+        );
+
+        // Without native tools, or without a result, calls stay text
+        let text_only = ChatTranscript::from_history(&history(), &[]);
+        assert!(matches!(
+            text_only.entries()[1],
+            TranscriptEntry::Assistant(_)
+        ));
+        assert!(matches!(
+            text_only.entries()[2],
+            TranscriptEntry::ToolOutput(_)
+        ));
+        let unanswered = ChatTranscript::from_history(&history()[..2], &["shell"]);
+        assert!(matches!(
+            unanswered.entries()[1],
+            TranscriptEntry::Assistant(_)
+        ));
+    }
+
+    #[test]
+    fn test_plain_text() {
+        let text = ChatTranscript::from_history(&history(), &[])
+            .with_system("System")
+            .to_plain_text();
+        assert_eq!(
+            text,
+            "System\n\nUser: List files\n\nAssistant: {\"tool\": \"shell\", \"command\": \"ls\"}\n\n\
+             Tool output:\na.txt\n\nAssistant: There is one file: a.txt\n\n"
+        );
+    }
+
+    #[test]
+    fn test_openai_round_trip() {
+        let transcript = ChatTranscript::from_history(&history(), &["shell"]).with_system("Sys");
+        let messages = transcript.to_openai();
+        assert_eq!(messages[0]["role"], "system");
+        assert_eq!(messages[2]["tool_calls"][0]["function"]["name"], "shell");
+        assert_eq!(
+            messages[2]["tool_calls"][0]["function"]["arguments"],
+            r#"{"command":"ls"}"#
+        );
+        assert_eq!(messages[3]["role"], "tool");
+        assert_eq!(messages[3]["tool_call_id"], "call_1");
+
+        let parsed = ChatTranscript::from_openai(&messages).unwrap();
+        assert_eq!(parsed, transcript);
+
+        let history = parsed.into_history();
+        assert_eq!(history.len(), 4);
+        assert!(matches!(history[1].role, Role::Assistant));
+        assert_eq!(history[1].content, r#"{"command":"ls","tool":"shell"}"#);
+        assert!(matches!(history[2].role, Role::Tool));
+
+        assert_eq!(
+            ChatTranscript::from_openai(&[json!({"role": "robot", "content": "x"})]),
+            Err(TranscriptError::UnknownRole("robot".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_anthropic_round_trip() {
+        let transcript = ChatTranscript::from_history(&history(), &["shell"]).with_system("Sys");
+        let messages = transcript.to_anthropic();
+        assert_eq!(messages.len(), 4);
+        assert_eq!(messages[1]["content"][0]["type"], "tool_use");
+        assert_eq!(messages[1]["content"][0]["input"]["command"], "ls");
+        assert_eq!(messages[2]["role"], "user");
+        assert_eq!(messages[2]["content"][0]["tool_use_id"], "call_1");
+
+        let parsed = ChatTranscript::from_anthropic(transcript.system(), &messages).unwrap();
+        assert_eq!(parsed, transcript);
+    }
+
+    #[test]
+    fn test_gemini_contents() {
+        let contents = ChatTranscript::from_history(&history(), &["shell"]).to_gemini();
+        assert_eq!(contents[1]["role"], "model");
+        assert_eq!(contents[1]["parts"][0]["functionCall"]["name"], "shell");
+        assert_eq!(contents[2]["parts"][0]["functionResponse"]["name"], "shell");
+        assert_eq!(contents[3]["parts"][0]["text"], "There is one file: a.txt");
+    }
+}
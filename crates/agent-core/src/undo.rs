@@ -0,0 +1,188 @@
+//! Undo journal of the files a run changed
+//!
+//! Tools that write files record one [`FileUndo`] per file: the SHA-256 of its
+//! content before and after the call. Hosts keep each pre-image under its hash
+//! with the run's artifacts and record the descriptors in the audit log.
+//! [`plan_undo`] turns a run's descriptors into the steps that put every file
+//! back the way the run found it.
+//!
+//! Undo is best-effort: a file changed again after the run (by the user or a
+//! shell command) is left alone rather than overwritten.
+
+use crate::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// How to revert one file a tool call changed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileUndo {
+    /// Path as the tool wrote it, relative to the run's working directory
+    pub path: String,
+    /// SHA-256 of the content before the call; `None` if the call created it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub before: Option<String>,
+    /// SHA-256 of the content after the call; `None` if the call deleted it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after: Option<String>,
+}
+
+/// What undoing a run does to one file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UndoStep {
+    /// Write back the pre-image with hash `before`
+    Restore { path: String, before: String },
+    /// Delete a file the run created
+    Remove { path: String },
+    /// Leave the file as it is
+    Skip { path: String, reason: SkipReason },
+}
+
+impl UndoStep {
+    pub fn path(&self) -> &str {
+        match self {
+            Self::Restore { path, .. } | Self::Remove { path } | Self::Skip { path, .. } => path,
+        }
+    }
+}
+
+/// Why a file is left alone
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    /// The file is already as the run found it
+    AlreadyUndone,
+    /// The file changed after the run
+    ChangedSince,
+}
+
+impl SkipReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::AlreadyUndone => "already as before the run",
+            Self::ChangedSince => "changed since the run",
+        }
+    }
+}
+
+/// The steps undoing `changes` (in the order they were made), one per file
+///
+/// `current` gives the SHA-256 of a file's content now, or `None` if it does
+/// not exist. A file changed several times goes back to its content before the
+/// first change, if it still holds what the last change left.
+pub fn plan_undo<'a>(
+    changes: impl IntoIterator<Item = &'a FileUndo>,
+    current: impl Fn(&str) -> Option<String>,
+) -> Vec<UndoStep> {
+    // Per path: content before the first change, content after the last
+    let mut files: Vec<(&str, &Option<String>, &Option<String>)> = Vec::new();
+    for change in changes {
+        match files.iter_mut().find(|(path, ..)| *path == change.path) {
+            Some(file) => file.2 = &change.after,
+            None => files.push((&change.path, &change.before, &change.after)),
+        }
+    }
+
+    files
+        .into_iter()
+        .map(|(path, before, after)| {
+            let now = current(path);
+            let path = path.to_string();
+            if now == *before {
+                UndoStep::Skip {
+                    path,
+                    reason: SkipReason::AlreadyUndone,
+                }
+            } else if now != *after {
+                UndoStep::Skip {
+                    path,
+                    reason: SkipReason::ChangedSince,
+                }
+            } else {
+                match before {
+                    Some(before) => UndoStep::Restore {
+                        path,
+                        before: before.clone(),
+                    },
+                    None => UndoStep::Remove { path },
+                }
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::collections::BTreeMap;
+
+    fn change(path: &str, before: Option<&str>, after: Option<&str>) -> FileUndo {
+        FileUndo {
+            path: path.to_string(),
+            before: before.map(str::to_string),
+            after: after.map(str::to_string),
+        }
+    }
+
+    fn plan(changes: &[FileUndo], now: &[(&str, &str)]) -> Vec<UndoStep> {
+        let now: BTreeMap<&str, &str> = now.iter().copied().collect();
+        plan_undo(changes, |path| now.get(path).map(|hash| hash.to_string()))
+    }
+
+    #[test]
+    fn test_plan_restores_and_removes() {
+        let changes = [
+            change("a.txt", Some("a0"), Some("a1")),
+            change("new.txt", None, Some("n1")),
+            change("gone.txt", Some("g0"), None),
+        ];
+        assert_eq!(
+            plan(&changes, &[("a.txt", "a1"), ("new.txt", "n1")]),
+            vec![
+                UndoStep::Restore {
+                    path: "a.txt".to_string(),
+                    before: "a0".to_string()
+                },
+                UndoStep::Remove {
+                    path: "new.txt".to_string()
+                },
+                UndoStep::Restore {
+                    path: "gone.txt".to_string(),
+                    before: "g0".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plan_spans_repeated_changes_and_skips() {
+        let changes = [
+            change("a.txt", Some("a0"), Some("a1")),
+            change("b.txt", Some("b0"), Some("b1")),
+            change("a.txt", Some("a1"), Some("a2")),
+        ];
+        // a.txt still holds the last change; b.txt was edited afterwards
+        let steps = plan(&changes, &[("a.txt", "a2"), ("b.txt", "b9")]);
+        assert_eq!(
+            steps[0],
+            UndoStep::Restore {
+                path: "a.txt".to_string(),
+                before: "a0".to_string()
+            }
+        );
+        assert_eq!(
+            steps[1],
+            UndoStep::Skip {
+                path: "b.txt".to_string(),
+                reason: SkipReason::ChangedSince
+            }
+        );
+
+        // Undoing twice changes nothing
+        let steps = plan(&changes, &[("a.txt", "a0"), ("b.txt", "b0")]);
+        assert!(steps.iter().all(|step| matches!(
+            step,
+            UndoStep::Skip {
+                reason: SkipReason::AlreadyUndone,
+                ..
+            }
+        )));
+    }
+}
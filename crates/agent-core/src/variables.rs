@@ -0,0 +1,207 @@
+//! Scratchpad variables
+//!
+//! The model can stash an intermediate value (a computed list, an extracted
+//! id) under a name and use it later instead of asking a tool to derive it
+//! again:
+//!
+//! ```text
+//! {"set_var": {"name": "order_id", "value": "A-1042"}}
+//! {"get_var": {"name": "order_id"}}
+//! {"tool": "shell", "command": "grep ${order_id} orders.csv"}
+//! ```
+//!
+//! Variables live in [`AgentState::variables`], so they travel with the
+//! state. Setting and reading them needs no host, and `${name}` in tool
+//! parameters is replaced before the host sees the request. References to
+//! names that are not set are left alone, so shell syntax like `${HOME}`
+//! keeps working.
+
+use crate::agent::AgentState;
+use crate::prelude::*;
+use crate::tool::{ContentKind, ToolResult};
+use alloc::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// A scratchpad operation requested by the model
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VariableRequest {
+    SetVar { name: String, value: Value },
+    GetVar { name: String },
+}
+
+impl VariableRequest {
+    /// Protocol name of the operation (`set_var` or `get_var`)
+    pub fn operation(&self) -> &'static str {
+        match self {
+            VariableRequest::SetVar { .. } => "set_var",
+            VariableRequest::GetVar { .. } => "get_var",
+        }
+    }
+}
+
+/// Whether `name` can be used as a variable (`[A-Za-z_][A-Za-z0-9_]*`)
+pub fn is_valid_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Carry out a scratchpad operation on the state
+pub fn apply_variable_request(state: &mut AgentState, request: &VariableRequest) -> ToolResult {
+    match request {
+        VariableRequest::SetVar { name, .. } | VariableRequest::GetVar { name }
+            if !is_valid_variable_name(name) =>
+        {
+            ToolResult::failure(format!(
+                "Invalid variable name '{}': use letters, digits and underscores",
+                name
+            ))
+        }
+        VariableRequest::SetVar { name, value } => {
+            state.variables.insert(name.clone(), value.clone());
+            ToolResult::success(format!("Stored ${{{}}}", name))
+        }
+        VariableRequest::GetVar { name } => match state.variables.get(name) {
+            Some(value) => {
+                ToolResult::success(value.to_string()).with_content_kind(ContentKind::Json)
+            }
+            None => ToolResult::failure(format!(
+                "No variable '{}' (set: {})",
+                name,
+                variable_names(&state.variables)
+            )),
+        },
+    }
+}
+
+fn variable_names(variables: &BTreeMap<String, Value>) -> String {
+    if variables.is_empty() {
+        return "none".to_string();
+    }
+    variables.keys().cloned().collect::<Vec<_>>().join(", ")
+}
+
+/// Replace `${name}` references in tool parameters
+///
+/// A string that is only a reference takes the variable's JSON value, so
+/// `{"ids": "${ids}"}` can pass a list. Inside longer strings, string values
+/// are inserted as they are and other values as JSON.
+pub fn substitute_variables(params: &Value, variables: &BTreeMap<String, Value>) -> Value {
+    match params {
+        Value::String(text) => {
+            if let Some(value) = whole_reference(text).and_then(|name| variables.get(name)) {
+                return value.clone();
+            }
+            Value::String(substitute_text(text, variables))
+        }
+        Value::Array(items) => Value::Array(
+            items
+                .iter()
+                .map(|item| substitute_variables(item, variables))
+                .collect(),
+        ),
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(key, value)| (key.clone(), substitute_variables(value, variables)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+/// `name` if `text` is exactly `${name}`
+fn whole_reference(text: &str) -> Option<&str> {
+    text.strip_prefix("${")?.strip_suffix('}')
+}
+
+fn substitute_text(text: &str, variables: &BTreeMap<String, Value>) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let value = after
+            .find('}')
+            .and_then(|end| variables.get(&after[..end]).map(|value| (end, value)));
+        match value {
+            Some((end, value)) => {
+                match value {
+                    Value::String(s) => out.push_str(s),
+                    other => out.push_str(&other.to_string()),
+                }
+                rest = &after[end + 1..];
+            }
+            None => {
+                out.push_str("${");
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_set_and_get() {
+        let mut state = AgentState::new("Find the order");
+        let set = VariableRequest::SetVar {
+            name: "ids".to_string(),
+            value: json!([3, 7]),
+        };
+        assert!(apply_variable_request(&mut state, &set).success);
+
+        let get = VariableRequest::GetVar {
+            name: "ids".to_string(),
+        };
+        assert_eq!(apply_variable_request(&mut state, &get).output, "[3,7]");
+
+        let missing = VariableRequest::GetVar {
+            name: "total".to_string(),
+        };
+        let result = apply_variable_request(&mut state, &missing);
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("set: ids"));
+
+        let invalid = VariableRequest::SetVar {
+            name: "a-b".to_string(),
+            value: json!(1),
+        };
+        assert!(!apply_variable_request(&mut state, &invalid).success);
+    }
+
+    #[test]
+    fn test_substitute_variables() {
+        let mut variables = BTreeMap::new();
+        variables.insert("id".to_string(), json!("A-1042"));
+        variables.insert("ids".to_string(), json!([3, 7]));
+        variables.insert("count".to_string(), json!(2));
+
+        let params = json!({
+            "command": "grep ${id} orders.csv | head -n ${count}; echo ${HOME}",
+            "list": "${ids}",
+            "nested": ["${id}", 5],
+        });
+        assert_eq!(
+            substitute_variables(&params, &variables),
+            json!({
+                "command": "grep A-1042 orders.csv | head -n 2; echo ${HOME}",
+                "list": [3, 7],
+                "nested": ["A-1042", 5],
+            })
+        );
+        assert_eq!(
+            substitute_variables(&json!("${unclosed"), &variables),
+            json!("${unclosed")
+        );
+    }
+}
@@ -0,0 +1,202 @@
+//! Anthropic Messages API backend
+//!
+//! Sends the agent conversation as Messages API turns and offers host tools
+//! through native tool use. `tool_use` blocks in the response are rendered in
+//! the agent's JSON protocol, so the agent loop handles them like any other
+//! tool call. Images tools returned are sent as `image` blocks. The API has
+//! no embeddings endpoint, so the backend cannot embed.
+
+use crate::llm::{ImageAttachment, LLMBackend, LLMInput, LLMOutput, Sampling};
+use crate::throttle::{estimate_tokens, http_agent, Throttle};
+use agent_core::{protocol::format_tool_call, tool::ToolRequest};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+pub const DEFAULT_ENDPOINT: &str = "https://api.anthropic.com";
+
+pub const DEFAULT_MODEL_ID: &str = "claude-sonnet-4-5";
+
+/// Environment variable holding the API key
+pub const API_KEY_VAR: &str = "ANTHROPIC_API_KEY";
+
+const API_VERSION: &str = "2023-06-01";
+
+/// Client for the Anthropic Messages API
+pub struct AnthropicBackend {
+    agent: ureq::Agent,
+    throttle: Arc<Throttle>,
+    url: String,
+    api_key: String,
+    model: String,
+    sampling: Sampling,
+}
+
+#[derive(Debug, Deserialize)]
+struct MessagesResponse {
+    content: Vec<ContentBlock>,
+    #[serde(default)]
+    usage: Option<Usage>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ContentBlock {
+    Text {
+        text: String,
+    },
+    ToolUse {
+        name: String,
+        input: Value,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct Usage {
+    input_tokens: i32,
+    output_tokens: i32,
+}
+
+impl AnthropicBackend {
+    pub fn new(endpoint: &str, api_key: String, model: &str) -> Self {
+        Self {
+            agent: http_agent(),
+            throttle: Arc::default(),
+            url: format!("{}/v1/messages", endpoint.trim_end_matches('/')),
+            api_key,
+            model: model.to_string(),
+            sampling: Sampling::default(),
+        }
+    }
+
+    /// Send through `throttle`, shared with other backends holding it
+    pub fn with_throttle(mut self, throttle: Arc<Throttle>) -> Self {
+        self.throttle = throttle;
+        self
+    }
+
+    pub fn with_sampling(mut self, sampling: Sampling) -> Self {
+        self.sampling = sampling;
+        self
+    }
+
+    fn request_body(&self, input: &LLMInput) -> Value {
+        let mut body = json!({
+            "model": self.model,
+            "max_tokens": input.max_tokens,
+        });
+        // The Messages API takes no seed
+        if let Some(temperature) = self.sampling.temperature {
+            body["temperature"] = json!(temperature);
+        }
+
+        match &input.chat {
+            Some(chat) => {
+                body["system"] = json!(chat.transcript.system());
+                body["messages"] = json!(chat.transcript.to_anthropic());
+                if !input.images.is_empty() {
+                    attach_images(&mut body["messages"], &input.images);
+                }
+                if !chat.tools.is_empty() {
+                    body["tools"] = chat
+                        .tools
+                        .iter()
+                        .map(|tool| {
+                            json!({
+                                "name": tool.frontmatter.name,
+                                "description": tool.frontmatter.description,
+                                "input_schema": tool.input_schema(),
+                            })
+                        })
+                        .collect();
+                }
+            }
+            None => {
+                body["messages"] = json!([{ "role": "user", "content": input.prompt }]);
+            }
+        }
+
+        body
+    }
+}
+
+/// Add `images` to the last user message, each after a label naming its
+/// artifact (after any `tool_result` blocks, which must come first)
+fn attach_images(messages: &mut Value, images: &[ImageAttachment]) {
+    let Some(messages) = messages.as_array_mut() else {
+        return;
+    };
+    if messages
+        .last()
+        .is_none_or(|message| message["role"] != "user")
+    {
+        messages.push(json!({ "role": "user", "content": [] }));
+    }
+    let Some(message) = messages.last_mut() else {
+        return;
+    };
+    if let Some(text) = message["content"].as_str() {
+        message["content"] = json!([{ "type": "text", "text": text }]);
+    }
+    let Some(blocks) = message["content"].as_array_mut() else {
+        return;
+    };
+    for image in images {
+        blocks.push(json!({ "type": "text", "text": format!("Image {}:", image.reference) }));
+        blocks.push(json!({
+            "type": "image",
+            "source": {
+                "type": "base64",
+                "media_type": image.media_type,
+                "data": image.base64(),
+            },
+        }));
+    }
+}
+
+impl LLMBackend for AnthropicBackend {
+    fn infer(&mut self, input: LLMInput) -> Result<LLMOutput> {
+        let body = self.request_body(&input);
+
+        let response: MessagesResponse = self
+            .throttle
+            .send(estimate_tokens(&input), || {
+                self.agent
+                    .post(&self.url)
+                    .header("x-api-key", &self.api_key)
+                    .header("anthropic-version", API_VERSION)
+                    .send_json(&body)
+            })
+            .with_context(|| format!("Request to Anthropic API at {} failed", self.url))?
+            .body_mut()
+            .read_json()
+            .context("Invalid response from Anthropic API")?;
+
+        // A tool call wins over any accompanying text
+        let mut text = String::new();
+        let mut tool_call = None;
+        for block in response.content {
+            match block {
+                ContentBlock::Text { text: part } => text.push_str(&part),
+                ContentBlock::ToolUse { name, input } if tool_call.is_none() => {
+                    tool_call = Some(format_tool_call(&ToolRequest {
+                        tool: name,
+                        call_id: None,
+                        params: input,
+                    }));
+                }
+                _ => {}
+            }
+        }
+
+        Ok(LLMOutput {
+            text: tool_call.unwrap_or(text),
+            tokens_processed: response
+                .usage
+                .map_or(0, |usage| usage.input_tokens + usage.output_tokens),
+        })
+    }
+}
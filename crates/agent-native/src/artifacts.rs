@@ -0,0 +1,245 @@
+//! Run artifacts on disk
+//!
+//! Each run gets a directory `<artifacts dir>/<run id>/`, created on the first
+//! spooled output. Tool outputs are numbered in the order they are recorded;
+//! those too large for the history are written to `tool-NNN.txt` and replaced
+//! by an excerpt that tells the model how to read the rest. Each spooled
+//! output's page size is sized to the context left when it was recorded.
+//! Outputs compressed into observations are always written, and replaced by
+//! their summary.
+//!
+//! A tool that returns a file reference ([`ContentKind::BinaryRef`],
+//! [`ContentKind::ImageRef`]) gets the file copied into the run directory and
+//! its output replaced by the artifact id. Images are attached to the next
+//! prompt of a multimodal backend (see [`RunArtifacts::pending_images`]).
+//!
+//! With `--memory`, the full text of every output is also kept for the run's
+//! memory ([`RunArtifacts::archived`]), whether or not it was spooled.
+
+use crate::audit::RunId;
+use crate::console;
+use crate::llm::{image_media_type, ImageAttachment};
+use agent_core::agent::{Message, Role};
+use agent_core::artifact::{artifact_id, is_valid_artifact_id, ArtifactPolicy, ArtifactRequest};
+use agent_core::observation::ObservationPolicy;
+use agent_core::tool::{CallId, ContentKind, ToolResult};
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Default directory for run artifacts
+pub const DEFAULT_ARTIFACTS_DIR: &str = ".agent-runs";
+
+/// Default context window, in tokens, that pages are sized against
+pub const DEFAULT_CONTEXT_TOKENS: usize = 8192;
+
+/// Artifact storage of one agent run
+#[derive(Debug)]
+pub struct RunArtifacts {
+    dir: PathBuf,
+    policy: ArtifactPolicy,
+    recorded: usize,
+    context_tokens: usize,
+    /// Page size of each spooled output, by artifact id
+    page_lines: BTreeMap<String, usize>,
+    /// Stored files, by artifact id
+    files: BTreeMap<String, PathBuf>,
+    /// Artifact id of each image a tool returned, by call
+    images: BTreeMap<CallId, String>,
+    /// Full text outputs by artifact id (`Some` with `--memory`)
+    archive: Option<Vec<(String, String)>>,
+}
+
+impl RunArtifacts {
+    /// Storage for run `run` under `base`
+    pub fn new(base: &Path, run: &RunId) -> Self {
+        Self {
+            dir: base.join(run.as_str()),
+            policy: ArtifactPolicy::default(),
+            recorded: 0,
+            context_tokens: DEFAULT_CONTEXT_TOKENS,
+            page_lines: BTreeMap::new(),
+            files: BTreeMap::new(),
+            images: BTreeMap::new(),
+            archive: None,
+        }
+    }
+
+    /// Keep the full text of every successful output (see [`Self::archived`])
+    pub fn with_archive(mut self) -> Self {
+        self.archive = Some(Vec::new());
+        self
+    }
+
+    /// Size pages against a context window of `tokens`
+    pub fn with_context_tokens(mut self, tokens: usize) -> Self {
+        self.context_tokens = tokens;
+        self
+    }
+
+    /// Prepare a tool result for the history, spooling a large output to disk
+    ///
+    /// `used_tokens` is how much of the context window is already taken.
+    pub fn record(&mut self, result: ToolResult, used_tokens: usize) -> Result<ToolResult> {
+        self.recorded += 1;
+        self.archive_output(&result);
+        if result.success && result.content_kind.is_ref() {
+            return self.store_file(result);
+        }
+        if !result.success || !self.policy.should_spool(&result.output) {
+            return Ok(result);
+        }
+
+        let id = artifact_id(self.recorded);
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create {}", self.dir.display()))?;
+        let path = self.path(&id);
+        fs::write(&path, &result.output)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+
+        let free_tokens = self.context_tokens.saturating_sub(used_tokens);
+        let page_lines = self.policy.page_lines_for(&result.output, free_tokens);
+        let excerpt = self.policy_for(page_lines).excerpt(&id, &result.output);
+        self.page_lines.insert(id, page_lines);
+        Ok(ToolResult::success(excerpt).with_call_id(result.call_id))
+    }
+
+    /// Keep a tool output as an artifact and put `summary` into the history
+    /// in its place
+    pub fn record_observation(
+        &mut self,
+        result: ToolResult,
+        summary: &str,
+        observations: &ObservationPolicy,
+        used_tokens: usize,
+    ) -> Result<ToolResult> {
+        self.recorded += 1;
+        self.archive_output(&result);
+        let id = artifact_id(self.recorded);
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create {}", self.dir.display()))?;
+        let path = self.path(&id);
+        fs::write(&path, &result.output)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+
+        let free_tokens = self.context_tokens.saturating_sub(used_tokens);
+        let page_lines = self.policy.page_lines_for(&result.output, free_tokens);
+        let observation = observations.observation(&id, result.output.lines().count(), summary);
+        self.page_lines.insert(id, page_lines);
+        Ok(ToolResult::success(observation).with_call_id(result.call_id))
+    }
+
+    /// Copy the file a tool referred to into the run directory
+    ///
+    /// The output becomes the artifact id; a missing file fails the result.
+    fn store_file(&mut self, result: ToolResult) -> Result<ToolResult> {
+        let source = PathBuf::from(result.output.trim());
+        if !source.is_file() {
+            return Ok(ToolResult::failure(format!(
+                "The tool referred to '{}', which is not a file",
+                source.display()
+            ))
+            .with_call_id(result.call_id));
+        }
+
+        let id = artifact_id(self.recorded);
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create {}", self.dir.display()))?;
+        let mut path = self.dir.join(&id);
+        if let Some(extension) = source.extension() {
+            path.set_extension(extension);
+        }
+        fs::copy(&source, &path).with_context(|| {
+            format!("Failed to copy {} to {}", source.display(), path.display())
+        })?;
+
+        if let (ContentKind::ImageRef, Some(call)) = (result.content_kind, result.call_id) {
+            self.images.insert(call, id.clone());
+        }
+        self.files.insert(id.clone(), path);
+        Ok(ToolResult::success(id)
+            .with_content_kind(result.content_kind)
+            .with_call_id(result.call_id))
+    }
+
+    /// Images returned by the tool results after the model's last reply
+    ///
+    /// Attached to every prompt until the model replies, so a retried turn
+    /// still sees them. An image that cannot be read is left out with a
+    /// warning.
+    pub fn pending_images(&self, history: &[Message]) -> Vec<ImageAttachment> {
+        let mut images: Vec<ImageAttachment> = history
+            .iter()
+            .rev()
+            .take_while(|message| !message.role.is_model())
+            .filter(|message| message.role == Role::Tool)
+            .filter_map(|message| self.images.get(&message.call_id?))
+            .filter_map(|id| {
+                let path = self.files.get(id)?;
+                let media_type = image_media_type(path)?;
+                match fs::read(path) {
+                    Ok(data) => Some(ImageAttachment {
+                        reference: id.clone(),
+                        media_type,
+                        data,
+                    }),
+                    Err(e) => {
+                        console::warn(format!("Could not attach image {}: {}", id, e));
+                        None
+                    }
+                }
+            })
+            .collect();
+        images.reverse();
+        images
+    }
+
+    /// Serve a read request; a bad id or range is a failed result for the model
+    pub fn read(&self, request: &ArtifactRequest) -> ToolResult {
+        if !is_valid_artifact_id(&request.id) {
+            return ToolResult::failure(format!("Invalid artifact id '{}'", request.id));
+        }
+        if self.files.contains_key(&request.id) {
+            return ToolResult::failure(format!(
+                "Artifact '{}' is a file and cannot be read as text",
+                request.id
+            ));
+        }
+
+        let content = match fs::read_to_string(self.path(&request.id)) {
+            Ok(content) => content,
+            Err(_) => return ToolResult::failure(format!("No artifact '{}'", request.id)),
+        };
+        let policy = match self.page_lines.get(&request.id) {
+            Some(&lines) => self.policy_for(lines),
+            None => self.policy.clone(),
+        };
+        match policy.read(request, &content) {
+            Ok(text) => ToolResult::success(text),
+            Err(e) => ToolResult::failure(e.to_string()),
+        }
+    }
+
+    /// The full text outputs of the run by artifact id, oldest first (with
+    /// [`Self::with_archive`])
+    pub fn archived(&self) -> &[(String, String)] {
+        self.archive.as_deref().unwrap_or_default()
+    }
+
+    fn archive_output(&mut self, result: &ToolResult) {
+        if let Some(archive) = &mut self.archive {
+            if result.success && result.content_kind.is_text() {
+                archive.push((artifact_id(self.recorded), result.output.clone()));
+            }
+        }
+    }
+
+    fn policy_for(&self, page_lines: usize) -> ArtifactPolicy {
+        self.policy.clone().with_page_lines(page_lines)
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.txt", id))
+    }
+}
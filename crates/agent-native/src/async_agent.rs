@@ -0,0 +1,456 @@
+//! Async agent runner (`async` feature)
+//!
+//! Mirrors [`run_agent`](crate::run_agent) on top of [`AsyncLLMBackend`] and
+//! [`AsyncTool`], so HTTP backends and tools await I/O instead of blocking a
+//! thread. Both drive an [`AgentSession`](agent_core::AgentSession); manifest
+//! checks, approvals and events are shared with the sync runner. Approval prompts still read stdin synchronously: the agent
+//! cannot continue until the user answers anyway.
+
+use crate::events::{AgentEvent, EventSink};
+use crate::llm::{AsyncLLMBackend, BackendError, Inline, LLMInput, LLMOutput};
+use crate::skill_discovery::{LoadedSkill, LoadedSkills};
+use crate::undo::UndoJournal;
+use crate::{console, AgentArgs, Chunking, ReplyBounds, ToolHost};
+use agent_core::{
+    agent::AgentState,
+    confinement::ConfinementLevel,
+    limits::ResourceLimits,
+    observation::ObservationMode,
+    session::StepAction,
+    skill::{
+        ExtractionInput, ExtractionOutput, ExtractionTarget, SkillError, SkillRequest, SkillResult,
+        SkillResult_,
+    },
+    stop::PromptPhase,
+    tool::{execute_builtin_tool, ToolRequest, ToolResult},
+};
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Boxed future returned by [`AsyncTool::call`]
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// A host tool that executes without blocking the runner
+///
+/// Requests reach `call` only after the manifest check, pre-execution guardrails
+/// and approval have passed.
+pub trait AsyncTool: Send + Sync {
+    /// Tool name, matching its TOOL.md manifest
+    fn name(&self) -> &str;
+
+    /// Execute a tool request
+    fn call<'a>(&'a self, request: &'a ToolRequest) -> BoxFuture<'a, Result<ToolResult>>;
+}
+
+/// The shell tool; commands run on the blocking thread pool
+#[derive(Debug, Default)]
+pub struct AsyncShellTool {
+    confinement: ConfinementLevel,
+    limits: ResourceLimits,
+}
+
+impl AsyncShellTool {
+    /// Run commands confined to `confinement` (see [`crate::confine`]) and
+    /// within `limits` (see [`crate::limits`])
+    pub fn new(confinement: ConfinementLevel, limits: ResourceLimits) -> Self {
+        Self {
+            confinement,
+            limits,
+        }
+    }
+}
+
+impl AsyncTool for AsyncShellTool {
+    fn name(&self) -> &str {
+        "shell"
+    }
+
+    fn call<'a>(&'a self, request: &'a ToolRequest) -> BoxFuture<'a, Result<ToolResult>> {
+        let (confinement, limits) = (self.confinement, self.limits);
+        Box::pin(async move {
+            let command = crate::shell_command(request)?.to_string();
+            tokio::task::spawn_blocking(move || {
+                let process = crate::confine::shell_process(&command, confinement)?;
+                let run = crate::limits::run(process, &limits)?;
+                Ok(crate::shell_result(&command, run))
+            })
+            .await?
+        })
+    }
+}
+
+/// The patch tool; its file reads and writes are small and run inline
+#[derive(Debug)]
+pub struct AsyncPatchTool {
+    undo: Arc<UndoJournal>,
+}
+
+impl AsyncPatchTool {
+    /// Save the files' pre-images to `undo` before writing them
+    pub fn new(undo: Arc<UndoJournal>) -> Self {
+        Self { undo }
+    }
+}
+
+impl AsyncTool for AsyncPatchTool {
+    fn name(&self) -> &str {
+        crate::patch_tool::TOOL_NAME
+    }
+
+    fn call<'a>(&'a self, request: &'a ToolRequest) -> BoxFuture<'a, Result<ToolResult>> {
+        Box::pin(async move { Ok(crate::patch_tool::execute(request, &self.undo)) })
+    }
+}
+
+/// The search tool; the file walk runs on the blocking thread pool
+#[derive(Debug, Default)]
+pub struct AsyncSearchTool;
+
+impl AsyncTool for AsyncSearchTool {
+    fn name(&self) -> &str {
+        crate::search_tool::TOOL_NAME
+    }
+
+    fn call<'a>(&'a self, request: &'a ToolRequest) -> BoxFuture<'a, Result<ToolResult>> {
+        let request = request.clone();
+        Box::pin(async move {
+            Ok(tokio::task::spawn_blocking(move || crate::search_tool::execute(&request)).await?)
+        })
+    }
+}
+
+/// The sql tool; queries run on the blocking thread pool
+#[derive(Debug, Default)]
+pub struct AsyncSqlTool;
+
+impl AsyncTool for AsyncSqlTool {
+    fn name(&self) -> &str {
+        crate::sql_tool::TOOL_NAME
+    }
+
+    fn call<'a>(&'a self, request: &'a ToolRequest) -> BoxFuture<'a, Result<ToolResult>> {
+        let request = request.clone();
+        Box::pin(async move {
+            Ok(tokio::task::spawn_blocking(move || crate::sql_tool::execute(&request)).await?)
+        })
+    }
+}
+
+/// The view_image tool; it only checks a path, inline
+#[derive(Debug, Default)]
+pub struct AsyncImageTool;
+
+impl AsyncTool for AsyncImageTool {
+    fn name(&self) -> &str {
+        crate::image_tool::TOOL_NAME
+    }
+
+    fn call<'a>(&'a self, request: &'a ToolRequest) -> BoxFuture<'a, Result<ToolResult>> {
+        Box::pin(async move { Ok(crate::image_tool::execute(request)) })
+    }
+}
+
+/// Async tool implementations keyed by name
+#[derive(Default)]
+pub struct AsyncTools {
+    tools: BTreeMap<String, Box<dyn AsyncTool>>,
+}
+
+impl AsyncTools {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a tool, replacing any tool with the same name
+    pub fn with_tool(mut self, tool: Box<dyn AsyncTool>) -> Self {
+        self.tools.insert(tool.name().to_string(), tool);
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn AsyncTool> {
+        self.tools.get(name).map(|tool| tool.as_ref())
+    }
+}
+
+/// Run the agent with a blocking backend on a current-thread tokio runtime
+pub fn run(
+    args: AgentArgs,
+    system_prompt: String,
+    skills: LoadedSkills,
+    tools: ToolHost,
+    events: &mut EventSink,
+) -> Result<()> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_io()
+        .build()?;
+
+    events.emit(AgentEvent::Started {
+        run_id: args.run_id.to_string(),
+        query: args.query.clone(),
+    });
+
+    let mut backend = Inline(crate::chaos::wrap(
+        crate::load_backend(&args.backend)?,
+        tools.chaos.as_ref(),
+    ));
+    let async_tools = AsyncTools::new()
+        .with_tool(Box::new(AsyncShellTool::new(
+            tools.confinement("shell"),
+            tools.limits("shell"),
+        )))
+        .with_tool(Box::new(AsyncPatchTool::new(Arc::clone(&tools.undo))))
+        .with_tool(Box::new(AsyncSearchTool))
+        .with_tool(Box::new(AsyncSqlTool))
+        .with_tool(Box::new(AsyncImageTool));
+
+    runtime.block_on(run_agent_async(
+        &args,
+        &system_prompt,
+        &skills,
+        &tools,
+        &async_tools,
+        &mut backend,
+        events,
+    ))
+}
+
+/// The agent loop on an async backend and async tools
+///
+/// Same session as the sync loop: a corrective retry follows a guardrail
+/// rejection or an inconclusive output and does not count as an iteration; a
+/// second failure ends the run.
+pub async fn run_agent_async<B: AsyncLLMBackend>(
+    args: &AgentArgs,
+    system_prompt: &str,
+    skills: &LoadedSkills,
+    tools: &ToolHost,
+    async_tools: &AsyncTools,
+    backend: &mut B,
+    events: &mut EventSink,
+) -> Result<()> {
+    let embedder = crate::embedder(args)?;
+    let (guardrail_chain, answer_guards) = crate::guard_chains(args, embedder.as_ref())?;
+    let mut memory = crate::open_memory(args, embedder)?;
+    let mut artifacts = crate::run_artifacts(args, system_prompt);
+
+    let mut session = crate::run_session(args, system_prompt, guardrail_chain, answer_guards);
+    let mut first_generation = true;
+
+    loop {
+        let prompt = crate::next_prompt(&mut session, events)?;
+        let label = if prompt.retry { "Retrying" } else { "Thinking" };
+        let input = crate::turn_input(args, &prompt, &session, &artifacts, tools, first_generation);
+        let llm_output = generate(backend, input, label, events).await?;
+        first_generation = false;
+
+        let action = match session.accept_model_output(llm_output.text)? {
+            StepAction::CallTool { id, request } => {
+                let result =
+                    execute_tool(&request, tools, async_tools, session.state(), events).await?;
+                match crate::review_tool_result(&mut session, events, id, &request, &result)? {
+                    Some(action) => action,
+                    None => {
+                        let summary =
+                            summarize_observation(args, &request, &result, backend, events).await?;
+                        crate::record_tool_result(
+                            &mut session,
+                            id,
+                            result,
+                            summary,
+                            &args.observations,
+                            &mut artifacts,
+                        )?
+                    }
+                }
+            }
+            StepAction::CallSkill { id, request } => {
+                let result =
+                    execute_skill(&request, backend, args.extraction_reply(), skills, events)
+                        .await?;
+                events.emit(crate::skill_result_event(&request, &result));
+                session.accept_skill_result(id, &result)?
+            }
+            StepAction::ReadArtifact { id, request } => session
+                .accept_tool_result(id, crate::read_artifact(&request, &artifacts, events))?,
+            StepAction::Recall { id, request } => {
+                session.accept_tool_result(id, crate::recall(&request, memory.as_ref(), events))?
+            }
+            action => action,
+        };
+
+        if crate::report_step(events, action) {
+            return crate::remember_run(args, session.state(), memory.as_mut(), &artifacts);
+        }
+    }
+}
+
+/// Run one generation, showing a spinner and logging details at higher verbosity
+async fn generate<B: AsyncLLMBackend>(
+    backend: &mut B,
+    mut input: LLMInput,
+    label: &str,
+    events: &EventSink,
+) -> Result<LLMOutput> {
+    crate::trace_prompt(events, label, &input);
+    let prompt = events.recording().then(|| input.prompt.clone());
+
+    let started = Instant::now();
+    let output = {
+        let spinner = console::Spinner::start(label);
+        input.progress = Some(crate::prompt_progress(&spinner, label));
+        backend.infer(input).await.context(BackendError)?
+    };
+
+    crate::trace_output(events, label, started, prompt, &output);
+    Ok(output)
+}
+
+/// Compress an accepted tool output into an observation (see
+/// [`summarize_observation`](crate::summarize_observation))
+async fn summarize_observation<B: AsyncLLMBackend>(
+    args: &AgentArgs,
+    request: &ToolRequest,
+    result: &ToolResult,
+    backend: &mut B,
+    events: &EventSink,
+) -> Result<Option<String>> {
+    let policy = &args.observations;
+    if !policy.should_summarize(result) {
+        return Ok(None);
+    }
+    if policy.mode() == ObservationMode::Model {
+        let output = generate(
+            backend,
+            LLMInput {
+                prompt: policy.summary_prompt(&args.query, &request.tool, &result.output),
+                max_tokens: policy.summary_tokens(),
+                stop: args.stop.for_phase(PromptPhase::Summary).to_vec(),
+                first_generation: false,
+                images: Vec::new(),
+                chat: None,
+                progress: None,
+            },
+            "Summarizing",
+            events,
+        )
+        .await?;
+        if !output.text.trim().is_empty() {
+            return Ok(Some(output.text));
+        }
+    }
+    Ok(Some(policy.local_summary(&result.output)))
+}
+
+/// Execute a tool request after the shared manifest, guardrail and approval checks
+async fn execute_tool(
+    request: &ToolRequest,
+    tools: &ToolHost,
+    async_tools: &AsyncTools,
+    state: &AgentState,
+    events: &mut EventSink,
+) -> Result<ToolResult> {
+    let request = &tools.coerced(request);
+    if let Some(rejected) = crate::authorize_tool(request, tools, state, events)? {
+        return Ok(rejected.with_call_id(request.call_id));
+    }
+
+    if let Some(result) = tools.replayed(request) {
+        crate::report_tool_output(events, request, &result);
+        return Ok(result.with_call_id(request.call_id));
+    }
+
+    let result = match async_tools.get(&request.tool) {
+        Some(tool) => tool.call(request).await?,
+        None => execute_builtin_tool(request)
+            .unwrap_or_else(|| ToolResult::failure(format!("Unknown tool: {}", request.tool))),
+    };
+    let result = tools.perturbed(result);
+
+    tools.report_changes(events, request);
+    crate::report_tool_output(events, request, &result);
+    Ok(result.with_call_id(request.call_id))
+}
+
+/// Execute a skill request (see [`execute_skill`](crate::execute_skill))
+async fn execute_skill<B: AsyncLLMBackend>(
+    request: &SkillRequest,
+    backend: &mut B,
+    reply: ReplyBounds<'_>,
+    skills: &LoadedSkills,
+    events: &EventSink,
+) -> Result<SkillResult_> {
+    if request.skill != "extract" {
+        return Ok(SkillResult_::failure(SkillError::UnknownSkill(
+            request.skill.clone(),
+        )));
+    }
+
+    let (batch, target) = match crate::start_extraction(request, Chunking::default(), events) {
+        Ok(started) => started,
+        Err(failure) => return Ok(failure),
+    };
+
+    let chunks = batch.chunks();
+    let mut results = Vec::with_capacity(chunks.len());
+    for chunk in &chunks {
+        crate::report_chunk_progress(events, chunk, chunks.len());
+        let chunk_input = batch.chunk_input(chunk);
+        match extract_chunk(
+            &chunk_input,
+            target,
+            skills.get("extract"),
+            backend,
+            reply,
+            events,
+        )
+        .await?
+        {
+            Ok(output) => results.push((chunk.clone(), output)),
+            Err(e) => return Ok(SkillResult_::failure(e)),
+        }
+    }
+
+    Ok(crate::finish_extraction(&batch, target, results))
+}
+
+/// Run one extraction prompt and validate its output against the given input
+async fn extract_chunk<B: AsyncLLMBackend>(
+    input: &ExtractionInput,
+    target: ExtractionTarget,
+    skill: Option<&LoadedSkill>,
+    backend: &mut B,
+    reply: ReplyBounds<'_>,
+    events: &EventSink,
+) -> Result<SkillResult<ExtractionOutput>> {
+    let prompt = match crate::extraction_prompt(input, target, skill) {
+        Ok(prompt) => prompt,
+        Err(e) => return Ok(Err(e)),
+    };
+
+    let llm_output = generate(
+        backend,
+        LLMInput {
+            prompt,
+            max_tokens: reply.max_tokens,
+            stop: reply.stop.to_vec(),
+            first_generation: false,
+            images: Vec::new(),
+            chat: None,
+            progress: None,
+        },
+        "Extracting",
+        events,
+    )
+    .await?;
+
+    Ok(crate::check_extraction_output(
+        input,
+        target,
+        skill,
+        &llm_output.text,
+    ))
+}
@@ -0,0 +1,436 @@
+//! Run ids, the audit log on disk and `agent audit`
+//!
+//! Every agent run gets a [`RunId`] (a UUID) that names its directory under the
+//! artifacts dir, and an append-only audit log at `<run dir>/audit.jsonl` (see
+//! [`agent_core::audit`]). The log is fed from the run's events, so both
+//! runners are covered, and it numbers tool executions so the records (and
+//! JSONL events) about one call share an id.
+
+use crate::console;
+use crate::events::{AgentEvent, ApprovalSource};
+use agent_core::audit::{sha256_hex, verify_log, AuditChain, AuditEntry, AuditRecord};
+use anyhow::{Context, Result};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+/// Audit log file name inside a run directory
+pub const AUDIT_FILE: &str = "audit.jsonl";
+
+/// Identifier of one agent run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunId(String);
+
+impl RunId {
+    pub fn new() -> Self {
+        Self(Uuid::new_v4().to_string())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Default for RunId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for RunId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// The audit log of the current run
+#[derive(Debug)]
+pub struct AuditLog {
+    file: File,
+    path: PathBuf,
+    chain: AuditChain,
+    user: String,
+    executions: u64,
+    /// Execution whose outcome is not recorded yet
+    open: Option<u64>,
+    /// Execution that ended last; guardrail verdicts are about its result
+    last: Option<u64>,
+}
+
+impl AuditLog {
+    /// Start the log of run `run` under `artifacts_dir`
+    pub fn create(artifacts_dir: &Path, run: &RunId) -> Result<Self> {
+        let dir = artifacts_dir.join(run.as_str());
+        fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        let path = dir.join(AUDIT_FILE);
+        let file = OpenOptions::new()
+            .append(true)
+            .create_new(true)
+            .open(&path)
+            .with_context(|| format!("Failed to create {}", path.display()))?;
+        Ok(Self {
+            file,
+            path,
+            chain: AuditChain::new(run.as_str()),
+            user: current_user(),
+            executions: 0,
+            open: None,
+            last: None,
+        })
+    }
+
+    /// Record what `event` says about the run
+    ///
+    /// Returns the tool execution the event belongs to, if any.
+    pub fn observe(&mut self, event: &AgentEvent) -> Option<u64> {
+        let (execution, entry) = match event {
+            AgentEvent::Started { query, .. } => (
+                None,
+                Some(AuditEntry::RunStarted {
+                    query: query.clone(),
+                    user: self.user.clone(),
+                }),
+            ),
+            AgentEvent::ToolProposed {
+                tool,
+                safety,
+                params,
+            } => {
+                self.open = Some(self.next_execution());
+                (
+                    self.open,
+                    Some(AuditEntry::ToolProposed {
+                        tool: tool.clone(),
+                        safety: *safety,
+                        params: params.clone(),
+                    }),
+                )
+            }
+            // A call that fails the manifest check is rejected before it is proposed
+            AgentEvent::ToolRejected { tool, reason } => (
+                match self.close() {
+                    Some(execution) => Some(execution),
+                    None => {
+                        self.last = Some(self.next_execution());
+                        self.last
+                    }
+                },
+                Some(AuditEntry::ToolRejected {
+                    tool: tool.clone(),
+                    reason: reason.clone(),
+                }),
+            ),
+            AgentEvent::ApprovalNeeded { .. } => (self.open, None),
+            AgentEvent::ApprovalDecided {
+                tool,
+                approved,
+                source,
+            } => {
+                let execution = match approved {
+                    true => self.open,
+                    false => self.close(),
+                };
+                let by = match source {
+                    ApprovalSource::Policy => "policy".to_string(),
+                    ApprovalSource::User => self.user.clone(),
+                };
+                (
+                    execution,
+                    Some(AuditEntry::ApprovalDecided {
+                        tool: tool.clone(),
+                        approved: *approved,
+                        by,
+                    }),
+                )
+            }
+            // Artifact reads and scratchpad operations are not executions
+            AgentEvent::ToolOutput {
+                tool,
+                success,
+                output,
+                error,
+                ..
+            } => match self.close() {
+                Some(execution) => (
+                    Some(execution),
+                    Some(AuditEntry::ToolExecuted {
+                        tool: tool.clone(),
+                        success: *success,
+                        output_bytes: output.len(),
+                        output_sha256: sha256_hex(output.as_bytes()),
+                        error: error.clone(),
+                    }),
+                ),
+                None => (None, None),
+            },
+            AgentEvent::FilesChanged { tool, files } => (
+                self.open,
+                Some(AuditEntry::FilesChanged {
+                    tool: tool.clone(),
+                    files: files.clone(),
+                }),
+            ),
+            AgentEvent::GuardrailVerdict {
+                tool,
+                accepted,
+                reason,
+            } => (
+                self.last,
+                Some(AuditEntry::GuardrailVerdict {
+                    tool: tool.clone(),
+                    accepted: *accepted,
+                    reason: reason.clone(),
+                }),
+            ),
+            AgentEvent::SandboxApplied { files, .. } => {
+                (None, Some(AuditEntry::SandboxApplied { files: *files }))
+            }
+            AgentEvent::FinalAnswer { answer } => (
+                None,
+                Some(AuditEntry::RunFinished {
+                    outcome: "answered".to_string(),
+                    answer_sha256: Some(sha256_hex(answer.as_bytes())),
+                }),
+            ),
+            AgentEvent::Failed { code, .. } => (
+                None,
+                Some(AuditEntry::RunFinished {
+                    outcome: code.clone(),
+                    answer_sha256: None,
+                }),
+            ),
+            _ => (None, None),
+        };
+
+        if let Some(entry) = entry {
+            if let Err(e) = self.write(execution, entry) {
+                console::warn(format!(
+                    "Failed to write audit log {}: {}",
+                    self.path.display(),
+                    e
+                ));
+            }
+        }
+        execution
+    }
+
+    /// End the open execution, if any
+    fn close(&mut self) -> Option<u64> {
+        let execution = self.open.take();
+        if execution.is_some() {
+            self.last = execution;
+        }
+        execution
+    }
+
+    fn next_execution(&mut self) -> u64 {
+        self.executions += 1;
+        self.executions
+    }
+
+    fn write(&mut self, execution: Option<u64>, entry: AuditEntry) -> std::io::Result<()> {
+        let line = self.chain.append(unix_time(), execution, entry);
+        writeln!(self.file, "{}", line)?;
+        self.file.flush()
+    }
+}
+
+/// Print the runs recorded under `artifacts_dir`, oldest first
+pub fn list_runs(artifacts_dir: &Path) -> Result<()> {
+    let mut runs = Vec::new();
+    let entries = match fs::read_dir(artifacts_dir) {
+        Ok(entries) => entries,
+        Err(_) => {
+            println!("No runs in {}", artifacts_dir.display());
+            return Ok(());
+        }
+    };
+    for entry in entries.flatten() {
+        let Ok(log) = fs::read_to_string(entry.path().join(AUDIT_FILE)) else {
+            continue;
+        };
+        let records: Vec<AuditRecord> = log
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect();
+        if let Some(first) = records.first() {
+            let query = match &first.entry {
+                AuditEntry::RunStarted { query, .. } => query.clone(),
+                _ => String::new(),
+            };
+            let outcome = records
+                .iter()
+                .rev()
+                .find_map(|record| match &record.entry {
+                    AuditEntry::RunFinished { outcome, .. } => Some(outcome.clone()),
+                    _ => None,
+                })
+                .unwrap_or_else(|| "-".to_string());
+            runs.push((first.time, first.run.clone(), outcome, query));
+        }
+    }
+    if runs.is_empty() {
+        println!("No runs in {}", artifacts_dir.display());
+        return Ok(());
+    }
+
+    runs.sort();
+    println!("{:<36}  {:<20}  {:<16}  QUERY", "RUN", "STARTED", "OUTCOME");
+    for (time, run, outcome, query) in runs {
+        println!(
+            "{:<36}  {:<20}  {:<16}  {}",
+            run,
+            format_time(time),
+            outcome,
+            crate::truncate_string(&query, 60)
+        );
+    }
+    Ok(())
+}
+
+/// Print the audit log of run `run` after checking its chain
+pub fn show_run(artifacts_dir: &Path, run: &str) -> Result<()> {
+    let run = Uuid::parse_str(run)
+        .map_err(|_| anyhow::anyhow!("Invalid run id '{}'", run))?
+        .to_string();
+    let path = artifacts_dir.join(&run).join(AUDIT_FILE);
+    let log = fs::read_to_string(&path)
+        .with_context(|| format!("No audit log for run {} ({})", run, path.display()))?;
+    let records = verify_log(&log)
+        .with_context(|| format!("Audit log {} fails verification", path.display()))?;
+
+    println!("Run {}", run);
+    for record in &records {
+        let execution = record
+            .execution
+            .map(|execution| format!("#{}", execution))
+            .unwrap_or_default();
+        println!(
+            "{:>4}  {}  {:>4}  {}",
+            record.seq,
+            format_time(record.time),
+            execution,
+            describe(&record.entry)
+        );
+    }
+    println!("\nChain verified: {} record(s)", records.len());
+    // The chain cannot tell records cut off the end from a run that stopped
+    if !matches!(
+        records.last().map(|record| &record.entry),
+        Some(AuditEntry::RunFinished { .. })
+    ) {
+        println!("Warning: the log does not end with the run finishing (interrupted or truncated)");
+    }
+    Ok(())
+}
+
+fn describe(entry: &AuditEntry) -> String {
+    match entry {
+        AuditEntry::RunStarted { query, user } => {
+            format!("run started by {}: {}", user, query)
+        }
+        AuditEntry::ToolProposed {
+            tool,
+            safety,
+            params,
+        } => format!("{} [{}] proposed: {}", tool, safety.as_str(), params),
+        AuditEntry::ToolRejected { tool, reason } => format!("{} rejected: {}", tool, reason),
+        AuditEntry::ApprovalDecided { tool, approved, by } => match approved {
+            true => format!("{} approved by {}", tool, by),
+            false => format!("{} refused by {}", tool, by),
+        },
+        AuditEntry::ToolExecuted {
+            tool,
+            success,
+            output_bytes,
+            output_sha256,
+            error,
+        } => match (success, error) {
+            (false, Some(error)) => format!(
+                "{} failed: {} (output sha256 {})",
+                tool,
+                error.lines().next().unwrap_or_default(),
+                output_sha256
+            ),
+            _ => format!(
+                "{} ran: {} byte(s), sha256 {}",
+                tool, output_bytes, output_sha256
+            ),
+        },
+        AuditEntry::FilesChanged { tool, files } => {
+            let paths: Vec<&str> = files.iter().map(|file| file.path.as_str()).collect();
+            format!("{} changed {} (undoable)", tool, paths.join(", "))
+        }
+        AuditEntry::GuardrailVerdict {
+            tool,
+            accepted,
+            reason,
+        } => match (accepted, reason) {
+            (true, _) => format!("guardrails accepted {} output", tool),
+            (false, reason) => format!(
+                "guardrails rejected {} output: {}",
+                tool,
+                reason.as_deref().unwrap_or("no reason given")
+            ),
+        },
+        AuditEntry::SandboxApplied { files } => {
+            format!("sandbox changes applied: {} file(s)", files)
+        }
+        AuditEntry::RunFinished {
+            outcome,
+            answer_sha256,
+        } => match answer_sha256 {
+            Some(hash) => format!("run finished: {} (answer sha256 {})", outcome, hash),
+            None => format!("run finished: {}", outcome),
+        },
+    }
+}
+
+/// Name of the user running the agent, as the OS reports it
+fn current_user() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+/// Seconds since the Unix epoch
+pub fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// `YYYY-MM-DD HH:MM:SS` in UTC
+fn format_time(seconds: u64) -> String {
+    let days = (seconds / 86_400) as i64;
+    let time = seconds % 86_400;
+
+    // Civil date from days since 1970-01-01 (proleptic Gregorian calendar)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
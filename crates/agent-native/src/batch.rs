@@ -0,0 +1,200 @@
+//! `agent batch`: run a file of independent queries
+//!
+//! Each task runs as a child `agent-native --output jsonl` process through
+//! the eval runner, recording itself with `--record` into
+//! `<transcripts dir>/<task id>/`. Records are appended to the results file as
+//! tasks finish, in finishing order. Nobody is there to answer approval
+//! prompts, so calls that would prompt are declined unless `--yes` is given;
+//! `--approve` rules after `--` let tools run without a prompt. Tasks that ask
+//! for more than the batch's [`OverrideBounds`] allow are recorded as failed
+//! without running. Failed tasks
+//! and the end of the batch are announced through the [`Notifier`]. See
+//! [`agent_core::batch`] for the task format.
+
+use crate::console;
+use crate::eval::run_agent;
+use crate::notify::Notifier;
+use crate::{backend_args, Cli};
+use agent_core::batch::{
+    completed_tasks, parse_tasks, BatchNotification, BatchRecord, BatchSummary, BatchTask,
+    OverrideBounds,
+};
+use agent_core::scenario::SCENARIO_FILE;
+use anyhow::{Context, Result};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// Options of `agent batch`
+#[derive(Debug)]
+pub struct BatchOptions {
+    /// Results file (default: next to the task file, `<name>.results.jsonl`)
+    pub results: Option<PathBuf>,
+    /// Transcript directory (default: next to the task file, `<name>.transcripts`)
+    pub transcripts: Option<PathBuf>,
+    /// Tasks to run at once
+    pub jobs: usize,
+    /// Time limit for one task, in seconds
+    pub timeout_secs: u64,
+    /// Skip tasks the results file already records as completed
+    pub resume: bool,
+    /// Approve calls that would prompt
+    pub yes: bool,
+    /// Where notifications go
+    pub notifier: Notifier,
+    /// How far tasks may go with their own limits
+    pub bounds: OverrideBounds,
+    /// Agent arguments for every task
+    pub args: Vec<String>,
+}
+
+/// Run the tasks at `path`
+///
+/// Returns whether every task completed.
+pub fn run(cli: &Cli, path: &Path, options: &BatchOptions) -> Result<bool> {
+    let jsonl =
+        fs::read_to_string(path).with_context(|| format!("Cannot read {}", path.display()))?;
+    let tasks = parse_tasks(&jsonl).with_context(|| format!("Cannot load {}", path.display()))?;
+    let results_path = options
+        .results
+        .clone()
+        .unwrap_or_else(|| path.with_extension("results.jsonl"));
+    let transcripts = options
+        .transcripts
+        .clone()
+        .unwrap_or_else(|| path.with_extension("transcripts"));
+
+    let done = match (options.resume, fs::read_to_string(&results_path)) {
+        (true, Ok(results)) => completed_tasks(&results),
+        _ => Default::default(),
+    };
+    let pending: Vec<&BatchTask> = tasks
+        .iter()
+        .filter(|task| !done.contains(&task.id))
+        .collect();
+    if !done.is_empty() {
+        console::info(format!("Skipping {} completed task(s)", done.len()));
+    }
+    if pending.is_empty() {
+        console::success("Nothing to run");
+        return Ok(true);
+    }
+
+    let mut results = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(options.resume)
+        .truncate(!options.resume)
+        .open(&results_path)
+        .with_context(|| format!("Cannot write {}", results_path.display()))?;
+    if options.resume {
+        end_with_newline(&mut results, &results_path)?;
+    }
+
+    let mut args = backend_args(cli);
+    if let Some(temperature) = cli.temperature {
+        args.extend(["--temperature".to_string(), temperature.to_string()]);
+    }
+    if let Some(seed) = cli.seed {
+        args.extend(["--seed".to_string(), seed.to_string()]);
+    }
+    args.extend(options.args.iter().cloned());
+
+    let workers = options.jobs.clamp(1, pending.len());
+    console::info(format!(
+        "Running {} task(s), {} at a time\n",
+        pending.len(),
+        workers
+    ));
+
+    let exe = std::env::current_exe()?;
+    let batch = path.display().to_string();
+    let next = AtomicUsize::new(0);
+    let output = Mutex::new((results, Vec::new()));
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                while let Some(task) = pending.get(next.fetch_add(1, Ordering::SeqCst)) {
+                    let record = run_task(&exe, &args, task, &transcripts, options);
+                    report(&record);
+                    if let Some(notification) = BatchNotification::task_failed(&batch, &record) {
+                        options.notifier.send(&notification);
+                    }
+                    let (file, records) = &mut *output.lock().unwrap();
+                    let line = serde_json::to_string(&record).expect("records serialize");
+                    if let Err(e) = writeln!(file, "{}", line).and_then(|_| file.flush()) {
+                        console::warn(format!("Cannot write {}: {}", results_path.display(), e));
+                    }
+                    records.push(record);
+                }
+            });
+        }
+    });
+
+    let records = output.into_inner().unwrap().1;
+    options.notifier.send(&BatchNotification::finished(
+        &batch,
+        &records,
+        results_path.display().to_string(),
+    ));
+    let summary = BatchSummary::of(&records);
+    println!(
+        "\n{} completed, {} failed, {} tokens, {:.1}s\nResults: {}",
+        summary.completed,
+        summary.failed,
+        summary.total_tokens,
+        summary.total_wall_seconds,
+        results_path.display()
+    );
+    Ok(summary.failed == 0)
+}
+
+fn run_task(
+    exe: &Path,
+    args: &[String],
+    task: &BatchTask,
+    transcripts: &Path,
+    options: &BatchOptions,
+) -> BatchRecord {
+    if let Err(reason) = options.bounds.check(task) {
+        return BatchRecord::rejected(task, &reason);
+    }
+    let dir = transcripts.join(&task.id);
+    let mut task_args = args.to_vec();
+    task_args.extend(task.run_args(&options.bounds));
+    task_args.extend(["--record".to_string(), dir.display().to_string()]);
+    let run = run_agent(
+        exe,
+        &task_args,
+        &task.query,
+        options.timeout_secs,
+        options.yes,
+    );
+    let transcript = dir.join(SCENARIO_FILE);
+    let transcript = transcript
+        .exists()
+        .then(|| transcript.display().to_string());
+    BatchRecord::new(task, run, transcript)
+}
+
+fn report(record: &BatchRecord) {
+    match &record.error {
+        None => console::success(format!(
+            "{} ({} iteration(s), {} tokens, {:.1}s)",
+            record.id, record.iterations, record.tokens, record.wall_seconds
+        )),
+        Some(error) => console::failure(format!("{}: {}", record.id, error)),
+    }
+}
+
+/// Terminate a partly written last line, so appended records start on their own
+fn end_with_newline(file: &mut File, path: &Path) -> Result<()> {
+    let contents = fs::read(path).with_context(|| format!("Cannot read {}", path.display()))?;
+    if contents.last().is_some_and(|byte| *byte != b'\n') {
+        writeln!(file).with_context(|| format!("Cannot write {}", path.display()))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,62 @@
+//! Fault injection (`--chaos`)
+//!
+//! One [`ChaosInjector`] per run, shared by the places faults enter: the
+//! backend wrapper below (timeouts, corrupted replies), [`ToolHost`] (tool
+//! results) and the [`EventSink`], which follows the run's progress and emits
+//! the report when it ends.
+//!
+//! [`ToolHost`]: crate::ToolHost
+//! [`EventSink`]: crate::events::EventSink
+
+use crate::llm::{LLMBackend, LLMInput, LLMOutput};
+use agent_core::chaos::ChaosInjector;
+use anyhow::{bail, Result};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// The run's injector
+pub type SharedChaos = Arc<Mutex<ChaosInjector>>;
+
+/// A backend whose calls fail or return corrupted text as the injector decides
+struct ChaosBackend {
+    inner: Box<dyn LLMBackend>,
+    chaos: SharedChaos,
+}
+
+impl LLMBackend for ChaosBackend {
+    fn infer(&mut self, input: LLMInput) -> Result<LLMOutput> {
+        if self.chaos.lock().unwrap().fail_model_call() {
+            bail!("Injected fault: the backend timed out");
+        }
+        let mut output = self.inner.infer(input)?;
+        output.text = self.chaos.lock().unwrap().model_output(&output.text);
+        Ok(output)
+    }
+
+    fn save_state(&mut self, path: &Path) -> Result<Option<i32>> {
+        self.inner.save_state(path)
+    }
+
+    fn load_state(&mut self, path: &Path) -> Result<Option<i32>> {
+        self.inner.load_state(path)
+    }
+
+    fn rewind(&mut self, position: i32) -> Result<bool> {
+        self.inner.rewind(position)
+    }
+
+    fn embed(&mut self, texts: &[String]) -> Result<Option<Vec<Vec<f32>>>> {
+        self.inner.embed(texts)
+    }
+}
+
+/// `backend`, with faults injected when `chaos` is set
+pub fn wrap(backend: Box<dyn LLMBackend>, chaos: Option<&SharedChaos>) -> Box<dyn LLMBackend> {
+    match chaos {
+        Some(chaos) => Box::new(ChaosBackend {
+            inner: backend,
+            chaos: Arc::clone(chaos),
+        }),
+        None => backend,
+    }
+}
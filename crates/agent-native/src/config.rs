@@ -0,0 +1,38 @@
+//! The config file and `--profile`
+//!
+//! Profiles live in `agent.yaml` in the working directory, or the file given
+//! with `--config` (see [`agent_core::profile`] for the format). Prompt files
+//! are resolved against the directory of the config file.
+
+use agent_core::profile::{AgentConfig, Profile};
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+
+/// Config file read when `--config` is not given
+pub const DEFAULT_CONFIG_FILE: &str = "agent.yaml";
+
+/// Load profile `name` and its custom prompt text, if it has one
+pub fn load_profile(config: Option<&Path>, name: &str) -> Result<(Profile, Option<String>)> {
+    let path = config.unwrap_or(Path::new(DEFAULT_CONFIG_FILE));
+    let yaml = fs::read_to_string(path)
+        .with_context(|| format!("Cannot read config {}", path.display()))?;
+    let config =
+        AgentConfig::from_yaml(&yaml).with_context(|| format!("Cannot load {}", path.display()))?;
+    let profile = config.profile(name)?.clone();
+
+    let prompt = match (&profile.system_prompt, &profile.system_prompt_file) {
+        (Some(text), _) => Some(text.clone()),
+        (None, Some(file)) => {
+            let base = path.parent().unwrap_or(Path::new("."));
+            Some(read_prompt(&base.join(file))?)
+        }
+        (None, None) => None,
+    };
+    Ok((profile, prompt))
+}
+
+/// Read a custom system prompt file
+pub fn read_prompt(path: &Path) -> Result<String> {
+    fs::read_to_string(path).with_context(|| format!("Cannot read prompt {}", path.display()))
+}
@@ -0,0 +1,263 @@
+//! OS-level confinement of tool subprocesses
+//!
+//! Enforces the [`ConfinementLevel`] the policy picks for a tool on the
+//! processes it starts:
+//!
+//! - Linux: Landlock limits the filesystem (kernel 5.13+), a seccomp filter
+//!   refuses to create internet sockets
+//! - macOS: the command runs under `sandbox-exec` with a generated profile
+//!
+//! The workspace is the working directory when the command starts (the copy
+//! in a `--sandbox` run). Where a restriction is not available the command
+//! runs without it; [`report_support`] warns about that once per run.
+
+use crate::console;
+use agent_core::confinement::{ConfinementLevel, ConfinementPolicy};
+use anyhow::Result;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// What the platform can enforce
+#[derive(Debug, Clone, Copy)]
+pub struct Support {
+    pub filesystem: bool,
+    pub network: bool,
+}
+
+/// Warn about the restrictions the policy asks for that this system cannot
+/// enforce
+pub fn report_support(policy: &ConfinementPolicy) {
+    let wanted = policy.strictest();
+    let support = platform::support();
+    if wanted.restricts_filesystem() && !support.filesystem {
+        console::warn(format!(
+            "Filesystem confinement needs {}; tool commands can write anywhere",
+            platform::FILESYSTEM_NEEDS
+        ));
+    }
+    if wanted.restricts_network() && !support.network {
+        console::warn(format!(
+            "Network confinement needs {}; tool commands keep network access",
+            platform::NETWORK_NEEDS
+        ));
+    }
+}
+
+/// `sh -c command`, confined to `level`
+pub fn shell_process(command: &str, level: ConfinementLevel) -> Result<Command> {
+    platform::shell(command, level, &std::env::current_dir()?)
+}
+
+/// Directories outside the workspace a confined command may write to
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn writable_dirs() -> Vec<PathBuf> {
+    let temp = std::env::temp_dir();
+    vec![temp.canonicalize().unwrap_or(temp)]
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::{writable_dirs, Support};
+    use agent_core::confinement::ConfinementLevel;
+    use anyhow::{Context, Result};
+    use landlock::{
+        path_beneath_rules, Access, AccessFs, Ruleset, RulesetAttr, RulesetCreatedAttr, ABI,
+    };
+    use seccompiler::{
+        BpfProgram, SeccompAction, SeccompCmpArgLen, SeccompCmpOp, SeccompCondition, SeccompFilter,
+        SeccompRule, TargetArch,
+    };
+    use std::io;
+    use std::os::unix::process::CommandExt;
+    use std::path::Path;
+    use std::process::Command;
+
+    /// Address families a confined command may not open sockets for
+    const BLOCKED_FAMILIES: &[i32] = &[libc::AF_INET, libc::AF_INET6, libc::AF_PACKET];
+
+    const LANDLOCK_ABI: ABI = ABI::V5;
+
+    pub const FILESYSTEM_NEEDS: &str = "Landlock (Linux 5.13 or later)";
+    pub const NETWORK_NEEDS: &str = "seccomp on x86_64, aarch64 or riscv64";
+
+    pub fn support() -> Support {
+        Support {
+            filesystem: landlock_version() >= 1,
+            network: TargetArch::try_from(std::env::consts::ARCH).is_ok(),
+        }
+    }
+
+    /// Landlock ABI version of the running kernel, 0 or less without Landlock
+    fn landlock_version() -> i64 {
+        const LANDLOCK_CREATE_RULESET_VERSION: u32 = 1;
+        // SAFETY: with a null attribute and the VERSION flag the call only
+        // returns a number
+        unsafe {
+            libc::syscall(
+                libc::SYS_landlock_create_ruleset,
+                std::ptr::null::<libc::c_void>(),
+                0usize,
+                LANDLOCK_CREATE_RULESET_VERSION,
+            )
+        }
+    }
+
+    pub fn shell(command: &str, level: ConfinementLevel, workspace: &Path) -> Result<Command> {
+        let mut process = Command::new("sh");
+        process.arg("-c").arg(command);
+
+        let support = support();
+        let mut ruleset = match level.restricts_filesystem() && support.filesystem {
+            true => Some(
+                Ruleset::default()
+                    .handle_access(AccessFs::from_all(LANDLOCK_ABI))?
+                    .create()?
+                    .add_rules(path_beneath_rules(["/"], AccessFs::from_read(LANDLOCK_ABI)))?
+                    .add_rules(path_beneath_rules(
+                        writable_dirs().iter().map(|dir| dir.as_path()).chain([
+                            workspace,
+                            Path::new("/dev/null"),
+                            Path::new("/dev/tty"),
+                        ]),
+                        AccessFs::from_all(LANDLOCK_ABI),
+                    ))
+                    .context("Cannot build the Landlock ruleset")?,
+            ),
+            false => None,
+        };
+        let filter = match level.restricts_network() && support.network {
+            true => Some(network_filter()?),
+            false => None,
+        };
+
+        // SAFETY: the closure runs between fork and exec; it only makes
+        // syscalls on data prepared above and allocates nothing on success
+        unsafe {
+            process.pre_exec(move || {
+                if let Some(ruleset) = ruleset.take() {
+                    ruleset
+                        .restrict_self()
+                        .map_err(|_| io::Error::from_raw_os_error(libc::EPERM))?;
+                }
+                if let Some(filter) = &filter {
+                    seccompiler::apply_filter(filter)
+                        .map_err(|_| io::Error::from_raw_os_error(libc::EPERM))?;
+                }
+                Ok(())
+            });
+        }
+        Ok(process)
+    }
+
+    /// Seccomp filter failing `socket()` for internet and raw packet sockets
+    /// with EACCES; local (Unix) sockets keep working
+    fn network_filter() -> Result<BpfProgram> {
+        let rules = BLOCKED_FAMILIES
+            .iter()
+            .map(|&family| {
+                SeccompCondition::new(0, SeccompCmpArgLen::Dword, SeccompCmpOp::Eq, family as u64)
+                    .and_then(|condition| SeccompRule::new(vec![condition]))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let filter = SeccompFilter::new(
+            [(libc::SYS_socket, rules)].into_iter().collect(),
+            SeccompAction::Allow,
+            SeccompAction::Errno(libc::EACCES as u32),
+            TargetArch::try_from(std::env::consts::ARCH)?,
+        )?;
+        Ok(filter.try_into()?)
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::{writable_dirs, Support};
+    use agent_core::confinement::ConfinementLevel;
+    use anyhow::Result;
+    use std::path::Path;
+    use std::process::Command;
+
+    const SANDBOX_EXEC: &str = "/usr/bin/sandbox-exec";
+
+    pub const FILESYSTEM_NEEDS: &str = SANDBOX_EXEC;
+    pub const NETWORK_NEEDS: &str = SANDBOX_EXEC;
+
+    pub fn support() -> Support {
+        let available = Path::new(SANDBOX_EXEC).exists();
+        Support {
+            filesystem: available,
+            network: available,
+        }
+    }
+
+    pub fn shell(command: &str, level: ConfinementLevel, workspace: &Path) -> Result<Command> {
+        if level == ConfinementLevel::None || !support().filesystem {
+            let mut process = Command::new("sh");
+            process.arg("-c").arg(command);
+            return Ok(process);
+        }
+
+        let mut process = Command::new(SANDBOX_EXEC);
+        process
+            .arg("-p")
+            .arg(profile(level, workspace))
+            .arg("sh")
+            .arg("-c")
+            .arg(command);
+        Ok(process)
+    }
+
+    /// Sandbox profile for `level`; later rules take precedence
+    fn profile(level: ConfinementLevel, workspace: &Path) -> String {
+        let workspace = workspace
+            .canonicalize()
+            .unwrap_or_else(|_| workspace.to_path_buf());
+        let mut writable = vec![format!("(subpath {})", quote(&workspace))];
+        writable.extend(
+            writable_dirs()
+                .iter()
+                .map(|dir| format!("(subpath {})", quote(dir))),
+        );
+
+        let mut profile = String::from("(version 1)\n(allow default)\n");
+        profile.push_str("(deny file-write*)\n");
+        profile.push_str(&format!(
+            "(allow file-write* {} (literal \"/dev/null\") (literal \"/dev/tty\"))\n",
+            writable.join(" ")
+        ));
+        if level.restricts_network() {
+            profile.push_str("(deny network*)\n");
+        }
+        profile
+    }
+
+    fn quote(path: &Path) -> String {
+        let path = path.to_string_lossy();
+        format!("\"{}\"", path.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+mod platform {
+    use super::Support;
+    use agent_core::confinement::ConfinementLevel;
+    use anyhow::Result;
+    use std::path::Path;
+    use std::process::Command;
+
+    pub const FILESYSTEM_NEEDS: &str = "Linux or macOS";
+    pub const NETWORK_NEEDS: &str = "Linux or macOS";
+
+    pub fn support() -> Support {
+        Support {
+            filesystem: false,
+            network: false,
+        }
+    }
+
+    pub fn shell(command: &str, _level: ConfinementLevel, _workspace: &Path) -> Result<Command> {
+        let mut process = Command::new("sh");
+        process.arg("-c").arg(command);
+        Ok(process)
+    }
+}
@@ -0,0 +1,185 @@
+//! Console output: verbosity levels, status lines and spinners
+//!
+//! All human-facing diagnostics go through this module so `-s` keeps scripts
+//! quiet and `-v`/`-vv` expose what the agent is doing. Status lines share one
+//! format (`✓`, `✗`, `⚠️`), and spinners only appear on an interactive terminal.
+
+use indicatif::{ProgressBar, ProgressStyle};
+use std::io::IsTerminal;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// How much the console prints
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Verbosity {
+    /// Only results, errors and approval prompts
+    Quiet,
+    /// Progress and status lines
+    #[default]
+    Normal,
+    /// Plus agent loop details (iterations, token counts, timings)
+    Verbose,
+    /// Plus raw prompts and model outputs
+    Debug,
+}
+
+impl Verbosity {
+    /// Map `-s` (`--quiet`) and the number of `-v` flags to a level
+    pub fn from_flags(quiet: bool, verbose: u8) -> Self {
+        match (quiet, verbose) {
+            (true, _) => Self::Quiet,
+            (false, 0) => Self::Normal,
+            (false, 1) => Self::Verbose,
+            (false, _) => Self::Debug,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Settings {
+    verbosity: Verbosity,
+    decorations: bool,
+}
+
+static SETTINGS: OnceLock<Settings> = OnceLock::new();
+
+/// Configure the console once at startup
+///
+/// `decorations` enables spinners; disable it when stdout carries
+/// machine-readable output.
+pub fn init(verbosity: Verbosity, decorations: bool) {
+    let _ = SETTINGS.set(Settings {
+        verbosity,
+        decorations,
+    });
+}
+
+fn settings() -> Settings {
+    SETTINGS.get().copied().unwrap_or(Settings {
+        verbosity: Verbosity::Normal,
+        decorations: true,
+    })
+}
+
+pub fn verbosity() -> Verbosity {
+    settings().verbosity
+}
+
+fn enabled(level: Verbosity) -> bool {
+    verbosity() >= level
+}
+
+/// Plain progress output (stdout)
+pub fn info(message: impl AsRef<str>) {
+    if enabled(Verbosity::Normal) {
+        println!("{}", message.as_ref());
+    }
+}
+
+/// `✓` status line (stdout)
+pub fn success(message: impl AsRef<str>) {
+    if enabled(Verbosity::Normal) {
+        println!("  ✓ {}", message.as_ref());
+    }
+}
+
+/// `✗` status line (stderr); shown even in quiet mode
+pub fn failure(message: impl AsRef<str>) {
+    eprintln!("  ✗ {}", message.as_ref());
+}
+
+/// `⚠️` warning (stderr)
+pub fn warn(message: impl AsRef<str>) {
+    if enabled(Verbosity::Normal) {
+        eprintln!("⚠️  {}", message.as_ref());
+    }
+}
+
+/// Progress of a long job (stderr), so stdout stays free for its results
+pub fn progress(message: impl AsRef<str>) {
+    if enabled(Verbosity::Normal) {
+        eprintln!("{}", message.as_ref());
+    }
+}
+
+/// Details shown with `-v` (stderr)
+pub fn verbose(message: impl AsRef<str>) {
+    if enabled(Verbosity::Verbose) {
+        eprintln!("· {}", message.as_ref());
+    }
+}
+
+/// Raw prompts and outputs shown with `-vv` (stderr)
+pub fn debug(message: impl AsRef<str>) {
+    if enabled(Verbosity::Debug) {
+        eprintln!("{}", message.as_ref());
+    }
+}
+
+/// A unified diff for review (stdout); shown even in quiet mode
+///
+/// Added lines are green, removed lines red and hunk headers cyan when stdout
+/// is a terminal and decorations are enabled.
+pub fn diff(patch: &str) {
+    let colored = settings().decorations && std::io::stdout().is_terminal();
+    for line in patch.lines() {
+        let color = match line.as_bytes().first() {
+            _ if !colored => None,
+            _ if line.starts_with("+++") || line.starts_with("---") => Some("1"),
+            Some(b'+') => Some("32"),
+            Some(b'-') => Some("31"),
+            Some(b'@') => Some("36"),
+            _ => None,
+        };
+        match color {
+            Some(code) => println!("    \x1b[{}m{}\x1b[0m", code, line),
+            None => println!("    {}", line),
+        }
+    }
+}
+
+/// A spinner on stderr while a long operation runs
+///
+/// Hidden unless stderr is a terminal, decorations are enabled and the console
+/// is not quiet. The spinner is cleared when dropped.
+pub struct Spinner {
+    bar: Option<ProgressBar>,
+}
+
+impl Spinner {
+    pub fn start(message: impl Into<String>) -> Self {
+        let settings = settings();
+        let visible = settings.decorations
+            && settings.verbosity >= Verbosity::Normal
+            && std::io::stderr().is_terminal();
+        if !visible {
+            return Self { bar: None };
+        }
+
+        let bar = ProgressBar::new_spinner();
+        if let Ok(style) = ProgressStyle::with_template("{spinner} {msg} ({elapsed})") {
+            bar.set_style(style);
+        }
+        bar.set_message(message.into());
+        bar.enable_steady_tick(Duration::from_millis(100));
+        Self { bar: Some(bar) }
+    }
+
+    /// Replaces the message; can be moved to whatever reports the progress
+    pub fn message_setter(&self) -> impl Fn(String) + Send + Sync + 'static {
+        let bar = self.bar.clone();
+        move |message| {
+            if let Some(bar) = &bar {
+                bar.set_message(message);
+            }
+        }
+    }
+}
+
+impl Drop for Spinner {
+    fn drop(&mut self) {
+        if let Some(bar) = self.bar.take() {
+            bar.finish_and_clear();
+        }
+    }
+}
@@ -0,0 +1,93 @@
+//! Critic reviews on a backend of their own (`--critic`)
+//!
+//! The agent loop owns its backend, and in-process backends cannot move
+//! between threads, so the critic's backend is loaded on a thread of its own.
+//! [`CriticReviewer`] sends it prompts through a channel, which makes it a
+//! [`Reviewer`] that [`CriticGuard`](agent_core::CriticGuard) can share.
+
+use crate::console;
+use crate::llm::LLMInput;
+use crate::{load_backend, BackendConfig};
+use agent_core::Reviewer;
+use anyhow::{anyhow, Result};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Mutex;
+use std::thread;
+
+/// Tokens a critic reply may take
+const CRITIC_MAX_TOKENS: usize = 256;
+
+type Reply = std::result::Result<String, String>;
+
+/// Runs critic prompts on the critic's thread
+pub struct CriticReviewer {
+    channel: Mutex<(Sender<String>, Receiver<Reply>)>,
+}
+
+impl CriticReviewer {
+    /// Load the critic's backend on a new thread; its replies end at `stop`
+    pub fn spawn(config: BackendConfig, stop: Vec<String>) -> Result<Self> {
+        let (prompts, prompt_rx) = mpsc::channel::<String>();
+        let (reply_tx, replies) = mpsc::channel::<Reply>();
+
+        thread::Builder::new()
+            .name("critic".to_string())
+            .spawn(move || {
+                let mut backend = match load_backend(&config) {
+                    Ok(backend) => {
+                        let _ = reply_tx.send(Ok(String::new()));
+                        backend
+                    }
+                    Err(err) => {
+                        let _ = reply_tx.send(Err(format!("{:#}", err)));
+                        return;
+                    }
+                };
+                let mut first_generation = true;
+                for prompt in prompt_rx {
+                    let reply = backend
+                        .infer(LLMInput {
+                            prompt,
+                            max_tokens: CRITIC_MAX_TOKENS,
+                            stop: stop.clone(),
+                            first_generation,
+                            images: Vec::new(),
+                            chat: None,
+                            progress: None,
+                        })
+                        .map(|output| {
+                            first_generation = false;
+                            output.text
+                        })
+                        .map_err(|err| format!("{:#}", err));
+                    if reply_tx.send(reply).is_err() {
+                        break;
+                    }
+                }
+            })?;
+
+        match replies.recv() {
+            Ok(Ok(_)) => Ok(Self {
+                channel: Mutex::new((prompts, replies)),
+            }),
+            Ok(Err(err)) => Err(anyhow!("Failed to load the critic's backend: {}", err)),
+            Err(_) => Err(anyhow!("The critic's thread exited while loading")),
+        }
+    }
+}
+
+impl Reviewer for CriticReviewer {
+    fn review(&self, prompt: &str) -> Reply {
+        let channel = self.channel.lock().map_err(|_| "critic unavailable")?;
+        let (prompts, replies) = &*channel;
+        let reply = prompts
+            .send(prompt.to_string())
+            .map_err(|_| "critic unavailable".to_string())
+            .and_then(|_| replies.recv().map_err(|_| "critic unavailable".to_string()))
+            .and_then(|reply| reply);
+        if let Err(err) = &reply {
+            console::warn(format!("Critic failed, keeping the answer: {}", err));
+        }
+        reply
+    }
+}
@@ -0,0 +1,386 @@
+//! `agent doctor`: check what a run needs before the first one
+//!
+//! Most first-run failures (a model path that holds a web page, llama.cpp
+//! built without GPU support, a SKILL.md that no longer parses) otherwise
+//! surface halfway through a run as an error chain. The doctor checks each
+//! piece on its own and says how to fix what is wrong. It loads the model but
+//! generates nothing.
+
+use crate::config::{self, DEFAULT_CONFIG_FILE};
+use crate::console::Spinner;
+use crate::skill_commands::lint_skill;
+use crate::{
+    anthropic_backend, gemini_backend, llama_cpp_backend, BackendConfig, BackendKind,
+    DEFAULT_SKILLS_DIR, DEFAULT_TOOLS_DIR,
+};
+use agent_core::profile::AgentConfig;
+use agent_core::skill_manifest::LintLevel;
+use agent_core::tool_manifest::parse_tool_manifest;
+use anyhow::Result;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// How long a server may take to answer the reachability check
+const SERVER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// GGUF versions llama.cpp reads
+const GGUF_VERSIONS: std::ops::RangeInclusive<u32> = 2..=3;
+
+enum Status {
+    Ok,
+    Warn,
+    Fail,
+}
+
+/// The outcome of one check, with how to fix it unless it passed
+struct Check {
+    name: &'static str,
+    status: Status,
+    detail: String,
+    fix: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Ok,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn warn(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Warn,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Fail,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn passed(&self) -> bool {
+        !matches!(self.status, Status::Fail)
+    }
+
+    fn print(&self) {
+        let mark = match self.status {
+            Status::Ok => "✓",
+            Status::Warn => "⚠️ ",
+            Status::Fail => "✗",
+        };
+        println!("  {} {}: {}", mark, self.name, self.detail);
+        if let Some(fix) = &self.fix {
+            println!("      → {}", fix);
+        }
+    }
+}
+
+/// Run every check and print the results; `Ok(false)` when one failed
+pub fn run(backend: &BackendConfig, config: Option<&Path>, profile: Option<&str>) -> Result<bool> {
+    let mut checks = backend_checks(backend);
+    checks.push(config_check(config, profile));
+    checks.push(skills_check(Path::new(DEFAULT_SKILLS_DIR)));
+    checks.push(tools_check(Path::new(DEFAULT_TOOLS_DIR)));
+    checks.push(match find_command("sh") {
+        Some(path) => Check::ok("sh", path.display().to_string()),
+        None => Check::fail(
+            "sh",
+            "not found on PATH; the shell tool runs commands with it",
+            "Install a POSIX shell or add its directory to PATH",
+        ),
+    });
+    checks.push(match find_command("git") {
+        Some(path) => Check::ok("git", path.display().to_string()),
+        None => Check::warn(
+            "git",
+            "not found on PATH; shell commands the model runs often use it",
+            "Install git, or expect git commands to fail",
+        ),
+    });
+
+    for check in &checks {
+        check.print();
+    }
+    let failed = checks.iter().filter(|check| !check.passed()).count();
+    let warned = checks
+        .iter()
+        .filter(|check| matches!(check.status, Status::Warn))
+        .count();
+    if failed == 0 {
+        println!("✓ Ready to run ({} warning(s))", warned);
+    } else {
+        println!("✗ {} problem(s), {} warning(s)", failed, warned);
+    }
+    Ok(failed == 0)
+}
+
+fn backend_checks(config: &BackendConfig) -> Vec<Check> {
+    match config.kind {
+        BackendKind::LlamaCpp => {
+            let mut checks = vec![model_file_check(&config.model)];
+            let runtime = match llama_cpp_backend::gpu_offload_supported() {
+                Ok(gpu) => {
+                    checks.push(Check::ok("llama.cpp", "initialized"));
+                    checks.push(if gpu {
+                        Check::ok("GPU offload", "available")
+                    } else {
+                        Check::warn(
+                            "GPU offload",
+                            "this llama.cpp build runs on the CPU only",
+                            "Build llama-cpp-2 with its metal, cuda or vulkan feature for faster inference",
+                        )
+                    });
+                    true
+                }
+                Err(err) => {
+                    checks.push(Check::fail(
+                        "llama.cpp",
+                        format!("failed to initialize: {:#}", err),
+                        "Rebuild with CMake and a C/C++ compiler installed (see Prerequisites)",
+                    ));
+                    false
+                }
+            };
+            if runtime && checks[0].passed() {
+                checks.push(model_load_check(&config.model));
+            }
+            checks
+        }
+        BackendKind::LlamaServer | BackendKind::Mistralrs => vec![server_check(config)],
+        BackendKind::Anthropic => vec![api_key_check(anthropic_backend::API_KEY_VAR)],
+        BackendKind::Gemini => vec![api_key_check(gemini_backend::API_KEY_VAR)],
+    }
+}
+
+/// The model file exists and starts like a GGUF file
+fn model_file_check(path: &Path) -> Check {
+    const NAME: &str = "model file";
+    let shown = path.display();
+    let download = "Download a GGUF model (see Quick Start) or pass -m PATH";
+    let mut header = [0u8; 32];
+    let read = match File::open(path).and_then(|mut file| file.read(&mut header)) {
+        Ok(read) => read,
+        Err(_) if !path.exists() => {
+            return Check::fail(NAME, format!("{} not found", shown), download)
+        }
+        Err(err) => return Check::fail(NAME, format!("cannot read {}: {}", shown, err), download),
+    };
+    let header = &header[..read];
+    let size = fs::metadata(path).map(|meta| meta.len()).unwrap_or(0);
+
+    if let Some(version) = header.strip_prefix(b"GGUF").and_then(|rest| rest.get(..4)) {
+        let version = u32::from_le_bytes([version[0], version[1], version[2], version[3]]);
+        return if GGUF_VERSIONS.contains(&version) {
+            Check::ok(
+                NAME,
+                format!("{} (GGUF v{}, {:.1} GB)", shown, version, size as f64 / 1e9),
+            )
+        } else {
+            Check::fail(
+                NAME,
+                format!(
+                    "{} is GGUF v{}, which llama.cpp cannot read",
+                    shown, version
+                ),
+                "Download a current GGUF build of the model",
+            )
+        };
+    }
+    let text = String::from_utf8_lossy(header).trim_start().to_lowercase();
+    if text.starts_with("version https://git-lfs") {
+        Check::fail(
+            NAME,
+            format!("{} is a Git LFS pointer, not the model", shown),
+            "Run `git lfs pull` in the model's repository, or download the file directly",
+        )
+    } else if text.starts_with('<') {
+        Check::fail(
+            NAME,
+            format!("{} is a web page, not a model", shown),
+            "Download the file itself: on Hugging Face use the /resolve/ URL, not /blob/",
+        )
+    } else {
+        Check::fail(
+            NAME,
+            format!("{} is not a GGUF file", shown),
+            "llama.cpp only loads GGUF; download a GGUF build or convert the model with llama.cpp's convert_hf_to_gguf.py",
+        )
+    }
+}
+
+fn model_load_check(path: &Path) -> Check {
+    let spinner = Spinner::start("Loading the model");
+    let started = Instant::now();
+    let loaded = llama_cpp_backend::LlamaCppBackend::new(path);
+    drop(spinner);
+    match loaded {
+        Ok(_) => Check::ok(
+            "model load",
+            format!("loaded in {:.1}s", started.elapsed().as_secs_f32()),
+        ),
+        Err(err) => Check::fail(
+            "model load",
+            format!("{:#}", err),
+            "The file may be truncated or need a newer llama.cpp: download it again, or update llama-cpp-2",
+        ),
+    }
+}
+
+/// The server answers its model list
+fn server_check(config: &BackendConfig) -> Check {
+    let url = format!("{}/v1/models", config.endpoint().trim_end_matches('/'));
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .http_status_as_error(false)
+        .timeout_global(Some(SERVER_TIMEOUT))
+        .build()
+        .into();
+    match agent.get(&url).call() {
+        Ok(response) if response.status().is_success() => {
+            Check::ok("server", format!("{} is up", config.describe()))
+        }
+        Ok(response) => Check::warn(
+            "server",
+            format!("{} answered {}", url, response.status()),
+            "Check that --endpoint points at the server's base URL",
+        ),
+        Err(err) => Check::fail(
+            "server",
+            format!("cannot reach {}: {}", config.describe(), err),
+            "Start the server (e.g. `llama-server -m model.gguf`) or pass --endpoint URL",
+        ),
+    }
+}
+
+fn api_key_check(var: &str) -> Check {
+    match std::env::var(var) {
+        Ok(key) if !key.trim().is_empty() => Check::ok("API key", format!("{} is set", var)),
+        _ => Check::fail(
+            "API key",
+            format!("{} is not set", var),
+            format!("export {}=... (or add it to .env)", var),
+        ),
+    }
+}
+
+/// The config file parses, and so do the profiles' prompt files
+fn config_check(config: Option<&Path>, profile: Option<&str>) -> Check {
+    const NAME: &str = "config";
+    let path = config.unwrap_or(Path::new(DEFAULT_CONFIG_FILE));
+    if !path.exists() {
+        return match (config, profile) {
+            (None, None) => Check::ok(
+                NAME,
+                format!("no {} (profiles are optional)", path.display()),
+            ),
+            _ => Check::fail(
+                NAME,
+                format!("{} not found", path.display()),
+                "Create it (see System Prompts and Profiles) or pass --config FILE",
+            ),
+        };
+    }
+    let fix = format!("Fix {} (see System Prompts and Profiles)", path.display());
+    let parsed = fs::read_to_string(path)
+        .map_err(anyhow::Error::from)
+        .and_then(|yaml| Ok(AgentConfig::from_yaml(&yaml)?));
+    let names: Vec<String> = match (parsed, profile) {
+        (Err(err), _) => return Check::fail(NAME, format!("{}: {:#}", path.display(), err), fix),
+        (Ok(_), Some(name)) => vec![name.to_string()],
+        (Ok(parsed), None) => parsed.profiles.into_keys().collect(),
+    };
+    for name in &names {
+        if let Err(err) = config::load_profile(Some(path), name) {
+            return Check::fail(NAME, format!("profile '{}': {:#}", name, err), fix);
+        }
+    }
+    Check::ok(
+        NAME,
+        format!("{}: {} profile(s)", path.display(), names.len()),
+    )
+}
+
+fn skills_check(dir: &Path) -> Check {
+    const NAME: &str = "skills";
+    let manifests = manifests_in(dir, "SKILL.md");
+    if manifests.is_empty() {
+        return Check::ok(NAME, format!("none in {}", dir.display()));
+    }
+    for manifest in &manifests {
+        let errors = match lint_skill(manifest) {
+            Ok(lints) => lints
+                .iter()
+                .filter(|lint| lint.level == LintLevel::Error)
+                .count(),
+            Err(_) => 1,
+        };
+        if errors > 0 {
+            let skill = manifest.parent().unwrap_or(dir);
+            return Check::fail(
+                NAME,
+                format!("{} has {} error(s)", manifest.display(), errors),
+                format!("Run `agent-native skills validate {}`", skill.display()),
+            );
+        }
+    }
+    Check::ok(
+        NAME,
+        format!("{} skill(s) in {} parse", manifests.len(), dir.display()),
+    )
+}
+
+fn tools_check(dir: &Path) -> Check {
+    const NAME: &str = "tools";
+    let manifests = manifests_in(dir, "TOOL.md");
+    if manifests.is_empty() {
+        return Check::ok(NAME, format!("none in {} (built-in tools)", dir.display()));
+    }
+    for manifest in &manifests {
+        let parsed = fs::read_to_string(manifest)
+            .map_err(|err| err.to_string())
+            .and_then(|content| parse_tool_manifest(&content).map_err(|err| err.to_string()));
+        if let Err(err) = parsed {
+            return Check::fail(
+                NAME,
+                format!("{}: {}", manifest.display(), err),
+                "Fix its frontmatter (see Tool Manifests)",
+            );
+        }
+    }
+    Check::ok(
+        NAME,
+        format!("{} tool(s) in {} parse", manifests.len(), dir.display()),
+    )
+}
+
+/// `<dir>/*/<file>`, sorted
+fn manifests_in(dir: &Path, file: &str) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path().join(file))
+        .filter(|path| path.is_file())
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// Where `name` is found on PATH
+fn find_command(name: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(name))
+        .find(|candidate| candidate.is_file())
+}
@@ -0,0 +1,80 @@
+//! `--dry-run`: the calls the agent would make, without making them
+//!
+//! One model call produces the plan, and [`Plan`] reviews it against the same
+//! manifests, pre-execution guardrails and approval policy as a real run. No
+//! tool or skill runs and no audit log is written.
+
+use crate::events::{AgentEvent, EventSink};
+use crate::llm::{ChatContext, LLMInput};
+use crate::skill_discovery::LoadedSkills;
+use crate::{fail, generate, load_backend, AgentArgs, ToolHost};
+use agent_core::{
+    agent::AgentState,
+    plan::{plan_system_prompt, Plan, PlanReview},
+    stop::PromptPhase,
+    transcript::ChatTranscript,
+};
+use anyhow::Result;
+
+pub fn run(
+    args: &AgentArgs,
+    system_prompt: &str,
+    skills: &LoadedSkills,
+    tools: &ToolHost,
+    events: &EventSink,
+) -> Result<()> {
+    events.emit(AgentEvent::Started {
+        run_id: args.run_id.to_string(),
+        query: args.query.clone(),
+    });
+
+    let mut llm_backend = load_backend(&args.backend)?;
+    let state = AgentState::new(&args.query);
+    let transcript = ChatTranscript::from_history(&state.history, &[])
+        .with_system(plan_system_prompt(system_prompt, args.max_iterations));
+    let mut prompt = transcript.to_plain_text();
+    prompt.push_str("Assistant: ");
+
+    // The plan covers the whole run, so it gets every iteration's token budget
+    let output = generate(
+        &mut llm_backend,
+        LLMInput {
+            prompt,
+            max_tokens: args.max_tokens * args.max_iterations,
+            stop: args.stop.for_phase(PromptPhase::Turn).to_vec(),
+            first_generation: true,
+            // No native tools: the plan is a JSON array, not a call
+            images: Vec::new(),
+            chat: Some(ChatContext {
+                transcript,
+                tools: Vec::new(),
+            }),
+            progress: None,
+        },
+        "Planning",
+        events,
+    )?;
+
+    let skill_names: Vec<String> = skills.keys().cloned().collect();
+    let review = PlanReview {
+        registry: &tools.registry,
+        policy: &tools.policy,
+        guards: &tools.guards,
+        skills: &skill_names,
+    };
+    match Plan::parse(&args.query, &output.text, &review) {
+        Ok(plan) => {
+            events.emit(AgentEvent::Planned { plan });
+            Ok(())
+        }
+        Err(e) => fail(
+            events,
+            "invalid_plan",
+            format!(
+                "\n❌ ERROR: The model did not produce a usable plan: {}\n\nOutput: \"{}\"",
+                e,
+                output.text.lines().next().unwrap_or_default()
+            ),
+        ),
+    }
+}
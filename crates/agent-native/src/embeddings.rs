@@ -0,0 +1,181 @@
+//! `agent embed`, and the embeddings request the server backends share
+//!
+//! Backends that can embed text (see [`LLMBackend::embed`]) return one vector
+//! per text; `agent embed` prints them as JSON arrays, one line per text, for
+//! indexing or checking a model before relying on it for retrieval.
+//!
+//! The `semantic-relevance` guardrail and `--memory` embed through an
+//! [`EmbeddingWorker`]: like the critic, it loads its own backend on a thread
+//! of its own.
+
+use crate::console;
+use crate::input::{self, Input};
+use crate::llm::{BackendError, LLMBackend};
+use crate::load_backend;
+use crate::throttle::{prompt_tokens, Throttle};
+use crate::BackendConfig;
+use agent_core::Embedder;
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Mutex;
+use std::thread;
+
+type Reply = std::result::Result<Vec<Vec<f32>>, String>;
+
+/// Embed `texts`, or each non-empty line of stdin, and print the vectors
+pub fn run(config: &BackendConfig, texts: &[String], max_input_bytes: u64) -> Result<()> {
+    let texts: Vec<String> = if texts.is_empty() {
+        if !input::stdin_is_piped() {
+            bail!("Give the texts with --text or one per line on stdin");
+        }
+        Input::Stdin
+            .read("texts", max_input_bytes)?
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(str::to_string)
+            .collect()
+    } else {
+        texts.to_vec()
+    };
+
+    let mut backend = load_backend(config)?;
+    let Some(embeddings) = backend.embed(&texts).context(BackendError)? else {
+        bail!("{} cannot compute embeddings", config.describe());
+    };
+    if let Some(first) = embeddings.first() {
+        console::verbose(format!(
+            "{} embedding(s) of {} dimensions",
+            embeddings.len(),
+            first.len()
+        ));
+    }
+    for embedding in embeddings {
+        println!("{}", serde_json::to_string(&embedding)?);
+    }
+    Ok(())
+}
+
+/// The parts of an OpenAI-style `/v1/embeddings` response the host uses
+#[derive(Debug, Deserialize)]
+struct EmbeddingsResponse {
+    data: Vec<EmbeddingData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingData {
+    #[serde(default)]
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+/// Embed `texts` through an OpenAI-style `/v1/embeddings` endpoint at `url`
+/// (llama-server, mistral.rs)
+pub fn openai_embeddings(
+    agent: &ureq::Agent,
+    throttle: &Throttle,
+    url: &str,
+    model: Option<&str>,
+    texts: &[String],
+) -> Result<Vec<Vec<f32>>> {
+    let mut body = json!({ "input": texts });
+    if let Some(model) = model {
+        body["model"] = json!(model);
+    }
+    let tokens = texts.iter().map(|text| prompt_tokens(text)).sum::<usize>() as u64;
+    let mut response: EmbeddingsResponse = throttle
+        .send(tokens, || agent.post(url).send_json(&body))
+        .with_context(|| format!("Embedding request to {} failed", url))?
+        .body_mut()
+        .read_json()
+        .with_context(|| format!("Invalid embedding response from {}", url))?;
+    if response.data.len() != texts.len() {
+        bail!(
+            "{} returned {} embeddings for {} texts",
+            url,
+            response.data.len(),
+            texts.len()
+        );
+    }
+    response.data.sort_by_key(|data| data.index);
+    Ok(response
+        .data
+        .into_iter()
+        .map(|data| data.embedding)
+        .collect())
+}
+
+/// Computes embeddings for guardrails and memory on the embedder's thread
+pub struct EmbeddingWorker {
+    channel: Mutex<(Sender<Vec<String>>, Receiver<Reply>)>,
+}
+
+impl EmbeddingWorker {
+    /// Load a backend on a new thread and check that it can embed; `purpose`
+    /// names what needs the embeddings if it cannot
+    pub fn spawn(config: BackendConfig, purpose: &str) -> Result<Self> {
+        let (requests, request_rx) = mpsc::channel::<Vec<String>>();
+        let (reply_tx, replies) = mpsc::channel::<Reply>();
+
+        let describe = config.describe();
+        thread::Builder::new()
+            .name("embedder".to_string())
+            .spawn(move || {
+                let mut backend = match load_backend(&config) {
+                    Ok(backend) => backend,
+                    Err(err) => {
+                        let _ = reply_tx.send(Err(format!("{:#}", err)));
+                        return;
+                    }
+                };
+                for texts in request_rx {
+                    let reply = match backend.embed(&texts) {
+                        Ok(Some(embeddings)) => Ok(embeddings),
+                        Ok(None) => Err(format!("{} cannot compute embeddings", config.describe())),
+                        Err(err) => Err(format!("{:#}", err)),
+                    };
+                    if reply_tx.send(reply).is_err() {
+                        break;
+                    }
+                }
+            })?;
+
+        let worker = Self {
+            channel: Mutex::new((requests, replies)),
+        };
+        worker
+            .embed_texts(vec!["probe".to_string()])
+            .map_err(|err| anyhow!("{} needs embeddings: {}", purpose, err))?;
+        console::verbose(format!("Embedder: {}", describe));
+        Ok(worker)
+    }
+
+    /// One embedding per text, in order
+    pub fn embed_texts(&self, texts: Vec<String>) -> Reply {
+        let channel = self.channel.lock().map_err(|_| "embedder unavailable")?;
+        let (requests, replies) = &*channel;
+        requests
+            .send(texts)
+            .map_err(|_| "embedder unavailable".to_string())
+            .and_then(|_| {
+                replies
+                    .recv()
+                    .map_err(|_| "embedder unavailable".to_string())
+            })
+            .and_then(|reply| reply)
+    }
+}
+
+impl Embedder for EmbeddingWorker {
+    fn embed(&self, texts: &[String]) -> Reply {
+        let reply = self.embed_texts(texts.to_vec());
+        if let Err(err) = &reply {
+            console::warn(format!(
+                "Embedding failed, skipping the relevance check: {}",
+                err
+            ));
+        }
+        reply
+    }
+}
@@ -0,0 +1,170 @@
+//! Environment facts for the system prompt (`--context`)
+//!
+//! Each provider reads one part of the host: the local clock, the operating
+//! system, the working directory. The facts are stated at the end of the
+//! system prompt (see [`agent_core::environment`]).
+
+use agent_core::environment::{ContextFact, ContextProvider, LocalTime};
+use anyhow::{bail, Result};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Provider names `--context` accepts
+pub const PROVIDERS: [&str; 3] = ["time", "os", "cwd"];
+
+/// Entries of the working directory named in its summary
+const MAX_LISTED_ENTRIES: usize = 10;
+
+/// The providers named by `--context`, in the order given
+pub fn providers(names: &[String]) -> Result<Vec<Box<dyn ContextProvider>>> {
+    names
+        .iter()
+        .map(|name| -> Result<Box<dyn ContextProvider>> {
+            Ok(match name.trim() {
+                "time" => Box::new(Clock),
+                "os" => Box::new(System),
+                "cwd" => Box::new(WorkingDir),
+                other => bail!(
+                    "Unknown context '{}'. Expected one of: {}",
+                    other,
+                    PROVIDERS.join(", ")
+                ),
+            })
+        })
+        .collect()
+}
+
+/// Local date, time and time zone
+struct Clock;
+
+impl ContextProvider for Clock {
+    fn name(&self) -> &str {
+        "time"
+    }
+
+    fn facts(&self) -> Vec<ContextFact> {
+        let Ok(now) = SystemTime::now().duration_since(UNIX_EPOCH) else {
+            return Vec::new();
+        };
+        local_time(now.as_secs() as i64).facts()
+    }
+}
+
+/// The local clock at `unix_seconds`, as the C library sees it (`TZ` or the
+/// system zone)
+#[cfg(unix)]
+pub fn local_time(unix_seconds: i64) -> LocalTime {
+    let time = unix_seconds as libc::time_t;
+    // SAFETY: `tm` is plain data that localtime_r fills in; tm_zone points
+    // into static zone data when set
+    unsafe {
+        let mut tm: libc::tm = std::mem::zeroed();
+        if libc::localtime_r(&time, &mut tm).is_null() {
+            return utc(unix_seconds);
+        }
+        let zone = (!tm.tm_zone.is_null()).then(|| {
+            std::ffi::CStr::from_ptr(tm.tm_zone)
+                .to_string_lossy()
+                .into_owned()
+        });
+        LocalTime {
+            unix_seconds,
+            utc_offset_seconds: tm.tm_gmtoff as i32,
+            zone,
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub fn local_time(unix_seconds: i64) -> LocalTime {
+    utc(unix_seconds)
+}
+
+fn utc(unix_seconds: i64) -> LocalTime {
+    LocalTime {
+        unix_seconds,
+        utc_offset_seconds: 0,
+        zone: Some("UTC".to_string()),
+    }
+}
+
+/// Operating system and architecture
+struct System;
+
+impl ContextProvider for System {
+    fn name(&self) -> &str {
+        "os"
+    }
+
+    fn facts(&self) -> Vec<ContextFact> {
+        vec![ContextFact::new(
+            "Operating system",
+            format!("{} ({})", std::env::consts::OS, std::env::consts::ARCH),
+        )]
+    }
+}
+
+/// The working directory and a summary of what it holds
+struct WorkingDir;
+
+impl ContextProvider for WorkingDir {
+    fn name(&self) -> &str {
+        "cwd"
+    }
+
+    fn facts(&self) -> Vec<ContextFact> {
+        let Ok(dir) = std::env::current_dir() else {
+            return Vec::new();
+        };
+        let mut facts = vec![ContextFact::new(
+            "Working directory",
+            dir.display().to_string(),
+        )];
+        if let Some(summary) = listing_summary(&dir) {
+            facts.push(ContextFact::new("Contents", summary));
+        }
+        facts
+    }
+}
+
+/// `N entries: a.txt, src/, ...`, directories marked with a slash and hidden
+/// entries counted but not named
+fn listing_summary(dir: &Path) -> Option<String> {
+    let mut names: Vec<String> = std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            match entry.file_type() {
+                Ok(kind) if kind.is_dir() => format!("{}/", name),
+                _ => name,
+            }
+        })
+        .collect();
+    if names.is_empty() {
+        return Some("nothing".to_string());
+    }
+    let total = names.len();
+    names.retain(|name| !name.starts_with('.'));
+    if names.is_empty() {
+        return Some(format!("{} hidden {}", total, entries(total)));
+    }
+    names.sort();
+    let more = names.len() > MAX_LISTED_ENTRIES;
+    names.truncate(MAX_LISTED_ENTRIES);
+    Some(format!(
+        "{} {}: {}{}",
+        total,
+        entries(total),
+        names.join(", "),
+        if more { ", ..." } else { "" }
+    ))
+}
+
+fn entries(count: usize) -> &'static str {
+    if count == 1 {
+        "entry"
+    } else {
+        "entries"
+    }
+}
@@ -0,0 +1,386 @@
+//! `agent eval`: run a suite across targets and compare them
+//!
+//! Each case runs as a child `agent-native --output jsonl` process with the
+//! target's and the suite's arguments, so runs are isolated from each other
+//! and can go in parallel (`--jobs`, by default one per target; a target's
+//! `max_concurrent` caps its own runs, for rate-limited APIs). The runner
+//! answers the child's approval requests (tool calls are approved, sandbox
+//! changes are never applied) and reads the answer, tool calls, iterations
+//! and tokens from its events. Fixture cases run with `--replay`, their query
+//! taken from the recording unless the case gives one. See [`agent_core::eval`]
+//! for the suite format.
+
+use crate::console;
+use crate::scenario;
+use agent_core::eval::{
+    comparison_table, results_csv, summarize, CaseResult, CaseRun, EvalCase, EvalSuite, EvalTarget,
+    TargetSummary,
+};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often a running case is checked against its time limit
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The `--json` export
+#[derive(Serialize)]
+struct Report<'a> {
+    suite: &'a str,
+    summaries: &'a [TargetSummary],
+    results: &'a [CaseResult],
+}
+
+/// Options of `agent eval`
+#[derive(Debug)]
+pub struct EvalOptions {
+    /// Runs to execute at once (default: one per target)
+    pub jobs: Option<usize>,
+    /// Only run these targets
+    pub targets: Vec<String>,
+    pub json: Option<PathBuf>,
+    pub csv: Option<PathBuf>,
+}
+
+/// Run the suite at `path` and print the comparison
+///
+/// Returns whether every case passed on every target.
+pub fn run_suite(path: &Path, options: &EvalOptions) -> Result<bool> {
+    let yaml =
+        fs::read_to_string(path).with_context(|| format!("Cannot read {}", path.display()))?;
+    let suite =
+        EvalSuite::from_yaml(&yaml).with_context(|| format!("Cannot load {}", path.display()))?;
+    let targets = select_targets(&suite, &options.targets)?;
+    let base = path.parent().unwrap_or(Path::new("."));
+    let runs = suite.cases.len() * targets.len();
+    let workers = options.jobs.unwrap_or(targets.len()).clamp(1, runs);
+    console::info(format!(
+        "Running {} case(s) on {} target(s), {} at a time\n",
+        suite.cases.len(),
+        targets.len(),
+        workers
+    ));
+
+    let results = run_cases(&suite, &targets, base, workers)?;
+    let summaries = summarize(&results);
+    println!("\n{}", comparison_table(&summaries));
+
+    if let Some(path) = &options.json {
+        let report = Report {
+            suite: &suite.name,
+            summaries: &summaries,
+            results: &results,
+        };
+        fs::write(path, serde_json::to_string_pretty(&report)?)
+            .with_context(|| format!("Cannot write {}", path.display()))?;
+    }
+    if let Some(path) = &options.csv {
+        fs::write(path, results_csv(&results))
+            .with_context(|| format!("Cannot write {}", path.display()))?;
+    }
+
+    Ok(results.iter().all(|result| result.passed))
+}
+
+/// Run every case of `suite` on every target, `workers` at a time, and score
+/// the runs
+///
+/// Fixtures are resolved against `base`. Results come back in target, then
+/// case order.
+pub fn run_cases(
+    suite: &EvalSuite,
+    targets: &[EvalTarget],
+    base: &Path,
+    workers: usize,
+) -> Result<Vec<CaseResult>> {
+    let exe = std::env::current_exe()?;
+    let cases = resolve_cases(suite, base)?;
+
+    // Case-major, so every target makes progress from the start
+    let jobs: VecDeque<(&EvalTarget, &ResolvedCase)> = cases
+        .iter()
+        .flat_map(|case| targets.iter().map(move |target| (target, case)))
+        .collect();
+
+    let queue = JobQueue::new(jobs);
+    let results = Mutex::new(Vec::new());
+    thread::scope(|scope| {
+        for _ in 0..workers.max(1) {
+            scope.spawn(|| {
+                while let Some((target, case)) = queue.next() {
+                    let run = run_case(&exe, suite, target, case);
+                    queue.finish(target);
+                    let result = CaseResult::score(run, &case.case.expect);
+                    report(&result);
+                    results.lock().unwrap().push(result);
+                }
+            });
+        }
+    });
+
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|result| {
+        let target = targets.iter().position(|t| t.name == result.run.target);
+        let case = suite.cases.iter().position(|c| c.id == result.run.case);
+        (target, case)
+    });
+    Ok(results)
+}
+
+type Job<'a> = (&'a EvalTarget, &'a ResolvedCase<'a>);
+
+/// Runs waiting to start, handed out within each target's `max_concurrent`
+struct JobQueue<'a> {
+    state: Mutex<(VecDeque<Job<'a>>, HashMap<&'a str, usize>)>,
+    freed: Condvar,
+}
+
+impl<'a> JobQueue<'a> {
+    fn new(jobs: VecDeque<Job<'a>>) -> Self {
+        Self {
+            state: Mutex::new((jobs, HashMap::new())),
+            freed: Condvar::new(),
+        }
+    }
+
+    /// The first job whose target has room, waiting for one if none has;
+    /// `None` once the queue is empty
+    fn next(&self) -> Option<Job<'a>> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            let (jobs, running) = &mut *state;
+            if jobs.is_empty() {
+                return None;
+            }
+            let ready = jobs.iter().position(|(target, _)| {
+                let count = running.get(target.name.as_str()).copied().unwrap_or(0);
+                target.max_concurrent.is_none_or(|max| count < max.max(1))
+            });
+            if let Some(index) = ready {
+                let job = jobs.remove(index)?;
+                *running.entry(job.0.name.as_str()).or_default() += 1;
+                return Some(job);
+            }
+            state = self.freed.wait(state).unwrap();
+        }
+    }
+
+    fn finish(&self, target: &EvalTarget) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(count) = state.1.get_mut(target.name.as_str()) {
+            *count -= 1;
+        }
+        self.freed.notify_all();
+    }
+}
+
+/// A case with its query and fixture directory worked out
+struct ResolvedCase<'a> {
+    case: &'a EvalCase,
+    query: String,
+    fixture: Option<PathBuf>,
+}
+
+/// Resolve fixtures against the suite's directory and fill in missing queries
+fn resolve_cases<'a>(suite: &'a EvalSuite, base: &Path) -> Result<Vec<ResolvedCase<'a>>> {
+    suite
+        .cases
+        .iter()
+        .map(|case| {
+            let fixture = case.fixture.as_ref().map(|dir| base.join(dir));
+            let query = match (&case.query, &fixture) {
+                (Some(query), _) => query.clone(),
+                (None, Some(dir)) => {
+                    scenario::load(dir)
+                        .with_context(|| format!("Case '{}'", case.id))?
+                        .query
+                }
+                (None, None) => unreachable!("suites are checked for a query or fixture"),
+            };
+            Ok(ResolvedCase {
+                case,
+                query,
+                fixture,
+            })
+        })
+        .collect()
+}
+
+fn select_targets(suite: &EvalSuite, names: &[String]) -> Result<Vec<EvalTarget>> {
+    if suite.targets.is_empty() {
+        return Ok(vec![EvalTarget {
+            name: "default".to_string(),
+            args: Vec::new(),
+            max_concurrent: None,
+        }]);
+    }
+    if let Some(unknown) = names
+        .iter()
+        .find(|name| !suite.targets.iter().any(|target| &target.name == *name))
+    {
+        anyhow::bail!("Unknown target '{}'", unknown);
+    }
+    Ok(suite
+        .targets
+        .iter()
+        .filter(|target| names.is_empty() || names.contains(&target.name))
+        .cloned()
+        .collect())
+}
+
+fn report(result: &CaseResult) {
+    let run = &result.run;
+    let label = format!("{} / {}", run.target, run.case);
+    match &result.reason {
+        None => console::success(format!(
+            "{} ({} iteration(s), {} tokens, {:.1}s)",
+            label, run.iterations, run.tokens, run.wall_seconds
+        )),
+        Some(reason) => console::failure(format!("{}: {}", label, reason)),
+    }
+}
+
+/// Run one case on one target in a child process
+fn run_case(exe: &Path, suite: &EvalSuite, target: &EvalTarget, case: &ResolvedCase) -> CaseRun {
+    let mut args: Vec<String> = target.args.iter().chain(&suite.args).cloned().collect();
+    if let Some(dir) = &case.fixture {
+        args.push("--replay".to_string());
+        args.push(dir.display().to_string());
+    }
+    let mut run = run_agent(exe, &args, &case.query, suite.timeout_secs, true);
+    run.target = target.name.clone();
+    run.case = case.case.id.clone();
+    run
+}
+
+/// Run the agent on `query` in a child `--output jsonl` process with `args`
+///
+/// With `approve`, the child's tool calls that need approval are approved;
+/// otherwise they are declined. Sandbox changes are never applied. Failures
+/// to run the child end up in the run's `error`.
+pub fn run_agent(
+    exe: &Path,
+    args: &[String],
+    query: &str,
+    timeout_secs: u64,
+    approve: bool,
+) -> CaseRun {
+    let mut args = args.to_vec();
+    args.extend(["--query".to_string(), query.to_string()]);
+    run_agent_command(exe, &args, timeout_secs, approve)
+}
+
+/// Run a child `--output jsonl` process with `args`, which say what to run
+/// (`--query ...`, or a subcommand such as `run task.yaml`), as [`run_agent`]
+/// does
+pub fn run_agent_command(exe: &Path, args: &[String], timeout_secs: u64, approve: bool) -> CaseRun {
+    let started = Instant::now();
+    let mut run = match spawn_and_observe(exe, args, timeout_secs, approve) {
+        Ok(run) => run,
+        Err(e) => CaseRun {
+            error: Some(format!("{:#}", e)),
+            ..Default::default()
+        },
+    };
+    run.wall_seconds = started.elapsed().as_secs_f64();
+    run
+}
+
+fn spawn_and_observe(
+    exe: &Path,
+    args: &[String],
+    timeout_secs: u64,
+    approve: bool,
+) -> Result<CaseRun> {
+    let mut child = Command::new(exe)
+        .args(args)
+        .args(["--output", "jsonl"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Cannot start the agent")?;
+
+    let stdin = child.stdin.take().context("No stdin")?;
+    let stdout = child.stdout.take().context("No stdout")?;
+    let mut stderr = child.stderr.take().context("No stderr")?;
+    let events = thread::spawn(move || observe(stdout, stdin, approve));
+    let diagnostics = thread::spawn(move || {
+        let mut text = String::new();
+        let _ = stderr.read_to_string(&mut text);
+        text
+    });
+
+    let deadline = Instant::now() + Duration::from_secs(timeout_secs);
+    let mut timed_out = false;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            timed_out = true;
+            child.kill()?;
+            break child.wait()?;
+        }
+        thread::sleep(POLL_INTERVAL);
+    };
+
+    let mut run = events
+        .join()
+        .map_err(|_| anyhow::anyhow!("event reader panicked"))?;
+    let diagnostics = diagnostics.join().unwrap_or_default();
+    if timed_out {
+        run.error = Some(format!("timed out after {}s", timeout_secs));
+    } else if run.answer.is_none() && run.error.is_none() {
+        let last_line = diagnostics
+            .lines()
+            .rev()
+            .find(|line| !line.trim().is_empty());
+        run.error = Some(match last_line {
+            Some(line) => line.trim().to_string(),
+            None => format!("agent exited with {}", status),
+        });
+    }
+    Ok(run)
+}
+
+/// Follow a child's events, answering its approval requests
+fn observe(stdout: ChildStdout, mut stdin: ChildStdin, approve: bool) -> CaseRun {
+    let mut run = CaseRun::default();
+    for line in BufReader::new(stdout).lines() {
+        let Ok(line) = line else { break };
+        let Ok(event) = serde_json::from_str::<Value>(&line) else {
+            continue;
+        };
+        let text = |field: &str| event[field].as_str().unwrap_or_default().to_string();
+        match event["event"].as_str().unwrap_or_default() {
+            "started" => run.run_id = Some(text("run_id")),
+            "iteration" => {
+                run.iterations = run
+                    .iterations
+                    .max(event["number"].as_u64().unwrap_or(0) as usize);
+            }
+            "generated" => run.tokens += event["tokens"].as_u64().unwrap_or(0) as usize,
+            "tool_proposed" => run.tools.push(text("tool")),
+            "approval_needed" => {
+                let answer = json!({
+                    "id": event["id"],
+                    "approved": approve && event["tool"] != "sandbox",
+                });
+                let _ = writeln!(stdin, "{}", answer).and_then(|_| stdin.flush());
+            }
+            "final_answer" => run.answer = Some(text("answer")),
+            "failed" => run.error = Some(text("code")),
+            _ => {}
+        }
+    }
+    run
+}
@@ -0,0 +1,636 @@
+//! User-visible agent events and their rendering
+//!
+//! Every event the user sees goes through [`EventSink`], which renders it either
+//! as the human-readable console text or as one JSON object per line
+//! (`--output jsonl`). In JSONL mode stdout carries nothing but events, and
+//! approval answers are read from stdin as JSON, so editors and GUIs can drive
+//! the binary without scraping text.
+
+use crate::audit::AuditLog;
+use crate::chaos::SharedChaos;
+use crate::console::{self, Verbosity};
+use crate::preview::Preview;
+use crate::scenario::Recorder;
+use crate::telemetry::Telemetry;
+use agent_core::chaos::{FaultSite, InjectedFault};
+use agent_core::plan::Plan;
+use agent_core::protocol::InconclusiveReason;
+use agent_core::tool::{ContentKind, ToolRequest};
+use agent_core::tool_manifest::SafetyClass;
+use agent_core::undo::FileUndo;
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cell::{Cell, RefCell};
+use std::io::{self, BufRead, Write};
+
+/// How events are written to stdout
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum OutputMode {
+    /// Human-readable console output
+    #[default]
+    Text,
+    /// One JSON event per line; approvals are read from stdin as JSON
+    Jsonl,
+}
+
+/// Who made an approval decision
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ApprovalSource {
+    /// The approval policy decided without asking
+    Policy,
+    /// The user answered a prompt
+    User,
+}
+
+/// A user-visible event
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum AgentEvent {
+    /// The agent loop started
+    Started { run_id: String, query: String },
+    /// An agent loop iteration started
+    Iteration { number: usize, max: usize },
+    /// A model call is about to be sent (`--show-prompt`); `tokens` is an
+    /// estimate
+    Prompt {
+        label: String,
+        tokens: usize,
+        prompt: String,
+    },
+    /// The model replied; `tokens` counts prompt and output
+    Generated {
+        label: String,
+        tokens: usize,
+        seconds: f32,
+    },
+    /// The model asked for a tool call
+    ToolProposed {
+        tool: String,
+        safety: SafetyClass,
+        params: Value,
+    },
+    /// The model called `tool` by another name (`--fuzzy-tools`, aliases)
+    ToolAliased { requested: String, tool: String },
+    /// The tool call was rejected before execution (manifest or pre-execution guardrail)
+    ToolRejected { tool: String, reason: String },
+    /// The host waits for an approval answer
+    ApprovalNeeded {
+        id: u64,
+        tool: String,
+        /// What the tool call will do (absent for sandbox changes)
+        #[serde(skip_serializing_if = "Option::is_none")]
+        preview: Option<Preview>,
+    },
+    /// A tool call was approved or refused
+    ApprovalDecided {
+        tool: String,
+        approved: bool,
+        source: ApprovalSource,
+    },
+    /// A tool finished executing
+    ToolOutput {
+        tool: String,
+        success: bool,
+        output: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+        #[serde(skip_serializing_if = "ContentKind::is_text")]
+        content_kind: ContentKind,
+    },
+    /// A tool changed files; their pre-images are kept for `undo`
+    FilesChanged { tool: String, files: Vec<FileUndo> },
+    /// Semantic guardrails judged a tool output
+    GuardrailVerdict {
+        tool: String,
+        accepted: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+    },
+    /// The model produced neither a call nor a final answer
+    Inconclusive {
+        output: String,
+        reason: InconclusiveReason,
+    },
+    /// The agent re-prompts the model with stricter instructions
+    Retrying { reason: String },
+    /// The model asked for a skill
+    SkillProposed {
+        skill: String,
+        target: String,
+        text: String,
+    },
+    /// A chunk of a long skill input is being processed
+    SkillProgress {
+        chunk: usize,
+        total: usize,
+        start: usize,
+        end: usize,
+    },
+    /// A skill finished
+    SkillResult {
+        skill: String,
+        success: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        output: Option<Value>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    /// Answer guardrails sent a final answer back to the model
+    AnswerRejected { answer: String, reason: String },
+    /// The model asked for something this host cannot run; sent back
+    Unsupported { reason: String },
+    /// The agent answered the query
+    FinalAnswer { answer: String },
+    /// The agent gave up; `code` is stable, `message` is for humans
+    Failed { code: String, message: String },
+    /// A `--dry-run` plan, reviewed but not executed
+    Planned { plan: Plan },
+    /// A `--sandbox` run ended with changes to the workspace copy
+    SandboxChanges { files: Vec<String>, diff: String },
+    /// Sandbox changes were written to the real workspace
+    SandboxApplied { workspace: String, files: usize },
+    /// The faults a `--chaos` run injected and whether it got past them
+    ChaosReport {
+        seed: u64,
+        faults: Vec<InjectedFault>,
+        recovered: usize,
+    },
+}
+
+/// An event as written in JSONL mode, with the tool execution it belongs to
+#[derive(Serialize)]
+struct Envelope<'a> {
+    #[serde(flatten)]
+    event: &'a AgentEvent,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    execution: Option<u64>,
+}
+
+/// Approval answer read from stdin in JSONL mode: `{"id": 1, "approved": true}`
+#[derive(Debug, Deserialize)]
+struct ApprovalAnswer {
+    id: u64,
+    approved: bool,
+}
+
+/// Renders events for the selected output mode
+#[derive(Debug, Default)]
+pub struct EventSink {
+    mode: OutputMode,
+    next_approval_id: u64,
+    audit: Option<RefCell<AuditLog>>,
+    recorder: Option<RefCell<Recorder>>,
+    telemetry: Option<RefCell<Telemetry>>,
+    chaos: Option<SharedChaos>,
+    chaos_reported: Cell<bool>,
+    show_prompts: bool,
+    text_output: bool,
+    /// Tool calls refused so far, by policy or by the user
+    denied: Cell<usize>,
+}
+
+impl EventSink {
+    pub fn new(mode: OutputMode) -> Self {
+        Self {
+            mode,
+            next_approval_id: 1,
+            audit: None,
+            recorder: None,
+            telemetry: None,
+            chaos: None,
+            chaos_reported: Cell::new(false),
+            show_prompts: false,
+            text_output: true,
+            denied: Cell::new(0),
+        }
+    }
+
+    /// Render events as text (the default in text mode); off for commands
+    /// whose stdout carries results, such as `extract --records`
+    pub fn with_text_output(mut self, on: bool) -> Self {
+        self.text_output = on;
+        self
+    }
+
+    /// Emit every prompt before it is sent (`--show-prompt`)
+    pub fn with_prompts(mut self, show: bool) -> Self {
+        self.show_prompts = show;
+        self
+    }
+
+    /// Whether prompts are emitted as events
+    pub fn shows_prompts(&self) -> bool {
+        self.show_prompts
+    }
+
+    /// Record the run in `audit` as events are emitted
+    pub fn with_audit(mut self, audit: AuditLog) -> Self {
+        self.audit = Some(RefCell::new(audit));
+        self
+    }
+
+    /// Record the run into a scenario fixture as events are emitted
+    pub fn with_recorder(mut self, recorder: Recorder) -> Self {
+        self.recorder = Some(RefCell::new(recorder));
+        self
+    }
+
+    /// Export the run's spans and metrics as events are emitted
+    pub fn with_telemetry(mut self, telemetry: Telemetry) -> Self {
+        self.telemetry = Some(RefCell::new(telemetry));
+        self
+    }
+
+    /// Follow the run's progress past injected faults and report them when it
+    /// ends (`--chaos`)
+    pub fn with_chaos(mut self, chaos: SharedChaos) -> Self {
+        self.chaos = Some(chaos);
+        self
+    }
+
+    /// Emit the chaos report, once; runs ending in an error call this
+    pub fn report_chaos(&self) {
+        let Some(chaos) = &self.chaos else {
+            return;
+        };
+        if self.chaos_reported.replace(true) {
+            return;
+        }
+        let report = chaos.lock().unwrap().report();
+        self.emit(AgentEvent::ChaosReport {
+            seed: report.seed,
+            recovered: report.recovered(),
+            faults: report.faults,
+        });
+    }
+
+    /// Whether model calls are being recorded (scenario or telemetry)
+    pub fn recording(&self) -> bool {
+        self.recorder.is_some() || self.telemetry.is_some()
+    }
+
+    /// Record a model call when recording a scenario or exporting telemetry
+    pub fn record_model_call(&self, prompt: String, output: &str) {
+        if let Some(telemetry) = &self.telemetry {
+            telemetry.borrow_mut().model_call(&prompt, output);
+        }
+        if let Some(recorder) = &self.recorder {
+            recorder.borrow_mut().model_call(prompt, output);
+        }
+    }
+
+    pub fn mode(&self) -> OutputMode {
+        self.mode
+    }
+
+    /// Write an event to stdout (text mode renders through the console layer)
+    pub fn emit(&self, event: AgentEvent) {
+        if let AgentEvent::ApprovalDecided {
+            tool,
+            approved: false,
+            ..
+        } = &event
+        {
+            if tool != "sandbox" {
+                self.denied.set(self.denied.get() + 1);
+            }
+        }
+        let execution = self
+            .audit
+            .as_ref()
+            .and_then(|audit| audit.borrow_mut().observe(&event));
+        if let Some(recorder) = &self.recorder {
+            recorder.borrow_mut().observe(&event);
+        }
+        if let Some(telemetry) = &self.telemetry {
+            telemetry.borrow_mut().observe(&event);
+        }
+        if let Some(chaos) = &self.chaos {
+            match &event {
+                AgentEvent::ToolProposed { .. }
+                | AgentEvent::SkillProposed { .. }
+                | AgentEvent::AnswerRejected { .. } => chaos.lock().unwrap().model_progress(),
+                AgentEvent::FinalAnswer { .. } => chaos.lock().unwrap().answered(),
+                _ => {}
+            }
+        }
+        let ends_run = matches!(
+            event,
+            AgentEvent::FinalAnswer { .. } | AgentEvent::Failed { .. }
+        );
+        match self.mode {
+            OutputMode::Jsonl => {
+                let envelope = Envelope {
+                    event: &event,
+                    execution,
+                };
+                if let Ok(line) = serde_json::to_string(&envelope) {
+                    println!("{}", line);
+                }
+                let _ = io::stdout().flush();
+            }
+            OutputMode::Text if self.text_output => render_text(&event),
+            OutputMode::Text => {}
+        }
+        if ends_run {
+            self.report_chaos();
+        }
+    }
+
+    /// Tool calls refused so far in this run
+    pub fn denied_calls(&self) -> usize {
+        self.denied.get()
+    }
+
+    /// Ask the user whether a tool may run, showing what it will do
+    pub fn request_approval(&mut self, request: &ToolRequest, preview: Preview) -> Result<bool> {
+        let tool = request.tool.as_str();
+        if self.mode == OutputMode::Text {
+            // Quiet mode hides the proposal, but the user must see what they approve
+            let quiet = console::verbosity() == Verbosity::Quiet;
+            if quiet {
+                println!("→ {}:", tool);
+            }
+            for field in &preview.fields {
+                println!("  {}: {}", field.label, field.value);
+            }
+            // Otherwise the proposal already showed the patch
+            if let Some(diff) = preview.diff.as_deref().filter(|_| quiet) {
+                console::diff(diff);
+            }
+        }
+        self.ask(tool, "Execute?", Some(preview))
+    }
+
+    /// Ask the user whether a sandbox run's changes may be applied
+    ///
+    /// The diff was already shown by [`AgentEvent::SandboxChanges`].
+    pub fn request_sandbox_apply(&mut self, diff: &str) -> Result<bool> {
+        if self.mode == OutputMode::Text && console::verbosity() == Verbosity::Quiet {
+            console::diff(diff);
+        }
+        self.ask("sandbox", "Apply to workspace?", None)
+    }
+
+    /// Read a yes/no answer; `subject` names what is approved in events
+    fn ask(&mut self, subject: &str, question: &str, preview: Option<Preview>) -> Result<bool> {
+        let id = self.next_approval_id;
+        self.next_approval_id += 1;
+
+        let approved = match self.mode {
+            OutputMode::Text => {
+                print!("  {} (y/n): ", question);
+                io::stdout().flush()?;
+
+                let mut input = String::new();
+                io::stdin().read_line(&mut input)?;
+                input.trim().eq_ignore_ascii_case("y")
+            }
+            OutputMode::Jsonl => {
+                self.emit(AgentEvent::ApprovalNeeded {
+                    id,
+                    tool: subject.to_string(),
+                    preview,
+                });
+
+                let mut line = String::new();
+                io::stdin().lock().read_line(&mut line)?;
+                match serde_json::from_str::<ApprovalAnswer>(&line) {
+                    Ok(answer) if answer.id == id => answer.approved,
+                    Ok(answer) => {
+                        console::warn(format!(
+                            "Approval answer for id {} does not match request {}; rejecting",
+                            answer.id, id
+                        ));
+                        false
+                    }
+                    Err(e) => {
+                        console::warn(format!("Invalid approval answer ({}); rejecting", e));
+                        false
+                    }
+                }
+            }
+        };
+
+        self.emit(AgentEvent::ApprovalDecided {
+            tool: subject.to_string(),
+            approved,
+            source: ApprovalSource::User,
+        });
+        Ok(approved)
+    }
+}
+
+fn render_text(event: &AgentEvent) {
+    match event {
+        AgentEvent::Started { run_id, query } => {
+            console::info("=== agent.rs ===");
+            console::verbose(format!("Run: {}", run_id));
+            console::info(format!("Query: {}\n", query));
+        }
+        AgentEvent::Iteration { number, max } => {
+            console::verbose(format!("Iteration {}/{}", number, max));
+        }
+        AgentEvent::Prompt {
+            label,
+            tokens,
+            prompt,
+        } => {
+            // Asked for explicitly, so shown at every verbosity
+            println!("--- prompt: {} (~{} tokens) ---", label, tokens);
+            println!("{}", prompt);
+            println!("--- end of prompt ---");
+        }
+        AgentEvent::Generated {
+            label,
+            tokens,
+            seconds,
+        } => console::verbose(format!("{}: {} tokens in {:.1}s", label, tokens, seconds)),
+        AgentEvent::ToolProposed {
+            tool,
+            safety,
+            params,
+        } => match patch_param(params) {
+            Some(patch) => {
+                console::info(format!("\n→ {} [{}]:", tool, safety.as_str()));
+                if console::verbosity() >= Verbosity::Normal {
+                    console::diff(patch);
+                }
+            }
+            None => console::info(format!(
+                "\n→ {} [{}]: {}",
+                tool,
+                safety.as_str(),
+                describe_params(params)
+            )),
+        },
+        AgentEvent::ToolRejected { reason, .. } => {
+            console::failure(format!("Tool request rejected: {}", reason));
+        }
+        AgentEvent::ApprovalNeeded { .. } => {}
+        AgentEvent::ApprovalDecided {
+            approved, source, ..
+        } => match (approved, source) {
+            (true, ApprovalSource::Policy) => console::success("Auto-approved"),
+            (false, ApprovalSource::Policy) => console::failure("Denied by approval policy"),
+            (true, ApprovalSource::User) => {}
+            (false, ApprovalSource::User) => console::failure("Rejected"),
+        },
+        AgentEvent::ToolOutput {
+            success,
+            output,
+            error,
+            content_kind,
+            ..
+        } => {
+            if *success {
+                // Always show output section, even if empty
+                if output.is_empty() {
+                    console::info("  (no output)");
+                } else {
+                    console::info(format!("\n{}", content_kind.display(output)));
+                }
+            } else {
+                console::failure(error.as_deref().unwrap_or("unknown error"));
+            }
+        }
+        AgentEvent::GuardrailVerdict {
+            accepted, reason, ..
+        } => {
+            if !accepted {
+                console::warn(format!(
+                    "Guardrail rejected tool output: {}",
+                    reason.as_deref().unwrap_or("no reason given")
+                ));
+            }
+        }
+        AgentEvent::AnswerRejected { reason, .. } => {
+            console::warn(format!("Guardrail rejected final answer: {}", reason));
+        }
+        AgentEvent::ToolAliased { requested, tool } => {
+            console::verbose(format!("Taking tool '{}' as '{}'", requested, tool));
+        }
+        AgentEvent::Unsupported { reason } => {
+            console::warn(format!("Request refused: {}", reason));
+        }
+        AgentEvent::Inconclusive { output, reason } => {
+            console::warn(format!(
+                "Model produced inconclusive output ({}): \"{}\"",
+                reason.as_str(),
+                output.lines().next().unwrap_or(output)
+            ));
+        }
+        AgentEvent::Retrying { reason } => {
+            console::warn(format!("Attempting corrective retry ({})...", reason));
+        }
+        AgentEvent::SkillProposed {
+            skill,
+            target,
+            text,
+        } => {
+            console::info(format!("\n→ skill: {} (target: {})", skill, target));
+            console::info(format!("  Text: \"{}\"", crate::truncate_string(text, 50)));
+        }
+        AgentEvent::SkillProgress {
+            chunk,
+            total,
+            start,
+            end,
+        } => {
+            console::info(format!(
+                "  Chunk {}/{} (chars {}..{})",
+                chunk, total, start, end
+            ));
+        }
+        AgentEvent::SkillResult {
+            success,
+            output,
+            error,
+            ..
+        } => {
+            if *success {
+                let output = output.as_ref().map(Value::to_string).unwrap_or_default();
+                console::success(format!("Skill result: {}", output));
+            } else {
+                console::failure(format!(
+                    "Skill error: {}",
+                    error.as_deref().unwrap_or("unknown error")
+                ));
+            }
+        }
+        AgentEvent::FinalAnswer { answer } => {
+            // The answer is the point of the run: print it at every verbosity
+            if console::verbosity() > Verbosity::Quiet {
+                println!();
+            }
+            println!("{}", answer);
+        }
+        AgentEvent::Planned { plan } => {
+            // Like the final answer, the plan is the point of a dry run
+            if console::verbosity() > Verbosity::Quiet {
+                println!();
+            }
+            print!("{}", plan.to_markdown());
+        }
+        AgentEvent::Failed { message, .. } => eprintln!("{}", message),
+        AgentEvent::SandboxChanges { files, diff } => {
+            if files.is_empty() {
+                console::info("\nSandbox: no files changed");
+            } else {
+                console::info(format!("\nSandbox changed {} file(s):", files.len()));
+                if console::verbosity() >= Verbosity::Normal {
+                    console::diff(diff);
+                }
+            }
+        }
+        AgentEvent::FilesChanged { files, .. } => {
+            console::verbose(format!("Saved {} file(s) for undo", files.len()));
+        }
+        AgentEvent::SandboxApplied { workspace, files } => {
+            console::success(format!("Applied {} file(s) to {}", files, workspace));
+        }
+        AgentEvent::ChaosReport {
+            seed,
+            faults,
+            recovered,
+        } => {
+            console::info(format!(
+                "\nChaos: {} fault(s) injected (seed {}), {} recovered",
+                faults.len(),
+                seed,
+                recovered
+            ));
+            for fault in faults {
+                let site = match fault.site {
+                    FaultSite::Model => "model call",
+                    FaultSite::Tool => "tool call",
+                };
+                let line = format!("{} in {} {}", fault.fault.as_str(), site, fault.call);
+                if fault.recovered {
+                    console::success(line);
+                } else {
+                    console::failure(line);
+                }
+            }
+        }
+    }
+}
+
+/// The diff of an `apply_patch` call, previewed instead of the raw parameters
+fn patch_param(params: &Value) -> Option<&str> {
+    params.get("patch").and_then(Value::as_str)
+}
+
+/// One-line summary of tool parameters: a lone string parameter is shown as-is
+fn describe_params(params: &Value) -> String {
+    match params.as_object() {
+        Some(map) if map.len() == 1 => match map.values().next() {
+            Some(Value::String(value)) => value.clone(),
+            _ => params.to_string(),
+        },
+        _ => params.to_string(),
+    }
+}
@@ -0,0 +1,277 @@
+//! Google Gemini API backend
+//!
+//! Sends the agent conversation as Gemini `contents` and offers host tools as
+//! function declarations. `functionCall` parts in the response are rendered in
+//! the agent's JSON protocol, so the agent loop handles them like any other
+//! tool call. Images tools returned are sent as `inlineData` parts.
+//! Embeddings come from [`EMBEDDING_MODEL_ID`] through `batchEmbedContents`.
+
+use crate::llm::{ImageAttachment, LLMBackend, LLMInput, LLMOutput, Sampling};
+use crate::throttle::{estimate_tokens, http_agent, prompt_tokens, Throttle};
+use agent_core::{protocol::format_tool_call, tool::ToolRequest};
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+pub const DEFAULT_ENDPOINT: &str = "https://generativelanguage.googleapis.com";
+
+pub const DEFAULT_MODEL_ID: &str = "gemini-2.5-flash";
+
+/// Model embedding texts, whichever model generates
+pub const EMBEDDING_MODEL_ID: &str = "gemini-embedding-001";
+
+/// Environment variable holding the API key
+pub const API_KEY_VAR: &str = "GEMINI_API_KEY";
+
+/// Client for the Gemini `generateContent` API
+pub struct GeminiBackend {
+    agent: ureq::Agent,
+    throttle: Arc<Throttle>,
+    url: String,
+    embeddings_url: String,
+    api_key: String,
+    sampling: Sampling,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GenerateResponse {
+    #[serde(default)]
+    candidates: Vec<Candidate>,
+    #[serde(default)]
+    usage_metadata: Option<UsageMetadata>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Candidate {
+    content: Option<CandidateContent>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CandidateContent {
+    #[serde(default)]
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Part {
+    #[serde(default)]
+    text: Option<String>,
+    #[serde(default)]
+    function_call: Option<FunctionCall>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FunctionCall {
+    name: String,
+    #[serde(default)]
+    args: Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbedResponse {
+    #[serde(default)]
+    embeddings: Vec<ContentEmbedding>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ContentEmbedding {
+    values: Vec<f32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UsageMetadata {
+    #[serde(default)]
+    total_token_count: i32,
+}
+
+impl GeminiBackend {
+    pub fn new(endpoint: &str, api_key: String, model: &str) -> Self {
+        Self {
+            agent: http_agent(),
+            throttle: Arc::default(),
+            url: format!(
+                "{}/v1beta/models/{}:generateContent",
+                endpoint.trim_end_matches('/'),
+                model
+            ),
+            embeddings_url: format!(
+                "{}/v1beta/models/{}:batchEmbedContents",
+                endpoint.trim_end_matches('/'),
+                EMBEDDING_MODEL_ID
+            ),
+            api_key,
+            sampling: Sampling::default(),
+        }
+    }
+
+    /// Send through `throttle`, shared with other backends holding it
+    pub fn with_throttle(mut self, throttle: Arc<Throttle>) -> Self {
+        self.throttle = throttle;
+        self
+    }
+
+    pub fn with_sampling(mut self, sampling: Sampling) -> Self {
+        self.sampling = sampling;
+        self
+    }
+
+    fn request_body(&self, input: &LLMInput) -> Value {
+        let mut body = json!({
+            "generationConfig": { "maxOutputTokens": input.max_tokens },
+        });
+        if let Some(temperature) = self.sampling.temperature {
+            body["generationConfig"]["temperature"] = json!(temperature);
+        }
+        if let Some(seed) = self.sampling.seed {
+            body["generationConfig"]["seed"] = json!(seed);
+        }
+
+        match &input.chat {
+            Some(chat) => {
+                if let Some(system) = chat.transcript.system() {
+                    body["systemInstruction"] = json!({ "parts": [{ "text": system }] });
+                }
+                body["contents"] = json!(chat.transcript.to_gemini());
+                if !input.images.is_empty() {
+                    attach_images(&mut body["contents"], &input.images);
+                }
+                if !chat.tools.is_empty() {
+                    let declarations: Vec<Value> = chat
+                        .tools
+                        .iter()
+                        .map(|tool| {
+                            json!({
+                                "name": tool.frontmatter.name,
+                                "description": tool.frontmatter.description,
+                                "parameters": tool.input_schema(),
+                            })
+                        })
+                        .collect();
+                    body["tools"] = json!([{ "functionDeclarations": declarations }]);
+                }
+            }
+            None => {
+                body["contents"] = json!([{ "role": "user", "parts": [{ "text": input.prompt }] }]);
+            }
+        }
+
+        body
+    }
+}
+
+/// Add `images` to the last user turn, each after a label naming its artifact
+fn attach_images(contents: &mut Value, images: &[ImageAttachment]) {
+    let Some(contents) = contents.as_array_mut() else {
+        return;
+    };
+    if contents
+        .last()
+        .is_none_or(|content| content["role"] != "user")
+    {
+        contents.push(json!({ "role": "user", "parts": [] }));
+    }
+    let Some(parts) = contents
+        .last_mut()
+        .and_then(|content| content["parts"].as_array_mut())
+    else {
+        return;
+    };
+    for image in images {
+        parts.push(json!({ "text": format!("Image {}:", image.reference) }));
+        parts.push(json!({
+            "inlineData": { "mimeType": image.media_type, "data": image.base64() },
+        }));
+    }
+}
+
+impl LLMBackend for GeminiBackend {
+    fn infer(&mut self, input: LLMInput) -> Result<LLMOutput> {
+        let body = self.request_body(&input);
+
+        let response: GenerateResponse = self
+            .throttle
+            .send(estimate_tokens(&input), || {
+                self.agent
+                    .post(&self.url)
+                    .header("x-goog-api-key", &self.api_key)
+                    .send_json(&body)
+            })
+            .with_context(|| format!("Request to Gemini API at {} failed", self.url))?
+            .body_mut()
+            .read_json()
+            .context("Invalid response from Gemini API")?;
+
+        let parts = response
+            .candidates
+            .into_iter()
+            .next()
+            .and_then(|candidate| candidate.content)
+            .map(|content| content.parts)
+            .context("Gemini API returned no candidate")?;
+
+        // A function call wins over any accompanying text
+        let mut text = String::new();
+        let mut tool_call = None;
+        for part in parts {
+            if let Some(call) = part.function_call {
+                tool_call.get_or_insert_with(|| {
+                    format_tool_call(&ToolRequest {
+                        tool: call.name,
+                        call_id: None,
+                        params: call.args,
+                    })
+                });
+            } else if let Some(part) = part.text {
+                text.push_str(&part);
+            }
+        }
+
+        Ok(LLMOutput {
+            text: tool_call.unwrap_or(text),
+            tokens_processed: response
+                .usage_metadata
+                .map_or(0, |usage| usage.total_token_count),
+        })
+    }
+
+    fn embed(&mut self, texts: &[String]) -> Result<Option<Vec<Vec<f32>>>> {
+        let model = format!("models/{}", EMBEDDING_MODEL_ID);
+        let requests: Vec<Value> = texts
+            .iter()
+            .map(|text| json!({ "model": model, "content": { "parts": [{ "text": text }] } }))
+            .collect();
+        let body = json!({ "requests": requests });
+        let tokens = texts.iter().map(|text| prompt_tokens(text)).sum::<usize>() as u64;
+
+        let response: EmbedResponse = self
+            .throttle
+            .send(tokens, || {
+                self.agent
+                    .post(&self.embeddings_url)
+                    .header("x-goog-api-key", &self.api_key)
+                    .send_json(&body)
+            })
+            .with_context(|| format!("Request to Gemini API at {} failed", self.embeddings_url))?
+            .body_mut()
+            .read_json()
+            .context("Invalid embedding response from Gemini API")?;
+        if response.embeddings.len() != texts.len() {
+            bail!(
+                "Gemini API returned {} embeddings for {} texts",
+                response.embeddings.len(),
+                texts.len()
+            );
+        }
+        Ok(Some(
+            response
+                .embeddings
+                .into_iter()
+                .map(|embedding| embedding.values)
+                .collect(),
+        ))
+    }
+}
@@ -0,0 +1,40 @@
+//! The `view_image` tool
+//!
+//! Returns a reference to an image file under the working directory. The run's
+//! artifacts copy the image and attach it to the next prompt of a multimodal
+//! backend; other backends see a placeholder.
+
+use crate::llm::image_media_type;
+use agent_core::tool::{is_workspace_path, ContentKind, ToolRequest, ToolResult};
+use std::path::Path;
+
+pub const TOOL_NAME: &str = "view_image";
+
+/// Execute the view_image tool
+pub fn execute(request: &ToolRequest) -> ToolResult {
+    match image_path(request) {
+        Ok(path) => ToolResult::success(path).with_content_kind(ContentKind::ImageRef),
+        Err(e) => ToolResult::failure(e),
+    }
+}
+
+fn image_path(request: &ToolRequest) -> Result<&str, String> {
+    let path = request
+        .params
+        .get("path")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing 'path' parameter")?;
+    if !is_workspace_path(path) {
+        return Err(format!(
+            "'{}' is outside the working directory; give a relative path",
+            path
+        ));
+    }
+    if image_media_type(Path::new(path)).is_none() {
+        return Err(format!("'{}' is not a PNG, JPEG, GIF or WebP image", path));
+    }
+    if !Path::new(path).is_file() {
+        return Err(format!("No such file: '{}'", path));
+    }
+    Ok(path)
+}
@@ -0,0 +1,77 @@
+//! Queries and extraction text from arguments, files or stdin
+//!
+//! `-` as a value reads stdin. File and stdin input is read up to
+//! `--max-input-bytes`, so a wrong pipe fails fast instead of filling memory.
+//! Extraction text longer than the chunk size is then split by
+//! [`ExtractionBatch`](agent_core::extraction_batch::ExtractionBatch) as usual.
+
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::{self, IsTerminal, Read};
+use std::path::Path;
+
+/// Default `--max-input-bytes`
+pub const DEFAULT_MAX_INPUT_BYTES: u64 = 16 * 1024 * 1024;
+
+/// The value that stands for stdin
+pub const STDIN: &str = "-";
+
+/// Where a piece of input comes from
+#[derive(Debug, Clone, Copy)]
+pub enum Input<'a> {
+    Value(&'a str),
+    File(&'a Path),
+    Stdin,
+}
+
+impl<'a> Input<'a> {
+    /// A value, or stdin if it is `-`
+    pub fn value(value: &'a str) -> Self {
+        if value == STDIN {
+            Self::Stdin
+        } else {
+            Self::Value(value)
+        }
+    }
+
+    /// Read the input; `what` names it in errors
+    pub fn read(self, what: &str, max_bytes: u64) -> Result<String> {
+        match self {
+            Self::Value(value) => Ok(value.to_string()),
+            Self::File(path) => {
+                let file = File::open(path)
+                    .with_context(|| format!("Cannot read {} from {}", what, path.display()))?;
+                read_limited(file, what, &path.display().to_string(), max_bytes)
+            }
+            Self::Stdin => {
+                if io::stdin().is_terminal() {
+                    anyhow::bail!("Expected the {} on stdin, but stdin is a terminal", what);
+                }
+                read_limited(io::stdin().lock(), what, "stdin", max_bytes)
+            }
+        }
+    }
+}
+
+/// Whether stdin is a pipe or file rather than a terminal
+pub fn stdin_is_piped() -> bool {
+    !io::stdin().is_terminal()
+}
+
+fn read_limited(reader: impl Read, what: &str, source: &str, max_bytes: u64) -> Result<String> {
+    let mut bytes = Vec::new();
+    reader
+        .take(max_bytes.saturating_add(1))
+        .read_to_end(&mut bytes)
+        .with_context(|| format!("Cannot read {} from {}", what, source))?;
+    if bytes.len() as u64 > max_bytes {
+        anyhow::bail!(
+            "The {} from {} is larger than {} bytes (raise --max-input-bytes)",
+            what,
+            source,
+            max_bytes
+        );
+    }
+    String::from_utf8(bytes)
+        .map_err(|_| anyhow::anyhow!("The {} from {} is not UTF-8", what, source))
+}
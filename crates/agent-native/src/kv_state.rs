@@ -0,0 +1,71 @@
+//! `--kv-state`: keep a conversation's llama.cpp context between runs
+//!
+//! When a run answers, the backend's context is saved to the file and its
+//! [`KvCheckpoint`] next to it (`<file>.json`). A later run continuing the
+//! conversation (`--conversation`) loads the context back, rewound to the
+//! checkpointed position, provided the conversation still starts with the
+//! history the context was built from; otherwise it starts from an empty
+//! context. The backend then reuses the loaded prefix of the next prompt.
+//! Backends that keep no context between calls ignore the option.
+
+use crate::console;
+use crate::llm::{BackendError, LLMBackend};
+use agent_core::agent::AgentState;
+use agent_core::kv_checkpoint::KvCheckpoint;
+use anyhow::{Context, Result};
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Load the context saved in `path` if it matches `state`
+pub fn resume(path: &Path, state: &AgentState, backend: &mut dyn LLMBackend) -> Result<()> {
+    let sidecar = checkpoint_path(path);
+    if !path.exists() || !sidecar.exists() {
+        return Ok(());
+    }
+    let json = fs::read_to_string(&sidecar)
+        .with_context(|| format!("Cannot read {}", sidecar.display()))?;
+    let checkpoint: KvCheckpoint = serde_json::from_str(&json)
+        .with_context(|| format!("Invalid context checkpoint {}", sidecar.display()))?;
+    if !checkpoint.matches(state) {
+        console::warn(format!(
+            "Saved context {} is for another conversation; starting over",
+            path.display()
+        ));
+        return Ok(());
+    }
+
+    let Some(end) = backend.load_state(path).context(BackendError)? else {
+        console::verbose("The backend keeps no context; --kv-state is ignored");
+        return Ok(());
+    };
+    if end != checkpoint.position {
+        backend.rewind(checkpoint.position).context(BackendError)?;
+    }
+    console::verbose(format!(
+        "Resumed the context of {} message(s) at position {}",
+        checkpoint.messages, checkpoint.position
+    ));
+    Ok(())
+}
+
+/// Save the backend's context, built from `state`, to `path`
+pub fn save(path: &Path, state: &AgentState, backend: &mut dyn LLMBackend) -> Result<()> {
+    let Some(position) = backend.save_state(path).context(BackendError)? else {
+        console::verbose("The backend keeps no context; --kv-state is ignored");
+        return Ok(());
+    };
+    let checkpoint = KvCheckpoint::of(state, position);
+    let sidecar = checkpoint_path(path);
+    fs::write(&sidecar, serde_json::to_string_pretty(&checkpoint)?)
+        .with_context(|| format!("Cannot write {}", sidecar.display()))?;
+    console::verbose(format!("Saved the context to {}", path.display()));
+    Ok(())
+}
+
+/// `<path>.json`
+fn checkpoint_path(path: &Path) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(".json");
+    PathBuf::from(name)
+}
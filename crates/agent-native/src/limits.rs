@@ -0,0 +1,195 @@
+//! Resource limits on tool subprocesses
+//!
+//! Enforces [`ResourceLimits`] on a command: CPU time, address space and
+//! process count are rlimits set in the child before it starts, and output is
+//! read up to the limit from each pipe, after which the command's whole
+//! process group is killed.
+
+use agent_core::limits::ResourceLimits;
+use anyhow::Result;
+use std::io::{self, Read};
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::process::{Command, Output, Stdio};
+use std::thread;
+
+/// A finished command and whether a limit stopped it
+#[derive(Debug)]
+pub struct LimitedOutput {
+    pub output: Output,
+    /// Output went over the limit and the command was killed
+    pub truncated: bool,
+    limits: ResourceLimits,
+}
+
+impl LimitedOutput {
+    /// Why a limit stopped the command, if one did
+    pub fn exceeded(&self) -> Option<String> {
+        if self.truncated {
+            let limit = self.limits.max_output_bytes.unwrap_or_default();
+            return Some(format!(
+                "Command stopped: output exceeded the {} limit",
+                format_bytes(limit)
+            ));
+        }
+        match (self.output.status.signal(), self.limits.cpu_seconds) {
+            (Some(libc::SIGXCPU), Some(seconds)) => Some(format!(
+                "Command stopped: CPU time limit of {}s exceeded",
+                seconds
+            )),
+            _ => None,
+        }
+    }
+}
+
+/// `CPU 60s, output 10 MB`, or `none`
+pub fn describe(limits: &ResourceLimits) -> String {
+    let parts: Vec<String> = [
+        limits.cpu_seconds.map(|s| format!("CPU {}s", s)),
+        limits
+            .memory_bytes
+            .map(|b| format!("memory {}", format_bytes(b))),
+        limits.max_processes.map(|n| format!("{} processes", n)),
+        limits
+            .max_output_bytes
+            .map(|b| format!("output {}", format_bytes(b))),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    if parts.is_empty() {
+        return "none".to_string();
+    }
+    parts.join(", ")
+}
+
+/// Run `process` to completion under `limits`, capturing its output
+///
+/// Stdin is closed, as with [`Command::output`].
+pub fn run(mut process: Command, limits: &ResourceLimits) -> Result<LimitedOutput> {
+    let rlimits = rlimits(limits);
+    // SAFETY: the closure only calls getrlimit/setrlimit on values prepared
+    // above
+    unsafe {
+        process.pre_exec(move || {
+            for &(resource, value) in &rlimits {
+                set_rlimit(resource, value)?;
+            }
+            Ok(())
+        });
+    }
+    // A group of its own, so a stopped command takes its pipeline with it
+    let mut child = process
+        .process_group(0)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let group = child.id() as libc::pid_t;
+    let max_output = limits.max_output_bytes;
+    let stderr = child
+        .stderr
+        .take()
+        .map(|pipe| thread::spawn(move || read_capped(pipe, max_output, group)));
+    let (stdout, stdout_truncated) = match child.stdout.take() {
+        Some(pipe) => read_capped(pipe, max_output, group)?,
+        None => (Vec::new(), false),
+    };
+    let (stderr, stderr_truncated) = match stderr {
+        Some(reader) => reader
+            .join()
+            .map_err(|_| anyhow::anyhow!("stderr reader panicked"))??,
+        None => (Vec::new(), false),
+    };
+    let status = child.wait()?;
+
+    Ok(LimitedOutput {
+        output: Output {
+            status,
+            stdout,
+            stderr,
+        },
+        truncated: stdout_truncated || stderr_truncated,
+        limits: *limits,
+    })
+}
+
+/// The rlimits to set in the child, as `(resource, limit)`
+fn rlimits(limits: &ResourceLimits) -> Vec<(Resource, u64)> {
+    [
+        (libc::RLIMIT_CPU, limits.cpu_seconds),
+        (libc::RLIMIT_AS, limits.memory_bytes),
+        (libc::RLIMIT_NPROC, limits.max_processes),
+    ]
+    .into_iter()
+    .filter_map(|(resource, value)| value.map(|value| (resource, value)))
+    .collect()
+}
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+type Resource = libc::__rlimit_resource_t;
+#[cfg(not(all(target_os = "linux", target_env = "gnu")))]
+type Resource = libc::c_int;
+
+/// Lower a limit in the calling process; never above the current hard limit
+fn set_rlimit(resource: Resource, value: u64) -> io::Result<()> {
+    let mut current = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    // SAFETY: both calls only read or write the struct passed to them
+    unsafe {
+        if libc::getrlimit(resource, &mut current) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let soft = (value as libc::rlim_t).min(current.rlim_max);
+        // CPU time: SIGXCPU at the soft limit, SIGKILL a second later
+        let hard = if resource == libc::RLIMIT_CPU {
+            soft.saturating_add(1).min(current.rlim_max)
+        } else {
+            soft
+        };
+        let limit = libc::rlimit {
+            rlim_cur: soft,
+            rlim_max: hard,
+        };
+        if libc::setrlimit(resource, &limit) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Read a pipe to the end, or up to `max` bytes and then kill `group`
+fn read_capped(
+    pipe: impl Read,
+    max: Option<u64>,
+    group: libc::pid_t,
+) -> io::Result<(Vec<u8>, bool)> {
+    let mut buffer = Vec::new();
+    let Some(max) = max else {
+        let mut pipe = pipe;
+        pipe.read_to_end(&mut buffer)?;
+        return Ok((buffer, false));
+    };
+
+    pipe.take(max.saturating_add(1)).read_to_end(&mut buffer)?;
+    if buffer.len() as u64 <= max {
+        return Ok((buffer, false));
+    }
+    buffer.truncate(max as usize);
+    // SAFETY: sends a signal to the command's process group only
+    unsafe {
+        libc::kill(-group, libc::SIGKILL);
+    }
+    Ok((buffer, true))
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const MB: u64 = 1024 * 1024;
+    match bytes {
+        b if b >= MB && b % MB == 0 => format!("{} MB", b / MB),
+        b if b >= 1024 && b % 1024 == 0 => format!("{} KB", b / 1024),
+        b => format!("{} bytes", b),
+    }
+}
@@ -2,70 +2,133 @@
 //!
 //! This module encapsulates all llama.cpp-specific logic.
 
-use crate::llm::{LLMBackend, LLMInput, LLMOutput};
-use anyhow::{Context, Result};
+use crate::llm::{LLMBackend, LLMInput, LLMOutput, Sampling};
+use agent_core::stop_position;
+use anyhow::{bail, Context, Result};
 use llama_cpp_2::context::params::LlamaContextParams;
 use llama_cpp_2::llama_backend::LlamaBackend as LlamaCppLlamaBackend;
 use llama_cpp_2::llama_batch::LlamaBatch;
 use llama_cpp_2::model::params::LlamaModelParams;
 use llama_cpp_2::model::LlamaModel;
 use llama_cpp_2::model::{AddBos, Special};
+use llama_cpp_2::sampling::LlamaSampler;
 use llama_cpp_2::token::data_array::LlamaTokenDataArray;
+use llama_cpp_2::token::LlamaToken;
 use std::fs::OpenOptions;
 use std::num::NonZeroU32;
 use std::os::fd::AsRawFd;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// A GGUF model loaded once per process and shared by the contexts (runs,
+/// the critic) created from it
+///
+/// Loading maps the whole file and, on Metal, compiles the shaders on first
+/// use; a context is cheap next to that.
+pub struct LlamaCppModel {
+    path: PathBuf,
+    model: LlamaModel,
+}
+
+/// Models loaded in this process; they stay loaded until it exits
+static WARM_POOL: Mutex<Vec<Arc<LlamaCppModel>>> = Mutex::new(Vec::new());
+
+impl LlamaCppModel {
+    /// The model at `path`, loaded now unless this process already has it
+    pub fn shared(path: &Path) -> Result<Arc<Self>> {
+        let mut pool = WARM_POOL.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(model) = pool.iter().find(|model| model.path == path) {
+            return Ok(Arc::clone(model));
+        }
+        let model = Arc::new(Self {
+            path: path.to_path_buf(),
+            model: LlamaModel::load_from_file(llama_backend()?, path, &LlamaModelParams::default())
+                .context("Failed to load model")?,
+        });
+        pool.push(Arc::clone(&model));
+        Ok(model)
+    }
+}
+
+/// The llama.cpp runtime, which may only be initialized once per process
+fn llama_backend() -> Result<&'static LlamaCppLlamaBackend> {
+    static BACKEND: OnceLock<LlamaCppLlamaBackend> = OnceLock::new();
+    // First called by `LlamaCppModel::shared` under the pool lock, so only
+    // one thread initializes
+    if let Some(backend) = BACKEND.get() {
+        return Ok(backend);
+    }
+    let backend = LlamaCppLlamaBackend::init().context("Failed to initialize llama.cpp")?;
+    Ok(BACKEND.get_or_init(|| backend))
+}
+
+/// Whether this llama.cpp build can offload layers to a GPU (initializes
+/// llama.cpp)
+pub fn gpu_offload_supported() -> Result<bool> {
+    Ok(llama_backend()?.supports_gpu_offload())
+}
+
+/// Prompt tokens decoded at once; longer prompts are decoded in pieces
+const PROMPT_BATCH: u32 = 512;
+
+/// Tokens of the longest text embedded (in one batch)
+const EMBEDDING_BATCH: u32 = 2048;
 
 /// llama.cpp backend implementation
 ///
-/// This struct encapsulates llama.cpp state and provides a safe interface.
-/// We use Box to store the dependencies to ensure stable addresses.
+/// Each backend has a context of its own on a shared [`LlamaCppModel`].
 pub struct LlamaCppBackend {
-    // Boxed to ensure stable memory addresses
-    _backend: Box<LlamaCppLlamaBackend>,
-    model: Box<LlamaModel>,
+    // The context borrows from the model, which the Arc keeps at a stable
+    // address for as long as the backend lives
+    model: Arc<LlamaCppModel>,
     // Store context as raw pointer with manual lifetime management
     context: *mut llama_cpp_2::context::LlamaContext<'static>,
+    /// Tokens in the KV cache, by position; saved with the context
+    cached: Vec<LlamaToken>,
+    sampling: Sampling,
 }
 
 impl LlamaCppBackend {
-    /// Initialize a new llama.cpp backend from a GGUF model file
+    /// A backend on the GGUF model file at `model_path`, loading the model
+    /// unless this process already has it
     pub fn new(model_path: &Path) -> Result<Self> {
-        // Initialize llama.cpp backend (must be kept alive)
-        let backend = Box::new(LlamaCppLlamaBackend::init()?);
-
-        // Load model
-        let model_params = LlamaModelParams::default();
-        let model = Box::new(
-            LlamaModel::load_from_file(&backend, model_path, &model_params)
-                .context("Failed to load model")?,
-        );
+        let model = LlamaCppModel::shared(model_path)?;
 
         // Create context - it borrows from model
-        let ctx_params = LlamaContextParams::default().with_n_ctx(NonZeroU32::new(2048));
+        let ctx_params = LlamaContextParams::default()
+            .with_n_ctx(NonZeroU32::new(2048))
+            .with_n_batch(PROMPT_BATCH);
 
         let context = model
-            .new_context(&backend, ctx_params)
+            .model
+            .new_context(llama_backend()?, ctx_params)
             .context("Failed to create context")?;
 
-        // SAFETY: We box the model and backend to ensure stable addresses.
-        // The context pointer remains valid as long as model and backend are alive.
-        // We manually manage the context lifetime via Drop.
+        // SAFETY: The Arc keeps the model alive and in place, and the backend
+        // is a process-wide static. The context pointer remains valid as long
+        // as Self holds the Arc; we manually manage the context lifetime via Drop.
         let context_ptr = Box::into_raw(Box::new(unsafe {
             std::mem::transmute::<_, llama_cpp_2::context::LlamaContext<'static>>(context)
         }));
 
         Ok(Self {
-            _backend: backend,
             model,
             context: context_ptr,
+            cached: Vec::new(),
+            sampling: Sampling::default(),
         })
     }
+
+    pub fn with_sampling(mut self, sampling: Sampling) -> Self {
+        self.sampling = sampling;
+        self
+    }
 }
 
 impl Drop for LlamaCppBackend {
     fn drop(&mut self) {
-        // SAFETY: We created this pointer in new() and haven't dropped it yet
+        // SAFETY: We created this pointer in new() and haven't dropped it yet;
+        // it is freed before the model Arc is released
         unsafe {
             if !self.context.is_null() {
                 let _ = Box::from_raw(self.context);
@@ -88,62 +151,113 @@ impl LLMBackend for LlamaCppBackend {
 
         // Tokenize prompt
         let tokens = self
+            .model
             .model
             .str_to_token(&input.prompt, AddBos::Always)
             .context("Failed to tokenize prompt")?;
+        let n_ctx = context.n_ctx() as usize;
+        if tokens.len() + input.max_tokens > n_ctx {
+            bail!(
+                "Prompt of {} tokens plus {} to generate exceeds the context of {} tokens",
+                tokens.len(),
+                input.max_tokens,
+                n_ctx
+            );
+        }
 
-        // Create batch with size based on prompt length + generation headroom
-        let batch_size = (tokens.len() + input.max_tokens).max(512);
-        let mut batch = LlamaBatch::new(batch_size, 1);
-        for (i, token) in tokens.iter().enumerate() {
-            let is_last = i == tokens.len() - 1;
-            batch.add(*token, input.current_pos + i as i32, &[0], is_last)?;
+        // Keep the cached prefix the prompt starts with, but evaluate at least
+        // its last token so there are logits to sample from
+        let reused = self
+            .cached
+            .iter()
+            .zip(&tokens)
+            .take_while(|(cached, token)| cached == token)
+            .count()
+            .min(tokens.len() - 1);
+        if reused < self.cached.len() {
+            context
+                .clear_kv_cache_seq(Some(0), Some(reused as u32), None)
+                .context("Failed to trim the context")?;
+            self.cached.truncate(reused);
         }
 
-        // Decode the prompt
-        context
-            .decode(&mut batch)
-            .context("Failed to decode batch")?;
+        // Decode the prompt in batches of at most n_batch tokens, which is all
+        // llama.cpp takes in one decode
+        let n_batch = context.n_batch() as usize;
+        let mut batch = LlamaBatch::new(n_batch, 1);
+        let pending = tokens.len() - reused;
+        for chunk in tokens[reused..].chunks(n_batch) {
+            batch.clear();
+            let start = self.cached.len();
+            for (offset, token) in chunk.iter().enumerate() {
+                let i = start + offset;
+                batch.add(*token, i as i32, &[0], i == tokens.len() - 1)?;
+            }
+            context
+                .decode(&mut batch)
+                .context("Failed to decode batch")?;
+            self.cached.extend(chunk);
+            if let Some(progress) = input.progress.as_ref().filter(|_| pending > n_batch) {
+                progress.report(self.cached.len() - reused, pending);
+            }
+        }
 
         // Generate tokens
         let mut result = String::new();
         let mut n_generated = 0;
-        let prompt_len = tokens.len() as i32;
+        let evaluated = pending as i32;
 
         while n_generated < input.max_tokens {
-            // Get token candidates and sample greedily
+            // Get token candidates
             let candidates = context.candidates();
             let mut candidates_array = LlamaTokenDataArray::from_iter(candidates, false);
 
-            // Select token with highest probability (greedy sampling)
-            candidates_array.sample_token_greedy();
+            match self.sampling.temperature {
+                // Draw from the tempered distribution; each token gets its own
+                // seed so a run is reproducible without repeating the same draw.
+                // Without a seed, u32::MAX (LLAMA_DEFAULT_SEED) draws at random
+                Some(temperature) if temperature > 0.0 => {
+                    candidates_array.apply_sampler(&LlamaSampler::temp(temperature));
+                    let seed = self
+                        .sampling
+                        .seed
+                        .map_or(u32::MAX, |seed| seed.wrapping_add(n_generated as u32));
+                    candidates_array.sample_token(seed);
+                }
+                // Select token with highest probability (greedy sampling)
+                _ => {
+                    candidates_array.sample_token_greedy();
+                }
+            }
             let token = match candidates_array.selected_token() {
                 Some(t) => t,
                 None => break, // No token selected, end generation
             };
 
             // Check for EOS
-            if self.model.is_eog_token(token) {
+            if self.model.model.is_eog_token(token) {
                 break;
             }
 
             // Decode token
-            if let Ok(piece) = self.model.token_to_str(token, Special::Tokenize) {
+            if let Ok(piece) = self.model.model.token_to_str(token, Special::Tokenize) {
                 result.push_str(&piece);
             }
 
+            // The model started the next turn (or a tool output) itself
+            if let Some(end) = stop_position(&result, &input.stop) {
+                result.truncate(end);
+                break;
+            }
+
             // Prepare next batch
             batch.clear();
-            batch.add(
-                token,
-                input.current_pos + prompt_len + n_generated as i32,
-                &[0],
-                true,
-            )?;
+            batch.add(token, self.cached.len() as i32, &[0], true)?;
 
             context
                 .decode(&mut batch)
                 .context("Failed to decode batch")?;
+            self.cached.push(token);
 
             n_generated += 1;
 
@@ -168,12 +282,84 @@ impl LLMBackend for LlamaCppBackend {
             }
         }
 
-        // Return generated text and total tokens processed (prompt + generated)
+        // Return generated text and the tokens evaluated (prompt + generated)
         Ok(LLMOutput {
             text: result.trim().to_string(),
-            tokens_processed: prompt_len + n_generated as i32,
+            tokens_processed: evaluated + n_generated as i32,
         })
     }
+
+    fn save_state(&mut self, path: &Path) -> Result<Option<i32>> {
+        // SAFETY: context pointer is valid for the lifetime of Self
+        let context = unsafe { self.context.as_ref().context("Context pointer is null")? };
+        context
+            .state_save_file(path, &self.cached)
+            .with_context(|| format!("Failed to save the context to {}", path.display()))?;
+        Ok(Some(self.cached.len() as i32))
+    }
+
+    fn load_state(&mut self, path: &Path) -> Result<Option<i32>> {
+        // SAFETY: context pointer is valid for the lifetime of Self
+        let context = unsafe { self.context.as_mut().context("Context pointer is null")? };
+        let max_tokens = context.n_ctx() as usize;
+        self.cached = context
+            .state_load_file(path, max_tokens)
+            .with_context(|| format!("Failed to load the context from {}", path.display()))?;
+        Ok(Some(self.cached.len() as i32))
+    }
+
+    fn rewind(&mut self, position: i32) -> Result<bool> {
+        // SAFETY: context pointer is valid for the lifetime of Self
+        let context = unsafe { self.context.as_mut().context("Context pointer is null")? };
+        let position = position.max(0);
+        context
+            .clear_kv_cache_seq(Some(0), Some(position as u32), None)
+            .context("Failed to rewind the context")?;
+        self.cached.truncate(position as usize);
+        Ok(true)
+    }
+
+    /// Pooled embeddings from a context of their own, so the generation
+    /// context is left as it is; the model must pool them (embedding models do)
+    fn embed(&mut self, texts: &[String]) -> Result<Option<Vec<Vec<f32>>>> {
+        let params = LlamaContextParams::default()
+            .with_n_ctx(NonZeroU32::new(EMBEDDING_BATCH))
+            .with_n_batch(EMBEDDING_BATCH)
+            .with_embeddings(true);
+        let mut context = self
+            .model
+            .model
+            .new_context(llama_backend()?, params)
+            .context("Failed to create an embedding context")?;
+        let mut batch = LlamaBatch::new(EMBEDDING_BATCH as usize, 1);
+
+        let mut embeddings = Vec::with_capacity(texts.len());
+        for text in texts {
+            let tokens = self
+                .model
+                .model
+                .str_to_token(text, AddBos::Always)
+                .context("Failed to tokenize text")?;
+            if tokens.len() > EMBEDDING_BATCH as usize {
+                bail!(
+                    "Text of {} tokens exceeds the {} tokens an embedding covers",
+                    tokens.len(),
+                    EMBEDDING_BATCH
+                );
+            }
+            context.clear_kv_cache();
+            batch.clear();
+            batch.add_sequence(&tokens, 0, false)?;
+            context
+                .decode(&mut batch)
+                .context("Failed to decode text")?;
+            let embedding = context
+                .embeddings_seq_ith(0)
+                .context("The model returned no pooled embedding")?;
+            embeddings.push(embedding.to_vec());
+        }
+        Ok(Some(embeddings))
+    }
 }
 
 /// Temporarily suppress stderr (for Metal shader compilation logs)
@@ -0,0 +1,106 @@
+//! llama.cpp `llama-server` backend
+//!
+//! Attaches to a running `llama-server` through its native `/completion`
+//! endpoint (prompt in, text out) instead of loading a second copy of the model.
+//! Embeddings go to `/v1/embeddings`, which needs a server started with
+//! `--embeddings`.
+
+use crate::embeddings::openai_embeddings;
+use crate::llm::{LLMBackend, LLMInput, LLMOutput, Sampling};
+use crate::throttle::{estimate_tokens, http_agent, Throttle};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+
+/// Address `llama-server` listens on by default
+pub const DEFAULT_ENDPOINT: &str = "http://localhost:8080";
+
+/// Client for a `llama-server` instance
+pub struct LlamaServerBackend {
+    agent: ureq::Agent,
+    throttle: Arc<Throttle>,
+    url: String,
+    embeddings_url: String,
+    sampling: Sampling,
+}
+
+/// The parts of a `/completion` response the agent uses
+#[derive(Debug, Deserialize)]
+struct CompletionResponse {
+    content: String,
+    #[serde(default)]
+    tokens_evaluated: i32,
+    #[serde(default)]
+    tokens_predicted: i32,
+}
+
+impl LlamaServerBackend {
+    /// Connect to the server at `endpoint` (e.g. `http://localhost:8080`)
+    pub fn new(endpoint: &str) -> Self {
+        Self {
+            agent: http_agent(),
+            throttle: Arc::default(),
+            url: format!("{}/completion", endpoint.trim_end_matches('/')),
+            embeddings_url: format!("{}/v1/embeddings", endpoint.trim_end_matches('/')),
+            sampling: Sampling::default(),
+        }
+    }
+
+    /// Send through `throttle`, shared with other backends holding it
+    pub fn with_throttle(mut self, throttle: Arc<Throttle>) -> Self {
+        self.throttle = throttle;
+        self
+    }
+
+    pub fn with_sampling(mut self, sampling: Sampling) -> Self {
+        self.sampling = sampling;
+        self
+    }
+}
+
+impl LLMBackend for LlamaServerBackend {
+    fn infer(&mut self, input: LLMInput) -> Result<LLMOutput> {
+        // The server keeps its own KV cache; `cache_prompt` reuses the shared prefix
+        let mut body = json!({
+            "prompt": input.prompt,
+            "n_predict": input.max_tokens,
+            "cache_prompt": true,
+        });
+        if let Some(temperature) = self.sampling.temperature {
+            body["temperature"] = json!(temperature);
+        }
+        if let Some(seed) = self.sampling.seed {
+            body["seed"] = json!(seed);
+        }
+        if !input.stop.is_empty() {
+            body["stop"] = json!(input.stop);
+        }
+
+        let response: CompletionResponse = self
+            .throttle
+            .send(estimate_tokens(&input), || {
+                self.agent.post(&self.url).send_json(&body)
+            })
+            .with_context(|| format!("Request to llama-server at {} failed", self.url))?
+            .body_mut()
+            .read_json()
+            .context("Invalid response from llama-server")?;
+
+        Ok(LLMOutput {
+            text: response.content,
+            tokens_processed: response.tokens_evaluated + response.tokens_predicted,
+        })
+    }
+
+    fn embed(&mut self, texts: &[String]) -> Result<Option<Vec<Vec<f32>>>> {
+        openai_embeddings(
+            &self.agent,
+            &self.throttle,
+            &self.embeddings_url,
+            None,
+            texts,
+        )
+        .map(Some)
+    }
+}
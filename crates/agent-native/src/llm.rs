@@ -3,7 +3,23 @@
 //! This module defines the interface between the host runtime and LLM inference engines.
 //! The agent core never depends on this - it only sees text input/output.
 
+use agent_core::{tool_manifest::ToolManifest, transcript::ChatTranscript};
 use anyhow::Result;
+use base64::Engine;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Context attached to errors from loading or calling a backend, so the
+/// CLI can exit with [`RunOutcome::BackendError`](agent_core::RunOutcome)
+#[derive(Debug, Clone, Copy)]
+pub struct BackendError;
+
+impl fmt::Display for BackendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("The model backend failed")
+    }
+}
 
 /// Input to an LLM inference call
 #[derive(Debug, Clone)]
@@ -14,11 +30,104 @@ pub struct LLMInput {
     /// Maximum number of tokens to generate
     pub max_tokens: usize,
 
-    /// Current position in the KV cache (for append-only context)
-    pub current_pos: i32,
+    /// Where generation stops; the sequence is not part of the output
+    ///
+    /// Completion backends honor it (see [`StopSequences`](agent_core::StopSequences));
+    /// chat backends keep roles apart themselves and ignore it.
+    pub stop: Vec<String>,
 
     /// Whether this is the first generation (may require special handling like stderr suppression)
     pub first_generation: bool,
+
+    /// The same turn as a structured conversation, for chat APIs
+    ///
+    /// Set for agent loop turns; `None` for one-off prompts such as extraction,
+    /// which chat backends send as a single user message.
+    pub chat: Option<ChatContext>,
+
+    /// Images tools returned since the model's last reply
+    ///
+    /// Chat backends with vision (Anthropic, Gemini) attach them to the last
+    /// turn; others ignore them, and the model reads the placeholder the
+    /// history holds for each.
+    pub images: Vec<ImageAttachment>,
+
+    /// Where a backend evaluating a long prompt in pieces reports how far it got
+    pub progress: Option<PromptProgress>,
+}
+
+/// Told the prompt tokens evaluated so far and the total
+#[derive(Clone)]
+pub struct PromptProgress(Arc<dyn Fn(usize, usize) + Send + Sync>);
+
+impl PromptProgress {
+    pub fn new(report: impl Fn(usize, usize) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(report))
+    }
+
+    pub fn report(&self, done: usize, total: usize) {
+        (self.0)(done, total)
+    }
+}
+
+impl fmt::Debug for PromptProgress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PromptProgress")
+    }
+}
+
+/// An image passed to a multimodal model with a chat turn
+#[derive(Debug, Clone)]
+pub struct ImageAttachment {
+    /// The artifact the image is stored as (`tool-002`)
+    pub reference: String,
+    /// MIME type, e.g. `image/png`
+    pub media_type: &'static str,
+    pub data: Vec<u8>,
+}
+
+impl ImageAttachment {
+    /// The image data, base64-encoded as the APIs take it
+    pub fn base64(&self) -> String {
+        base64::engine::general_purpose::STANDARD.encode(&self.data)
+    }
+}
+
+/// MIME type of an image file, from its extension
+pub fn image_media_type(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    match extension.as_str() {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        _ => None,
+    }
+}
+
+/// An agent turn in structured form
+///
+/// Text backends render the conversation into `LLMInput::prompt`; chat backends
+/// convert the transcript to their message format and offer the tools through
+/// native tool use.
+#[derive(Debug, Clone)]
+pub struct ChatContext {
+    /// System prompt (including any instructions for this turn) and history,
+    /// with calls to `tools` as tool call entries
+    pub transcript: ChatTranscript,
+
+    /// Host tools the model may call
+    pub tools: Vec<ToolManifest>,
+}
+
+/// Sampling settings of a backend; `None` keeps the backend's default
+///
+/// The llama.cpp backend samples greedily unless a temperature is set. Hosted
+/// APIs that take no seed (Anthropic) ignore it.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Sampling {
+    pub temperature: Option<f32>,
+    pub seed: Option<u32>,
 }
 
 /// Output from an LLM inference call
@@ -27,7 +136,8 @@ pub struct LLMOutput {
     /// The generated text
     pub text: String,
 
-    /// Total tokens processed (prompt + generated)
+    /// Tokens this call processed: the prompt tokens evaluated (not those a
+    /// backend reused from its cache) plus the generated ones
     pub tokens_processed: i32,
 }
 
@@ -35,7 +145,83 @@ pub struct LLMOutput {
 ///
 /// Implementors provide actual inference capabilities.
 /// The agent core never sees this trait - it remains environment-agnostic.
+///
+/// Every call carries the whole prompt. A backend that keeps a context between
+/// calls owns it: it reuses the part of its cache the new prompt starts with,
+/// evaluates the rest, and never lets hosts address cache positions except
+/// through [`rewind`](Self::rewind) and the saved states.
 pub trait LLMBackend {
     /// Perform inference on the given input
     fn infer(&mut self, input: LLMInput) -> Result<LLMOutput>;
+
+    /// Save the context (KV cache) to `path`; the position it ends at, or
+    /// `None` if the backend keeps none between calls
+    fn save_state(&mut self, _path: &Path) -> Result<Option<i32>> {
+        Ok(None)
+    }
+
+    /// Load a context saved with [`save_state`](Self::save_state); the
+    /// position it ends at, or `None` if the backend keeps none
+    fn load_state(&mut self, _path: &Path) -> Result<Option<i32>> {
+        Ok(None)
+    }
+
+    /// Drop the context from `position` on, so the next input continues
+    /// there; `false` if the backend keeps none
+    fn rewind(&mut self, _position: i32) -> Result<bool> {
+        Ok(false)
+    }
+
+    /// One embedding per text, in order (see [`agent_core::embedding`]);
+    /// `None` if the backend cannot embed
+    fn embed(&mut self, _texts: &[String]) -> Result<Option<Vec<Vec<f32>>>> {
+        Ok(None)
+    }
+}
+
+impl<B: LLMBackend + ?Sized> LLMBackend for Box<B> {
+    fn infer(&mut self, input: LLMInput) -> Result<LLMOutput> {
+        (**self).infer(input)
+    }
+
+    fn save_state(&mut self, path: &Path) -> Result<Option<i32>> {
+        (**self).save_state(path)
+    }
+
+    fn load_state(&mut self, path: &Path) -> Result<Option<i32>> {
+        (**self).load_state(path)
+    }
+
+    fn rewind(&mut self, position: i32) -> Result<bool> {
+        (**self).rewind(position)
+    }
+
+    fn embed(&mut self, texts: &[String]) -> Result<Option<Vec<Vec<f32>>>> {
+        (**self).embed(texts)
+    }
+}
+
+/// Non-blocking LLM backend interface (`async` feature)
+///
+/// HTTP backends implement this so a request in flight does not hold a thread.
+/// No `Send` bound: in-process backends such as llama.cpp own raw pointers and
+/// stay on the thread that created them.
+#[cfg(feature = "async")]
+pub trait AsyncLLMBackend {
+    /// Perform inference on the given input
+    fn infer(&mut self, input: LLMInput) -> impl std::future::Future<Output = Result<LLMOutput>>;
+}
+
+/// Runs a blocking [`LLMBackend`] inline on the async runner
+///
+/// Inference blocks the current task, which is what an in-process backend does
+/// anyway; use it on a current-thread runtime.
+#[cfg(feature = "async")]
+pub struct Inline<B>(pub B);
+
+#[cfg(feature = "async")]
+impl<B: LLMBackend> AsyncLLMBackend for Inline<B> {
+    fn infer(&mut self, input: LLMInput) -> impl std::future::Future<Output = Result<LLMOutput>> {
+        std::future::ready(self.0.infer(input))
+    }
 }
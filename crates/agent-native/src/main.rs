@@ -1,72 +1,218 @@
+mod anthropic_backend;
+mod artifacts;
+#[cfg(feature = "async")]
+mod async_agent;
+mod audit;
+mod batch;
+mod chaos;
+mod config;
+mod confine;
+mod console;
+mod critic;
+mod doctor;
+mod dry_run;
+mod embeddings;
+mod environment;
+mod eval;
+mod events;
+mod gemini_backend;
+mod image_tool;
+mod input;
+mod kv_state;
+mod limits;
 mod llama_cpp_backend;
+mod llama_server_backend;
 mod llm;
+mod memory;
+mod mistralrs_backend;
+mod notify;
+mod patch_tool;
+mod preview;
+mod sandbox;
+mod scenario;
+mod schedule;
+mod search_tool;
+mod skill_commands;
 mod skill_discovery;
+mod sql_tool;
+mod sweep;
+mod task;
+mod telemetry;
+mod throttle;
+mod tool_commands;
+mod tool_discovery;
+mod transcribe;
+mod undo;
+mod warmup;
 
 use agent_core::{
-    agent::{apply_tool_result, process_model_output, AgentDecision, AgentState, Role},
-    guardrail::{GuardrailChain, GuardrailContext, GuardrailResult, PlausibilityGuard},
+    agent::{AgentState, Role},
+    approval::{ApprovalMode, ApprovalPolicy},
+    artifact::ArtifactRequest,
+    batch::{NotifyOn, OverrideBounds},
+    capabilities::HostCapabilities,
+    chaos::{ChaosInjector, ChaosProfile},
+    confinement::{ConfinementLevel, ConfinementPolicy},
+    critic::CriticGuard,
+    embedding::RelevanceGuard,
+    environment::environment_prompt,
+    eval::DEFAULT_CASE_TIMEOUT_SECS,
+    extraction_batch::{ExtractionBatch, TextChunk, DEFAULT_CHUNK_OVERLAP, DEFAULT_CHUNK_SIZE},
+    extraction_format::{format_extraction, ExtractFormat},
+    extraction_records::{parse_records, ExtractionRecordResult},
+    few_shot::{FewShotPolicy, DEFAULT_EXAMPLES_BUDGET, DEFAULT_EXAMPLES_PER_ITEM},
+    guardrail::{
+        is_search_command, AnswerChain, GuardrailChain, GuardrailResult, PreExecutionChain,
+        PreExecutionContext, SafetyCeilingGuard,
+    },
+    history_budget::{HistoryBudget, DEFAULT_OLDER_TURN_TOKENS},
+    limits::{parse_size, LimitPolicy, ResourceLimits},
+    memory::{RecallRequest, RECALL_INSTRUCTIONS},
+    observation::{
+        ObservationMode, ObservationPolicy, DEFAULT_OBSERVATION_MIN_TOKENS, DEFAULT_SUMMARY_TOKENS,
+    },
+    outcome::RunOutcome,
+    profile::{compose_system_prompt, GuardrailSet, Profile, PromptMode, PromptSections},
+    prompt_template::PromptTemplate,
+    protocol::AnswerProtocol,
+    rate_limit::{RateLimits, DEFAULT_MAX_RETRIES},
+    reasoning::ReasoningDelimiters,
+    response_spec::ResponseSpec,
+    scenario::Scenario,
+    session::{
+        AgentSession, PromptSpec, RetryReason, SessionError, SessionFailure, StepAction, ToolReview,
+    },
     skill::{
-        parse_skill_output, validate_extraction_output, ExtractionInput, ExtractionTarget,
-        SkillError, SkillRequest, SkillResult_,
+        parse_skill_json, ExtractionInput, ExtractionOutput, ExtractionTarget, SkillError,
+        SkillRequest, SkillResult, SkillResult_, EXTRACTION_SKILL,
     },
-    tool::{ToolRequest, ToolResult},
+    skill_guardrail::{SkillGuardContext, SkillGuardrailChain},
+    stop::{PromptPhase, StopSequences},
+    sweep::DEFAULT_SWEEP_RUNS,
+    task::TaskFile,
+    telemetry::ContentPolicy,
+    tool::{execute_builtin_tool, CallId, ToolRequest, ToolResult},
+    tool_manifest::{SafetyClass, ToolAliases, ToolManifest, ToolRegistry},
 };
+use anthropic_backend::AnthropicBackend;
 use anyhow::{Context, Result};
-use clap::{Parser, Subcommand};
+use artifacts::{RunArtifacts, DEFAULT_ARTIFACTS_DIR, DEFAULT_CONTEXT_TOKENS};
+use audit::{AuditLog, RunId};
+use chaos::SharedChaos;
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
+use console::{Spinner, Verbosity};
+use embeddings::EmbeddingWorker;
+use events::{AgentEvent, ApprovalSource, EventSink, OutputMode};
+use gemini_backend::GeminiBackend;
+use input::{Input, DEFAULT_MAX_INPUT_BYTES};
 use llama_cpp_backend::LlamaCppBackend;
-use llm::{LLMBackend, LLMInput};
+use llama_server_backend::LlamaServerBackend;
+use llm::{BackendError, ChatContext, LLMBackend, LLMInput, LLMOutput, PromptProgress, Sampling};
+use memory::RunMemory;
+use mistralrs_backend::MistralRsBackend;
+use notify::Notifier;
+use preview::ToolPreview;
+use sandbox::Sandbox;
 use serde_json::json;
-use skill_discovery::{build_available_skills_prompt, discover_skills};
+use skill_discovery::{
+    build_available_skills_prompt, discover_skills, load_skills, LoadedSkill, LoadedSkills,
+};
 use std::io::{self, Write};
-use std::path::PathBuf;
-use std::process::Command;
-
-const BASE_SYSTEM_PROMPT: &str = r#"You are a helpful AI agent with access to tools and skills.
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use telemetry::Telemetry;
+use throttle::Throttle;
+use tool_discovery::{build_tool_registry, discover_tools, with_result_validators};
+use transcribe::{TranscribeConfig, Transcriber};
+use undo::UndoJournal;
 
-Available tools:
-- shell: Execute shell commands
+const SYSTEM_PROMPT_INTRO: &str = "You are a helpful AI agent with access to tools and skills.";
 
-Available skills:
-- extract: Extract structured information from text (email, url, date, entity, name)
+/// Instructions following the generated "Available tools" section
+const BASE_SYSTEM_PROMPT: &str = r#"Available skills:
+- extract: Extract structured information from text (email, url, date, entity, name, fields)
 
-To invoke a tool, respond with JSON:
-{"tool": "shell", "command": "your command here"}
+To invoke a tool, respond with JSON naming the tool and its parameters:
+{"tool": "<tool name>", "<param>": "<value>"}
 
 To invoke a skill, respond with JSON:
 {"skill": "extract", "text": "the text to extract from", "target": "email"}
 
-Supported extraction targets: email, url, date, entity, name
+Supported extraction targets: email, url, date, entity, name, fields
+For target "entity", you may limit the result with "classes": ["people", "organizations", "locations"]
+For target "fields", pass the field names and descriptions as "schema": {"invoice_number": "the invoice id"}
+
+Long tool outputs are shortened and saved as artifacts. To read the next page
+of one, respond with JSON naming the artifact number and the page:
+{"tool_more": {"id": 1, "page": 2}}
+or name the lines to read:
+{"read_artifact": {"id": "tool-001", "range": [41, 140]}}
+
+To keep a value for later steps, store it under a name, and read it back or
+use it as ${name} in tool parameters:
+{"set_var": {"name": "order_id", "value": "A-1042"}}
+{"get_var": {"name": "order_id"}}
 
 IMPORTANT:
-- Only output JSON when you want to invoke a tool or skill
+- Only output JSON when you want to invoke a tool, a skill, an artifact read or a variable
 - For final answers, respond in plain text (no JSON)
 - Be concise and helpful
 
-Example tool invocation:
-{"tool": "shell", "command": "ls -la"}
-
-Example skill invocation:
-{"skill": "extract", "text": "Contact us at hello@agent.rs", "target": "email"}
-
 Example final answer:
 The directory contains 5 files including README.md and src/."#;
 
-const TOOL_RESPONSE_SCHEMA: &str = r#"When responding after tool usage:
-- First provide an OBSERVATIONS section containing factual information derived directly from tool output.
-- Then provide a FINAL ANSWER section that directly answers the user request.
+const DEFAULT_MODEL_PATH: &str = "./granite-4.0-micro-Q8_0.gguf";
 
-Both sections are required."#;
+const DEFAULT_SKILLS_DIR: &str = "skills";
 
-const DEFAULT_MODEL_PATH: &str = "./granite-4.0-micro-Q8_0.gguf";
+const DEFAULT_TOOLS_DIR: &str = "tools";
 
-fn build_system_prompt(available_skills_prompt: &str) -> String {
-    let mut prompt = String::new();
-    prompt.push_str(BASE_SYSTEM_PROMPT);
-    if !available_skills_prompt.trim().is_empty() {
-        prompt.push_str("\n\n");
-        prompt.push_str(available_skills_prompt);
-    }
-    prompt
+/// Extraction prompt used when the skill manifest declares no `prompt` template
+const DEFAULT_EXTRACTION_TEMPLATE: &str = r#"Extract {{target_desc}} from the following text.
+
+IMPORTANT:
+- Output ONLY valid JSON
+- Only include values that ACTUALLY APPEAR in the text
+- Do NOT invent or hallucinate values
+- {{no_match}}
+
+Text: "{{input.text}}"
+
+Output format: {{output_format}}
+
+JSON output:"#;
+
+/// The system prompt: intro, generated tool list, built-in instructions
+/// (with recall when there is a `memory`), few-shot examples, skills and
+/// environment facts, with the custom prompt (`--system-prompt` or a
+/// profile's) merged in
+fn build_system_prompt(
+    available_tools_prompt: &str,
+    examples_prompt: &str,
+    available_skills_prompt: &str,
+    environment_facts: &str,
+    custom: Option<(&str, PromptMode)>,
+    memory: bool,
+) -> String {
+    let instructions = match memory {
+        // Recall goes with the other calls, before the answer rules
+        true => BASE_SYSTEM_PROMPT.replacen(
+            "\n\nIMPORTANT:",
+            &format!("\n\n{}\n\nIMPORTANT:", RECALL_INSTRUCTIONS),
+            1,
+        ),
+        false => BASE_SYSTEM_PROMPT.to_string(),
+    };
+    let sections = PromptSections {
+        intro: SYSTEM_PROMPT_INTRO,
+        tools: available_tools_prompt,
+        instructions: &instructions,
+        examples: examples_prompt,
+        skills: available_skills_prompt,
+        environment: environment_facts,
+    };
+    compose_system_prompt(sections, custom)
 }
 
 #[derive(Parser, Debug)]
@@ -76,408 +222,2082 @@ struct Cli {
     #[command(subcommand)]
     command: Option<CliCommand>,
 
-    /// Path to the GGUF model file (agent mode)
+    /// Path to the GGUF model file (agent mode, llama-cpp backend)
     #[arg(short, long)]
     model: Option<PathBuf>,
 
-    /// The user query to process (agent mode)
+    /// Inference engine: load the model in-process, or attach to a running server
+    #[arg(long, value_enum, default_value_t = BackendKind::LlamaCpp, global = true)]
+    backend: BackendKind,
+
+    /// Server or API base URL (default: the backend's standard address)
+    #[arg(long, global = true)]
+    endpoint: Option<String>,
+
+    /// Model id for server and hosted API backends
+    #[arg(long, global = true)]
+    model_id: Option<String>,
+
+    /// Requests per minute to send to a server or API backend
+    #[arg(long, value_name = "N", global = true)]
+    requests_per_minute: Option<u32>,
+
+    /// Tokens per minute (prompt plus completion budget) to send to a server
+    /// or API backend
+    #[arg(long, value_name = "N", global = true)]
+    tokens_per_minute: Option<u64>,
+
+    /// Requests to a server or API backend in flight at once; more queue
+    #[arg(long, value_name = "N", global = true)]
+    max_concurrent: Option<usize>,
+
+    /// Retries of a request the server throttled (HTTP 429 or 503), after its
+    /// Retry-After or with exponential backoff
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_MAX_RETRIES, global = true)]
+    max_retries: u32,
+
+    /// Sampling temperature; unset keeps each backend's default (greedy for
+    /// llama-cpp)
+    #[arg(long, value_name = "T", global = true)]
+    temperature: Option<f32>,
+
+    /// Sampling seed, for repeatable runs where the backend supports one
+    #[arg(long, value_name = "N", global = true)]
+    seed: Option<u32>,
+
+    /// The user query to process (agent mode); `-` reads it from stdin
     #[arg(short, long)]
     query: Option<String>,
 
+    /// Read the query from a file
+    #[arg(long, value_name = "PATH", conflicts_with = "query")]
+    query_file: Option<PathBuf>,
+
+    /// Transcribe a spoken query from an audio recording
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["query", "query_file"])]
+    audio: Option<PathBuf>,
+
+    /// Continue a conversation exported from a chat UI or API (a JSON
+    /// messages array or request body) instead of starting from a query
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["query", "query_file", "audio"])]
+    conversation: Option<PathBuf>,
+
+    /// Format of `--conversation`
+    #[arg(long, value_enum, default_value = "openai", requires = "conversation")]
+    conversation_format: ConversationFormat,
+
+    /// Save the llama.cpp context to PATH when the run answers, and resume
+    /// from it when a later run continues the same conversation
+    #[arg(long, value_name = "PATH")]
+    kv_state: Option<PathBuf>,
+
+    /// Remember the tool outputs and answers of runs in PATH, and let the
+    /// model recall them in later runs (needs a backend that embeds)
+    #[arg(long, value_name = "PATH")]
+    memory: Option<PathBuf>,
+
+    /// Where `--audio` is transcribed
+    #[arg(long, value_enum, default_value = "api", requires = "audio")]
+    transcriber: Transcriber,
+
+    /// Transcription endpoint (`--transcriber api`)
+    #[arg(long, value_name = "URL", default_value = transcribe::DEFAULT_ENDPOINT,
+          requires = "audio")]
+    transcription_endpoint: String,
+
+    /// Transcription model: a model id for `api` (default whisper-1), a ggml
+    /// model file for `whisper-cpp`
+    #[arg(long, value_name = "MODEL", requires = "audio")]
+    transcription_model: Option<String>,
+
+    /// Language spoken in `--audio` (ISO-639-1, e.g. `en`); detected when unset
+    #[arg(long, value_name = "CODE", requires = "audio")]
+    audio_language: Option<String>,
+
+    /// Largest query or extraction text read from a file or stdin (bytes, or
+    /// with a K/M/G suffix)
+    #[arg(long, value_name = "SIZE", value_parser = parse_input_size,
+          default_value_t = DEFAULT_MAX_INPUT_BYTES, global = true)]
+    max_input_bytes: u64,
+
     /// Maximum number of agent loop iterations
-    #[arg(short = 'i', long, default_value = "5")]
+    #[arg(
+        short = 'i',
+        long,
+        default_value = "5",
+        overrides_with = "max_iterations"
+    )]
     max_iterations: usize,
 
     /// Number of tokens to generate per iteration
-    #[arg(short = 'n', long, default_value = "256")]
+    #[arg(
+        short = 'n',
+        long,
+        default_value = "256",
+        overrides_with = "max_tokens"
+    )]
     max_tokens: usize,
+
+    /// Directory for run artifacts (large tool outputs, one subdirectory per run)
+    #[arg(long, default_value = DEFAULT_ARTIFACTS_DIR)]
+    artifacts_dir: PathBuf,
+
+    /// Context window of the model in tokens; pages of long tool outputs are
+    /// sized to what is left of it
+    #[arg(long, default_value_t = DEFAULT_CONTEXT_TOKENS)]
+    context_tokens: usize,
+
+    /// Tokens each tool output may take in the prompt; the run keeps it whole
+    #[arg(long, value_name = "TOKENS")]
+    tool_output_tokens: Option<usize>,
+
+    /// Assistant turns, counted from the end, shown whole in the prompt;
+    /// older ones are cut to --older-turn-tokens
+    #[arg(long, value_name = "N")]
+    full_turns: Option<usize>,
+
+    /// Tokens each older assistant turn may take in the prompt
+    #[arg(long, value_name = "TOKENS", default_value_t = DEFAULT_OLDER_TURN_TOKENS, requires = "full_turns")]
+    older_turn_tokens: usize,
+
+    /// Leave rejected answers, refused calls and calls with rejected outputs
+    /// out of the prompt once the run has recovered from them; the saved state
+    /// and the audit log keep them
+    #[arg(long)]
+    prune_corrections: bool,
+
+    /// Compress accepted tool outputs above --observation-min-tokens into a
+    /// short observation, keeping the raw output as an artifact: raw (off),
+    /// model (the model summarizes) or local (first and last lines)
+    #[arg(long, value_name = "MODE", default_value = "raw", value_parser = parse_observation_mode)]
+    observations: ObservationMode,
+
+    /// Tool outputs up to this many tokens are kept as they are
+    #[arg(long, value_name = "TOKENS", default_value_t = DEFAULT_OBSERVATION_MIN_TOKENS)]
+    observation_min_tokens: usize,
+
+    /// Tokens an observation's summary may take
+    #[arg(long, value_name = "TOKENS", default_value_t = DEFAULT_SUMMARY_TOKENS)]
+    summary_tokens: usize,
+
+    /// Stop sequence for completion backends as `phase=text`, where phase is
+    /// turn, summary, extraction or critique and `\n` stands for a newline;
+    /// an empty text drops the phase's defaults (repeatable)
+    #[arg(long = "stop", value_name = "PHASE=TEXT")]
+    stop: Vec<String>,
+
+    /// Approval rule as `target=mode`, where target is a safety class
+    /// (read-only, network, mutating, destructive) or a tool name and mode is
+    /// auto, prompt or deny (repeatable). Destructive tools always prompt.
+    #[arg(long = "approve", value_name = "TARGET=MODE")]
+    approve: Vec<String>,
+
+    /// OS confinement rule for tool subprocesses as `target=level`, where target
+    /// is a safety class or a tool name and level is none, workspace (writes
+    /// only in the working directory and temp) or offline (also no network)
+    /// (repeatable)
+    #[arg(long = "confine", value_name = "TARGET=LEVEL")]
+    confine: Vec<String>,
+
+    /// Resource limit rule for tool subprocesses as `target=limit:value,...`,
+    /// where target is all, a safety class or a tool name and limits are cpu
+    /// (seconds), memory, procs and output (bytes, K/M/G suffixes, or none)
+    /// (repeatable). Default: all=cpu:60s,output:10M
+    #[arg(long = "limit", value_name = "TARGET=LIMITS")]
+    limit: Vec<String>,
+
+    /// Use a named profile (system prompt, tools and guardrails) from the
+    /// config file
+    #[arg(long)]
+    profile: Option<String>,
+
+    /// Config file holding the profiles (default: agent.yaml)
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    /// Custom system prompt file; overrides the profile's prompt
+    #[arg(long, value_name = "FILE")]
+    system_prompt: Option<PathBuf>,
+
+    /// How the custom prompt combines with the built-in instructions: extend
+    /// (add after them) or replace (use instead of them). The generated tool
+    /// and skill sections are kept either way. Default: the profile's, or extend
+    #[arg(long, value_parser = parse_prompt_mode)]
+    prompt_mode: Option<PromptMode>,
+
+    /// Example calls per tool and skill to show the model in the system prompt
+    /// (0 disables them)
+    #[arg(long, default_value_t = DEFAULT_EXAMPLES_PER_ITEM)]
+    examples: usize,
+
+    /// Tokens the examples may take in the system prompt
+    #[arg(long, value_name = "TOKENS", default_value_t = DEFAULT_EXAMPLES_BUDGET)]
+    examples_budget: usize,
+
+    /// Environment facts to state at the end of the system prompt: time
+    /// (local date, time and time zone), os, cwd (the working directory and
+    /// what it holds). Comma-separated; none by default
+    #[arg(long, value_name = "FACTS", value_delimiter = ',')]
+    context: Vec<String>,
+
+    /// Take only replies starting with `FINAL:` (or `{"final": ...}`) as final
+    /// answers, whatever their language; other plain text is retried
+    #[arg(long)]
+    final_sentinel: bool,
+
+    /// Words the final answer may take; longer answers are sent back
+    #[arg(long, value_name = "N")]
+    max_words: Option<usize>,
+
+    /// Require the final answer to be a bullet list
+    #[arg(long)]
+    bullets: bool,
+
+    /// Language of the final answer, by name or code (e.g. German, de)
+    #[arg(long, value_name = "LANG")]
+    language: Option<String>,
+
+    /// Have a critic review each final answer against the transcript before it
+    /// is returned; a requested revision gives the model one more iteration
+    #[arg(long)]
+    critic: bool,
+
+    /// Backend of the critic (default: the run's backend; llama-cpp loads the
+    /// model a second time)
+    #[arg(long, value_enum, requires = "critic")]
+    critic_backend: Option<BackendKind>,
+
+    /// Model id of the critic on server and hosted API backends
+    #[arg(long, requires = "critic")]
+    critic_model_id: Option<String>,
+
+    /// Delimiters of the model's reasoning blocks, taken out of its replies:
+    /// think (`<think>`, the default), thinking, magistral, or `OPEN,CLOSE`
+    #[arg(long, value_name = "TAGS", value_parser = parse_reasoning_tags)]
+    reasoning_tags: Option<ReasoningDelimiters>,
+
+    /// Leave reasoning blocks in the model's replies
+    #[arg(long, conflicts_with = "reasoning_tags")]
+    keep_reasoning: bool,
+
+    /// Refuse tools above this safety class without prompting
+    #[arg(long, value_parser = parse_safety_class)]
+    max_safety: Option<SafetyClass>,
+
+    /// Take misspelled tool names within this edit distance as the tool they
+    /// spell (aliases like `bash` for `shell` are always taken)
+    #[arg(long, value_name = "DISTANCE")]
+    fuzzy_tools: Option<usize>,
+
+    /// Offer only these tools (comma-separated), within the profile's
+    #[arg(
+        long,
+        value_name = "NAMES",
+        value_delimiter = ',',
+        overrides_with = "tools"
+    )]
+    tools: Option<Vec<String>>,
+
+    /// Run in a temporary copy of the working directory and apply the changes
+    /// only if approved when the run ends
+    #[arg(long)]
+    sandbox: bool,
+
+    /// Ask the model for the calls it would make and review them against the
+    /// tool manifests, guardrails and approval policy, running nothing
+    #[arg(long, conflicts_with_all = ["sandbox", "record", "replay"])]
+    dry_run: bool,
+
+    /// Record the run (query, model calls, tool calls and results) as a
+    /// replayable scenario in this directory
+    #[arg(long, value_name = "DIR", conflicts_with = "replay")]
+    record: Option<PathBuf>,
+
+    /// Replay a recorded scenario: tool calls get the recorded results instead
+    /// of running, and the query defaults to the recorded one
+    #[arg(long, value_name = "DIR")]
+    replay: Option<PathBuf>,
+
+    /// Export the run's spans and metrics to this OTLP/HTTP collector (e.g.
+    /// http://localhost:4318); content is redacted to lengths
+    #[arg(long, value_name = "URL")]
+    otlp_endpoint: Option<String>,
+
+    /// Record queries, prompts, answers and tool input and output in exported
+    /// spans instead of only their lengths
+    #[arg(long, requires = "otlp_endpoint")]
+    otlp_include_content: bool,
+
+    /// Inject faults to test recovery: `default` (each fault at 10%) or
+    /// probabilities like `timeout=0.05,malformed=0.3,empty=0.2,truncate=0.1`
+    #[arg(long, value_name = "PROFILE", num_args = 0..=1, default_missing_value = "default",
+          value_parser = parse_chaos_profile)]
+    chaos: Option<String>,
+
+    /// Seed of the injected faults, to repeat a chaos run (default: random)
+    #[arg(long, value_name = "N", requires = "chaos")]
+    chaos_seed: Option<u64>,
+
+    /// Output format: human-readable text, or one JSON event per line with
+    /// approvals read from stdin as `{"id": N, "approved": true|false}`
+    #[arg(long, value_enum, default_value_t = OutputMode::Text, global = true)]
+    output: OutputMode,
+
+    /// Only print results, errors and approval prompts
+    #[arg(short = 's', long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Show agent loop details (-v) or raw prompts and model output (-vv)
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    verbose: u8,
+
+    /// Print the full prompt of every model call (system prompt, history and
+    /// turn instructions) with its estimated token count before sending it
+    #[arg(long, global = true)]
+    show_prompt: bool,
+
+    /// Run the agent loop on the async runtime (requires the `async` feature)
+    #[cfg(feature = "async")]
+    #[arg(long, conflicts_with = "kv_state")]
+    async_runtime: bool,
+
+    /// The task file `agent run` loaded
+    #[arg(skip)]
+    task: Option<TaskFile>,
 }
 
 #[derive(Subcommand, Debug)]
 enum CliCommand {
     /// Invoke the extraction skill directly (bypasses agent loop)
-    Extract {
-        /// Text to extract from
-        #[arg(long)]
-        text: String,
-        /// Target to extract (email, url, date, entity)
-        #[arg(short, long, value_parser = parse_target)]
-        target: ExtractionTarget,
-        /// Path to the GGUF model file
-        #[arg(short, long)]
-        model: Option<PathBuf>,
-        /// Number of tokens to generate
-        #[arg(short = 'n', long, default_value = "256")]
-        max_tokens: usize,
-    },
+    Extract(ExtractArgs),
     /// Invoke a specific skill explicitly (extensible for future skills)
     Skill {
         #[command(subcommand)]
         command: SkillCommand,
     },
+    /// Inspect and lint skill manifests (SKILL.md)
+    Skills {
+        #[command(subcommand)]
+        command: SkillsCommand,
+    },
+    /// Inspect host tools declared by TOOL.md manifests
+    Tools {
+        #[command(subcommand)]
+        command: ToolsCommand,
+    },
+    /// Run an evaluation suite on one or more backends/models and compare them
+    Eval {
+        /// Suite file (YAML)
+        suite: PathBuf,
+        /// Runs to execute at once (default: one per target)
+        #[arg(long)]
+        jobs: Option<usize>,
+        /// Only run this target (repeatable)
+        #[arg(long = "target")]
+        targets: Vec<String>,
+        /// Write summaries and per-run results as JSON
+        #[arg(long)]
+        json: Option<PathBuf>,
+        /// Write per-run results as CSV
+        #[arg(long)]
+        csv: Option<PathBuf>,
+    },
+    /// Run a JSONL file of independent queries, one result record per task
+    Batch {
+        /// Task file (JSONL: `{"id": ..., "query": ..., "args": [...]}` per line)
+        tasks: PathBuf,
+        /// Results file (default: `<tasks>.results.jsonl`)
+        #[arg(long)]
+        results: Option<PathBuf>,
+        /// Directory for task transcripts (default: `<tasks>.transcripts/`)
+        #[arg(long)]
+        transcripts: Option<PathBuf>,
+        /// Tasks to run at once
+        #[arg(long, default_value_t = 1)]
+        jobs: usize,
+        /// Time limit for one task, in seconds
+        #[arg(long, default_value_t = DEFAULT_CASE_TIMEOUT_SECS)]
+        timeout: u64,
+        /// Skip tasks the results file already records as completed, and
+        /// append to it
+        #[arg(long)]
+        resume: bool,
+        /// Approve tool calls that would prompt (they are declined otherwise)
+        #[arg(long)]
+        yes: bool,
+        /// POST notifications as JSON to this URL (repeatable)
+        #[arg(long = "notify-url", value_name = "URL")]
+        notify_urls: Vec<String>,
+        /// Run this shell command with each notification as JSON on stdin
+        /// (repeatable)
+        #[arg(long = "notify-command", value_name = "COMMAND")]
+        notify_commands: Vec<String>,
+        /// Notify when the batch finishes, or on each failed task and a
+        /// batch with failures
+        #[arg(long, value_name = "WHEN", default_value = "finish", value_parser = parse_notify_on)]
+        notify_on: NotifyOn,
+        /// Most iterations a task may ask for. Setting any task bound refuses
+        /// tasks' raw `args`
+        #[arg(long, value_name = "N")]
+        max_task_iterations: Option<usize>,
+        /// Most tokens per model call a task may ask for
+        #[arg(long, value_name = "N")]
+        max_task_tokens: Option<usize>,
+        /// Tools tasks may use (comma-separated); tasks that name none get these
+        #[arg(long, value_name = "NAMES", value_delimiter = ',')]
+        task_tools: Option<Vec<String>>,
+        /// Safety class or tool tasks may approve with `auto` (repeatable)
+        #[arg(long = "task-auto-approve", value_name = "TARGET")]
+        task_auto_approve: Vec<String>,
+        /// Agent arguments for every task (model, `--approve` rules...), after `--`
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
+    /// Run task files on cron schedules, until interrupted
+    Schedule {
+        /// Schedule file (YAML: jobs with a name, cron expression and task file)
+        schedule: PathBuf,
+        /// Directory for the jobs' run histories (default: `<schedule>.history/`)
+        #[arg(long)]
+        history: Option<PathBuf>,
+        /// Approve tool calls that would prompt (they are declined otherwise)
+        #[arg(long)]
+        yes: bool,
+        /// Agent arguments for every job (model, `--approve` rules...), after `--`
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
+    /// Run one query several times across temperatures and seeds and report
+    /// how often the runs agree on tools and answer
+    Sweep {
+        /// The query to run
+        #[arg(long)]
+        query: String,
+        /// Runs in total, cycling through the temperatures (seeds count up
+        /// from `--seed`)
+        #[arg(long, default_value_t = DEFAULT_SWEEP_RUNS)]
+        runs: usize,
+        /// Temperatures to run at (comma-separated)
+        #[arg(long, value_delimiter = ',', default_value = "0.2,0.7,1.0")]
+        temperatures: Vec<f32>,
+        /// Runs to execute at once
+        #[arg(long, default_value_t = 1)]
+        jobs: usize,
+        /// Write the report and per-run results as JSON
+        #[arg(long)]
+        json: Option<PathBuf>,
+        /// Agent arguments for every run (model, tools, `--sandbox`...), after `--`
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
+    /// Show a run's audit log after verifying it, or list recorded runs
+    Audit {
+        /// Run id (omit to list runs)
+        run_id: Option<String>,
+        /// Directory the runs were recorded in
+        #[arg(long, default_value = DEFAULT_ARTIFACTS_DIR)]
+        artifacts_dir: PathBuf,
+    },
+    /// Embed texts with the backend and print one JSON array per text
+    Embed {
+        /// Text to embed (repeatable); without it, each line of stdin is a text
+        #[arg(long)]
+        text: Vec<String>,
+        /// Path to the GGUF model file (llama-cpp backend)
+        #[arg(short, long)]
+        model: Option<PathBuf>,
+    },
+    /// Load the model and generate one token, so the next run starts warm
+    /// (model file in the page cache, Metal shaders compiled)
+    Warmup {
+        /// Path to the GGUF model file (llama-cpp backend)
+        #[arg(short, long)]
+        model: Option<PathBuf>,
+    },
+    /// Check the model, backend, config, skills, tools and required commands,
+    /// and say how to fix what is wrong
+    Doctor {
+        /// Path to the GGUF model file (llama-cpp backend)
+        #[arg(short, long)]
+        model: Option<PathBuf>,
+    },
+    /// Run the job a task file describes (query template, tools,
+    /// guardrails, iteration limit and answer format)
+    Run {
+        /// Task file (YAML)
+        task: PathBuf,
+        /// Value of a query variable (repeatable)
+        #[arg(long = "var", value_name = "NAME=VALUE", value_parser = parse_var)]
+        vars: Vec<(String, String)>,
+    },
+    /// Put back the files a run's tools changed (best-effort: files changed
+    /// since are left alone)
+    Undo {
+        /// Run id
+        run_id: String,
+        /// Directory the run was recorded in
+        #[arg(long, default_value = DEFAULT_ARTIFACTS_DIR)]
+        artifacts_dir: PathBuf,
+        /// Only list what would be restored or removed
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ToolsCommand {
+    /// List tools available to the agent with their safety class and source
+    List {
+        /// Directory to search for tools (repeatable)
+        #[arg(long = "dir", default_value = DEFAULT_TOOLS_DIR)]
+        dirs: Vec<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum SkillsCommand {
+    /// List discovered skills with version and source directory
+    List {
+        /// Directory to search for skills (repeatable)
+        #[arg(long = "dir", default_value = DEFAULT_SKILLS_DIR)]
+        dirs: Vec<PathBuf>,
+    },
+    /// Show a skill's manifest, template variables and schemas
+    Show {
+        /// Skill name as declared in its frontmatter
+        name: String,
+        /// Directory to search for skills (repeatable)
+        #[arg(long = "dir", default_value = DEFAULT_SKILLS_DIR)]
+        dirs: Vec<PathBuf>,
+    },
+    /// Lint a SKILL.md file or skill directory
+    Validate {
+        /// Path to SKILL.md or the directory containing it
+        path: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum SkillCommand {
+    /// Extract structured data from text
+    Extract(ExtractArgs),
+}
+
+#[derive(Args, Debug)]
+struct ExtractArgs {
+    /// Text to extract from; `-` or omitted reads it from stdin
+    #[arg(long)]
+    text: Option<String>,
+    /// Read the text from a file
+    #[arg(long, value_name = "PATH", conflicts_with = "text")]
+    text_file: Option<PathBuf>,
+    /// Target to extract (email, url, date, entity, name, fields)
+    #[arg(short, long, value_parser = parse_target)]
+    target: ExtractionTarget,
+    /// Entity classes to return with `--target entity` (people, organizations, locations)
+    #[arg(long, value_delimiter = ',')]
+    classes: Option<Vec<String>>,
+    /// Field to extract with `--target fields`, as `name=description` (repeatable)
+    #[arg(long = "field", value_parser = parse_field)]
+    fields: Vec<(String, String)>,
+    /// Path to the GGUF model file (llama-cpp backend)
+    #[arg(short, long)]
+    model: Option<PathBuf>,
+    /// Number of tokens to generate
+    #[arg(short = 'n', long, default_value = "256")]
+    max_tokens: usize,
+    /// Split texts longer than this many characters into chunks
+    #[arg(long, default_value_t = DEFAULT_CHUNK_SIZE)]
+    chunk_size: usize,
+    /// Characters shared between consecutive chunks
+    #[arg(long, default_value_t = DEFAULT_CHUNK_OVERLAP)]
+    chunk_overlap: usize,
+    /// Output format: json, csv, table or plain (one value per line, TSV for
+    /// entities and fields)
+    #[arg(long, value_parser = parse_format, default_value = "json")]
+    format: ExtractFormat,
+    /// Read the text as JSONL records (`{"id": .., "text": ..}` or a JSON
+    /// string per line), extract from each over one loaded model and print
+    /// one JSON result per line
+    #[arg(long)]
+    records: bool,
+}
+
+impl ExtractArgs {
+    fn input(&self) -> Input<'_> {
+        match (&self.text, &self.text_file) {
+            (Some(text), _) => Input::value(text),
+            (None, Some(path)) => Input::File(path),
+            (None, None) => Input::Stdin,
+        }
+    }
+
+    fn chunking(&self) -> Chunking {
+        Chunking {
+            size: self.chunk_size,
+            overlap: self.chunk_overlap,
+        }
+    }
+}
+
+/// Where a one-off reply (such as an extraction) ends
+#[derive(Debug, Clone, Copy)]
+struct ReplyBounds<'a> {
+    max_tokens: usize,
+    stop: &'a [String],
+}
+
+/// Chunking parameters for long extraction inputs
+#[derive(Debug, Clone, Copy)]
+struct Chunking {
+    size: usize,
+    overlap: usize,
+}
+
+impl Default for Chunking {
+    fn default() -> Self {
+        Self {
+            size: DEFAULT_CHUNK_SIZE,
+            overlap: DEFAULT_CHUNK_OVERLAP,
+        }
+    }
+}
+
+/// Wire format of a conversation to continue
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ConversationFormat {
+    /// OpenAI chat completions `messages`
+    Openai,
+    /// Anthropic Messages API `messages` (with `system` in a request body)
+    Anthropic,
+}
+
+/// Inference engine serving the model
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum BackendKind {
+    /// Load the GGUF model in-process with llama.cpp
+    LlamaCpp,
+    /// Attach to llama.cpp's `llama-server` (`/completion`)
+    LlamaServer,
+    /// Attach to a mistral.rs server (`/v1/completions`)
+    Mistralrs,
+    /// Anthropic Messages API with native tool use (`ANTHROPIC_API_KEY`)
+    Anthropic,
+    /// Google Gemini API with native function calling (`GEMINI_API_KEY`)
+    Gemini,
+}
+
+impl BackendKind {
+    /// Whether the backend sends tool images to the model (see
+    /// [`LLMInput::images`])
+    fn supports_images(self) -> bool {
+        matches!(self, Self::Anthropic | Self::Gemini)
+    }
+}
+
+/// Where inference runs
+#[derive(Debug, Clone)]
+struct BackendConfig {
+    kind: BackendKind,
+    /// GGUF model file (llama-cpp backend)
+    model: PathBuf,
+    /// Server URL (server backends); falls back to the server's default address
+    endpoint: Option<String>,
+    /// Model id (server and hosted API backends)
+    model_id: Option<String>,
+    /// Client-side limits (server and hosted API backends)
+    rate_limits: RateLimits,
+    /// Temperature and seed, passed to every backend
+    sampling: Sampling,
+}
+
+impl BackendConfig {
+    fn endpoint(&self) -> &str {
+        match (&self.endpoint, self.kind) {
+            (Some(endpoint), _) => endpoint,
+            (None, BackendKind::Mistralrs) => mistralrs_backend::DEFAULT_ENDPOINT,
+            (None, BackendKind::Anthropic) => anthropic_backend::DEFAULT_ENDPOINT,
+            (None, BackendKind::Gemini) => gemini_backend::DEFAULT_ENDPOINT,
+            (None, _) => llama_server_backend::DEFAULT_ENDPOINT,
+        }
+    }
+
+    fn model_id(&self) -> &str {
+        match (&self.model_id, self.kind) {
+            (Some(model_id), _) => model_id,
+            (None, BackendKind::Anthropic) => anthropic_backend::DEFAULT_MODEL_ID,
+            (None, BackendKind::Gemini) => gemini_backend::DEFAULT_MODEL_ID,
+            (None, _) => mistralrs_backend::DEFAULT_MODEL_ID,
+        }
+    }
+
+    /// Human-readable description of the model source
+    fn describe(&self) -> String {
+        match self.kind {
+            BackendKind::LlamaCpp => self.model.display().to_string(),
+            BackendKind::LlamaServer => format!("llama-server at {}", self.endpoint()),
+            BackendKind::Mistralrs => format!("mistral.rs server at {}", self.endpoint()),
+            BackendKind::Anthropic => format!("Anthropic API ({})", self.model_id()),
+            BackendKind::Gemini => format!("Gemini API ({})", self.model_id()),
+        }
+    }
+}
+
+/// Read a hosted API key from the environment
+fn api_key(var: &str) -> Result<String> {
+    std::env::var(var).with_context(|| format!("{} is not set", var))
+}
+
+#[derive(Debug)]
+struct AgentArgs {
+    run_id: RunId,
+    backend: BackendConfig,
+    query: String,
+    /// Conversation the run continues (`--conversation`)
+    conversation: Option<AgentState>,
+    /// Where the backend context is kept between runs (`--kv-state`)
+    kv_state: Option<PathBuf>,
+    /// Memory file of earlier runs (`--memory`)
+    memory: Option<PathBuf>,
+    max_iterations: usize,
+    max_tokens: usize,
+    artifacts_dir: PathBuf,
+    context_tokens: usize,
+    history_budget: HistoryBudget,
+    observations: ObservationPolicy,
+    /// Where completion backends stop generating, by prompt phase (`--stop`)
+    stop: StopSequences,
+    guardrails: GuardrailSet,
+    answer_protocol: AnswerProtocol,
+    reasoning_delimiters: Option<ReasoningDelimiters>,
+    capabilities: HostCapabilities,
+    /// Shape of the final answer (`--max-words`, `--bullets`, `--language`)
+    response_spec: Option<ResponseSpec>,
+    /// Backend reviewing final answers (`--critic`)
+    critic: Option<BackendConfig>,
+}
+
+impl AgentArgs {
+    /// Bounds of the replies to extraction prompts
+    fn extraction_reply(&self) -> ReplyBounds<'_> {
+        ReplyBounds {
+            max_tokens: self.max_tokens,
+            stop: self.stop.for_phase(PromptPhase::Extraction),
+        }
+    }
+}
+
+/// Tools offered to the model and the checks applied before running them
+struct ToolHost {
+    registry: ToolRegistry,
+    policy: ApprovalPolicy,
+    guards: PreExecutionChain,
+    confinement: ConfinementPolicy,
+    limits: LimitPolicy,
+    /// Recorded tool results served instead of running tools (`--replay`)
+    replay: Option<Scenario>,
+    /// Fault injection (`--chaos`)
+    chaos: Option<SharedChaos>,
+    /// Pre-images of the files tools change
+    undo: Arc<UndoJournal>,
+}
+
+impl ToolHost {
+    /// How tightly the OS confines the processes `tool` starts
+    fn confinement(&self, tool: &str) -> ConfinementLevel {
+        self.confinement.decide(tool, self.safety(tool))
+    }
+
+    /// Resource limits for the processes `tool` starts
+    fn limits(&self, tool: &str) -> ResourceLimits {
+        self.limits.decide(tool, self.safety(tool))
+    }
+
+    /// Report the files the last call changed, so they can be undone
+    fn report_changes(&self, events: &EventSink, request: &ToolRequest) {
+        let files = self.undo.take();
+        if !files.is_empty() {
+            events.emit(AgentEvent::FilesChanged {
+                tool: request.tool.clone(),
+                files,
+            });
+        }
+    }
+
+    /// `result`, possibly emptied or truncated by `--chaos`
+    fn perturbed(&self, result: ToolResult) -> ToolResult {
+        match &self.chaos {
+            Some(chaos) => chaos.lock().unwrap().tool_result(result),
+            None => result,
+        }
+    }
+
+    /// `request` with its parameters mapped onto the declared ones
+    fn coerced(&self, request: &ToolRequest) -> ToolRequest {
+        let mut request = request.clone();
+        for change in self.registry.coerce_request(&mut request) {
+            console::verbose(format!("Tool '{}': {}", request.tool, change));
+        }
+        request
+    }
+
+    /// The recorded result of `request` when replaying a scenario
+    fn replayed(&self, request: &ToolRequest) -> Option<ToolResult> {
+        self.replay.as_ref().map(|scenario| {
+            scenario
+                .result_for(request)
+                .cloned()
+                .unwrap_or_else(|| Scenario::unrecorded(request))
+        })
+    }
+
+    fn safety(&self, tool: &str) -> SafetyClass {
+        self.registry
+            .get(tool)
+            .map_or(SafetyClass::Destructive, |manifest| {
+                manifest.frontmatter.safety
+            })
+    }
+}
+
+fn parse_prompt_mode(value: &str) -> Result<PromptMode, String> {
+    PromptMode::from_str(value).ok_or_else(|| {
+        format!(
+            "Invalid prompt mode '{}'. Expected one of: extend, replace",
+            value
+        )
+    })
+}
+
+/// The state continuing a `--conversation` file
+fn read_conversation(
+    path: &Path,
+    format: ConversationFormat,
+    max_bytes: u64,
+) -> Result<AgentState> {
+    let json = Input::File(path).read("conversation", max_bytes)?;
+    let state = match format {
+        ConversationFormat::Openai => AgentState::from_openai_messages(&json),
+        ConversationFormat::Anthropic => AgentState::from_anthropic_messages(&json),
+    };
+    state.with_context(|| format!("Cannot continue the conversation in {}", path.display()))
+}
+
+/// The query a continued conversation asks: its latest user message
+fn conversation_query(state: &AgentState) -> String {
+    state
+        .history
+        .iter()
+        .rev()
+        .find(|message| message.role == Role::User)
+        .map(|message| message.content.clone())
+        .unwrap_or_default()
+}
+
+/// The final answer's shape from `--max-words`, `--bullets` and `--language`
+fn response_spec(cli: &Cli) -> ResponseSpec {
+    let mut spec = ResponseSpec::new();
+    if let Some(words) = cli.max_words {
+        spec = spec.with_max_words(words);
+    }
+    if cli.bullets {
+        spec = spec.with_bullets();
+    }
+    if let Some(language) = &cli.language {
+        spec = spec.with_language(language.clone());
+    }
+    spec
+}
+
+/// How much of the history prompts show, from the command line
+fn history_budget(cli: &Cli) -> HistoryBudget {
+    let mut budget = HistoryBudget::new().with_older_turn_tokens(cli.older_turn_tokens);
+    if let Some(tokens) = cli.tool_output_tokens {
+        budget = budget.with_tool_output_tokens(tokens);
+    }
+    if let Some(turns) = cli.full_turns {
+        budget = budget.with_full_assistant_turns(turns);
+    }
+    if cli.prune_corrections {
+        budget = budget.with_pruned_corrections();
+    }
+    budget
+}
+
+fn parse_observation_mode(value: &str) -> Result<ObservationMode, String> {
+    ObservationMode::from_str(value).ok_or_else(|| {
+        format!(
+            "Invalid observation mode '{}'. Expected one of: raw, model, local",
+            value
+        )
+    })
+}
+
+fn parse_reasoning_tags(value: &str) -> Result<ReasoningDelimiters, String> {
+    ReasoningDelimiters::from_str(value).ok_or_else(|| {
+        format!(
+            "Invalid reasoning tags '{}'. Expected one of: think, thinking, magistral, or OPEN,CLOSE",
+            value
+        )
+    })
+}
+
+fn parse_safety_class(value: &str) -> Result<SafetyClass, String> {
+    SafetyClass::from_str(value).ok_or_else(|| {
+        format!(
+            "Invalid safety class '{}'. Expected one of: read-only, network, mutating, destructive",
+            value
+        )
+    })
+}
+
+fn parse_notify_on(value: &str) -> Result<NotifyOn, String> {
+    NotifyOn::from_str(value).ok_or_else(|| {
+        format!(
+            "Invalid notification policy '{}'. Expected one of: finish, failure",
+            value
+        )
+    })
+}
+
+fn parse_target(value: &str) -> Result<ExtractionTarget, String> {
+    ExtractionTarget::from_str(value).ok_or_else(|| {
+        format!(
+            "Invalid target '{}'. Expected one of: email, url, date, entity, name, fields",
+            value
+        )
+    })
+}
+
+fn parse_format(value: &str) -> Result<ExtractFormat, String> {
+    ExtractFormat::from_str(value).ok_or_else(|| {
+        format!(
+            "Invalid format '{}'. Expected one of: json, csv, table, plain",
+            value
+        )
+    })
+}
+
+fn parse_field(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((name, desc)) if !name.trim().is_empty() => {
+            Ok((name.trim().to_string(), desc.trim().to_string()))
+        }
+        _ => Err(format!(
+            "Invalid field '{}'. Expected name=description",
+            value
+        )),
+    }
+}
+
+fn parse_var(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((name, value)) if !name.trim().is_empty() => {
+            Ok((name.trim().to_string(), value.to_string()))
+        }
+        _ => Err(format!("Invalid variable '{}'. Expected name=value", value)),
+    }
+}
+
+fn parse_chaos_profile(value: &str) -> Result<String, String> {
+    ChaosProfile::parse(value, 0).map(|_| value.to_string())
+}
+
+fn parse_input_size(value: &str) -> Result<u64, String> {
+    parse_size(value).ok_or_else(|| {
+        format!(
+            "Invalid size '{}'. Expected bytes, optionally with a K, M or G suffix",
+            value
+        )
+    })
+}
+
+fn backend_config(cli: &Cli, model: Option<&PathBuf>) -> BackendConfig {
+    BackendConfig {
+        kind: cli.backend,
+        model: model
+            .cloned()
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_MODEL_PATH)),
+        endpoint: cli.endpoint.clone(),
+        model_id: cli.model_id.clone(),
+        rate_limits: RateLimits::default()
+            .with_requests_per_minute(cli.requests_per_minute)
+            .with_tokens_per_minute(cli.tokens_per_minute)
+            .with_max_concurrent(cli.max_concurrent)
+            .with_max_retries(cli.max_retries),
+        sampling: Sampling {
+            temperature: cli.temperature,
+            seed: cli.seed,
+        },
+    }
+}
+
+/// The critic's backend: the run's, with the `--critic-*` overrides
+fn critic_config(cli: &Cli, backend: &BackendConfig) -> BackendConfig {
+    let mut config = backend.clone();
+    if let Some(kind) = cli.critic_backend.filter(|kind| *kind != backend.kind) {
+        config.kind = kind;
+        config.endpoint = None;
+        config.model_id = None;
+    }
+    if let Some(model_id) = &cli.critic_model_id {
+        config.model_id = Some(model_id.clone());
+    }
+    config
+}
+
+/// The global backend flags, for child runs of `sweep` and `batch`
+fn backend_args(cli: &Cli) -> Vec<String> {
+    let mut args = Vec::new();
+    if let Some(backend) = cli.backend.to_possible_value() {
+        args.extend(["--backend".to_string(), backend.get_name().to_string()]);
+    }
+    let optional = [
+        ("--endpoint", cli.endpoint.clone()),
+        ("--model-id", cli.model_id.clone()),
+        (
+            "--requests-per-minute",
+            cli.requests_per_minute.map(|n| n.to_string()),
+        ),
+        (
+            "--tokens-per-minute",
+            cli.tokens_per_minute.map(|n| n.to_string()),
+        ),
+        (
+            "--max-concurrent",
+            cli.max_concurrent.map(|n| n.to_string()),
+        ),
+        ("--max-retries", Some(cli.max_retries.to_string())),
+    ];
+    for (flag, value) in optional {
+        if let Some(value) = value {
+            args.extend([flag.to_string(), value]);
+        }
+    }
+    args
+}
+
+fn main() {
+    let cli = Cli::try_parse().unwrap_or_else(|e| {
+        let _ = e.print();
+        // Usage errors exit 1, since 2 means a guardrail failure
+        let outcome = match e.use_stderr() {
+            true => RunOutcome::Error,
+            false => RunOutcome::Answered,
+        };
+        std::process::exit(outcome.exit_code())
+    });
+    if let Err(e) = run(cli) {
+        eprintln!("Error: {:?}", e);
+        let outcome = match e.downcast_ref::<BackendError>() {
+            Some(_) => RunOutcome::BackendError,
+            None => RunOutcome::Error,
+        };
+        std::process::exit(outcome.exit_code());
+    }
+}
+
+fn run(cli: Cli) -> Result<()> {
+    let mut verbosity = Verbosity::from_flags(cli.quiet, cli.verbose);
+    // CSV and plain extraction output feeds pipelines: keep progress off stdout
+    if let Some(CliCommand::Extract(args))
+    | Some(CliCommand::Skill {
+        command: SkillCommand::Extract(args),
+    }) = &cli.command
+    {
+        if matches!(args.format, ExtractFormat::Csv | ExtractFormat::Plain) && cli.verbose == 0 {
+            verbosity = Verbosity::Quiet;
+        }
+    }
+    console::init(verbosity, cli.output == OutputMode::Text);
+    let cli = match &cli.command {
+        Some(CliCommand::Run { .. }) => task::prepare(cli)?,
+        _ => cli,
+    };
+
+    match &cli.command {
+        Some(CliCommand::Extract(args))
+        | Some(CliCommand::Skill {
+            command: SkillCommand::Extract(args),
+        }) => run_extract_mode(
+            args,
+            cli.max_input_bytes,
+            &backend_config(&cli, args.model.as_ref()),
+            &EventSink::new(cli.output)
+                .with_prompts(cli.show_prompt)
+                .with_text_output(!args.records),
+        ),
+        Some(CliCommand::Skills { command }) => match command {
+            SkillsCommand::List { dirs } => skill_commands::list_skills(dirs),
+            SkillsCommand::Show { name, dirs } => skill_commands::show_skill(dirs, name),
+            SkillsCommand::Validate { path } => {
+                if !skill_commands::validate_skill(path)? {
+                    std::process::exit(1);
+                }
+                Ok(())
+            }
+        },
+        Some(CliCommand::Tools { command }) => match command {
+            ToolsCommand::List { dirs } => tool_commands::list_tools(dirs),
+        },
+        Some(CliCommand::Eval {
+            suite,
+            jobs,
+            targets,
+            json,
+            csv,
+        }) => {
+            let options = eval::EvalOptions {
+                jobs: *jobs,
+                targets: targets.clone(),
+                json: json.clone(),
+                csv: csv.clone(),
+            };
+            if !eval::run_suite(suite, &options)? {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Some(CliCommand::Batch {
+            tasks,
+            results,
+            transcripts,
+            jobs,
+            timeout,
+            resume,
+            yes,
+            notify_urls,
+            notify_commands,
+            notify_on,
+            max_task_iterations,
+            max_task_tokens,
+            task_tools,
+            task_auto_approve,
+            args,
+        }) => {
+            let options = batch::BatchOptions {
+                results: results.clone(),
+                transcripts: transcripts.clone(),
+                jobs: *jobs,
+                timeout_secs: *timeout,
+                resume: *resume,
+                yes: *yes,
+                notifier: Notifier::new(notify_urls.clone(), notify_commands.clone(), *notify_on),
+                bounds: OverrideBounds {
+                    max_iterations: *max_task_iterations,
+                    max_tokens: *max_task_tokens,
+                    tools: task_tools.clone(),
+                    auto_approve: task_auto_approve.clone(),
+                },
+                args: args.clone(),
+            };
+            if !batch::run(&cli, tasks, &options)? {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Some(CliCommand::Schedule {
+            schedule,
+            history,
+            yes,
+            args,
+        }) => schedule::run(
+            &cli,
+            schedule,
+            &schedule::ScheduleOptions {
+                history: history.clone(),
+                yes: *yes,
+                args: args.clone(),
+            },
+        ),
+        Some(CliCommand::Sweep {
+            query,
+            runs,
+            temperatures,
+            jobs,
+            json,
+            args,
+        }) => sweep::run(
+            &cli,
+            &sweep::SweepOptions {
+                query: query.clone(),
+                runs: *runs,
+                temperatures: temperatures.clone(),
+                jobs: *jobs,
+                args: args.clone(),
+                json: json.clone(),
+            },
+        ),
+        Some(CliCommand::Audit {
+            run_id,
+            artifacts_dir,
+        }) => match run_id {
+            Some(run_id) => audit::show_run(artifacts_dir, run_id),
+            None => audit::list_runs(artifacts_dir),
+        },
+        Some(CliCommand::Embed { text, model }) => embeddings::run(
+            &backend_config(&cli, model.as_ref().or(cli.model.as_ref())),
+            text,
+            cli.max_input_bytes,
+        ),
+        Some(CliCommand::Warmup { model }) => {
+            warmup::run(&backend_config(&cli, model.as_ref().or(cli.model.as_ref())))
+        }
+        Some(CliCommand::Doctor { model }) => {
+            let backend = backend_config(&cli, model.as_ref().or(cli.model.as_ref()));
+            if !doctor::run(&backend, cli.config.as_deref(), cli.profile.as_deref())? {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Some(CliCommand::Undo {
+            run_id,
+            artifacts_dir,
+            dry_run,
+        }) => undo::undo_run(artifacts_dir, run_id, *dry_run),
+        // `task::prepare` filled in the query and limits of a task run
+        None | Some(CliCommand::Run { .. }) => {
+            let backend = backend_config(&cli, cli.model.as_ref());
+            let replay = cli.replay.as_deref().map(scenario::load).transpose()?;
+            let chaos = cli
+                .chaos
+                .as_deref()
+                .map(|spec| {
+                    let seed = cli
+                        .chaos_seed
+                        .unwrap_or_else(|| uuid::Uuid::new_v4().as_u64_pair().0);
+                    ChaosProfile::parse(spec, seed)
+                        .map(|profile| Arc::new(Mutex::new(ChaosInjector::new(profile))))
+                })
+                .transpose()
+                .map_err(|e| anyhow::anyhow!(e))?;
+            let query = match (&cli.query, &cli.query_file) {
+                (Some(query), _) => Some(Input::value(query)),
+                (None, Some(path)) => Some(Input::File(path)),
+                (None, None) => None,
+            };
+            let conversation = cli
+                .conversation
+                .as_deref()
+                .map(|path| read_conversation(path, cli.conversation_format, cli.max_input_bytes))
+                .transpose()?;
+            let query = match (query, &cli.audio) {
+                (Some(input), _) => input.read("query", cli.max_input_bytes)?.trim().to_string(),
+                (None, Some(audio)) => {
+                    let query = transcribe::transcribe(
+                        audio,
+                        &TranscribeConfig {
+                            transcriber: cli.transcriber,
+                            endpoint: cli.transcription_endpoint.clone(),
+                            model: cli.transcription_model.clone(),
+                            language: cli.audio_language.clone(),
+                        },
+                    )?;
+                    console::verbose(format!("Transcribed query: {}", query));
+                    query
+                }
+                (None, None) => conversation
+                    .as_ref()
+                    .map(conversation_query)
+                    .or_else(|| replay.as_ref().map(|scenario| scenario.query.clone()))
+                    .ok_or_else(|| anyhow::anyhow!("Missing required --query argument"))?,
+            };
+
+            let (profile, profile_prompt) = match &cli.profile {
+                Some(name) => config::load_profile(cli.config.as_deref(), name)?,
+                None => (Profile::default(), None),
+            };
+            let profile = match &cli.task {
+                Some(task) => task.apply_to(profile),
+                None => profile,
+            };
+
+            let mut args = AgentArgs {
+                run_id: RunId::new(),
+                critic: cli.critic.then(|| critic_config(&cli, &backend)),
+                backend,
+                query,
+                conversation,
+                kv_state: cli.kv_state.clone(),
+                memory: cli.memory.clone(),
+                max_iterations: cli.max_iterations,
+                max_tokens: cli.max_tokens,
+                artifacts_dir: cli.artifacts_dir.clone(),
+                context_tokens: cli.context_tokens,
+                history_budget: history_budget(&cli),
+                observations: ObservationPolicy::new(cli.observations)
+                    .with_min_tokens(cli.observation_min_tokens)
+                    .with_summary_tokens(cli.summary_tokens),
+                stop: cli
+                    .stop
+                    .iter()
+                    .try_fold(StopSequences::default(), |stops, rule| {
+                        stops.with_rule(rule)
+                    })
+                    .map_err(|e| anyhow::anyhow!(e))?,
+                guardrails: profile.guardrails(),
+                answer_protocol: if cli.final_sentinel {
+                    AnswerProtocol::Sentinel
+                } else {
+                    AnswerProtocol::Heuristic
+                },
+                reasoning_delimiters: (!cli.keep_reasoning)
+                    .then(|| cli.reasoning_tags.clone().unwrap_or_default()),
+                capabilities: HostCapabilities::default(),
+                response_spec: Some(response_spec(&cli)),
+            };
+
+            let discovered_skills = discover_skills(&[PathBuf::from(DEFAULT_SKILLS_DIR)]);
+            let available_skills_prompt = build_available_skills_prompt(&discovered_skills);
+            let mut registry =
+                build_tool_registry(&discover_tools(&[PathBuf::from(DEFAULT_TOOLS_DIR)]))
+                    .with_aliases(ToolAliases::new().with_max_distance(cli.fuzzy_tools));
+            if !args.backend.kind.supports_images() {
+                // The model would only see placeholders
+                let names: Vec<String> = registry
+                    .tools()
+                    .map(|tool| tool.frontmatter.name.clone())
+                    .filter(|name| name != image_tool::TOOL_NAME)
+                    .collect();
+                registry.retain(&names)?;
+            }
+            if let Some(names) = &profile.tools {
+                registry.retain(names).with_context(|| match &cli.task {
+                    Some(task) => format!("Task '{}'", task.name),
+                    None => format!("Profile '{}'", cli.profile.as_deref().unwrap_or_default()),
+                })?;
+            }
+            if let Some(names) = &cli.tools {
+                registry.retain(names).context("--tools")?;
+            }
+            let custom_prompt = match &cli.system_prompt {
+                Some(path) => Some(config::read_prompt(path)?),
+                None => profile_prompt,
+            };
+            let prompt_mode = cli.prompt_mode.unwrap_or(profile.prompt_mode);
+            let mut example_items = registry.examples();
+            example_items.extend(
+                discovered_skills
+                    .iter()
+                    .map(|skill| skill.manifest.frontmatter.examples.as_slice()),
+            );
+            let examples_prompt = FewShotPolicy::default()
+                .with_per_item(cli.examples)
+                .with_budget(cli.examples_budget)
+                .build_prompt(&example_items);
+            let system_prompt = build_system_prompt(
+                &registry.build_prompt(),
+                &examples_prompt,
+                &available_skills_prompt,
+                &environment_prompt(&environment::providers(&cli.context)?),
+                custom_prompt.as_deref().map(|text| (text, prompt_mode)),
+                cli.memory.is_some(),
+            );
+            let skills = load_skills(&discovered_skills);
+            // Calls to tools this host lacks are refused before they reach it
+            args.capabilities = HostCapabilities::new()
+                .with_registry(&registry)
+                .with_memory(cli.memory.is_some());
+
+            let policy = cli
+                .approve
+                .iter()
+                .try_fold(ApprovalPolicy::default(), |policy, rule| {
+                    policy.with_rule(rule)
+                })
+                .map_err(|e| anyhow::anyhow!(e))?;
+            let mut guards = PreExecutionChain::new();
+            if let Some(max) = cli.max_safety.or(profile.max_safety) {
+                guards = guards.add(Box::new(SafetyCeilingGuard::new(max)));
+            }
+            let confinement = cli
+                .confine
+                .iter()
+                .try_fold(ConfinementPolicy::default(), |policy, rule| {
+                    policy.with_rule(rule)
+                })
+                .map_err(|e| anyhow::anyhow!(e))?;
+            confine::report_support(&confinement);
+            let limits = cli
+                .limit
+                .iter()
+                .try_fold(LimitPolicy::default(), |policy, rule| {
+                    policy.with_rule(rule)
+                })
+                .map_err(|e| anyhow::anyhow!(e))?;
+            let tools = ToolHost {
+                registry,
+                policy,
+                guards,
+                confinement,
+                limits,
+                replay,
+                chaos: chaos.clone(),
+                // Absolute, as a sandbox run moves into its copy
+                undo: Arc::new(UndoJournal::new(
+                    &std::path::absolute(&args.artifacts_dir)?,
+                    args.run_id.as_str(),
+                )),
+            };
+
+            let mut events = EventSink::new(cli.output).with_prompts(cli.show_prompt);
+            if let Some(chaos) = &chaos {
+                events = events.with_chaos(Arc::clone(chaos));
+            }
+            if cli.dry_run {
+                return dry_run::run(&args, &system_prompt, &skills, &tools, &events);
+            }
+            if let Some(dir) = &cli.record {
+                events = events.with_recorder(scenario::Recorder::create(dir)?);
+            }
+            if let Some(endpoint) = &cli.otlp_endpoint {
+                let content = if cli.otlp_include_content {
+                    ContentPolicy::Include
+                } else {
+                    ContentPolicy::Redact
+                };
+                events = events.with_telemetry(Telemetry::new(
+                    endpoint,
+                    content,
+                    args.backend.describe(),
+                ));
+            }
+
+            // Tools resolve paths against the working directory, so a sandbox
+            // run moves into the copy; artifacts still go to the real tree
+            let sandbox = if cli.sandbox {
+                let sandbox =
+                    Sandbox::create(Path::new("."), std::slice::from_ref(&cli.artifacts_dir))?;
+                args.artifacts_dir = std::path::absolute(&cli.artifacts_dir)?;
+                args.kv_state = cli
+                    .kv_state
+                    .as_deref()
+                    .map(std::path::absolute)
+                    .transpose()?;
+                args.memory = cli.memory.as_deref().map(std::path::absolute).transpose()?;
+                std::env::set_current_dir(sandbox.root())?;
+                console::verbose(format!("Sandbox: {}", sandbox.root().display()));
+                Some(sandbox)
+            } else {
+                None
+            };
+            let mut events =
+                events.with_audit(AuditLog::create(&args.artifacts_dir, &args.run_id)?);
+
+            #[cfg(feature = "async")]
+            let run = if cli.async_runtime {
+                async_agent::run
+            } else {
+                run_agent
+            };
+            #[cfg(not(feature = "async"))]
+            let run = run_agent;
+            let result = run(args, system_prompt, skills, tools, &mut events);
+            events.report_chaos();
+            result?;
+
+            match sandbox {
+                Some(sandbox) => finish_sandbox(&sandbox, &mut events),
+                None => Ok(()),
+            }
+        }
+    }
+}
+
+fn run_agent(
+    args: AgentArgs,
+    system_prompt: String,
+    skills: LoadedSkills,
+    tools: ToolHost,
+    events: &mut EventSink,
+) -> Result<()> {
+    events.emit(AgentEvent::Started {
+        run_id: args.run_id.to_string(),
+        query: args.query.clone(),
+    });
+
+    // Initialize LLM backend (llama.cpp in this case)
+    let mut llm_backend = chaos::wrap(load_backend(&args.backend)?, tools.chaos.as_ref());
+
+    // Initialize semantic guardrail chain
+    let embedder = embedder(&args)?;
+    let (guardrail_chain, answer_guards) = guard_chains(&args, embedder.as_ref())?;
+    let mut memory = open_memory(&args, embedder)?;
+    let mut artifacts = run_artifacts(&args, &system_prompt);
+
+    let mut session = run_session(&args, &system_prompt, guardrail_chain, answer_guards);
+    if let Some(path) = &args.kv_state {
+        kv_state::resume(path, session.state(), &mut llm_backend)?;
+    }
+    let mut first_generation = true; // Track first decode (Metal shader compilation)
+
+    // Agent loop
+    loop {
+        let prompt = next_prompt(&mut session, events)?;
+        let label = if prompt.retry { "Retrying" } else { "Thinking" };
+        let input = turn_input(
+            &args,
+            &prompt,
+            &session,
+            &artifacts,
+            &tools,
+            first_generation,
+        );
+        let llm_output = generate(&mut llm_backend, input, label, events)?;
+        first_generation = false;
+
+        // Calls are answered first; their results may lead to a retry
+        let action = match session.accept_model_output(llm_output.text)? {
+            StepAction::CallTool { id, request } => {
+                let result = execute_tool(&request, &tools, session.state(), events)?;
+                match review_tool_result(&mut session, events, id, &request, &result)? {
+                    Some(action) => action,
+                    None => {
+                        let summary = summarize_observation(
+                            &args,
+                            &request,
+                            &result,
+                            &mut llm_backend,
+                            events,
+                        )?;
+                        record_tool_result(
+                            &mut session,
+                            id,
+                            result,
+                            summary,
+                            &args.observations,
+                            &mut artifacts,
+                        )?
+                    }
+                }
+            }
+            StepAction::CallSkill { id, request } => {
+                let result = execute_skill(
+                    &request,
+                    &mut llm_backend,
+                    args.extraction_reply(),
+                    &skills,
+                    events,
+                )?;
+                events.emit(skill_result_event(&request, &result));
+                session.accept_skill_result(id, &result)?
+            }
+            StepAction::ReadArtifact { id, request } => {
+                session.accept_tool_result(id, read_artifact(&request, &artifacts, events))?
+            }
+            StepAction::Recall { id, request } => {
+                session.accept_tool_result(id, recall(&request, memory.as_ref(), events))?
+            }
+            action => action,
+        };
+
+        if report_step(events, action) {
+            return finish_run(
+                &args,
+                session.state(),
+                &mut llm_backend,
+                memory.as_mut(),
+                &artifacts,
+            );
+        }
+    }
+}
+
+/// The session of a run: its conversation, limits, guardrails and prompt
+/// settings
+fn run_session(
+    args: &AgentArgs,
+    system_prompt: &str,
+    guardrail_chain: GuardrailChain,
+    answer_guards: AnswerChain,
+) -> AgentSession {
+    let state = args
+        .conversation
+        .clone()
+        .unwrap_or_else(|| AgentState::new(&args.query));
+    AgentSession::from_state(state, system_prompt)
+        .with_max_iterations(args.max_iterations)
+        .with_stop_sequences(args.stop.clone())
+        .with_guardrails(guardrail_chain)
+        .with_answer_guards(answer_guards)
+        .with_history_budget(args.history_budget.clone())
+        .with_answer_protocol(args.answer_protocol)
+        .with_reasoning_delimiters(args.reasoning_delimiters.clone())
+        .with_capabilities(args.capabilities.clone())
+        .with_response_spec(args.response_spec.clone())
+}
+
+/// The session's next prompt, reporting a new iteration
+///
+/// Fails the run once the iterations run out.
+fn next_prompt(session: &mut AgentSession, events: &EventSink) -> Result<PromptSpec> {
+    let prompt = match session.next_prompt() {
+        Ok(prompt) => prompt,
+        Err(SessionError::Failed(failure)) => report_session_failure(events, failure),
+        Err(e) => return Err(e.into()),
+    };
+    if !prompt.retry {
+        events.emit(AgentEvent::Iteration {
+            number: prompt.iteration,
+            max: prompt.max_iterations,
+        });
+    }
+    Ok(prompt)
+}
+
+/// The backend input of a turn's prompt
+fn turn_input(
+    args: &AgentArgs,
+    prompt: &PromptSpec,
+    session: &AgentSession,
+    artifacts: &RunArtifacts,
+    tools: &ToolHost,
+    first_generation: bool,
+) -> LLMInput {
+    LLMInput {
+        prompt: prompt.to_plain_text(),
+        max_tokens: args.max_tokens,
+        stop: prompt.stop.clone(),
+        first_generation,
+        images: artifacts.pending_images(&session.state().history),
+        chat: Some(chat_context(prompt, &tools.registry)),
+        progress: None,
+    }
+}
+
+/// Report what the session made of a step
+///
+/// Returns true once the run answered; a failed run ends here.
+fn report_step(events: &EventSink, action: StepAction) -> bool {
+    match action {
+        StepAction::Retry(RetryReason::Guardrail { .. }) => {
+            events.emit(AgentEvent::Retrying {
+                reason: "guardrail rejected tool output".to_string(),
+            });
+        }
+        StepAction::Retry(RetryReason::Inconclusive { output, reason }) => {
+            // Model failed to produce a tool call or complete the task
+            events.emit(AgentEvent::Inconclusive { output, reason });
+            events.emit(AgentEvent::Retrying {
+                reason: "stricter instructions".to_string(),
+            });
+        }
+        StepAction::Variable { operation, result } => report_variable(events, operation, &result),
+        StepAction::Unsupported { reason } => events.emit(AgentEvent::Unsupported { reason }),
+        StepAction::AnswerRejected { answer, reason } => {
+            events.emit(AgentEvent::AnswerRejected { answer, reason });
+        }
+        StepAction::Done { answer } => {
+            events.emit(AgentEvent::FinalAnswer { answer });
+            return true;
+        }
+        StepAction::Failed(failure) => report_session_failure(events, failure),
+        // Calls are answered before their step is reported
+        StepAction::Continue
+        | StepAction::CallTool { .. }
+        | StepAction::CallSkill { .. }
+        | StepAction::ReadArtifact { .. }
+        | StepAction::Recall { .. } => {}
+    }
+    false
+}
+
+/// End the run with the report of why the session failed
+fn report_session_failure(events: &EventSink, failure: SessionFailure) -> ! {
+    match failure {
+        SessionFailure::MaxIterations { .. } => report_max_iterations(events),
+        SessionFailure::GuardrailFailure { initial, retry } => {
+            report_guardrail_failure(events, &initial, &retry)
+        }
+        SessionFailure::InconclusiveAfterGuardrail { reason, output } => {
+            report_inconclusive_after_guardrail_failure(events, &reason, &output)
+        }
+        SessionFailure::Inconclusive { first, retry } => {
+            report_inconclusive_after_retry(events, &first, &retry)
+        }
+        SessionFailure::Refused { reason } => report_refused(events, &reason),
+    }
+}
+
+/// Keep what a run that answered leaves for later runs: its outputs and
+/// answer in the memory (`--memory`) and its context (`--kv-state`)
+fn finish_run(
+    args: &AgentArgs,
+    state: &AgentState,
+    backend: &mut dyn LLMBackend,
+    memory: Option<&mut RunMemory>,
+    artifacts: &RunArtifacts,
+) -> Result<()> {
+    remember_run(args, state, memory, artifacts)?;
+    save_kv_state(args, state, backend)
+}
+
+/// Add a run that answered to the memory (`--memory`)
+fn remember_run(
+    args: &AgentArgs,
+    state: &AgentState,
+    memory: Option<&mut RunMemory>,
+    artifacts: &RunArtifacts,
+) -> Result<()> {
+    match memory {
+        Some(memory) => memory.save_run(&args.run_id, state, artifacts),
+        None => Ok(()),
+    }
 }
 
-#[derive(Subcommand, Debug)]
-enum SkillCommand {
-    /// Extract structured data from text
-    Extract {
-        /// Text to extract from
-        #[arg(long)]
-        text: String,
-        /// Target to extract (email, url, date, entity)
-        #[arg(short, long, value_parser = parse_target)]
-        target: ExtractionTarget,
-        /// Path to the GGUF model file
-        #[arg(short, long)]
-        model: Option<PathBuf>,
-        /// Number of tokens to generate
-        #[arg(short = 'n', long, default_value = "256")]
-        max_tokens: usize,
-    },
+/// Save the context for a later run continuing this conversation (`--kv-state`)
+fn save_kv_state(args: &AgentArgs, state: &AgentState, backend: &mut dyn LLMBackend) -> Result<()> {
+    match &args.kv_state {
+        Some(path) => kv_state::save(path, state, backend),
+        None => Ok(()),
+    }
 }
 
-#[derive(Debug)]
-struct AgentArgs {
-    model: PathBuf,
-    query: String,
-    max_iterations: usize,
-    max_tokens: usize,
+/// Report a run that used up its iterations without an answer
+///
+/// If a tool call was denied along the way, the denial is the likely cause and
+/// the run fails as `tool_denied`.
+fn report_max_iterations(events: &EventSink) -> ! {
+    match events.denied_calls() {
+        0 => fail(
+            events,
+            "max_iterations",
+            "\n⚠️  Warning: Agent reached maximum iterations without completing.".to_string(),
+        ),
+        denied => fail(
+            events,
+            "tool_denied",
+            format!(
+                "\n⚠️  Warning: Agent reached maximum iterations without completing after {} \
+                 tool call(s) were denied.",
+                denied
+            ),
+        ),
+    }
 }
 
-fn parse_target(value: &str) -> Result<ExtractionTarget, String> {
-    ExtractionTarget::from_str(value).ok_or_else(|| {
-        format!(
-            "Invalid target '{}'. Expected one of: email, url, date, entity",
-            value
-        )
-    })
+/// End the run with a `failed` event and the exit code of its failure code
+/// (see [`RunOutcome`])
+fn fail(events: &EventSink, code: &str, message: String) -> ! {
+    events.emit(AgentEvent::Failed {
+        code: code.to_string(),
+        message,
+    });
+    // Exiting skips destructors, the sandbox's included
+    sandbox::discard();
+    std::process::exit(RunOutcome::from_failure_code(code).exit_code())
 }
 
-fn main() -> Result<()> {
-    let cli = Cli::parse();
-
-    match &cli.command {
-        Some(CliCommand::Extract {
-            text,
-            target,
-            model,
-            max_tokens,
-        }) => {
-            let model_path = model
-                .clone()
-                .unwrap_or_else(|| PathBuf::from(DEFAULT_MODEL_PATH));
-            run_extract_mode(text, *target, model_path, *max_tokens)
-        }
-        Some(CliCommand::Skill { command }) => match command {
-            SkillCommand::Extract {
-                text,
-                target,
-                model,
-                max_tokens,
-            } => {
-                let model_path = model
-                    .clone()
-                    .unwrap_or_else(|| PathBuf::from(DEFAULT_MODEL_PATH));
-                run_extract_mode(text, *target, model_path, *max_tokens)
-            }
-        },
-        None => {
-            let model = cli
-                .model
-                .clone()
-                .unwrap_or_else(|| PathBuf::from(DEFAULT_MODEL_PATH));
-            let query = cli
-                .query
-                .clone()
-                .ok_or_else(|| anyhow::anyhow!("Missing required --query argument"))?;
-
-            let args = AgentArgs {
-                model,
-                query,
-                max_iterations: cli.max_iterations,
-                max_tokens: cli.max_tokens,
-            };
+/// Show what a sandbox run changed and apply it to the workspace if approved
+fn finish_sandbox(sandbox: &Sandbox, events: &mut EventSink) -> Result<()> {
+    std::env::set_current_dir(sandbox.workspace())?;
+    let changes = sandbox.changes()?;
+    let diff = sandbox.diff(&changes)?;
+    events.emit(AgentEvent::SandboxChanges {
+        files: changes
+            .iter()
+            .map(|change| change.path.display().to_string())
+            .collect(),
+        diff: diff.clone(),
+    });
+    if changes.is_empty() || !events.request_sandbox_apply(&diff)? {
+        return Ok(());
+    }
 
-            let discovered_skills = discover_skills(&[PathBuf::from("skills")]);
-            let available_skills_prompt = build_available_skills_prompt(&discovered_skills);
-            let system_prompt = build_system_prompt(&available_skills_prompt);
+    sandbox.apply(&changes)?;
+    events.emit(AgentEvent::SandboxApplied {
+        workspace: sandbox.workspace().display().to_string(),
+        files: changes.len(),
+    });
+    Ok(())
+}
 
-            run_agent(args, system_prompt)
-        }
+/// Artifact store for a run, with pages sized to the context the system
+/// prompt leaves free
+fn run_artifacts(args: &AgentArgs, system_prompt: &str) -> RunArtifacts {
+    let prompt_tokens = system_prompt.chars().count().div_ceil(4);
+    let artifacts = RunArtifacts::new(&args.artifacts_dir, &args.run_id)
+        .with_context_tokens(args.context_tokens.saturating_sub(prompt_tokens));
+    match args.memory {
+        Some(_) => artifacts.with_archive(),
+        None => artifacts,
     }
 }
 
-fn run_agent(args: AgentArgs, system_prompt: String) -> Result<()> {
-    println!("=== agent.rs ===");
-    println!("Query: {}\n", args.query);
+/// Check the output of tool call `id` with the session's guardrails and
+/// report the verdict
+///
+/// Returns what follows a rejection, or `None` if the output is to be recorded.
+fn review_tool_result(
+    session: &mut AgentSession,
+    events: &EventSink,
+    id: CallId,
+    request: &ToolRequest,
+    result: &ToolResult,
+) -> Result<Option<StepAction>> {
+    Ok(match session.review_tool_result(id, result)? {
+        ToolReview::Unchecked => None,
+        ToolReview::Accepted => {
+            emit_guardrail_verdict(events, request, None);
+            None
+        }
+        ToolReview::Rejected { reason, action } => {
+            emit_guardrail_verdict(events, request, Some(reason));
+            Some(action)
+        }
+    })
+}
 
-    // Initialize LLM backend (llama.cpp in this case)
-    let mut llm_backend =
-        LlamaCppBackend::new(&args.model).context("Failed to initialize LLM backend")?;
+/// Record an accepted tool result in the conversation
+///
+/// Large outputs are spooled to the run's artifacts and only an excerpt goes
+/// into the history, or the `summary` of the output if it was summarized.
+fn record_tool_result(
+    session: &mut AgentSession,
+    id: CallId,
+    result: ToolResult,
+    summary: Option<String>,
+    observations: &ObservationPolicy,
+    artifacts: &mut RunArtifacts,
+) -> Result<StepAction> {
+    let used_tokens = session.state().estimated_tokens();
+    let result = match summary {
+        Some(summary) => {
+            artifacts.record_observation(result, &summary, observations, used_tokens)?
+        }
+        None => artifacts.record(result, used_tokens)?,
+    };
+    Ok(session.accept_tool_result(id, result)?)
+}
 
-    // Initialize semantic guardrail chain
-    let guardrail_chain = GuardrailChain::new().add(Box::new(PlausibilityGuard::new()));
+/// Compress an accepted tool output into an observation (`--observations`)
+///
+/// Returns the summary, or `None` if the output is kept as it is. A model
+/// reply that is empty falls back to the local summary.
+fn summarize_observation(
+    args: &AgentArgs,
+    request: &ToolRequest,
+    result: &ToolResult,
+    llm_backend: &mut dyn LLMBackend,
+    events: &EventSink,
+) -> Result<Option<String>> {
+    let policy = &args.observations;
+    if !policy.should_summarize(result) {
+        return Ok(None);
+    }
+    if policy.mode() == ObservationMode::Model {
+        let output = generate(
+            llm_backend,
+            LLMInput {
+                prompt: policy.summary_prompt(&args.query, &request.tool, &result.output),
+                max_tokens: policy.summary_tokens(),
+                stop: args.stop.for_phase(PromptPhase::Summary).to_vec(),
+                first_generation: false,
+                images: Vec::new(),
+                chat: None,
+                progress: None,
+            },
+            "Summarizing",
+            events,
+        )?;
+        if !output.text.trim().is_empty() {
+            return Ok(Some(output.text));
+        }
+    }
+    Ok(Some(policy.local_summary(&result.output)))
+}
 
-    // Initialize agent state
-    let mut state = AgentState::new(&args.query);
-    let mut iteration = 0;
-    let mut current_pos: i32 = 0; // Track KV cache position
-    let mut tool_used = false; // Track if any tool has been invoked
-    let mut first_generation = true; // Track first decode (Metal shader compilation)
+/// Serve an artifact read, reporting the lines like a tool output
+fn read_artifact(
+    request: &ArtifactRequest,
+    artifacts: &RunArtifacts,
+    events: &EventSink,
+) -> ToolResult {
+    let result = artifacts.read(request);
+    events.emit(AgentEvent::ToolOutput {
+        tool: "read_artifact".to_string(),
+        success: result.success,
+        output: result.output.clone(),
+        error: result.error.clone(),
+        content_kind: result.content_kind,
+    });
+    result
+}
 
-    // Agent loop
-    while iteration < args.max_iterations {
-        iteration += 1;
+/// Answer a recall from the memory (`--memory`)
+fn recall(request: &RecallRequest, memory: Option<&RunMemory>, events: &EventSink) -> ToolResult {
+    let result = match memory {
+        Some(memory) => memory.recall(request),
+        None => ToolResult::failure("This host keeps no memory"),
+    };
+    events.emit(AgentEvent::ToolOutput {
+        tool: "recall".to_string(),
+        success: result.success,
+        output: result.output.clone(),
+        error: result.error.clone(),
+        content_kind: result.content_kind,
+    });
+    result
+}
 
-        // Lifecycle callback: before_llm_call
-        let prompt = before_llm_call(&state, tool_used, false, &system_prompt);
+/// Report a scratchpad operation agent-core already applied
+fn report_variable(events: &EventSink, operation: String, result: &ToolResult) {
+    events.emit(AgentEvent::ToolOutput {
+        tool: operation,
+        success: result.success,
+        output: result.output.clone(),
+        error: result.error.clone(),
+        content_kind: result.content_kind,
+    });
+}
 
-        // Call LLM backend
-        let llm_output = llm_backend.infer(LLMInput {
-            prompt,
-            max_tokens: args.max_tokens,
-            current_pos,
-            first_generation,
-        })?;
+/// Report a second refusal in a row, which ends the run
+fn report_refused(events: &EventSink, reason: &str) -> ! {
+    events.emit(AgentEvent::Unsupported {
+        reason: reason.to_string(),
+    });
+    let message = format!(
+        r#"
+❌ ERROR: Model asked again for something this host cannot do.
 
-        current_pos += llm_output.tokens_processed;
-        first_generation = false;
+Last refusal: {}
 
-        // Process the output
-        match process_model_output(&mut state, llm_output.text) {
-            AgentDecision::InvokeSkill(skill_request) => {
-                // Execute skill
-                let result = execute_skill(
-                    &skill_request,
-                    &mut llm_backend,
-                    args.max_tokens,
-                    &mut current_pos,
-                )?;
+The model was shown the tools and skills it can call after the first refusal.
 
-                if result.success {
-                    // Apply result to state
-                    state.add_message(Role::Tool, format!("Skill output:\n{}", result.to_json()));
-                    println!("\n✓ Skill result: {}", result.to_json());
-                } else {
-                    // Skill failed - add error to state
-                    let error_msg = result.error.as_deref().unwrap_or("unknown error");
-                    state.add_message(Role::Tool, format!("Skill failed: {}", error_msg));
-                    eprintln!("\n✗ Skill error: {}", error_msg);
-                }
-            }
-            AgentDecision::InvokeTool(tool_request) => {
-                // Execute tool
-                let result = execute_tool(&tool_request)?;
-
-                // Validate tool output with semantic guardrails
-                let guard_ctx = GuardrailContext {
-                    state: &state,
-                    tool_request: &tool_request,
-                    tool_result: &result,
-                };
+Suggestions:
+  - Use a model specifically tuned for tool use
+  - Name the tool to use in the query"#,
+        reason
+    );
+    fail(events, "refused", message)
+}
 
-                match guardrail_chain.validate(&guard_ctx) {
-                    GuardrailResult::Accept => {
-                        // Apply result to state
-                        apply_tool_result(&mut state, &result);
+fn skill_result_event(request: &SkillRequest, result: &SkillResult_) -> AgentEvent {
+    AgentEvent::SkillResult {
+        skill: request.skill.clone(),
+        success: result.success,
+        output: result.output.clone(),
+        error: result.error.clone(),
+    }
+}
 
-                        // Lifecycle callback: after_tool_execution
-                        after_tool_execution(&mut state, &result);
-                        tool_used = true;
-                    }
-                    GuardrailResult::Reject { reason } => {
-                        // Guardrail rejected output - treat as inconclusive
-                        eprintln!("\n⚠️  Guardrail rejected tool output:");
-                        eprintln!("   {}", reason);
-                        eprintln!("\n   Attempting corrective retry...\n");
-
-                        // Corrective retry with stricter instructions
-                        let corrective_prompt =
-                            before_llm_call(&state, tool_used, true, &system_prompt);
-
-                        let retry_output = llm_backend.infer(LLMInput {
-                            prompt: corrective_prompt,
-                            max_tokens: args.max_tokens,
-                            current_pos,
-                            first_generation: false,
-                        })?;
-
-                        current_pos += retry_output.tokens_processed;
-
-                        // Process retry output
-                        match process_model_output(&mut state, retry_output.text) {
-                            AgentDecision::InvokeSkill(skill_request) => {
-                                // Execute skill on retry
-                                let result = execute_skill(
-                                    &skill_request,
-                                    &mut llm_backend,
-                                    args.max_tokens,
-                                    &mut current_pos,
-                                )?;
-                                if result.success {
-                                    state.add_message(
-                                        Role::Tool,
-                                        format!("Skill output:\n{}", result.to_json()),
-                                    );
-                                } else {
-                                    let error_msg =
-                                        result.error.as_deref().unwrap_or("unknown error");
-                                    state.add_message(
-                                        Role::Tool,
-                                        format!("Skill failed: {}", error_msg),
-                                    );
-                                }
-                            }
-                            AgentDecision::InvokeTool(retry_request) => {
-                                // Execute retry
-                                let retry_result = execute_tool(&retry_request)?;
-
-                                // Validate retry output
-                                let retry_guard_ctx = GuardrailContext {
-                                    state: &state,
-                                    tool_request: &retry_request,
-                                    tool_result: &retry_result,
-                                };
-
-                                match guardrail_chain.validate(&retry_guard_ctx) {
-                                    GuardrailResult::Accept => {
-                                        // Success - apply result
-                                        apply_tool_result(&mut state, &retry_result);
-                                        after_tool_execution(&mut state, &retry_result);
-                                        tool_used = true;
-                                    }
-                                    GuardrailResult::Reject {
-                                        reason: retry_reason,
-                                    } => {
-                                        report_guardrail_failure(&reason, &retry_reason);
-                                    }
-                                }
-                            }
-                            AgentDecision::Done(answer) => {
-                                println!("\n{}", answer);
-                                return Ok(());
-                            }
-                            AgentDecision::Inconclusive(retry_output) => {
-                                report_inconclusive_after_guardrail_failure(&reason, &retry_output);
-                            }
-                        }
-                    }
-                }
-            }
-            AgentDecision::Done(answer) => {
-                println!("\n{}", answer);
-                return Ok(());
-            }
-            AgentDecision::Inconclusive(output) => {
-                // Model failed to produce a tool call or complete the task
-                eprintln!("\n⚠️  Model produced inconclusive output:");
-                eprintln!("   \"{}\"", output.lines().next().unwrap_or(&output));
-                eprintln!("\n   Attempting corrective retry with stricter instructions...\n");
-
-                // Corrective retry: re-prompt with explicit tool requirement
-                let corrective_prompt = before_llm_call(&state, tool_used, true, &system_prompt);
-
-                let retry_output = llm_backend.infer(LLMInput {
-                    prompt: corrective_prompt,
-                    max_tokens: args.max_tokens,
-                    current_pos,
-                    first_generation: false,
-                })?;
+/// The embedder of a run, if its guardrails or its memory need one
+fn embedder(args: &AgentArgs) -> Result<Option<Arc<EmbeddingWorker>>> {
+    let purpose = match (args.guardrails.relevance_threshold(), &args.memory) {
+        (None, None) => return Ok(None),
+        (Some(_), None) => "The semantic-relevance guardrail",
+        (None, Some(_)) => "--memory",
+        (Some(_), Some(_)) => "The semantic-relevance guardrail and --memory",
+    };
+    let worker = EmbeddingWorker::spawn(args.backend.clone(), purpose)?;
+    Ok(Some(Arc::new(worker)))
+}
 
-                current_pos += retry_output.tokens_processed;
+/// The run's memory (`--memory`), embedding with `embedder`
+fn open_memory(
+    args: &AgentArgs,
+    embedder: Option<Arc<EmbeddingWorker>>,
+) -> Result<Option<RunMemory>> {
+    match (&args.memory, embedder) {
+        (Some(path), Some(embedder)) => Ok(Some(RunMemory::open(
+            path,
+            args.backend.describe(),
+            embedder,
+        )?)),
+        _ => Ok(None),
+    }
+}
 
-                // Process retry output
-                match process_model_output(&mut state, retry_output.text) {
-                    AgentDecision::InvokeSkill(skill_request) => {
-                        // Success - execute skill
-                        let result = execute_skill(
-                            &skill_request,
-                            &mut llm_backend,
-                            args.max_tokens,
-                            &mut current_pos,
-                        )?;
-                        if result.success {
-                            state.add_message(
-                                Role::Tool,
-                                format!("Skill output:\n{}", result.to_json()),
-                            );
-                        } else {
-                            let error_msg = result.error.as_deref().unwrap_or("unknown error");
-                            state.add_message(Role::Tool, format!("Skill failed: {}", error_msg));
-                        }
-                    }
-                    AgentDecision::InvokeTool(tool_request) => {
-                        // Success - execute tool
-                        let result = execute_tool(&tool_request)?;
-                        apply_tool_result(&mut state, &result);
-                        after_tool_execution(&mut state, &result);
-                        tool_used = true;
-                    }
-                    AgentDecision::Done(answer) => {
-                        println!("\n{}", answer);
-                        return Ok(());
-                    }
-                    AgentDecision::Inconclusive(retry_output) => {
-                        // Still inconclusive after retry - fail loudly
-                        eprintln!(
-                            "\n❌ ERROR: Model failed to produce a valid response after retry.\n"
-                        );
-                        eprintln!(
-                            "Original output: \"{}\"",
-                            output.lines().next().unwrap_or(&output)
-                        );
-                        eprintln!(
-                            "Retry output:    \"{}\"",
-                            retry_output.lines().next().unwrap_or(&retry_output)
-                        );
-                        eprintln!(
-                            "\nThe model did not invoke a tool/skill or provide a complete answer."
-                        );
-                        eprintln!("This is common with small models (3-4B parameters).");
-                        eprintln!("\nSuggestions:");
-                        eprintln!("  - Use a larger model (7B+ parameters)");
-                        eprintln!("  - Use a model specifically tuned for tool use");
-                        eprintln!("  - Simplify the query");
-
-                        std::process::exit(1);
-                    }
-                }
-            }
-        }
+/// The tool-output and final-answer guardrails of a run
+///
+/// Host tools' results pass their own validators first. The semantic
+/// relevance guard, when enabled, checks both with the run's `embedder`; the
+/// answer chain ends with the critic (`--critic`).
+fn guard_chains(
+    args: &AgentArgs,
+    embedder: Option<&Arc<EmbeddingWorker>>,
+) -> Result<(GuardrailChain, AnswerChain)> {
+    let mut outputs = with_result_validators(args.guardrails.output_chain());
+    let mut answers = args.guardrails.answer_chain();
+    if let (Some(threshold), Some(embedder)) = (args.guardrails.relevance_threshold(), embedder) {
+        let guard = RelevanceGuard::shared(embedder.clone()).with_threshold(threshold);
+        outputs = outputs.add(Box::new(guard.clone()));
+        answers = answers.add(Box::new(guard));
     }
+    Ok((outputs, answer_chain(args, answers)?))
+}
 
-    eprintln!("\n⚠️  Warning: Agent reached maximum iterations without completing.");
-    std::process::exit(1)
+/// `chain` ending with the critic (`--critic`)
+fn answer_chain(args: &AgentArgs, chain: AnswerChain) -> Result<AnswerChain> {
+    let Some(config) = &args.critic else {
+        return Ok(chain);
+    };
+    console::verbose(format!("Critic: {}", config.describe()));
+    let stop = args.stop.for_phase(PromptPhase::Critique).to_vec();
+    let reviewer = critic::CriticReviewer::spawn(config.clone(), stop)?;
+    Ok(chain.add(Box::new(CriticGuard::new(reviewer))))
+}
+
+fn emit_guardrail_verdict(events: &EventSink, request: &ToolRequest, rejection: Option<String>) {
+    events.emit(AgentEvent::GuardrailVerdict {
+        tool: request.tool.clone(),
+        accepted: rejection.is_none(),
+        reason: rejection,
+    });
 }
 
 fn run_extract_mode(
-    text: &str,
-    target: ExtractionTarget,
-    model: PathBuf,
-    max_tokens: usize,
+    args: &ExtractArgs,
+    max_input_bytes: u64,
+    backend: &BackendConfig,
+    events: &EventSink,
 ) -> Result<()> {
-    println!("=== agent.rs | extract ===");
-    println!("Model: {}", model.display());
-    println!("Target: {}", target.as_str());
-    println!("Text: \"{}\"\n", truncate_string(text, 80));
-
-    let mut llm_backend =
-        LlamaCppBackend::new(&model).context("Failed to initialize LLM backend")?;
-
-    let mut current_pos: i32 = 0;
-    let request = SkillRequest::new(
-        "extract",
-        json!({
-            "text": text,
-            "target": target.as_str()
-        }),
-    );
+    if args.text.is_none() && args.text_file.is_none() && !input::stdin_is_piped() {
+        anyhow::bail!("Give the text with --text, --text-file or on stdin");
+    }
+    let text = args.input().read("text", max_input_bytes)?;
+    if args.records {
+        return run_extract_records(args, &text, backend, events);
+    }
+    let text = text.as_str();
+    let target = args.target;
+
+    if events.mode() == OutputMode::Text {
+        console::info("=== agent.rs | extract ===");
+        console::info(format!("Model: {}", backend.describe()));
+        console::info(format!("Target: {}", target.as_str()));
+        console::info(format!("Text: \"{}\"\n", truncate_string(text, 80)));
+    }
+
+    let mut llm_backend = load_backend(backend)?;
 
-    let result =
-        execute_extraction_skill(&request, &mut llm_backend, max_tokens, &mut current_pos)?;
+    let request = extract_request(args, text);
+    let skills = load_skills(&discover_skills(&[PathBuf::from(DEFAULT_SKILLS_DIR)]));
+    let result = execute_extraction_skill(
+        &request,
+        &mut llm_backend,
+        ReplyBounds {
+            max_tokens: args.max_tokens,
+            stop: StopSequences::default().for_phase(PromptPhase::Extraction),
+        },
+        args.chunking(),
+        skills.get("extract"),
+        events,
+    )?;
+
+    if events.mode() == OutputMode::Jsonl {
+        events.emit(skill_result_event(&request, &result));
+    }
 
     if result.success {
-        println!("{}", result.to_json());
+        if events.mode() == OutputMode::Text {
+            match (&result.output, args.format) {
+                (Some(output), format) if format != ExtractFormat::Json => {
+                    print!("{}", format_extraction(target, &output["result"], format))
+                }
+                _ => println!("{}", result.to_json()),
+            }
+        }
         Ok(())
     } else {
         let msg = result
@@ -488,88 +2308,257 @@ fn run_extract_mode(
     }
 }
 
-/// Lifecycle callback: before_llm_call
-/// Constructs the prompt and injects response schema if tools have been used
-/// If `corrective` is true, adds stricter instructions for tool invocation
-fn before_llm_call(
-    state: &AgentState,
-    tool_used: bool,
-    corrective: bool,
-    system_prompt: &str,
-) -> String {
-    let mut prompt = String::new();
-
-    // Add system prompt
-    prompt.push_str(system_prompt);
-    prompt.push_str("\n\n");
-
-    // Add conversation history
-    for msg in &state.history {
-        match msg.role {
-            Role::User => {
-                prompt.push_str("User: ");
-                prompt.push_str(&msg.content);
-                prompt.push_str("\n\n");
-            }
-            Role::Assistant => {
-                prompt.push_str("Assistant: ");
-                prompt.push_str(&msg.content);
-                prompt.push_str("\n\n");
-            }
-            Role::Tool => {
-                prompt.push_str(&msg.content);
-                prompt.push_str("\n\n");
+/// The extraction skill request for `text`
+fn extract_request(args: &ExtractArgs, text: &str) -> SkillRequest {
+    let mut params = json!({
+        "text": text,
+        "target": args.target.as_str()
+    });
+    if let Some(ref classes) = args.classes {
+        params["classes"] = json!(classes);
+    }
+    if !args.fields.is_empty() {
+        let schema: serde_json::Map<String, serde_json::Value> = args
+            .fields
+            .iter()
+            .map(|(name, desc)| (name.clone(), json!(desc)))
+            .collect();
+        params["schema"] = json!(schema);
+    }
+    SkillRequest::new("extract", params)
+}
+
+/// `extract --records`: every record over one loaded model, a result line
+/// each on stdout and progress on stderr
+fn run_extract_records(
+    args: &ExtractArgs,
+    jsonl: &str,
+    backend: &BackendConfig,
+    events: &EventSink,
+) -> Result<()> {
+    if args.format != ExtractFormat::Json {
+        anyhow::bail!("--records prints JSON lines; drop --format");
+    }
+    let records = parse_records(jsonl).context("Invalid records")?;
+    console::progress(format!(
+        "Extracting {} from {} record(s) with {}",
+        args.target.as_str(),
+        records.len(),
+        backend.describe()
+    ));
+
+    let started = Instant::now();
+    let mut llm_backend = load_backend(backend)?;
+    let skills = load_skills(&discover_skills(&[PathBuf::from(DEFAULT_SKILLS_DIR)]));
+    let stop = StopSequences::default();
+    let reply = ReplyBounds {
+        max_tokens: args.max_tokens,
+        stop: stop.for_phase(PromptPhase::Extraction),
+    };
+
+    let mut failed = 0;
+    for (index, record) in records.iter().enumerate() {
+        let request = extract_request(args, &record.text);
+        let result = execute_extraction_skill(
+            &request,
+            &mut llm_backend,
+            reply,
+            args.chunking(),
+            skills.get("extract"),
+            events,
+        );
+        let line = match result {
+            Ok(result) => {
+                if events.mode() == OutputMode::Jsonl {
+                    events.emit(skill_result_event(&request, &result));
+                }
+                match (result.success, result.output) {
+                    (true, Some(output)) => ExtractionRecordResult::success(&record.id, output),
+                    _ => ExtractionRecordResult::failure(
+                        &record.id,
+                        result.error.unwrap_or_else(|| "unknown error".to_string()),
+                    ),
+                }
             }
+            Err(e) => ExtractionRecordResult::failure(&record.id, format!("{:#}", e)),
+        };
+        let mark = if line.success { "✓" } else { "✗" };
+        if !line.success {
+            failed += 1;
+        }
+        console::progress(format!(
+            "[{}/{}] {} {}",
+            index + 1,
+            records.len(),
+            mark,
+            record.id
+        ));
+        if events.mode() == OutputMode::Text {
+            println!("{}", line.to_json());
+            io::stdout().flush()?;
         }
     }
 
-    // Inject response schema if at least one tool has been used
-    if tool_used {
-        prompt.push_str(TOOL_RESPONSE_SCHEMA);
-        prompt.push_str("\n\n");
+    let elapsed = started.elapsed().as_secs_f32();
+    console::progress(format!(
+        "{} record(s) in {:.1}s ({:.2}/s), {} failed",
+        records.len(),
+        elapsed,
+        records.len() as f32 / elapsed.max(f32::EPSILON),
+        failed
+    ));
+    if failed > 0 {
+        anyhow::bail!("{} of {} record(s) failed", failed, records.len());
     }
+    Ok(())
+}
 
-    // Add corrective instruction if this is a retry
-    // This prompt addresses common LLM failures: reasoning instead of action,
-    // and generating commands that produce unusable outputs (headers, summaries).
-    if corrective {
-        prompt.push_str("CRITICAL: You MUST call a tool to complete this task.\n");
-        prompt.push_str("Respond ONLY with valid JSON in the exact format shown above.\n");
-        prompt.push_str(
-            "Do NOT explain what you will do. Do NOT use plain text. Output JSON only.\n\n",
-        );
+/// Set up the configured backend, showing a spinner while a local model loads
+fn load_backend(config: &BackendConfig) -> Result<Box<dyn LLMBackend>> {
+    open_backend(config).context(BackendError)
+}
 
-        prompt.push_str("IMPORTANT: The tool command must directly produce the final answer.\n");
-        prompt.push_str("Avoid commands that output headers, summaries, or non-answer lines.\n");
-        prompt.push_str(
-            "The tool output should be the actual data requested, not metadata about it.\n\n",
-        );
+fn open_backend(config: &BackendConfig) -> Result<Box<dyn LLMBackend>> {
+    let throttle = Arc::new(Throttle::new(config.rate_limits));
+    let backend: Box<dyn LLMBackend> = match config.kind {
+        BackendKind::LlamaCpp => {
+            let started = Instant::now();
+            let backend = {
+                let _spinner = Spinner::start(format!("Loading model {}", config.describe()));
+                LlamaCppBackend::new(&config.model)
+                    .context("Failed to initialize LLM backend")?
+                    .with_sampling(config.sampling)
+            };
+            console::verbose(format!(
+                "Loaded {} in {:.1}s",
+                config.describe(),
+                started.elapsed().as_secs_f32()
+            ));
+            Box::new(backend)
+        }
+        BackendKind::LlamaServer => Box::new(
+            LlamaServerBackend::new(config.endpoint())
+                .with_throttle(throttle)
+                .with_sampling(config.sampling),
+        ),
+        BackendKind::Mistralrs => Box::new(
+            MistralRsBackend::new(config.endpoint(), config.model_id())
+                .with_throttle(throttle)
+                .with_sampling(config.sampling),
+        ),
+        BackendKind::Anthropic => Box::new(
+            AnthropicBackend::new(
+                config.endpoint(),
+                api_key(anthropic_backend::API_KEY_VAR)?,
+                config.model_id(),
+            )
+            .with_throttle(throttle)
+            .with_sampling(config.sampling),
+        ),
+        BackendKind::Gemini => Box::new(
+            GeminiBackend::new(
+                config.endpoint(),
+                api_key(gemini_backend::API_KEY_VAR)?,
+                config.model_id(),
+            )
+            .with_throttle(throttle)
+            .with_sampling(config.sampling),
+        ),
+    };
+    if config.kind != BackendKind::LlamaCpp {
+        console::verbose(format!("Using {}", config.describe()));
+    }
+    Ok(backend)
+}
+
+/// Run one generation, showing a spinner and logging details at higher verbosity
+fn generate(
+    backend: &mut dyn LLMBackend,
+    mut input: LLMInput,
+    label: &str,
+    events: &EventSink,
+) -> Result<LLMOutput> {
+    trace_prompt(events, label, &input);
+    let prompt = events.recording().then(|| input.prompt.clone());
+
+    let started = Instant::now();
+    let output = {
+        let spinner = Spinner::start(label);
+        input.progress = Some(prompt_progress(&spinner, label));
+        backend.infer(input).context(BackendError)?
+    };
 
-        // NOTE: Semantic guardrails validate tool outputs at runtime.
-        // TODO: Future enhancement - Tool-defined postconditions
-        //
-        // Tools should optionally declare explicit semantic contracts (postconditions)
-        // that replace heuristic guardrails. This aligns with agent.cpp's callback
-        // extensibility and any-guardrail's pluggable validation model.
+    trace_output(events, label, started, prompt, &output);
+    Ok(output)
+}
+
+/// Progress of a long prompt, shown on the spinner
+fn prompt_progress(spinner: &Spinner, label: &str) -> PromptProgress {
+    let set_message = spinner.message_setter();
+    let label = label.to_string();
+    PromptProgress::new(move |done, total| {
+        set_message(format!(
+            "{} (reading prompt: {}/{} tokens)",
+            label, done, total
+        ))
+    })
+}
+
+/// Show the prompt of a model call: as an event with `--show-prompt`,
+/// otherwise at `-vv`
+fn trace_prompt(events: &EventSink, label: &str, input: &LLMInput) {
+    if events.shows_prompts() {
+        events.emit(AgentEvent::Prompt {
+            label: label.to_string(),
+            tokens: throttle::prompt_tokens(&input.prompt),
+            prompt: input.prompt.clone(),
+        });
+    } else {
+        console::debug(format!("--- prompt ---\n{}\n--------------", input.prompt));
     }
+}
 
-    prompt.push_str("Assistant: ");
-    prompt
+/// Report a generation; `prompt` is the rendered prompt when recording
+fn trace_output(
+    events: &EventSink,
+    label: &str,
+    started: Instant,
+    prompt: Option<String>,
+    output: &LLMOutput,
+) {
+    events.emit(AgentEvent::Generated {
+        label: label.to_string(),
+        tokens: output.tokens_processed.max(0) as usize,
+        seconds: started.elapsed().as_secs_f32(),
+    });
+    if let Some(prompt) = prompt {
+        events.record_model_call(prompt, &output.text);
+    }
+    console::debug(format!(
+        "--- model output ---\n{}\n--------------------",
+        output.text
+    ));
 }
 
-/// Lifecycle callback: after_tool_execution
-/// Logs tool execution details and validates result
-fn after_tool_execution(_state: &mut AgentState, tool_result: &ToolResult) {
-    // Silent - tool result is already in state history
-    // Future: add logging, metrics, validation here
-    let _ = tool_result; // Suppress unused warning
+/// Chat form of a turn's prompt, for chat backends
+fn chat_context(prompt: &PromptSpec, registry: &ToolRegistry) -> ChatContext {
+    let tools: Vec<ToolManifest> = registry.tools().cloned().collect();
+    let names: Vec<&str> = tools
+        .iter()
+        .map(|tool| tool.frontmatter.name.as_str())
+        .collect();
+
+    ChatContext {
+        transcript: prompt.to_transcript(&names),
+        tools,
+    }
 }
 
 /// Report guardrail failure to user with structured output
 ///
 /// Event: AgentFailedAfterGuardrails
 /// Triggered when the agent fails after guardrails reject both initial and retry attempts.
-fn report_guardrail_failure(initial_reason: &str, retry_reason: &str) -> ! {
+fn report_guardrail_failure(events: &EventSink, initial_reason: &str, retry_reason: &str) -> ! {
     let message = format!(
         r#"
 ❌ TASK FAILED: Agent could not produce valid output
@@ -601,12 +2590,15 @@ Note: A correct system that fails honestly is better than one that
         initial_reason, retry_reason
     );
 
-    eprintln!("{}", message);
-    std::process::exit(1);
+    fail(events, "guardrail_failure", message)
 }
 
 /// Report model failure to produce tool call after guardrail rejection
-fn report_inconclusive_after_guardrail_failure(guardrail_reason: &str, model_output: &str) -> ! {
+fn report_inconclusive_after_guardrail_failure(
+    events: &EventSink,
+    guardrail_reason: &str,
+    model_output: &str,
+) -> ! {
     let message = format!(
         r#"
 ❌ TASK FAILED: Model could not recover from validation failure
@@ -631,58 +2623,210 @@ What you can do:
         model_output.lines().next().unwrap_or(model_output)
     );
 
-    eprintln!("{}", message);
-    std::process::exit(1);
+    fail(events, "inconclusive_after_guardrail", message)
+}
+
+/// Report model failure to produce a tool call or answer after a corrective retry
+fn report_inconclusive_after_retry(events: &EventSink, output: &str, retry_output: &str) -> ! {
+    let message = format!(
+        r#"
+❌ ERROR: Model failed to produce a valid response after retry.
+
+Original output: "{}"
+Retry output:    "{}"
+
+The model did not invoke a tool/skill or provide a complete answer.
+This is common with small models (3-4B parameters).
+
+Suggestions:
+  - Use a larger model (7B+ parameters)
+  - Use a model specifically tuned for tool use
+  - Simplify the query"#,
+        output.lines().next().unwrap_or(output),
+        retry_output.lines().next().unwrap_or(retry_output)
+    );
+
+    fail(events, "inconclusive", message)
 }
 
 /// Execute a tool request
-fn execute_tool(request: &ToolRequest) -> Result<ToolResult> {
-    match request.tool.as_str() {
-        "shell" => execute_shell_tool(request),
-        _ => Ok(ToolResult::failure(format!(
-            "Unknown tool: {}",
-            request.tool
-        ))),
+fn execute_tool(
+    request: &ToolRequest,
+    tools: &ToolHost,
+    state: &AgentState,
+    events: &mut EventSink,
+) -> Result<ToolResult> {
+    let request = &tools.coerced(request);
+    if let Some(rejected) = authorize_tool(request, tools, state, events)? {
+        return Ok(rejected.with_call_id(request.call_id));
     }
-}
 
-/// Execute the shell tool with human approval
-fn execute_shell_tool(request: &ToolRequest) -> Result<ToolResult> {
-    // Extract command from params
-    let command = request
-        .params
-        .get("command")
-        .and_then(|v| v.as_str())
-        .ok_or_else(|| anyhow::anyhow!("Missing 'command' parameter"))?;
+    if let Some(result) = tools.replayed(request) {
+        report_tool_output(events, request, &result);
+        return Ok(result.with_call_id(request.call_id));
+    }
 
-    println!("\n→ shell: {}", command);
-    print!("  Execute? (y/n): ");
-    io::stdout().flush()?;
+    let result = match request.tool.as_str() {
+        "shell" => execute_shell_tool(
+            request,
+            tools.confinement(&request.tool),
+            &tools.limits(&request.tool),
+        )?,
+        patch_tool::TOOL_NAME => patch_tool::execute(request, &tools.undo),
+        search_tool::TOOL_NAME => search_tool::execute(request),
+        sql_tool::TOOL_NAME => sql_tool::execute(request),
+        image_tool::TOOL_NAME => image_tool::execute(request),
+        _ => execute_builtin_tool(request)
+            .unwrap_or_else(|| ToolResult::failure(format!("Unknown tool: {}", request.tool))),
+    };
+    let result = tools.perturbed(result);
 
-    let mut input = String::new();
-    io::stdin().read_line(&mut input)?;
+    tools.report_changes(events, request);
+    report_tool_output(events, request, &result);
+    Ok(result.with_call_id(request.call_id))
+}
 
-    if !input.trim().eq_ignore_ascii_case("y") {
-        println!("  ✗ Rejected\n");
-        return Ok(ToolResult::failure("Command rejected by user"));
+/// Check a tool request against its manifest, the pre-execution guardrails and
+/// the approval policy
+///
+/// Returns the failure to hand back to the model when the tool may not run.
+fn authorize_tool(
+    request: &ToolRequest,
+    tools: &ToolHost,
+    state: &AgentState,
+    events: &mut EventSink,
+) -> Result<Option<ToolResult>> {
+    // A call the model made by another name is reported as such
+    if let Some(requested) = request.call_id.and_then(|id| state.aliased_calls.get(&id)) {
+        events.emit(AgentEvent::ToolAliased {
+            requested: requested.clone(),
+            tool: request.tool.clone(),
+        });
     }
-    let output = Command::new("sh").arg("-c").arg(command).output()?;
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let stderr = String::from_utf8_lossy(&output.stderr);
+    // Reject requests that don't match a declared tool before executing anything
+    let safety = match tools.registry.check_request(request) {
+        Ok(manifest) => manifest.frontmatter.safety,
+        Err(e) => {
+            events.emit(AgentEvent::ToolRejected {
+                tool: request.tool.clone(),
+                reason: e.to_string(),
+            });
+            return Ok(Some(ToolResult::failure(e.to_string())));
+        }
+    };
 
-    if output.status.success() {
-        let result = stdout.to_string();
+    events.emit(AgentEvent::ToolProposed {
+        tool: request.tool.clone(),
+        safety,
+        params: request.params.clone(),
+    });
+
+    // Pre-execution guardrails key off the tool's safety class
+    let guard_ctx = PreExecutionContext {
+        state,
+        tool_request: request,
+        safety,
+    };
+    if let GuardrailResult::Reject { reason } = tools.guards.check(&guard_ctx) {
+        events.emit(AgentEvent::ToolRejected {
+            tool: request.tool.clone(),
+            reason: reason.clone(),
+        });
+        return Ok(Some(ToolResult::failure(reason)));
+    }
 
-        // Always show output section, even if empty
-        if !result.is_empty() {
-            println!("\n{}", result);
-        } else {
-            println!("  (no output)\n");
+    // Nobody should be asked to approve a patch that does not apply
+    if request.tool == patch_tool::TOOL_NAME {
+        if let Err(reason) = patch_tool::prepare(request) {
+            events.emit(AgentEvent::ToolRejected {
+                tool: request.tool.clone(),
+                reason: reason.clone(),
+            });
+            return Ok(Some(ToolResult::failure(reason)));
         }
+    }
+
+    if !approve_tool(request, safety, tools, events)? {
+        return Ok(Some(ToolResult::failure("Tool call rejected by user")));
+    }
+
+    Ok(None)
+}
+
+fn report_tool_output(events: &EventSink, request: &ToolRequest, result: &ToolResult) {
+    events.emit(AgentEvent::ToolOutput {
+        tool: request.tool.clone(),
+        success: result.success,
+        output: result.output.clone(),
+        error: result.error.clone(),
+        content_kind: result.content_kind,
+    });
+}
+
+/// Apply the approval policy to a tool request, asking the user if required
+///
+/// Returns whether the tool may run.
+fn approve_tool(
+    request: &ToolRequest,
+    safety: SafetyClass,
+    tools: &ToolHost,
+    events: &mut EventSink,
+) -> Result<bool> {
+    let approved = match tools.policy.decide(&request.tool, safety) {
+        ApprovalMode::Auto => true,
+        ApprovalMode::Deny => false,
+        ApprovalMode::Prompt => return events.request_approval(request, tools.preview(request)),
+    };
+
+    events.emit(AgentEvent::ApprovalDecided {
+        tool: request.tool.clone(),
+        approved,
+        source: ApprovalSource::Policy,
+    });
+    Ok(approved)
+}
+
+/// Execute the shell tool (approval is handled by `execute_tool`)
+fn execute_shell_tool(
+    request: &ToolRequest,
+    confinement: ConfinementLevel,
+    limits: &ResourceLimits,
+) -> Result<ToolResult> {
+    let command = shell_command(request)?;
+    let run = limits::run(confine::shell_process(command, confinement)?, limits)?;
+    Ok(shell_result(command, run))
+}
+
+/// Extract the command from a shell tool request
+fn shell_command(request: &ToolRequest) -> Result<&str> {
+    request
+        .params
+        .get("command")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| anyhow::anyhow!("Missing 'command' parameter"))
+}
 
+/// Turn a finished shell process into a tool result
+///
+/// A search that matched nothing (`grep` exiting with status 1 and no output)
+/// is an empty result, not a failure. A command a resource limit stopped fails
+/// with the limit it hit.
+fn shell_result(command: &str, run: limits::LimitedOutput) -> ToolResult {
+    if let Some(reason) = run.exceeded() {
+        return ToolResult::failure(reason);
+    }
+    let output = run.output;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let no_match = output.status.code() == Some(1)
+        && stdout.is_empty()
+        && stderr.is_empty()
+        && is_search_command(command);
+
+    if output.status.success() || no_match {
         // Send to model (empty output is valid)
-        Ok(ToolResult::success(result))
+        ToolResult::success(stdout.to_string())
     } else {
         let error = if !stderr.is_empty() {
             stderr.to_string()
@@ -690,8 +2834,7 @@ fn execute_shell_tool(request: &ToolRequest) -> Result<ToolResult> {
             format!("Command exited with status {}", output.status)
         };
 
-        println!("  ✗ {}\n", error);
-        Ok(ToolResult::failure(error))
+        ToolResult::failure(error)
     }
 }
 
@@ -704,12 +2847,20 @@ fn execute_shell_tool(request: &ToolRequest) -> Result<ToolResult> {
 /// 3. Validating output against schema and anti-hallucination rules
 fn execute_skill(
     request: &SkillRequest,
-    llm_backend: &mut LlamaCppBackend,
-    max_tokens: usize,
-    current_pos: &mut i32,
+    llm_backend: &mut dyn LLMBackend,
+    reply: ReplyBounds<'_>,
+    skills: &LoadedSkills,
+    events: &EventSink,
 ) -> Result<SkillResult_> {
     match request.skill.as_str() {
-        "extract" => execute_extraction_skill(request, llm_backend, max_tokens, current_pos),
+        "extract" => execute_extraction_skill(
+            request,
+            llm_backend,
+            reply,
+            Chunking::default(),
+            skills.get("extract"),
+            events,
+        ),
         _ => Ok(SkillResult_::failure(SkillError::UnknownSkill(
             request.skill.clone(),
         ))),
@@ -717,93 +2868,252 @@ fn execute_skill(
 }
 
 /// Execute the extraction skill
+///
+/// Texts longer than the chunk size are split into overlapping chunks; the skill
+/// runs once per chunk and the outputs are merged with provenance.
 fn execute_extraction_skill(
     request: &SkillRequest,
-    llm_backend: &mut LlamaCppBackend,
-    max_tokens: usize,
-    current_pos: &mut i32,
+    llm_backend: &mut dyn LLMBackend,
+    reply: ReplyBounds<'_>,
+    chunking: Chunking,
+    skill: Option<&LoadedSkill>,
+    events: &EventSink,
 ) -> Result<SkillResult_> {
-    // Parse and validate input
-    let input = match request.parse_extraction_input() {
-        Ok(input) => input,
-        Err(e) => return Ok(SkillResult_::failure(e)),
+    let (batch, target) = match start_extraction(request, chunking, events) {
+        Ok(started) => started,
+        Err(failure) => return Ok(failure),
     };
 
-    let target = match input.validate() {
-        Ok(target) => target,
-        Err(e) => return Ok(SkillResult_::failure(e)),
-    };
+    let chunks = batch.chunks();
+    let mut results = Vec::with_capacity(chunks.len());
+    for chunk in &chunks {
+        report_chunk_progress(events, chunk, chunks.len());
+        let chunk_input = batch.chunk_input(chunk);
+        match extract_chunk(&chunk_input, target, skill, llm_backend, reply, events)? {
+            Ok(output) => results.push((chunk.clone(), output)),
+            Err(e) => return Ok(SkillResult_::failure(e)),
+        }
+    }
 
-    println!("\n→ skill: extract (target: {})", target.as_str());
-    println!("  Text: \"{}\"", truncate_string(&input.text, 50));
+    Ok(finish_extraction(&batch, target, results))
+}
 
-    // Build extraction prompt
-    let extraction_prompt = build_extraction_prompt(&input, target);
+/// Parse and validate an extraction request and announce it
+///
+/// On invalid input, returns the failure result to report instead.
+fn start_extraction(
+    request: &SkillRequest,
+    chunking: Chunking,
+    events: &EventSink,
+) -> Result<(ExtractionBatch, ExtractionTarget), SkillResult_> {
+    let input = request
+        .parse_extraction_input()
+        .map_err(SkillResult_::failure)?;
+
+    let batch = ExtractionBatch::new(input)
+        .with_chunk_size(chunking.size)
+        .with_overlap(chunking.overlap);
+
+    let target = batch.validate().map_err(SkillResult_::failure)?;
+
+    events.emit(AgentEvent::SkillProposed {
+        skill: request.skill.clone(),
+        target: target.as_str().to_string(),
+        text: batch.input().text.clone(),
+    });
+
+    Ok((batch, target))
+}
 
-    // Call LLM
-    let llm_output = llm_backend.infer(LLMInput {
-        prompt: extraction_prompt,
-        max_tokens,
-        current_pos: *current_pos,
-        first_generation: false,
-    })?;
-
-    *current_pos += llm_output.tokens_processed;
-
-    // Parse LLM output
-    let output = match parse_skill_output(&llm_output.text, target) {
-        Ok(output) => output,
-        Err(e) => {
-            eprintln!("  ✗ {}", e);
-            return Ok(SkillResult_::failure(e));
-        }
-    };
+fn report_chunk_progress(events: &EventSink, chunk: &TextChunk, total: usize) {
+    if total > 1 {
+        events.emit(AgentEvent::SkillProgress {
+            chunk: chunk.index + 1,
+            total,
+            start: chunk.start,
+            end: chunk.end,
+        });
+    }
+}
 
-    // Validate output (anti-hallucination)
-    if let Err(e) = validate_extraction_output(&input, &output, target) {
-        eprintln!("  ✗ {}", e);
-        return Ok(SkillResult_::failure(e));
+/// Combine per-chunk outputs; a single chunk is reported as-is
+///
+/// Merged outputs go through the skill's post-processing again, since
+/// duplicates and ordering span chunks.
+fn finish_extraction(
+    batch: &ExtractionBatch,
+    target: ExtractionTarget,
+    mut results: Vec<(TextChunk, ExtractionOutput)>,
+) -> SkillResult_ {
+    if results.len() == 1 {
+        let (_, output) = results.remove(0);
+        return SkillResult_::success(output.to_value());
     }
+    let merged = batch.merge(target, &results);
+    let merged = match EXTRACTION_SKILL.post_process {
+        Some(post_process) => merged.post_processed(post_process, target),
+        None => merged,
+    };
+    SkillResult_::success(merged.to_value())
+}
+
+/// Run one extraction prompt and validate its output against the given input
+fn extract_chunk(
+    input: &ExtractionInput,
+    target: ExtractionTarget,
+    skill: Option<&LoadedSkill>,
+    llm_backend: &mut dyn LLMBackend,
+    reply: ReplyBounds<'_>,
+    events: &EventSink,
+) -> Result<SkillResult<ExtractionOutput>> {
+    let extraction_prompt = match extraction_prompt(input, target, skill) {
+        Ok(prompt) => prompt,
+        Err(e) => return Ok(Err(e)),
+    };
+
+    // Call LLM
+    let llm_output = generate(
+        llm_backend,
+        LLMInput {
+            prompt: extraction_prompt,
+            max_tokens: reply.max_tokens,
+            stop: reply.stop.to_vec(),
+            first_generation: false,
+            images: Vec::new(),
+            chat: None,
+            progress: None,
+        },
+        "Extracting",
+        events,
+    )?;
+
+    Ok(check_extraction_output(
+        input,
+        target,
+        skill,
+        &llm_output.text,
+    ))
+}
 
-    // Success
-    Ok(SkillResult_::success(output.result))
+/// Build the extraction prompt, reporting template errors as skill failures
+fn extraction_prompt(
+    input: &ExtractionInput,
+    target: ExtractionTarget,
+    skill: Option<&LoadedSkill>,
+) -> SkillResult<String> {
+    build_extraction_prompt(input, target, skill).map_err(|e| {
+        let e = SkillError::InvalidTemplate(e.to_string());
+        console::verbose(format!("Extraction failed: {}", e));
+        e
+    })
+}
+
+/// Parse model output for one extraction, run it through the skill's
+/// guardrails (schema and anti-hallucination unless SKILL.md declares others)
+/// and post-process what passes (deduplicated, sorted)
+fn check_extraction_output(
+    input: &ExtractionInput,
+    target: ExtractionTarget,
+    skill: Option<&LoadedSkill>,
+    text: &str,
+) -> SkillResult<ExtractionOutput> {
+    let guardrails = skill
+        .and_then(|skill| skill.guardrails.clone())
+        .unwrap_or_else(SkillGuardrailChain::extraction);
+    let context = SkillGuardContext {
+        skill: EXTRACTION_SKILL.name,
+        input,
+        target,
+    };
+    let result = parse_skill_json(text)
+        .and_then(|output| guardrails.check(&context, output))
+        .map(|output| match EXTRACTION_SKILL.post_process {
+            Some(post_process) => post_process(output, target),
+            None => output,
+        });
+    if let Err(e) = &result {
+        console::verbose(format!("Extraction failed: {}", e));
+    }
+    result
 }
 
 /// Build prompt for extraction skill
-fn build_extraction_prompt(input: &ExtractionInput, target: ExtractionTarget) -> String {
+///
+/// Renders the template declared in the skill's SKILL.md, or the built-in
+/// default when the manifest declares none. Assets referenced by the manifest
+/// are available to the template as `{{assets.<name>}}`.
+fn build_extraction_prompt(
+    input: &ExtractionInput,
+    target: ExtractionTarget,
+    skill: Option<&LoadedSkill>,
+) -> Result<String> {
+    let classes = input
+        .entity_classes()
+        .iter()
+        .map(|c| c.as_str())
+        .collect::<Vec<_>>();
+
     let target_desc = match target {
-        ExtractionTarget::Email => "email addresses",
-        ExtractionTarget::Url => "URLs",
-        ExtractionTarget::Date => "dates (in ISO format YYYY-MM-DD)",
-        ExtractionTarget::Entity => "named entities (people, organizations, locations)",
-        ExtractionTarget::Name => "person names (first name, last name, full names)",
+        ExtractionTarget::Email => "email addresses".to_string(),
+        ExtractionTarget::Url => "URLs".to_string(),
+        ExtractionTarget::Date => "dates (in ISO format YYYY-MM-DD)".to_string(),
+        ExtractionTarget::Entity => format!("named entities ({})", classes.join(", ")),
+        ExtractionTarget::Name => "person names (first name, last name, full names)".to_string(),
+        ExtractionTarget::Fields => {
+            let mut desc = String::from("these fields:");
+            for (name, description) in input.schema.iter().flatten() {
+                desc.push_str(&format!("\n- {}: {}", name, description));
+            }
+            desc
+        }
     };
 
     let output_format = match target {
         ExtractionTarget::Entity => {
-            r#"{"entity": {"people": [...], "organizations": [...], "locations": [...]}}"#
+            let fields = classes
+                .iter()
+                .map(|c| format!(r#""{}": [...]"#, c))
+                .collect::<Vec<_>>();
+            format!(r#"{{"entity": {{{}}}}}"#, fields.join(", "))
+        }
+        ExtractionTarget::Fields => {
+            let fields = input
+                .schema
+                .iter()
+                .flatten()
+                .map(|(name, _)| format!(r#""{}": "...""#, name))
+                .collect::<Vec<_>>();
+            format!(r#"{{"fields": {{{}}}}}"#, fields.join(", "))
         }
-        _ => &format!(r#"{{"{}": [...]}}"#, target.as_str()),
+        _ => format!(r#"{{"{}": [...]}}"#, target.as_str()),
     };
 
-    format!(
-        r#"Extract {target_desc} from the following text.
-
-IMPORTANT:
-- Output ONLY valid JSON
-- Only include values that ACTUALLY APPEAR in the text
-- Do NOT invent or hallucinate values
-- If no matches found, return an empty array
-
-Text: "{text}"
+    let no_match = match target {
+        ExtractionTarget::Fields => {
+            "Include every field exactly once; use null if a field is not found"
+        }
+        _ => "If no matches found, return an empty array",
+    };
 
-Output format: {output_format}
+    let assets = match skill {
+        Some(skill) => skill.assets.clone(),
+        None => json!({}),
+    };
 
-JSON output:"#,
-        target_desc = target_desc,
-        text = input.text,
-        output_format = output_format
-    )
+    let context = json!({
+        "input": input,
+        "target": target.as_str(),
+        "target_desc": target_desc,
+        "output_format": output_format,
+        "no_match": no_match,
+        "assets": assets,
+    });
+
+    let prompt = match skill.and_then(|s| s.template.as_ref()) {
+        Some(template) => template.render(&context)?,
+        None => PromptTemplate::parse(DEFAULT_EXTRACTION_TEMPLATE)?.render(&context)?,
+    };
+    Ok(prompt)
 }
 
 /// Truncate string for display
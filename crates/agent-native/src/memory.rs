@@ -0,0 +1,133 @@
+//! `--memory`: recall what earlier runs found
+//!
+//! The memory file holds a [`MemoryStore`] as JSON. When a run answers, the
+//! full text of its tool outputs and a note of its question and answer are
+//! chunked, embedded and added to it; runs that end without an answer leave
+//! nothing. The model searches it with `{"recall": "..."}`, and the host
+//! answers with the most similar chunks. Embeddings come from the run's
+//! backend (see [`EmbeddingWorker`]), so keep using the same model with one
+//! memory file.
+
+use crate::artifacts::RunArtifacts;
+use crate::audit::RunId;
+use crate::console;
+use crate::embeddings::EmbeddingWorker;
+use agent_core::agent::AgentState;
+use agent_core::memory::{chunk_text, recall_output, session_note, MemoryStore, RecallRequest};
+use agent_core::tool::ToolResult;
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Chunks embedded per request when a run is added
+const EMBED_BATCH: usize = 32;
+
+/// A memory file and the embedder its entries come from
+pub struct RunMemory {
+    path: PathBuf,
+    store: MemoryStore,
+    embedder: Arc<EmbeddingWorker>,
+}
+
+impl RunMemory {
+    /// Load the memory in `path`, or start one; `model` names the embedding
+    /// model
+    pub fn open(path: &Path, model: String, embedder: Arc<EmbeddingWorker>) -> Result<Self> {
+        let store = if path.exists() {
+            let json = fs::read_to_string(path)
+                .with_context(|| format!("Cannot read {}", path.display()))?;
+            let store: MemoryStore = serde_json::from_str(&json)
+                .with_context(|| format!("Invalid memory file {}", path.display()))?;
+            if store.model != model {
+                console::warn(format!(
+                    "{} was built with {}; recalls with {} may miss",
+                    path.display(),
+                    store.model,
+                    model
+                ));
+            }
+            store
+        } else {
+            MemoryStore::new(model)
+        };
+        console::verbose(format!(
+            "Memory: {} chunk(s) in {}",
+            store.len(),
+            path.display()
+        ));
+        Ok(Self {
+            path: path.to_path_buf(),
+            store,
+            embedder,
+        })
+    }
+
+    /// The chunks most similar to the request's query, for the model
+    pub fn recall(&self, request: &RecallRequest) -> ToolResult {
+        let query = match self.embedder.embed_texts(vec![request.query.clone()]) {
+            Ok(mut embeddings) if embeddings.len() == 1 => embeddings.remove(0),
+            Ok(_) => return ToolResult::failure("The embedder returned no embedding"),
+            Err(err) => return ToolResult::failure(format!("Recall failed: {}", err)),
+        };
+        let hits = self.store.search(&query, request.k());
+        ToolResult::success(recall_output(request, &hits))
+    }
+
+    /// Add the outputs and the answer of a run that answered, and save
+    ///
+    /// A run that cannot be embedded is left out with a warning.
+    pub fn save_run(
+        &mut self,
+        run: &RunId,
+        state: &AgentState,
+        artifacts: &RunArtifacts,
+    ) -> Result<()> {
+        let mut chunks: Vec<(String, String)> = Vec::new();
+        for (id, output) in artifacts.archived() {
+            let source = format!("{}/{}", run.as_str(), id);
+            chunks.extend(
+                chunk_text(output)
+                    .into_iter()
+                    .map(|text| (source.clone(), text)),
+            );
+        }
+        if let Some(note) = session_note(state) {
+            let source = format!("{}/answer", run.as_str());
+            chunks.extend(
+                chunk_text(&note)
+                    .into_iter()
+                    .map(|text| (source.clone(), text)),
+            );
+        }
+
+        let mut embeddings = Vec::with_capacity(chunks.len());
+        for batch in chunks.chunks(EMBED_BATCH) {
+            let texts = batch.iter().map(|(_, text)| text.clone()).collect();
+            match self.embedder.embed_texts(texts) {
+                Ok(batch) => embeddings.extend(batch),
+                Err(err) => {
+                    console::warn(format!("The run was not added to memory: {}", err));
+                    return Ok(());
+                }
+            }
+        }
+        if embeddings.len() != chunks.len() {
+            console::warn("The run was not added to memory: the embedder skipped texts");
+            return Ok(());
+        }
+
+        let added = chunks.len();
+        for ((source, text), embedding) in chunks.into_iter().zip(embeddings) {
+            self.store.add(source, text, embedding);
+        }
+        fs::write(&self.path, serde_json::to_string(&self.store)?)
+            .with_context(|| format!("Cannot write {}", self.path.display()))?;
+        console::verbose(format!(
+            "Remembered {} chunk(s) in {}",
+            added,
+            self.path.display()
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,124 @@
+//! mistral.rs HTTP server backend
+//!
+//! Attaches to a running `mistralrs-server` through its text completion
+//! endpoint (`/v1/completions`: prompt in, text out), so the agent's own prompt
+//! format is sent unchanged rather than re-templated as chat messages.
+//! Embeddings go to `/v1/embeddings`, for servers running an embedding model.
+
+use crate::embeddings::openai_embeddings;
+use crate::llm::{LLMBackend, LLMInput, LLMOutput, Sampling};
+use crate::throttle::{estimate_tokens, http_agent, Throttle};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+
+/// Address used by the mistral.rs server examples (`--port 1234`)
+pub const DEFAULT_ENDPOINT: &str = "http://localhost:1234";
+
+/// Model id sent when none is configured; a single-model server accepts any id
+pub const DEFAULT_MODEL_ID: &str = "default";
+
+/// Client for a mistral.rs server instance
+pub struct MistralRsBackend {
+    agent: ureq::Agent,
+    throttle: Arc<Throttle>,
+    url: String,
+    embeddings_url: String,
+    model: String,
+    sampling: Sampling,
+}
+
+#[derive(Debug, Deserialize)]
+struct CompletionResponse {
+    choices: Vec<CompletionChoice>,
+    #[serde(default)]
+    usage: Option<Usage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CompletionChoice {
+    text: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Usage {
+    total_tokens: i32,
+}
+
+impl MistralRsBackend {
+    /// Connect to the server at `endpoint` (e.g. `http://localhost:1234`)
+    pub fn new(endpoint: &str, model: &str) -> Self {
+        Self {
+            agent: http_agent(),
+            throttle: Arc::default(),
+            url: format!("{}/v1/completions", endpoint.trim_end_matches('/')),
+            embeddings_url: format!("{}/v1/embeddings", endpoint.trim_end_matches('/')),
+            model: model.to_string(),
+            sampling: Sampling::default(),
+        }
+    }
+
+    /// Send through `throttle`, shared with other backends holding it
+    pub fn with_throttle(mut self, throttle: Arc<Throttle>) -> Self {
+        self.throttle = throttle;
+        self
+    }
+
+    pub fn with_sampling(mut self, sampling: Sampling) -> Self {
+        self.sampling = sampling;
+        self
+    }
+}
+
+impl LLMBackend for MistralRsBackend {
+    fn infer(&mut self, input: LLMInput) -> Result<LLMOutput> {
+        let mut body = json!({
+            "model": self.model,
+            "prompt": input.prompt,
+            "max_tokens": input.max_tokens,
+        });
+        if let Some(temperature) = self.sampling.temperature {
+            body["temperature"] = json!(temperature);
+        }
+        if let Some(seed) = self.sampling.seed {
+            body["seed"] = json!(seed);
+        }
+        if !input.stop.is_empty() {
+            body["stop"] = json!(input.stop);
+        }
+
+        let response: CompletionResponse = self
+            .throttle
+            .send(estimate_tokens(&input), || {
+                self.agent.post(&self.url).send_json(&body)
+            })
+            .with_context(|| format!("Request to mistral.rs server at {} failed", self.url))?
+            .body_mut()
+            .read_json()
+            .context("Invalid response from mistral.rs server")?;
+
+        let text = response
+            .choices
+            .into_iter()
+            .next()
+            .map(|choice| choice.text)
+            .context("mistral.rs server returned no completion")?;
+
+        Ok(LLMOutput {
+            text,
+            tokens_processed: response.usage.map_or(0, |usage| usage.total_tokens),
+        })
+    }
+
+    fn embed(&mut self, texts: &[String]) -> Result<Option<Vec<Vec<f32>>>> {
+        openai_embeddings(
+            &self.agent,
+            &self.throttle,
+            &self.embeddings_url,
+            Some(&self.model),
+            texts,
+        )
+        .map(Some)
+    }
+}
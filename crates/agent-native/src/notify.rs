@@ -0,0 +1,110 @@
+//! `--notify-url` and `--notify-command`: tell others how a batch went
+//!
+//! Each [`BatchNotification`] the batch's [`NotifyOn`] asks for is POSTed as
+//! JSON to every URL and written to the stdin of every command (run with
+//! `sh -c`, the event name in `AGENT_EVENT`). Webhooks and commands get
+//! [`NOTIFY_TIMEOUT`] each. A notification that cannot be delivered is a
+//! warning: it never fails the batch.
+
+use crate::console;
+use agent_core::batch::{BatchNotification, NotifyOn};
+use std::io::Write;
+use std::os::unix::process::CommandExt;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Time a webhook has to answer, or a command to exit before it is killed
+const NOTIFY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often a running command is checked
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Where a batch's notifications go
+#[derive(Debug, Clone)]
+pub struct Notifier {
+    urls: Vec<String>,
+    commands: Vec<String>,
+    on: NotifyOn,
+}
+
+impl Notifier {
+    pub fn new(urls: Vec<String>, commands: Vec<String>, on: NotifyOn) -> Self {
+        Self { urls, commands, on }
+    }
+
+    /// Deliver `notification`, if it is one to send
+    pub fn send(&self, notification: &BatchNotification) {
+        if !notification.is_sent(self.on) {
+            return;
+        }
+        let json = serde_json::to_string(notification).expect("notifications serialize");
+        for url in &self.urls {
+            if let Err(e) = post(url, &json) {
+                console::warn(format!("Cannot notify {}: {}", url, e));
+            }
+        }
+        for command in &self.commands {
+            if let Err(e) = exec(command, notification.event(), &json) {
+                console::warn(format!("Notification command '{}' failed: {}", command, e));
+            }
+        }
+    }
+}
+
+fn post(url: &str, json: &str) -> Result<(), String> {
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .http_status_as_error(false)
+        .timeout_global(Some(NOTIFY_TIMEOUT))
+        .build()
+        .into();
+    let response = agent
+        .post(url)
+        .header("Content-Type", "application/json")
+        .send(json)
+        .map_err(|e| e.to_string())?;
+    match response.status() {
+        status if status.is_success() => Ok(()),
+        status => Err(format!("HTTP {}", status)),
+    }
+}
+
+fn exec(command: &str, event: &str, json: &str) -> Result<(), String> {
+    // A group of its own, so a command that times out takes its pipeline with it
+    let mut child = Command::new("sh")
+        .args(["-c", command])
+        .process_group(0)
+        .env("AGENT_EVENT", event)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .map_err(|e| e.to_string())?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // Written aside, so a command that does not read its input cannot
+        // block the batch on a full pipe; the write fails once it exits
+        let json = json.to_string();
+        thread::spawn(move || {
+            let _ = writeln!(stdin, "{}", json);
+        });
+    }
+
+    let deadline = Instant::now() + NOTIFY_TIMEOUT;
+    let status = loop {
+        if let Some(status) = child.try_wait().map_err(|e| e.to_string())? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            // SAFETY: sends a signal to the command's process group only
+            unsafe {
+                libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+            }
+            let _ = child.wait();
+            return Err(format!("timed out after {}s", NOTIFY_TIMEOUT.as_secs()));
+        }
+        thread::sleep(POLL_INTERVAL);
+    };
+    match status.success() {
+        true => Ok(()),
+        false => Err(status.to_string()),
+    }
+}
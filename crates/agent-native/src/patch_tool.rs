@@ -0,0 +1,167 @@
+//! The `apply_patch` tool
+//!
+//! Applies a unified diff from the model to files under the working directory.
+//! The patch is checked against the current files before the user is asked to
+//! approve it, and written only if every hunk applies: new contents go to
+//! temporary files next to their targets first, then replace them. The files'
+//! current contents are saved for `agent undo` before anything is written.
+
+use crate::undo::UndoJournal;
+use agent_core::diff::unified_diff;
+use agent_core::patch::parse_patch;
+use agent_core::tool::{ToolRequest, ToolResult};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub const TOOL_NAME: &str = "apply_patch";
+
+/// A patch applied in memory, ready to be written
+#[derive(Debug)]
+pub struct PreparedPatch {
+    changes: Vec<FileChange>,
+}
+
+#[derive(Debug)]
+struct FileChange {
+    path: PathBuf,
+    /// Current content, or `None` for a new file
+    original: Option<String>,
+    /// New content, or `None` to delete the file
+    content: Option<String>,
+    summary: String,
+}
+
+/// Parse the request's patch and apply it to the current files in memory
+///
+/// The error is the message handed back to the model.
+pub fn prepare(request: &ToolRequest) -> Result<PreparedPatch, String> {
+    let text = request
+        .params
+        .get("patch")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing 'patch' parameter")?;
+    let patch = parse_patch(text).map_err(|e| e.to_string())?;
+
+    let mut changes = Vec::new();
+    for file in &patch.files {
+        let path = PathBuf::from(file.path());
+        let original = match fs::read_to_string(&path) {
+            Ok(_) if file.is_creation() => return Err(format!("'{}' already exists", file.path())),
+            Ok(content) => Some(content),
+            Err(e) if e.kind() == io::ErrorKind::NotFound && file.is_creation() => None,
+            Err(e) => return Err(format!("Cannot read '{}': {}", file.path(), e)),
+        };
+
+        let content = file.apply(original.as_deref()).map_err(|e| e.to_string())?;
+        let (added, removed) = file.stats();
+        let kind = match (file.is_creation(), file.is_deletion()) {
+            (true, _) => 'A',
+            (_, true) => 'D',
+            _ => 'M',
+        };
+        changes.push(FileChange {
+            path,
+            original,
+            content,
+            summary: format!("{} {} (+{} -{})", kind, file.path(), added, removed),
+        });
+    }
+
+    Ok(PreparedPatch { changes })
+}
+
+impl PreparedPatch {
+    /// One line per file: `M src/lib.rs (+3 -1)`
+    pub fn summary(&self) -> Vec<&str> {
+        self.changes.iter().map(|c| c.summary.as_str()).collect()
+    }
+
+    /// The changes as a unified diff against the current files, with the line
+    /// numbers they will really land at
+    pub fn diff(&self) -> String {
+        self.changes
+            .iter()
+            .map(|change| {
+                unified_diff(
+                    &change.path.to_string_lossy(),
+                    change.original.as_deref(),
+                    change.content.as_deref(),
+                )
+            })
+            .collect()
+    }
+
+    /// Write all changes, or none if any temporary file (or pre-image for
+    /// `undo`) cannot be written
+    pub fn write(self, undo: &UndoJournal) -> io::Result<String> {
+        let journaled = self
+            .changes
+            .iter()
+            .map(|change| {
+                undo.save(
+                    &change.path,
+                    change.original.as_deref(),
+                    change.content.as_deref(),
+                )
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let mut staged = Vec::new();
+        for change in &self.changes {
+            let Some(content) = &change.content else {
+                continue;
+            };
+            let temp = temp_path(&change.path);
+            let written = change
+                .path
+                .parent()
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|()| fs::write(&temp, content));
+            if let Err(e) = written {
+                for temp in &staged {
+                    let _ = fs::remove_file(temp);
+                }
+                return Err(e);
+            }
+            staged.push(temp);
+        }
+
+        undo.record(journaled);
+        for change in &self.changes {
+            match change.content {
+                Some(_) => fs::rename(temp_path(&change.path), &change.path)?,
+                None => fs::remove_file(&change.path)?,
+            }
+        }
+
+        let summary = self.summary();
+        Ok(format!(
+            "Patched {} file(s):\n{}\n",
+            summary.len(),
+            summary.join("\n")
+        ))
+    }
+}
+
+/// Temporary file next to `path`, so the final rename stays on one filesystem
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.agent-patch", name))
+}
+
+/// Execute the patch tool (approval is handled by `execute_tool`)
+pub fn execute(request: &ToolRequest, undo: &UndoJournal) -> ToolResult {
+    let prepared = match prepare(request) {
+        Ok(prepared) => prepared,
+        Err(e) => return ToolResult::failure(e),
+    };
+    match prepared.write(undo) {
+        Ok(summary) => ToolResult::success(summary),
+        Err(e) => ToolResult::failure(format!("Failed to write patch: {}", e)),
+    }
+}
@@ -0,0 +1,152 @@
+//! What a tool call will do, shown before it is approved
+//!
+//! The approval prompt (text or JSONL) states the call as it will run rather
+//! than as the model wrote it: the shell command with the directory it runs
+//! in and its confinement, the diff a patch writes against the current files,
+//! the resolved paths a search or query reads. Tools without a dedicated
+//! preview list their parameters.
+
+use crate::{image_tool, limits, patch_tool, search_tool, sql_tool, ToolHost};
+use agent_core::search::SearchRequest;
+use agent_core::sql::SqlRequest;
+use agent_core::tool::ToolRequest;
+use serde::Serialize;
+use serde_json::Value;
+use std::path::Path;
+
+/// A tool call as it will run
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Preview {
+    pub fields: Vec<PreviewField>,
+    /// Changes to files, as a unified diff
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PreviewField {
+    pub label: String,
+    pub value: String,
+}
+
+impl Preview {
+    fn with_field(mut self, label: &str, value: impl Into<String>) -> Self {
+        self.fields.push(PreviewField {
+            label: label.to_string(),
+            value: value.into(),
+        });
+        self
+    }
+}
+
+/// Renders tool calls for approval
+pub trait ToolPreview {
+    fn preview(&self, request: &ToolRequest) -> Preview;
+}
+
+impl ToolPreview for ToolHost {
+    fn preview(&self, request: &ToolRequest) -> Preview {
+        match request.tool.as_str() {
+            "shell" => shell(self, request),
+            patch_tool::TOOL_NAME => patch(request),
+            search_tool::TOOL_NAME => search(request),
+            sql_tool::TOOL_NAME => sql(request),
+            image_tool::TOOL_NAME => image(request),
+            _ => params(request),
+        }
+    }
+}
+
+fn shell(tools: &ToolHost, request: &ToolRequest) -> Preview {
+    let command = request
+        .params
+        .get("command")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    Preview::default()
+        .with_field("Working directory", working_dir())
+        .with_field("Command (sh -c)", command)
+        .with_field("Confinement", tools.confinement(&request.tool).as_str())
+        .with_field("Limits", limits::describe(&tools.limits(&request.tool)))
+}
+
+fn patch(request: &ToolRequest) -> Preview {
+    match patch_tool::prepare(request) {
+        Ok(prepared) => {
+            let preview = Preview {
+                diff: Some(prepared.diff()),
+                ..Preview::default()
+            };
+            prepared
+                .summary()
+                .into_iter()
+                .fold(preview, |preview, file| preview.with_field("File", file))
+        }
+        // Rejected before approval; shown as written if it ever gets here
+        Err(_) => params(request),
+    }
+}
+
+fn search(request: &ToolRequest) -> Preview {
+    let Ok(search) = SearchRequest::from_params(&request.params) else {
+        return params(request);
+    };
+    let preview = Preview::default()
+        .with_field("Pattern", search.pattern)
+        .with_field("Path", resolved(&search.path));
+    let preview = match search.glob {
+        Some(glob) => preview.with_field("Files", glob),
+        None => preview,
+    };
+    if search.ignore_case {
+        return preview.with_field("Case", "ignored");
+    }
+    preview
+}
+
+fn sql(request: &ToolRequest) -> Preview {
+    let Ok(sql) = SqlRequest::from_params(&request.params) else {
+        return params(request);
+    };
+    Preview::default()
+        .with_field("Database", resolved(&sql.database))
+        .with_field("Query", sql.query)
+}
+
+fn image(request: &ToolRequest) -> Preview {
+    match request.params.get("path").and_then(Value::as_str) {
+        Some(path) => Preview::default().with_field("Image", resolved(path)),
+        None => params(request),
+    }
+}
+
+/// Each parameter on its own line
+fn params(request: &ToolRequest) -> Preview {
+    let Some(map) = request.params.as_object() else {
+        return Preview::default().with_field("Parameters", request.params.to_string());
+    };
+    map.iter()
+        .fold(Preview::default(), |preview, (name, value)| {
+            let value = match value {
+                Value::String(text) => text.clone(),
+                other => other.to_string(),
+            };
+            preview.with_field(name, value)
+        })
+}
+
+fn working_dir() -> String {
+    std::env::current_dir()
+        .map(|dir| dir.display().to_string())
+        .unwrap_or_else(|e| format!("unknown ({})", e))
+}
+
+/// `path` under the working directory, as an absolute path
+fn resolved(path: &str) -> String {
+    let relative = path.trim_start_matches("./");
+    match std::env::current_dir() {
+        Ok(dir) if relative == "." || relative.is_empty() => dir.display().to_string(),
+        Ok(dir) => dir.join(Path::new(relative)).display().to_string(),
+        Err(_) => path.to_string(),
+    }
+}
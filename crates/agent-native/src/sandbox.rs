@@ -0,0 +1,301 @@
+//! Copy-on-write workspace for `--sandbox` runs
+//!
+//! The workspace is copied to a temporary directory and the run works in the
+//! copy, so mutating tools (and auto-approved ones in particular) cannot touch
+//! the real tree. When the run ends, the changes are shown as a diff and
+//! applied only if the user approves.
+//!
+//! `.git` and files ignored by `.gitignore` are neither copied nor carried
+//! back; symlinks are copied as links and never applied, and changes are not
+//! written through a symlink in the workspace.
+//!
+//! Changes are what the run did to the copy: each copied file's hash is kept,
+//! and a file the run touched that has since changed in the workspace too is a
+//! conflict, which stops the whole apply.
+//!
+//! The copy is removed when the [`Sandbox`] is dropped, or by [`discard`] for
+//! runs that end with `process::exit`, which skips destructors.
+
+use agent_core::audit::sha256_hex;
+use agent_core::diff::unified_diff;
+use anyhow::{bail, Context, Result};
+use ignore::WalkBuilder;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Root of the live sandbox, for [`discard`]
+static ACTIVE: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Remove the live sandbox's copy; call before `process::exit`
+pub fn discard() {
+    if let Some(root) = ACTIVE.lock().unwrap_or_else(|e| e.into_inner()).take() {
+        let _ = fs::remove_dir_all(root);
+    }
+}
+
+/// A file the run created, changed or deleted
+#[derive(Debug)]
+pub struct Change {
+    /// Path relative to the workspace
+    pub path: PathBuf,
+    /// Hash of the file when it was copied, `None` when the run created it
+    original: Option<String>,
+    /// New content, `None` when the run deleted the file
+    content: Option<Vec<u8>>,
+}
+
+/// A temporary copy of a workspace
+#[derive(Debug)]
+pub struct Sandbox {
+    workspace: PathBuf,
+    root: PathBuf,
+    /// Workspace-relative paths left out of the copy (the artifacts directory)
+    excluded: Vec<PathBuf>,
+    /// Hash of each file as copied, by workspace-relative path
+    snapshot: BTreeMap<PathBuf, String>,
+}
+
+impl Sandbox {
+    /// Copy `workspace` into a new temporary directory, leaving out `excluded`
+    /// paths (absolute, or relative to the workspace)
+    pub fn create(workspace: &Path, excluded: &[PathBuf]) -> Result<Self> {
+        let workspace = workspace
+            .canonicalize()
+            .with_context(|| format!("Cannot open workspace {}", workspace.display()))?;
+        let started = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        let root =
+            std::env::temp_dir().join(format!("agent-sandbox-{}-{}", started, std::process::id()));
+        let excluded = excluded
+            .iter()
+            .filter_map(|path| {
+                let path = workspace.join(path);
+                path.strip_prefix(&workspace).ok().map(Path::to_path_buf)
+            })
+            .collect();
+
+        let mut sandbox = Self {
+            workspace,
+            root,
+            excluded,
+            snapshot: BTreeMap::new(),
+        };
+        // A directory already at this path is not ours to use
+        fs::create_dir(&sandbox.root)
+            .with_context(|| format!("Cannot create sandbox {}", sandbox.root.display()))?;
+        *ACTIVE.lock().unwrap_or_else(|e| e.into_inner()) = Some(sandbox.root.clone());
+        sandbox.snapshot = sandbox.copy_workspace()?;
+        Ok(sandbox)
+    }
+
+    /// Directory the run works in
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The real workspace the sandbox was copied from
+    pub fn workspace(&self) -> &Path {
+        &self.workspace
+    }
+
+    /// Copy the workspace, returning the hash of each file copied
+    fn copy_workspace(&self) -> Result<BTreeMap<PathBuf, String>> {
+        let mut snapshot = BTreeMap::new();
+        for relative in self.files(&self.workspace, true) {
+            let source = self.workspace.join(&relative);
+            let target = self.root.join(&relative);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            if source.is_symlink() {
+                std::os::unix::fs::symlink(fs::read_link(&source)?, &target)?;
+            } else {
+                // Hash what was copied, not what the workspace holds later
+                let content = fs::read(&source)
+                    .with_context(|| format!("Cannot copy {}", source.display()))?;
+                fs::write(&target, &content)
+                    .with_context(|| format!("Cannot copy {}", source.display()))?;
+                snapshot.insert(relative, sha256_hex(&content));
+            }
+        }
+        Ok(snapshot)
+    }
+
+    /// Workspace-relative paths of the files under `dir`, symlinks included
+    /// only if `with_links`
+    fn files(&self, dir: &Path, with_links: bool) -> BTreeSet<PathBuf> {
+        let excluded = self.excluded.clone();
+        let base = dir.to_path_buf();
+        WalkBuilder::new(dir)
+            .hidden(false)
+            .require_git(false)
+            .filter_entry(move |entry| {
+                let relative = entry.path().strip_prefix(&base).unwrap_or(entry.path());
+                entry.file_name() != ".git" && !excluded.iter().any(|path| relative == path)
+            })
+            .build()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                entry
+                    .file_type()
+                    .is_some_and(|kind| kind.is_file() || (with_links && kind.is_symlink()))
+            })
+            .filter_map(|entry| entry.path().strip_prefix(dir).ok().map(Path::to_path_buf))
+            .collect()
+    }
+
+    /// Files the run created, changed or deleted, against the copy it started
+    /// from
+    pub fn changes(&self) -> Result<Vec<Change>> {
+        let before: BTreeSet<PathBuf> = self.snapshot.keys().cloned().collect();
+        let after = self.files(&self.root, false);
+
+        let mut changes = Vec::new();
+        for path in before.union(&after) {
+            let original = self.snapshot.get(path);
+            let new = read_if_exists(&self.root.join(path))?;
+            if new.as_deref().map(sha256_hex).as_ref() != original {
+                changes.push(Change {
+                    path: path.clone(),
+                    original: original.cloned(),
+                    content: new,
+                });
+            }
+        }
+        Ok(changes)
+    }
+
+    /// Workspace content of a changed file, `None` when the workspace no
+    /// longer holds what was copied (a conflict)
+    fn original(&self, change: &Change) -> Result<Option<Option<Vec<u8>>>> {
+        let current = read_if_exists(&self.workspace.join(&change.path))?;
+        Ok((current.as_deref().map(sha256_hex) == change.original).then_some(current))
+    }
+
+    /// Unified diff of the changes against the workspace, with conflicts
+    /// noted instead of diffed
+    pub fn diff(&self, changes: &[Change]) -> Result<String> {
+        let mut diff = String::new();
+        for change in changes {
+            let path = change.path.to_string_lossy();
+            let Some(old) = self.original(change)? else {
+                diff.push_str(&format!(
+                    "Conflict: {} changed in the workspace during the run\n",
+                    path
+                ));
+                continue;
+            };
+            match (text(old.as_deref()), text(change.content.as_deref())) {
+                (Some(old), Some(new)) => diff.push_str(&unified_diff(&path, old, new)),
+                _ => diff.push_str(&format!("Binary file {} differs\n", path)),
+            }
+        }
+        Ok(diff)
+    }
+
+    /// Write the changes to the real workspace
+    ///
+    /// Fails before writing anything if a changed file also changed in the
+    /// workspace during the run, or if a change would go through a symlink of
+    /// the workspace (the file itself or a directory above it), which could
+    /// point outside it. New contents are staged in temporary files next to
+    /// their targets and renamed into place once all are written.
+    pub fn apply(&self, changes: &[Change]) -> Result<()> {
+        let mut conflicts = Vec::new();
+        for change in changes {
+            if self.original(change)?.is_none() {
+                conflicts.push(change.path.to_string_lossy());
+            }
+        }
+        if !conflicts.is_empty() {
+            bail!(
+                "Not applying the sandbox: changed in the workspace during the run: {}",
+                conflicts.join(", ")
+            );
+        }
+        for change in changes {
+            let mut path = self.workspace.clone();
+            for component in change.path.components() {
+                path.push(component);
+                if path.is_symlink() {
+                    bail!(
+                        "Not applying {}: {} is a symlink in the workspace",
+                        change.path.display(),
+                        path.display()
+                    );
+                }
+            }
+        }
+
+        let mut staged = Vec::new();
+        for change in changes {
+            let Some(content) = &change.content else {
+                continue;
+            };
+            let target = self.workspace.join(&change.path);
+            let temp = temp_path(&target);
+            let written = target
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|()| fs::write(&temp, content));
+            if let Err(e) = written {
+                for temp in &staged {
+                    let _ = fs::remove_file(temp);
+                }
+                return Err(e).with_context(|| format!("Cannot write {}", target.display()));
+            }
+            staged.push(temp);
+        }
+
+        for change in changes {
+            let target = self.workspace.join(&change.path);
+            match change.content {
+                Some(_) => fs::rename(temp_path(&target), &target)
+                    .with_context(|| format!("Cannot write {}", target.display()))?,
+                None => fs::remove_file(&target)
+                    .with_context(|| format!("Cannot remove {}", target.display()))?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+        let mut active = ACTIVE.lock().unwrap_or_else(|e| e.into_inner());
+        if active.as_ref() == Some(&self.root) {
+            *active = None;
+        }
+    }
+}
+
+/// Temporary file next to `path`, so the final rename stays on one filesystem
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.agent-sandbox", name))
+}
+
+fn read_if_exists(path: &Path) -> Result<Option<Vec<u8>>> {
+    match fs::read(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("Cannot read {}", path.display())),
+    }
+}
+
+/// File content as text, `Some(None)` for a missing file and `None` for
+/// binary content
+fn text(content: Option<&[u8]>) -> Option<Option<&str>> {
+    match content {
+        Some(bytes) => std::str::from_utf8(bytes).ok().map(Some),
+        None => Some(None),
+    }
+}
@@ -0,0 +1,115 @@
+//! `--record` and `--replay`: scenarios on disk
+//!
+//! A recording run writes `<dir>/scenario.json` (see [`agent_core::scenario`])
+//! as it goes, so a run that crashes or is interrupted still leaves what it got
+//! through. Model calls are recorded from [`generate`](crate::generate); tool
+//! calls, the query and the answer are taken from the run's events, which
+//! covers both runners.
+
+use crate::console;
+use crate::events::AgentEvent;
+use agent_core::scenario::{ModelCall, Scenario, ToolCall, SCENARIO_FILE};
+use agent_core::tool::ToolResult;
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Records the current run into a fixture directory
+#[derive(Debug)]
+pub struct Recorder {
+    path: PathBuf,
+    scenario: Scenario,
+    /// Tool call proposed but not executed yet
+    pending: Option<(String, Value)>,
+}
+
+impl Recorder {
+    /// Start recording into `dir`, replacing any scenario already there
+    pub fn create(dir: &Path) -> Result<Self> {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        let recorder = Self {
+            path: std::path::absolute(dir.join(SCENARIO_FILE))?,
+            scenario: Scenario::default(),
+            pending: None,
+        };
+        recorder.save()?;
+        Ok(recorder)
+    }
+
+    /// Record what `event` says about the run
+    pub fn observe(&mut self, event: &AgentEvent) {
+        match event {
+            AgentEvent::Started { query, .. } => self.scenario.query = query.clone(),
+            AgentEvent::ToolProposed { tool, params, .. } => {
+                self.pending = Some((tool.clone(), params.clone()));
+                return;
+            }
+            AgentEvent::ToolRejected { .. } => {
+                self.pending = None;
+                return;
+            }
+            AgentEvent::ApprovalDecided {
+                approved: false, ..
+            } => {
+                self.pending = None;
+                return;
+            }
+            // Artifact reads and scratchpad operations are not proposed first
+            AgentEvent::ToolOutput {
+                success,
+                output,
+                error,
+                content_kind,
+                ..
+            } => {
+                let Some((tool, params)) = self.pending.take() else {
+                    return;
+                };
+                self.scenario.tool_calls.push(ToolCall {
+                    tool,
+                    params,
+                    result: ToolResult {
+                        success: *success,
+                        output: output.clone(),
+                        error: error.clone(),
+                        call_id: None,
+                        content_kind: *content_kind,
+                    },
+                });
+            }
+            AgentEvent::FinalAnswer { answer } => self.scenario.answer = Some(answer.clone()),
+            _ => return,
+        }
+        self.save_or_warn();
+    }
+
+    /// Record one model call
+    pub fn model_call(&mut self, prompt: String, output: &str) {
+        self.scenario.model_calls.push(ModelCall {
+            prompt,
+            output: output.to_string(),
+        });
+        self.save_or_warn();
+    }
+
+    fn save(&self) -> Result<()> {
+        let json = serde_json::to_string_pretty(&self.scenario)?;
+        fs::write(&self.path, json)
+            .with_context(|| format!("Failed to write {}", self.path.display()))
+    }
+
+    fn save_or_warn(&self) {
+        if let Err(e) = self.save() {
+            console::warn(format!("{:#}", e));
+        }
+    }
+}
+
+/// Load the scenario recorded in fixture directory `dir`
+pub fn load(dir: &Path) -> Result<Scenario> {
+    let path = dir.join(SCENARIO_FILE);
+    let json =
+        fs::read_to_string(&path).with_context(|| format!("Cannot read {}", path.display()))?;
+    serde_json::from_str(&json).with_context(|| format!("Cannot load {}", path.display()))
+}
@@ -0,0 +1,204 @@
+//! `agent schedule`: run task files on cron schedules
+//!
+//! The scheduler stays in the foreground and wakes at the start of every
+//! minute. Each job that is due runs as a child `agent-native run <task>`
+//! process through the eval runner, with the backend flags given to
+//! `schedule`, the arguments after `--` and the job's own `args`. A job still
+//! running when it is due again is skipped. Every run, skipped ones included,
+//! is appended to `<history dir>/<job>.jsonl`. As in `batch`, nobody answers
+//! approval prompts: calls that would prompt are declined unless `--yes` is
+//! given. See [`agent_core::schedule`] for the file format.
+
+use crate::console;
+use crate::environment::local_time;
+use crate::eval::run_agent_command;
+use crate::{backend_args, Cli};
+use agent_core::eval::DEFAULT_CASE_TIMEOUT_SECS;
+use agent_core::schedule::{
+    CronSchedule, ScheduleFile, ScheduledJob, ScheduledRun, ScheduledStatus,
+};
+use agent_core::task::TaskFile;
+use anyhow::{Context, Result};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Options of `agent schedule`
+#[derive(Debug)]
+pub struct ScheduleOptions {
+    /// History directory (default: next to the schedule file, `<name>.history`)
+    pub history: Option<PathBuf>,
+    /// Approve calls that would prompt
+    pub yes: bool,
+    /// Agent arguments for every job
+    pub args: Vec<String>,
+}
+
+/// A job with its task file resolved and whether a run of it is going
+struct Job {
+    spec: ScheduledJob,
+    task: PathBuf,
+    schedule: CronSchedule,
+    running: Arc<AtomicBool>,
+}
+
+/// Run the jobs of the schedule at `path` until interrupted
+pub fn run(cli: &Cli, path: &Path, options: &ScheduleOptions) -> Result<()> {
+    let yaml =
+        fs::read_to_string(path).with_context(|| format!("Cannot read {}", path.display()))?;
+    let file = ScheduleFile::from_yaml(&yaml)
+        .with_context(|| format!("Cannot load {}", path.display()))?;
+    let base = path.parent().unwrap_or(Path::new("."));
+    let jobs = file
+        .jobs
+        .into_iter()
+        .map(|spec| load_job(base, spec))
+        .collect::<Result<Vec<Job>>>()?;
+    let history = options
+        .history
+        .clone()
+        .unwrap_or_else(|| path.with_extension("history"));
+    fs::create_dir_all(&history).with_context(|| format!("Cannot create {}", history.display()))?;
+
+    let mut args = backend_args(cli);
+    if let Some(temperature) = cli.temperature {
+        args.extend(["--temperature".to_string(), temperature.to_string()]);
+    }
+    if let Some(seed) = cli.seed {
+        args.extend(["--seed".to_string(), seed.to_string()]);
+    }
+    args.extend(options.args.iter().cloned());
+
+    let now = unix_now();
+    for job in &jobs {
+        let next = job
+            .schedule
+            .next_after(now, local_time)
+            .map(|moment| local_time(moment).calendar().to_string())
+            .unwrap_or_else(|| "not within a year".to_string());
+        let last = last_run(&history_file(&history, &job.spec.name))
+            .map(|run| format!(", last {} ({})", status_name(run.status), run.due))
+            .unwrap_or_default();
+        console::info(format!(
+            "{} ({}): next {}{}",
+            job.spec.name, job.spec.cron, next, last
+        ));
+    }
+    console::info(format!(
+        "Scheduling {} job(s); history in {}\n",
+        jobs.len(),
+        history.display()
+    ));
+
+    let exe = std::env::current_exe()?;
+    let mut minute = unix_now().div_euclid(60) * 60;
+    loop {
+        let wait = minute + 60 - unix_now();
+        if wait > 0 {
+            thread::sleep(Duration::from_secs(wait as u64));
+        }
+        // After a suspend, go on from the current minute rather than firing
+        // every minute missed
+        minute = (minute + 60).max(unix_now().div_euclid(60) * 60);
+        let clock = local_time(minute);
+        let due = clock.calendar().to_string();
+        for job in jobs.iter().filter(|job| job.schedule.matches(&clock)) {
+            let history_path = history_file(&history, &job.spec.name);
+            if job.running.swap(true, Ordering::SeqCst) {
+                console::warn(format!(
+                    "{}: skipped at {}, the previous run is still going",
+                    job.spec.name, due
+                ));
+                append(&history_path, &ScheduledRun::skipped(&job.spec.name, &due));
+                continue;
+            }
+            console::info(format!("{}: started at {}", job.spec.name, due));
+            let mut job_args = args.clone();
+            job_args.extend(job.spec.args.iter().cloned());
+            job_args.extend(["run".to_string(), job.task.display().to_string()]);
+            for (name, value) in &job.spec.vars {
+                job_args.extend(["--var".to_string(), format!("{}={}", name, value)]);
+            }
+            let (exe, name, due) = (exe.clone(), job.spec.name.clone(), due.clone());
+            let timeout = job.spec.timeout_secs.unwrap_or(DEFAULT_CASE_TIMEOUT_SECS);
+            let (yes, running) = (options.yes, Arc::clone(&job.running));
+            thread::spawn(move || {
+                let run = run_agent_command(&exe, &job_args, timeout, yes);
+                let record = ScheduledRun::new(&name, &due, run);
+                report(&record);
+                append(&history_path, &record);
+                running.store(false, Ordering::SeqCst);
+            });
+        }
+    }
+}
+
+/// Resolve a job's task file and check it can be rendered with the job's
+/// variables, so a broken job fails at startup rather than when it is due
+fn load_job(base: &Path, spec: ScheduledJob) -> Result<Job> {
+    let task = base.join(&spec.task);
+    let yaml =
+        fs::read_to_string(&task).with_context(|| format!("Cannot read {}", task.display()))?;
+    TaskFile::from_yaml(&yaml)
+        .and_then(|file| file.render_query(&spec.vars))
+        .with_context(|| format!("Job '{}': {}", spec.name, task.display()))?;
+    Ok(Job {
+        schedule: spec.schedule()?,
+        task,
+        spec,
+        running: Arc::new(AtomicBool::new(false)),
+    })
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs() as i64)
+}
+
+fn history_file(history: &Path, job: &str) -> PathBuf {
+    history.join(format!("{}.jsonl", job))
+}
+
+/// The last record of a job's history
+fn last_run(path: &Path) -> Option<ScheduledRun> {
+    fs::read_to_string(path)
+        .ok()?
+        .lines()
+        .rev()
+        .find_map(|line| serde_json::from_str(line).ok())
+}
+
+fn append(path: &Path, record: &ScheduledRun) {
+    let line = serde_json::to_string(record).expect("records serialize");
+    let written = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| writeln!(file, "{}", line));
+    if let Err(e) = written {
+        console::warn(format!("Cannot write {}: {}", path.display(), e));
+    }
+}
+
+fn status_name(status: ScheduledStatus) -> &'static str {
+    match status {
+        ScheduledStatus::Completed => "completed",
+        ScheduledStatus::Failed => "failed",
+        ScheduledStatus::Skipped => "skipped",
+    }
+}
+
+fn report(record: &ScheduledRun) {
+    match &record.error {
+        None => console::success(format!(
+            "{} ({}): {} iteration(s), {} tokens, {:.1}s",
+            record.job, record.due, record.iterations, record.tokens, record.wall_seconds
+        )),
+        Some(error) => console::failure(format!("{} ({}): {}", record.job, record.due, error)),
+    }
+}
@@ -0,0 +1,81 @@
+//! The `search` tool
+//!
+//! Runs a regex over the files under a directory of the working directory and
+//! returns the matching lines as JSON. Files are walked like ripgrep does:
+//! `.gitignore`d and hidden files are skipped, and so are binary and very
+//! large files.
+
+use agent_core::search::{SearchLimits, SearchRequest, SearchResults};
+use agent_core::tool::{ContentKind, ToolRequest, ToolResult};
+use ignore::overrides::OverrideBuilder;
+use ignore::WalkBuilder;
+use regex::RegexBuilder;
+use std::fs;
+use std::path::Path;
+
+pub const TOOL_NAME: &str = "search";
+
+/// Files larger than this are not searched
+const MAX_FILE_BYTES: u64 = 1024 * 1024;
+
+/// Execute the search tool
+pub fn execute(request: &ToolRequest) -> ToolResult {
+    match search(request) {
+        Ok(results) => ToolResult::success(results.to_json()).with_content_kind(ContentKind::Json),
+        Err(e) => ToolResult::failure(e),
+    }
+}
+
+fn search(request: &ToolRequest) -> Result<SearchResults, String> {
+    let request = SearchRequest::from_params(&request.params).map_err(|e| e.to_string())?;
+    let regex = RegexBuilder::new(&request.pattern)
+        .case_insensitive(request.ignore_case)
+        .build()
+        .map_err(|e| format!("Invalid pattern: {}", e))?;
+
+    let root = Path::new(&request.path);
+    if !root.exists() {
+        return Err(format!("No such file or directory: '{}'", request.path));
+    }
+    let mut walk = WalkBuilder::new(root);
+    if let Some(glob) = &request.glob {
+        let overrides = OverrideBuilder::new(root)
+            .add(glob)
+            .and_then(|builder| builder.build())
+            .map_err(|e| format!("Invalid glob '{}': {}", glob, e))?;
+        walk.overrides(overrides);
+    }
+
+    let mut results = SearchResults::new(SearchLimits::default().for_request(&request));
+    for entry in walk.build().flatten() {
+        let path = entry.path();
+        let searchable = entry.file_type().is_some_and(|kind| kind.is_file())
+            && entry
+                .metadata()
+                .is_ok_and(|meta| meta.len() <= MAX_FILE_BYTES);
+        if !searchable {
+            continue;
+        }
+        let Ok(bytes) = fs::read(path) else {
+            continue;
+        };
+        if bytes.iter().take(8192).any(|&b| b == 0) {
+            continue;
+        }
+
+        let display = path.strip_prefix(".").unwrap_or(path).to_string_lossy();
+        results.start_file();
+        for (index, line) in String::from_utf8_lossy(&bytes).lines().enumerate() {
+            if let Some(found) = regex.find(line) {
+                if !results.push(&display, index + 1, line, found.start()) {
+                    break;
+                }
+            }
+        }
+        if results.is_full() && results.is_truncated() {
+            break;
+        }
+    }
+
+    Ok(results)
+}
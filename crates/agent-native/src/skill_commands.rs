@@ -0,0 +1,206 @@
+//! `agent skills` subcommands: list, show and validate skill manifests
+//!
+//! These give skill authors feedback on a SKILL.md without loading a model or
+//! running the agent loop.
+
+use crate::skill_discovery::{discover_skills, DiscoveredSkill, SkillAssets};
+use agent_core::skill_manifest::{parse_skill_manifest, LintLevel, ManifestLint};
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Print discovered skills with their version and source directory
+pub fn list_skills(dirs: &[PathBuf]) -> Result<()> {
+    let skills = discover_skills(dirs);
+    if skills.is_empty() {
+        println!("No skills found in {}", display_dirs(dirs));
+        return Ok(());
+    }
+
+    let name_width = skills
+        .iter()
+        .map(|s| s.manifest.frontmatter.name.len())
+        .max()
+        .unwrap_or(0)
+        .max("NAME".len());
+
+    println!("{:<name_width$}  {:<8}  SOURCE", "NAME", "VERSION");
+    for skill in &skills {
+        println!(
+            "{:<name_width$}  {:<8}  {}",
+            skill.manifest.frontmatter.name,
+            skill.manifest.frontmatter.version().unwrap_or("-"),
+            skill_dir(skill).display()
+        );
+    }
+
+    Ok(())
+}
+
+/// Print a skill's manifest, prompt template variables, assets and JSON schemas
+pub fn show_skill(dirs: &[PathBuf], name: &str) -> Result<()> {
+    let skills = discover_skills(dirs);
+    let skill = skills
+        .iter()
+        .find(|s| s.manifest.frontmatter.name == name)
+        .ok_or_else(|| anyhow::anyhow!("Skill '{}' not found in {}", name, display_dirs(dirs)))?;
+    let fm = &skill.manifest.frontmatter;
+
+    println!("Name:          {}", fm.name);
+    println!("Version:       {}", fm.version().unwrap_or("-"));
+    println!("Description:   {}", fm.description);
+    if let Some(license) = &fm.license {
+        println!("License:       {}", license);
+    }
+    if let Some(compatibility) = &fm.compatibility {
+        println!("Compatibility: {}", compatibility);
+    }
+    if let Some(tools) = fm.allowed_tools.as_deref().filter(|t| !t.is_empty()) {
+        println!("Allowed tools: {}", tools);
+    }
+    println!("Source:        {}", skill.path.display());
+
+    if let Some(metadata) = &fm.metadata {
+        println!("\nMetadata:\n{}", serde_json::to_string_pretty(metadata)?);
+    }
+
+    if !fm.assets.is_empty() {
+        println!("\nAssets:");
+        for (asset, path) in &fm.assets {
+            println!("  {} -> {}", asset, path);
+        }
+    }
+
+    match skill.manifest.prompt_template() {
+        Some(Ok(template)) => {
+            println!("\nPrompt template variables:");
+            for variable in template.variables() {
+                println!("  {{{{{}}}}}", variable);
+            }
+        }
+        Some(Err(err)) => println!("\nPrompt template: invalid ({})", err),
+        None => println!("\nPrompt template: none (host default)"),
+    }
+
+    for schema_path in schema_files(skill_dir(skill)) {
+        let content = fs::read_to_string(&schema_path)
+            .with_context(|| format!("Failed to read {}", schema_path.display()))?;
+        let schema: serde_json::Value = serde_json::from_str(&content)
+            .with_context(|| format!("Invalid JSON in {}", schema_path.display()))?;
+        println!("\nSchema ({}):", schema_path.display());
+        println!("{}", serde_json::to_string_pretty(&schema)?);
+    }
+
+    Ok(())
+}
+
+/// Lint a SKILL.md file (or the directory containing it)
+///
+/// Returns `Ok(false)` when the manifest has errors, so the caller can set the
+/// exit status.
+pub fn validate_skill(path: &Path) -> Result<bool> {
+    let manifest_path = if path.is_dir() {
+        path.join("SKILL.md")
+    } else {
+        path.to_path_buf()
+    };
+    let lints = lint_skill(&manifest_path)?;
+
+    let errors = lints.iter().filter(|l| l.level == LintLevel::Error).count();
+    for lint in &lints {
+        match lint.level {
+            LintLevel::Error => println!("  ✗ error: {}", lint.message),
+            LintLevel::Warning => println!("  ⚠️  warning: {}", lint.message),
+        }
+    }
+
+    if errors == 0 {
+        println!(
+            "✓ {} is valid ({} warning(s))",
+            manifest_path.display(),
+            lints.len()
+        );
+    } else {
+        println!(
+            "✗ {}: {} error(s), {} warning(s)",
+            manifest_path.display(),
+            errors,
+            lints.len() - errors
+        );
+    }
+
+    Ok(errors == 0)
+}
+
+/// Lint findings for the SKILL.md at `manifest_path`, its assets and schemas,
+/// errors first
+pub fn lint_skill(manifest_path: &Path) -> Result<Vec<ManifestLint>> {
+    let content = fs::read_to_string(manifest_path)
+        .with_context(|| format!("Failed to read {}", manifest_path.display()))?;
+
+    let mut lints = match parse_skill_manifest(&content) {
+        Ok(manifest) => {
+            let root = manifest_path.parent().unwrap_or(Path::new("."));
+            let assets = SkillAssets::index(root);
+            let mut lints = manifest.lint();
+            for (name, relative) in &manifest.frontmatter.assets {
+                if !assets.contains(relative) {
+                    lints.push(ManifestLint {
+                        level: LintLevel::Error,
+                        message: format!("asset '{}' not found: {}", name, relative),
+                    });
+                }
+            }
+            for schema_path in schema_files(root) {
+                if let Err(err) = fs::read_to_string(&schema_path)
+                    .map_err(|e| e.to_string())
+                    .and_then(|c| {
+                        serde_json::from_str::<serde_json::Value>(&c).map_err(|e| e.to_string())
+                    })
+                {
+                    lints.push(ManifestLint {
+                        level: LintLevel::Error,
+                        message: format!("{}: {}", schema_path.display(), err),
+                    });
+                }
+            }
+            lints
+        }
+        Err(err) => vec![ManifestLint {
+            level: LintLevel::Error,
+            message: err.to_string(),
+        }],
+    };
+    lints.sort_by_key(|l| l.level != LintLevel::Error);
+    Ok(lints)
+}
+
+fn skill_dir(skill: &DiscoveredSkill) -> &Path {
+    skill.path.parent().unwrap_or(Path::new("."))
+}
+
+/// JSON schema files shipped at the top level of a skill directory
+fn schema_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.is_file()
+                && path
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| n.ends_with("schema.json"))
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+fn display_dirs(dirs: &[PathBuf]) -> String {
+    dirs.iter()
+        .map(|d| d.display().to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
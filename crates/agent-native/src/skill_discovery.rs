@@ -1,4 +1,12 @@
-use agent_core::skill_manifest::{parse_skill_manifest, SkillManifest, SkillManifestError};
+use crate::console;
+use agent_core::prompt_template::PromptTemplate;
+use agent_core::skill_guardrail::SkillGuardrailChain;
+use agent_core::skill_manifest::{
+    parse_skill_manifest, validate_asset_path, SkillManifest, SkillManifestError,
+};
+use anyhow::{Context, Result};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 
@@ -6,6 +14,75 @@ use std::path::{Path, PathBuf};
 pub struct DiscoveredSkill {
     pub path: PathBuf,
     pub manifest: SkillManifest,
+    pub assets: SkillAssets,
+}
+
+/// Auxiliary files (examples, reference data, scripts) shipped next to a SKILL.md
+///
+/// Files are indexed at discovery time and addressed by paths relative to the
+/// skill directory. Only indexed files can be resolved, and symlinks are not
+/// indexed, so a manifest cannot reach outside its own directory.
+#[derive(Debug, Clone, Default)]
+pub struct SkillAssets {
+    root: PathBuf,
+    files: Vec<PathBuf>,
+}
+
+impl SkillAssets {
+    /// Index every file under `root` except SKILL.md, hidden entries and
+    /// symlinks
+    pub fn index(root: &Path) -> Self {
+        let mut files = Vec::new();
+        collect_files(root, Path::new(""), &mut files);
+        files.sort();
+        Self {
+            root: root.to_path_buf(),
+            files,
+        }
+    }
+
+    /// Whether a relative path refers to an indexed file
+    pub fn contains(&self, relative: &str) -> bool {
+        validate_asset_path(relative).is_ok() && self.files.iter().any(|f| f == Path::new(relative))
+    }
+
+    /// Resolve a relative asset path to its location on disk
+    pub fn resolve(&self, relative: &str) -> Result<PathBuf> {
+        validate_asset_path(relative)?;
+        if !self.contains(relative) {
+            anyhow::bail!("asset '{}' not found in {}", relative, self.root.display());
+        }
+        Ok(self.root.join(relative))
+    }
+
+    /// Read an asset as UTF-8 text
+    pub fn read_to_string(&self, relative: &str) -> Result<String> {
+        let path = self.resolve(relative)?;
+        fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))
+    }
+}
+
+fn collect_files(root: &Path, relative: &Path, out: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(root.join(relative)) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        if name.to_string_lossy().starts_with('.') {
+            continue;
+        }
+        // The entry's own type: symlinks are skipped, never followed
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        let rel = relative.join(&name);
+        if file_type.is_dir() {
+            collect_files(root, &rel, out);
+        } else if file_type.is_file() && rel != Path::new("SKILL.md") {
+            out.push(rel);
+        }
+    }
 }
 
 /// Discover skills by scanning provided directories for SKILL.md files.
@@ -38,16 +115,28 @@ pub fn discover_skills(skill_dirs: &[PathBuf]) -> Vec<DiscoveredSkill> {
 fn load_skill_manifest(path: &Path) -> Option<DiscoveredSkill> {
     let content = fs::read_to_string(path).ok()?;
     match parse_skill_manifest(&content) {
-        Ok(manifest) => Some(DiscoveredSkill {
-            path: path.to_path_buf(),
-            manifest,
-        }),
+        Ok(manifest) => {
+            let assets = SkillAssets::index(path.parent().unwrap_or(Path::new(".")));
+            for (name, relative) in &manifest.frontmatter.assets {
+                if !assets.contains(relative) {
+                    console::warn(format!(
+                        "Skill '{}' references missing asset '{}' ({})",
+                        manifest.frontmatter.name, name, relative
+                    ));
+                }
+            }
+            Some(DiscoveredSkill {
+                path: path.to_path_buf(),
+                manifest,
+                assets,
+            })
+        }
         Err(err) => {
-            eprintln!(
-                "⚠️  Failed to parse skill manifest {}: {}",
+            console::warn(format!(
+                "Failed to parse skill manifest {}: {}",
                 path.display(),
                 format_manifest_error(err)
-            );
+            ));
             None
         }
     }
@@ -58,7 +147,113 @@ fn format_manifest_error(err: SkillManifestError) -> String {
         SkillManifestError::MissingDelimiter => "missing YAML frontmatter delimiter".to_string(),
         SkillManifestError::MissingFrontmatter => "missing YAML frontmatter content".to_string(),
         SkillManifestError::FrontmatterParse(msg) => format!("invalid frontmatter: {}", msg),
+        SkillManifestError::InvalidAssetPath(path) => format!("invalid asset path: {}", path),
+    }
+}
+
+/// Host-side resources of a discovered skill, handed to the skill executor
+#[derive(Debug, Clone, Default)]
+pub struct LoadedSkill {
+    /// Prompt template declared in SKILL.md, if any
+    pub template: Option<PromptTemplate>,
+    /// Contents of the manifest-referenced assets, as the `assets` template
+    /// context; read once when the skill is loaded
+    pub assets: Value,
+    /// Guardrails and checks declared in SKILL.md; `None` keeps the skill's
+    /// built-in checks
+    pub guardrails: Option<SkillGuardrailChain>,
+}
+
+/// Loaded skills keyed by skill name
+pub type LoadedSkills = HashMap<String, LoadedSkill>;
+
+/// Load templates, assets and guardrails of discovered skills.
+/// Skills with an invalid template are reported and fall back to the built-in prompt;
+/// skills declaring an unknown guardrail fall back to the built-in checks, and
+/// invalid `checks` and unreadable assets are reported and left out.
+pub fn load_skills(skills: &[DiscoveredSkill]) -> LoadedSkills {
+    let mut loaded = HashMap::new();
+
+    for skill in skills {
+        let template = match skill.manifest.prompt_template() {
+            Some(Ok(template)) => Some(template),
+            Some(Err(err)) => {
+                console::warn(format!(
+                    "Invalid prompt template in {}: {}",
+                    skill.path.display(),
+                    err
+                ));
+                None
+            }
+            None => None,
+        };
+
+        let declared = skill.manifest.frontmatter.guardrails().and_then(|names| {
+            SkillGuardrailChain::from_names(&names)
+                .map_err(|err| {
+                    console::warn(format!(
+                        "Invalid guardrails in {}: {}",
+                        skill.path.display(),
+                        err
+                    ))
+                })
+                .ok()
+        });
+        let checks = skill
+            .manifest
+            .frontmatter
+            .checks
+            .as_ref()
+            .and_then(|checks| {
+                checks
+                    .compile()
+                    .map_err(|err| {
+                        console::warn(format!(
+                            "Invalid checks in {}: {}",
+                            skill.path.display(),
+                            err
+                        ))
+                    })
+                    .ok()
+            });
+        // Declared checks run after the skill's guardrails
+        let guardrails = match checks {
+            Some(checks) => Some(
+                declared
+                    .unwrap_or_else(SkillGuardrailChain::extraction)
+                    .add(Box::new(checks)),
+            ),
+            None => declared,
+        };
+
+        let mut assets = Map::new();
+        for (name, relative) in &skill.manifest.frontmatter.assets {
+            match skill.assets.read_to_string(relative) {
+                Ok(content) => {
+                    assets.insert(name.clone(), Value::String(content));
+                }
+                // Missing assets were reported when the skill was discovered
+                Err(_) if !skill.assets.contains(relative) => {}
+                Err(err) => console::warn(format!(
+                    "Unreadable asset '{}' in {}: {}",
+                    name,
+                    skill.path.display(),
+                    err
+                )),
+            }
+        }
+
+        loaded.insert(
+            skill.manifest.frontmatter.name.clone(),
+            LoadedSkill {
+                template,
+                assets: Value::Object(assets),
+                guardrails,
+            },
+        );
     }
+
+    loaded
 }
 
 /// Build an XML block compatible with Agent Skills prompt format.
@@ -0,0 +1,76 @@
+//! The `sql` tool
+//!
+//! Runs one read-only query against a SQLite file under the working directory
+//! and returns the rows as JSON. The database is opened read-only, and
+//! statements SQLite reports as writing are refused before they run.
+
+use agent_core::sql::{is_read_only_query, QueryResults, SqlError, SqlLimits, SqlRequest};
+use agent_core::tool::{ContentKind, ToolRequest, ToolResult};
+use rusqlite::types::ValueRef;
+use rusqlite::{Connection, OpenFlags};
+use serde_json::Value;
+use std::time::Duration;
+
+pub const TOOL_NAME: &str = "sql";
+
+/// Execute the sql tool
+pub fn execute(request: &ToolRequest) -> ToolResult {
+    match query(request) {
+        Ok(results) => ToolResult::success(results.to_json()).with_content_kind(ContentKind::Json),
+        Err(e) => ToolResult::failure(e),
+    }
+}
+
+fn query(request: &ToolRequest) -> Result<QueryResults, String> {
+    let request = SqlRequest::from_params(&request.params).map_err(|e| e.to_string())?;
+    if !is_read_only_query(&request.query) {
+        return Err(SqlError::NotReadOnly.to_string());
+    }
+
+    let connection = Connection::open_with_flags(
+        &request.database,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )
+    .map_err(|e| format!("Cannot open '{}': {}", request.database, e))?;
+    connection
+        .busy_timeout(Duration::from_secs(5))
+        .map_err(|e| e.to_string())?;
+
+    let mut statement = connection
+        .prepare(&request.query)
+        .map_err(|e| format!("Invalid query: {}", e))?;
+    if !statement.readonly() {
+        return Err(SqlError::NotReadOnly.to_string());
+    }
+
+    let columns: Vec<String> = statement
+        .column_names()
+        .into_iter()
+        .map(String::from)
+        .collect();
+    let width = columns.len();
+    let mut results = QueryResults::new(columns, SqlLimits::default().for_request(&request));
+
+    let mut rows = statement.query([]).map_err(|e| e.to_string())?;
+    while let Some(row) = rows.next().map_err(|e| e.to_string())? {
+        let values = (0..width)
+            .map(|index| row.get_ref(index).map(json_value))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        if !results.push_row(values) {
+            break;
+        }
+    }
+
+    Ok(results)
+}
+
+fn json_value(value: ValueRef<'_>) -> Value {
+    match value {
+        ValueRef::Null => Value::Null,
+        ValueRef::Integer(n) => Value::from(n),
+        ValueRef::Real(x) => Value::from(x),
+        ValueRef::Text(text) => Value::String(String::from_utf8_lossy(text).into_owned()),
+        ValueRef::Blob(bytes) => Value::String(format!("<blob, {} bytes>", bytes.len())),
+    }
+}
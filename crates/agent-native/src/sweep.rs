@@ -0,0 +1,112 @@
+//! `agent sweep`: one query across temperatures and seeds
+//!
+//! Every run is an eval target of its own (the backend flags, the arguments
+//! after `--`, and its `--temperature` and `--seed`) running a single case, so
+//! runs are isolated child processes and tool calls are approved as in
+//! `agent eval`. The runs are then compared with [`SweepReport`].
+
+use crate::console;
+use crate::eval::run_cases;
+use crate::{backend_args, Cli};
+use agent_core::eval::{EvalCase, EvalSuite, EvalTarget, DEFAULT_CASE_TIMEOUT_SECS};
+use agent_core::sweep::{sweep_settings, SweepReport, SweepRun, SweepSetting};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Seed of the first run when `--seed` is not given
+const DEFAULT_FIRST_SEED: u32 = 1;
+
+/// The `--json` export
+#[derive(Serialize)]
+struct Report<'a> {
+    query: &'a str,
+    report: &'a SweepReport,
+    runs: &'a [SweepRun],
+}
+
+/// Options of `agent sweep`
+#[derive(Debug)]
+pub struct SweepOptions {
+    pub query: String,
+    pub runs: usize,
+    pub temperatures: Vec<f32>,
+    /// Runs to execute at once
+    pub jobs: usize,
+    /// Agent arguments for every run
+    pub args: Vec<String>,
+    pub json: Option<PathBuf>,
+}
+
+/// Run the sweep and print how stable the runs were
+pub fn run(cli: &Cli, options: &SweepOptions) -> Result<()> {
+    if options.runs == 0 || options.temperatures.is_empty() {
+        anyhow::bail!("A sweep needs at least one run and one temperature");
+    }
+    let settings = sweep_settings(
+        options.runs,
+        &options.temperatures,
+        cli.seed.unwrap_or(DEFAULT_FIRST_SEED),
+    );
+
+    let mut args = backend_args(cli);
+    args.extend(options.args.iter().cloned());
+    let suite = EvalSuite {
+        name: "sweep".to_string(),
+        targets: settings.iter().map(target).collect(),
+        args,
+        timeout_secs: DEFAULT_CASE_TIMEOUT_SECS,
+        cases: vec![EvalCase {
+            id: "query".to_string(),
+            query: Some(options.query.clone()),
+            fixture: None,
+            expect: Default::default(),
+        }],
+    };
+    let workers = options.jobs.clamp(1, settings.len());
+    console::info(format!(
+        "Running \"{}\" {} time(s), {} at a time\n",
+        options.query,
+        settings.len(),
+        workers
+    ));
+
+    let results = run_cases(&suite, &suite.targets, Path::new("."), workers)?;
+    let runs: Vec<SweepRun> = settings
+        .iter()
+        .zip(results)
+        .map(|(setting, result)| SweepRun {
+            setting: *setting,
+            tools: result.run.tools,
+            answer: result.run.answer,
+            error: result.run.error,
+        })
+        .collect();
+    let report = SweepReport::new(&runs);
+    println!("\n{}", report.to_text());
+
+    if let Some(path) = &options.json {
+        let export = Report {
+            query: &options.query,
+            report: &report,
+            runs: &runs,
+        };
+        fs::write(path, serde_json::to_string_pretty(&export)?)
+            .with_context(|| format!("Cannot write {}", path.display()))?;
+    }
+    Ok(())
+}
+
+fn target(setting: &SweepSetting) -> EvalTarget {
+    EvalTarget {
+        name: format!("t={} seed={}", setting.temperature, setting.seed),
+        args: vec![
+            "--temperature".to_string(),
+            setting.temperature.to_string(),
+            "--seed".to_string(),
+            setting.seed.to_string(),
+        ],
+        max_concurrent: None,
+    }
+}
@@ -0,0 +1,53 @@
+//! `agent run`: run the job a task file describes
+//!
+//! The task file fills in what the command line would otherwise give: the
+//! query (its template rendered with `--var` values), the profile, the
+//! iteration limit and the answer format. Tools, guardrails and the safety
+//! ceiling are applied on top of the profile when the run starts. Agent flags
+//! (model, backend, `--approve` rules...) still come from the command line,
+//! before `run`. See [`agent_core::task`] for the format.
+
+use crate::{console, Cli, CliCommand};
+use agent_core::task::TaskFile;
+use anyhow::{bail, Context, Result};
+use std::collections::BTreeMap;
+use std::fs;
+
+/// Turn `agent run <task>` into the agent run the task describes
+pub fn prepare(mut cli: Cli) -> Result<Cli> {
+    let Some(CliCommand::Run { task: path, vars }) = &cli.command else {
+        return Ok(cli);
+    };
+    if cli.query.is_some()
+        || cli.query_file.is_some()
+        || cli.audio.is_some()
+        || cli.conversation.is_some()
+    {
+        bail!("agent run takes its query from the task file");
+    }
+    let yaml =
+        fs::read_to_string(path).with_context(|| format!("Cannot read {}", path.display()))?;
+    let task =
+        TaskFile::from_yaml(&yaml).with_context(|| format!("Cannot load {}", path.display()))?;
+    let values: BTreeMap<String, String> = vars.iter().cloned().collect();
+    let query = task
+        .render_query(&values)
+        .with_context(|| format!("Task '{}'", task.name))?;
+    console::verbose(format!("Task '{}': {}", task.name, query));
+
+    cli.query = Some(query);
+    if cli.profile.is_none() {
+        cli.profile = task.profile.clone();
+    }
+    if let Some(iterations) = task.max_iterations {
+        cli.max_iterations = iterations;
+    }
+    let output = &task.output;
+    cli.max_words = cli.max_words.or(output.max_words);
+    cli.bullets |= output.bullets;
+    if cli.language.is_none() {
+        cli.language = output.language.clone();
+    }
+    cli.task = Some(task);
+    Ok(cli)
+}
@@ -0,0 +1,316 @@
+//! OpenTelemetry export of runs (`--otlp-endpoint`)
+//!
+//! [`Telemetry`] watches a run's events the way the audit log does and builds
+//! its spans and counters (see [`agent_core::telemetry`]). They are sent over
+//! OTLP/HTTP with JSON encoding when the run ends, so any collector listening on
+//! port 4318 receives them. Export failures are warnings; they never fail a run.
+//!
+//! Content is redacted unless `--otlp-include-content` is given.
+
+use crate::console;
+use crate::events::{AgentEvent, ApprovalSource};
+use agent_core::telemetry::{
+    metrics_request, traces_request, AttributeValue, Attributes, ContentPolicy, Counters, Span,
+    GUARDRAIL_SPAN, LLM_CALL_SPAN, RUN_SPAN, TOOL_EXEC_SPAN,
+};
+use serde_json::Value;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+/// Service name reported when `OTEL_SERVICE_NAME` is not set
+const DEFAULT_SERVICE_NAME: &str = "agent-native";
+
+/// Extra request headers, as `key=value` pairs separated by commas
+const HEADERS_VAR: &str = "OTEL_EXPORTER_OTLP_HEADERS";
+
+const SERVICE_NAME_VAR: &str = "OTEL_SERVICE_NAME";
+
+/// How long one export request may take
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Spans and counters of the current run, exported when it ends
+#[derive(Debug)]
+pub struct Telemetry {
+    endpoint: String,
+    content: ContentPolicy,
+    backend: String,
+    trace_id: String,
+    run: Option<Span>,
+    tool: Option<Span>,
+    spans: Vec<Span>,
+    counters: Counters,
+    exported: bool,
+}
+
+impl Telemetry {
+    /// Export to the OTLP/HTTP collector at `endpoint` (e.g.
+    /// `http://localhost:4318`); `backend` describes the model being run
+    pub fn new(endpoint: &str, content: ContentPolicy, backend: String) -> Self {
+        Self {
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            content,
+            backend,
+            trace_id: Uuid::new_v4().simple().to_string(),
+            run: None,
+            tool: None,
+            spans: Vec::new(),
+            counters: Counters::new(now()),
+            exported: false,
+        }
+    }
+
+    /// Record what `event` says about the run
+    pub fn observe(&mut self, event: &AgentEvent) {
+        match event {
+            AgentEvent::Started { run_id, query } => {
+                let mut run = Span::new(RUN_SPAN, &self.trace_id, span_id(), now());
+                run.set("agent.run_id", run_id.as_str());
+                run.set("agent.backend", self.backend.as_str());
+                run.set_content("agent.query", query, self.content);
+                self.run = Some(run);
+            }
+            AgentEvent::Generated {
+                label,
+                tokens,
+                seconds,
+            } => {
+                let end = now();
+                let start = end.saturating_sub((*seconds as f64 * 1e9) as u64);
+                let mut span = self.child(LLM_CALL_SPAN, start);
+                span.set("llm.label", label.as_str());
+                span.set("llm.tokens", *tokens);
+                span.end(end);
+                self.spans.push(span);
+                self.counters.add("agent.llm.calls", &[("label", label)], 1);
+                self.counters
+                    .add("agent.llm.tokens", &[("label", label)], *tokens as u64);
+            }
+            AgentEvent::ToolProposed {
+                tool,
+                safety,
+                params,
+            } => {
+                self.close_tool(Some("superseded by another call"));
+                let mut span = self.child(TOOL_EXEC_SPAN, now());
+                span.set("tool.name", tool.as_str());
+                span.set("tool.safety", safety.as_str());
+                span.set_content("tool.params", &params.to_string(), self.content);
+                self.tool = Some(span);
+            }
+            AgentEvent::ToolRejected { tool, reason } => {
+                self.guardrail("pre_execution", Some(tool), false, Some(reason));
+                self.close_tool(Some(reason));
+            }
+            AgentEvent::ApprovalDecided {
+                approved, source, ..
+            } => {
+                if let Some(span) = &mut self.tool {
+                    span.set("tool.approved", *approved);
+                    span.set(
+                        "tool.approval_source",
+                        match source {
+                            ApprovalSource::Policy => "policy",
+                            ApprovalSource::User => "user",
+                        },
+                    );
+                }
+                if !approved {
+                    self.close_tool(Some("not approved"));
+                }
+            }
+            AgentEvent::ToolOutput {
+                tool,
+                success,
+                output,
+                error,
+                content_kind,
+            } => {
+                if let Some(span) = &mut self.tool {
+                    span.set("tool.success", *success);
+                    span.set("tool.content_kind", content_kind.as_str());
+                    span.set_content("tool.output", output, self.content);
+                }
+                self.close_tool(error.as_deref().or((!success).then_some("tool failed")));
+                let success = if *success { "true" } else { "false" };
+                self.counters.add(
+                    "agent.tool.calls",
+                    &[("tool", tool), ("success", success)],
+                    1,
+                );
+            }
+            AgentEvent::GuardrailVerdict {
+                tool,
+                accepted,
+                reason,
+            } => self.guardrail("output", Some(tool), *accepted, reason.as_deref()),
+            AgentEvent::AnswerRejected { answer, reason } => {
+                self.guardrail("answer", None, false, Some(reason));
+                if let Some(span) = self.spans.last_mut() {
+                    span.set_content("agent.answer", answer, self.content);
+                }
+            }
+            AgentEvent::FinalAnswer { answer } => {
+                if let Some(run) = &mut self.run {
+                    run.set_content("agent.answer", answer, self.content);
+                }
+                self.finish(None);
+            }
+            AgentEvent::Failed { code, message } => {
+                if let Some(run) = &mut self.run {
+                    run.set("agent.failure_code", code.as_str());
+                }
+                self.finish(Some(message));
+            }
+            _ => {}
+        }
+    }
+
+    /// Attach a model call's prompt and completion to its span
+    pub fn model_call(&mut self, prompt: &str, output: &str) {
+        let Some(span) = self
+            .spans
+            .iter_mut()
+            .rev()
+            .find(|span| span.name == LLM_CALL_SPAN)
+        else {
+            return;
+        };
+        span.set_content("llm.prompt", prompt, self.content);
+        span.set_content("llm.completion", output, self.content);
+    }
+
+    fn child(&self, name: &str, start: u64) -> Span {
+        Span::new(name, &self.trace_id, span_id(), start)
+            .with_parent(self.run.as_ref().map(|run| run.span_id.clone()))
+    }
+
+    fn close_tool(&mut self, error: Option<&str>) {
+        if let Some(mut span) = self.tool.take() {
+            if let Some(error) = error {
+                span.fail(error);
+            }
+            span.end(now());
+            self.spans.push(span);
+        }
+    }
+
+    fn guardrail(&mut self, stage: &str, tool: Option<&str>, accepted: bool, reason: Option<&str>) {
+        let mut span = self.child(GUARDRAIL_SPAN, now());
+        span.set("guardrail.stage", stage);
+        if let Some(tool) = tool {
+            span.set("tool.name", tool);
+        }
+        span.set("guardrail.accepted", accepted);
+        if let Some(reason) = reason {
+            span.set("guardrail.reason", reason);
+        }
+        self.spans.push(span);
+        let accepted = if accepted { "true" } else { "false" };
+        self.counters.add(
+            "agent.guardrail.verdicts",
+            &[("stage", stage), ("accepted", accepted)],
+            1,
+        );
+    }
+
+    /// End the run span and export everything recorded
+    fn finish(&mut self, error: Option<&str>) {
+        if self.exported {
+            return;
+        }
+        self.exported = true;
+        self.close_tool(Some("run ended"));
+        if let Some(mut run) = self.run.take() {
+            if let Some(error) = error {
+                run.fail(error);
+            }
+            run.end(now());
+            self.spans.push(run);
+        }
+
+        let resource = resource();
+        let agent = export_agent();
+        if !self.spans.is_empty() {
+            let body = traces_request(&resource, &self.spans);
+            self.export(&agent, "/v1/traces", &body);
+        }
+        if !self.counters.is_empty() {
+            let body = metrics_request(&resource, &self.counters, now());
+            self.export(&agent, "/v1/metrics", &body);
+        }
+    }
+
+    fn export(&self, agent: &ureq::Agent, path: &str, body: &Value) {
+        let url = format!("{}{}", self.endpoint, path);
+        let mut request = agent.post(&url);
+        for (key, value) in headers() {
+            request = request.header(key, value);
+        }
+        match request.send_json(body) {
+            Ok(response) if response.status().is_success() => {
+                console::verbose(format!("Exported telemetry to {}", url))
+            }
+            Ok(response) => console::warn(format!(
+                "Telemetry export to {} failed: HTTP {}",
+                url,
+                response.status()
+            )),
+            Err(err) => console::warn(format!("Telemetry export to {} failed: {}", url, err)),
+        }
+    }
+}
+
+impl Drop for Telemetry {
+    /// Runs that end in an error never emit `final_answer` or `failed`
+    fn drop(&mut self) {
+        if self.run.is_some() {
+            self.finish(Some("run aborted"));
+        }
+    }
+}
+
+fn resource() -> Attributes {
+    let service = std::env::var(SERVICE_NAME_VAR)
+        .ok()
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| DEFAULT_SERVICE_NAME.to_string());
+    vec![
+        ("service.name".to_string(), AttributeValue::from(service)),
+        (
+            "service.version".to_string(),
+            AttributeValue::from(env!("CARGO_PKG_VERSION")),
+        ),
+    ]
+}
+
+/// `OTEL_EXPORTER_OTLP_HEADERS` as name-value pairs
+fn headers() -> Vec<(String, String)> {
+    std::env::var(HEADERS_VAR)
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .filter(|(key, _)| !key.is_empty())
+        .collect()
+}
+
+fn export_agent() -> ureq::Agent {
+    ureq::Agent::config_builder()
+        .http_status_as_error(false)
+        .timeout_global(Some(EXPORT_TIMEOUT))
+        .build()
+        .into()
+}
+
+/// A new random 16-digit span id
+fn span_id() -> String {
+    Uuid::new_v4().simple().to_string()[..16].to_string()
+}
+
+/// Nanoseconds since the Unix epoch
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos() as u64)
+        .unwrap_or_default()
+}
@@ -0,0 +1,154 @@
+//! Rate limiting and retries for the HTTP backends
+//!
+//! Every HTTP backend sends through a [`Throttle`]: requests wait for a free
+//! slot (`--max-concurrent`) and for room in the per-minute limits
+//! (`--requests-per-minute`, `--tokens-per-minute`), and a request the server
+//! throttles is retried after its `Retry-After` (see [`agent_core::rate_limit`]).
+//!
+//! Limits hold per process. Parallel eval runs are separate processes; cap them
+//! per target with `max_concurrent` in the suite instead.
+
+use crate::audit::unix_time;
+use crate::console;
+use crate::llm::LLMInput;
+use agent_core::rate_limit::{is_throttled, parse_retry_after, RateLimits, RateWindow};
+use anyhow::{bail, Result};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::Instant;
+use ureq::http::Response;
+use ureq::Body;
+
+/// Characters per token assumed when charging a request against the token limit
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Error bodies longer than this are cut in error messages
+const MAX_ERROR_BODY: usize = 500;
+
+/// Paces, queues and retries the requests of the backends sharing it
+pub struct Throttle {
+    limits: RateLimits,
+    epoch: Instant,
+    window: Mutex<RateWindow>,
+    in_flight: Mutex<usize>,
+    freed: Condvar,
+}
+
+impl Default for Throttle {
+    fn default() -> Self {
+        Self::new(RateLimits::default())
+    }
+}
+
+impl Throttle {
+    pub fn new(limits: RateLimits) -> Self {
+        Self {
+            limits,
+            epoch: Instant::now(),
+            window: Mutex::new(RateWindow::new()),
+            in_flight: Mutex::new(0),
+            freed: Condvar::new(),
+        }
+    }
+
+    /// Send a request of about `tokens` tokens within the limits
+    ///
+    /// `request` is called once per attempt. Throttled responses (429, 503)
+    /// are retried; any other non-success status is an error.
+    pub fn send(
+        &self,
+        tokens: u64,
+        mut request: impl FnMut() -> std::result::Result<Response<Body>, ureq::Error>,
+    ) -> Result<Response<Body>> {
+        let _slot = self.acquire_slot();
+        let mut attempt = 0;
+        loop {
+            self.wait_turn(tokens);
+            let mut response = request()?;
+            let status = response.status();
+            if status.is_success() {
+                return Ok(response);
+            }
+
+            if is_throttled(status.as_u16()) {
+                let retry_after = response
+                    .headers()
+                    .get("retry-after")
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| parse_retry_after(value, unix_time()));
+                if let Some(delay) = self.limits.retry_delay(attempt, retry_after) {
+                    console::warn(format!(
+                        "Rate limited (HTTP {}), retrying in {:.1}s",
+                        status.as_u16(),
+                        delay.as_secs_f32()
+                    ));
+                    thread::sleep(delay);
+                    attempt += 1;
+                    continue;
+                }
+            }
+
+            let body = response.body_mut().read_to_string().unwrap_or_default();
+            let body: String = body.trim().chars().take(MAX_ERROR_BODY).collect();
+            bail!("HTTP {}: {}", status, body);
+        }
+    }
+
+    /// Wait until the per-minute limits have room, then count the request
+    fn wait_turn(&self, tokens: u64) {
+        loop {
+            let delay = {
+                let mut window = self.window.lock().unwrap();
+                let now = self.epoch.elapsed();
+                let delay = window.delay(&self.limits, now, tokens);
+                if delay.is_zero() {
+                    window.record(now, tokens);
+                    return;
+                }
+                delay
+            };
+            thread::sleep(delay);
+        }
+    }
+
+    fn acquire_slot(&self) -> Slot<'_> {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        if let Some(max) = self.limits.max_concurrent {
+            while *in_flight >= max.max(1) {
+                in_flight = self.freed.wait(in_flight).unwrap();
+            }
+        }
+        *in_flight += 1;
+        Slot(self)
+    }
+}
+
+/// A request in flight; frees its slot when dropped
+struct Slot<'a>(&'a Throttle);
+
+impl Drop for Slot<'_> {
+    fn drop(&mut self) {
+        *self.0.in_flight.lock().unwrap() -= 1;
+        self.0.freed.notify_one();
+    }
+}
+
+/// HTTP client for the backends: error statuses come back as responses, so
+/// [`Throttle::send`] can read `Retry-After`
+pub fn http_agent() -> ureq::Agent {
+    ureq::Agent::config_builder()
+        .http_status_as_error(false)
+        .build()
+        .into()
+}
+
+/// Tokens to charge for a request: the prompt plus the full completion
+/// budget, as hosted APIs count it
+pub fn estimate_tokens(input: &LLMInput) -> u64 {
+    (prompt_tokens(&input.prompt) + input.max_tokens) as u64
+}
+
+/// Rough token count of a prompt
+pub fn prompt_tokens(prompt: &str) -> usize {
+    prompt.chars().count().div_ceil(CHARS_PER_TOKEN)
+}
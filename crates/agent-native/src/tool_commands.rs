@@ -0,0 +1,42 @@
+//! `agent tools` subcommands
+
+use crate::tool_discovery::{build_tool_registry, discover_tools};
+use anyhow::Result;
+use std::path::PathBuf;
+
+/// Print the tools offered to the model with their safety class and source
+pub fn list_tools(dirs: &[PathBuf]) -> Result<()> {
+    let discovered = discover_tools(dirs);
+    let registry = build_tool_registry(&discovered);
+
+    let name_width = registry
+        .tools()
+        .map(|t| t.frontmatter.name.len())
+        .max()
+        .unwrap_or(0)
+        .max("NAME".len());
+
+    println!(
+        "{:<name_width$}  {:<11}  {:<20}  SOURCE",
+        "NAME", "SAFETY", "PARAMS"
+    );
+    for tool in registry.tools() {
+        let fm = &tool.frontmatter;
+        let source = discovered
+            .iter()
+            .find(|d| d.manifest.frontmatter.name == fm.name)
+            .map(|d| d.path.display().to_string())
+            .unwrap_or_else(|| "(built-in)".to_string());
+        let params = fm.params.keys().cloned().collect::<Vec<_>>().join(",");
+
+        println!(
+            "{:<name_width$}  {:<11}  {:<20}  {}",
+            fm.name,
+            fm.safety.as_str(),
+            if params.is_empty() { "-" } else { &params },
+            source
+        );
+    }
+
+    Ok(())
+}
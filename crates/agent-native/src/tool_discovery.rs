@@ -0,0 +1,114 @@
+use crate::{console, search_tool, sql_tool};
+use agent_core::guardrail::{BoxedGuard, GuardrailChain};
+use agent_core::search::SearchResultValidator;
+use agent_core::sql::QueryResultValidator;
+use agent_core::tool_manifest::{parse_tool_manifest, ToolManifest, ToolRegistry};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Tools this host knows how to execute
+pub const HOST_TOOLS: &[&str] = &[
+    "shell",
+    "apply_patch",
+    "search",
+    "sql",
+    "eval_math",
+    "view_image",
+];
+
+/// `chain` with the host tools' validators of their own results, which run
+/// before its guards
+pub fn with_result_validators(chain: GuardrailChain) -> GuardrailChain {
+    chain
+        .add_tool_validator(
+            search_tool::TOOL_NAME,
+            BoxedGuard::new(SearchResultValidator),
+        )
+        .add_tool_validator(sql_tool::TOOL_NAME, BoxedGuard::new(QueryResultValidator))
+}
+
+/// Manifests of the host tools, used when no TOOL.md is found on disk
+const BUILTIN_MANIFESTS: &[(&str, &str)] = &[
+    ("shell", include_str!("../tools/shell/TOOL.md")),
+    ("apply_patch", include_str!("../tools/apply_patch/TOOL.md")),
+    ("search", include_str!("../tools/search/TOOL.md")),
+    ("sql", include_str!("../tools/sql/TOOL.md")),
+    ("view_image", include_str!("../tools/view_image/TOOL.md")),
+];
+
+#[derive(Debug, Clone)]
+pub struct DiscoveredTool {
+    pub path: PathBuf,
+    pub manifest: ToolManifest,
+}
+
+/// Find `<dir>/<tool>/TOOL.md` manifests
+pub fn discover_tools(tool_dirs: &[PathBuf]) -> Vec<DiscoveredTool> {
+    let mut found = Vec::new();
+
+    for dir in tool_dirs {
+        let Ok(entries) = fs::read_dir(dir) else {
+            continue;
+        };
+
+        let mut paths: Vec<PathBuf> = entries
+            .flatten()
+            .map(|entry| entry.path().join("TOOL.md"))
+            .filter(|path| path.is_file())
+            .collect();
+        paths.sort();
+
+        found.extend(paths.iter().filter_map(|path| load_tool_manifest(path)));
+    }
+
+    found
+}
+
+fn load_tool_manifest(path: &Path) -> Option<DiscoveredTool> {
+    let content = fs::read_to_string(path).ok()?;
+    match parse_tool_manifest(&content) {
+        Ok(manifest) => Some(DiscoveredTool {
+            path: path.to_path_buf(),
+            manifest,
+        }),
+        Err(err) => {
+            console::warn(format!(
+                "Failed to parse tool manifest {}: {}",
+                path.display(),
+                err
+            ));
+            None
+        }
+    }
+}
+
+/// Build the registry of tools the agent may call
+///
+/// Starts from agent-core's default tools and this host's built-in manifests;
+/// discovered manifests override them. Manifests for tools the host
+/// cannot execute are reported and skipped, so the model is never offered a tool
+/// that would fail as unknown.
+pub fn build_tool_registry(discovered: &[DiscoveredTool]) -> ToolRegistry {
+    let mut registry = ToolRegistry::with_default_tools();
+
+    for (name, markdown) in BUILTIN_MANIFESTS {
+        match parse_tool_manifest(markdown) {
+            Ok(manifest) => registry.register(manifest),
+            Err(err) => console::warn(format!("Invalid built-in manifest for '{}': {}", name, err)),
+        }
+    }
+
+    for tool in discovered {
+        if HOST_TOOLS.contains(&tool.manifest.frontmatter.name.as_str()) {
+            registry.register(tool.manifest.clone());
+        } else {
+            console::warn(format!(
+                "Skipping tool '{}' ({}): not implemented by this host",
+                tool.manifest.frontmatter.name,
+                tool.path.display()
+            ));
+        }
+    }
+
+    registry
+}
@@ -0,0 +1,202 @@
+//! Spoken queries (`--audio`)
+//!
+//! A recording is transcribed into the run's query before the agent loop
+//! starts. Two transcribers are available:
+//!
+//! - `api` posts the file to an OpenAI-compatible transcription endpoint
+//!   (`/v1/audio/transcriptions`, or the `/inference` endpoint of whisper.cpp's
+//!   `whisper-server`); `OPENAI_API_KEY` is sent when set
+//! - `whisper-cpp` runs a ggml Whisper model in process; it needs the `whisper`
+//!   feature and a 16 kHz WAV recording
+
+use crate::throttle::{http_agent, Throttle};
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::path::Path;
+
+/// OpenAI's transcription endpoint
+pub const DEFAULT_ENDPOINT: &str = "https://api.openai.com/v1/audio/transcriptions";
+
+/// Model requested from the endpoint when none is configured
+pub const DEFAULT_MODEL: &str = "whisper-1";
+
+/// Sent as a bearer token to the endpoint when set
+pub const API_KEY_VAR: &str = "OPENAI_API_KEY";
+
+/// Boundary between the parts of a multipart request body
+const BOUNDARY: &str = "agent-native-audio-boundary";
+
+/// Where recordings are transcribed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Transcriber {
+    /// An OpenAI-compatible HTTP endpoint
+    #[default]
+    Api,
+    /// whisper.cpp in process (`whisper` feature)
+    WhisperCpp,
+}
+
+/// How to transcribe `--audio`
+#[derive(Debug, Clone)]
+pub struct TranscribeConfig {
+    pub transcriber: Transcriber,
+    /// Endpoint URL (`api`)
+    pub endpoint: String,
+    /// Model id (`api`) or ggml model file (`whisper-cpp`)
+    pub model: Option<String>,
+    /// Spoken language as an ISO-639-1 code; detected when unset
+    pub language: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TranscriptionResponse {
+    text: String,
+}
+
+/// The text spoken in the recording at `path`
+pub fn transcribe(path: &Path, config: &TranscribeConfig) -> Result<String> {
+    let text = match config.transcriber {
+        Transcriber::Api => transcribe_api(path, config)?,
+        Transcriber::WhisperCpp => transcribe_local(path, config)?,
+    };
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.is_empty() {
+        bail!("No speech found in {}", path.display());
+    }
+    Ok(text)
+}
+
+fn transcribe_api(path: &Path, config: &TranscribeConfig) -> Result<String> {
+    let audio =
+        std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "audio.wav".to_string());
+
+    let model = config.model.as_deref().unwrap_or(DEFAULT_MODEL);
+    let mut fields = vec![("model", model), ("response_format", "json")];
+    if let Some(language) = &config.language {
+        fields.push(("language", language));
+    }
+    let body = multipart_body(&fields, &file_name, &audio);
+    let api_key = std::env::var(API_KEY_VAR).ok();
+
+    let agent = http_agent();
+    let response: TranscriptionResponse = Throttle::default()
+        .send(0, || {
+            let request = agent.post(&config.endpoint).header(
+                "content-type",
+                format!("multipart/form-data; boundary={}", BOUNDARY),
+            );
+            match &api_key {
+                Some(key) => request
+                    .header("authorization", format!("Bearer {}", key))
+                    .send(&body[..]),
+                None => request.send(&body[..]),
+            }
+        })
+        .with_context(|| format!("Transcription request to {} failed", config.endpoint))?
+        .body_mut()
+        .read_json()
+        .context("Invalid response from the transcription endpoint")?;
+    Ok(response.text)
+}
+
+/// A `multipart/form-data` body with text `fields` and the audio as `file`
+fn multipart_body(fields: &[(&str, &str)], file_name: &str, audio: &[u8]) -> Vec<u8> {
+    let mut body = Vec::with_capacity(audio.len() + 512);
+    for (name, value) in fields {
+        body.extend_from_slice(
+            format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n",
+                BOUNDARY, name, value
+            )
+            .as_bytes(),
+        );
+    }
+    body.extend_from_slice(
+        format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\n\
+             Content-Type: application/octet-stream\r\n\r\n",
+            BOUNDARY,
+            file_name.replace('"', "")
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(audio);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", BOUNDARY).as_bytes());
+    body
+}
+
+#[cfg(feature = "whisper")]
+fn transcribe_local(path: &Path, config: &TranscribeConfig) -> Result<String> {
+    use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
+
+    let model = config
+        .model
+        .as_deref()
+        .map(std::path::PathBuf::from)
+        .context("--transcriber whisper-cpp needs --transcription-model <ggml model file>")?;
+    let samples = read_wav(path)?;
+
+    let context = WhisperContext::new_with_params(
+        &model.to_string_lossy(),
+        WhisperContextParameters::default(),
+    )
+    .with_context(|| format!("Failed to load Whisper model {}", model.display()))?;
+    let mut state = context.create_state()?;
+    let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+    params.set_language(Some(config.language.as_deref().unwrap_or("auto")));
+    params.set_print_progress(false);
+    params.set_print_realtime(false);
+    params.set_print_timestamps(false);
+    state
+        .full(params, &samples)
+        .context("Whisper transcription failed")?;
+
+    let mut text = String::new();
+    for segment in 0..state.full_n_segments()? {
+        text.push_str(&state.full_get_segment_text(segment)?);
+        text.push(' ');
+    }
+    Ok(text)
+}
+
+#[cfg(not(feature = "whisper"))]
+fn transcribe_local(_path: &Path, _config: &TranscribeConfig) -> Result<String> {
+    bail!("--transcriber whisper-cpp needs a build with the `whisper` feature")
+}
+
+/// Mono samples of a 16 kHz WAV file, as Whisper expects them
+#[cfg(feature = "whisper")]
+fn read_wav(path: &Path) -> Result<Vec<f32>> {
+    let mut reader = hound::WavReader::open(path)
+        .with_context(|| format!("Failed to read {} as WAV", path.display()))?;
+    let spec = reader.spec();
+    if spec.sample_rate != 16_000 {
+        bail!(
+            "{} is sampled at {} Hz; Whisper needs 16 kHz \
+             (convert with `ffmpeg -i {} -ar 16000 -ac 1 out.wav`)",
+            path.display(),
+            spec.sample_rate,
+            path.display()
+        );
+    }
+    let samples: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>()?,
+        hound::SampleFormat::Int => {
+            let scale = (1_i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|sample| sample.map(|sample| sample as f32 / scale))
+                .collect::<Result<_, _>>()?
+        }
+    };
+    let channels = spec.channels.max(1) as usize;
+    Ok(samples
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+        .collect())
+}
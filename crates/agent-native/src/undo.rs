@@ -0,0 +1,156 @@
+//! Pre-images of the files a run changes, and `agent undo`
+//!
+//! Before a tool writes a file, the file's current content is saved under its
+//! SHA-256 in `<run dir>/undo/`, and the change is recorded in the audit log
+//! (see [`agent_core::undo`]). `agent undo <run-id>` reads the log back and
+//! restores what it can. Changes made by shell commands are not journaled.
+
+use crate::audit::AUDIT_FILE;
+use crate::console;
+use agent_core::audit::{sha256_hex, verify_log, AuditEntry};
+use agent_core::undo::{plan_undo, FileUndo, UndoStep};
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use uuid::Uuid;
+
+/// Pre-image directory inside a run directory
+pub const UNDO_DIR: &str = "undo";
+
+/// Where the current run keeps pre-images, and the changes not reported yet
+#[derive(Debug)]
+pub struct UndoJournal {
+    dir: PathBuf,
+    pending: Mutex<Vec<FileUndo>>,
+}
+
+impl UndoJournal {
+    /// Journal of run `run` under `artifacts_dir`, which should be absolute
+    /// so a sandbox run still writes to the real tree
+    pub fn new(artifacts_dir: &Path, run: &str) -> Self {
+        Self {
+            dir: artifacts_dir.join(run).join(UNDO_DIR),
+            pending: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Save `before` so a change of `path` from `before` to `after` can be
+    /// undone; `None` stands for a file that does not exist
+    ///
+    /// The change is reported once [`record`](Self::record)ed.
+    pub fn save(
+        &self,
+        path: &Path,
+        before: Option<&str>,
+        after: Option<&str>,
+    ) -> io::Result<FileUndo> {
+        let before = match before {
+            Some(content) => {
+                let hash = sha256_hex(content.as_bytes());
+                let image = self.dir.join(&hash);
+                if !image.exists() {
+                    fs::create_dir_all(&self.dir)?;
+                    fs::write(&image, content)?;
+                }
+                Some(hash)
+            }
+            None => None,
+        };
+        Ok(FileUndo {
+            path: path.to_string_lossy().into_owned(),
+            before,
+            after: after.map(|content| sha256_hex(content.as_bytes())),
+        })
+    }
+
+    /// Note changes that were made
+    pub fn record(&self, changes: Vec<FileUndo>) {
+        self.pending.lock().unwrap().extend(changes);
+    }
+
+    /// Changes recorded since the last call
+    pub fn take(&self) -> Vec<FileUndo> {
+        std::mem::take(&mut *self.pending.lock().unwrap())
+    }
+}
+
+/// Put back the files run `run` changed, or only say what would happen
+pub fn undo_run(artifacts_dir: &Path, run: &str, dry_run: bool) -> Result<()> {
+    let run = Uuid::parse_str(run)
+        .map_err(|_| anyhow::anyhow!("Invalid run id '{}'", run))?
+        .to_string();
+    let run_dir = artifacts_dir.join(&run);
+    let path = run_dir.join(AUDIT_FILE);
+    let log = fs::read_to_string(&path)
+        .with_context(|| format!("No audit log for run {} ({})", run, path.display()))?;
+    let records = verify_log(&log)
+        .with_context(|| format!("Audit log {} fails verification", path.display()))?;
+
+    let changes: Vec<&FileUndo> = records
+        .iter()
+        .filter_map(|record| match &record.entry {
+            AuditEntry::FilesChanged { files, .. } => Some(files),
+            _ => None,
+        })
+        .flatten()
+        .collect();
+    if changes.is_empty() {
+        println!("Run {} changed no files through its tools", run);
+        return Ok(());
+    }
+
+    let steps = plan_undo(changes, |path| {
+        fs::read(path).ok().map(|content| sha256_hex(&content))
+    });
+    let mut failed = 0;
+    for step in &steps {
+        let (verb, done, result) = match step {
+            UndoStep::Skip { reason, .. } => {
+                console::warn(format!("Skipped {}: {}", step.path(), reason.as_str()));
+                continue;
+            }
+            UndoStep::Restore { path, before } => (
+                "restore",
+                "Restored",
+                (!dry_run).then(|| restore(&run_dir, path, before)),
+            ),
+            UndoStep::Remove { path } => (
+                "remove",
+                "Removed",
+                (!dry_run).then(|| fs::remove_file(path).map_err(Into::into)),
+            ),
+        };
+        match result {
+            None => println!("Would {} {}", verb, step.path()),
+            Some(Ok(())) => console::success(format!("{} {}", done, step.path())),
+            Some(Err(e)) => {
+                failed += 1;
+                console::failure(format!("Cannot {} {}: {:#}", verb, step.path(), e));
+            }
+        }
+    }
+    if failed > 0 {
+        bail!("{} of {} file(s) could not be undone", failed, steps.len());
+    }
+    Ok(())
+}
+
+/// Write the pre-image `hash` of run `run_dir` back to `path`
+fn restore(run_dir: &Path, path: &str, hash: &str) -> Result<()> {
+    let image = run_dir.join(UNDO_DIR).join(hash);
+    let content =
+        fs::read(&image).with_context(|| format!("pre-image {} is missing", image.display()))?;
+    if sha256_hex(&content) != hash {
+        bail!("pre-image {} was modified", image.display());
+    }
+    if let Some(dir) = Path::new(path)
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+    {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, content)?;
+    Ok(())
+}
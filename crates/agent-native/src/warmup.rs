@@ -0,0 +1,42 @@
+//! `agent warmup`: pay a backend's start-up cost ahead of a run
+//!
+//! The first run on a machine reads the whole GGUF from disk and, on Metal,
+//! compiles the shaders on its first token. Warming up does both once, so the
+//! file sits in the page cache and the shaders in the system's shader cache
+//! when the next run (or `extract`) loads the model. Within one process the
+//! model is loaded once anyway (see [`LlamaCppModel`](crate::llama_cpp_backend::LlamaCppModel)).
+//! For a server backend it is the server that loads the model on the first
+//! request.
+
+use crate::console;
+use crate::llm::{BackendError, LLMInput};
+use crate::{load_backend, BackendConfig};
+use anyhow::{Context, Result};
+use std::time::Instant;
+
+/// Load the backend and generate one token
+pub fn run(config: &BackendConfig) -> Result<()> {
+    let started = Instant::now();
+    let mut backend = load_backend(config)?;
+    let loaded = started.elapsed();
+
+    let started = Instant::now();
+    backend
+        .infer(LLMInput {
+            prompt: "Hello".to_string(),
+            max_tokens: 1,
+            stop: Vec::new(),
+            first_generation: true,
+            chat: None,
+            images: Vec::new(),
+            progress: None,
+        })
+        .context(BackendError)?;
+    console::success(format!(
+        "{} is warm: loaded in {:.1}s, first token in {:.1}s",
+        config.describe(),
+        loaded.as_secs_f32(),
+        started.elapsed().as_secs_f32()
+    ));
+    Ok(())
+}
@@ -0,0 +1,32 @@
+import type {
+  AgentStateJson,
+  DecisionOutput,
+  Message,
+  SkillResult,
+  ToolResult,
+} from "./pkg/agent_wasm.js";
+
+export { default as init } from "./pkg/agent_wasm.js";
+export type * from "./pkg/agent_wasm.js";
+
+export declare class Agent {
+  /** Start a run with a user query (call `init()` first) */
+  constructor(query: string);
+  /** Resume a run from state JSON (`toJSON()`), migrating older versions */
+  static fromJSON(stateJson: string | AgentStateJson): Agent;
+  /** Process one model output; returns (and keeps) the decision */
+  step(modelOutput: string): DecisionOutput;
+  /** The last decision, or `null` before the first step */
+  readonly decision: DecisionOutput | null;
+  /** Feed back the result of an `invoke_tool`, `read_artifact` or `recall` decision */
+  toolResult(result: ToolResult): void;
+  /** Feed back the result of an `invoke_skill` decision */
+  skillResult(result: SkillResult): void;
+  readonly state: AgentStateJson;
+  readonly history: Message[];
+  readonly isComplete: boolean;
+  /** State JSON to persist; `Agent.fromJSON` restores it */
+  toJSON(): AgentStateJson;
+  /** Release the WASM-side state; the agent is unusable afterwards */
+  free(): void;
+}
@@ -0,0 +1,90 @@
+// Idiomatic wrapper over agent-wasm's handle API: the state stays inside WASM
+// and decisions come back as objects instead of JSON strings.
+
+import init, {
+  apply_skill_result,
+  apply_tool_result,
+  create_state,
+  export_state,
+  free_state,
+  import_state,
+  step,
+} from "./pkg/agent_wasm.js";
+
+export { init };
+
+// Lets `fromJSON` hand the constructor an imported state instead of a query
+class Imported {
+  constructor(handle) {
+    this.handle = handle;
+  }
+}
+
+export class Agent {
+  #handle;
+  #decision = null;
+
+  /** Start a run with a user query (call `init()` first) */
+  constructor(query) {
+    this.#handle =
+      query instanceof Imported ? query.handle : create_state(query);
+  }
+
+  /** Resume a run from state JSON (`toJSON()`), migrating older versions */
+  static fromJSON(stateJson) {
+    const json =
+      typeof stateJson === "string" ? stateJson : JSON.stringify(stateJson);
+    return new Agent(new Imported(import_state(json)));
+  }
+
+  /** Process one model output; returns (and keeps) the decision */
+  step(modelOutput) {
+    this.#decision = JSON.parse(step(this.#handle, modelOutput));
+    return this.#decision;
+  }
+
+  /** The last decision, or `null` before the first step */
+  get decision() {
+    return this.#decision;
+  }
+
+  /** Feed back the result of an `invoke_tool`, `read_artifact` or `recall` decision */
+  toolResult(result) {
+    apply_tool_result(this.#handle, JSON.stringify(this.#withCallId(result)));
+  }
+
+  /** Feed back the result of an `invoke_skill` decision */
+  skillResult(result) {
+    apply_skill_result(this.#handle, JSON.stringify(this.#withCallId(result)));
+  }
+
+  get state() {
+    return JSON.parse(export_state(this.#handle));
+  }
+
+  get history() {
+    return this.state.history;
+  }
+
+  get isComplete() {
+    return this.#decision?.type === "done";
+  }
+
+  /** State JSON to persist; `Agent.fromJSON` restores it */
+  toJSON() {
+    return this.state;
+  }
+
+  /** Release the WASM-side state; the agent is unusable afterwards */
+  free() {
+    free_state(this.#handle);
+  }
+
+  // Results answer the last decision unless they name a call themselves
+  #withCallId(result) {
+    const callId = this.#decision?.call_id;
+    return result.call_id == null && callId != null
+      ? { ...result, call_id: callId }
+      : result;
+  }
+}
@@ -0,0 +1,149 @@
+//! Agent states kept inside WASM, addressed by handle
+//!
+//! `run_agent_step` and `run_agent_step_cbor` pass the whole state across the
+//! boundary on every step. Here the state stays on the WASM side: the host
+//! holds a `u32` handle, sends only model output and results in, and exports
+//! the state when it wants to persist it.
+//!
+//! ```javascript
+//! const handle = create_state("What is 2 + 2?");
+//! const decision = JSON.parse(step(handle, modelOutput));
+//! if (decision.type === "invoke_tool") {
+//!   apply_tool_result(handle, JSON.stringify({ ...await run(decision), call_id: decision.call_id }));
+//! }
+//! const saved = export_state(handle);
+//! free_state(handle);
+//! ```
+//!
+//! Handles are never reused, so a freed handle stays invalid.
+
+use crate::{decide, parse_capabilities};
+use agent_core::{skill::SkillResult_, AgentState, ToolResult};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
+
+/// Live states by handle
+#[derive(Default)]
+struct States {
+    states: HashMap<u32, AgentState>,
+    last_handle: u32,
+}
+
+impl States {
+    fn insert(&mut self, state: AgentState) -> u32 {
+        self.last_handle += 1;
+        self.states.insert(self.last_handle, state);
+        self.last_handle
+    }
+}
+
+thread_local! {
+    static STATES: RefCell<States> = RefCell::new(States::default());
+}
+
+fn with_state<T>(handle: u32, f: impl FnOnce(&mut AgentState) -> T) -> Result<T, JsValue> {
+    STATES.with(|states| {
+        let mut states = states.borrow_mut();
+        let state = states
+            .states
+            .get_mut(&handle)
+            .ok_or_else(|| JsValue::from_str(&format!("Unknown state handle: {}", handle)))?;
+        Ok(f(state))
+    })
+}
+
+/// Create a state with a user query and return its handle
+#[wasm_bindgen]
+pub fn create_state(query: &str) -> u32 {
+    STATES.with(|states| states.borrow_mut().insert(AgentState::new(query)))
+}
+
+/// Load state JSON (migrating older versions) and return its handle
+#[wasm_bindgen]
+pub fn import_state(state_json: &str) -> Result<u32, JsValue> {
+    let state = AgentState::from_versioned_json(state_json)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    Ok(STATES.with(|states| states.borrow_mut().insert(state)))
+}
+
+/// Declare what this host can run for a state (`HostCapabilities` JSON);
+/// capabilities are not exported with the state
+#[wasm_bindgen]
+pub fn set_capabilities(handle: u32, capabilities_json: &str) -> Result<(), JsValue> {
+    let capabilities = parse_capabilities(capabilities_json)?;
+    with_state(handle, |state| state.capabilities = capabilities)
+}
+
+/// Process a model output against a state; returns the decision as JSON
+/// (same shape as `run_agent_step`'s `decision`)
+#[wasm_bindgen]
+pub fn step(handle: u32, model_output: &str) -> Result<String, JsValue> {
+    let decision = with_state(handle, |state| decide(state, model_output.to_string()))?;
+    serde_json::to_string(&decision)
+        .map_err(|e| JsValue::from_str(&format!("Failed to serialize decision: {}", e)))
+}
+
+/// Add a tool result (JSON, with the decision's `call_id`) to a state
+#[wasm_bindgen]
+pub fn apply_tool_result(handle: u32, result_json: &str) -> Result<(), JsValue> {
+    let result: ToolResult = serde_json::from_str(result_json)
+        .map_err(|e| JsValue::from_str(&format!("Invalid tool result JSON: {}", e)))?;
+    with_state(handle, |state| {
+        agent_core::agent::apply_tool_result(state, &result)
+    })
+}
+
+/// Add a skill result (JSON, with the decision's `call_id`) to a state
+#[wasm_bindgen]
+pub fn apply_skill_result(handle: u32, result_json: &str) -> Result<(), JsValue> {
+    let result: SkillResult_ = serde_json::from_str(result_json)
+        .map_err(|e| JsValue::from_str(&format!("Invalid skill result JSON: {}", e)))?;
+    with_state(handle, |state| {
+        agent_core::agent::apply_skill_result(state, &result)
+    })
+}
+
+/// A state as JSON, e.g. to persist it or hand it to `run_agent_step`
+#[wasm_bindgen]
+pub fn export_state(handle: u32) -> Result<String, JsValue> {
+    with_state(handle, |state| serde_json::to_string(state))?
+        .map_err(|e| JsValue::from_str(&format!("Failed to serialize state: {}", e)))
+}
+
+/// Drop a state; returns whether the handle was live
+#[wasm_bindgen]
+pub fn free_state(handle: u32) -> bool {
+    STATES.with(|states| states.borrow_mut().states.remove(&handle).is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_handles() {
+        let handle = create_state("What is 2+2?");
+        let other = create_state("Other");
+        assert_ne!(handle, other);
+
+        let decision: serde_json::Value = serde_json::from_str(
+            &step(handle, r#"{"tool":"eval_math","expression":"2+2"}"#).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(decision["type"], "invoke_tool");
+        let result = crate::execute_default_tool(r#"{"tool":"eval_math","expression":"2+2"}"#)
+            .unwrap()
+            .replace('{', r#"{"call_id":1,"#);
+        apply_tool_result(handle, &result).unwrap();
+
+        let state = AgentState::from_versioned_json(&export_state(handle).unwrap()).unwrap();
+        assert_eq!(state.history.len(), 3);
+        assert_eq!(state.history[2].call_id, Some(agent_core::CallId(1)));
+
+        let imported = import_state(&export_state(handle).unwrap()).unwrap();
+        assert!(free_state(handle));
+        assert!(!free_state(handle));
+        assert!(free_state(imported) && free_state(other));
+    }
+}
@@ -4,12 +4,35 @@
 //!
 //! This demonstrates that the agent logic is portable and can run in WASM.
 //! The LLM inference and tool execution happen outside WASM - this module
-//! only proves the decision-making logic is sandboxable.
+//! only proves the decision-making logic is sandboxable. The exception is
+//! agent-core's pure default tools (`eval_math`), which run here too.
+//!
+//! State crosses the boundary as JSON (`run_agent_step`), as CBOR
+//! (`run_agent_step_cbor`), or stays inside WASM behind a handle
+//! (`create_state`, `step`).
 
-use agent_core::{agent::process_model_output, AgentState};
+use agent_core::{
+    agent::process_model_output, approval::ApprovalPolicy, few_shot::FewShotPolicy,
+    profile::GuardrailSet, protocol::parse_model_output_with, skill::SkillResult_,
+    tool_manifest::parse_tool_manifest, AgentSession, AgentState, AnswerProtocol, CallId,
+    HostCapabilities, InconclusiveReason, ParseResult, PromptSpec, ResponseSpec, SessionError,
+    StateError, StopSequences, ToolRegistry, ToolRequest, ToolResult,
+};
 use serde::{Deserialize, Serialize};
 use wasm_bindgen::prelude::*;
 
+mod handles;
+
+pub use handles::{
+    apply_skill_result, apply_tool_result, create_state, export_state, free_state, import_state,
+    set_capabilities, step,
+};
+
+// TypeScript declarations for the JSON shapes below (`DecisionOutput`,
+// `StepAction`, ...), appended to wasm-bindgen's generated `.d.ts`
+#[wasm_bindgen(typescript_custom_section)]
+const TS_TYPES: &str = include_str!("types.d.ts");
+
 /// Input to the agent step function
 #[derive(Debug, Serialize, Deserialize)]
 pub struct StepInput {
@@ -18,6 +41,10 @@ pub struct StepInput {
 
     /// The latest model output
     pub model_output: String,
+
+    /// What this host can run; the default allows everything
+    #[serde(default)]
+    pub capabilities: HostCapabilities,
 }
 
 /// Output from the agent step function
@@ -34,23 +61,168 @@ pub struct StepOutput {
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum DecisionOutput {
-    /// Invoke a tool
+    /// Invoke a tool; its result goes back with the same `call_id`
     InvokeTool {
         tool: String,
         params: serde_json::Value,
+        call_id: Option<CallId>,
+        /// The name the model used, when it called the tool by another one
+        requested_as: Option<String>,
     },
 
     /// Invoke a skill
     InvokeSkill {
         skill: String,
         params: serde_json::Value,
+        call_id: Option<CallId>,
+    },
+
+    /// Read lines or a page of a stored tool output (the host owns artifact
+    /// storage)
+    ReadArtifact {
+        id: String,
+        range: Option<(usize, usize)>,
+        page: Option<usize>,
+        call_id: Option<CallId>,
     },
 
+    /// Search the memory of earlier runs (the host owns the memory)
+    Recall {
+        query: String,
+        k: Option<usize>,
+        call_id: Option<CallId>,
+    },
+
+    /// A scratchpad variable was stored or read; already applied to the state,
+    /// so the host only asks the model again
+    Variable {
+        operation: String,
+        success: bool,
+        output: String,
+    },
+
+    /// The host cannot do what the model asked; the reason is already in the
+    /// history, so the host only asks the model again
+    Unsupported { reason: String },
+
     /// Agent is done
     Done { answer: String },
 
-    /// Model produced inconclusive output (reasoning without action)
-    Inconclusive { output: String },
+    /// Model produced inconclusive output (reasoning without action), and why
+    Inconclusive {
+        output: String,
+        reason: InconclusiveReason,
+    },
+}
+
+/// What a model output asks for, as `parse_model_output` reports it
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ParseOutput {
+    ToolCall {
+        tool: String,
+        params: serde_json::Value,
+        call_id: Option<CallId>,
+    },
+    SkillCall {
+        skill: String,
+        params: serde_json::Value,
+        call_id: Option<CallId>,
+    },
+    ReadArtifact {
+        id: String,
+        range: Option<(usize, usize)>,
+        page: Option<usize>,
+        call_id: Option<CallId>,
+    },
+    Recall {
+        query: String,
+        k: Option<usize>,
+        call_id: Option<CallId>,
+    },
+    /// `set_var` or `get_var`; `value` is set for `set_var`
+    Variable {
+        operation: String,
+        name: String,
+        value: Option<serde_json::Value>,
+    },
+    FinalAnswer {
+        answer: String,
+    },
+    Inconclusive {
+        output: String,
+        reason: InconclusiveReason,
+    },
+}
+
+impl From<ParseResult> for ParseOutput {
+    fn from(result: ParseResult) -> Self {
+        match result {
+            ParseResult::ToolCall(req) => ParseOutput::ToolCall {
+                tool: req.tool,
+                params: req.params,
+                call_id: req.call_id,
+            },
+            ParseResult::SkillCall(req) => ParseOutput::SkillCall {
+                skill: req.skill,
+                params: req.params,
+                call_id: req.call_id,
+            },
+            ParseResult::ReadArtifact(req) => ParseOutput::ReadArtifact {
+                id: req.id,
+                range: req.range,
+                page: req.page,
+                call_id: req.call_id,
+            },
+            ParseResult::Recall(req) => ParseOutput::Recall {
+                query: req.query,
+                k: req.k,
+                call_id: req.call_id,
+            },
+            ParseResult::Variable(req) => {
+                let operation = req.operation().to_string();
+                let (name, value) = match req {
+                    agent_core::VariableRequest::SetVar { name, value } => (name, Some(value)),
+                    agent_core::VariableRequest::GetVar { name } => (name, None),
+                };
+                ParseOutput::Variable {
+                    operation,
+                    name,
+                    value,
+                }
+            }
+            ParseResult::FinalAnswer(answer) => ParseOutput::FinalAnswer { answer },
+            ParseResult::Inconclusive(output, reason) => {
+                ParseOutput::Inconclusive { output, reason }
+            }
+        }
+    }
+}
+
+/// Parse a model output without touching any state
+///
+/// Lets hosts look at an output before committing it to a step, e.g. to
+/// stream a final answer to the UI but hold back a tool call. Returns a
+/// `ParseOutput` as JSON. `answer_protocol` is `"heuristic"` (default) or
+/// `"sentinel"`. Reasoning blocks are not taken out here; the step functions
+/// do that with the state's delimiters.
+#[wasm_bindgen]
+pub fn parse_model_output(
+    model_output: &str,
+    answer_protocol: Option<String>,
+) -> Result<String, JsValue> {
+    let protocol = match answer_protocol.as_deref() {
+        None => AnswerProtocol::default(),
+        Some(name) => AnswerProtocol::from_str(name).ok_or_else(|| {
+            JsValue::from_str(&format!(
+                "Invalid answer protocol '{}'. Expected one of: heuristic, sentinel",
+                name
+            ))
+        })?,
+    };
+    let output = ParseOutput::from(parse_model_output_with(model_output, protocol));
+    serde_json::to_string(&output)
+        .map_err(|e| JsValue::from_str(&format!("Failed to serialize parse result: {}", e)))
 }
 
 /// Run one step of the agent loop in WASM
@@ -65,7 +237,7 @@ pub enum DecisionOutput {
 ///
 /// ```javascript
 /// const input = {
-///   state_json: '{"history":[...],"is_complete":false,"final_answer":null}',
+///   state_json: '{"version":8,"history":[...],"is_complete":false,"final_answer":null}',
 ///   model_output: '{"tool":"shell","command":"ls"}'
 /// };
 /// const output = run_agent_step(JSON.stringify(input));
@@ -77,26 +249,13 @@ pub fn run_agent_step(input_json: &str) -> Result<String, JsValue> {
     let input: StepInput = serde_json::from_str(input_json)
         .map_err(|e| JsValue::from_str(&format!("Invalid input JSON: {}", e)))?;
 
-    // Deserialize state
-    let mut state: AgentState = serde_json::from_str(&input.state_json)
-        .map_err(|e| JsValue::from_str(&format!("Invalid state JSON: {}", e)))?;
+    // Deserialize state, migrating state saved by older versions
+    let mut state = AgentState::from_versioned_json(&input.state_json)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
 
     // Process model output
-    let decision = process_model_output(&mut state, input.model_output);
-
-    // Convert decision to output format
-    let decision_output = match decision {
-        agent_core::AgentDecision::InvokeTool(req) => DecisionOutput::InvokeTool {
-            tool: req.tool,
-            params: req.params,
-        },
-        agent_core::AgentDecision::InvokeSkill(req) => DecisionOutput::InvokeSkill {
-            skill: req.skill,
-            params: req.params,
-        },
-        agent_core::AgentDecision::Done(answer) => DecisionOutput::Done { answer },
-        agent_core::AgentDecision::Inconclusive(output) => DecisionOutput::Inconclusive { output },
-    };
+    state.capabilities = input.capabilities;
+    let decision = decide(&mut state, input.model_output);
 
     // Serialize state
     let state_json = serde_json::to_string(&state)
@@ -105,7 +264,7 @@ pub fn run_agent_step(input_json: &str) -> Result<String, JsValue> {
     // Create output
     let output = StepOutput {
         state_json,
-        decision: decision_output,
+        decision,
     };
 
     // Serialize output
@@ -113,6 +272,51 @@ pub fn run_agent_step(input_json: &str) -> Result<String, JsValue> {
         .map_err(|e| JsValue::from_str(&format!("Failed to serialize output: {}", e)))
 }
 
+fn parse_capabilities(capabilities_json: &str) -> Result<HostCapabilities, JsValue> {
+    serde_json::from_str(capabilities_json)
+        .map_err(|e| JsValue::from_str(&format!("Invalid capabilities JSON: {}", e)))
+}
+
+/// Process one model output and convert the decision to its output format
+fn decide(state: &mut AgentState, model_output: String) -> DecisionOutput {
+    match process_model_output(state, model_output) {
+        agent_core::AgentDecision::InvokeTool(req) => DecisionOutput::InvokeTool {
+            requested_as: req
+                .call_id
+                .and_then(|id| state.aliased_calls.get(&id).cloned()),
+            tool: req.tool,
+            params: req.params,
+            call_id: req.call_id,
+        },
+        agent_core::AgentDecision::InvokeSkill(req) => DecisionOutput::InvokeSkill {
+            skill: req.skill,
+            params: req.params,
+            call_id: req.call_id,
+        },
+        agent_core::AgentDecision::ReadArtifact(req) => DecisionOutput::ReadArtifact {
+            id: req.id,
+            range: req.range,
+            page: req.page,
+            call_id: req.call_id,
+        },
+        agent_core::AgentDecision::Recall(req) => DecisionOutput::Recall {
+            query: req.query,
+            k: req.k,
+            call_id: req.call_id,
+        },
+        agent_core::AgentDecision::Variable { operation, result } => DecisionOutput::Variable {
+            operation,
+            success: result.success,
+            output: result.error.unwrap_or(result.output),
+        },
+        agent_core::AgentDecision::Unsupported(reason) => DecisionOutput::Unsupported { reason },
+        agent_core::AgentDecision::Done(answer) => DecisionOutput::Done { answer },
+        agent_core::AgentDecision::Inconclusive(output, reason) => {
+            DecisionOutput::Inconclusive { output, reason }
+        }
+    }
+}
+
 /// Create a new agent state with a user query
 #[wasm_bindgen]
 pub fn create_agent_state(query: &str) -> Result<String, JsValue> {
@@ -121,6 +325,336 @@ pub fn create_agent_state(query: &str) -> Result<String, JsValue> {
         .map_err(|e| JsValue::from_str(&format!("Failed to serialize state: {}", e)))
 }
 
+/// Output from the CBOR step function
+#[wasm_bindgen]
+pub struct CborStepOutput {
+    state: Vec<u8>,
+    decision_json: String,
+}
+
+#[wasm_bindgen]
+impl CborStepOutput {
+    /// The updated agent state as CBOR
+    #[wasm_bindgen(getter)]
+    pub fn state(&self) -> Vec<u8> {
+        self.state.clone()
+    }
+
+    /// The decision as JSON (same shape as `run_agent_step`'s `decision`)
+    #[wasm_bindgen(getter)]
+    pub fn decision_json(&self) -> String {
+        self.decision_json.clone()
+    }
+}
+
+/// Run one step of the agent loop on CBOR state
+///
+/// Like `run_agent_step`, but the state goes in and out as CBOR bytes
+/// (`Uint8Array`), which skips JSON encoding and parsing of the whole history
+/// on every step. The decision is still JSON.
+///
+/// ```javascript
+/// let state = create_agent_state_cbor("What is 2 + 2?");
+/// const output = run_agent_step_cbor(state, modelOutput);
+/// state = output.state;
+/// const decision = JSON.parse(output.decision_json);
+/// ```
+#[wasm_bindgen]
+pub fn run_agent_step_cbor(state: &[u8], model_output: &str) -> Result<CborStepOutput, JsValue> {
+    let mut state = AgentState::from_cbor(state).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let decision = decide(&mut state, model_output.to_string());
+    Ok(CborStepOutput {
+        state: state
+            .to_cbor()
+            .map_err(|e| JsValue::from_str(&format!("Failed to serialize state: {}", e)))?,
+        decision_json: serde_json::to_string(&decision)
+            .map_err(|e| JsValue::from_str(&format!("Failed to serialize decision: {}", e)))?,
+    })
+}
+
+/// Create an agent state continuing a conversation in OpenAI chat
+/// completions format (a `messages` array or a request body holding one)
+#[wasm_bindgen]
+pub fn import_openai_conversation(messages_json: &str) -> Result<String, JsValue> {
+    conversation_state(AgentState::from_openai_messages(messages_json))
+}
+
+/// Create an agent state continuing a conversation in Anthropic Messages API
+/// format (a `messages` array or a request body holding one)
+#[wasm_bindgen]
+pub fn import_anthropic_conversation(messages_json: &str) -> Result<String, JsValue> {
+    conversation_state(AgentState::from_anthropic_messages(messages_json))
+}
+
+fn conversation_state(state: Result<AgentState, StateError>) -> Result<String, JsValue> {
+    let state = state.map_err(|e| JsValue::from_str(&e.to_string()))?;
+    serde_json::to_string(&state)
+        .map_err(|e| JsValue::from_str(&format!("Failed to serialize state: {}", e)))
+}
+
+/// Create a new agent state with a user query, as CBOR
+#[wasm_bindgen]
+pub fn create_agent_state_cbor(query: &str) -> Result<Vec<u8>, JsValue> {
+    AgentState::new(query)
+        .to_cbor()
+        .map_err(|e| JsValue::from_str(&format!("Failed to serialize state: {}", e)))
+}
+
+/// Convert state JSON to CBOR (migrating older versions)
+#[wasm_bindgen]
+pub fn state_json_to_cbor(state_json: &str) -> Result<Vec<u8>, JsValue> {
+    AgentState::from_versioned_json(state_json)
+        .and_then(|state| state.to_cbor())
+        .map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Convert CBOR state to JSON, e.g. to inspect or persist it
+#[wasm_bindgen]
+pub fn state_cbor_to_json(state: &[u8]) -> Result<String, JsValue> {
+    let state = AgentState::from_cbor(state).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    serde_json::to_string(&state)
+        .map_err(|e| JsValue::from_str(&format!("Failed to serialize state: {}", e)))
+}
+
+/// The "Available tools" prompt section for the tools agent-core executes
+/// itself (currently `eval_math`), followed by an example call of each
+#[wasm_bindgen]
+pub fn default_tools_prompt() -> String {
+    tools_prompt(&ToolRegistry::with_default_tools())
+}
+
+fn tools_prompt(registry: &ToolRegistry) -> String {
+    let examples = FewShotPolicy::default().build_prompt(&registry.examples());
+    format!("{}\n\n{}", registry.build_prompt(), examples)
+        .trim_end()
+        .to_string()
+}
+
+/// The host's own tools, from their TOOL.md manifests, next to the default tools
+///
+/// Gives JavaScript hosts what the native runtime does with `tools/`: the
+/// prompt section, parameter checks, and the approval decision.
+///
+/// ```javascript
+/// const tools = new Tools([readFileSync("tools/shell/TOOL.md", "utf8")], ["network=auto"]);
+/// const session = new Session(query, `${SYSTEM}\n\n${tools.prompt()}`, 5);
+/// // on call_tool:
+/// const mode = tools.check(JSON.stringify(action.request)); // "auto" | "prompt" | "deny"
+/// ```
+#[wasm_bindgen]
+pub struct Tools {
+    registry: ToolRegistry,
+    approval: ApprovalPolicy,
+}
+
+#[wasm_bindgen]
+impl Tools {
+    /// The default tools plus one per TOOL.md in `manifests`; `approval_rules`
+    /// are `target=mode` rules as for the native `--approve`
+    #[wasm_bindgen(constructor)]
+    pub fn new(manifests: Vec<String>, approval_rules: Vec<String>) -> Result<Tools, JsValue> {
+        let mut registry = ToolRegistry::with_default_tools();
+        for markdown in &manifests {
+            let manifest = parse_tool_manifest(markdown)
+                .map_err(|e| JsValue::from_str(&format!("Invalid TOOL.md: {}", e)))?;
+            registry.register(manifest);
+        }
+        let approval = approval_rules
+            .iter()
+            .try_fold(ApprovalPolicy::default(), |policy, rule| {
+                policy.with_rule(rule)
+            })
+            .map_err(|e| JsValue::from_str(&e))?;
+        Ok(Tools { registry, approval })
+    }
+
+    /// The "Available tools" prompt section, followed by an example call of
+    /// each tool
+    pub fn prompt(&self) -> String {
+        tools_prompt(&self.registry)
+    }
+
+    /// The tool request (JSON) with its parameters mapped onto the declared
+    /// ones (`cmd` to `command`, `["ls", "-la"]` to `"ls -la"`); check and
+    /// execute this rather than the model's request
+    pub fn coerce(&self, request_json: &str) -> Result<String, JsValue> {
+        let mut request: ToolRequest = serde_json::from_str(request_json)
+            .map_err(|e| JsValue::from_str(&format!("Invalid request JSON: {}", e)))?;
+        self.registry.coerce_request(&mut request);
+        serde_json::to_string(&request).map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    /// Check a tool request (JSON) against its manifest and decide its approval:
+    /// `"auto"`, `"prompt"` or `"deny"`. Fails for unknown tools and invalid
+    /// parameters; send that back to the model as a failed result.
+    pub fn check(&self, request_json: &str) -> Result<String, JsValue> {
+        let request: ToolRequest = serde_json::from_str(request_json)
+            .map_err(|e| JsValue::from_str(&format!("Invalid request JSON: {}", e)))?;
+        let manifest = self
+            .registry
+            .check_request(&request)
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        let mode = self
+            .approval
+            .decide(&request.tool, manifest.frontmatter.safety);
+        Ok(mode.as_str().to_string())
+    }
+}
+
+/// Execute one of agent-core's default tools locally
+///
+/// Takes the tool request as JSON (as in an `invoke_tool` decision) and
+/// returns the tool result as JSON. Fails for tools the host must execute.
+#[wasm_bindgen]
+pub fn execute_default_tool(request_json: &str) -> Result<String, JsValue> {
+    let request: ToolRequest = serde_json::from_str(request_json)
+        .map_err(|e| JsValue::from_str(&format!("Invalid request JSON: {}", e)))?;
+    let result = agent_core::execute_builtin_tool(&request)
+        .ok_or_else(|| JsValue::from_str(&format!("Not a default tool: {}", request.tool)))?;
+    serde_json::to_string(&result)
+        .map_err(|e| JsValue::from_str(&format!("Failed to serialize result: {}", e)))
+}
+
+/// An agent run driven from JavaScript (see [`AgentSession`])
+///
+/// Unlike `run_agent_step`, the session keeps the state and does the
+/// sequencing: iterations, corrective retries and guardrail checks. Actions and
+/// results cross the boundary as JSON.
+///
+/// ```javascript
+/// const session = new Session(query, systemPrompt, 5);
+/// while (true) {
+///   const prompt = JSON.parse(session.next_prompt());
+///   let action = JSON.parse(session.accept_model_output(await infer(prompt.text)));
+///   if (action.action === "call_tool") {
+///     action = JSON.parse(session.accept_tool_result(action.id, JSON.stringify(await run(action.request))));
+///   }
+///   if (action.action === "done" || action.action === "failed") break;
+/// }
+/// ```
+#[wasm_bindgen]
+pub struct Session {
+    inner: AgentSession,
+}
+
+/// A prompt as `next_prompt` returns it: the spec plus its plain-text rendering
+#[derive(Serialize)]
+struct PromptOutput {
+    #[serde(flatten)]
+    spec: PromptSpec,
+    text: String,
+}
+
+#[wasm_bindgen]
+impl Session {
+    /// A session with the default guardrails
+    #[wasm_bindgen(constructor)]
+    pub fn new(query: &str, system_prompt: &str, max_iterations: usize) -> Session {
+        let guardrails = GuardrailSet::default();
+        Session {
+            inner: AgentSession::new(query, system_prompt)
+                .with_max_iterations(max_iterations)
+                .with_guardrails(guardrails.output_chain())
+                .with_answer_guards(guardrails.answer_chain()),
+        }
+    }
+
+    /// The session with what this host can run (`HostCapabilities` JSON)
+    pub fn with_capabilities(self, capabilities_json: &str) -> Result<Session, JsValue> {
+        Ok(Session {
+            inner: self
+                .inner
+                .with_capabilities(parse_capabilities(capabilities_json)?),
+        })
+    }
+
+    /// The session with answer constraints (`ResponseSpec` JSON), stated in
+    /// each turn's instructions and checked on every answer
+    pub fn with_response_spec(self, spec_json: &str) -> Result<Session, JsValue> {
+        let spec: ResponseSpec = serde_json::from_str(spec_json)
+            .map_err(|e| JsValue::from_str(&format!("Invalid response spec JSON: {}", e)))?;
+        Ok(Session {
+            inner: self.inner.with_response_spec(Some(spec)),
+        })
+    }
+
+    /// The session with its stop sequences changed by `phase=sequence` rules
+    /// as for the native `--stop`; its prompts carry the `turn` ones
+    pub fn with_stop_rules(self, rules: Vec<String>) -> Result<Session, JsValue> {
+        let stops = rules
+            .iter()
+            .try_fold(StopSequences::default(), |stops, rule| {
+                stops.with_rule(rule)
+            })
+            .map_err(|e| JsValue::from_str(&e))?;
+        Ok(Session {
+            inner: self.inner.with_stop_sequences(stops),
+        })
+    }
+
+    /// The next prompt as JSON; fails once the run has ended or run out of
+    /// iterations (the message starts with the failure code)
+    pub fn next_prompt(&mut self) -> Result<String, JsValue> {
+        let spec = self.inner.next_prompt().map_err(session_error)?;
+        let text = spec.to_plain_text();
+        to_json(&PromptOutput { spec, text })
+    }
+
+    /// Hand over the model's reply; returns the step action as JSON
+    pub fn accept_model_output(&mut self, output: &str) -> Result<String, JsValue> {
+        let action = self
+            .inner
+            .accept_model_output(output)
+            .map_err(session_error)?;
+        to_json(&action)
+    }
+
+    /// Hand over a tool result (or artifact read) as JSON
+    pub fn accept_tool_result(&mut self, id: u32, result_json: &str) -> Result<String, JsValue> {
+        let result: ToolResult = serde_json::from_str(result_json)
+            .map_err(|e| JsValue::from_str(&format!("Invalid result JSON: {}", e)))?;
+        let action = self
+            .inner
+            .accept_tool_result(CallId(id.into()), result)
+            .map_err(session_error)?;
+        to_json(&action)
+    }
+
+    /// Hand over a skill result as JSON
+    pub fn accept_skill_result(&mut self, id: u32, result_json: &str) -> Result<String, JsValue> {
+        let result: SkillResult_ = serde_json::from_str(result_json)
+            .map_err(|e| JsValue::from_str(&format!("Invalid result JSON: {}", e)))?;
+        let action = self
+            .inner
+            .accept_skill_result(CallId(id.into()), &result)
+            .map_err(session_error)?;
+        to_json(&action)
+    }
+
+    /// The agent state as JSON
+    pub fn state_json(&self) -> Result<String, JsValue> {
+        to_json(self.inner.state())
+    }
+
+    pub fn is_ended(&self) -> bool {
+        self.inner.is_ended()
+    }
+}
+
+fn session_error(error: SessionError) -> JsValue {
+    match error {
+        SessionError::Failed(failure) => {
+            JsValue::from_str(&format!("{}: {}", failure.code(), failure))
+        }
+        other => JsValue::from_str(&other.to_string()),
+    }
+}
+
+fn to_json(value: &impl Serialize) -> Result<String, JsValue> {
+    serde_json::to_string(value)
+        .map_err(|e| JsValue::from_str(&format!("Failed to serialize output: {}", e)))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -133,6 +667,7 @@ mod tests {
         let input = StepInput {
             state_json,
             model_output: r#"{"tool":"shell","command":"ls"}"#.to_string(),
+            capabilities: HostCapabilities::default(),
         };
 
         let input_json = serde_json::to_string(&input).unwrap();
@@ -145,6 +680,19 @@ mod tests {
             }
             _ => panic!("Expected tool invocation"),
         }
+
+        // A host without a shell refuses the same call
+        let input_json = serde_json::json!({
+            "state_json": serde_json::to_string(&state).unwrap(),
+            "model_output": r#"{"tool":"shell","command":"ls"}"#,
+            "capabilities": {"tools": ["fs_list"]},
+        });
+        let output: StepOutput =
+            serde_json::from_str(&run_agent_step(&input_json.to_string()).unwrap()).unwrap();
+        assert!(matches!(
+            output.decision,
+            DecisionOutput::Unsupported { .. }
+        ));
     }
 
     #[test]
@@ -155,6 +703,7 @@ mod tests {
         let input = StepInput {
             state_json,
             model_output: "The answer is 4.".to_string(),
+            capabilities: HostCapabilities::default(),
         };
 
         let input_json = serde_json::to_string(&input).unwrap();
@@ -169,6 +718,44 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_execute_default_tool() {
+        assert!(default_tools_prompt().contains("- eval_math:"));
+        assert!(default_tools_prompt().contains("Examples:"));
+
+        let output =
+            execute_default_tool(r#"{"tool":"eval_math","expression":"120 * 15%"}"#).unwrap();
+        let result: agent_core::ToolResult = serde_json::from_str(&output).unwrap();
+        assert!(result.success);
+        assert_eq!(result.output, "18");
+    }
+
+    #[test]
+    fn test_tools() {
+        let shell = include_str!("../../../tools/shell/TOOL.md");
+        let tools = Tools::new(
+            vec![shell.to_string()],
+            vec!["read-only=prompt".to_string()],
+        )
+        .unwrap();
+        assert!(tools.prompt().contains("- shell: Execute shell commands"));
+        assert!(tools.prompt().contains("- eval_math:"));
+
+        let check = |request: &str| tools.check(request).unwrap();
+        assert_eq!(check(r#"{"tool":"shell","command":"ls"}"#), "prompt");
+        assert_eq!(
+            check(r#"{"tool":"eval_math","expression":"1+1"}"#),
+            "prompt"
+        );
+        assert_eq!(
+            Tools::new(vec![], vec![])
+                .unwrap()
+                .check(r#"{"tool":"eval_math","expression":"1+1"}"#)
+                .unwrap(),
+            "auto"
+        );
+    }
+
     #[test]
     fn test_create_agent_state() {
         let state_json = create_agent_state("Test query").unwrap();
@@ -177,4 +764,134 @@ mod tests {
         assert_eq!(state.history.len(), 1);
         assert!(!state.is_complete);
     }
+
+    #[test]
+    fn test_import_anthropic_conversation() {
+        let state_json = import_anthropic_conversation(
+            r#"{"system": "Be brief.", "messages": [{"role": "user", "content": "Hi"}]}"#,
+        )
+        .unwrap();
+        let state: AgentState = serde_json::from_str(&state_json).unwrap();
+        assert_eq!(state.history[0].content, "Hi");
+    }
+
+    #[test]
+    fn test_run_agent_step_cbor() {
+        let state = create_agent_state_cbor("What is 2+2?").unwrap();
+        let output =
+            run_agent_step_cbor(&state, r#"{"tool":"eval_math","expression":"2+2"}"#).unwrap();
+        let decision: serde_json::Value = serde_json::from_str(&output.decision_json()).unwrap();
+        assert_eq!(decision["type"], "invoke_tool");
+        assert_eq!(decision["call_id"], 1);
+
+        let state: AgentState =
+            serde_json::from_str(&state_cbor_to_json(&output.state()).unwrap()).unwrap();
+        assert_eq!(state.history.len(), 2);
+        let json = serde_json::to_string(&state).unwrap();
+        assert_eq!(state_json_to_cbor(&json).unwrap(), output.state());
+    }
+
+    #[test]
+    fn test_ts_types_cover_outputs() {
+        let ts_types = include_str!("types.d.ts");
+        let decisions = [
+            DecisionOutput::InvokeTool {
+                tool: String::new(),
+                params: serde_json::Value::Null,
+                call_id: None,
+                requested_as: Some(String::new()),
+            },
+            DecisionOutput::InvokeSkill {
+                skill: String::new(),
+                params: serde_json::Value::Null,
+                call_id: None,
+            },
+            DecisionOutput::ReadArtifact {
+                id: String::new(),
+                range: None,
+                page: None,
+                call_id: None,
+            },
+            DecisionOutput::Recall {
+                query: String::new(),
+                k: None,
+                call_id: None,
+            },
+            DecisionOutput::Variable {
+                operation: String::new(),
+                success: true,
+                output: String::new(),
+            },
+            DecisionOutput::Unsupported {
+                reason: String::new(),
+            },
+            DecisionOutput::Done {
+                answer: String::new(),
+            },
+            DecisionOutput::Inconclusive {
+                output: String::new(),
+                reason: InconclusiveReason::Planning,
+            },
+        ];
+        for decision in decisions {
+            let json = serde_json::to_value(&decision).unwrap();
+            let tag = format!(r#"type: "{}""#, json["type"].as_str().unwrap());
+            assert!(ts_types.contains(&tag), "types.d.ts lacks {}", tag);
+            for field in json.as_object().unwrap().keys() {
+                assert!(ts_types.contains(&format!("{}:", field)), "{}", field);
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_model_output() {
+        let parsed: serde_json::Value = serde_json::from_str(
+            &parse_model_output(r#"{"tool": "eval_math", "expression": "2+2"}"#, None).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(parsed["type"], "tool_call");
+        assert_eq!(parsed["tool"], "eval_math");
+
+        let ts_types = include_str!("types.d.ts");
+        for output in ["4", "FINAL: 4"] {
+            let json = parse_model_output(output, Some("sentinel".to_string())).unwrap();
+            let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+            let tag = format!(r#"type: "{}""#, parsed["type"].as_str().unwrap());
+            assert!(ts_types.contains(&tag), "types.d.ts lacks {}", tag);
+        }
+    }
+
+    #[test]
+    fn test_session_round_trip() {
+        let mut session = Session::new("What is 120 * 15%?", "SYSTEM", 3);
+        let prompt: serde_json::Value =
+            serde_json::from_str(&session.next_prompt().unwrap()).unwrap();
+        assert_eq!(prompt["iteration"], 1);
+        assert!(prompt["text"].as_str().unwrap().starts_with("SYSTEM"));
+
+        let action: serde_json::Value = serde_json::from_str(
+            &session
+                .accept_model_output(r#"{"tool":"eval_math","expression":"120 * 15%"}"#)
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(action["action"], "call_tool");
+        let id = action["id"].as_u64().unwrap() as u32;
+        let result = execute_default_tool(&action["request"].to_string()).unwrap();
+        let action = session.accept_tool_result(id, &result).unwrap();
+        assert_eq!(action, r#"{"action":"continue"}"#);
+
+        session.next_prompt().unwrap();
+        let action = session.accept_model_output("120 * 15% is 18.").unwrap();
+        assert!(action.contains(r#""action":"done""#));
+        assert!(session.is_ended());
+
+        let mut session = Session::new("What is 120 * 15%?", "SYSTEM", 3)
+            .with_response_spec(r#"{"max_words": 3}"#)
+            .unwrap();
+        let prompt = session.next_prompt().unwrap();
+        assert!(prompt.contains("Use at most 3 words."));
+        let action = session.accept_model_output("120 * 15% is 18.").unwrap();
+        assert!(action.contains(r#""action":"answer_rejected""#));
+    }
 }
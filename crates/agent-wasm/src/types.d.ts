@@ -0,0 +1,233 @@
+// JSON shapes exchanged with agent-wasm. Appended to the wasm-bindgen
+// declarations; keep in sync with the serde types in agent-core and lib.rs.
+
+/** Id linking a tool, skill or artifact call to its result */
+export type CallId = number;
+
+/** `{ agent: name }` is a named participant of a multi-agent run */
+export type Role = "user" | "assistant" | "tool" | { agent: string };
+
+export interface Message {
+  role: Role;
+  content: string;
+  call_id?: CallId;
+  /** Reasoning block the model gave before the reply */
+  reasoning?: string;
+}
+
+/** `AgentState` as JSON (`create_agent_state`, `export_state`) */
+export interface AgentStateJson {
+  version: number;
+  history: Message[];
+  is_complete: boolean;
+  final_answer: string | null;
+  guardrail_rejections?: Record<string, number>;
+  variables?: Record<string, unknown>;
+  calls_issued?: number;
+  /** How final answers are recognized; "sentinel" takes only `FINAL:` replies */
+  answer_protocol?: "heuristic" | "sentinel";
+  /** Reasoning block delimiters taken out of model output (default `<think>`) */
+  reasoning_delimiters?: { open: string; close: string } | null;
+  /** Named agent whose replies are recorded (multi-agent runs) */
+  speaker?: string;
+  response_spec?: ResponseSpec;
+}
+
+/** Shape of the final answer (`Session.with_response_spec`); omitted fields
+ * are free */
+export interface ResponseSpec {
+  max_words?: number;
+  bullets?: boolean;
+  /** By name or code, e.g. "German" or "de" */
+  language?: string;
+}
+
+/** What the host can run (`StepInput.capabilities`, `set_capabilities`,
+ * `Session.with_capabilities`); omitted fields allow everything */
+export interface HostCapabilities {
+  /** Tools the host runs; null accepts any tool */
+  tools?: string[] | null;
+  skills?: boolean;
+  /** Whether someone is there to answer `ask_user` */
+  ask_user?: boolean;
+  /** Whether the host keeps a memory to `recall` from */
+  memory?: boolean;
+  max_parallel_calls?: number;
+  /** Parameter schemas of the tools, shown with refused calls */
+  tool_schemas?: Record<string, Record<string, unknown>>;
+  /** Guessed tool names and the tools they mean; defaults to the built-in table */
+  tool_aliases?: {
+    aliases?: Record<string, string>;
+    /** Misspellings within this edit distance are taken as the tool */
+    max_distance?: number | null;
+  };
+}
+
+/** Input to `run_agent_step` */
+export interface StepInput {
+  state_json: string;
+  model_output: string;
+  capabilities?: HostCapabilities;
+}
+
+/** Output of `run_agent_step` */
+export interface StepOutput {
+  state_json: string;
+  decision: DecisionOutput;
+}
+
+/** What the host should do next (`run_agent_step`, `step`) */
+export type DecisionOutput =
+  | {
+      type: "invoke_tool";
+      tool: string;
+      params: Record<string, unknown>;
+      call_id: CallId | null;
+      /** The name the model used, when it called the tool by another one */
+      requested_as: string | null;
+    }
+  | {
+      type: "invoke_skill";
+      skill: string;
+      params: Record<string, unknown>;
+      call_id: CallId | null;
+    }
+  | {
+      type: "read_artifact";
+      id: string;
+      range: [number, number] | null;
+      page: number | null;
+      call_id: CallId | null;
+    }
+  | { type: "recall"; query: string; k: number | null; call_id: CallId | null }
+  | { type: "variable"; operation: string; success: boolean; output: string }
+  | { type: "unsupported"; reason: string }
+  | { type: "done"; answer: string }
+  | { type: "inconclusive"; output: string; reason: InconclusiveReason };
+
+/** What a model output asks for (`parse_model_output`); no state is touched */
+export type ParseOutput =
+  | {
+      type: "tool_call";
+      tool: string;
+      params: Record<string, unknown>;
+      call_id: CallId | null;
+    }
+  | {
+      type: "skill_call";
+      skill: string;
+      params: Record<string, unknown>;
+      call_id: CallId | null;
+    }
+  | {
+      type: "read_artifact";
+      id: string;
+      range: [number, number] | null;
+      page: number | null;
+      call_id: CallId | null;
+    }
+  | { type: "recall"; query: string; k: number | null; call_id: CallId | null }
+  | {
+      type: "variable";
+      operation: "set_var" | "get_var";
+      name: string;
+      value: unknown | null;
+    }
+  | { type: "final_answer"; answer: string }
+  | { type: "inconclusive"; output: string; reason: InconclusiveReason };
+
+/** A tool request: the tool name plus its parameters */
+export interface ToolRequest {
+  tool: string;
+  call_id?: CallId;
+  [param: string]: unknown;
+}
+
+/** What a tool's output holds; `*_ref` outputs reference data kept elsewhere */
+export type ContentKind = "text" | "json" | "table" | "binary_ref" | "image_ref";
+
+export interface ToolResult {
+  success: boolean;
+  output: string;
+  error?: string;
+  call_id?: CallId;
+  /** Default "text" */
+  content_kind?: ContentKind;
+}
+
+export interface SkillRequest {
+  skill: string;
+  call_id?: CallId;
+  [param: string]: unknown;
+}
+
+export interface SkillResult {
+  success: boolean;
+  output: unknown | null;
+  error: string | null;
+  call_id?: CallId;
+}
+
+export interface ArtifactRequest {
+  id: string;
+  range?: [number, number];
+  page?: number;
+  call_id?: CallId;
+}
+
+/** A memory search (`{"recall": "..."}`); the host owns the memory */
+export interface RecallRequest {
+  recall: string;
+  k?: number;
+  call_id?: CallId;
+}
+
+/** A prompt from `Session.next_prompt` */
+export interface PromptOutput {
+  iteration: number;
+  max_iterations: number;
+  retry: boolean;
+  system: string;
+  history: Message[];
+  instructions: string;
+  /** Where a completion API should stop generating (its `stop` parameter) */
+  stop: string[];
+  /** The whole prompt rendered as plain text */
+  text: string;
+}
+
+export type SessionFailure =
+  | { code: "max_iterations"; max: number }
+  | { code: "guardrail_failure"; initial: string; retry: string }
+  | { code: "inconclusive_after_guardrail"; reason: string; output: string }
+  | { code: "inconclusive"; first: string; retry: string }
+  | { code: "refused"; reason: string };
+
+/** Why an output was neither a call nor an answer */
+export type InconclusiveReason =
+  | { kind: "empty" }
+  | { kind: "reasoning_only" }
+  | { kind: "malformed_json"; error: string }
+  | { kind: "invalid_call"; error: string }
+  | { kind: "text_around_call" }
+  | { kind: "missing_sentinel" }
+  | { kind: "planning" }
+  | { kind: "fabricated_output"; marker: string };
+
+export type RetryReason =
+  | { kind: "guardrail"; reason: string }
+  | { kind: "inconclusive"; output: string; reason: InconclusiveReason };
+
+/** What a `Session` asks of the host after model output or a result */
+export type StepAction =
+  | { action: "call_tool"; id: CallId; request: ToolRequest }
+  | { action: "call_skill"; id: CallId; request: SkillRequest }
+  | { action: "read_artifact"; id: CallId; request: ArtifactRequest }
+  | { action: "recall"; id: CallId; request: RecallRequest }
+  | { action: "variable"; operation: string; result: ToolResult }
+  | { action: "answer_rejected"; answer: string; reason: string }
+  | { action: "unsupported"; reason: string }
+  | ({ action: "retry" } & RetryReason)
+  | { action: "continue" }
+  | { action: "done"; answer: string }
+  | ({ action: "failed" } & SessionFailure);
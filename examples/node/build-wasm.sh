@@ -0,0 +1,18 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+# Build script for the Node.js host example
+# This script builds agent-wasm for Node.js (CommonJS bindings that load the
+# .wasm from disk) into agent-wasm/
+
+SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
+REPO_ROOT="$(cd "$SCRIPT_DIR/../.." && pwd)"
+WASM_CRATE="$REPO_ROOT/crates/agent-wasm"
+
+echo "🔧 Building WASM module..."
+
+cd "$WASM_CRATE"
+wasm-pack build --target nodejs --out-dir "$SCRIPT_DIR/agent-wasm"
+
+echo "✅ WASM build complete"
+echo "   Artifacts available at: agent-wasm/"
@@ -0,0 +1,214 @@
+#!/usr/bin/env node
+/**
+ * Node.js Host for agent.rs
+ *
+ * A reference implementation of the host contract outside Rust: agent-wasm's
+ * `Session` makes every decision, a local Ollama runs the model, and this file
+ * only does I/O - inference over HTTP, the shell tool via child_process, and
+ * approvals via readline.
+ *
+ * Usage:
+ *   node main.mjs --query "How many .rs files are in this directory?"
+ *   node main.mjs --model llama3.1:8b --approve shell=deny --query "..."
+ */
+
+import { execFile } from 'node:child_process';
+import { readFileSync } from 'node:fs';
+import { createRequire } from 'node:module';
+import { createInterface } from 'node:readline';
+import { parseArgs } from 'node:util';
+
+const require = createRequire(import.meta.url);
+const wasm = require('./agent-wasm/agent_wasm.js');
+
+/** Output beyond this is cut before it goes back to the model */
+const MAX_OUTPUT = 8000;
+const SHELL_TIMEOUT_MS = 30_000;
+
+const SYSTEM_PROMPT = `You are an agent that answers questions about the user's machine.
+Reply with exactly one JSON object to call a tool, or with plain text once you
+can answer. Use tools to look things up; never guess file names or contents.`;
+
+const { values: args } = parseArgs({
+  options: {
+    query: { type: 'string', short: 'q' },
+    model: { type: 'string', default: process.env.OLLAMA_MODEL ?? 'qwen2.5:7b' },
+    endpoint: { type: 'string', default: process.env.OLLAMA_HOST ?? 'http://localhost:11434' },
+    'max-iterations': { type: 'string', default: '5' },
+    // target=mode rules, as for agent-native --approve (e.g. shell=deny)
+    approve: { type: 'string', multiple: true, default: [] }
+  }
+});
+
+if (!args.query) {
+  console.error('Usage: node main.mjs --query "..." [--model NAME] [--endpoint URL] [--approve TARGET=MODE]');
+  process.exit(2);
+}
+
+const shellManifest = readFileSync(new URL('../../tools/shell/TOOL.md', import.meta.url), 'utf8');
+const tools = new wasm.Tools([shellManifest], args.approve);
+// Approvals are read line by line, so they can also be piped in
+const rl = createInterface({ input: process.stdin });
+const lines = rl[Symbol.asyncIterator]();
+
+/**
+ * Run one completion on Ollama
+ */
+async function complete(prompt) {
+  const res = await fetch(`${args.endpoint}/api/generate`, {
+    method: 'POST',
+    headers: { 'Content-Type': 'application/json' },
+    body: JSON.stringify({
+      model: args.model,
+      prompt,
+      stream: false,
+      options: { temperature: 0.2, num_predict: 512 }
+    })
+  });
+  if (!res.ok) {
+    throw new Error(`Ollama returned HTTP ${res.status}: ${(await res.text()).slice(0, 300)}`);
+  }
+  return (await res.json()).response;
+}
+
+/**
+ * Check, approve and run a tool request; always returns a ToolResult
+ */
+async function executeTool(request) {
+  let mode;
+  try {
+    mode = tools.check(JSON.stringify(request));
+  } catch (error) {
+    // Unknown tool or invalid parameters: the model gets to correct itself
+    return failure(String(error));
+  }
+
+  if (mode === 'deny') {
+    return failure(`Tool '${request.tool}' is denied by the approval policy`);
+  }
+  if (mode === 'prompt') {
+    if (!(await confirm(`Run ${request.tool}: ${describe(request)}?`))) {
+      return failure('The user declined this tool call');
+    }
+  }
+
+  if (request.tool === 'shell') {
+    return runShell(request.command);
+  }
+  try {
+    // agent-core's own tools (eval_math) run inside WASM
+    return JSON.parse(wasm.execute_default_tool(JSON.stringify(request)));
+  } catch (error) {
+    return failure(String(error));
+  }
+}
+
+/**
+ * The shell tool, as in tools/shell/TOOL.md: stdout on success, stderr on a
+ * non-zero exit
+ */
+function runShell(command) {
+  return new Promise((resolve) => {
+    execFile('sh', ['-c', command], { timeout: SHELL_TIMEOUT_MS, maxBuffer: 4 * 1024 * 1024 }, (error, stdout, stderr) => {
+      if (error) {
+        const reason = error.killed ? `timed out after ${SHELL_TIMEOUT_MS / 1000}s` : `exit status ${error.code}`;
+        resolve(failure(`Command failed (${reason}): ${stderr.trim() || error.message}`));
+      } else {
+        resolve({ success: true, output: truncate(stdout) });
+      }
+    });
+  });
+}
+
+/** Ask a yes/no question; end of input counts as no */
+async function confirm(question) {
+  process.stdout.write(`\n${question} [y/N] `);
+  const { value, done } = await lines.next();
+  return !done && /^y(es)?$/i.test(value.trim());
+}
+
+function failure(error) {
+  return { success: false, output: '', error };
+}
+
+function truncate(text) {
+  return text.length > MAX_OUTPUT ? `${text.slice(0, MAX_OUTPUT)}\n... (truncated)` : text;
+}
+
+function describe(request) {
+  const { tool: _tool, call_id: _id, ...params } = request;
+  return request.tool === 'shell' ? request.command : JSON.stringify(params);
+}
+
+/**
+ * Drive the session to an answer or a failure; returns the exit status
+ */
+async function run() {
+  const system = `${SYSTEM_PROMPT}\n\n${tools.prompt()}`;
+  const session = new wasm.Session(args.query, system, Number(args['max-iterations']));
+  console.log(`Query: ${args.query}\nModel: ${args.model} (${args.endpoint})`);
+
+  try {
+    while (!session.is_ended()) {
+      let prompt;
+      try {
+        prompt = JSON.parse(session.next_prompt());
+      } catch (error) {
+        // Out of iterations; the message starts with the failure code
+        console.error(`\n❌ ${error}`);
+        return 1;
+      }
+
+      console.log(`\n--- Iteration ${prompt.iteration}/${prompt.max_iterations}${prompt.retry ? ' (retry)' : ''} ---`);
+      const output = await complete(prompt.text);
+      console.log(`Model: ${output.trim()}`);
+
+      let action = JSON.parse(session.accept_model_output(output));
+      for (;;) {
+        if (action.action === 'call_tool') {
+          const result = await executeTool(action.request);
+          console.log(result.success ? `Output:\n${result.output.trimEnd()}` : `Tool failed: ${result.error}`);
+          action = JSON.parse(session.accept_tool_result(action.id, JSON.stringify(result)));
+        } else if (action.action === 'call_skill') {
+          const result = { success: false, output: null, error: 'This host has no skills' };
+          action = JSON.parse(session.accept_skill_result(action.id, JSON.stringify(result)));
+        } else if (action.action === 'read_artifact') {
+          action = JSON.parse(session.accept_tool_result(action.id, JSON.stringify(failure('This host stores no artifacts'))));
+        } else {
+          break;
+        }
+      }
+
+      switch (action.action) {
+        case 'done':
+          console.log(`\n✅ ${action.answer.trim()}`);
+          return 0;
+        case 'failed':
+          console.error(`\n❌ ${action.code}: the run cannot continue`);
+          return 1;
+        case 'retry':
+          console.log(action.kind === 'guardrail' ? `✗ Guardrail: ${action.reason}` : '✗ No tool call or answer; retrying');
+          break;
+        case 'answer_rejected':
+          console.log(`✗ Answer rejected: ${action.reason}`);
+          break;
+        case 'variable':
+          console.log(`${action.operation}: ${action.result.output}`);
+          break;
+      }
+    }
+    return 1;
+  } finally {
+    session.free();
+  }
+}
+
+try {
+  process.exitCode = await run();
+} catch (error) {
+  console.error(`Error: ${error.message ?? error}`);
+  process.exitCode = 1;
+} finally {
+  rl.close();
+  tools.free();
+}
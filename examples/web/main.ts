@@ -0,0 +1,258 @@
+/**
+ * Web Host for agent.rs
+ *
+ * A complete agent run in the browser: agent-wasm's `Session` drives the loop
+ * (iterations, corrective retries, guardrails), WebLLM runs the model on
+ * WebGPU, and the page provides the tools - a virtual filesystem, fetch, and
+ * asking the user. Nothing leaves the browser except `fetch_url` requests.
+ *
+ * Compared to examples/browser (a single step), this host runs the whole
+ * decision surface: tool and skill calls, artifact reads, retries, answer
+ * rejections and explicit failures.
+ */
+
+import { CreateMLCEngine, type MLCEngine } from '@mlc-ai/web-llm';
+import type { PromptOutput, StepAction } from './public/agent-wasm/agent_wasm.js';
+import { executeSkill, executeTool, TOOLS_PROMPT, type Host } from './tools';
+import { VirtualFS } from './vfs';
+
+type AgentWasm = typeof import('./public/agent-wasm/agent_wasm.js');
+
+const MODEL = 'Qwen2.5-3B-Instruct-q4f16_1-MLC';
+const MAX_ITERATIONS = 6;
+
+let engine: MLCEngine | null = null;
+let wasm: AgentWasm | null = null;
+const fs = new VirtualFS();
+
+/**
+ * Initialize WebLLM (local inference via WebGPU)
+ */
+async function initWebLLM(): Promise<void> {
+  const statusEl = document.getElementById('model-status')!;
+  const fill = document.getElementById('progress-bar-fill')!;
+
+  engine = await CreateMLCEngine(MODEL, {
+    initProgressCallback: (p) => {
+      fill.style.width = `${Math.round(p.progress * 100)}%`;
+      statusEl.textContent = `Loading ${MODEL}: ${Math.round(p.progress * 100)}%`;
+    }
+  });
+
+  statusEl.textContent = 'Ready';
+  statusEl.className = 'status success';
+}
+
+/**
+ * Load the WASM module (built into public/ by build-wasm.sh)
+ */
+async function loadWASM(): Promise<void> {
+  const mod: AgentWasm = await import(/* @vite-ignore */ '/public/agent-wasm/agent_wasm.js');
+  await mod.default();
+  wasm = mod;
+}
+
+async function complete(prompt: string, maxTokens = 512): Promise<string> {
+  const response = await engine!.chat.completions.create({
+    messages: [{ role: 'user', content: prompt }],
+    temperature: 0.3,
+    max_tokens: maxTokens
+  });
+  return response.choices[0].message.content ?? '';
+}
+
+function systemPrompt(): string {
+  return `You are an agent working in a small virtual workspace in the user's browser.
+Reply with exactly one JSON object to call a tool or skill, or with plain text
+once you can answer. Read files before answering questions about them.
+
+${TOOLS_PROMPT}
+
+${wasm!.default_tools_prompt()}`;
+}
+
+const host: Host = {
+  fs,
+  ask: askUser,
+  complete: (prompt) => complete(prompt, 256),
+  executeDefaultTool: (request) => {
+    try {
+      return JSON.parse(wasm!.execute_default_tool(JSON.stringify(request)));
+    } catch {
+      return null;
+    }
+  }
+};
+
+/**
+ * Run a query to completion
+ *
+ * Each iteration asks the session for a prompt, sends it to the model, and
+ * hands the reply back. Calls the session asks for run here and their results
+ * go back with the call id, until the session says to continue, retry, or stop.
+ */
+async function runAgent(query: string): Promise<void> {
+  const session = new wasm!.Session(query, systemPrompt(), MAX_ITERATIONS);
+  try {
+    while (!session.is_ended()) {
+      let prompt: PromptOutput;
+      try {
+        prompt = JSON.parse(session.next_prompt());
+      } catch (error) {
+        // Out of iterations; the message starts with the failure code
+        render('status error', `❌ ${error}`);
+        return;
+      }
+
+      render('status info', `Iteration ${prompt.iteration}/${prompt.max_iterations}${prompt.retry ? ' (retry)' : ''}`);
+      const output = await complete(prompt.text);
+      render('model', output);
+
+      let action: StepAction = JSON.parse(session.accept_model_output(output));
+      while (true) {
+        if (action.action === 'call_tool') {
+          render('call', `→ ${action.request.tool} ${JSON.stringify(action.request)}`);
+          const result = await executeTool(host, action.request);
+          render(result.success ? 'result' : 'result failed', result.success ? result.output : `Error: ${result.error}`);
+          action = JSON.parse(session.accept_tool_result(action.id, JSON.stringify(result)));
+        } else if (action.action === 'call_skill') {
+          render('call', `→ skill ${action.request.skill}`);
+          const result = await executeSkill(host, action.request);
+          render(result.success ? 'result' : 'result failed', result.success ? JSON.stringify(result.output) : `Error: ${result.error}`);
+          action = JSON.parse(session.accept_skill_result(action.id, JSON.stringify(result)));
+        } else if (action.action === 'read_artifact') {
+          // This host keeps no artifact store; tool outputs are cut instead
+          const result = { success: false, output: '', error: 'This host stores no artifacts' };
+          action = JSON.parse(session.accept_tool_result(action.id, JSON.stringify(result)));
+        } else if (action.action === 'recall') {
+          // Nor a memory of earlier runs
+          const result = { success: false, output: '', error: 'This host keeps no memory' };
+          action = JSON.parse(session.accept_tool_result(action.id, JSON.stringify(result)));
+        } else {
+          break;
+        }
+      }
+
+      switch (action.action) {
+        case 'done':
+          render('final-answer', action.answer);
+          return;
+        case 'failed':
+          render('status error', `❌ ${action.code}: the run cannot continue. The system refused to return an unverified answer (by design).`);
+          return;
+        case 'retry':
+          render('guardrail reject', action.kind === 'guardrail' ? `✗ Guardrail: ${action.reason}` : '✗ No tool call or answer; retrying');
+          break;
+        case 'answer_rejected':
+          render('guardrail reject', `✗ Answer rejected: ${action.reason}`);
+          break;
+        case 'variable':
+          render('result', `${action.operation}: ${action.result.output}`);
+          break;
+        case 'continue':
+          break;
+      }
+    }
+  } finally {
+    session.free();
+  }
+}
+
+/**
+ * Ask the user inline and wait for their answer (the `ask_user` tool)
+ */
+function askUser(question: string): Promise<string> {
+  return new Promise((resolve) => {
+    const form = document.createElement('form');
+    form.className = 'ask-user';
+    form.innerHTML = `<label></label><input type="text" autocomplete="off"><button type="submit">Answer</button>`;
+    form.querySelector('label')!.textContent = `🙋 ${question}`;
+    const input = form.querySelector('input')!;
+    form.onsubmit = (event) => {
+      event.preventDefault();
+      form.replaceWith(entry('result', `You: ${input.value}`));
+      resolve(input.value);
+    };
+    document.getElementById('output-content')!.appendChild(form);
+    input.focus();
+  });
+}
+
+function entry(kind: string, text: string): HTMLElement {
+  const el = document.createElement('div');
+  el.className = kind;
+  el.textContent = text;
+  return el;
+}
+
+/**
+ * Append a line to the transcript (as text, never HTML: tool outputs come
+ * from the network)
+ */
+function render(kind: string, text: string): void {
+  document.getElementById('output-content')!.appendChild(entry(kind, text));
+  document.getElementById('output')!.classList.remove('hidden');
+}
+
+function renderFiles(): void {
+  const list = document.getElementById('files')!;
+  list.textContent = '';
+  const walk = (dir: string) => {
+    for (const name of fs.list(dir)) {
+      const path = dir.replace(/\/?$/, '/') + name;
+      if (name.endsWith('/')) {
+        walk(path);
+      } else {
+        const item = document.createElement('li');
+        item.textContent = path;
+        item.title = fs.read(path);
+        list.appendChild(item);
+      }
+    }
+  };
+  walk('/');
+}
+
+async function init(): Promise<void> {
+  const input = document.getElementById('query') as HTMLInputElement;
+  const btn = document.getElementById('run-btn') as HTMLButtonElement;
+  const resetBtn = document.getElementById('reset-btn') as HTMLButtonElement;
+
+  renderFiles();
+  resetBtn.onclick = () => {
+    fs.reset();
+    renderFiles();
+  };
+
+  try {
+    await loadWASM();
+    await initWebLLM();
+    input.disabled = false;
+    btn.disabled = false;
+
+    btn.onclick = async () => {
+      const query = input.value.trim();
+      if (!query) return;
+      btn.disabled = input.disabled = true;
+      document.getElementById('output-content')!.textContent = '';
+      try {
+        await runAgent(query);
+      } catch (error: any) {
+        render('status error', `Error: ${error.message ?? error}`);
+      } finally {
+        btn.disabled = input.disabled = false;
+        renderFiles();
+      }
+    };
+  } catch (error: any) {
+    const statusEl = document.getElementById('model-status')!;
+    statusEl.textContent = `Init failed: ${error.message}`;
+    statusEl.className = 'status error';
+  }
+}
+
+if (document.readyState === 'loading') {
+  document.addEventListener('DOMContentLoaded', init);
+} else {
+  init();
+}
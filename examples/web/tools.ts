@@ -0,0 +1,123 @@
+/**
+ * Host tools and skills for the web example
+ *
+ * Everything the agent can touch is implemented here, in the page: the
+ * virtual filesystem, fetch, and asking the user. agent-core (in WASM) only
+ * decides which of them to call.
+ */
+
+import type { SkillRequest, SkillResult, ToolRequest, ToolResult } from './public/agent-wasm/agent_wasm.js';
+import { VirtualFS } from './vfs';
+
+/** Tool outputs longer than this are cut, as the native runtime does */
+const MAX_OUTPUT = 4000;
+
+export const TOOLS_PROMPT = `Host tools (use the "tool" key):
+- fs_list: list a directory. {"tool":"fs_list","path":"/notes"}
+- fs_read: read a file. {"tool":"fs_read","path":"/notes/todo.txt"}
+- fs_write: write a file. {"tool":"fs_write","path":"/notes/done.txt","content":"..."}
+- fetch_url: fetch a URL (CORS permitting). {"tool":"fetch_url","url":"https://httpbin.org/json"}
+- ask_user: ask the user a question and get their answer. {"tool":"ask_user","question":"Which file?"}
+
+Skills (use the "skill" key, not "tool"):
+- extract: pull values out of text. {"skill":"extract","text":"Contact dana@example.org","target":"email"}
+  Targets: email, url, date, name`;
+
+export interface Host {
+  fs: VirtualFS;
+  /** Show a question and resolve with the user's answer */
+  ask(question: string): Promise<string>;
+  /** Run a one-off completion (for skills) */
+  complete(prompt: string): Promise<string>;
+  /** agent-core's own tools (eval_math); `null` for tools it doesn't know */
+  executeDefaultTool(request: ToolRequest): ToolResult | null;
+}
+
+export async function executeTool(host: Host, request: ToolRequest): Promise<ToolResult> {
+  const builtin = host.executeDefaultTool(request);
+  if (builtin) return builtin;
+
+  try {
+    const output = await runTool(host, request);
+    return { success: true, output: output.slice(0, MAX_OUTPUT) };
+  } catch (error: any) {
+    return { success: false, output: '', error: error.message };
+  }
+}
+
+async function runTool(host: Host, request: ToolRequest): Promise<string> {
+  const param = (name: string): string => {
+    const value = request[name];
+    if (typeof value !== 'string') throw new Error(`Missing ${name} parameter`);
+    return value;
+  };
+
+  switch (request.tool) {
+    case 'fs_list':
+      return host.fs.list(typeof request.path === 'string' ? request.path : '/').join('\n');
+    case 'fs_read':
+      return host.fs.read(param('path'));
+    case 'fs_write': {
+      const content = param('content');
+      host.fs.write(param('path'), content);
+      return `Wrote ${content.length} characters to ${param('path')}`;
+    }
+    case 'fetch_url': {
+      const res = await fetch(param('url'));
+      if (!res.ok) throw new Error(`HTTP ${res.status}`);
+      return await res.text();
+    }
+    case 'ask_user':
+      return `User answered: ${await host.ask(param('question'))}`;
+    default:
+      throw new Error(`Unknown tool: ${request.tool}`);
+  }
+}
+
+const EXTRACT_TARGETS: Record<string, string> = {
+  email: 'email addresses',
+  url: 'URLs',
+  date: 'dates (in ISO format YYYY-MM-DD)',
+  name: 'person names'
+};
+
+/**
+ * Run a skill: an LLM call with a schema and a host-side check, as the
+ * native extraction skill does
+ */
+export async function executeSkill(host: Host, request: SkillRequest): Promise<SkillResult> {
+  try {
+    if (request.skill !== 'extract') throw new Error(`Unknown skill: ${request.skill}`);
+    const { text, target } = request as { text?: unknown; target?: unknown };
+    if (typeof text !== 'string') throw new Error('Missing text parameter');
+    if (typeof target !== 'string' || !(target in EXTRACT_TARGETS)) {
+      throw new Error(`Invalid target: ${target}. Must be one of: ${Object.keys(EXTRACT_TARGETS).join(', ')}`);
+    }
+
+    const reply = await host.complete(`Extract ${EXTRACT_TARGETS[target]} from the text below.
+Output ONLY JSON of the form {"${target}": [...]}, with values that appear in the text.
+
+Text: """${text}"""
+
+JSON:`);
+
+    let parsed: any;
+    try {
+      parsed = JSON.parse(reply.trim());
+    } catch {
+      throw new Error(`MalformedOutput: not JSON: ${reply.slice(0, 200)}`);
+    }
+    if (!Array.isArray(parsed?.[target])) {
+      throw new Error(`SchemaViolation: output missing '${target}' array`);
+    }
+    // Anti-hallucination: every value must appear in the source text
+    for (const value of parsed[target]) {
+      if (typeof value !== 'string' || !text.toLowerCase().includes(value.toLowerCase())) {
+        throw new Error(`HallucinationDetected: '${value}' not found in source text`);
+      }
+    }
+    return { success: true, output: parsed, error: null };
+  } catch (error: any) {
+    return { success: false, output: null, error: error.message };
+  }
+}
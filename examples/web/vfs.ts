@@ -0,0 +1,85 @@
+/**
+ * Virtual filesystem for the web host
+ *
+ * Files live in memory and persist to localStorage, so the agent gets a
+ * workspace to list, read and write without any access to the real disk.
+ * Paths are absolute and `/`-separated; directories exist implicitly.
+ */
+
+const STORAGE_KEY = 'agent-rs-web-vfs';
+
+const SEED_FILES: Record<string, string> = {
+  '/README.md': `# Demo workspace
+
+A virtual filesystem for the agent.rs web host. Nothing here touches your disk.
+
+- notes/todo.txt: open tasks
+- data/contacts.csv: people to follow up with
+`,
+  '/notes/todo.txt': `- Draft the release notes for 0.2
+- Email Dana about the WASM benchmarks
+- Review the guardrail docs before Friday
+`,
+  '/data/contacts.csv': `name,email,team
+Dana Reyes,dana@example.org,runtime
+Sam Okafor,sam.okafor@example.org,docs
+Lee Park,lee@example.net,infra
+`
+};
+
+export class VirtualFS {
+  private files: Map<string, string>;
+
+  constructor() {
+    const saved = localStorage.getItem(STORAGE_KEY);
+    this.files = new Map(Object.entries(saved ? JSON.parse(saved) : SEED_FILES));
+  }
+
+  /** Entries directly under `dir`; directories end with `/` */
+  list(dir: string): string[] {
+    const prefix = normalize(dir).replace(/\/?$/, '/');
+    const entries = new Set<string>();
+    for (const path of this.files.keys()) {
+      if (!path.startsWith(prefix)) continue;
+      const rest = path.slice(prefix.length);
+      const slash = rest.indexOf('/');
+      entries.add(slash === -1 ? rest : rest.slice(0, slash + 1));
+    }
+    if (entries.size === 0 && prefix !== '/') {
+      throw new Error(`No such directory: ${dir}`);
+    }
+    return [...entries].sort();
+  }
+
+  read(path: string): string {
+    const content = this.files.get(normalize(path));
+    if (content === undefined) throw new Error(`No such file: ${path}`);
+    return content;
+  }
+
+  write(path: string, content: string): void {
+    this.files.set(normalize(path), content);
+    this.save();
+  }
+
+  /** Drop all changes and restore the seed files */
+  reset(): void {
+    this.files = new Map(Object.entries(SEED_FILES));
+    this.save();
+  }
+
+  private save(): void {
+    localStorage.setItem(STORAGE_KEY, JSON.stringify(Object.fromEntries(this.files)));
+  }
+}
+
+/** Resolve `.` and `..` and make the path absolute */
+function normalize(path: string): string {
+  const parts: string[] = [];
+  for (const part of path.split('/')) {
+    if (part === '' || part === '.') continue;
+    if (part === '..') parts.pop();
+    else parts.push(part);
+  }
+  return '/' + parts.join('/');
+}
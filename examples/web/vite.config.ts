@@ -0,0 +1,18 @@
+import { defineConfig } from 'vite'
+
+export default defineConfig({
+  server: {
+    port: 8081,
+    headers: {
+      // Required for WebGPU and WASM
+      'Cross-Origin-Embedder-Policy': 'require-corp',
+      'Cross-Origin-Opener-Policy': 'same-origin'
+    }
+  },
+  build: {
+    target: 'esnext'
+  },
+  optimizeDeps: {
+    exclude: ['@mlc-ai/web-llm']
+  }
+})